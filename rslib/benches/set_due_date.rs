@@ -0,0 +1,64 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use anki::bench_support;
+use anki::card::CardId;
+use anki::collection::Collection;
+use anki::collection::CollectionBuilder;
+use anki::notes::AddNoteRequest;
+use anki::notes::Note;
+use anki::search::SortMode;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::Criterion;
+
+const CARDS: usize = 10_000;
+const DECKS: usize = 20;
+
+/// An in-memory collection with `CARDS` review cards spread over `DECKS`
+/// decks.
+fn collection_with_review_cards() -> (Collection, Vec<CardId>) {
+    let mut col = CollectionBuilder::default().build().unwrap();
+    let notetype = col.get_notetype_by_name("Basic").unwrap().unwrap();
+    let deck_ids: Vec<_> = (0..DECKS)
+        .map(|i| {
+            col.get_or_create_normal_deck(&format!("deck{i}"))
+                .unwrap()
+                .id
+        })
+        .collect();
+    let mut requests: Vec<_> = (0..CARDS)
+        .map(|i| {
+            let mut note = Note::new(&notetype);
+            note.set_field(0, format!("front {i}")).unwrap();
+            AddNoteRequest {
+                note,
+                deck_id: deck_ids[i % DECKS],
+            }
+        })
+        .collect();
+    col.add_notes(&mut requests).unwrap();
+    let cids = col.search_cards("", SortMode::NoOrder).unwrap();
+    col.set_due_date(&cids, "1-30", None).unwrap();
+    (col, cids)
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("set_due_date");
+    group.sample_size(10);
+    for (name, prefetch) in [
+        ("lazy_deck_configs", false),
+        ("prefetched_deck_configs", true),
+    ] {
+        // each variant starts from an identical collection, as every run adds
+        // to the revlog and undo queue
+        let (mut col, cids) = collection_with_review_cards();
+        group.bench_function(name, |b| {
+            b.iter(|| bench_support::set_due_date(&mut col, &cids, "1-30", prefetch).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
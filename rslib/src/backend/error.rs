@@ -48,6 +48,7 @@ impl AnkiError {
             #[cfg(windows)]
             AnkiError::WindowsError { .. } => Kind::OsError,
             AnkiError::SchedulerUpgradeRequired => Kind::SchedulerUpgradeRequired,
+            AnkiError::SchemaChangeNotAllowed => Kind::InvalidInput,
             AnkiError::FsrsInsufficientReviews { .. } => Kind::InvalidInput,
             AnkiError::InvalidCertificateFormat => Kind::InvalidCertificateFormat,
         };
@@ -1,6 +1,7 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use crate::notetype::CardChanges;
 use crate::ops::OpChanges;
 use crate::prelude::*;
 use crate::undo::UndoOutput;
@@ -41,6 +42,12 @@ impl From<OpOutput<()>> for anki_proto::collection::OpChanges {
     }
 }
 
+impl From<OpOutput<CardChanges>> for anki_proto::collection::OpChanges {
+    fn from(o: OpOutput<CardChanges>) -> Self {
+        o.changes.into()
+    }
+}
+
 impl From<OpOutput<usize>> for anki_proto::collection::OpChangesWithCount {
     fn from(out: OpOutput<usize>) -> Self {
         anki_proto::collection::OpChangesWithCount {
@@ -0,0 +1,20 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Entry points for the benchmarks in `benches/`, which can only call public
+//! items. Not part of the API.
+
+use crate::prelude::*;
+
+/// Runs [Collection::set_due_date], optionally reading the initial ease of all
+/// decks up front instead of looking up each deck's preset the first time one
+/// of its cards is encountered.
+pub fn set_due_date(
+    col: &mut Collection,
+    cids: &[CardId],
+    days: &str,
+    prefetch_deck_configs: bool,
+) -> Result<()> {
+    col.set_due_date_inner(cids, days, None, prefetch_deck_configs)
+        .map(|_| ())
+}
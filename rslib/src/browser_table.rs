@@ -110,7 +110,7 @@ impl Card {
     }
 
     /// Returns the card's due date as a timestamp if it has one.
-    fn due_time(&self, timing: &SchedTimingToday) -> Option<TimestampSecs> {
+    pub(crate) fn due_time(&self, timing: &SchedTimingToday) -> Option<TimestampSecs> {
         if self.queue == CardQueue::Learn {
             Some(TimestampSecs(self.original_or_current_due() as i64))
         } else if self.is_due_in_days() {
@@ -146,7 +146,7 @@ impl Card {
 }
 
 impl Note {
-    fn is_marked(&self) -> bool {
+    pub(crate) fn is_marked(&self) -> bool {
         self.tags
             .iter()
             .any(|tag| tag.eq_ignore_ascii_case("marked"))
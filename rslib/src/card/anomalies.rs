@@ -0,0 +1,269 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use crate::card::CardQueue;
+use crate::card::CardType;
+use crate::prelude::*;
+use crate::scheduler::states::review::MINIMUM_EASE_FACTOR;
+
+/// New card positions from here on were left by old versions, and sort after
+/// every other new card.
+pub(crate) const NEW_POSITION_LIMIT: i32 = 1_000_000;
+/// Review due numbers above this are not day numbers, and are usually
+/// timestamps. The database check uses the same cutoff.
+pub(crate) const REVIEW_DUE_LIMIT: i32 = 100_000;
+pub(crate) const MINIMUM_EASE_FACTOR_THOUSANDS: u16 = (MINIMUM_EASE_FACTOR * 1000.0) as u16;
+
+/// A scheduling value that other code can't cope with, usually left by an
+/// import from an old version. Each variant describes how
+/// [Collection::fix_card_anomalies] corrects it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardAnomalyKind {
+    /// A new card's position is 1,000,000 or more. The card is moved to the
+    /// end of the new queue. The database check leaves such positions above
+    /// 1,000,000.
+    NewPositionTooHigh,
+    /// A review card's due is not a day number. The card is made due today,
+    /// as the database check does.
+    ReviewDueNotDays,
+    /// A review card has an interval of 0. The database check accepts this,
+    /// so the interval is set to 1 day, the shortest interval a review card
+    /// can be given.
+    ReviewIntervalZero,
+    /// A review card scheduled with SM-2 has an ease below the minimum. The
+    /// ease is raised to that minimum of 130%, which is what the scheduler
+    /// would clamp it to on the next answer. This is not the reset to 250%
+    /// done for the eases lowered by the schema 15 bug, which were valid
+    /// values, and the database check doesn't touch eases.
+    EaseTooLow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CardAnomaly {
+    pub card_id: CardId,
+    pub kind: CardAnomalyKind,
+    /// The offending value: a position, due number, interval or ease in
+    /// thousandths.
+    pub value: i64,
+    /// What the value was changed to, if the anomaly was fixed.
+    pub fixed_value: Option<i64>,
+}
+
+impl Card {
+    fn anomalies(&self) -> Vec<(CardAnomalyKind, i64)> {
+        let mut found = vec![];
+        if self.ctype == CardType::New && self.original_or_current_due() >= NEW_POSITION_LIMIT {
+            found.push((
+                CardAnomalyKind::NewPositionTooHigh,
+                self.original_or_current_due() as i64,
+            ));
+        }
+        if self.queue == CardQueue::Review && self.due > REVIEW_DUE_LIMIT {
+            found.push((CardAnomalyKind::ReviewDueNotDays, self.due as i64));
+        }
+        if self.ctype == CardType::Review && self.interval == 0 {
+            found.push((CardAnomalyKind::ReviewIntervalZero, 0));
+        }
+        if matches!(self.ctype, CardType::Review | CardType::Relearn)
+            && self.memory_state.is_none()
+            && (1..MINIMUM_EASE_FACTOR_THOUSANDS).contains(&self.ease_factor)
+        {
+            found.push((CardAnomalyKind::EaseTooLow, self.ease_factor as i64));
+        }
+        found
+    }
+}
+
+impl Collection {
+    /// Cards with scheduling values that break sorting and scheduling, in
+    /// card id order. Unlike the database check, nothing is changed.
+    pub fn card_anomalies(&self) -> Result<Vec<CardAnomaly>> {
+        Ok(self
+            .storage
+            .cards_with_possible_anomalies()?
+            .iter()
+            .flat_map(|card| {
+                card.anomalies()
+                    .into_iter()
+                    .map(|(kind, value)| CardAnomaly {
+                        card_id: card.id,
+                        kind,
+                        value,
+                        fixed_value: None,
+                    })
+            })
+            .collect())
+    }
+
+    /// Fix the anomalies of the cards in `cids`, or of all cards, returning
+    /// what was changed. Only review dues are fixed as the database check
+    /// would; see [CardAnomalyKind] for the other values. Misplaced new cards
+    /// keep their relative order.
+    pub fn fix_card_anomalies(
+        &mut self,
+        cids: Option<&[CardId]>,
+    ) -> Result<OpOutput<Vec<CardAnomaly>>> {
+        let today = self.timing_today()?.days_elapsed;
+        let usn = self.usn()?;
+        self.transact(Op::UpdateCard, |col| {
+            let mut cards = match cids {
+                Some(cids) => col.all_cards_for_ids(cids, false)?,
+                None => col.storage.cards_with_possible_anomalies()?,
+            };
+            cards.sort_by_key(|card| (card.original_or_current_due(), card.id));
+            let mut next_position = None;
+            let mut fixed = vec![];
+            for mut card in cards {
+                let anomalies = card.anomalies();
+                if anomalies.is_empty() {
+                    continue;
+                }
+                let original = card.clone();
+                for (kind, value) in anomalies {
+                    let fixed_value = match kind {
+                        CardAnomalyKind::NewPositionTooHigh => {
+                            let position = match next_position {
+                                Some(position) => position,
+                                None => col.next_valid_new_position()?,
+                            };
+                            next_position = Some(position + 1);
+                            card.set_new_position(position);
+                            position
+                        }
+                        CardAnomalyKind::ReviewDueNotDays => {
+                            card.due = today as i32;
+                            today
+                        }
+                        CardAnomalyKind::ReviewIntervalZero => {
+                            card.interval = 1;
+                            1
+                        }
+                        CardAnomalyKind::EaseTooLow => {
+                            card.ease_factor = MINIMUM_EASE_FACTOR_THOUSANDS;
+                            MINIMUM_EASE_FACTOR_THOUSANDS as u32
+                        }
+                    };
+                    fixed.push(CardAnomaly {
+                        card_id: card.id,
+                        kind,
+                        value,
+                        fixed_value: Some(fixed_value as i64),
+                    });
+                }
+                col.update_card_inner(&mut card, original, usn)?;
+            }
+            if let Some(position) = next_position {
+                col.set_next_card_position(position)?;
+            }
+            fixed.sort_by_key(|anomaly| anomaly.card_id);
+            Ok(fixed)
+        })
+    }
+
+    /// The position after the last new card that is below
+    /// [NEW_POSITION_LIMIT].
+    fn next_valid_new_position(&self) -> Result<u32> {
+        let configured = self.get_next_card_position();
+        let after_last = self
+            .storage
+            .next_new_card_position_below(NEW_POSITION_LIMIT)?;
+        Ok(if configured < NEW_POSITION_LIMIT as u32 {
+            configured.max(after_last)
+        } else {
+            after_last
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::card::FsrsMemoryState;
+    use crate::tests::NoteAdder;
+
+    #[test]
+    fn anomalies_are_reported_and_fixed() -> Result<()> {
+        let mut col = Collection::new();
+        let mut cards = vec![];
+        for _ in 0..5 {
+            let note = NoteAdder::basic(&mut col).add(&mut col);
+            cards.push(col.storage.all_cards_of_note(note.id)?[0].clone());
+        }
+        let normal_position = cards[0].due;
+        cards[1].due = 2_000_005;
+        cards[2].due = 1_700_000_000;
+        cards[2].ctype = CardType::Review;
+        cards[2].queue = CardQueue::Review;
+        cards[2].interval = 0;
+        cards[2].ease_factor = 1200;
+        cards[3].due = 1_000_000;
+        cards[4].ctype = CardType::Review;
+        cards[4].queue = CardQueue::Review;
+        cards[4].interval = 3;
+        cards[4].ease_factor = 1200;
+        cards[4].memory_state = Some(FsrsMemoryState {
+            stability: 10.0,
+            difficulty: 5.0,
+        });
+        for card in &cards {
+            col.storage.update_card(card)?;
+        }
+        col.set_next_card_position(1_000_100)?;
+        let cids: Vec<_> = cards.iter().map(|card| card.id).collect();
+
+        let found: Vec<_> = col
+            .card_anomalies()?
+            .into_iter()
+            .map(|anomaly| (anomaly.card_id, anomaly.kind, anomaly.value))
+            .collect();
+        // FSRS cards have no ease to check
+        assert_eq!(
+            found,
+            [
+                (cids[1], CardAnomalyKind::NewPositionTooHigh, 2_000_005),
+                (cids[2], CardAnomalyKind::ReviewDueNotDays, 1_700_000_000),
+                (cids[2], CardAnomalyKind::ReviewIntervalZero, 0),
+                (cids[2], CardAnomalyKind::EaseTooLow, 1200),
+                (cids[3], CardAnomalyKind::NewPositionTooHigh, 1_000_000),
+            ]
+        );
+
+        // only the given cards are fixed
+        let fixed = col.fix_card_anomalies(Some(&cids[2..]))?.output;
+        let today = col.timing_today()?.days_elapsed as i64;
+        let fixed: Vec<_> = fixed
+            .into_iter()
+            .map(|anomaly| (anomaly.card_id, anomaly.kind, anomaly.fixed_value))
+            .collect();
+        // the configured position is itself too high, so the new position
+        // follows the last valid one
+        let first_free = normal_position + 1;
+        assert_eq!(
+            fixed,
+            [
+                (cids[2], CardAnomalyKind::ReviewDueNotDays, Some(today)),
+                (cids[2], CardAnomalyKind::ReviewIntervalZero, Some(1)),
+                (cids[2], CardAnomalyKind::EaseTooLow, Some(1300)),
+                (
+                    cids[3],
+                    CardAnomalyKind::NewPositionTooHigh,
+                    Some(first_free as i64)
+                ),
+            ]
+        );
+        assert_eq!(col.card_anomalies()?.len(), 1);
+        assert_eq!(col.get_next_card_position(), first_free as u32 + 1);
+
+        // misplaced new cards go to the end of the queue
+        col.fix_card_anomalies(None)?;
+        assert!(col.card_anomalies()?.is_empty());
+        let card = col.storage.get_card(cids[1])?.unwrap();
+        assert_eq!(card.due, first_free + 1);
+        assert_eq!(col.storage.get_card(cids[4])?.unwrap().ease_factor, 1200);
+
+        col.undo()?;
+        col.undo()?;
+        assert_eq!(col.card_anomalies()?.len(), 5);
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+pub mod anomalies;
 mod service;
 pub(crate) mod undo;
 
@@ -22,8 +23,14 @@ use crate::error::AnkiError;
 use crate::error::FilteredDeckError;
 use crate::error::Result;
 use crate::notes::NoteId;
+use crate::ops::ChangedIds;
 use crate::ops::StateChanges;
 use crate::prelude::*;
+use crate::scheduler::states::review::INITIAL_EASE_FACTOR;
+use crate::scheduler::states::review::MINIMUM_EASE_FACTOR;
+use crate::search::JoinSearches;
+use crate::search::SearchNode;
+use crate::search::StateKind;
 use crate::timestamp::TimestampSecs;
 use crate::types::Usn;
 
@@ -277,7 +284,244 @@ impl Card {
     }
 }
 
+/// The highest ease factor that can be set in bulk.
+pub(crate) const MAXIMUM_EASE_FACTOR: f32 = 5.0;
+/// The number of cards loaded and updated at a time when setting ease
+/// factors in bulk.
+const SET_EASE_CHUNK_SIZE: usize = 1000;
+
+/// The result of [Collection::set_ease_factor].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SetEaseFactorOutput {
+    pub updated: usize,
+    /// Review cards that were left alone because they are scheduled with
+    /// FSRS, which does not use the ease factor.
+    pub skipped_fsrs: usize,
+}
+
+/// The ease at or above which an SM-2 card is estimated to have no
+/// difficulty.
+const SM2_EASIEST_FACTOR: f32 = 3.0;
+/// The estimated difficulty added by each lapse of an SM-2 card.
+const SM2_DIFFICULTY_PER_LAPSE: f32 = 0.05;
+
 impl Collection {
+    /// A page of the collection's card ids in ascending order, starting after
+    /// `after`. See [Collection::get_all_note_ids_paginated].
+    pub fn get_all_card_ids_paginated(
+        &mut self,
+        after: Option<CardId>,
+        limit: u32,
+    ) -> Result<Vec<CardId>> {
+        self.storage
+            .card_ids_after(after.unwrap_or(CardId(i64::MIN)), limit)
+    }
+
+    /// Cards in the deck or its children that have lapsed at least
+    /// `min_lapses` times, with the most lapses first.
+    pub fn get_lapse_counts_by_card(
+        &mut self,
+        deck_id: DeckId,
+        min_lapses: u32,
+    ) -> Result<Vec<(CardId, u32)>> {
+        let deck = self.get_deck(deck_id)?.or_not_found(deck_id)?;
+        let mut counts: Vec<_> = self
+            .all_cards_for_search(SearchNode::from_deck_name(&deck.human_name()))?
+            .into_iter()
+            .filter(|card| card.lapses >= min_lapses)
+            .map(|card| (card.id, card.lapses))
+            .collect();
+        counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        Ok(counts)
+    }
+
+    /// Review cards whose ease factor is more than `std_devs` standard
+    /// deviations from the mean ease of the review cards in the collection,
+    /// or in the deck and its children. The furthest outliers come first.
+    /// Cards without an ease factor, such as those scheduled with FSRS, are
+    /// ignored.
+    pub fn get_ease_factor_outliers(
+        &mut self,
+        deck_id: Option<DeckId>,
+        std_devs: f32,
+    ) -> Result<Vec<(CardId, f32)>> {
+        let search = match deck_id {
+            Some(did) => {
+                let deck = self.get_deck(did)?.or_not_found(did)?;
+                SearchNode::from_deck_name(&deck.human_name())
+            }
+            None => SearchNode::WholeCollection,
+        };
+        let eases: Vec<_> = self
+            .all_cards_for_search(search)?
+            .into_iter()
+            .filter(|card| {
+                matches!(card.ctype, CardType::Review | CardType::Relearn) && card.ease_factor > 0
+            })
+            .map(|card| (card.id, card.ease_factor()))
+            .collect();
+        if eases.is_empty() {
+            return Ok(vec![]);
+        }
+        let count = eases.len() as f32;
+        let mean = eases.iter().map(|(_, ease)| ease).sum::<f32>() / count;
+        let std_dev = (eases
+            .iter()
+            .map(|(_, ease)| (ease - mean).powi(2))
+            .sum::<f32>()
+            / count)
+            .sqrt();
+        let mut outliers: Vec<_> = eases
+            .into_iter()
+            .filter(|(_, ease)| (ease - mean).abs() > std_devs * std_dev)
+            .collect();
+        outliers.sort_by(|a, b| (b.1 - mean).abs().total_cmp(&(a.1 - mean).abs()));
+        Ok(outliers)
+    }
+
+    /// Cards in the review queue whose ease factor is between `min_ease` and
+    /// `max_ease` inclusive, in the collection or the deck and its children.
+    /// The lowest eases come first, and at most `limit` cards are returned.
+    /// Eases are decimals, eg 1.3 for 130%.
+    pub fn get_cards_by_ease_range(
+        &mut self,
+        deck_id: Option<DeckId>,
+        min_ease: f32,
+        max_ease: f32,
+        limit: u32,
+    ) -> Result<Vec<CardId>> {
+        require!(
+            min_ease.is_finite() && max_ease.is_finite() && min_ease <= max_ease,
+            "invalid ease range"
+        );
+        let search = match deck_id {
+            Some(did) => {
+                let deck = self.get_deck(did)?.or_not_found(did)?;
+                SearchNode::from_deck_name(&deck.human_name())
+            }
+            None => SearchNode::WholeCollection,
+        };
+        let min_ease = (min_ease * 1000.0).round() as i64;
+        let max_ease = (max_ease * 1000.0).round() as i64;
+        let mut cards: Vec<_> = self
+            .all_cards_for_search(search.and(SearchNode::State(StateKind::Review)))?
+            .into_iter()
+            .filter(|card| {
+                card.queue == CardQueue::Review
+                    && (min_ease..=max_ease).contains(&(card.ease_factor as i64))
+            })
+            .map(|card| (card.ease_factor, card.id))
+            .collect();
+        cards.sort_unstable();
+        Ok(cards
+            .into_iter()
+            .take(limit as usize)
+            .map(|(_, cid)| cid)
+            .collect())
+    }
+
+    /// The position of a card in the new queue, which determines the order new
+    /// cards are introduced in. [None] if the card is not in the new queue.
+    pub fn get_card_queue_position(&mut self, cid: CardId) -> Result<Option<u32>> {
+        let card = self.storage.get_card(cid)?.or_not_found(cid)?;
+        Ok((card.queue == CardQueue::New).then_some(card.due.max(0) as u32))
+    }
+
+    /// Cards in the review queue that have been due for more than
+    /// `overdue_days` days, in the collection or the deck and its children,
+    /// paired with the number of days they are overdue. The most overdue cards
+    /// come first.
+    pub fn get_long_overdue_cards(
+        &mut self,
+        deck_id: Option<DeckId>,
+        overdue_days: u32,
+    ) -> Result<Vec<(CardId, u32)>> {
+        let search = match deck_id {
+            Some(did) => {
+                let deck = self.get_deck(did)?.or_not_found(did)?;
+                SearchNode::from_deck_name(&deck.human_name())
+            }
+            None => SearchNode::WholeCollection,
+        };
+        let today = self.timing_today()?.days_elapsed as i64;
+        let mut cards: Vec<_> = self
+            .all_cards_for_search(search.and(SearchNode::State(StateKind::Review)))?
+            .into_iter()
+            .filter(|card| card.queue == CardQueue::Review)
+            .filter_map(|card| {
+                let overdue = today - card.original_or_current_due() as i64;
+                (overdue > overdue_days as i64).then_some((card.id, overdue as u32))
+            })
+            .collect();
+        cards.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        Ok(cards)
+    }
+
+    /// Set the ease factor of the review cards among `cards`, or only of
+    /// those whose ease is below `only_below`. Other cards are ignored. The
+    /// ease must be between [MINIMUM_EASE_FACTOR] and [MAXIMUM_EASE_FACTOR].
+    /// FSRS is enabled for every preset or none, so when it is on, the
+    /// matching cards are all skipped and counted.
+    pub fn set_ease_factor(
+        &mut self,
+        cards: &[CardId],
+        ease_factor: f32,
+        only_below: Option<f32>,
+    ) -> Result<OpOutput<SetEaseFactorOutput>> {
+        require!(
+            (MINIMUM_EASE_FACTOR..=MAXIMUM_EASE_FACTOR).contains(&ease_factor),
+            "ease factor must be between {MINIMUM_EASE_FACTOR} and {MAXIMUM_EASE_FACTOR}"
+        );
+        let ease_thousands = (ease_factor * 1000.0).round() as u16;
+        let fsrs = self.get_config_bool(BoolKey::Fsrs);
+        self.transact(Op::UpdateCard, |col| {
+            let usn = col.usn()?;
+            let mut output = SetEaseFactorOutput::default();
+            for chunk in cards.chunks(SET_EASE_CHUNK_SIZE) {
+                for mut card in col.all_cards_for_ids(chunk, false)? {
+                    if !matches!(card.ctype, CardType::Review | CardType::Relearn)
+                        || card.ease_factor == ease_thousands
+                        || only_below.is_some_and(|below| card.ease_factor() >= below)
+                    {
+                        continue;
+                    }
+                    if fsrs {
+                        output.skipped_fsrs += 1;
+                        continue;
+                    }
+                    let original = card.clone();
+                    card.ease_factor = ease_thousands;
+                    col.update_card_inner(&mut card, original, usn)?;
+                    output.updated += 1;
+                }
+            }
+            Ok(output)
+        })
+    }
+
+    /// The card's difficulty, normalized to 0.0-1.0. For cards scheduled with
+    /// FSRS, this is the difficulty of its memory state. Other cards only
+    /// have an ease factor, so an estimate is derived from it: an ease of
+    /// [SM2_EASIEST_FACTOR] or more maps to 0.0 and the minimum ease to 1.0,
+    /// linearly in between, and each lapse adds [SM2_DIFFICULTY_PER_LAPSE].
+    /// Cards that have not been reviewed are treated as having the initial
+    /// ease. The estimate is only meant for rough comparisons between
+    /// cards, and will not match what FSRS would calculate.
+    pub fn compute_card_difficulty(&mut self, cid: CardId) -> Result<f32> {
+        let card = self.storage.get_card(cid)?.or_not_found(cid)?;
+        if let Some(state) = card.memory_state {
+            return Ok(state.difficulty());
+        }
+        let ease = if card.ease_factor == 0 {
+            INITIAL_EASE_FACTOR
+        } else {
+            card.ease_factor()
+        };
+        let from_ease = (SM2_EASIEST_FACTOR - ease) / (SM2_EASIEST_FACTOR - MINIMUM_EASE_FACTOR);
+        let from_lapses = card.lapses as f32 * SM2_DIFFICULTY_PER_LAPSE;
+        Ok((from_ease + from_lapses).clamp(0.0, 1.0))
+    }
+
     pub(crate) fn update_cards_maybe_undoable(
         &mut self,
         cards: Vec<Card>,
@@ -305,6 +549,7 @@ impl Collection {
                             card: true,
                             ..Default::default()
                         },
+                        ids: ChangedIds::default(),
                     },
                 })
             })
@@ -341,17 +586,21 @@ impl Collection {
         self.add_card_undoable(card)
     }
 
-    /// Remove cards and any resulting orphaned notes.
+    /// Remove cards and any resulting orphaned notes, returning the ids of
+    /// the cards that existed and were removed, in the order given.
     /// Expects a transaction.
-    pub(crate) fn remove_cards_and_orphaned_notes(&mut self, cids: &[CardId]) -> Result<usize> {
+    pub(crate) fn remove_cards_and_orphaned_notes(
+        &mut self,
+        cids: &[CardId],
+    ) -> Result<Vec<CardId>> {
         let usn = self.usn()?;
         let mut nids = HashSet::new();
-        let mut card_count = 0;
+        let mut removed = vec![];
         for cid in cids {
             if let Some(card) = self.storage.get_card(*cid)? {
                 nids.insert(card.note_id);
                 self.remove_card_and_add_grave_undoable(card, usn)?;
-                card_count += 1;
+                removed.push(*cid);
             }
         }
         for nid in nids {
@@ -360,7 +609,27 @@ impl Collection {
             }
         }
 
-        Ok(card_count)
+        Ok(removed)
+    }
+
+    /// The notes that [Collection::remove_cards_and_orphaned_notes] would
+    /// remove along with `cids`, as none of their cards would remain.
+    pub(crate) fn notes_orphaned_by_removing_cards(&self, cids: &[CardId]) -> Result<Vec<NoteId>> {
+        let removed: HashSet<CardId> = cids.iter().copied().collect();
+        let mut nids = HashSet::new();
+        for cid in &removed {
+            if let Some(card) = self.storage.get_card(*cid)? {
+                nids.insert(card.note_id);
+            }
+        }
+        let mut orphaned = vec![];
+        for nid in nids {
+            let remaining = self.storage.card_ids_of_notes(&[nid])?;
+            if remaining.iter().all(|cid| removed.contains(cid)) {
+                orphaned.push(nid);
+            }
+        }
+        Ok(orphaned)
     }
 
     pub fn set_deck(&mut self, cards: &[CardId], deck_id: DeckId) -> Result<OpOutput<usize>> {
@@ -413,7 +682,6 @@ impl Collection {
     }
 
     /// Get deck config for the given card. If missing, return default values.
-    #[allow(dead_code)]
     pub(crate) fn deck_config_for_card(&mut self, card: &Card) -> Result<DeckConfig> {
         if let Some(deck) = self.get_deck(card.original_or_current_deck_id())? {
             if let Some(conf_id) = deck.config_id() {
@@ -506,10 +774,15 @@ impl From<MemoryState> for FsrsMemoryState {
 
 #[cfg(test)]
 mod test {
+    use super::CardQueue;
+    use super::CardType;
+    use super::FsrsMemoryState;
+    use super::SetEaseFactorOutput;
     use crate::prelude::*;
     use crate::tests::open_test_collection_with_learning_card;
     use crate::tests::open_test_collection_with_relearning_card;
     use crate::tests::DeckAdder;
+    use crate::tests::NoteAdder;
 
     #[test]
     fn should_increase_remaining_learning_steps_if_new_deck_has_more_unpassed_ones() {
@@ -555,4 +828,216 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn removing_cards_reports_the_ones_found() -> Result<()> {
+        let mut col = Collection::new();
+        let first = NoteAdder::basic(&mut col).add(&mut col);
+        let second = NoteAdder::basic(&mut col).add(&mut col);
+        let first = col.storage.all_cards_of_note(first.id)?[0].id;
+        let second = col.storage.all_cards_of_note(second.id)?[0].id;
+
+        let removed = col.transact_no_undo(|col| {
+            col.remove_cards_and_orphaned_notes(&[first, CardId(123), first, second])
+        })?;
+        assert_eq!(removed, [first, second]);
+        assert!(col.storage.get_all_note_ids()?.is_empty());
+        // removing them again finds nothing
+        let removed = col.transact_no_undo(|col| col.remove_cards_and_orphaned_notes(&[first]))?;
+        assert!(removed.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn lapse_counts() -> Result<()> {
+        let mut col = Collection::new();
+        let deck = DeckAdder::new("parent::child").add(&mut col);
+        let mut cids = vec![];
+        for lapses in [3, 1, 7] {
+            let note = NoteAdder::basic(&mut col).deck(deck.id).add(&mut col);
+            let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+            card.lapses = lapses;
+            col.storage.update_card(&card)?;
+            cids.push(card.id);
+        }
+        let parent = col.get_deck_id("parent")?.unwrap();
+
+        assert_eq!(
+            col.get_lapse_counts_by_card(parent, 2)?,
+            [(cids[2], 7), (cids[0], 3)]
+        );
+        assert!(col.get_lapse_counts_by_card(DeckId(1), 0)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn ease_factor_outliers() -> Result<()> {
+        let mut col = Collection::new();
+        let mut cids = vec![];
+        for ease in [2500, 2500, 2500, 2500, 2500, 2500, 2500, 2500, 1300, 0] {
+            let note = NoteAdder::basic(&mut col).add(&mut col);
+            let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+            card.ctype = CardType::Review;
+            card.ease_factor = ease;
+            col.storage.update_card(&card)?;
+            cids.push(card.id);
+        }
+
+        assert_eq!(col.get_ease_factor_outliers(None, 2.0)?, [(cids[8], 1.3)]);
+        assert_eq!(col.get_ease_factor_outliers(Some(DeckId(1)), 0.0)?.len(), 9);
+        assert!(col.get_ease_factor_outliers(None, 3.0)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn cards_by_ease_range() -> Result<()> {
+        let mut col = Collection::new();
+        let mut cids = vec![];
+        for (ease, queue) in [
+            (1800, CardQueue::Review),
+            (1300, CardQueue::Review),
+            (2500, CardQueue::Review),
+            (1500, CardQueue::Suspended),
+            (1500, CardQueue::Review),
+        ] {
+            let note = NoteAdder::basic(&mut col).add(&mut col);
+            let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+            card.ctype = CardType::Review;
+            card.queue = queue;
+            card.ease_factor = ease;
+            col.storage.update_card(&card)?;
+            cids.push(card.id);
+        }
+
+        assert_eq!(
+            col.get_cards_by_ease_range(None, 1.3, 1.8, 10)?,
+            [cids[1], cids[4], cids[0]]
+        );
+        assert_eq!(
+            col.get_cards_by_ease_range(Some(DeckId(1)), 1.3, 1.8, 2)?,
+            [cids[1], cids[4]]
+        );
+        assert!(col.get_cards_by_ease_range(None, 2.6, 5.0, 10)?.is_empty());
+        assert!(col.get_cards_by_ease_range(None, 1.8, 1.3, 10).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn queue_position() -> Result<()> {
+        let mut col = Collection::new();
+        NoteAdder::basic(&mut col).add(&mut col);
+        let note = NoteAdder::basic(&mut col).add(&mut col);
+        let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+        assert_eq!(col.get_card_queue_position(card.id)?, Some(2));
+
+        card.queue = CardQueue::Suspended;
+        col.storage.update_card(&card)?;
+        assert_eq!(col.get_card_queue_position(card.id)?, None);
+        assert!(col.get_card_queue_position(CardId(123)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn long_overdue_cards() -> Result<()> {
+        let mut col = Collection::new();
+        let today = col.timing_today()?.days_elapsed as i32;
+        let mut cids = vec![];
+        for (due, queue) in [
+            (today - 10, CardQueue::Review),
+            (today - 45, CardQueue::Review),
+            (today - 31, CardQueue::Review),
+            (today - 60, CardQueue::Suspended),
+            (today - 30, CardQueue::Review),
+        ] {
+            let note = NoteAdder::basic(&mut col).add(&mut col);
+            let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+            card.ctype = CardType::Review;
+            card.queue = queue;
+            card.due = due;
+            col.storage.update_card(&card)?;
+            cids.push(card.id);
+        }
+
+        assert_eq!(
+            col.get_long_overdue_cards(None, 30)?,
+            [(cids[1], 45), (cids[2], 31)]
+        );
+        assert_eq!(col.get_long_overdue_cards(Some(DeckId(1)), 5)?.len(), 4);
+        assert!(col.get_long_overdue_cards(None, 45)?.is_empty());
+        assert!(col.get_long_overdue_cards(Some(DeckId(123)), 5).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn set_ease_factor() -> Result<()> {
+        let mut col = Collection::new();
+        let mut cids = vec![];
+        for (ctype, ease) in [
+            (CardType::Review, 1300),
+            (CardType::Relearn, 2100),
+            (CardType::Review, 2300),
+            (CardType::New, 0),
+        ] {
+            let note = NoteAdder::basic(&mut col).add(&mut col);
+            let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+            card.ctype = ctype;
+            card.ease_factor = ease;
+            col.storage.update_card(&card)?;
+            cids.push(card.id);
+        }
+        let eases = |col: &mut Collection| -> Result<Vec<u16>> {
+            cids.iter()
+                .map(|&cid| Ok(col.storage.get_card(cid)?.unwrap().ease_factor))
+                .collect()
+        };
+
+        assert!(col.set_ease_factor(&cids, 1.2, None).is_err());
+        assert!(col.set_ease_factor(&cids, 5.1, None).is_err());
+
+        let out = col.set_ease_factor(&cids, 2.5, Some(2.2))?.output;
+        assert_eq!(out.updated, 2);
+        assert_eq!(eases(&mut col)?, [2500, 2500, 2300, 0]);
+        // a single undo step
+        col.undo()?;
+        assert_eq!(eases(&mut col)?, [1300, 2100, 2300, 0]);
+
+        col.set_config_bool(BoolKey::Fsrs, true, false)?;
+        let out = col.set_ease_factor(&cids, 2.5, None)?.output;
+        assert_eq!(
+            out,
+            SetEaseFactorOutput {
+                updated: 0,
+                skipped_fsrs: 3
+            }
+        );
+        assert_eq!(eases(&mut col)?, [1300, 2100, 2300, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn compute_difficulty() -> Result<()> {
+        let mut col = Collection::new();
+        let note = NoteAdder::basic(&mut col).add(&mut col);
+        let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+        // unreviewed cards use the initial ease
+        assert!((col.compute_card_difficulty(card.id)? - 0.294).abs() < 0.001);
+
+        card.ctype = CardType::Review;
+        card.ease_factor = 2500;
+        card.lapses = 2;
+        col.storage.update_card(&card)?;
+        assert!((col.compute_card_difficulty(card.id)? - 0.394).abs() < 0.001);
+
+        card.ease_factor = 1300;
+        col.storage.update_card(&card)?;
+        assert_eq!(col.compute_card_difficulty(card.id)?, 1.0);
+
+        card.memory_state = Some(FsrsMemoryState {
+            stability: 10.0,
+            difficulty: 5.5,
+        });
+        col.storage.update_card(&card)?;
+        assert_eq!(col.compute_card_difficulty(card.id)?, 0.5);
+        Ok(())
+    }
 }
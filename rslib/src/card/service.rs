@@ -57,6 +57,7 @@ impl crate::services::CardsService for Collection {
                     .map(Into::into)
                     .collect::<Vec<_>>(),
             )
+            .map(|removed| removed.len())
         })
         .map(Into::into)
     }
@@ -12,6 +12,15 @@ pub(crate) enum UndoableCardChange {
     GraveRemoved(Box<(CardId, Usn)>),
 }
 
+impl UndoableCardChange {
+    pub(crate) fn card_id(&self) -> CardId {
+        match self {
+            Self::Added(card) | Self::Updated(card) | Self::Removed(card) => card.id,
+            Self::GraveAdded(grave) | Self::GraveRemoved(grave) => grave.0,
+        }
+    }
+}
+
 impl Collection {
     pub(crate) fn undo_card_change(&mut self, change: UndoableCardChange) -> Result<()> {
         match change {
@@ -22,6 +22,15 @@ use crate::prelude::*;
 
 const BACKUP_FORMAT_STRING: &str = "backup-%Y-%m-%d-%H.%M.%S.colpkg";
 
+/// A backup file found in the collection's backup folder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupInfo {
+    pub filename: String,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    pub created: TimestampSecs,
+}
+
 impl Collection {
     /// Create a backup if enough time has elapsed, or if forced.
     /// Returns a handle that can be awaited if a backup was created.
@@ -46,6 +55,61 @@ impl Collection {
             })))
         }
     }
+
+    /// The folder backups are kept in, alongside the collection file as in a
+    /// desktop profile.
+    pub fn backup_folder(&self) -> PathBuf {
+        self.col_path
+            .parent()
+            .map(|folder| folder.join("backups"))
+            .unwrap_or_default()
+    }
+
+    /// The .colpkg files in the backup folder, newest first.
+    pub fn list_backups(&self) -> Result<Vec<BackupInfo>> {
+        let folder = self.backup_folder();
+        if !folder.is_dir() {
+            return Ok(vec![]);
+        }
+        Ok(read_dir(folder)?
+            .filter_map(|entry| entry.ok().and_then(BackupInfo::from_entry))
+            .sorted_by(|a, b| {
+                b.created
+                    .cmp(&a.created)
+                    .then_with(|| b.filename.cmp(&a.filename))
+            })
+            .collect())
+    }
+}
+
+impl BackupInfo {
+    fn from_entry(entry: DirEntry) -> Option<Self> {
+        let filename = entry.file_name().to_str()?.to_string();
+        if !filename.ends_with(".colpkg") {
+            return None;
+        }
+        let meta = entry.metadata().ok()?;
+        if !meta.is_file() {
+            return None;
+        }
+        // prefer the time encoded in the name, which survives copying
+        let created = match datetime_from_file_name(&filename) {
+            Some(datetime) => TimestampSecs(datetime.timestamp()),
+            None => TimestampSecs(
+                meta.modified()
+                    .ok()?
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .ok()?
+                    .as_secs() as i64,
+            ),
+        };
+        Some(Self {
+            filename,
+            path: entry.path(),
+            size_bytes: meta.len(),
+            created,
+        })
+    }
 }
 
 fn should_skip_backup(
@@ -288,6 +352,35 @@ mod test {
         };
     }
 
+    #[test]
+    fn listing() -> Result<()> {
+        let (col, _dir) = open_fs_test_collection("col");
+        assert_eq!(col.list_backups()?, vec![]);
+
+        let folder = col.backup_folder();
+        std::fs::create_dir(&folder)?;
+        for (name, len) in [
+            ("backup-2022-02-22-10.00.00.colpkg", 3),
+            ("backup-2023-01-01-10.00.00.colpkg", 5),
+            ("notes.txt", 1),
+        ] {
+            std::fs::write(folder.join(name), vec![0; len])?;
+        }
+        let backups = col.list_backups()?;
+        assert_eq!(
+            backups
+                .iter()
+                .map(|b| (b.filename.as_str(), b.size_bytes))
+                .collect_vec(),
+            [
+                ("backup-2023-01-01-10.00.00.colpkg", 5),
+                ("backup-2022-02-22-10.00.00.colpkg", 3)
+            ]
+        );
+        assert!(backups[0].created > backups[1].created);
+        Ok(())
+    }
+
     #[test]
     fn thinning_manual() {
         let today = Local
@@ -0,0 +1,181 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use crate::prelude::*;
+use crate::sync::collection::graves::Graves;
+
+/// Objects added, modified or removed since a given time.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ChangedEntities {
+    pub updated_cards: Vec<CardId>,
+    pub deleted_cards: Vec<CardId>,
+    pub updated_notes: Vec<NoteId>,
+    pub deleted_notes: Vec<NoteId>,
+    pub updated_decks: Vec<DeckId>,
+}
+
+impl Collection {
+    /// Return the cards, notes and decks that have been added or modified at
+    /// or after `since`, and the ones that have been deleted.
+    ///
+    /// Graves do not record when an object was deleted, so the deleted lists
+    /// include every deletion since the last full sync, and may contain ids
+    /// that were removed before `since`.
+    pub fn get_changed_entities_since(&self, since: TimestampSecs) -> Result<ChangedEntities> {
+        let graves = self.storage.all_graves()?;
+        Ok(ChangedEntities {
+            updated_cards: self.storage.card_ids_modified_since(since)?,
+            deleted_cards: graves.cards,
+            updated_notes: self.storage.note_ids_modified_since(since)?,
+            deleted_notes: graves.notes,
+            updated_decks: self.storage.deck_ids_modified_since(since)?,
+        })
+    }
+
+    /// Return the notes added or modified at or after `since`, with their
+    /// modification times, oldest first.
+    pub fn get_notes_modified_since(
+        &self,
+        since: TimestampSecs,
+    ) -> Result<Vec<(NoteId, TimestampSecs)>> {
+        self.storage.notes_modified_since(since)
+    }
+
+    /// Return the notes deleted since the last full sync. As with
+    /// [Collection::get_changed_entities_since()], graves do not record when
+    /// a note was deleted, so this cannot be narrowed down further.
+    pub fn get_deleted_notes(&self) -> Result<Vec<NoteId>> {
+        Ok(self.storage.all_graves()?.notes)
+    }
+
+    /// Return the cards, notes and decks that may have been deleted at or
+    /// after `since`. Graves do not record when an object was deleted, but
+    /// ones that have been synced must be older than the last sync, so when
+    /// `since` is later than that, only unsynced graves are returned.
+    /// Otherwise, every grave since the last full sync is.
+    pub fn get_graves_since(&self, since: TimestampSecs) -> Result<Graves> {
+        let last_sync = self.storage.get_collection_timestamps()?.last_sync;
+        if since.as_millis() >= last_sync {
+            self.storage.pending_graves(self.usn()?)
+        } else {
+            self.storage.all_graves()
+        }
+    }
+
+    /// Return the cards added or modified at or after `since`, with their
+    /// modification times, oldest first.
+    pub fn get_cards_modified_since(
+        &self,
+        since: TimestampSecs,
+    ) -> Result<Vec<(CardId, TimestampSecs)>> {
+        self.storage.cards_modified_since(since)
+    }
+
+    /// Return the cards deleted since the last full sync. Like
+    /// [Collection::get_deleted_notes()], this cannot be limited to recent
+    /// deletions.
+    pub fn get_deleted_cards(&self) -> Result<Vec<CardId>> {
+        Ok(self.storage.all_graves()?.cards)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn changes_since() -> Result<()> {
+        let mut col = Collection::new();
+        let old = NoteAdder::basic(&mut col).add(&mut col);
+        let old_cid = col.storage.all_card_ids_of_note_in_template_order(old.id)?[0];
+        // backdate everything that exists so far
+        col.storage.db.execute_batch(
+            "update cards set mod = 0; update notes set mod = 0; update decks set mtime_secs = 0",
+        )?;
+
+        let since = TimestampSecs(1);
+        let note = NoteAdder::basic(&mut col).add(&mut col);
+        let cid = col
+            .storage
+            .all_card_ids_of_note_in_template_order(note.id)?[0];
+        let deck = DeckAdder::new("new").add(&mut col);
+        col.remove_notes(&[old.id])?;
+
+        let changes = col.get_changed_entities_since(since)?;
+        assert_eq!(changes.updated_cards, vec![cid]);
+        assert_eq!(changes.updated_notes, vec![note.id]);
+        assert_eq!(changes.updated_decks, vec![deck.id]);
+        assert_eq!(changes.deleted_cards, vec![old_cid]);
+        assert_eq!(changes.deleted_notes, vec![old.id]);
+        Ok(())
+    }
+
+    #[test]
+    fn notes_modified_since() -> Result<()> {
+        let mut col = Collection::new();
+        let old = NoteAdder::basic(&mut col).add(&mut col);
+        let newer = NoteAdder::basic(&mut col).add(&mut col);
+        let removed = NoteAdder::basic(&mut col).add(&mut col);
+        col.storage.db.execute_batch(&format!(
+            "update notes set mod = 0 where id = {}; update notes set mod = 20 where id = {}",
+            old.id, newer.id
+        ))?;
+        col.remove_notes(&[removed.id])?;
+
+        assert_eq!(
+            col.get_notes_modified_since(TimestampSecs(10))?,
+            vec![(newer.id, TimestampSecs(20))]
+        );
+        assert_eq!(col.get_notes_modified_since(TimestampSecs(0))?.len(), 2);
+        assert_eq!(col.get_deleted_notes()?, vec![removed.id]);
+        Ok(())
+    }
+
+    #[test]
+    fn graves_since() -> Result<()> {
+        let mut col = Collection::new();
+        let synced = NoteAdder::basic(&mut col).add(&mut col);
+        let unsynced = NoteAdder::basic(&mut col).add(&mut col);
+        col.remove_notes(&[synced.id])?;
+        // pretend the first removal was synced
+        col.storage.db.execute("update graves set usn = 0", [])?;
+        col.storage.set_last_sync(TimestampMillis(10_000))?;
+        col.remove_notes(&[unsynced.id])?;
+
+        let graves = col.get_graves_since(TimestampSecs(10))?;
+        assert_eq!(graves.notes, vec![unsynced.id]);
+        assert_eq!(graves.cards.len(), 1);
+        let graves = col.get_graves_since(TimestampSecs(9))?;
+        assert_eq!(graves.notes.len(), 2);
+        assert_eq!(graves.cards.len(), 2);
+        assert!(graves.decks.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn cards_modified_since() -> Result<()> {
+        let mut col = Collection::new();
+        let old = NoteAdder::basic(&mut col).add(&mut col);
+        let newer = NoteAdder::basic(&mut col).add(&mut col);
+        let removed = NoteAdder::basic(&mut col).add(&mut col);
+        let old_cid = col.storage.all_card_ids_of_note_in_template_order(old.id)?[0];
+        let newer_cid = col
+            .storage
+            .all_card_ids_of_note_in_template_order(newer.id)?[0];
+        let removed_cid = col
+            .storage
+            .all_card_ids_of_note_in_template_order(removed.id)?[0];
+        col.storage.db.execute_batch(&format!(
+            "update cards set mod = 0 where id = {old_cid}; update cards set mod = 20 where id = {newer_cid}",
+        ))?;
+        col.remove_notes(&[removed.id])?;
+
+        assert_eq!(
+            col.get_cards_modified_since(TimestampSecs(10))?,
+            vec![(newer_cid, TimestampSecs(20))]
+        );
+        assert_eq!(col.get_cards_modified_since(TimestampSecs(0))?.len(), 2);
+        assert_eq!(col.get_deleted_cards()?, vec![removed_cid]);
+        Ok(())
+    }
+}
@@ -2,7 +2,9 @@
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
 pub mod backup;
+pub mod changes;
 mod service;
+pub mod size;
 pub(crate) mod timestamps;
 mod transact;
 pub(crate) mod undo;
@@ -13,10 +15,12 @@ use std::fmt::Formatter;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Instant;
 
 use anki_i18n::I18n;
 use anki_io::create_dir_all;
 
+use self::size::SizeBreakdown;
 use crate::browser_table;
 use crate::decks::Deck;
 use crate::decks::DeckId;
@@ -137,7 +141,12 @@ pub struct CollectionState {
     /// The modification time at the last backup, so we don't create multiple
     /// identical backups.
     pub(crate) last_backup_modified: Option<TimestampMillis>,
+    /// True while running an operation that must fail rather than force a
+    /// one-way sync. See [Collection::guard_schema_change()].
+    pub(crate) schema_change_not_allowed: bool,
     pub(crate) progress: Arc<Mutex<ProgressState>>,
+    /// See [Collection::get_collection_size_breakdown()].
+    pub(crate) size_breakdown: Option<(Instant, SizeBreakdown)>,
 }
 
 pub struct Collection {
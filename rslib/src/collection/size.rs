@@ -0,0 +1,135 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::ffi::OsString;
+use std::fs::metadata;
+use std::fs::read_dir;
+use std::path::Path;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::prelude::*;
+
+/// How long a computed breakdown is reused for, as walking the media folder
+/// of a large collection is slow.
+const SIZE_BREAKDOWN_CACHE_DURATION: Duration = Duration::from_secs(60);
+
+/// The disk space used by a collection and the files that belong to it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SizeBreakdown {
+    /// The collection file, and its write-ahead log if there is one.
+    pub database_bytes: u64,
+    pub media_bytes: u64,
+    pub media_count: u32,
+    pub backup_bytes: u64,
+    pub backup_count: u32,
+    pub total_bytes: u64,
+}
+
+impl Collection {
+    /// Where the collection's disk space is going. The result is cached for
+    /// a minute.
+    pub fn get_collection_size_breakdown(&mut self) -> Result<SizeBreakdown> {
+        let media_folder = self.media_folder.clone();
+        self.get_collection_size_breakdown_with_media_folder(&media_folder)
+    }
+
+    /// Like [Collection::get_collection_size_breakdown], for collections whose
+    /// media is kept outside [Collection::media_folder], as on the sync
+    /// server.
+    pub(crate) fn get_collection_size_breakdown_with_media_folder(
+        &mut self,
+        media_folder: &Path,
+    ) -> Result<SizeBreakdown> {
+        if let Some((computed_at, breakdown)) = &self.state.size_breakdown {
+            if computed_at.elapsed() < SIZE_BREAKDOWN_CACHE_DURATION {
+                return Ok(breakdown.clone());
+            }
+        }
+        let breakdown = self.compute_size_breakdown(media_folder)?;
+        self.state.size_breakdown = Some((Instant::now(), breakdown.clone()));
+        Ok(breakdown)
+    }
+
+    fn compute_size_breakdown(&self, media_folder: &Path) -> Result<SizeBreakdown> {
+        let mut wal_path = OsString::from(&self.col_path);
+        wal_path.push("-wal");
+        let database_bytes = [self.col_path.as_os_str(), wal_path.as_os_str()]
+            .into_iter()
+            .filter_map(|path| metadata(path).ok())
+            .map(|meta| meta.len())
+            .sum();
+        let (media_bytes, media_count) = folder_size(media_folder)?;
+        let backups = self.list_backups()?;
+        let backup_bytes = backups.iter().map(|backup| backup.size_bytes).sum();
+        let mut breakdown = SizeBreakdown {
+            database_bytes,
+            media_bytes,
+            media_count,
+            backup_bytes,
+            backup_count: backups.len() as u32,
+            total_bytes: 0,
+        };
+        breakdown.total_bytes = database_bytes + media_bytes + backup_bytes;
+        Ok(breakdown)
+    }
+}
+
+/// The total size and number of files directly inside `folder`, using their
+/// metadata only. A missing folder is empty.
+fn folder_size(folder: &Path) -> Result<(u64, u32)> {
+    if !folder.is_dir() {
+        return Ok((0, 0));
+    }
+    let mut bytes = 0;
+    let mut count = 0;
+    for entry in read_dir(folder)? {
+        let meta = entry?.metadata()?;
+        if meta.is_file() {
+            bytes += meta.len();
+            count += 1;
+        }
+    }
+    Ok((bytes, count))
+}
+
+#[cfg(test)]
+mod test {
+    use anki_io::create_dir_all;
+    use anki_io::write_file;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::collection::CollectionBuilder;
+
+    #[test]
+    fn size_breakdown() -> Result<()> {
+        let dir = tempdir()?;
+        let mut col = CollectionBuilder::new(dir.path().join("collection.anki2"))
+            .with_desktop_media_paths()
+            .build()?;
+        write_file(col.media_folder.join("a.jpg"), "12345")?;
+        write_file(col.media_folder.join("b.mp3"), "123")?;
+        create_dir_all(col.backup_folder())?;
+        write_file(
+            col.backup_folder()
+                .join("backup-2024-01-01-00.00.00.colpkg"),
+            "1234567",
+        )?;
+
+        let breakdown = col.get_collection_size_breakdown()?;
+        assert!(breakdown.database_bytes > 0);
+        assert_eq!(breakdown.media_bytes, 8);
+        assert_eq!(breakdown.media_count, 2);
+        assert_eq!(breakdown.backup_bytes, 7);
+        assert_eq!(breakdown.backup_count, 1);
+        assert_eq!(breakdown.total_bytes, breakdown.database_bytes + 15);
+
+        // cached until it expires
+        write_file(col.media_folder.join("c.jpg"), "1")?;
+        assert_eq!(col.get_collection_size_breakdown()?, breakdown);
+        col.state.size_breakdown = None;
+        assert_eq!(col.get_collection_size_breakdown()?.media_count, 3);
+        Ok(())
+    }
+}
@@ -30,10 +30,44 @@ impl Collection {
 
     /// Forces the next sync in one direction.
     pub fn set_schema_modified(&mut self) -> Result<()> {
+        if self.state.schema_change_not_allowed {
+            return Err(AnkiError::SchemaChangeNotAllowed);
+        }
         let stamps = self.storage.get_collection_timestamps()?;
         self.set_schema_modified_time_undoable(TimestampMillis::now(), stamps.schema_change)
     }
 
+    /// Run `func`, and return its output along with whether it modified the
+    /// schema. If `allow` is false, an operation that would modify the schema
+    /// fails with [AnkiError::SchemaChangeNotAllowed] instead, and as the
+    /// error aborts the operation's transaction, nothing is changed.
+    pub fn guard_schema_change<F, T>(&mut self, allow: bool, func: F) -> Result<(T, bool)>
+    where
+        F: FnOnce(&mut Collection) -> Result<T>,
+    {
+        let before = self.storage.get_collection_timestamps()?.schema_change;
+        self.state.schema_change_not_allowed = !allow;
+        let output = func(self);
+        self.state.schema_change_not_allowed = false;
+        let after = self.storage.get_collection_timestamps()?.schema_change;
+        Ok((output?, after != before))
+    }
+
+    /// The usn stored in the collection, which the server increments on each
+    /// sync. Unlike [Collection::usn], this is returned on clients as well.
+    pub fn get_collection_usn(&self) -> Result<i32> {
+        Ok(self.storage.usn(true)?.0)
+    }
+
+    /// True if the schema has been modified since the last sync, so the next
+    /// sync will need to be a full one.
+    pub fn schema_modified_since_sync(&self) -> Result<bool> {
+        Ok(self
+            .storage
+            .get_collection_timestamps()?
+            .schema_changed_since_sync())
+    }
+
     pub fn changed_since_last_backup(&self) -> Result<bool> {
         let stamps = self.storage.get_collection_timestamps()?;
         Ok(self
@@ -1,6 +1,7 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use crate::ops::ChangedIds;
 use crate::ops::StateChanges;
 use crate::prelude::*;
 
@@ -37,6 +38,7 @@ impl Collection {
                     OpChanges {
                         op: Op::SkipUndo,
                         changes: StateChanges::default(),
+                        ids: ChangedIds::default(),
                     }
                 };
                 self.end_undoable_operation(skip_undo_queue);
@@ -3,11 +3,12 @@
 
 use serde::Deserialize;
 use serde_aux::field_attributes::deserialize_bool_from_anything;
+use strum::EnumIter;
 use strum::IntoStaticStr;
 
 use crate::prelude::*;
 
-#[derive(Debug, Clone, Copy, IntoStaticStr)]
+#[derive(Debug, Clone, Copy, IntoStaticStr, EnumIter)]
 #[strum(serialize_all = "camelCase")]
 pub enum BoolKey {
     ApplyAllParentLimits,
@@ -56,6 +57,56 @@ pub enum BoolKey {
     AddingDefaultsToCurrentDeck,
 }
 
+impl BoolKey {
+    /// True for display and editing preferences that the sync server's REST
+    /// API can read and change. Keys that affect scheduling or imports are
+    /// left out. There is deliberately no catch-all arm, so that new keys
+    /// have to be classified.
+    pub(crate) fn is_rest_preference(self) -> bool {
+        match self {
+            BoolKey::BrowserTableShowNotesMode
+            | BoolKey::CardCountsSeparateInactive
+            | BoolKey::CollapseCardState
+            | BoolKey::CollapseDecks
+            | BoolKey::CollapseFlags
+            | BoolKey::CollapseNotetypes
+            | BoolKey::CollapseSavedSearches
+            | BoolKey::CollapseTags
+            | BoolKey::CollapseToday
+            | BoolKey::FutureDueShowBacklog
+            | BoolKey::HideAudioPlayButtons
+            | BoolKey::IgnoreAccentsInSearch
+            | BoolKey::InterruptAudioWhenAnswering
+            | BoolKey::PasteImagesAsPng
+            | BoolKey::PasteStripsFormatting
+            | BoolKey::PreviewBothSides
+            | BoolKey::RestorePositionBrowser
+            | BoolKey::RestorePositionReviewer
+            | BoolKey::ResetCountsBrowser
+            | BoolKey::ResetCountsReviewer
+            | BoolKey::RandomOrderReposition
+            | BoolKey::ShiftPositionOfExistingCards
+            | BoolKey::ShowIntervalsAboveAnswerButtons
+            | BoolKey::ShowRemainingDueCountsInStudy
+            | BoolKey::AddingDefaultsToCurrentDeck => true,
+            BoolKey::ApplyAllParentLimits
+            | BoolKey::NewCardsIgnoreReviewLimit
+            | BoolKey::RenderLatex
+            | BoolKey::Sched2021
+            | BoolKey::MergeNotetypes
+            | BoolKey::WithScheduling
+            | BoolKey::WithDeckConfigs
+            | BoolKey::Fsrs
+            | BoolKey::FsrsHealthCheck
+            | BoolKey::FsrsLegacyEvaluate
+            | BoolKey::LoadBalancerEnabled
+            | BoolKey::FsrsShortTermWithStepsEnabled
+            | BoolKey::NormalizeNoteText
+            | BoolKey::ShowDayLearningCardsFirst => false,
+        }
+    }
+}
+
 /// This is a workaround for old clients that used ints to represent boolean
 /// values. For new config items, prefer using a bool directly.
 #[derive(Deserialize, Default)]
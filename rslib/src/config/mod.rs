@@ -10,6 +10,9 @@ mod string;
 pub(crate) mod undo;
 
 use anki_proto::config::preferences::BackupLimits;
+use chrono::Datelike;
+use chrono::Days;
+use chrono::NaiveDate;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_repr::Deserialize_repr;
@@ -209,6 +212,11 @@ impl Collection {
             .map(|_| ())
     }
 
+    /// Set the position the next added new card will receive.
+    pub fn set_next_card_position_undoable(&mut self, pos: u32) -> Result<OpOutput<()>> {
+        self.transact(Op::UpdateConfig, |col| col.set_next_card_position(pos))
+    }
+
     pub(crate) fn scheduler_version(&self) -> SchedulerVersion {
         self.get_config_optional(ConfigKey::SchedulerVersion)
             .unwrap_or(SchedulerVersion::V1)
@@ -222,6 +230,16 @@ impl Collection {
         self.scheduler_version() == SchedulerVersion::V2 && self.get_config_bool(BoolKey::Sched2021)
     }
 
+    /// The active scheduler as a number from 1 to 3, with v3 being v2 with the
+    /// 2021 scheduler enabled.
+    pub fn get_scheduler_version(&self) -> Result<u8> {
+        Ok(match self.scheduler_version() {
+            SchedulerVersion::V1 => 1,
+            SchedulerVersion::V2 if self.v3_enabled() => 3,
+            SchedulerVersion::V2 => 2,
+        })
+    }
+
     /// Caution: this only updates the config setting.
     pub(crate) fn set_scheduler_version_config_key(&mut self, ver: SchedulerVersion) -> Result<()> {
         self.state.scheduler_info = None;
@@ -318,7 +336,9 @@ pub(crate) enum NewReviewMix {
     NewFirst = 2,
 }
 
-#[derive(PartialEq, Eq, Serialize_repr, Deserialize_repr, Clone, Copy)]
+/// The day weeks start on in the graphs. The values match chrono's
+/// `num_days_from_sunday()`.
+#[derive(PartialEq, Eq, Serialize_repr, Deserialize_repr, Clone, Copy, Debug)]
 #[repr(u8)]
 pub(crate) enum Weekday {
     Sunday = 0,
@@ -327,10 +347,35 @@ pub(crate) enum Weekday {
     Saturday = 6,
 }
 
+impl Weekday {
+    /// The first day of the week containing `date`, for weeks starting on
+    /// this day.
+    pub(crate) fn start_of_week(self, date: NaiveDate) -> NaiveDate {
+        let days_in = (date.weekday().num_days_from_sunday() + 7 - self as u32) % 7;
+        date - Days::new(days_in as u64)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn start_of_week() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 6, day).unwrap();
+        // 2024-06-09 was a Sunday
+        assert_eq!(Weekday::Sunday.start_of_week(date(9)), date(9));
+        assert_eq!(Weekday::Sunday.start_of_week(date(15)), date(9));
+        assert_eq!(Weekday::Monday.start_of_week(date(9)), date(3));
+        assert_eq!(Weekday::Monday.start_of_week(date(10)), date(10));
+        assert_eq!(Weekday::Saturday.start_of_week(date(9)), date(8));
+        // weeks can start in the previous month
+        assert_eq!(
+            Weekday::Friday.start_of_week(date(6)),
+            NaiveDate::from_ymd_opt(2024, 5, 31).unwrap()
+        );
+    }
+
     #[test]
     fn defaults() {
         let col = Collection::new();
@@ -1,11 +1,12 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use strum::EnumIter;
 use strum::IntoStaticStr;
 
 use crate::prelude::*;
 
-#[derive(Debug, Clone, Copy, IntoStaticStr)]
+#[derive(Debug, Clone, Copy, IntoStaticStr, EnumIter)]
 #[strum(serialize_all = "camelCase")]
 pub enum I32ConfigKey {
     CsvDuplicateResolution,
@@ -13,6 +14,16 @@ pub enum I32ConfigKey {
     LastFsrsOptimize,
 }
 
+impl I32ConfigKey {
+    /// As [BoolKey::is_rest_preference].
+    pub(crate) fn is_rest_preference(self) -> bool {
+        match self {
+            I32ConfigKey::CsvDuplicateResolution | I32ConfigKey::MatchScope => true,
+            I32ConfigKey::LastFsrsOptimize => false,
+        }
+    }
+}
+
 impl Collection {
     pub fn get_config_i32(&self, key: I32ConfigKey) -> i32 {
         #[allow(clippy::match_single_binding)]
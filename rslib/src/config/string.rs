@@ -1,17 +1,32 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use strum::EnumIter;
 use strum::IntoStaticStr;
 
 use crate::prelude::*;
 
-#[derive(Debug, Clone, Copy, IntoStaticStr)]
+#[derive(Debug, Clone, Copy, IntoStaticStr, EnumIter)]
 #[strum(serialize_all = "camelCase")]
 pub enum StringKey {
     SetDueBrowser,
     SetDueReviewer,
     DefaultSearchText,
     CardStateCustomizer,
+    Locale,
+}
+
+impl StringKey {
+    /// As [BoolKey::is_rest_preference]. The card state customizer runs as
+    /// code, and the locale is validated by its own endpoint.
+    pub(crate) fn is_rest_preference(self) -> bool {
+        match self {
+            StringKey::SetDueBrowser | StringKey::SetDueReviewer | StringKey::DefaultSearchText => {
+                true
+            }
+            StringKey::CardStateCustomizer | StringKey::Locale => false,
+        }
+    }
 }
 
 impl Collection {
@@ -0,0 +1,208 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::cmp::Ordering;
+
+use fsrs::FSRS;
+
+use super::DeckConfig;
+use super::DeckConfigInner;
+use crate::prelude::*;
+use crate::search::JoinSearches;
+use crate::search::SearchNode;
+use crate::search::SortMode;
+use crate::search::StateKind;
+use crate::storage::comma_separated_ids;
+
+/// The number of cards read from the collection at a time when previewing.
+const PREVIEW_CHUNK_SIZE: usize = 1000;
+
+/// Changes to the settings of a preset that determine how long review
+/// intervals are. Settings that are [None] are left as they are.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct IntervalSettingsChange {
+    pub maximum_review_interval: Option<u32>,
+    pub interval_multiplier: Option<f32>,
+    /// Only used when FSRS is enabled.
+    pub desired_retention: Option<f32>,
+}
+
+/// How a [IntervalSettingsChange] would affect the next interval of the
+/// preset's review cards, if they were answered Good today.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IntervalChangePreview {
+    pub review_cards: u32,
+    pub shorter: u32,
+    pub longer: u32,
+    pub unchanged: u32,
+    /// The total number of days the shorter intervals would lose.
+    pub days_shorter: u64,
+    /// The total number of days the longer intervals would gain.
+    pub days_longer: u64,
+}
+
+impl IntervalSettingsChange {
+    fn apply(&self, config: &mut DeckConfigInner) -> Result<()> {
+        if let Some(days) = self.maximum_review_interval {
+            require!(
+                (1..=36_500).contains(&days),
+                "maximum interval must be between 1 and 36500 days"
+            );
+            config.maximum_review_interval = days;
+        }
+        if let Some(multiplier) = self.interval_multiplier {
+            require!(
+                (0.5..=2.0).contains(&multiplier),
+                "interval multiplier must be between 0.5 and 2"
+            );
+            config.interval_multiplier = multiplier;
+        }
+        if let Some(retention) = self.desired_retention {
+            require!(
+                (0.7..=0.99).contains(&retention),
+                "desired retention must be between 0.7 and 0.99"
+            );
+            config.desired_retention = retention;
+        }
+        Ok(())
+    }
+}
+
+impl Collection {
+    /// Change the interval settings of a preset. Existing due dates are left
+    /// alone; the new settings apply as cards are answered.
+    pub fn update_deck_config_intervals(
+        &mut self,
+        dcid: DeckConfigId,
+        change: IntervalSettingsChange,
+    ) -> Result<OpOutput<DeckConfig>> {
+        self.transact(Op::UpdateDeckConfig, |col| {
+            let original = col.storage.get_deck_config(dcid)?.or_not_found(dcid)?;
+            let mut config = original.clone();
+            change.apply(&mut config.inner)?;
+            let usn = col.usn()?;
+            col.update_deck_config_inner(&mut config, original, Some(usn))?;
+            Ok(config)
+        })
+    }
+
+    /// Compare the next intervals the preset's review cards would get with
+    /// and without `change`, without saving anything.
+    pub fn preview_deck_config_intervals_change(
+        &mut self,
+        dcid: DeckConfigId,
+        change: IntervalSettingsChange,
+    ) -> Result<IntervalChangePreview> {
+        let current = self.storage.get_deck_config(dcid)?.or_not_found(dcid)?;
+        let mut proposed = current.clone();
+        change.apply(&mut proposed.inner)?;
+        let mut preview = IntervalChangePreview::default();
+        let dids: Vec<_> = self
+            .decks_using_deck_config(dcid)?
+            .into_iter()
+            .map(|deck| deck.id)
+            .collect();
+        if dids.is_empty() {
+            return Ok(preview);
+        }
+        let search =
+            SearchNode::DeckIdsWithoutChildren(comma_separated_ids(&dids)).and(StateKind::Review);
+        let cids = self.search_cards(search, SortMode::NoOrder)?;
+        let fsrs_enabled = self.get_config_bool(BoolKey::Fsrs);
+        let build_fsrs = |config: &DeckConfig| -> Result<Option<FSRS>> {
+            Ok(if fsrs_enabled {
+                Some(FSRS::new(Some(config.fsrs_params()))?)
+            } else {
+                None
+            })
+        };
+        let current_fsrs = build_fsrs(&current)?;
+        let proposed_fsrs = build_fsrs(&proposed)?;
+        for chunk in cids.chunks(PREVIEW_CHUNK_SIZE) {
+            for card in self.all_cards_for_ids(chunk, false)? {
+                let before = self.good_interval_with_config(
+                    card.clone(),
+                    current.clone(),
+                    current_fsrs.as_ref(),
+                )?;
+                let after =
+                    self.good_interval_with_config(card, proposed.clone(), proposed_fsrs.as_ref())?;
+                preview.review_cards += 1;
+                match after.cmp(&before) {
+                    Ordering::Less => {
+                        preview.shorter += 1;
+                        preview.days_shorter += (before - after) as u64;
+                    }
+                    Ordering::Greater => {
+                        preview.longer += 1;
+                        preview.days_longer += (after - before) as u64;
+                    }
+                    Ordering::Equal => preview.unchanged += 1,
+                }
+            }
+        }
+        Ok(preview)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::card::CardQueue;
+    use crate::card::CardType;
+    use crate::tests::NoteAdder;
+
+    #[test]
+    fn interval_change_preview() -> Result<()> {
+        let mut col = Collection::new();
+        for interval in [10, 100, 1000] {
+            let note = NoteAdder::basic(&mut col).add(&mut col);
+            let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+            card.ctype = CardType::Review;
+            card.queue = CardQueue::Review;
+            card.interval = interval;
+            card.due = col.timing_today()?.days_elapsed as i32;
+            card.ease_factor = 2500;
+            col.storage.update_card(&card)?;
+        }
+        // a new card is ignored
+        NoteAdder::basic(&mut col).add(&mut col);
+
+        let change = IntervalSettingsChange {
+            maximum_review_interval: Some(100),
+            ..Default::default()
+        };
+        let preview = col.preview_deck_config_intervals_change(DeckConfigId(1), change)?;
+        assert_eq!(preview.review_cards, 3);
+        assert_eq!((preview.shorter, preview.longer), (2, 0));
+        assert_eq!(preview.unchanged, 1);
+        assert!(preview.days_shorter > 2000);
+        // nothing was saved
+        let config = col.get_deck_config(DeckConfigId(1), false)?.unwrap();
+        assert_eq!(config.inner.maximum_review_interval, 36_500);
+
+        let preview = col.preview_deck_config_intervals_change(
+            DeckConfigId(1),
+            IntervalSettingsChange {
+                interval_multiplier: Some(2.0),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(preview.longer, 3);
+
+        let config = col
+            .update_deck_config_intervals(DeckConfigId(1), change)?
+            .output;
+        assert_eq!(config.inner.maximum_review_interval, 100);
+        assert!(col
+            .update_deck_config_intervals(
+                DeckConfigId(1),
+                IntervalSettingsChange {
+                    interval_multiplier: Some(5.0),
+                    ..Default::default()
+                },
+            )
+            .is_err());
+        Ok(())
+    }
+}
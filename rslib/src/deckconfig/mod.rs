@@ -1,6 +1,7 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+mod intervals;
 mod schema11;
 mod service;
 pub(crate) mod undo;
@@ -15,6 +16,8 @@ pub use anki_proto::deck_config::deck_config::config::QuestionAction;
 pub use anki_proto::deck_config::deck_config::config::ReviewCardOrder;
 pub use anki_proto::deck_config::deck_config::config::ReviewMix;
 pub use anki_proto::deck_config::deck_config::Config as DeckConfigInner;
+pub use intervals::IntervalChangePreview;
+pub use intervals::IntervalSettingsChange;
 pub use schema11::DeckConfSchema11;
 pub use schema11::NewCardOrderSchema11;
 pub use update::UpdateDeckConfigsRequest;
@@ -205,6 +208,35 @@ impl Collection {
         self.update_deck_config_undoable(config, original)
     }
 
+    /// Normal decks that use the given preset, in name order.
+    pub fn decks_using_deck_config(&self, dcid: DeckConfigId) -> Result<Vec<Deck>> {
+        let mut decks: Vec<_> = self
+            .storage
+            .get_all_decks()?
+            .into_iter()
+            .filter(|deck| deck.config_id() == Some(dcid))
+            .collect();
+        decks.sort_unstable_by(|a, b| a.name.as_native_str().cmp(b.name.as_native_str()));
+        Ok(decks)
+    }
+
+    /// Remove a preset, and assign the decks that used it to the default
+    /// preset. Returns the reassigned decks. This will force a full sync.
+    pub fn remove_deck_config(&mut self, dcid: DeckConfigId) -> Result<OpOutput<Vec<DeckId>>> {
+        self.transact(Op::UpdateDeckConfig, |col| {
+            col.remove_deck_config_inner(dcid)?;
+            let usn = col.usn()?;
+            let mut reassigned = vec![];
+            for original in col.decks_using_deck_config(dcid)? {
+                let mut deck = original.clone();
+                deck.normal_mut()?.config_id = 1;
+                col.update_deck_inner(&mut deck, original, usn)?;
+                reassigned.push(deck.id);
+            }
+            Ok(reassigned)
+        })
+    }
+
     /// Remove a deck configuration. This will force a full sync.
     pub(crate) fn remove_deck_config_inner(&mut self, dcid: DeckConfigId) -> Result<()> {
         require!(dcid.0 != 1, "can't delete default conf");
@@ -442,6 +442,36 @@ mod test {
     use crate::tests::open_test_collection_with_learning_card;
     use crate::tests::open_test_collection_with_relearning_card;
 
+    #[test]
+    fn removing_reassigns_decks() -> Result<()> {
+        let mut col = Collection::new();
+        let mut conf = DeckConfig::default();
+        col.add_or_update_deck_config(&mut conf)?;
+        let mut deck = DeckAdder::new("a").add(&mut col);
+        deck.normal_mut()?.config_id = conf.id.0;
+        col.add_or_update_deck(&mut deck)?;
+        assert_eq!(
+            col.decks_using_deck_config(conf.id)?
+                .iter()
+                .map(|d| d.id)
+                .collect::<Vec<_>>(),
+            [deck.id]
+        );
+
+        assert_eq!(col.remove_deck_config(conf.id)?.output, [deck.id]);
+        let deck = col.get_deck(deck.id)?.unwrap();
+        assert_eq!(deck.config_id(), Some(DeckConfigId(1)));
+        assert_eq!(deck.usn, Usn(-1));
+
+        col.undo()?;
+        let deck = col.get_deck(deck.id)?.unwrap();
+        assert_eq!(deck.config_id(), Some(conf.id));
+        assert!(col.storage.get_deck_config(conf.id)?.is_some());
+
+        assert!(col.remove_deck_config(DeckConfigId(1)).is_err());
+        Ok(())
+    }
+
     #[test]
     fn updating() -> Result<()> {
         let mut col = Collection::new();
@@ -50,6 +50,51 @@ pub fn limit_if_today(limit: DayLimit, today: u32) -> Option<u32> {
     (limit.today == today).then_some(limit.limit)
 }
 
+impl Collection {
+    /// Stop or resume introducing new cards from a normal deck, without
+    /// touching its limits or preset. Reviews are unaffected. If
+    /// `include_children` is set, normal subdecks are changed as well. Returns
+    /// the number of decks that were changed.
+    pub fn set_deck_new_paused(
+        &mut self,
+        deck_id: DeckId,
+        paused: bool,
+        include_children: bool,
+    ) -> Result<OpOutput<usize>> {
+        self.transact(Op::UpdateDeck, |col| {
+            let deck = col.storage.get_deck(deck_id)?.or_not_found(deck_id)?;
+            deck.normal()?;
+            let decks = if include_children {
+                col.storage.deck_with_children(deck_id)?
+            } else {
+                vec![deck]
+            };
+            let usn = col.usn()?;
+            let mut changed = 0;
+            for original in decks {
+                let mut deck = original.clone();
+                if let DeckKind::Normal(normal) = &mut deck.kind {
+                    if normal.new_paused != paused {
+                        normal.new_paused = paused;
+                        col.update_deck_inner(&mut deck, original, usn)?;
+                        changed += 1;
+                    }
+                }
+            }
+            Ok(changed)
+        })
+    }
+
+    /// True if new cards are paused in the deck or one of its subdecks.
+    pub(crate) fn new_paused_in_deck_tree(&self, deck_id: DeckId) -> Result<bool> {
+        Ok(self
+            .storage
+            .deck_with_children(deck_id)?
+            .iter()
+            .any(|deck| matches!(&deck.kind, DeckKind::Normal(normal) if normal.new_paused)))
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(crate) struct RemainingLimits {
     pub(crate) review: u32,
@@ -108,6 +153,10 @@ impl RemainingLimits {
             new_limit = new_limit.min(review_limit);
         }
 
+        if normal.new_paused {
+            new_limit = 0;
+        }
+
         Self {
             review: review_limit.max(0) as u32,
             new: new_limit.max(0) as u32,
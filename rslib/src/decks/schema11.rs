@@ -135,6 +135,12 @@ pub struct NormalDeckSchema11 {
     review_limit_today: Option<DayLimit>,
     #[serde(default, deserialize_with = "default_on_invalid")]
     new_limit_today: Option<DayLimit>,
+    #[serde(
+        default,
+        deserialize_with = "default_on_invalid",
+        skip_serializing_if = "is_false"
+    )]
+    new_paused: bool,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -249,6 +255,7 @@ impl Default for NormalDeckSchema11 {
             new_limit: None,
             review_limit_today: None,
             new_limit_today: None,
+            new_paused: false,
         }
     }
 }
@@ -325,6 +332,7 @@ impl From<NormalDeckSchema11> for NormalDeck {
             new_limit: deck.new_limit,
             review_limit_today: deck.review_limit_today,
             new_limit_today: deck.new_limit_today,
+            new_paused: deck.new_paused,
         }
     }
 }
@@ -366,6 +374,7 @@ impl From<Deck> for DeckSchema11 {
                 new_limit: norm.new_limit,
                 review_limit_today: norm.review_limit_today,
                 new_limit_today: norm.new_limit_today,
+                new_paused: norm.new_paused,
                 common: deck.into(),
             }),
             DeckKind::Filtered(ref filt) => DeckSchema11::Filtered(FilteredDeckSchema11 {
@@ -423,6 +432,7 @@ static RESERVED_DECK_KEYS: Set<&'static str> = phf_set! {
     "extendNew",
     "mod",
     "newLimitToday",
+    "newPaused",
     "desc",
     "name",
     "lrnToday",
@@ -13,6 +13,15 @@ pub(crate) enum UndoableDeckChange {
     GraveRemoved(Box<(DeckId, Usn)>),
 }
 
+impl UndoableDeckChange {
+    pub(crate) fn deck_id(&self) -> DeckId {
+        match self {
+            Self::Added(deck) | Self::Updated(deck) | Self::Removed(deck) => deck.id,
+            Self::GraveAdded(grave) | Self::GraveRemoved(grave) => grave.0,
+        }
+    }
+}
+
 impl Collection {
     pub(crate) fn undo_deck_change(&mut self, change: UndoableDeckChange) -> Result<()> {
         match change {
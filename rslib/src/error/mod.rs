@@ -122,6 +122,7 @@ pub enum AnkiError {
     },
     FsrsUnableToDetermineDesiredRetention,
     SchedulerUpgradeRequired,
+    SchemaChangeNotAllowed,
     InvalidCertificateFormat,
 }
 
@@ -166,6 +167,7 @@ impl AnkiError {
                 "fsrs_unable_to_determine_desired_retention"
             }
             AnkiError::SchedulerUpgradeRequired => "scheduler_upgrade_required",
+            AnkiError::SchemaChangeNotAllowed => "schema_change_not_allowed",
             AnkiError::InvalidCertificateFormat => "invalid_certificate_format",
         }
     }
@@ -215,6 +217,7 @@ impl AnkiError {
             | AnkiError::InvalidServiceIndex
             | AnkiError::InvalidMethodIndex
             | AnkiError::UndoEmpty
+            | AnkiError::SchemaChangeNotAllowed
             | AnkiError::InvalidCertificateFormat => format!("{self:?}"),
             AnkiError::FileIoError { source } => source.message(),
             AnkiError::InvalidInput { source } => source.message(),
@@ -246,6 +249,12 @@ impl AnkiError {
                 CardTypeErrorDetails::NoFrontField => HelpPage::CardTypeNoFrontField,
                 CardTypeErrorDetails::MissingCloze => HelpPage::CardTypeMissingCloze,
             }),
+            Self::SearchError { .. } => Some(HelpPage::SearchSyntax),
+            Self::ImportError { source } => Some(match source {
+                ImportError::NoFieldColumn | ImportError::EmptyFile => HelpPage::ImportCsv,
+                _ => HelpPage::Importing,
+            }),
+            Self::FilteredDeckError { .. } => Some(HelpPage::FilteredDeck),
             _ => None,
         }
     }
@@ -32,12 +32,17 @@ pub(super) struct ExchangeData {
 }
 
 impl ExchangeData {
+    /// If `to_home_decks`, cards in filtered decks are returned to their
+    /// original decks and due dates, and filtered decks are not gathered. This
+    /// is what recipients of an export want, as they have no use for a
+    /// filtered deck built from someone else's search.
     pub(super) fn gather_data(
         &mut self,
         col: &mut Collection,
         search: impl TryIntoSearch,
         with_scheduling: bool,
         with_deck_configs: bool,
+        to_home_decks: bool,
     ) -> Result<()> {
         self.days_elapsed = col.timing_today()?.days_elapsed;
         self.creation_utc_offset = col.get_creation_utc_offset();
@@ -45,8 +50,13 @@ impl ExchangeData {
         self.notes = notes;
         let (cards, guard) = guard.col.gather_cards()?;
         self.cards = cards;
-        self.decks = guard.col.gather_decks(with_scheduling, !with_scheduling)?;
+        self.decks = guard
+            .col
+            .gather_decks(with_scheduling || to_home_decks, !with_scheduling)?;
         self.notetypes = guard.col.gather_notetypes()?;
+        if to_home_decks {
+            self.return_cards_to_home_decks();
+        }
 
         let allow_filtered = self.enables_filtered_decks();
 
@@ -125,6 +135,7 @@ impl ExchangeData {
                         normal.review_limit_today = None;
                         normal.new_limit = None;
                         normal.new_limit_today = None;
+                        normal.new_paused = false;
                     }
                 }
                 DeckKind::Filtered(_) if reset_study_info || !allow_filtered => {
@@ -179,6 +190,14 @@ impl ExchangeData {
         }
     }
 
+    fn return_cards_to_home_decks(&mut self) {
+        for card in self.cards.iter_mut() {
+            card.remove_from_filtered_deck_restoring_queue();
+        }
+        // filtered decks can't have children, so no other deck depends on them
+        self.decks.retain(|deck| !deck.is_filtered());
+    }
+
     fn restore_cards_from_filtered_decks(&mut self) {
         for card in self.cards.iter_mut() {
             if card.is_filtered() {
@@ -329,7 +348,7 @@ mod test {
         let mut col = Collection::new();
 
         let note = NoteAdder::basic(&mut col).add(&mut col);
-        data.gather_data(&mut col, SearchNode::WholeCollection, true, true)
+        data.gather_data(&mut col, SearchNode::WholeCollection, true, true, false)
             .unwrap();
 
         assert_eq!(data.notes, [note]);
@@ -346,7 +365,7 @@ mod test {
         col.add_note_only_with_id_undoable(&mut note).unwrap();
 
         assert!(data
-            .gather_data(&mut col, SearchNode::WholeCollection, true, true)
+            .gather_data(&mut col, SearchNode::WholeCollection, true, true, false)
             .is_err());
     }
 }
@@ -3,12 +3,17 @@
 
 mod gather;
 mod insert;
+mod note_outcome;
 pub mod package;
+pub mod scheduling;
 mod service;
 pub mod text;
 
 pub use anki_proto::import_export::import_response::Log as NoteLog;
 pub use anki_proto::import_export::import_response::Note as LogNote;
+pub use note_outcome::ImportDecision;
+pub use note_outcome::NoteOutcome;
+pub use note_outcome::NoteOutcomeKind;
 use snafu::Snafu;
 
 use crate::prelude::*;
@@ -42,6 +47,7 @@ impl Note {
     pub(crate) fn into_log_note(self) -> LogNote {
         LogNote {
             id: Some(anki_proto::notes::NoteId { nid: self.id.0 }),
+            guid: self.guid.clone(),
             fields: self
                 .into_fields()
                 .into_iter()
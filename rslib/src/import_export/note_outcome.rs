@@ -0,0 +1,152 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use anki_proto::import_export::csv_metadata::DupeResolution;
+
+use super::LogNote;
+use super::NoteLog;
+use crate::prelude::*;
+
+/// The log list a note was recorded in. The desktop's import log strings are
+/// chosen by this kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteOutcomeKind {
+    New,
+    Updated,
+    Duplicate,
+    Conflicting,
+    FirstFieldMatch,
+    MissingNotetype,
+    MissingDeck,
+    EmptyFirstField,
+}
+
+/// What happened to an incoming note.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportDecision {
+    /// The note was added as a new note.
+    AddedNew,
+    /// An existing note was overwritten with the incoming one.
+    TookRemote,
+    /// An existing note was left unchanged.
+    KeptLocal,
+    /// The note could not be imported.
+    Skipped,
+}
+
+/// A single note from an import log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteOutcome {
+    pub guid: String,
+    /// The note in the target collection, if there is one.
+    pub note_id: Option<NoteId>,
+    /// The note's first field, stripped of HTML and shortened.
+    pub preview: String,
+    pub kind: NoteOutcomeKind,
+    pub decision: ImportDecision,
+}
+
+impl NoteOutcome {
+    /// All notes of the log, in the order of its lists.
+    pub fn from_log(log: &NoteLog) -> Vec<NoteOutcome> {
+        let first_field_match_decision = match log.dupe_resolution() {
+            DupeResolution::Update => ImportDecision::TookRemote,
+            DupeResolution::Preserve => ImportDecision::KeptLocal,
+            DupeResolution::Duplicate => ImportDecision::AddedNew,
+        };
+        [
+            (&log.new, NoteOutcomeKind::New, ImportDecision::AddedNew),
+            (
+                &log.updated,
+                NoteOutcomeKind::Updated,
+                ImportDecision::TookRemote,
+            ),
+            (
+                &log.duplicate,
+                NoteOutcomeKind::Duplicate,
+                ImportDecision::KeptLocal,
+            ),
+            (
+                &log.conflicting,
+                NoteOutcomeKind::Conflicting,
+                ImportDecision::Skipped,
+            ),
+            (
+                &log.first_field_match,
+                NoteOutcomeKind::FirstFieldMatch,
+                first_field_match_decision,
+            ),
+            (
+                &log.missing_notetype,
+                NoteOutcomeKind::MissingNotetype,
+                ImportDecision::Skipped,
+            ),
+            (
+                &log.missing_deck,
+                NoteOutcomeKind::MissingDeck,
+                ImportDecision::Skipped,
+            ),
+            (
+                &log.empty_first_field,
+                NoteOutcomeKind::EmptyFirstField,
+                ImportDecision::Skipped,
+            ),
+        ]
+        .into_iter()
+        .flat_map(|(notes, kind, decision)| {
+            notes
+                .iter()
+                .map(move |note| NoteOutcome::new(note, kind, decision))
+        })
+        .collect()
+    }
+
+    fn new(note: &LogNote, kind: NoteOutcomeKind, decision: ImportDecision) -> Self {
+        NoteOutcome {
+            guid: note.guid.clone(),
+            note_id: note.id.map(|id| NoteId(id.nid)),
+            preview: note.fields.first().cloned().unwrap_or_default(),
+            kind,
+            decision,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn outcomes_from_log() {
+        let note = |guid: &str| LogNote {
+            id: Some(anki_proto::notes::NoteId { nid: 1 }),
+            fields: vec!["front".into(), "back".into()],
+            guid: guid.into(),
+        };
+        let log = NoteLog {
+            new: vec![note("a")],
+            duplicate: vec![note("b")],
+            first_field_match: vec![note("c")],
+            dupe_resolution: DupeResolution::Preserve as i32,
+            ..Default::default()
+        };
+        let outcomes = NoteOutcome::from_log(&log);
+        assert_eq!(
+            outcomes
+                .iter()
+                .map(|o| (o.guid.as_str(), o.kind, o.decision))
+                .collect::<Vec<_>>(),
+            [
+                ("a", NoteOutcomeKind::New, ImportDecision::AddedNew),
+                ("b", NoteOutcomeKind::Duplicate, ImportDecision::KeptLocal),
+                (
+                    "c",
+                    NoteOutcomeKind::FirstFieldMatch,
+                    ImportDecision::KeptLocal
+                ),
+            ]
+        );
+        assert_eq!(outcomes[0].preview, "front");
+        assert_eq!(outcomes[0].note_id, Some(NoteId(1)));
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::path::Path;
+
+use anki_io::new_tempfile;
+use anki_io::remove_dir_all;
+use anki_io::remove_file;
+
+use crate::collection::CollectionBuilder;
+use crate::import_export::package::ExportAnkiPackageOptions;
+use crate::import_export::package::ImportAnkiPackageOptions;
+use crate::prelude::*;
+use crate::search::SearchNode;
+
+impl Collection {
+    /// Copy a deck and its children into a new collection at `dest_path`, so
+    /// it can be practised without affecting this collection. The deck is
+    /// exported as an .apkg and imported into the new collection, which is
+    /// closed afterwards. `dest_path` must not exist, and must be outside this
+    /// collection's folder. If the copy fails, the partly written collection
+    /// and its media are removed.
+    pub fn copy_deck_to_new_collection(
+        &mut self,
+        deck_id: DeckId,
+        dest_path: &Path,
+        include_scheduling: bool,
+    ) -> Result<()> {
+        self.get_deck(deck_id)?.or_not_found(deck_id)?;
+        let dest_media_folder = dest_path.with_extension("media");
+        let dest_media_db = dest_path.with_extension("mdb");
+        require!(
+            [dest_path, &dest_media_folder, &dest_media_db]
+                .iter()
+                .all(|path| !path.exists()),
+            "destination already exists"
+        );
+        let dest_folder = dest_path
+            .parent()
+            .filter(|folder| folder.is_dir())
+            .or_invalid("destination folder does not exist")?
+            .canonicalize()?;
+        if let Some(col_folder) = self.col_path.parent().filter(|folder| folder.is_dir()) {
+            require!(
+                !dest_folder.starts_with(col_folder.canonicalize()?),
+                "destination must be outside the collection folder"
+            );
+        }
+
+        let apkg = new_tempfile()?;
+        self.export_apkg(
+            apkg.path(),
+            ExportAnkiPackageOptions {
+                with_scheduling: include_scheduling,
+                with_deck_configs: include_scheduling,
+                with_media: true,
+                legacy: false,
+            },
+            SearchNode::from_deck_id(deck_id, true),
+            None,
+        )?;
+
+        let result =
+            import_into_new_collection(apkg.path(), dest_path, self.tr.clone(), include_scheduling);
+        if result.is_err() {
+            // best effort; the original error is more useful than any from
+            // the cleanup
+            let _ = remove_file(dest_path);
+            let _ = remove_dir_all(&dest_media_folder);
+            let _ = remove_file(&dest_media_db);
+        }
+        result
+    }
+}
+
+fn import_into_new_collection(
+    apkg: &Path,
+    dest_path: &Path,
+    tr: I18n,
+    include_scheduling: bool,
+) -> Result<()> {
+    let mut dest = CollectionBuilder::new(dest_path)
+        .with_desktop_media_paths()
+        .set_tr(tr)
+        .build()?;
+    dest.import_apkg(
+        apkg,
+        ImportAnkiPackageOptions {
+            with_scheduling: include_scheduling,
+            with_deck_configs: include_scheduling,
+            ..Default::default()
+        },
+    )?;
+    dest.close(None)
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::card::CardQueue;
+
+    #[test]
+    fn copy_deck() -> Result<()> {
+        let (mut col, col_dir) = crate::tests::open_fs_test_collection("col");
+        let deck = DeckAdder::new("practice").add(&mut col);
+        NoteAdder::basic(&mut col)
+            .fields(&["front", "back"])
+            .deck(deck.id)
+            .add(&mut col);
+        NoteAdder::basic(&mut col).add(&mut col);
+        let cid = col.storage.get_all_cards()[0].id;
+        col.set_due_date(&[cid], "5", None)?;
+
+        // destinations in the collection folder are rejected
+        let inside = col_dir.path().join("copy.anki2");
+        assert!(col
+            .copy_deck_to_new_collection(deck.id, &inside, false)
+            .is_err());
+
+        let dest_dir = tempdir()?;
+        let dest_path = dest_dir.path().join("copy.anki2");
+        col.copy_deck_to_new_collection(deck.id, &dest_path, false)?;
+        let copy = CollectionBuilder::new(&dest_path).build()?;
+        let cards = copy.storage.get_all_cards();
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[0].queue, CardQueue::New);
+        assert!(copy.get_deck_id("practice")?.is_some());
+
+        // the destination must not exist
+        assert!(col
+            .copy_deck_to_new_collection(deck.id, &dest_path, true)
+            .is_err());
+        Ok(())
+    }
+}
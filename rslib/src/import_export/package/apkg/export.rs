@@ -80,6 +80,7 @@ impl Collection {
             search,
             options.with_scheduling,
             options.with_deck_configs,
+            true,
         )?;
         if options.with_media {
             data.gather_media_names(progress)?;
@@ -39,10 +39,14 @@ impl Context<'_> {
             return Ok(MediaUseMap::default());
         }
 
-        let db_progress_fn = self.progress.media_db_fn(ImportProgress::MediaCheck)?;
-        let existing_sha1s = self
-            .media_manager
-            .all_checksums_after_checking(db_progress_fn)?;
+        let existing_sha1s = match self.existing_media.take() {
+            Some(existing_sha1s) => existing_sha1s,
+            None => {
+                let db_progress_fn = self.progress.media_db_fn(ImportProgress::MediaCheck)?;
+                self.media_manager
+                    .all_checksums_after_checking(db_progress_fn)?
+            }
+        };
 
         prepare_media(
             media_entries,
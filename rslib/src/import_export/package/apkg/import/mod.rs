@@ -6,6 +6,7 @@ mod decks;
 mod media;
 mod notes;
 
+use std::collections::HashMap;
 use std::fs::File;
 use std::path::Path;
 
@@ -41,6 +42,9 @@ struct Context<'a> {
     update_notes: UpdateCondition,
     update_notetypes: UpdateCondition,
     media_manager: MediaManager,
+    /// If set, used instead of the target's media folder to find the files
+    /// that package media could clash with.
+    existing_media: Option<HashMap<String, Sha1Hash>>,
     archive: ZipArchive<File>,
     meta: Meta,
     data: ExchangeData,
@@ -53,6 +57,31 @@ impl Collection {
         &mut self,
         path: impl AsRef<Path>,
         options: ImportAnkiPackageOptions,
+    ) -> Result<OpOutput<NoteLog>> {
+        self.import_apkg_inner(path.as_ref(), options, None)
+    }
+
+    /// Like [Collection::import_apkg], for a collection whose media is kept
+    /// somewhere other than its media folder, such as the sync server's media
+    /// store. `existing_media` holds the checksum of each file already
+    /// there. Package media whose name is taken by a different file is given
+    /// a hash suffix, and the imported notes refer to the new name, as they
+    /// would on the desktop. Media is written to the collection's media
+    /// folder, for the caller to move into place.
+    pub(crate) fn import_apkg_with_existing_media(
+        &mut self,
+        path: &Path,
+        options: ImportAnkiPackageOptions,
+        existing_media: HashMap<String, Sha1Hash>,
+    ) -> Result<OpOutput<NoteLog>> {
+        self.import_apkg_inner(path, options, Some(existing_media))
+    }
+
+    fn import_apkg_inner(
+        &mut self,
+        path: &Path,
+        options: ImportAnkiPackageOptions,
+        existing_media: Option<HashMap<String, Sha1Hash>>,
     ) -> Result<OpOutput<NoteLog>> {
         let file = open_file(path)?;
         let archive = ZipArchive::new(file)?;
@@ -64,7 +93,7 @@ impl Collection {
             col.set_config(BoolKey::WithDeckConfigs, &options.with_deck_configs)?;
             col.set_config(ConfigKey::UpdateNotes, &options.update_notes())?;
             col.set_config(ConfigKey::UpdateNotetypes, &options.update_notetypes())?;
-            let mut ctx = Context::new(archive, col, options, progress)?;
+            let mut ctx = Context::new(archive, col, options, existing_media, progress)?;
             ctx.import()
         })
     }
@@ -75,6 +104,7 @@ impl<'a> Context<'a> {
         mut archive: ZipArchive<File>,
         target_col: &'a mut Collection,
         options: ImportAnkiPackageOptions,
+        existing_media: Option<HashMap<String, Sha1Hash>>,
         mut progress: ThrottlingProgressHandler<ImportProgress>,
     ) -> Result<Self> {
         let media_manager = target_col.media()?;
@@ -94,6 +124,7 @@ impl<'a> Context<'a> {
             update_notes: options.update_notes(),
             update_notetypes: options.update_notetypes(),
             media_manager,
+            existing_media,
             archive,
             meta,
             data,
@@ -139,7 +170,7 @@ impl ExchangeData {
 
         progress.set(ImportProgress::Gathering)?;
         let mut data = ExchangeData::default();
-        data.gather_data(&mut col, search, with_scheduling, with_deck_configs)?;
+        data.gather_data(&mut col, search, with_scheduling, with_deck_configs, false)?;
 
         Ok(data)
     }
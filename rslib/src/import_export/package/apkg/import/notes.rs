@@ -223,7 +223,13 @@ impl<'n> NoteContext<'n> {
             notetype.usn = self.usn;
         }
         self.target_col
-            .add_or_update_notetype_with_existing_id_inner(notetype, Some(original), self.usn, true)
+            .add_or_update_notetype_with_existing_id_inner(
+                notetype,
+                Some(original),
+                self.usn,
+                true,
+            )?;
+        Ok(())
     }
 
     fn update_or_merge_notetype(
@@ -10,11 +10,14 @@ use std::io::Write;
 use anki_io::read_file;
 use anki_proto::import_export::ImportAnkiPackageOptions;
 
+use crate::card::CardQueue;
+use crate::card::CardType;
 use crate::import_export::package::ExportAnkiPackageOptions;
 use crate::media::files::sha1_of_data;
 use crate::media::MediaManager;
 use crate::prelude::*;
 use crate::search::SearchNode;
+use crate::search::SortMode;
 use crate::tests::open_fs_test_collection;
 
 const SAMPLE_JPG: &str = "sample.jpg";
@@ -67,6 +70,73 @@ fn roundtrip_inner(legacy: bool) {
     target_col.assert_empty();
 }
 
+#[test]
+fn filtered_cards_are_exported_in_their_home_deck() {
+    for with_scheduling in [true, false] {
+        let (mut src_col, src_tempdir) = open_fs_test_collection("src");
+        let (mut target_col, _target_tempdir) = open_fs_test_collection("target");
+        let apkg_path = src_tempdir.path().join("test.apkg");
+
+        let home = DeckAdder::new("home").add(&mut src_col);
+        let note = NoteAdder::basic(&mut src_col)
+            .deck(home.id)
+            .add(&mut src_col);
+        let cid = src_col
+            .search_cards(SearchNode::from_note_ids([note.id]), SortMode::NoOrder)
+            .unwrap()[0];
+        src_col.set_due_date(&[cid], "5", None).unwrap();
+        let home_due = src_col.storage.get_card(cid).unwrap().unwrap().due;
+        src_col
+            .create_filtered_deck_from_search("filtered", "deck:home", 10, 0)
+            .unwrap();
+        assert!(src_col
+            .storage
+            .get_card(cid)
+            .unwrap()
+            .unwrap()
+            .is_filtered());
+
+        src_col
+            .export_apkg(
+                &apkg_path,
+                ExportAnkiPackageOptions {
+                    with_scheduling,
+                    with_deck_configs: false,
+                    with_media: false,
+                    legacy: false,
+                },
+                SearchNode::from_card_ids([cid]),
+                None,
+            )
+            .unwrap();
+        target_col
+            .import_apkg(
+                &apkg_path,
+                ImportAnkiPackageOptions {
+                    with_scheduling,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let card = target_col.storage.get_card(cid).unwrap().unwrap();
+        let deck = target_col.get_deck(card.deck_id).unwrap().unwrap();
+        assert_eq!(deck.human_name(), "home");
+        assert!(!deck.is_filtered());
+        assert_eq!(target_col.get_deck_id("filtered").unwrap(), None);
+        assert!(!card.is_filtered());
+        assert_eq!(card.original_due, 0);
+        if with_scheduling {
+            assert_eq!(card.ctype, CardType::Review);
+            assert_eq!(card.queue, CardQueue::Review);
+            assert_eq!(card.due, home_due);
+        } else {
+            assert_eq!(card.ctype, CardType::New);
+            assert_eq!(card.queue, CardQueue::New);
+        }
+    }
+}
+
 impl Collection {
     fn add_sample_decks(&mut self) -> (Deck, Deck) {
         let sample = self.add_named_deck("parent\x1fsample");
@@ -79,7 +79,9 @@ impl Collection {
     }
 }
 
-fn export_collection_file(
+/// Write a .colpkg from a collection file that is not open, eg a snapshot of a
+/// collection that was closed with the desired schema version.
+pub(crate) fn export_collection_file(
     out_path: impl AsRef<Path>,
     col_path: impl AsRef<Path>,
     media_dir: Option<PathBuf>,
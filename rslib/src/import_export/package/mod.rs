@@ -12,6 +12,7 @@ pub use anki_proto::import_export::ImportAnkiPackageOptions;
 pub use anki_proto::import_export::ImportAnkiPackageUpdateCondition as UpdateCondition;
 use anki_proto::import_export::MediaEntries;
 pub(crate) use apkg::NoteMeta;
+pub(crate) use colpkg::export::export_collection_file;
 pub(crate) use colpkg::export::export_colpkg_from_data;
 pub use colpkg::import::import_colpkg;
 pub use media::MediaIter;
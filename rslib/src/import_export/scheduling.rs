@@ -0,0 +1,340 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Transfer of card scheduling between collections that share the same notes,
+//! without touching note content.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::card::CardQueue;
+use crate::card::CardType;
+use crate::card::FsrsMemoryState;
+use crate::prelude::*;
+use crate::revlog::RevlogEntry;
+use crate::search::SortMode;
+
+/// The scheduling of a single card, identified by its note's guid and its
+/// template ordinal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchedulingRecord {
+    pub guid: String,
+    pub card_ord: u16,
+    pub ctype: CardType,
+    pub queue: CardQueue,
+    /// For cards due on a particular day, the number of days from today.
+    /// Otherwise the position of a new card, or the timestamp of a learning
+    /// step.
+    pub due: i32,
+    pub interval: u32,
+    pub ease_factor: u16,
+    pub reps: u32,
+    pub lapses: u32,
+    pub remaining_steps: u32,
+    pub memory_state: Option<FsrsMemoryState>,
+    pub last_review_time: Option<TimestampSecs>,
+    /// Card ids refer to the source collection.
+    pub revlog: Vec<RevlogEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedulingConflictPolicy {
+    /// Use the imported scheduling if the card was reviewed more recently in
+    /// the source collection.
+    KeepNewer,
+    /// Always use the imported scheduling.
+    PreferImported,
+    /// As [SchedulingConflictPolicy::KeepNewer], and also add any review
+    /// history that is missing locally.
+    MergeRevlog,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SchedulingImportOutput {
+    pub updated: usize,
+    pub unchanged: usize,
+    pub revlog_added: usize,
+    /// (guid, card ordinal) of records without a matching local card.
+    pub missing: Vec<(String, u16)>,
+}
+
+impl Collection {
+    /// The scheduling of the cards matching `search`. Cards in filtered decks
+    /// are exported with the scheduling of their home deck.
+    pub fn export_scheduling(&mut self, search: &str) -> Result<Vec<SchedulingRecord>> {
+        let days_elapsed = self.timing_today()?.days_elapsed as i32;
+        let guids: HashMap<NoteId, String> = self
+            .storage
+            .all_notes_by_guid()?
+            .into_iter()
+            .map(|(guid, nid)| (nid, guid))
+            .collect();
+        self.all_cards_for_search_in_order(search, SortMode::NoOrder)?
+            .into_iter()
+            .map(|mut card| {
+                card.remove_from_filtered_deck_restoring_queue();
+                let mut due = card.due;
+                if card.due_in_days() {
+                    due -= days_elapsed;
+                }
+                Ok(SchedulingRecord {
+                    guid: guids.get(&card.note_id).or_not_found(card.note_id)?.clone(),
+                    card_ord: card.template_idx,
+                    ctype: card.ctype,
+                    queue: card.queue,
+                    due,
+                    interval: card.interval,
+                    ease_factor: card.ease_factor,
+                    reps: card.reps,
+                    lapses: card.lapses,
+                    remaining_steps: card.remaining_steps,
+                    memory_state: card.memory_state,
+                    last_review_time: card.last_review_time,
+                    revlog: self.storage.get_revlog_entries_for_card(card.id)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Apply scheduling exported from another collection to the cards with
+    /// the same guid and ordinal. Cards in filtered decks are returned to
+    /// their home deck. Missing cards are reported, not created.
+    pub fn import_scheduling(
+        &mut self,
+        records: Vec<SchedulingRecord>,
+        policy: SchedulingConflictPolicy,
+    ) -> Result<OpOutput<SchedulingImportOutput>> {
+        for entry in records.iter().flat_map(|record| &record.revlog) {
+            check_imported_revlog_entry(entry)?;
+        }
+        self.transact(Op::Import, |col| {
+            let days_elapsed = col.timing_today()?.days_elapsed as i32;
+            let usn = col.usn()?;
+            let nids = col.storage.all_notes_by_guid()?;
+            let mut output = SchedulingImportOutput::default();
+            for record in records {
+                let Some(original) = nids
+                    .get(&record.guid)
+                    .map(|&nid| col.storage.get_card_by_ordinal(nid, record.card_ord))
+                    .transpose()?
+                    .flatten()
+                else {
+                    output.missing.push((record.guid, record.card_ord));
+                    continue;
+                };
+                let local_revlog = col.storage.get_revlog_entries_for_card(original.id)?;
+                let last_local = local_revlog.iter().map(|entry| entry.id).max();
+                let last_imported = record.revlog.iter().map(|entry| entry.id).max();
+                let use_imported = match policy {
+                    SchedulingConflictPolicy::PreferImported => true,
+                    SchedulingConflictPolicy::KeepNewer | SchedulingConflictPolicy::MergeRevlog => {
+                        last_imported > last_local
+                    }
+                };
+                let mut changed = false;
+                if policy == SchedulingConflictPolicy::MergeRevlog {
+                    let existing: HashSet<_> = local_revlog.iter().map(|entry| entry.id).collect();
+                    for mut entry in record.revlog.iter().cloned() {
+                        if !existing.contains(&entry.id) {
+                            entry.cid = original.id;
+                            entry.usn = usn;
+                            // the id may be taken by another card's review
+                            if col.add_revlog_entry_if_unique_undoable(entry)? {
+                                output.revlog_added += 1;
+                                changed = true;
+                            }
+                        }
+                    }
+                }
+                if use_imported {
+                    let mut card = original.clone();
+                    card.remove_from_filtered_deck_before_reschedule();
+                    record.apply_to(&mut card, days_elapsed);
+                    if card != original {
+                        col.update_card_inner(&mut card, original, usn)?;
+                        changed = true;
+                    }
+                }
+                if changed {
+                    output.updated += 1;
+                } else {
+                    output.unchanged += 1;
+                }
+            }
+            Ok(output)
+        })
+    }
+}
+
+/// The longest interval the scheduler gives, in days.
+const MAX_REVLOG_INTERVAL_DAYS: i32 = 36_500;
+
+/// Reject review history that no scheduler could have produced, as it would
+/// skew statistics and FSRS. Negative intervals are in seconds.
+fn check_imported_revlog_entry(entry: &RevlogEntry) -> Result<()> {
+    require!(entry.id.0 > 0, "revlog id must be a timestamp");
+    require!(
+        entry.button_chosen <= 4,
+        "revlog button must be between 0 and 4"
+    );
+    require!(
+        entry.interval <= MAX_REVLOG_INTERVAL_DAYS
+            && entry.last_interval <= MAX_REVLOG_INTERVAL_DAYS,
+        "revlog intervals must be at most {MAX_REVLOG_INTERVAL_DAYS} days"
+    );
+    require!(
+        entry.ease_factor <= u16::MAX as u32,
+        "revlog ease factor is out of range"
+    );
+    Ok(())
+}
+
+impl SchedulingRecord {
+    fn apply_to(&self, card: &mut Card, days_elapsed: i32) {
+        card.ctype = self.ctype;
+        card.queue = self.queue;
+        card.due = self.due;
+        card.interval = self.interval;
+        card.ease_factor = self.ease_factor;
+        card.reps = self.reps;
+        card.lapses = self.lapses;
+        card.remaining_steps = self.remaining_steps;
+        card.memory_state = self.memory_state;
+        card.last_review_time = self.last_review_time;
+        if card.due_in_days() {
+            card.due += days_elapsed;
+        }
+    }
+}
+
+impl Card {
+    fn due_in_days(&self) -> bool {
+        matches!(self.queue, CardQueue::Review | CardQueue::DayLearn)
+            || self.ctype == CardType::Review
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::revlog::RevlogReviewKind;
+
+    fn review(cid: CardId, id: i64) -> RevlogEntry {
+        RevlogEntry {
+            id: RevlogId(id),
+            cid,
+            button_chosen: 3,
+            review_kind: RevlogReviewKind::Review,
+            ..Default::default()
+        }
+    }
+
+    /// A collection with a single basic note, whose card is in review and
+    /// was last reviewed at `reviewed`.
+    fn col_with_review(guid: &str, interval: u32, reviewed: i64) -> Result<(Collection, CardId)> {
+        let mut col = Collection::new();
+        let mut note = NoteAdder::basic(&mut col).note();
+        note.guid = guid.into();
+        col.add_note(&mut note, DeckId(1))?;
+        let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+        card.ctype = CardType::Review;
+        card.queue = CardQueue::Review;
+        card.interval = interval;
+        card.due = col.timing_today()?.days_elapsed as i32 + 3;
+        col.storage.update_card(&card)?;
+        col.storage
+            .add_revlog_entry(&review(card.id, reviewed), false)?;
+        Ok((col, card.id))
+    }
+
+    #[test]
+    fn scheduling_is_transferred_by_guid() -> Result<()> {
+        let (mut source, _) = col_with_review("shared", 30, 2_000)?;
+        NoteAdder::basic(&mut source).add(&mut source);
+        let records = source.export_scheduling("")?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].due, 3);
+
+        let (mut target, cid) = col_with_review("shared", 5, 1_000)?;
+        let output = target
+            .import_scheduling(records.clone(), SchedulingConflictPolicy::KeepNewer)?
+            .output;
+        assert_eq!(output.updated, 1);
+        assert_eq!(output.missing.len(), 1);
+        let card = target.storage.get_card(cid)?.unwrap();
+        assert_eq!(card.interval, 30);
+        assert_eq!(card.due, target.timing_today()?.days_elapsed as i32 + 3);
+        // revlog is left alone
+        assert_eq!(target.storage.get_revlog_entries_for_card(cid)?.len(), 1);
+
+        // merging adds the missing history once
+        let output = target
+            .import_scheduling(records.clone(), SchedulingConflictPolicy::MergeRevlog)?
+            .output;
+        assert_eq!((output.updated, output.revlog_added), (1, 1));
+        let output = target
+            .import_scheduling(records, SchedulingConflictPolicy::MergeRevlog)?
+            .output;
+        assert_eq!((output.updated, output.unchanged), (0, 1));
+        assert_eq!(target.storage.get_revlog_entries_for_card(cid)?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn older_scheduling_is_kept_unless_preferred() -> Result<()> {
+        let (mut source, _) = col_with_review("shared", 30, 1_000)?;
+        let records = source.export_scheduling("")?;
+        let (mut target, cid) = col_with_review("shared", 5, 2_000)?;
+
+        let output = target
+            .import_scheduling(records.clone(), SchedulingConflictPolicy::KeepNewer)?
+            .output;
+        assert_eq!(output.unchanged, 1);
+        assert_eq!(target.storage.get_card(cid)?.unwrap().interval, 5);
+
+        target.import_scheduling(records, SchedulingConflictPolicy::PreferImported)?;
+        assert_eq!(target.storage.get_card(cid)?.unwrap().interval, 30);
+        Ok(())
+    }
+
+    #[test]
+    fn revlog_ids_used_by_other_cards_are_not_counted() -> Result<()> {
+        let (mut source, source_cid) = col_with_review("shared", 30, 1_000)?;
+        source
+            .storage
+            .add_revlog_entry(&review(source_cid, 2_000), false)?;
+        let records = source.export_scheduling("")?;
+        let (mut target, _) = col_with_review("shared", 5, 1_000)?;
+        // another card already has a review with the same id
+        target
+            .storage
+            .add_revlog_entry(&review(CardId(999), 2_000), false)?;
+
+        let output = target
+            .import_scheduling(records, SchedulingConflictPolicy::MergeRevlog)?
+            .output;
+        assert_eq!(output.revlog_added, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn implausible_revlog_entries_are_rejected() -> Result<()> {
+        let (mut source, _) = col_with_review("shared", 30, 1_000)?;
+        let (mut target, _) = col_with_review("shared", 5, 2_000)?;
+        let records = source.export_scheduling("")?;
+        for corrupt in [
+            |entry: &mut RevlogEntry| entry.button_chosen = 5,
+            |entry: &mut RevlogEntry| entry.interval = 100_000,
+            |entry: &mut RevlogEntry| entry.last_interval = i32::MAX,
+            |entry: &mut RevlogEntry| entry.ease_factor = u32::MAX,
+            |entry: &mut RevlogEntry| entry.id = RevlogId(-1),
+        ] {
+            let mut records = records.clone();
+            corrupt(&mut records[0].revlog[0]);
+            assert!(target
+                .import_scheduling(records, SchedulingConflictPolicy::MergeRevlog)
+                .is_err());
+        }
+        Ok(())
+    }
+}
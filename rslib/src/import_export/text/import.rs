@@ -454,7 +454,8 @@ impl<'a> Context<'a> {
             .entry((notetype.id, deck_id))
             .or_insert_with(|| CardGenContext::new(notetype, Some(deck_id), self.usn));
         self.col
-            .generate_cards_for_existing_note(card_gen_context, note)
+            .generate_cards_for_existing_note(card_gen_context, note)?;
+        Ok(())
     }
 }
 
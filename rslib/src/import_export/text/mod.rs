@@ -96,6 +96,7 @@ impl ForeignNote {
     pub(crate) fn into_log_note(self) -> LogNote {
         LogNote {
             id: None,
+            guid: self.guid,
             fields: self
                 .fields
                 .into_iter()
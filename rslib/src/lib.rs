@@ -7,6 +7,9 @@ pub mod adding;
 pub(crate) mod ankidroid;
 pub mod ankihub;
 pub mod backend;
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench_support;
 pub mod browser_table;
 pub mod card;
 pub mod card_rendering;
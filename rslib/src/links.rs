@@ -39,6 +39,8 @@ pub fn help_page_link_suffix(page: HelpPage) -> &'static str {
         }
         HelpPage::CardTypeMissingCloze => "templates/errors.html#no-cloze-filter-on-cloze-notetype",
         HelpPage::Troubleshooting => "troubleshooting.html",
+        HelpPage::SearchSyntax => "searching.html",
+        HelpPage::ImportCsv => "importing/text-files.html",
     }
 }
 
@@ -3,6 +3,7 @@
 
 pub mod check;
 pub mod files;
+mod orphans;
 mod service;
 
 use std::borrow::Cow;
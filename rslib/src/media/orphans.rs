@@ -0,0 +1,107 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::prelude::*;
+use crate::text::extract_media_refs;
+use crate::text::REMOTE_FILENAME;
+
+impl Collection {
+    /// The local media files referenced by the notes in `nids` that no other
+    /// note references, so that removing the notes would leave them unused.
+    /// Only notes whose fields contain one of the filenames are checked, so
+    /// this is much cheaper than a media check.
+    pub fn media_only_referenced_by(&self, nids: &[NoteId]) -> Result<Vec<String>> {
+        let removed: HashSet<NoteId> = nids.iter().copied().collect();
+        // decoded filename -> the forms it was written in
+        let mut referenced: HashMap<String, HashSet<String>> = HashMap::new();
+        for nid in &removed {
+            let Some(note) = self.storage.get_note(*nid)? else {
+                continue;
+            };
+            for field in note.fields() {
+                for media_ref in extract_media_refs(field) {
+                    if REMOTE_FILENAME.is_match(media_ref.fname)
+                        || media_ref.fname.starts_with("data:")
+                    {
+                        continue;
+                    }
+                    referenced
+                        .entry(media_ref.fname_decoded.into_owned())
+                        .or_default()
+                        .insert(media_ref.fname.to_string());
+                }
+            }
+        }
+
+        let mut orphaned = vec![];
+        for (fname, written_as) in referenced {
+            if !self.media_referenced_by_other_notes(&fname, &written_as, &removed)? {
+                orphaned.push(fname);
+            }
+        }
+        orphaned.sort_unstable();
+        Ok(orphaned)
+    }
+
+    fn media_referenced_by_other_notes(
+        &self,
+        fname: &str,
+        written_as: &HashSet<String>,
+        excluded: &HashSet<NoteId>,
+    ) -> Result<bool> {
+        for text in written_as.iter().map(String::as_str).chain([fname]) {
+            for (nid, fields) in self.storage.notes_with_fields_containing(text)? {
+                if excluded.contains(&nid) {
+                    continue;
+                }
+                if extract_media_refs(&fields)
+                    .iter()
+                    .any(|media_ref| media_ref.fname_decoded == fname)
+                {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shared_media_is_not_orphaned() -> Result<()> {
+        let mut col = Collection::new();
+        let nt = col.get_notetype_by_name("Basic")?.unwrap();
+        let mut add = |front: &str, back: &str| -> Result<NoteId> {
+            let mut note = nt.new_note();
+            note.set_field(0, front)?;
+            note.set_field(1, back)?;
+            col.add_note(&mut note, DeckId(1))?;
+            Ok(note.id)
+        };
+        let deleted = add(
+            r#"<img src="shared.jpg"><img src="only.jpg">"#,
+            "[sound:a&amp;b.mp3] <img src='https://example.com/remote.png'>",
+        )?;
+        let surviving = add("<img src=\"shared.jpg\">", "")?;
+        // mentioning a filename in text is not a reference
+        add("only.jpg", "a&b.mp3")?;
+
+        assert_eq!(
+            col.media_only_referenced_by(&[deleted])?,
+            ["a&b.mp3", "only.jpg"]
+        );
+        // a shared file is orphaned once all the notes using it go
+        assert_eq!(
+            col.media_only_referenced_by(&[deleted, surviving])?,
+            ["a&b.mp3", "only.jpg", "shared.jpg"]
+        );
+        assert!(col.media_only_referenced_by(&[surviving])?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::collections::HashMap;
+
+use difflib::sequencematcher::SequenceMatcher;
+
+use crate::prelude::*;
+use crate::text::html_to_text_line;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldDiffKind {
+    /// No new content was proposed, or it matches the current content.
+    Unchanged,
+    Changed,
+    /// The proposal names a field the notetype doesn't have.
+    UnknownField,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffOp {
+    Equal,
+    Removed,
+    Added,
+}
+
+/// A run of consecutive words with the same [DiffOp].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffSegment {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub name: String,
+    pub kind: FieldDiffKind,
+    /// Empty for unknown fields.
+    pub current: String,
+    /// The proposed content, if any.
+    pub proposed: Option<String>,
+    /// The word-level changes to the field's text, with HTML stripped. Only
+    /// filled in for changed fields, and may consist only of
+    /// [DiffOp::Equal] segments if the change was to formatting alone.
+    pub segments: Vec<DiffSegment>,
+}
+
+impl Collection {
+    /// Compare a note's fields with proposed content keyed by field name,
+    /// without changing anything. Every field of the note is listed in
+    /// notetype order, followed by any unknown fields in name order.
+    pub fn diff_note_fields(
+        &mut self,
+        nid: NoteId,
+        proposed: &HashMap<String, String>,
+    ) -> Result<Vec<FieldDiff>> {
+        let note = self.storage.get_note(nid)?.or_not_found(nid)?;
+        let nt = self
+            .get_notetype(note.notetype_id)?
+            .or_not_found(note.notetype_id)?;
+        let mut diffs: Vec<_> = nt
+            .fields
+            .iter()
+            .zip(note.fields())
+            .map(|(field, current)| {
+                let proposed = proposed.get(&field.name);
+                let changed = proposed.is_some_and(|proposed| proposed != current);
+                FieldDiff {
+                    name: field.name.clone(),
+                    kind: if changed {
+                        FieldDiffKind::Changed
+                    } else {
+                        FieldDiffKind::Unchanged
+                    },
+                    current: current.clone(),
+                    proposed: proposed.cloned(),
+                    segments: match proposed {
+                        Some(proposed) if changed => diff_field_text(current, proposed),
+                        _ => vec![],
+                    },
+                }
+            })
+            .collect();
+        let mut unknown: Vec<_> = proposed
+            .iter()
+            .filter(|(name, _)| nt.get_field_ord(name).is_none())
+            .collect();
+        unknown.sort_unstable();
+        diffs.extend(unknown.into_iter().map(|(name, proposed)| FieldDiff {
+            name: name.clone(),
+            kind: FieldDiffKind::UnknownField,
+            current: String::new(),
+            proposed: Some(proposed.clone()),
+            segments: vec![],
+        }));
+        Ok(diffs)
+    }
+}
+
+/// A word-level diff of the text of two fields, ignoring HTML tags. Media
+/// filenames are kept, so swapping an image shows up as a change.
+pub fn diff_field_text(current: &str, proposed: &str) -> Vec<DiffSegment> {
+    let current_text = html_to_text_line(current, true);
+    let proposed_text = html_to_text_line(proposed, true);
+    let current_words: Vec<_> = current_text.split_whitespace().collect();
+    let proposed_words: Vec<_> = proposed_text.split_whitespace().collect();
+    let mut matcher = SequenceMatcher::new(&current_words, &proposed_words);
+    let mut segments: Vec<DiffSegment> = vec![];
+    let mut push = |op, words: &[&str]| {
+        if words.is_empty() {
+            return;
+        }
+        let text = words.join(" ");
+        match segments.last_mut() {
+            Some(last) if last.op == op => {
+                last.text.push(' ');
+                last.text.push_str(&text);
+            }
+            _ => segments.push(DiffSegment { op, text }),
+        }
+    };
+    for opcode in matcher.get_opcodes() {
+        let removed = &current_words[opcode.first_start..opcode.first_end];
+        let added = &proposed_words[opcode.second_start..opcode.second_end];
+        if opcode.tag == "equal" {
+            push(DiffOp::Equal, removed);
+        } else {
+            push(DiffOp::Removed, removed);
+            push(DiffOp::Added, added);
+        }
+    }
+    segments
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn segments(ops: &[(DiffOp, &str)]) -> Vec<DiffSegment> {
+        ops.iter()
+            .map(|(op, text)| DiffSegment {
+                op: *op,
+                text: text.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn word_diff_ignores_html() {
+        use DiffOp::*;
+        assert_eq!(
+            diff_field_text(
+                "<div>The <b>quick</b> brown&nbsp;fox</div>",
+                "The <i>slow</i> brown fox<br>jumps"
+            ),
+            segments(&[
+                (Equal, "The"),
+                (Removed, "quick"),
+                (Added, "slow"),
+                (Equal, "brown fox"),
+                (Added, "jumps"),
+            ])
+        );
+        // formatting-only changes leave the words alone
+        assert_eq!(
+            diff_field_text("<b>bold</b> text", "<u>bold</u> <span>text</span>"),
+            segments(&[(Equal, "bold text")])
+        );
+        // media references are compared by filename
+        assert_eq!(
+            diff_field_text(r#"cat <img src="cat.jpg">"#, r#"cat <img src="dog.jpg">"#),
+            segments(&[(Equal, "cat"), (Removed, "cat.jpg"), (Added, "dog.jpg")])
+        );
+        assert_eq!(
+            diff_field_text("", "<p>new &amp; text</p>"),
+            segments(&[(Added, "new & text")])
+        );
+    }
+
+    #[test]
+    fn note_field_diff() -> Result<()> {
+        let mut col = Collection::new();
+        let nt = col.get_notetype_by_name("Basic")?.unwrap();
+        let mut note = nt.new_note();
+        note.set_field(0, "<b>front</b> text")?;
+        note.set_field(1, "back")?;
+        col.add_note(&mut note, DeckId(1))?;
+
+        let proposed = HashMap::from([
+            ("Front".to_string(), "<b>front</b> words".to_string()),
+            ("Back".to_string(), "back".to_string()),
+            ("Extra".to_string(), "more".to_string()),
+        ]);
+        let diffs = col.diff_note_fields(note.id, &proposed)?;
+        let kinds: Vec<_> = diffs.iter().map(|diff| (&*diff.name, diff.kind)).collect();
+        assert_eq!(
+            kinds,
+            [
+                ("Front", FieldDiffKind::Changed),
+                ("Back", FieldDiffKind::Unchanged),
+                ("Extra", FieldDiffKind::UnknownField),
+            ]
+        );
+        assert_eq!(
+            diffs[0].segments,
+            segments(&[
+                (DiffOp::Equal, "front"),
+                (DiffOp::Removed, "text"),
+                (DiffOp::Added, "words")
+            ])
+        );
+        assert!(diffs[1].segments.is_empty());
+
+        // fields not mentioned are unchanged, and nothing was written
+        let diffs = col.diff_note_fields(note.id, &HashMap::new())?;
+        assert!(diffs
+            .iter()
+            .all(|diff| diff.kind == FieldDiffKind::Unchanged && diff.proposed.is_none()));
+        assert_eq!(
+            col.storage.get_note(note.id)?.unwrap().fields(),
+            note.fields()
+        );
+        assert!(col.diff_note_fields(NoteId(123), &proposed).is_err());
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+pub mod diff;
 pub(crate) mod service;
 pub(crate) mod undo;
 
@@ -20,11 +21,11 @@ use crate::error::AnkiError;
 use crate::error::OrInvalid;
 use crate::notetype::CardGenContext;
 use crate::notetype::NoteField;
+use crate::ops::ChangedIds;
 use crate::ops::StateChanges;
 use crate::prelude::*;
 use crate::template::field_is_empty;
 use crate::text::ensure_string_in_nfc;
-use crate::text::normalize_to_nfc;
 use crate::text::strip_html_preserving_media_filenames;
 
 define_newtype!(NoteId, i64);
@@ -91,9 +92,31 @@ impl Collection {
         self.transact(Op::AddNote, |col| col.add_note_inner(note, did))
     }
 
+    /// Add a note whose new cards are given `position` instead of the next
+    /// free position. The position counter is only advanced if it would
+    /// otherwise hand out `position` again.
+    pub fn add_note_at_position(
+        &mut self,
+        note: &mut Note,
+        did: DeckId,
+        position: u32,
+    ) -> Result<OpOutput<()>> {
+        self.transact(Op::AddNote, |col| {
+            let next = col.get_next_card_position();
+            col.set_next_card_position(position)?;
+            col.add_note_inner(note, did)?;
+            col.set_next_card_position(next.max(position.saturating_add(1)))
+        })
+    }
+
     pub fn add_notes(&mut self, requests: &mut [AddNoteRequest]) -> Result<OpOutput<()>> {
         self.transact(Op::AddNote, |col| {
+            // a contiguous range of ids is reserved up front, so the notes
+            // don't have to compete for the current timestamp
+            let mut next_id = col.storage.next_note_id()?;
             for request in requests {
+                request.note.id = next_id;
+                next_id.0 += 1;
                 col.add_note_inner(&mut request.note, request.deck_id)?;
             }
 
@@ -101,12 +124,48 @@ impl Collection {
         })
     }
 
+    /// A page of the collection's note ids in ascending order, starting after
+    /// `after`. Unlike a search, this does not need to load every id, so large
+    /// collections can be walked in batches by passing the last id of the
+    /// previous page.
+    pub fn get_all_note_ids_paginated(
+        &mut self,
+        after: Option<NoteId>,
+        limit: u32,
+    ) -> Result<Vec<NoteId>> {
+        self.storage
+            .note_ids_after(after.unwrap_or(NoteId(i64::MIN)), limit)
+    }
+
     /// Remove provided notes, and any cards that use them.
     pub fn remove_notes(&mut self, nids: &[NoteId]) -> Result<OpOutput<usize>> {
         let usn = self.usn()?;
         self.transact(Op::RemoveNote, |col| col.remove_notes_inner(nids, usn))
     }
 
+    /// Remove provided notes and their cards in a single undoable step. Ids
+    /// that don't exist are reported rather than causing an error, and
+    /// duplicate ids are ignored.
+    pub fn bulk_delete_notes(&mut self, nids: Vec<NoteId>) -> Result<OpOutput<BulkDeleteSummary>> {
+        let usn = self.usn()?;
+        self.transact(Op::RemoveNote, |col| {
+            let mut summary = BulkDeleteSummary::default();
+            let mut seen = HashSet::new();
+            for nid in nids {
+                if !seen.insert(nid) {
+                    continue;
+                }
+                if col.storage.get_note(nid)?.is_none() {
+                    summary.not_found.push(nid);
+                    continue;
+                }
+                summary.cards_deleted += col.remove_notes_inner(&[nid], usn)?;
+                summary.notes_deleted += 1;
+            }
+            Ok(summary)
+        })
+    }
+
     /// Update cards and field cache after notes modified externally.
     /// If gencards is false, skip card generation.
     pub fn after_note_updates(
@@ -138,6 +197,13 @@ impl NoteTags {
     }
 }
 
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BulkDeleteSummary {
+    pub notes_deleted: usize,
+    pub cards_deleted: usize,
+    pub not_found: Vec<NoteId>,
+}
+
 impl Note {
     pub fn new(notetype: &Notetype) -> Self {
         Note {
@@ -315,6 +381,18 @@ impl From<anki_proto::notes::Note> for Note {
     }
 }
 
+/// A first field as duplicate checks compare it: with invalid characters
+/// removed, converted to NFC if `normalize_text` is true, and with HTML
+/// stripped apart from media filenames. Returned along with the checksum
+/// that is stored for it in the notes table.
+pub fn normalized_first_field(field: &str, normalize_text: bool) -> (String, u32) {
+    let mut field = field.to_string();
+    normalize_field(&mut field, normalize_text);
+    let stripped = strip_html_preserving_media_filenames(&field).into_owned();
+    let checksum = field_checksum(&stripped);
+    (stripped, checksum)
+}
+
 /// Text must be passed to strip_html_preserving_media_filenames() by
 /// caller prior to passing in here.
 pub(crate) fn field_checksum(text: &str) -> u32 {
@@ -382,7 +460,11 @@ impl Collection {
         self.canonify_note_tags(note, ctx.usn)?;
         note.prepare_for_update(ctx.notetype, normalize_text)?;
         note.set_modified(ctx.usn);
-        self.add_note_only_undoable(note)?;
+        if note.id.0 == 0 {
+            self.add_note_only_undoable(note)?;
+        } else {
+            self.add_note_only_with_id_undoable(note)?;
+        }
         self.generate_cards_for_new_note(&ctx, note, did)?;
         self.set_last_deck_for_notetype(note.notetype_id, did)?;
         self.set_last_notetype_for_deck(did, note.notetype_id)?;
@@ -420,6 +502,7 @@ impl Collection {
                             card: true,
                             ..Default::default()
                         },
+                        ids: ChangedIds::default(),
                     },
                 })
             })
@@ -450,7 +533,7 @@ impl Collection {
         mark_note_modified: bool,
         normalize_text: bool,
         update_tags: bool,
-    ) -> Result<()> {
+    ) -> Result<Vec<CardId>> {
         self.update_note_inner_without_cards(UpdateNoteInnerWithoutCardsArgs {
             note,
             original,
@@ -599,17 +682,13 @@ impl Collection {
             if cloze_state == NoteFieldsState::FieldNotCloze {
                 NoteFieldsState::FieldNotCloze
             } else if let Some(text) = note.fields.first() {
-                let field1 = if self.get_config_bool(BoolKey::NormalizeNoteText) {
-                    normalize_to_nfc(text)
-                } else {
-                    text.into()
-                };
-                let stripped = strip_html_preserving_media_filenames(&field1);
+                let (stripped, checksum) =
+                    normalized_first_field(text, self.get_config_bool(BoolKey::NormalizeNoteText));
                 if stripped.trim().is_empty() {
                     NoteFieldsState::Empty
                 } else if cloze_state != NoteFieldsState::Normal {
                     cloze_state
-                } else if self.is_duplicate(&stripped, note)? {
+                } else if self.is_duplicate(&stripped, checksum, note)? {
                     NoteFieldsState::Duplicate
                 } else {
                     NoteFieldsState::Normal
@@ -620,8 +699,7 @@ impl Collection {
         })
     }
 
-    fn is_duplicate(&self, first_field: &str, note: &Note) -> Result<bool> {
-        let csum = field_checksum(first_field);
+    fn is_duplicate(&self, first_field: &str, csum: u32, note: &Note) -> Result<bool> {
         Ok(self
             .storage
             .note_fields_by_checksum(note.notetype_id, csum)?
@@ -684,6 +762,7 @@ fn note_differs_from_db(existing_note: &mut Note, note: &mut Note) -> bool {
 mod test {
     use super::anki_base91;
     use super::field_checksum;
+    use super::AddNoteRequest;
     use crate::config::BoolKey;
     use crate::decks::DeckId;
     use crate::error::Result;
@@ -705,6 +784,126 @@ mod test {
         assert_eq!(field_checksum("今日"), 1464653051);
     }
 
+    #[test]
+    fn normalized_first_field_matches_duplicate_check() -> Result<()> {
+        let mut col = Collection::new();
+        let nt = col.get_notetype_by_name("Basic")?.unwrap();
+        let text = "<b>\u{fa47}</b><img src=\"dog.jpg\">\u{1f}";
+        let (normalized, checksum) = super::normalized_first_field(text, true);
+        assert_eq!(normalized, "\u{6f22} dog.jpg ");
+        assert_eq!(
+            super::normalized_first_field(text, false).0,
+            "\u{fa47} dog.jpg "
+        );
+
+        let mut note = nt.new_note();
+        note.fields[0] = text.into();
+        col.add_note(&mut note, DeckId(1))?;
+        assert_eq!(note.checksum, Some(checksum));
+        let mut dupe = nt.new_note();
+        dupe.fields[0] = "\u{6f22} dog.jpg ".into();
+        assert_eq!(
+            col.note_fields_check(&dupe)?,
+            super::NoteFieldsState::Duplicate
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn paginated_note_ids() -> Result<()> {
+        let mut col = Collection::new();
+        let mut nids: Vec<_> = (0..5)
+            .map(|_| NoteAdder::basic(&mut col).add(&mut col).id)
+            .collect();
+        nids.sort();
+
+        let mut pages = vec![];
+        let mut after = None;
+        loop {
+            let page = col.get_all_note_ids_paginated(after, 2)?;
+            let Some(&last) = page.last() else {
+                break;
+            };
+            after = Some(last);
+            pages.push(page);
+        }
+        assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 1]);
+        assert_eq!(pages.concat(), nids);
+        Ok(())
+    }
+
+    #[test]
+    fn bulk_delete() -> Result<()> {
+        let mut col = Collection::new();
+        let first = NoteAdder::basic(&mut col).add(&mut col).id;
+        let second = NoteAdder::basic(&mut col).add(&mut col).id;
+        let kept = NoteAdder::basic(&mut col).add(&mut col).id;
+
+        let summary = col
+            .bulk_delete_notes(vec![first, NoteId(123), second, first])?
+            .output;
+        assert_eq!(summary.notes_deleted, 2);
+        assert_eq!(summary.cards_deleted, 2);
+        assert_eq!(summary.not_found, [NoteId(123)]);
+        assert_eq!(col.search_notes_unordered("")?, [kept]);
+
+        // undone as a single step
+        col.undo()?;
+        assert_eq!(col.storage.get_all_note_ids()?.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn bulk_add_ids() -> Result<()> {
+        let mut col = Collection::new();
+        let existing = NoteAdder::basic(&mut col).add(&mut col).id;
+        let nt = col.get_notetype_by_name("Basic")?.unwrap();
+        let mut requests: Vec<_> = (0..1_000)
+            .map(|idx| {
+                let mut note = nt.new_note();
+                note.set_field(0, idx.to_string()).unwrap();
+                AddNoteRequest {
+                    note,
+                    deck_id: DeckId(1),
+                }
+            })
+            .collect();
+
+        col.add_notes(&mut requests)?;
+
+        // notes get a contiguous range after the existing ones
+        assert!(requests[0].note.id > existing);
+        for (idx, request) in requests.iter().enumerate() {
+            assert_eq!(request.note.id.0, requests[0].note.id.0 + idx as i64);
+            assert_eq!(col.storage.all_cards_of_note(request.note.id)?.len(), 1);
+        }
+        assert_eq!(col.storage.get_all_note_ids()?.len(), 1_001);
+        Ok(())
+    }
+
+    #[test]
+    fn adding_at_position() -> Result<()> {
+        let mut col = Collection::new();
+        let first = NoteAdder::basic(&mut col).add(&mut col);
+        let mut note = NoteAdder::basic(&mut col).note();
+        col.add_note_at_position(&mut note, DeckId(1), 10)?;
+        let due = |col: &mut Collection, nid| col.storage.all_cards_of_note(nid).unwrap()[0].due;
+        assert_eq!(due(&mut col, first.id), 1);
+        assert_eq!(due(&mut col, note.id), 10);
+        assert_eq!(col.get_next_card_position(), 11);
+
+        // an earlier position does not move the counter back
+        let mut note = NoteAdder::basic(&mut col).note();
+        col.add_note_at_position(&mut note, DeckId(1), 0)?;
+        assert_eq!(due(&mut col, note.id), 0);
+        assert_eq!(col.get_next_card_position(), 11);
+
+        col.undo()?;
+        col.undo()?;
+        assert_eq!(col.get_next_card_position(), 2);
+        Ok(())
+    }
+
     #[test]
     fn adding_cards() -> Result<()> {
         let mut col = Collection::new();
@@ -16,6 +16,16 @@ pub(crate) enum UndoableNoteChange {
     TagsUpdated(Box<NoteTags>),
 }
 
+impl UndoableNoteChange {
+    pub(crate) fn note_id(&self) -> NoteId {
+        match self {
+            Self::Added(note) | Self::Updated(note) | Self::Removed(note) => note.id,
+            Self::GraveAdded(grave) | Self::GraveRemoved(grave) => grave.0,
+            Self::TagsUpdated(tags) => tags.id,
+        }
+    }
+}
+
 impl Collection {
     pub(crate) fn undo_note_change(&mut self, change: UndoableNoteChange) -> Result<()> {
         match change {
@@ -222,14 +222,16 @@ impl Collection {
             &[],
             Some(target_deck_id),
             &mut Default::default(),
-        )
+        )?;
+        Ok(())
     }
 
+    /// Returns the IDs of any cards that were added.
     pub(crate) fn generate_cards_for_existing_note(
         &mut self,
         ctx: &CardGenContext<impl Deref<Target = Notetype>>,
         note: &Note,
-    ) -> Result<()> {
+    ) -> Result<Vec<CardId>> {
         let existing = self.storage.existing_cards_for_note(note.id)?;
         self.generate_cards_for_note(ctx, note, &existing, ctx.last_deck, &mut Default::default())
     }
@@ -241,21 +243,23 @@ impl Collection {
         existing: &[AlreadyGeneratedCardInfo],
         target_deck_id: Option<DeckId>,
         cache: &mut CardGenCache,
-    ) -> Result<()> {
+    ) -> Result<Vec<CardId>> {
         let cards = ctx.new_cards_required(note, existing, true);
         if cards.is_empty() {
-            return Ok(());
+            return Ok(vec![]);
         }
         self.add_generated_cards(note.id, &cards, target_deck_id, cache)
     }
 
+    /// Returns the IDs of any cards that were added.
     pub(crate) fn generate_cards_for_notetype(
         &mut self,
         ctx: &CardGenContext<impl Deref<Target = Notetype>>,
-    ) -> Result<()> {
+    ) -> Result<Vec<CardId>> {
         let existing_cards = self.storage.existing_cards_for_notetype(ctx.notetype.id)?;
         let by_note = group_generated_cards_by_note(existing_cards);
         let mut cache = CardGenCache::default();
+        let mut added = vec![];
         for (nid, existing_cards) in by_note {
             if ctx.notetype.config.kind() == NotetypeKind::Normal
                 && existing_cards.len() == ctx.notetype.templates.len()
@@ -266,10 +270,16 @@ impl Collection {
             }
             cache.next_position = None;
             let note = self.storage.get_note(nid)?.unwrap();
-            self.generate_cards_for_note(ctx, &note, &existing_cards, None, &mut cache)?;
+            added.extend(self.generate_cards_for_note(
+                ctx,
+                &note,
+                &existing_cards,
+                None,
+                &mut cache,
+            )?);
         }
 
-        Ok(())
+        Ok(added)
     }
 
     pub(crate) fn add_generated_cards(
@@ -278,7 +288,8 @@ impl Collection {
         cards: &[CardToGenerate],
         target_deck_id: Option<DeckId>,
         cache: &mut CardGenCache,
-    ) -> Result<()> {
+    ) -> Result<Vec<CardId>> {
+        let mut added = Vec::with_capacity(cards.len());
         for c in cards {
             let (did, dcid) = self.deck_for_adding(c.did.or(target_deck_id))?;
             let due = if let Some(due) = c.due {
@@ -289,9 +300,10 @@ impl Collection {
             };
             let mut card = Card::new(nid, c.ord as u16, did, due as i32);
             self.add_card(&mut card)?;
+            added.push(card.id);
         }
 
-        Ok(())
+        Ok(added)
     }
 
     // not sure if entry() can be used due to get_deck_config() returning a result
@@ -37,12 +37,16 @@ pub use fields::NoteField;
 pub use notetypechange::ChangeNotetypeInput;
 pub use notetypechange::NotetypeChangeInfo;
 use regex::Regex;
+pub(crate) use render::CardPreview;
 pub(crate) use render::RenderCardOutput;
 pub use schema11::CardTemplateSchema11;
 pub use schema11::NoteFieldSchema11;
 pub use schema11::NotetypeSchema11;
+pub use schemachange::CardChanges;
+pub use schemachange::CardGenerationDiff;
 pub use stock::all_stock_notetypes;
 pub use templates::CardTemplate;
+pub use templates::TemplateWithNotetype;
 use unicase::UniCase;
 
 use crate::define_newtype;
@@ -67,6 +71,9 @@ pub(crate) const DEFAULT_CSS: &str = include_str!("styling.css");
 pub(crate) const DEFAULT_CLOZE_CSS: &str = include_str!("cloze_styling.css");
 pub(crate) const DEFAULT_LATEX_HEADER: &str = include_str!("header.tex");
 pub(crate) const DEFAULT_LATEX_FOOTER: &str = r"\end{document}";
+/// Styling longer than this is accepted, but is unusual enough that clients
+/// may want to flag it, as it often means fonts or images were embedded.
+pub const LONG_CSS_BYTES: usize = 64 * 1024;
 /// New entries must be handled in render.rs/add_special_fields().
 static SPECIAL_FIELDS: LazyLock<HashSet<&'static str>> = LazyLock::new(|| {
     HashSet::from_iter(vec![
@@ -161,11 +168,14 @@ impl Collection {
     ///
     /// This does not assign ordinals to the provided notetype, so if you wish
     /// to make use of template_idx, the notetype must be fetched again.
+    ///
+    /// Returns the cards that were added or removed as a result of template
+    /// changes.
     pub fn update_notetype(
         &mut self,
         notetype: &mut Notetype,
         skip_checks: bool,
-    ) -> Result<OpOutput<()>> {
+    ) -> Result<OpOutput<CardChanges>> {
         self.transact(Op::UpdateNotetype, |col| {
             let original = col
                 .storage
@@ -182,6 +192,20 @@ impl Collection {
         })
     }
 
+    /// Replace the styling shared by all of a notetype's templates.
+    pub fn update_template_css(
+        &mut self,
+        notetype_id: NotetypeId,
+        css: &str,
+    ) -> Result<OpOutput<()>> {
+        let mut notetype = self
+            .storage
+            .get_notetype(notetype_id)?
+            .or_not_found(notetype_id)?;
+        notetype.config.css = css.into();
+        Ok(self.update_notetype(&mut notetype, false)?.map(|_| ()))
+    }
+
     /// Used to support the current importing code; does not mark notetype as
     /// modified, and does not support undo.
     pub fn add_or_update_notetype_with_existing_id(
@@ -192,7 +216,13 @@ impl Collection {
         self.transact_no_undo(|col| {
             let usn = col.usn()?;
             let existing = col.storage.get_notetype(notetype.id)?;
-            col.add_or_update_notetype_with_existing_id_inner(notetype, existing, usn, skip_checks)
+            col.add_or_update_notetype_with_existing_id_inner(
+                notetype,
+                existing,
+                usn,
+                skip_checks,
+            )?;
+            Ok(())
         })
     }
 
@@ -225,6 +255,12 @@ impl Collection {
             .collect()
     }
 
+    /// The id, name and number of notes of every notetype, with the most
+    /// used notetypes first.
+    pub fn get_note_count_by_notetype(&self) -> Result<Vec<(NotetypeId, String, usize)>> {
+        self.storage.get_notetype_note_counts()
+    }
+
     pub fn get_all_notetypes_of_search_notes(
         &mut self,
     ) -> Result<HashMap<NotetypeId, Arc<Notetype>>> {
@@ -759,7 +795,7 @@ impl Collection {
         original: Option<Notetype>,
         usn: Usn,
         skip_checks: bool,
-    ) -> Result<()> {
+    ) -> Result<CardChanges> {
         let normalize = self.get_config_bool(BoolKey::NormalizeNoteText);
         notetype.prepare_for_update(original.as_ref(), skip_checks)?;
         self.ensure_notetype_name_unique(notetype, usn)?;
@@ -771,16 +807,17 @@ impl Collection {
                 original.config.sort_field_idx,
                 normalize,
             )?;
-            self.update_cards_for_changed_templates(notetype, &original.templates)?;
+            let card_changes =
+                self.update_cards_for_changed_templates(notetype, &original.templates)?;
             self.update_notetype_undoable(notetype, original)?;
+            Ok(card_changes)
         } else {
             // adding with existing id for old undo code, bypass undo
             self.state.notetype_cache.remove(&notetype.id);
             self.storage
                 .add_or_update_notetype_with_existing_id(notetype)?;
+            Ok(CardChanges::default())
         }
-
-        Ok(())
     }
 
     pub(crate) fn remove_notetype_inner(&mut self, ntid: NotetypeId) -> Result<()> {
@@ -820,6 +857,7 @@ impl Collection {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::tests::NoteAdder;
 
     #[test]
     fn update_templates_after_removing_crucial_fields() {
@@ -895,4 +933,81 @@ mod test {
         assert_eq!(nt_cloze.templates[0].config.q_format, "front {{cloze:foo}}");
         assert_eq!(nt_cloze.templates[0].config.a_format, "back {{cloze:foo}}");
     }
+    #[test]
+    fn note_count_by_notetype() -> Result<()> {
+        let mut col = Collection::new();
+        NoteAdder::basic(&mut col).add(&mut col);
+        NoteAdder::basic(&mut col).add(&mut col);
+        NoteAdder::cloze(&mut col).add(&mut col);
+
+        let counts = col.get_note_count_by_notetype()?;
+        assert_eq!(counts.len(), col.storage.get_all_notetype_ids()?.len());
+        let counts: Vec<_> = counts
+            .into_iter()
+            .map(|(_, name, count)| (name, count))
+            .take(3)
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("Basic".to_string(), 2),
+                ("Cloze".to_string(), 1),
+                ("Basic (and reversed card)".to_string(), 0)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn all_templates_with_notetypes() -> Result<()> {
+        let mut col = Collection::new();
+        let templates = col.get_all_templates_with_notetypes()?;
+        let template_count: usize = col
+            .get_all_notetypes()?
+            .iter()
+            .map(|nt| nt.templates.len())
+            .sum();
+        assert_eq!(templates.len(), template_count);
+
+        let reversed = col
+            .get_notetype_by_name("Basic (and reversed card)")?
+            .unwrap();
+        let names: Vec<_> = templates
+            .iter()
+            .take(3)
+            .map(|t| {
+                (
+                    t.notetype_name.as_str(),
+                    t.template_ord,
+                    t.template_name.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("Basic", 0, "Card 1"),
+                ("Basic (and reversed card)", 0, "Card 1"),
+                ("Basic (and reversed card)", 1, "Card 2"),
+            ]
+        );
+        assert_eq!(templates[1].notetype_id, reversed.id);
+        Ok(())
+    }
+
+    #[test]
+    fn update_template_css() -> Result<()> {
+        let mut col = Collection::new();
+        let ntid = col.get_notetype_by_name("Basic")?.unwrap().id;
+        let out = col.update_template_css(ntid, ".card { color: red; }")?;
+        assert!(out.changes.changes.notetype);
+        assert_eq!(
+            col.get_notetype(ntid)?.unwrap().config.css,
+            ".card { color: red; }"
+        );
+        col.undo()?;
+        assert_eq!(col.get_notetype(ntid)?.unwrap().config.css, DEFAULT_CSS);
+        assert!(col.update_template_css(NotetypeId(1), "").is_err());
+        Ok(())
+    }
 }
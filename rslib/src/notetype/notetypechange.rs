@@ -6,6 +6,7 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 
+use super::CardChanges;
 use super::CardGenContext;
 use super::Notetype;
 use super::NotetypeKind;
@@ -112,11 +113,52 @@ impl Collection {
         })
     }
 
-    pub fn change_notetype_of_notes(&mut self, input: ChangeNotetypeInput) -> Result<OpOutput<()>> {
+    pub fn change_notetype_of_notes(
+        &mut self,
+        input: ChangeNotetypeInput,
+    ) -> Result<OpOutput<CardChanges>> {
         self.transact(Op::ChangeNotetype, |col| {
             col.change_notetype_of_notes_inner(input)
         })
     }
+
+    /// Move a single note to `target_ntid`. `field_mapping` maps the ordinals
+    /// of the note's current fields to the ordinals of the target fields they
+    /// should be copied into; target fields that are not mapped to are left
+    /// empty. Cards are kept for templates with the same name, falling back on
+    /// unused templates in order, and any others are removed.
+    pub fn set_note_notetype(
+        &mut self,
+        nid: NoteId,
+        target_ntid: NotetypeId,
+        field_mapping: HashMap<u16, u16>,
+    ) -> Result<OpOutput<()>> {
+        let note = self.storage.get_note(nid)?.or_not_found(nid)?;
+        let info = self.notetype_change_info(note.notetype_id, target_ntid)?;
+        let mut new_fields = vec![None; info.new_field_names.len()];
+        for (source_ord, target_ord) in field_mapping {
+            require!(
+                (source_ord as usize) < info.old_field_names.len(),
+                "no source field with ordinal {source_ord}"
+            );
+            let target = new_fields
+                .get_mut(target_ord as usize)
+                .or_invalid(format!("no target field with ordinal {target_ord}"))?;
+            require!(
+                target.is_none(),
+                "more than one field mapped to target field {target_ord}"
+            );
+            *target = Some(source_ord as usize);
+        }
+        let input = ChangeNotetypeInput {
+            note_ids: vec![nid],
+            new_fields,
+            ..info.input
+        };
+        self.transact(Op::ChangeNotetype, |col| {
+            col.change_notetype_of_notes_inner(input).map(|_| ())
+        })
+    }
 }
 
 fn default_template_map(
@@ -212,7 +254,7 @@ impl Collection {
     pub(crate) fn change_notetype_of_notes_inner(
         &mut self,
         input: ChangeNotetypeInput,
-    ) -> Result<()> {
+    ) -> Result<CardChanges> {
         require!(
             input.current_schema == self.storage.get_collection_timestamps()?.schema_change,
             "schema changed"
@@ -220,7 +262,7 @@ impl Collection {
 
         let usn = self.usn()?;
         self.set_schema_modified()?;
-        if let Some(new_templates) = input.new_templates {
+        let removed = if let Some(new_templates) = input.new_templates {
             let old_notetype = self
                 .get_notetype(input.old_notetype_id)?
                 .or_not_found(input.old_notetype_id)?;
@@ -229,53 +271,55 @@ impl Collection {
                 old_notetype.templates.len(),
                 new_templates,
                 usn,
-            )?;
+            )?
         } else {
             self.maybe_remove_cards_with_missing_template(
                 &input.note_ids,
                 input.new_notetype_id,
                 usn,
-            )?;
-        }
-        self.update_notes_for_new_notetype_and_generate_cards(
+            )?
+        };
+        let added = self.update_notes_for_new_notetype_and_generate_cards(
             &input.note_ids,
             &input.new_fields,
             input.new_notetype_id,
             usn,
         )?;
 
-        Ok(())
+        Ok(CardChanges { added, removed })
     }
 
     /// Rewrite notes to match new notetype, and assigns new notetype id.
     ///
     /// `new_fields` should be the length of the new notetype's fields, and is a
     /// list of the previous field index each field should be mapped to. If
-    /// None, the field is left empty.
+    /// None, the field is left empty. Returns the IDs of any cards that were
+    /// added.
     fn update_notes_for_new_notetype_and_generate_cards(
         &mut self,
         note_ids: &[NoteId],
         new_fields: &[Option<usize>],
         new_notetype_id: NotetypeId,
         usn: Usn,
-    ) -> Result<()> {
+    ) -> Result<Vec<CardId>> {
         let notetype = self
             .get_notetype(new_notetype_id)?
             .or_not_found(new_notetype_id)?;
         let last_deck = self.get_last_deck_added_to_for_notetype(notetype.id);
         let ctx = CardGenContext::new(notetype.as_ref(), last_deck, usn);
+        let mut added = vec![];
 
         for nid in note_ids {
             let mut note = self.storage.get_note(*nid)?.or_not_found(nid)?;
             let original = note.clone();
             remap_fields(note.fields_mut(), new_fields);
             note.notetype_id = new_notetype_id;
-            self.update_note_inner_generating_cards(
+            added.extend(self.update_note_inner_generating_cards(
                 &ctx, &mut note, &original, true, false, false,
-            )?;
+            )?);
         }
 
-        Ok(())
+        Ok(added)
     }
 
     fn update_cards_for_new_notetype(
@@ -284,13 +328,13 @@ impl Collection {
         old_template_count: usize,
         new_templates: Vec<Option<usize>>,
         usn: Usn,
-    ) -> Result<()> {
+    ) -> Result<Vec<CardId>> {
         let nids: Node = SearchNode::NoteIds(comma_separated_ids(note_ids)).into();
         let map = TemplateMap::new(new_templates, old_template_count);
-        self.remove_unmapped_cards(&map, nids.clone(), usn)?;
+        let removed = self.remove_unmapped_cards(&map, nids.clone(), usn)?;
         self.rewrite_remapped_cards(&map, nids, usn)?;
 
-        Ok(())
+        Ok(removed)
     }
 
     fn remove_unmapped_cards(
@@ -298,16 +342,18 @@ impl Collection {
         map: &TemplateMap,
         nids: Node,
         usn: Usn,
-    ) -> Result<(), AnkiError> {
+    ) -> Result<Vec<CardId>, AnkiError> {
+        let mut removed = vec![];
         if !map.removed.is_empty() {
             let ords =
                 SearchBuilder::any(map.removed.iter().map(|o| TemplateKind::Ordinal(*o as u16)));
             for card in self.all_cards_for_search(nids.and(ords))? {
+                removed.push(card.id);
                 self.remove_card_and_add_grave_undoable(card, usn)?;
             }
         }
 
-        Ok(())
+        Ok(removed)
     }
 
     fn rewrite_remapped_cards(
@@ -342,8 +388,9 @@ impl Collection {
         note_ids: &[NoteId],
         notetype_id: NotetypeId,
         usn: Usn,
-    ) -> Result<()> {
+    ) -> Result<Vec<CardId>> {
         let notetype = self.get_notetype(notetype_id)?.or_not_found(notetype_id)?;
+        let mut removed = vec![];
 
         if notetype.config.kind() == NotetypeKind::Normal {
             // cloze -> normal change requires clean up
@@ -351,11 +398,12 @@ impl Collection {
                 .storage
                 .all_cards_of_notes_above_ordinal(note_ids, notetype.templates.len() - 1)?
             {
+                removed.push(card.id);
                 self.remove_card_and_add_grave_undoable(card, usn)?;
             }
         }
 
-        Ok(())
+        Ok(removed)
     }
 }
 
@@ -468,7 +516,7 @@ mod test {
             new_templates: Some(vec![None, Some(0)]),
             ..col.notetype_change_info(basic.id, basic2.id)?.input
         };
-        col.change_notetype_of_notes(input)?;
+        let changes = col.change_notetype_of_notes(input)?.output;
 
         // cards arrive in creation order, so the existing card will come first
         let cards = col.storage.all_cards_of_note(note.id)?;
@@ -478,7 +526,52 @@ mod test {
         // a new forward card should also have been generated
         assert_eq!(cards[1].template_idx, 0);
         assert_ne!(cards[1].id, first_card.id);
+        assert_eq!(changes.added, vec![cards[1].id]);
+        assert!(changes.removed.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn set_note_notetype() -> Result<()> {
+        let mut col = Collection::new();
+        let basic = col
+            .get_notetype_by_name("Basic (and reversed card)")?
+            .unwrap();
+        let mut note = basic.new_note();
+        note.set_field(0, "front")?;
+        note.set_field(1, "back")?;
+        col.add_note(&mut note, DeckId(1))?;
+        let forward_card = col.storage.all_cards_of_note(note.id)?[0].id;
 
+        // fields are swapped, and the reverse card is removed
+        let basic1 = col.get_notetype_by_name("Basic")?.unwrap();
+        col.set_note_notetype(note.id, basic1.id, [(0, 1), (1, 0)].into())?;
+        let note = col.storage.get_note(note.id)?.unwrap();
+        assert_eq!(note.notetype_id, basic1.id);
+        assert_eq!(note.fields(), &["back", "front"]);
+        let cards = col.storage.all_cards_of_note(note.id)?;
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[0].id, forward_card);
+
+        // and a new card is generated when moving back
+        col.set_note_notetype(note.id, basic.id, [(1, 0)].into())?;
+        let note = col.storage.get_note(note.id)?.unwrap();
+        assert_eq!(note.fields(), &["front", ""]);
+        assert_eq!(col.storage.all_cards_of_note(note.id)?.len(), 1);
+        col.set_note_notetype(note.id, basic1.id, [(0, 0)].into())?;
+        col.set_note_notetype(note.id, basic.id, [(0, 0), (0, 1)].into())?;
+        assert_eq!(col.storage.all_cards_of_note(note.id)?.len(), 2);
+
+        // mappings must refer to existing fields, and not share targets
+        for mapping in [[(2, 0)], [(0, 2)]] {
+            assert!(col
+                .set_note_notetype(note.id, basic1.id, mapping.into())
+                .is_err());
+        }
+        assert!(col
+            .set_note_notetype(note.id, basic1.id, [(0, 0), (1, 0)].into())
+            .is_err());
         Ok(())
     }
 
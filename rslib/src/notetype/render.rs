@@ -3,16 +3,21 @@
 
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::path::Path;
 
+use super::CardGenContext;
 use super::CardTemplate;
 use super::Notetype;
 use super::NotetypeKind;
+use crate::media::files::filename_if_normalized;
 use crate::prelude::*;
 use crate::template::field_is_empty;
 use crate::template::render_card;
 use crate::template::ParsedTemplate;
 use crate::template::RenderCardRequest;
 use crate::template::RenderedNode;
+use crate::text::extract_media_refs;
+use crate::text::REMOTE_FILENAME;
 
 #[derive(Debug)]
 pub struct RenderCardOutput {
@@ -39,9 +44,89 @@ impl RenderCardOutput {
             _ => "not fully rendered".into(),
         }
     }
+
+    /// Local media files referenced by either side that don't exist in
+    /// `media_folder`, in the order they are first referenced. Names that
+    /// could not be in the media folder, such as ones with path separators,
+    /// are skipped, so no other path is looked up. This is only valid to call
+    /// when partial_render=false.
+    pub fn missing_media(&self, media_folder: &Path) -> Vec<String> {
+        let question = self.question();
+        let answer = self.answer();
+        let mut missing: Vec<String> = vec![];
+        for media_ref in extract_media_refs(&question)
+            .into_iter()
+            .chain(extract_media_refs(&answer))
+        {
+            let fname = media_ref.fname_decoded;
+            if REMOTE_FILENAME.is_match(&fname)
+                || fname.starts_with("data:")
+                || missing.iter().any(|name| *name == fname)
+            {
+                continue;
+            }
+            let Some(normalized) = filename_if_normalized(&fname) else {
+                continue;
+            };
+            if !media_folder.join(normalized.as_ref()).exists() {
+                missing.push(fname.into_owned());
+            }
+        }
+        missing
+    }
+}
+
+/// A card a note would have if it were added, as rendered before saving it.
+#[derive(Debug)]
+pub struct CardPreview {
+    pub ord: u16,
+    pub template_name: String,
+    /// False if the card would not be generated, because its question would
+    /// be empty.
+    pub nonempty: bool,
+    pub output: RenderCardOutput,
 }
 
 impl Collection {
+    /// Render the cards `note` would have if it were added, using the same
+    /// requirements as when adding it. Normal notetypes get one entry per
+    /// template, including ones that would not be generated; cloze notetypes
+    /// get one entry per cloze number in the fields. If no entry is nonempty,
+    /// adding the note would only create a blank first card. The note's
+    /// fields are normalized as they would be when saving.
+    pub fn preview_note_cards(&mut self, note: &mut Note) -> Result<Vec<CardPreview>> {
+        let nt = self
+            .get_notetype(note.notetype_id)?
+            .or_invalid("no such notetype")?;
+        let normalize_text = self.get_config_bool(BoolKey::NormalizeNoteText);
+        note.prepare_for_update(&nt, normalize_text)?;
+        let ctx = CardGenContext::new(nt.as_ref(), None, self.usn()?);
+        let required: Vec<u16> = ctx
+            .new_cards_required(note, &[], false)
+            .into_iter()
+            .map(|card| card.ord as u16)
+            .collect();
+        let ords = match nt.config.kind() {
+            NotetypeKind::Normal => (0..nt.templates.len() as u16).collect(),
+            NotetypeKind::Cloze => required.clone(),
+        };
+        ords.into_iter()
+            .map(|ord| {
+                let template = match nt.config.kind() {
+                    NotetypeKind::Normal => nt.templates.get(ord as usize),
+                    NotetypeKind::Cloze => nt.templates.first(),
+                }
+                .or_invalid("missing template")?;
+                Ok(CardPreview {
+                    ord,
+                    template_name: template.name.clone(),
+                    nonempty: required.contains(&ord),
+                    output: self.render_uncommitted_card(note, template, ord, false, false)?,
+                })
+            })
+            .collect()
+    }
+
     /// Render an existing card saved in the database.
     pub fn render_existing_card(
         &mut self,
@@ -212,6 +297,9 @@ fn fill_empty_fields(note: &mut Note, qfmt: &str, nt: &Notetype, tr: &I18n) {
 
 #[cfg(test)]
 mod test {
+    use anki_io::write_file;
+    use tempfile::tempdir;
+
     use super::*;
     use crate::collection::CollectionBuilder;
     use crate::notetype::SPECIAL_FIELDS;
@@ -252,4 +340,70 @@ mod test {
         assert!(map.iter().all(|val| SPECIAL_FIELDS.contains(val.0)));
         Ok(())
     }
+
+    #[test]
+    fn missing_media() -> Result<()> {
+        let mut col = CollectionBuilder::default().build()?;
+        let media_folder = tempdir()?;
+        write_file(media_folder.path().join("here.jpg"), "")?;
+        let nt = col.get_notetype_by_name("Basic")?.unwrap();
+        let mut note = Note::new(&nt);
+        note.set_field(
+            0,
+            r#"<img src="here.jpg"><img src="gone.jpg"><img src="https://example.com/x.jpg">"#,
+        )?;
+        note.set_field(
+            1,
+            "[sound:a&amp;b.mp3]<img src='gone.jpg'><img src='../x.jpg'><img src='..'>",
+        )?;
+        let out = col.render_uncommitted_card(&mut note, &nt.templates[0], 0, false, false)?;
+        assert_eq!(
+            out.missing_media(media_folder.path()),
+            ["gone.jpg", "a&b.mp3"]
+        );
+        assert!(!out.is_empty);
+        Ok(())
+    }
+
+    #[test]
+    fn preview_note_cards() -> Result<()> {
+        let mut col = CollectionBuilder::default().build()?;
+        let nt = col
+            .get_notetype_by_name("Basic (optional reversed card)")?
+            .unwrap();
+        let mut note = Note::new(&nt);
+        note.set_field(0, "front")?;
+        note.set_field(1, "back")?;
+        let previews = col.preview_note_cards(&mut note)?;
+        assert_eq!(previews.len(), 2);
+        assert_eq!(previews[0].template_name, "Card 1");
+        assert!(previews[0].nonempty);
+        assert_eq!(&previews[0].output.question(), "front");
+        assert!(!previews[1].nonempty);
+        note.set_field(2, "y")?;
+        assert!(col.preview_note_cards(&mut note)?[1].nonempty);
+
+        // no card would be generated from an empty front
+        note.set_field(0, "")?;
+        note.set_field(2, "")?;
+        let previews = col.preview_note_cards(&mut note)?;
+        assert!(previews.iter().all(|preview| !preview.nonempty));
+
+        let nt = col.get_notetype_by_name("Cloze")?.unwrap();
+        let mut note = Note::new(&nt);
+        note.set_field(0, "{{c1::a}} {{c3::b}}")?;
+        let previews = col.preview_note_cards(&mut note)?;
+        assert_eq!(
+            previews
+                .iter()
+                .map(|preview| preview.ord)
+                .collect::<Vec<_>>(),
+            [0, 2]
+        );
+        assert!(previews.iter().all(|preview| preview.nonempty));
+        assert!(previews[1].output.question().contains("a"));
+        note.set_field(0, "no deletions")?;
+        assert!(col.preview_note_cards(&mut note)?.is_empty());
+        Ok(())
+    }
 }
@@ -6,6 +6,7 @@ use anki_proto::notetypes::stock_notetype::OriginalStockKind;
 
 use crate::notetype::stock::get_original_stock_notetype;
 use crate::notetype::stock::StockKind;
+use crate::notetype::CardChanges;
 use crate::prelude::*;
 
 impl Collection {
@@ -16,7 +17,7 @@ impl Collection {
         &mut self,
         notetype_id: NotetypeId,
         force_kind: Option<StockKind>,
-    ) -> Result<OpOutput<()>> {
+    ) -> Result<OpOutput<CardChanges>> {
         let mut nt = self
             .storage
             .get_notetype(notetype_id)?
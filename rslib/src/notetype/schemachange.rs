@@ -6,12 +6,14 @@
 use std::collections::HashMap;
 use std::mem;
 
+use super::cardgen::group_generated_cards_by_note;
 use super::CardGenContext;
 use super::CardTemplate;
 use super::Notetype;
 use crate::notes::UpdateNoteInnerWithoutCardsArgs;
 use crate::prelude::*;
 use crate::search::JoinSearches;
+use crate::search::SortMode;
 use crate::search::TemplateKind;
 
 /// True if any ordinals added, removed or reordered.
@@ -23,6 +25,22 @@ fn ords_changed(ords: &[Option<u32>], previous_len: usize) -> bool {
             .any(|(idx, &ord)| ord != Some(idx as u32))
 }
 
+/// Cards that were implicitly added or removed by a change to a notetype's
+/// templates, or by moving notes to a different notetype.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CardChanges {
+    pub added: Vec<CardId>,
+    pub removed: Vec<CardId>,
+}
+
+/// Cards that would be added or removed if a modified notetype were saved.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CardGenerationDiff {
+    /// Note id and template ordinal of each card that would be generated.
+    pub would_add: Vec<(NoteId, u16)>,
+    pub would_remove: Vec<CardId>,
+}
+
 #[derive(Default, PartialEq, Eq, Debug)]
 struct TemplateOrdChanges {
     added: Vec<u32>,
@@ -125,8 +143,9 @@ impl Collection {
         &mut self,
         nt: &Notetype,
         old_templates: &[CardTemplate],
-    ) -> Result<()> {
+    ) -> Result<CardChanges> {
         let usn = self.usn()?;
+        let mut card_changes = CardChanges::default();
         let ords: Vec<_> = nt.templates.iter().map(|f| f.ord).collect();
         let changes = TemplateOrdChanges::new(ords, old_templates.len() as u32);
 
@@ -139,6 +158,7 @@ impl Collection {
             let ords =
                 SearchBuilder::any(changes.removed.iter().cloned().map(TemplateKind::Ordinal));
             for card in self.all_cards_for_search(nt.id.and(ords))? {
+                card_changes.removed.push(card.id);
                 self.remove_card_and_add_grave_undoable(card, usn)?;
             }
         }
@@ -155,10 +175,60 @@ impl Collection {
         if should_generate_cards(&changes, nt, old_templates) {
             let last_deck = self.get_last_deck_added_to_for_notetype(nt.id);
             let ctx = CardGenContext::new(nt, last_deck, usn);
-            self.generate_cards_for_notetype(&ctx)?;
+            card_changes.added = self.generate_cards_for_notetype(&ctx)?;
         }
 
-        Ok(())
+        Ok(card_changes)
+    }
+}
+
+impl Collection {
+    /// Determine which cards saving the provided notetype would add or
+    /// remove, without modifying the collection. Template ordinals are
+    /// interpreted the same way as [Collection::update_notetype()].
+    pub fn get_notetype_card_generation_diff(
+        &mut self,
+        notetype: &Notetype,
+    ) -> Result<CardGenerationDiff> {
+        let original = self
+            .storage
+            .get_notetype(notetype.id)?
+            .or_not_found(notetype.id)?;
+        let mut nt = notetype.clone();
+        nt.prepare_for_update(Some(&original), false)?;
+        let ords: Vec<_> = nt.templates.iter().map(|t| t.ord).collect();
+        let changes = TemplateOrdChanges::new(ords, original.templates.len() as u32);
+        let mut diff = CardGenerationDiff::default();
+
+        if !changes.removed.is_empty() {
+            let ords =
+                SearchBuilder::any(changes.removed.iter().cloned().map(TemplateKind::Ordinal));
+            diff.would_remove = self.search_cards(nt.id.and(ords), SortMode::NoOrder)?;
+        }
+
+        if should_generate_cards(&changes, &nt, &original.templates) {
+            let field_ords: Vec<_> = nt.fields.iter().map(|f| f.ord).collect();
+            let ctx = CardGenContext::new(&nt, None, self.usn()?);
+            let existing_cards = self.storage.existing_cards_for_notetype(nt.id)?;
+            for (nid, mut existing_cards) in group_generated_cards_by_note(existing_cards) {
+                // apply the ordinal changes that saving would make
+                existing_cards.retain(|card| !changes.removed.contains(&(card.ord as u16)));
+                for card in &mut existing_cards {
+                    if let Some(&new_ord) = changes.moved.get(&(card.ord as u16)) {
+                        card.ord = new_ord as u32;
+                    }
+                }
+                let mut note = self.storage.get_note(nid)?.or_not_found(nid)?;
+                note.reorder_fields(&field_ords);
+                diff.would_add.extend(
+                    ctx.new_cards_required(&note, &existing_cards, true)
+                        .into_iter()
+                        .map(|card| (nid, card.ord as u16)),
+                );
+            }
+        }
+
+        Ok(diff)
     }
 }
 
@@ -197,7 +267,6 @@ impl Note {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::search::SortMode;
 
     #[test]
     fn ord_changes() {
@@ -303,6 +372,39 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn guarded_schema_changes() -> Result<()> {
+        let mut col = Collection::new();
+        col.storage.set_schema_modified_time(TimestampMillis(0))?;
+        let mut nt = col.basic_notetype();
+
+        // renaming a field leaves the schema alone
+        nt.fields[1].name = "Answer".into();
+        let (_, modified) =
+            col.guard_schema_change(false, |col| col.update_notetype(&mut nt, false))?;
+        assert!(!modified);
+
+        // removing one is refused, and rolled back
+        nt.fields.remove(1);
+        let err = col
+            .guard_schema_change(false, |col| col.update_notetype(&mut nt, false))
+            .unwrap_err();
+        assert_eq!(err, AnkiError::SchemaChangeNotAllowed);
+        assert_eq!(col.basic_notetype().fields.len(), 2);
+        assert_eq!(
+            col.storage.get_collection_timestamps()?.schema_change,
+            TimestampMillis(0)
+        );
+
+        // unless allowed
+        let (_, modified) =
+            col.guard_schema_change(true, |col| col.update_notetype(&mut nt, false))?;
+        assert!(modified);
+        assert_eq!(col.basic_notetype().fields.len(), 1);
+
+        Ok(())
+    }
+
     #[test]
     fn cards() -> Result<()> {
         let mut col = Collection::new();
@@ -323,12 +425,51 @@ mod test {
 
         // add an extra card template
         nt.add_template("card 2", "{{Front}}2", "");
-        col.update_notetype(&mut nt, false)?;
+        let changes = col.update_notetype(&mut nt, false)?.output;
 
-        assert_eq!(
-            col.search_cards(note.id, SortMode::NoOrder).unwrap().len(),
-            2
-        );
+        let cids = col.search_cards(note.id, SortMode::NoOrder).unwrap();
+        assert_eq!(cids.len(), 2);
+        assert_eq!(changes.added, &cids[1..]);
+        assert!(changes.removed.is_empty());
+
+        // removing it again should report the removed card
+        let mut nt = col.storage.get_notetype(nt.id)?.unwrap();
+        nt.templates.pop();
+        let changes = col.update_notetype(&mut nt, false)?.output;
+        assert!(changes.added.is_empty());
+        assert_eq!(changes.removed, &cids[1..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn card_generation_diff() -> Result<()> {
+        let mut col = Collection::new();
+        let mut nt = col.basic_notetype();
+        let full = NoteAdder::basic(&mut col)
+            .fields(&["one", "two"])
+            .add(&mut col);
+        let note = NoteAdder::basic(&mut col)
+            .fields(&["three", ""])
+            .add(&mut col);
+        let cid = col.search_cards(note.id, SortMode::NoOrder)?[0];
+
+        // a reverse template only generates cards for notes with a back
+        nt.add_template("card 2", "{{Back}}", "");
+        let diff = col.get_notetype_card_generation_diff(&nt)?;
+        assert_eq!(diff.would_add, vec![(full.id, 1)]);
+        assert!(diff.would_remove.is_empty());
+
+        // nothing is changed
+        assert_eq!(col.storage.get_all_cards().len(), 2);
+
+        // replacing the template removes the existing cards and generates new ones
+        let mut nt = col.basic_notetype();
+        nt.templates[0].ord = None;
+        let diff = col.get_notetype_card_generation_diff(&nt)?;
+        assert!(diff.would_remove.contains(&cid));
+        assert_eq!(diff.would_remove.len(), 2);
+        assert_eq!(diff.would_add.len(), 2);
 
         Ok(())
     }
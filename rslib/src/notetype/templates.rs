@@ -1,6 +1,8 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use unicase::UniCase;
+
 use super::CardTemplateConfig;
 use super::CardTemplateProto;
 use crate::prelude::*;
@@ -119,3 +121,35 @@ impl CardTemplate {
         Ok(())
     }
 }
+
+/// A template along with the notetype it belongs to.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TemplateWithNotetype {
+    pub notetype_id: NotetypeId,
+    pub notetype_name: String,
+    pub template_ord: u16,
+    pub template_name: String,
+}
+
+impl Collection {
+    /// Every template of every notetype, with notetypes sorted by name and
+    /// templates in ordinal order.
+    pub fn get_all_templates_with_notetypes(&mut self) -> Result<Vec<TemplateWithNotetype>> {
+        let mut notetypes = self.get_all_notetypes()?;
+        notetypes.sort_unstable_by(|a, b| UniCase::new(&a.name).cmp(&UniCase::new(&b.name)));
+        Ok(notetypes
+            .iter()
+            .flat_map(|nt| {
+                nt.templates
+                    .iter()
+                    .enumerate()
+                    .map(|(ord, template)| TemplateWithNotetype {
+                        notetype_id: nt.id,
+                        notetype_name: nt.name.clone(),
+                        template_ord: ord as u16,
+                        template_name: template.name.clone(),
+                    })
+            })
+            .collect())
+    }
+}
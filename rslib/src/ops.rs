@@ -115,10 +115,27 @@ pub struct StateChanges {
     pub mtime: bool,
 }
 
+/// The most ids of each kind that [ChangedIds] holds.
+pub const CHANGED_IDS_LIMIT: usize = 1000;
+
+/// The cards, notes and decks an op added, updated or removed, so that views
+/// can refresh just those. They're taken from the op's undo entries, so ops
+/// that skip the undo queue report none.
+#[derive(Debug, PartialEq, Eq, Default, Clone)]
+pub struct ChangedIds {
+    pub cards: Vec<CardId>,
+    pub notes: Vec<NoteId>,
+    pub decks: Vec<DeckId>,
+    /// True if more than [CHANGED_IDS_LIMIT] ids of some kind were touched,
+    /// and the rest were left out.
+    pub truncated: bool,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct OpChanges {
     pub op: Op,
     pub changes: StateChanges,
+    pub ids: ChangedIds,
 }
 
 impl Default for OpChanges {
@@ -126,6 +143,7 @@ impl Default for OpChanges {
         Self {
             op: Op::Custom(String::new()),
             changes: StateChanges::default(),
+            ids: ChangedIds::default(),
         }
     }
 }
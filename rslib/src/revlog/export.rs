@@ -0,0 +1,178 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::fmt::Write;
+
+use super::RevlogEntry;
+use super::RevlogId;
+use crate::prelude::*;
+
+/// The columns of [Collection::export_review_log_as_csv]. Every value is an
+/// integer.
+///
+/// - `reviewedAt`: the answer time in milliseconds, unique per entry
+/// - `noteId`, `deckId`: the card's note and home deck, empty if the card has
+///   since been deleted
+/// - `ease`: the button pressed, 1-4 for again-easy, or 0 for manual
+///   rescheduling
+/// - `interval`, `lastInterval`: in days, or negative seconds for learning
+///   steps
+pub const REVIEW_LOG_CSV_COLUMNS: [&str; 8] = [
+    "reviewedAt",
+    "cardId",
+    "noteId",
+    "deckId",
+    "ease",
+    "interval",
+    "lastInterval",
+    "timeTakenMs",
+];
+
+/// The number of entries read from the collection at a time.
+pub(crate) const REVIEW_LOG_CSV_CHUNK_ROWS: u32 = 10_000;
+
+impl Collection {
+    /// The review log as CSV, oldest entry first, with a header row of
+    /// [REVIEW_LOG_CSV_COLUMNS]. If `deck_id` is provided, only reviews of
+    /// cards whose home deck is it or one of its children are included.
+    pub fn export_review_log_as_csv(
+        &mut self,
+        deck_id: Option<DeckId>,
+        since: Option<TimestampSecs>,
+    ) -> Result<String> {
+        let mut csv = review_log_csv_header();
+        let mut after = None;
+        loop {
+            let (last, rows) =
+                self.review_log_csv_chunk(deck_id, since, after, REVIEW_LOG_CSV_CHUNK_ROWS)?;
+            csv.push_str(&rows);
+            match last {
+                Some(last) => after = Some(last),
+                None => return Ok(csv),
+            }
+        }
+    }
+
+    /// Up to `limit` rows of [Collection::export_review_log_as_csv] following
+    /// the entry `after`. Returns the id of the last entry, or [None] if there
+    /// were no more entries.
+    pub(crate) fn review_log_csv_chunk(
+        &mut self,
+        deck_id: Option<DeckId>,
+        since: Option<TimestampSecs>,
+        after: Option<RevlogId>,
+        limit: u32,
+    ) -> Result<(Option<RevlogId>, String)> {
+        let deck_ids = match deck_id {
+            Some(did) => {
+                let deck = self.get_deck(did)?.or_not_found(did)?;
+                Some(self.storage.deck_id_with_children(&deck)?)
+            }
+            None => None,
+        };
+        let entries = self.storage.get_revlog_entries_with_card_info(
+            after.unwrap_or_default(),
+            since.unwrap_or_default(),
+            deck_ids.as_deref(),
+            limit,
+        )?;
+        let last = entries.last().map(|(entry, _)| entry.id);
+        let mut rows = String::new();
+        for (entry, card_info) in entries {
+            write_review_log_csv_row(&mut rows, &entry, card_info);
+        }
+        Ok((last, rows))
+    }
+}
+
+pub(crate) fn review_log_csv_header() -> String {
+    REVIEW_LOG_CSV_COLUMNS.join(",") + "\n"
+}
+
+fn write_review_log_csv_row(
+    buf: &mut String,
+    entry: &RevlogEntry,
+    card_info: Option<(NoteId, DeckId)>,
+) {
+    let (note_id, deck_id) = card_info
+        .map(|(nid, did)| (nid.to_string(), did.to_string()))
+        .unwrap_or_default();
+    writeln!(
+        buf,
+        "{},{},{note_id},{deck_id},{},{},{},{}",
+        entry.id,
+        entry.cid,
+        entry.button_chosen,
+        entry.interval,
+        entry.last_interval,
+        entry.taken_millis
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tests::NoteAdder;
+
+    #[test]
+    fn review_log_csv() -> Result<()> {
+        let mut col = Collection::new();
+        let parent = col.get_or_create_normal_deck("parent")?.id;
+        let child = col.get_or_create_normal_deck("parent::child")?.id;
+        let note = NoteAdder::basic(&mut col).add(&mut col);
+        let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+        card.deck_id = child;
+        col.storage.update_card(&card)?;
+        let other = NoteAdder::basic(&mut col).add(&mut col);
+        let other_cid = col.storage.all_cards_of_note(other.id)?[0].id;
+        let add_entry = |id: i64, cid: CardId, interval: i32| {
+            col.storage.add_revlog_entry(
+                &RevlogEntry {
+                    id: RevlogId(id),
+                    cid,
+                    button_chosen: 3,
+                    interval,
+                    last_interval: -600,
+                    taken_millis: 4200,
+                    ..Default::default()
+                },
+                false,
+            )
+        };
+        add_entry(1_000, card.id, 1)?;
+        add_entry(2_000, other_cid, 2)?;
+        add_entry(3_000, CardId(123), 3)?;
+
+        let csv = col.export_review_log_as_csv(None, None)?;
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "reviewedAt,cardId,noteId,deckId,ease,interval,lastInterval,timeTakenMs"
+        );
+        assert_eq!(
+            lines[1],
+            format!("1000,{},{},{child},3,1,-600,4200", card.id, note.id)
+        );
+        // the card of the last entry no longer exists
+        assert_eq!(lines[3], "3000,123,,,3,3,-600,4200");
+        assert_eq!(lines.len(), 4);
+
+        // child decks are included
+        let csv = col.export_review_log_as_csv(Some(parent), None)?;
+        assert_eq!(csv.lines().count(), 2);
+        let csv = col.export_review_log_as_csv(None, Some(TimestampSecs(2)))?;
+        assert_eq!(csv.lines().count(), 3);
+        assert!(col
+            .export_review_log_as_csv(Some(DeckId(123)), None)
+            .is_err());
+
+        // chunks continue where the previous one ended
+        let (last, rows) = col.review_log_csv_chunk(None, None, None, 2)?;
+        assert_eq!((last, rows.lines().count()), (Some(RevlogId(2_000)), 2));
+        let (last, rows) = col.review_log_csv_chunk(None, None, last, 2)?;
+        assert_eq!((last, rows.lines().count()), (Some(RevlogId(3_000)), 1));
+        assert_eq!(col.review_log_csv_chunk(None, None, last, 2)?.0, None);
+        Ok(())
+    }
+}
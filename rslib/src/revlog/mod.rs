@@ -1,8 +1,10 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+pub mod export;
 pub(crate) mod undo;
 
+use chrono::NaiveDate;
 use num_enum::TryFromPrimitive;
 use serde::Deserialize;
 use serde_repr::Deserialize_repr;
@@ -86,7 +88,55 @@ impl RevlogEntry {
     }
 }
 
+/// A card's interval and ease after one of its answers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntervalSnapshot {
+    /// The day of the answer. Days start at the collection's rollover hour.
+    pub review_date: NaiveDate,
+    /// Positive values are in days, negative values are learning steps in
+    /// seconds.
+    pub interval_after: i32,
+    /// As stored in the revlog, so 10x the %, or normalized difficulty when
+    /// FSRS was active.
+    pub ease_after: u16,
+    /// The button chosen, or 0 for manual rescheduling.
+    pub rating: u8,
+}
+
+impl IntervalSnapshot {
+    pub fn is_learning_step(&self) -> bool {
+        self.interval_after < 0
+    }
+}
+
 impl Collection {
+    /// The card's interval after each of its answers, oldest first. Manual
+    /// rescheduling and resets are included with a rating of 0.
+    pub fn get_interval_history_for_card(&mut self, cid: CardId) -> Result<Vec<IntervalSnapshot>> {
+        self.storage.get_card(cid)?.or_not_found(cid)?;
+        let next_day_start = self.timing_today()?.next_day_at;
+        let offset = self.local_utc_offset_for_user()?;
+        let mut entries = self.storage.get_revlog_entries_for_card(cid)?;
+        entries.sort_unstable_by_key(|entry| entry.id);
+        entries
+            .into_iter()
+            .map(|entry| {
+                let days = entry
+                    .id
+                    .as_secs()
+                    .elapsed_secs_since(next_day_start)
+                    .div_euclid(86_400);
+                let day_start = next_day_start.adding_secs(days * 86_400);
+                Ok(IntervalSnapshot {
+                    review_date: day_start.datetime(offset)?.date_naive(),
+                    interval_after: entry.interval,
+                    ease_after: u16::try_from(entry.ease_factor).unwrap_or(u16::MAX),
+                    rating: entry.button_chosen,
+                })
+            })
+            .collect()
+    }
+
     // set due date or reset
     pub(crate) fn log_manually_scheduled_review(
         &mut self,
@@ -134,3 +184,59 @@ impl Collection {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tests::NoteAdder;
+
+    #[test]
+    fn interval_history() -> Result<()> {
+        let mut col = Collection::new();
+        let note = NoteAdder::basic(&mut col).add(&mut col);
+        let cid = col.storage.all_cards_of_note(note.id)?[0].id;
+        let today = TimestampSecs::now();
+        let add_entry = |days_ago: i64, interval: i32, ease_factor: u32, button_chosen: u8| {
+            col.storage.add_revlog_entry(
+                &RevlogEntry {
+                    id: RevlogId(today.adding_secs(-days_ago * 86_400).as_millis().0),
+                    cid,
+                    interval,
+                    ease_factor,
+                    button_chosen,
+                    ..Default::default()
+                },
+                false,
+            )
+        };
+        add_entry(3, 4, 2500, 3)?;
+        add_entry(5, -600, 0, 1)?;
+        add_entry(0, 12, 2650, 4)?;
+
+        let history = col.get_interval_history_for_card(cid)?;
+        assert_eq!(
+            history
+                .iter()
+                .map(|snapshot| (
+                    snapshot.interval_after,
+                    snapshot.ease_after,
+                    snapshot.rating
+                ))
+                .collect::<Vec<_>>(),
+            [(-600, 0, 1), (4, 2500, 3), (12, 2650, 4)]
+        );
+        assert!(history[0].is_learning_step());
+        assert!(!history[1].is_learning_step());
+        assert_eq!(
+            (history[2].review_date - history[1].review_date).num_days(),
+            3
+        );
+        assert_eq!(
+            (history[1].review_date - history[0].review_date).num_days(),
+            2
+        );
+
+        assert!(col.get_interval_history_for_card(CardId(1)).is_err());
+        Ok(())
+    }
+}
@@ -34,11 +34,16 @@ impl Collection {
         Ok(id)
     }
 
-    /// Add the provided revlog entry, if its ID is unique.
-    pub(crate) fn add_revlog_entry_if_unique_undoable(&mut self, entry: RevlogEntry) -> Result<()> {
-        if self.storage.add_revlog_entry(&entry, false)?.is_some() {
+    /// Add the provided revlog entry, if its ID is unique. Returns true if it
+    /// was added.
+    pub(crate) fn add_revlog_entry_if_unique_undoable(
+        &mut self,
+        entry: RevlogEntry,
+    ) -> Result<bool> {
+        let added = self.storage.add_revlog_entry(&entry, false)?.is_some();
+        if added {
             self.save_undo(UndoableRevlogChange::Added(Box::new(entry)));
         }
-        Ok(())
+        Ok(added)
     }
 }
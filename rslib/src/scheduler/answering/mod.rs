@@ -16,11 +16,15 @@ use revlog::RevlogEntryPartial;
 
 use super::fsrs::params::ignore_revlogs_before_ms_from_config;
 use super::queue::BuryMode;
+use super::states::fuzz::constrained_fuzz_bounds;
 use super::states::load_balancer::LoadBalancerContext;
 use super::states::steps::LearningSteps;
 use super::states::CardState;
 use super::states::FilteredState;
+use super::states::IntervalKind;
 use super::states::NormalState;
+use super::states::ReschedulingFilterState;
+use super::states::ReviewState;
 use super::states::SchedulingStates;
 use super::states::StateContext;
 use super::timespan::answer_button_time_collapsible;
@@ -53,6 +57,8 @@ pub struct CardAnswer {
     pub answered_at: TimestampMillis,
     pub milliseconds_taken: u32,
     pub custom_data: Option<String>,
+    /// False if the card was answered from outside the study queues, eg by
+    /// grading it in the browser.
     pub from_queue: bool,
 }
 
@@ -62,6 +68,32 @@ impl CardAnswer {
     }
 }
 
+/// The inputs used to fuzz a card's next review intervals, so they can be
+/// reproduced outside of Anki.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchedulingFuzz {
+    /// Derived from the card id and review count. None if fuzz is disabled.
+    pub seed: Option<u64>,
+    /// In range `0.0..1.0`; picks the final interval from the fuzz range.
+    pub factor: Option<f32>,
+    /// If true, the load balancer picks intervals from the fuzz range instead
+    /// of the fuzz factor.
+    pub load_balanced: bool,
+    /// For again/hard/good/easy, the fuzz range of answers that lead to a
+    /// review.
+    pub ranges: [Option<FuzzRange>; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuzzRange {
+    /// The interval before fuzz was applied.
+    pub unfuzzed_days: u32,
+    /// The bounds the fuzzed interval is picked from, before intervals of the
+    /// different buttons are adjusted to be in ascending order.
+    pub lower_days: u32,
+    pub upper_days: u32,
+}
+
 /// Holds the information required to determine a given card's
 /// current state, and to apply a state change to it.
 struct CardStateUpdater {
@@ -225,6 +257,39 @@ impl Rating {
 }
 
 impl Collection {
+    /// Describe the fuzz that [Collection::get_scheduling_states()] applies to
+    /// the card's review intervals.
+    pub fn get_scheduling_fuzz(&mut self, cid: CardId) -> Result<SchedulingFuzz> {
+        let card = self.storage.get_card(cid)?.or_not_found(cid)?;
+        let ctx = self.card_state_updater(card)?;
+        let mut state_ctx = ctx.state_context(None);
+        state_ctx.fuzz_factor = None;
+        let maximum = state_ctx.maximum_review_interval.max(1);
+        let unfuzzed = ctx.current_card_state().next_states(&state_ctx);
+        let range = |state: CardState| {
+            scheduled_review(state).map(|review| {
+                let (lower_days, upper_days) =
+                    constrained_fuzz_bounds(review.scheduled_days as f32, 1, maximum);
+                FuzzRange {
+                    unfuzzed_days: review.scheduled_days,
+                    lower_days,
+                    upper_days,
+                }
+            })
+        };
+        Ok(SchedulingFuzz {
+            seed: ctx.fuzz_seed,
+            factor: get_fuzz_factor(ctx.fuzz_seed),
+            load_balanced: self.get_config_bool(BoolKey::LoadBalancerEnabled),
+            ranges: [
+                range(unfuzzed.again),
+                range(unfuzzed.hard),
+                range(unfuzzed.good),
+                range(unfuzzed.easy),
+            ],
+        })
+    }
+
     /// Return the next states that will be applied for each answer button.
     pub fn get_scheduling_states(&mut self, cid: CardId) -> Result<SchedulingStates> {
         let card = self.storage.get_card(cid)?.or_not_found(cid)?;
@@ -368,7 +433,6 @@ impl Collection {
             }
         }
 
-        // Handle queue updates based on from_queue flag
         if answer.from_queue {
             self.update_queues_after_answering_card(
                 &card,
@@ -381,11 +445,25 @@ impl Collection {
                     }))
                 ),
             )?;
+        } else {
+            self.update_queues_after_grading_card(&card, timing)?;
         }
 
         Ok(())
     }
 
+    /// The card as `answer` would leave it, without saving anything. Unlike
+    /// [Collection::answer_card], no review is logged, siblings are not buried
+    /// and leeches are not tagged.
+    pub(crate) fn card_after_answer(&mut self, card: Card, answer: &CardAnswer) -> Result<Card> {
+        let mut updater = self.card_state_updater(card)?;
+        let current_state = updater.current_card_state();
+        updater.apply_study_state(current_state, answer.new_state)?;
+        let mut card = updater.into_card();
+        card.last_review_time = Some(answer.answered_at.as_secs());
+        Ok(card)
+    }
+
     fn maybe_bury_siblings(&mut self, card: &Card, config: &DeckConfig) -> Result<()> {
         let bury_mode = BuryMode::from_deck_config(config);
         if bury_mode.any_burying() {
@@ -437,17 +515,36 @@ impl Collection {
         )
     }
 
-    fn card_state_updater(&mut self, mut card: Card) -> Result<CardStateUpdater> {
-        let timing = self.timing_today()?;
+    fn card_state_updater(&mut self, card: Card) -> Result<CardStateUpdater> {
         let deck = self
             .storage
             .get_deck(card.deck_id)?
             .or_not_found(card.deck_id)?;
         let config = self.home_deck_config(deck.config_id(), card.original_deck_id)?;
+        self.card_state_updater_with_config(card, deck, config, None)
+    }
+
+    /// If FSRS is enabled, `fsrs` may provide a model already built from the
+    /// parameters of `config`, to avoid building one for each card.
+    fn card_state_updater_with_config(
+        &mut self,
+        mut card: Card,
+        deck: Deck,
+        config: DeckConfig,
+        fsrs: Option<&FSRS>,
+    ) -> Result<CardStateUpdater> {
+        let timing = self.timing_today()?;
         let fsrs_enabled = self.get_config_bool(BoolKey::Fsrs);
         let fsrs_next_states = if fsrs_enabled {
             let params = config.fsrs_params();
-            let fsrs = FSRS::new(Some(params))?;
+            let built;
+            let fsrs = match fsrs {
+                Some(fsrs) => fsrs,
+                None => {
+                    built = FSRS::new(Some(params))?;
+                    &built
+                }
+            };
             card.decay = Some(get_decay_from_params(params));
             if card.memory_state.is_none() && card.ctype != CardType::New {
                 // Card has been moved or imported into an FSRS deck after params were set,
@@ -506,6 +603,29 @@ impl Collection {
         })
     }
 
+    /// The interval in days `card` would get if it were answered Good now and
+    /// its preset were `config`, or 0 if it would be shown again today. When
+    /// FSRS is enabled, `fsrs` must be built from the parameters of `config`.
+    pub(crate) fn good_interval_with_config(
+        &mut self,
+        card: Card,
+        config: DeckConfig,
+        fsrs: Option<&FSRS>,
+    ) -> Result<u32> {
+        let deck = self
+            .storage
+            .get_deck(card.deck_id)?
+            .or_not_found(card.deck_id)?;
+        let ctx = self.card_state_updater_with_config(card, deck, config, fsrs)?;
+        let next = ctx
+            .current_card_state()
+            .next_states(&ctx.state_context(None));
+        Ok(match next.good.interval_kind() {
+            IntervalKind::InDays(days) => days,
+            IntervalKind::InSecs(_) => 0,
+        })
+    }
+
     pub(crate) fn home_deck_config(
         &self,
         config_id: Option<DeckConfigId>,
@@ -624,6 +744,17 @@ impl Card {
     }
 }
 
+/// The review state of `state`, if it will be scheduled as a (fuzzed) review.
+fn scheduled_review(state: CardState) -> Option<ReviewState> {
+    match state {
+        CardState::Normal(NormalState::Review(review))
+        | CardState::Filtered(FilteredState::Rescheduling(ReschedulingFilterState {
+            original_state: NormalState::Review(review),
+        })) => Some(review),
+        _ => None,
+    }
+}
+
 /// Return a consistent seed for a given card at a given number of reps.
 /// If for_reschedule is true, we use card.reps - 1 to match the previous
 /// review.
@@ -824,6 +955,32 @@ pub(crate) mod test {
         Ok(())
     }
 
+    #[test]
+    fn scheduling_fuzz() -> Result<()> {
+        let mut col = Collection::new();
+        let note = NoteAdder::basic(&mut col).add(&mut col);
+        let cid = col
+            .storage
+            .all_card_ids_of_note_in_template_order(note.id)?[0];
+
+        let fuzz = col.get_scheduling_fuzz(cid)?;
+        // fuzz is disabled in tests
+        assert_eq!(fuzz.seed, None);
+        assert_eq!(fuzz.factor, None);
+        // only easy graduates a new card with the default config
+        assert_eq!(&fuzz.ranges[..3], &[None, None, None]);
+        assert_eq!(
+            fuzz.ranges[3],
+            Some(FuzzRange {
+                unfuzzed_days: 4,
+                lower_days: 3,
+                upper_days: 5,
+            })
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn elapsed_secs() -> Result<()> {
         let mut col = Collection::new();
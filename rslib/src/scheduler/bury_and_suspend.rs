@@ -1,6 +1,8 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use std::collections::HashMap;
+
 use anki_proto::scheduler::bury_or_suspend_cards_request::Mode as BuryOrSuspendMode;
 use anki_proto::scheduler::unbury_deck_request::Mode as UnburyDeckMode;
 
@@ -129,6 +131,53 @@ impl Collection {
         })
     }
 
+    /// Suspend the cards whose lapses have reached the leech threshold of
+    /// their deck's preset, in the deck and its children or in the whole
+    /// collection. Returns the number of cards suspended.
+    pub fn suspend_leech_cards(&mut self, deck_id: Option<DeckId>) -> Result<OpOutput<usize>> {
+        self.suspend_leech_cards_returning_ids(deck_id)
+            .map(|out| out.map(|cids| cids.len()))
+    }
+
+    /// As [Collection::suspend_leech_cards], returning the ids of the
+    /// suspended cards.
+    pub(crate) fn suspend_leech_cards_returning_ids(
+        &mut self,
+        deck_id: Option<DeckId>,
+    ) -> Result<OpOutput<Vec<CardId>>> {
+        let search = match deck_id {
+            Some(did) => {
+                let deck = self.get_deck(did)?.or_not_found(did)?;
+                SearchNode::from_deck_name(&deck.human_name())
+            }
+            None => SearchNode::WholeCollection,
+        };
+        self.transact(Op::Suspend, |col| {
+            let mut thresholds = HashMap::new();
+            let mut leeches = vec![];
+            for card in col.all_cards_for_search(search)? {
+                if card.queue == CardQueue::Suspended || card.lapses == 0 {
+                    continue;
+                }
+                let did = card.original_or_current_deck_id();
+                let threshold = match thresholds.get(&did) {
+                    Some(&threshold) => threshold,
+                    None => {
+                        let threshold = col.deck_config_for_card(&card)?.inner.leech_threshold;
+                        thresholds.insert(did, threshold);
+                        threshold
+                    }
+                };
+                if threshold > 0 && card.lapses >= threshold {
+                    leeches.push(card);
+                }
+            }
+            let cids = leeches.iter().map(|card| card.id).collect();
+            col.bury_or_suspend_cards_inner(leeches, BuryOrSuspendMode::Suspend)?;
+            Ok(cids)
+        })
+    }
+
     pub(crate) fn bury_siblings(
         &mut self,
         card: &Card,
@@ -169,8 +218,12 @@ mod test {
     use crate::card::Card;
     use crate::card::CardQueue;
     use crate::collection::Collection;
+    use crate::decks::DeckId;
+    use crate::error::Result;
     use crate::search::SortMode;
     use crate::search::StateKind;
+    use crate::tests::DeckAdder;
+    use crate::tests::NoteAdder;
 
     #[test]
     fn unbury() {
@@ -201,4 +254,29 @@ mod test {
         col.unbury_if_day_rolled_over(timing).unwrap();
         assert_count(&mut col, 0);
     }
+
+    #[test]
+    fn suspend_leeches() -> Result<()> {
+        let mut col = Collection::new();
+        let child = DeckAdder::new("Default::child").add(&mut col);
+        let mut cids = vec![];
+        for (did, lapses) in [(DeckId(1), 8), (DeckId(1), 7), (child.id, 9)] {
+            let note = NoteAdder::basic(&mut col).add(&mut col);
+            let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+            card.deck_id = did;
+            card.lapses = lapses;
+            col.storage.update_card(&card)?;
+            cids.push(card.id);
+        }
+
+        assert_eq!(col.suspend_leech_cards(Some(child.id))?.output, 1);
+        let suspended = col.suspend_leech_cards_returning_ids(None)?.output;
+        assert_eq!(suspended, [cids[0]]);
+        assert_eq!(
+            col.storage.get_card(cids[1])?.unwrap().queue,
+            CardQueue::New
+        );
+        assert_eq!(col.suspend_leech_cards(None)?.output, 0);
+        Ok(())
+    }
 }
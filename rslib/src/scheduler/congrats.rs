@@ -20,6 +20,7 @@ impl Collection {
         let info = self.storage.congrats_info(&deck, today)?;
         let is_filtered_deck = deck.is_filtered();
         let deck_description = deck.rendered_description();
+        let new_paused = self.new_paused_in_deck_tree(deck.id)?;
         let secs_until_next_learn = if info.next_learn_due == 0 {
             // signal to the frontend that no learning cards are due later
             86_400
@@ -37,6 +38,7 @@ impl Collection {
             secs_until_next_learn,
             bridge_commands_supported: true,
             deck_description,
+            new_paused,
         })
     }
 }
@@ -60,7 +62,8 @@ mod test {
                 is_filtered_deck: false,
                 secs_until_next_learn: 86_400,
                 bridge_commands_supported: true,
-                deck_description: "".to_string()
+                deck_description: "".to_string(),
+                new_paused: false,
             }
         )
     }
@@ -7,6 +7,7 @@ mod custom_study;
 use crate::config::ConfigKey;
 use crate::config::SchedulerVersion;
 use crate::decks::FilteredDeck;
+use crate::decks::FilteredSearchOrder;
 use crate::decks::FilteredSearchTerm;
 use crate::error::FilteredDeckError;
 use crate::prelude::*;
@@ -62,6 +63,36 @@ impl Collection {
         })
     }
 
+    /// Add a filtered deck called `name` with a single search term, and
+    /// build it. `order` is a [FilteredSearchOrder] value. As with
+    /// [Collection::add_or_update_filtered_deck], adding is aborted if the
+    /// search does not match any cards.
+    pub fn create_filtered_deck_from_search(
+        &mut self,
+        name: &str,
+        search: &str,
+        limit: u32,
+        order: u32,
+    ) -> Result<OpOutput<DeckId>> {
+        let order = i32::try_from(order)
+            .ok()
+            .and_then(|order| FilteredSearchOrder::try_from(order).ok())
+            .or_invalid("invalid filtered deck order")?;
+        let mut deck = Deck::new_filtered();
+        let config = deck.filtered_mut()?;
+        config.search_terms = vec![FilteredSearchTerm {
+            search: search.into(),
+            limit,
+            order: order as i32,
+        }];
+        self.add_or_update_filtered_deck(FilteredDeckForUpdate {
+            id: DeckId(0),
+            human_name: name.into(),
+            config: config.clone(),
+            allow_empty: false,
+        })
+    }
+
     pub fn empty_filtered_deck(&mut self, did: DeckId) -> Result<OpOutput<()>> {
         self.transact(Op::EmptyFilteredDeck, |col| {
             let deck = col.get_deck(did)?.or_not_found(did)?;
@@ -69,6 +100,14 @@ impl Collection {
         })
     }
 
+    /// The cards in filtered deck `deck_id`, with the due values they will
+    /// be given back when returned to their home decks.
+    pub fn get_card_ids_by_original_due(&mut self, deck_id: DeckId) -> Result<Vec<(CardId, i32)>> {
+        let deck = self.get_deck(deck_id)?.or_not_found(deck_id)?;
+        deck.filtered()?;
+        self.storage.card_original_dues_in_deck(deck_id)
+    }
+
     // Unlike the old Python code, this also marks the cards as modified.
     pub fn rebuild_filtered_deck(&mut self, did: DeckId) -> Result<OpOutput<usize>> {
         self.transact(Op::RebuildFilteredDeck, |col| {
@@ -255,3 +294,62 @@ fn apply_update_to_filtered_deck(deck: &mut Deck, update: FilteredDeckForUpdate)
     deck.name = NativeDeckName::from_human_name(&update.human_name);
     deck.kind = DeckKind::Filtered(update.config);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn create_from_search() -> Result<()> {
+        let mut col = Collection::new();
+        NoteAdder::basic(&mut col)
+            .fields(&["one", ""])
+            .add(&mut col);
+        NoteAdder::basic(&mut col)
+            .fields(&["two", ""])
+            .add(&mut col);
+
+        let did = col
+            .create_filtered_deck_from_search(
+                "Filtered",
+                "one",
+                10,
+                FilteredSearchOrder::Due as u32,
+            )?
+            .output;
+        let deck = col.get_deck(did)?.unwrap();
+        assert_eq!(deck.human_name(), "Filtered");
+        assert_eq!(deck.filtered()?.search_terms.len(), 1);
+        assert_eq!(col.storage.all_cards_in_single_deck(did)?.len(), 1);
+
+        // searches matching nothing, and unknown orders, are rejected
+        assert!(col
+            .create_filtered_deck_from_search("Empty", "three", 10, 0)
+            .is_err());
+        assert!(col
+            .create_filtered_deck_from_search("Bad order", "two", 10, 999)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn original_dues() -> Result<()> {
+        let mut col = Collection::new();
+        let note = NoteAdder::basic(&mut col).add(&mut col);
+        let mut card = col.storage.all_cards_of_note(note.id)?.remove(0);
+        card.due = 7;
+        col.storage.update_card(&card)?;
+
+        let did = col
+            .create_filtered_deck_from_search("Filtered", "", 10, 0)?
+            .output;
+        assert_eq!(col.get_card_ids_by_original_due(did)?, [(card.id, 7)]);
+        assert!(matches!(
+            col.get_card_ids_by_original_due(DeckId(1)),
+            Err(AnkiError::FilteredDeckError {
+                source: FilteredDeckError::FilteredDeckRequired
+            })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+use fsrs::FSRSItem;
+use fsrs::FSRS;
+
+use super::memory_state::get_decay_from_params;
+use super::params::fsrs_items_for_training;
+use crate::prelude::*;
+
+/// Fewer held-out reviews than this can't say much about calibration.
+pub(crate) const MIN_HOLDOUT_REVIEWS: usize = 50;
+
+/// Predicted retrievabilities are clamped to this distance from 0 and 1 when
+/// calculating log loss.
+const LOG_LOSS_EPSILON: f32 = 1e-4;
+
+/// How well the predictions of a set of FSRS parameters match the outcomes of
+/// the most recent reviews.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct FsrsCalibration {
+    /// Equal-width buckets of predicted retrievability, lowest first.
+    pub buckets: Vec<CalibrationBucket>,
+    /// The number of reviews whose outcome was predicted.
+    pub holdout_reviews: usize,
+    /// The number of earlier reviews that were not evaluated.
+    pub training_reviews: usize,
+    pub log_loss: f32,
+    /// Mean predicted retrievability minus the observed pass rate. Positive
+    /// values mean the parameters are overconfident.
+    pub bias: f32,
+}
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct CalibrationBucket {
+    pub lower: f32,
+    pub upper: f32,
+    pub count: u32,
+    /// [None] for empty buckets.
+    pub mean_predicted: Option<f32>,
+    /// The fraction of reviews in the bucket that were not answered with
+    /// Again. [None] for empty buckets.
+    pub observed: Option<f32>,
+}
+
+impl Collection {
+    /// Evaluate `params` against the reviews matching `search`. Review
+    /// history is split chronologically, and the predicted retrievability of
+    /// the last `holdout_fraction` of reviews is compared with their actual
+    /// outcome. Empty params evaluate the defaults.
+    pub fn compute_fsrs_calibration(
+        &mut self,
+        params: &[f32],
+        search: impl TryIntoSearch,
+        ignore_revlogs_before: TimestampMillis,
+        holdout_fraction: f32,
+        bucket_count: usize,
+    ) -> Result<FsrsCalibration> {
+        require!(
+            holdout_fraction > 0.0 && holdout_fraction < 1.0,
+            "holdout fraction must be between 0 and 1"
+        );
+        require!(
+            (1..=100).contains(&bucket_count),
+            "bucket count must be between 1 and 100"
+        );
+        let fsrs = FSRS::new(Some(params))?;
+        let timing = self.timing_today()?;
+        let revlogs = self.revlog_for_srs(search)?;
+        let (mut items, _) =
+            fsrs_items_for_training(revlogs, timing.next_day_at, ignore_revlogs_before);
+        let split = items.len() - (items.len() as f32 * holdout_fraction).round() as usize;
+        let holdout = items.split_off(split);
+        if holdout.len() < MIN_HOLDOUT_REVIEWS {
+            return Err(AnkiError::FsrsInsufficientData);
+        }
+        let mut calibration =
+            calibrate(&fsrs, get_decay_from_params(params), holdout, bucket_count)?;
+        calibration.training_reviews = items.len();
+        Ok(calibration)
+    }
+}
+
+/// Predict the outcome of the last review of each item from the reviews
+/// preceding it.
+fn calibrate(
+    fsrs: &FSRS,
+    decay: f32,
+    items: Vec<FSRSItem>,
+    bucket_count: usize,
+) -> Result<FsrsCalibration> {
+    let width = 1.0 / bucket_count as f32;
+    let mut sums = vec![(0u32, 0.0f32, 0u32); bucket_count];
+    let mut log_loss = 0.0;
+    let mut bias = 0.0;
+    let holdout_reviews = items.len();
+    for mut item in items {
+        let Some(last) = item.reviews.pop() else {
+            continue;
+        };
+        let state = fsrs.memory_state(item, None)?;
+        let predicted = fsrs.current_retrievability(state, last.delta_t, decay);
+        let passed = last.rating > 1;
+        let idx = ((predicted / width) as usize).min(bucket_count - 1);
+        let (count, predicted_sum, passes) = &mut sums[idx];
+        *count += 1;
+        *predicted_sum += predicted;
+        *passes += passed as u32;
+
+        let p = predicted.clamp(LOG_LOSS_EPSILON, 1.0 - LOG_LOSS_EPSILON);
+        log_loss -= if passed { p.ln() } else { (1.0 - p).ln() };
+        bias += predicted - passed as u32 as f32;
+    }
+    let buckets = sums
+        .into_iter()
+        .enumerate()
+        .map(|(idx, (count, predicted_sum, passes))| CalibrationBucket {
+            lower: idx as f32 * width,
+            upper: (idx + 1) as f32 * width,
+            count,
+            mean_predicted: (count > 0).then(|| predicted_sum / count as f32),
+            observed: (count > 0).then(|| passes as f32 / count as f32),
+        })
+        .collect();
+    let total = holdout_reviews.max(1) as f32;
+    Ok(FsrsCalibration {
+        buckets,
+        holdout_reviews,
+        training_reviews: 0,
+        log_loss: log_loss / total,
+        bias: bias / total,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use fsrs::FSRSReview;
+
+    use super::*;
+
+    fn item(delta_ts: &[u32], last_rating: u32) -> FSRSItem {
+        let mut reviews = delta_ts
+            .iter()
+            .map(|&delta_t| FSRSReview { rating: 3, delta_t })
+            .collect::<Vec<_>>();
+        reviews.last_mut().unwrap().rating = last_rating;
+        FSRSItem { reviews }
+    }
+
+    #[test]
+    fn bucketing() -> Result<()> {
+        let fsrs = FSRS::new(Some(&[]))?;
+        let decay = get_decay_from_params(&[]);
+        // reviewed shortly after learning, and long overdue
+        let items = vec![item(&[0, 1], 3), item(&[0, 1], 1), item(&[0, 1000], 1)];
+        let calibration = calibrate(&fsrs, decay, items, 10)?;
+        assert_eq!(calibration.holdout_reviews, 3);
+        assert_eq!(calibration.buckets.len(), 10);
+        assert_eq!(calibration.buckets[0].lower, 0.0);
+        assert!((calibration.buckets[9].upper - 1.0).abs() < 1e-6);
+        assert_eq!(calibration.buckets.iter().map(|b| b.count).sum::<u32>(), 3);
+
+        let recent = &calibration.buckets[9];
+        assert_eq!(recent.count, 2);
+        assert_eq!(recent.observed, Some(0.5));
+        assert!(recent.mean_predicted.unwrap() >= 0.9);
+        let overdue = calibration
+            .buckets
+            .iter()
+            .take(9)
+            .find(|b| b.count > 0)
+            .unwrap();
+        assert_eq!(overdue.observed, Some(0.0));
+        assert!(calibration
+            .buckets
+            .iter()
+            .any(|b| b.mean_predicted.is_none()));
+        // predicted mostly passes, but 2 of 3 failed
+        assert!(calibration.bias > 0.0);
+        assert!(calibration.log_loss > 0.0);
+        Ok(())
+    }
+
+    #[test]
+    fn insufficient_history() -> Result<()> {
+        let mut col = Collection::new();
+        assert!(matches!(
+            col.compute_fsrs_calibration(&[], "", 0.into(), 0.2, 10),
+            Err(AnkiError::FsrsInsufficientData)
+        ));
+        assert!(col
+            .compute_fsrs_calibration(&[], "", 0.into(), 1.0, 10)
+            .is_err());
+        Ok(())
+    }
+}
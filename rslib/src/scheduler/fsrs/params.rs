@@ -340,7 +340,7 @@ pub struct ComputeParamsProgress {
 }
 
 /// Convert a series of revlog entries sorted by card id into FSRS items.
-fn fsrs_items_for_training(
+pub(crate) fn fsrs_items_for_training(
     revlogs: Vec<RevlogEntry>,
     next_day_at: TimestampSecs,
     review_revlogs_before: TimestampMillis,
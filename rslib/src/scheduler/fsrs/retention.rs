@@ -4,9 +4,12 @@ use anki_proto::scheduler::SimulateFsrsReviewRequest;
 use fsrs::extract_simulator_config;
 use fsrs::SimulatorConfig;
 use fsrs::FSRS;
+use fsrs::FSRS5_DEFAULT_DECAY;
 
+use crate::card::FsrsMemoryState;
 use crate::prelude::*;
 use crate::revlog::RevlogEntry;
+use crate::search::SearchNode;
 
 #[derive(Default, Clone, Copy, Debug)]
 pub struct ComputeRetentionProgress {
@@ -14,6 +17,33 @@ pub struct ComputeRetentionProgress {
     pub total: u32,
 }
 
+/// The current retrievability of every reviewed card in the collection.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct RetentionDistribution {
+    /// (lower bound, card count) for each 10% wide bucket, lowest first.
+    pub buckets: Vec<(f32, u32)>,
+    pub mean: f32,
+    /// The retrievability 10% of cards fall below.
+    pub p10: f32,
+    pub p50: f32,
+}
+
+/// The memory state of a card scheduled with FSRS.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FsrsCardState {
+    pub card_id: CardId,
+    pub stability: f32,
+    /// In the range 1.0-10.0.
+    pub difficulty: f32,
+    /// [None] for cards last reviewed before review times were recorded.
+    pub last_review: Option<TimestampSecs>,
+    pub retrievability: f32,
+}
+
+/// Retention assumed when estimating a memory state from SM-2 ease and
+/// interval.
+const SM2_ESTIMATE_RETENTION: f32 = 0.9;
+
 impl Collection {
     pub fn compute_optimal_retention(&mut self, req: SimulateFsrsReviewRequest) -> Result<f32> {
         let mut anki_progress = self.new_progress_handler::<ComputeRetentionProgress>();
@@ -39,6 +69,102 @@ impl Collection {
             .clamp(0.7, 0.95))
     }
 
+    /// Calculate the current retrievability of all cards that have been
+    /// reviewed. Cards without a memory state use an estimate derived from
+    /// their ease factor and interval.
+    pub fn get_fsrs_retention_for_all_cards(&mut self) -> Result<RetentionDistribution> {
+        let timing = self.timing_today()?;
+        // default parameters are used for SM-2 estimates
+        let fsrs = FSRS::new(Some(&[]))?;
+        let mut retrievabilities = vec![];
+        for card in self.all_cards_for_search("-is:new")? {
+            let state = match card.memory_state {
+                Some(state) => state.into(),
+                None if card.interval == 0 => continue,
+                None => fsrs.memory_state_from_sm2(
+                    card.ease_factor(),
+                    card.interval as f32,
+                    SM2_ESTIMATE_RETENTION,
+                )?,
+            };
+            let elapsed_days = card.days_since_last_review(&timing).unwrap_or_default();
+            retrievabilities.push(fsrs.current_retrievability(
+                state,
+                elapsed_days,
+                card.decay.unwrap_or(FSRS5_DEFAULT_DECAY),
+            ));
+        }
+        Ok(RetentionDistribution::new(retrievabilities))
+    }
+
+    /// The memory state and current retrievability of every card with a
+    /// memory state, optionally limited to a deck and its children. Cards in
+    /// filtered decks are included if their home deck matches.
+    pub fn get_fsrs_state_for_all_cards(
+        &mut self,
+        deck_id: Option<DeckId>,
+    ) -> Result<Vec<FsrsCardState>> {
+        let search = match deck_id {
+            Some(did) => {
+                let deck = self.get_deck(did)?.or_not_found(did)?;
+                SearchNode::from_deck_name(&deck.human_name())
+            }
+            None => SearchNode::WholeCollection,
+        };
+        let timing = self.timing_today()?;
+        let fsrs = FSRS::new(None)?;
+        Ok(self
+            .all_cards_for_search(search)?
+            .into_iter()
+            .filter_map(|card| {
+                let state = card.memory_state?;
+                let elapsed_days = card.days_since_last_review(&timing).unwrap_or_default();
+                Some(FsrsCardState {
+                    card_id: card.id,
+                    stability: state.stability,
+                    difficulty: state.difficulty,
+                    last_review: card.last_review_time,
+                    retrievability: fsrs.current_retrievability(
+                        state.into(),
+                        elapsed_days,
+                        card.decay.unwrap_or(FSRS5_DEFAULT_DECAY),
+                    ),
+                })
+            })
+            .collect())
+    }
+
+    /// Restore previously exported memory states. The retrievability of each
+    /// state is ignored. Cards that no longer exist are skipped; returns the
+    /// number of cards that were updated.
+    pub fn import_fsrs_states(&mut self, states: Vec<FsrsCardState>) -> Result<OpOutput<usize>> {
+        for state in &states {
+            require!(
+                state.stability > 0.0 && (1.0..=10.0).contains(&state.difficulty),
+                "invalid memory state for card {}",
+                state.card_id
+            );
+        }
+        self.transact(Op::UpdateCard, |col| {
+            let usn = col.usn()?;
+            let mut updated = 0;
+            for state in states {
+                let Some(original) = col.storage.get_card(state.card_id)? else {
+                    continue;
+                };
+                let mut card = original.clone();
+                card.memory_state = Some(FsrsMemoryState {
+                    stability: state.stability,
+                    difficulty: state.difficulty,
+                });
+                card.last_review_time = state.last_review;
+                col.update_card_inner(&mut card, original, usn)?;
+                updated += 1;
+            }
+            Ok(updated)
+        })
+    }
+
     pub fn get_optimal_retention_parameters(
         &mut self,
         revlogs: Vec<RevlogEntry>,
@@ -50,6 +176,29 @@ impl Collection {
     }
 }
 
+impl RetentionDistribution {
+    fn new(mut retrievabilities: Vec<f32>) -> Self {
+        let mut buckets: Vec<_> = (0..10).map(|idx| (idx as f32 / 10.0, 0)).collect();
+        if retrievabilities.is_empty() {
+            return RetentionDistribution {
+                buckets,
+                ..Default::default()
+            };
+        }
+        for r in &retrievabilities {
+            buckets[((r * 10.0) as usize).min(9)].1 += 1;
+        }
+        retrievabilities.sort_unstable_by(f32::total_cmp);
+        let percentile = |p: usize| retrievabilities[(retrievabilities.len() - 1) * p / 100];
+        RetentionDistribution {
+            mean: retrievabilities.iter().sum::<f32>() / retrievabilities.len() as f32,
+            p10: percentile(10),
+            p50: percentile(50),
+            buckets,
+        }
+    }
+}
+
 impl From<crate::revlog::RevlogReviewKind> for fsrs::RevlogReviewKind {
     fn from(kind: crate::revlog::RevlogReviewKind) -> Self {
         match kind {
@@ -78,3 +227,121 @@ impl From<crate::revlog::RevlogEntry> for fsrs::RevlogEntry {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::card::CardQueue;
+    use crate::card::CardType;
+
+    #[test]
+    fn distribution() {
+        let dist = RetentionDistribution::new(vec![0.95, 0.05, 1.0, 0.55, 0.9]);
+        assert_eq!(dist.buckets.len(), 10);
+        assert_eq!(dist.buckets[0], (0.0, 1));
+        assert_eq!(dist.buckets[5], (0.5, 1));
+        assert_eq!(dist.buckets[9], (0.9, 3));
+        assert!((dist.mean - 0.69).abs() < 1e-6);
+        assert_eq!(dist.p10, 0.05);
+        assert_eq!(dist.p50, 0.9);
+
+        let empty = RetentionDistribution::new(vec![]);
+        assert_eq!(empty.buckets.iter().map(|b| b.1).sum::<u32>(), 0);
+        assert_eq!(empty.mean, 0.0);
+    }
+
+    #[test]
+    fn sm2_cards_are_estimated() -> Result<()> {
+        let mut col = Collection::new();
+        NoteAdder::basic(&mut col).add(&mut col);
+        NoteAdder::basic(&mut col).add(&mut col);
+        let mut card = col.storage.get_all_cards()[0].clone();
+        card.ctype = CardType::Review;
+        card.queue = CardQueue::Review;
+        card.interval = 10;
+        card.due = col.timing_today()?.days_elapsed as i32 + 5;
+        card.ease_factor = 2500;
+        col.storage.update_card(&card)?;
+
+        let dist = col.get_fsrs_retention_for_all_cards()?;
+        assert_eq!(dist.buckets.iter().map(|b| b.1).sum::<u32>(), 1);
+        assert!(dist.mean > 0.9 && dist.mean <= 1.0);
+        Ok(())
+    }
+
+    #[test]
+    fn fsrs_states_skip_cards_without_memory_state() -> Result<()> {
+        let mut col = Collection::new();
+        let deck = DeckAdder::new("french").add(&mut col);
+        NoteAdder::basic(&mut col).add(&mut col);
+        let note = NoteAdder::basic(&mut col).add(&mut col);
+        let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+        card.deck_id = deck.id;
+        card.ctype = CardType::Review;
+        card.queue = CardQueue::Review;
+        card.interval = 10;
+        card.memory_state = Some(FsrsMemoryState {
+            stability: 10.0,
+            difficulty: 5.0,
+        });
+        card.last_review_time = Some(TimestampSecs::now().adding_secs(-86_400 * 5));
+        col.storage.update_card(&card)?;
+
+        let states = col.get_fsrs_state_for_all_cards(None)?;
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].card_id, card.id);
+        assert_eq!(states[0].stability, 10.0);
+        assert!(states[0].retrievability > 0.9 && states[0].retrievability < 1.0);
+
+        assert_eq!(col.get_fsrs_state_for_all_cards(Some(deck.id))?.len(), 1);
+        assert!(col
+            .get_fsrs_state_for_all_cards(Some(DeckId(1)))?
+            .is_empty());
+
+        // cards in filtered decks belong to their home deck
+        let filtered = DeckAdder::new("filtered").filtered(true).add(&mut col);
+        card.original_deck_id = deck.id;
+        card.deck_id = filtered.id;
+        col.storage.update_card(&card)?;
+        assert_eq!(col.get_fsrs_state_for_all_cards(Some(deck.id))?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn importing_fsrs_states() -> Result<()> {
+        let mut col = Collection::new();
+        let note = NoteAdder::basic(&mut col).add(&mut col);
+        let cid = col.storage.all_cards_of_note(note.id)?[0].id;
+        let state = FsrsCardState {
+            card_id: cid,
+            stability: 12.0,
+            difficulty: 6.0,
+            last_review: Some(TimestampSecs(1_700_000_000)),
+            retrievability: 0.0,
+        };
+        let missing = FsrsCardState {
+            card_id: CardId(123),
+            ..state
+        };
+        assert_eq!(col.import_fsrs_states(vec![state, missing])?.output, 1);
+        let card = col.storage.get_card(cid)?.unwrap();
+        assert_eq!(
+            card.memory_state,
+            Some(FsrsMemoryState {
+                stability: 12.0,
+                difficulty: 6.0
+            })
+        );
+        assert_eq!(card.last_review_time, state.last_review);
+
+        col.undo()?;
+        assert_eq!(col.storage.get_card(cid)?.unwrap().memory_state, None);
+
+        let invalid = FsrsCardState {
+            difficulty: 0.0,
+            ..state
+        };
+        assert!(col.import_fsrs_states(vec![invalid]).is_err());
+        Ok(())
+    }
+}
@@ -22,6 +22,7 @@ mod upgrade;
 
 use chrono::FixedOffset;
 pub use reviews::parse_due_date_str;
+pub use reviews::GradeNowPreview;
 use timing::sched_timing_today;
 use timing::SchedTimingToday;
 
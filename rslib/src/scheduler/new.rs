@@ -63,7 +63,7 @@ impl Card {
     }
 
     /// If the card is new, change its position, and return true.
-    fn set_new_position(&mut self, position: u32) -> bool {
+    pub(crate) fn set_new_position(&mut self, position: u32) -> bool {
         if self.ctype == CardType::New {
             if self.is_filtered() {
                 self.original_due = position as i32;
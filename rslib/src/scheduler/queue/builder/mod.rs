@@ -366,6 +366,40 @@ mod test {
         assert_eq!(col.queue_as_deck_and_template(DeckId(1)), vec![]);
     }
 
+    #[test]
+    fn paused_decks_skip_new_cards_only() -> Result<()> {
+        let mut col = Collection::new();
+        let parent = DeckAdder::new("parent").add(&mut col);
+        let child = DeckAdder::new("parent::child").add(&mut col);
+        for deck in [&parent, &child] {
+            CardAdder::new().deck(deck.id).add(&mut col);
+            CardAdder::new()
+                .due_dates(["0"])
+                .deck(deck.id)
+                .add(&mut col);
+        }
+        col.set_current_deck(parent.id)?;
+        assert_eq!(col.counts(), [2, 0, 2]);
+
+        assert_eq!(col.set_deck_new_paused(child.id, true, false)?.output, 1);
+        assert_eq!(col.counts(), [1, 0, 2]);
+        // a paused parent's limit applies to its subdecks when it is studied
+        col.set_deck_new_paused(child.id, false, false)?;
+        col.set_deck_new_paused(parent.id, true, false)?;
+        assert_eq!(col.counts(), [0, 0, 2]);
+        col.set_current_deck(child.id)?;
+        assert_eq!(col.counts(), [1, 0, 1]);
+
+        assert_eq!(col.set_deck_new_paused(parent.id, true, true)?.output, 1);
+        assert_eq!(col.counts(), [0, 0, 1]);
+        assert!(col.new_paused_in_deck_tree(parent.id)?);
+        assert_eq!(col.set_deck_new_paused(parent.id, false, true)?.output, 2);
+        assert!(!col.new_paused_in_deck_tree(parent.id)?);
+        col.set_current_deck(parent.id)?;
+        assert_eq!(col.counts(), [2, 0, 2]);
+        Ok(())
+    }
+
     #[test]
     fn new_queue_building() -> Result<()> {
         let mut col = Collection::new();
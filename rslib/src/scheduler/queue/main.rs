@@ -21,18 +21,30 @@ pub(crate) enum MainQueueEntryKind {
 impl CardQueues {
     /// Remove the head of the main queue, and update counts.
     pub(super) fn pop_main(&mut self) -> Option<MainQueueEntry> {
-        self.main.pop_front().inspect(|head| {
-            match head.kind {
-                MainQueueEntryKind::New => self.counts.new -= 1,
-                MainQueueEntryKind::Review => self.counts.review -= 1,
-                MainQueueEntryKind::InterdayLearning => {
-                    // the bug causing learning counts to go below zero should
-                    // hopefully be fixed at this point, but ensure we don't wrap
-                    // if it isn't
-                    self.counts.learning = self.counts.learning.saturating_sub(1)
-                }
-            };
-        })
+        self.main
+            .pop_front()
+            .inspect(|head| self.decrement_main_count(head.kind))
+    }
+
+    /// Remove a card from anywhere in the main queue, and update counts.
+    pub(super) fn remove_main_entry(&mut self, id: CardId) -> Option<MainQueueEntry> {
+        let position = self.main.iter().position(|e| e.id == id)?;
+        self.main
+            .remove(position)
+            .inspect(|entry| self.decrement_main_count(entry.kind))
+    }
+
+    fn decrement_main_count(&mut self, kind: MainQueueEntryKind) {
+        match kind {
+            MainQueueEntryKind::New => self.counts.new -= 1,
+            MainQueueEntryKind::Review => self.counts.review -= 1,
+            MainQueueEntryKind::InterdayLearning => {
+                // the bug causing learning counts to go below zero should
+                // hopefully be fixed at this point, but ensure we don't wrap
+                // if it isn't
+                self.counts.learning = self.counts.learning.saturating_sub(1)
+            }
+        };
     }
 
     /// Add an undone entry to the top of the main queue.
@@ -22,6 +22,7 @@ pub(crate) use main::MainQueueEntryKind;
 use self::undo::QueueUpdate;
 use super::states::SchedulingStates;
 use super::timing::SchedTimingToday;
+use crate::card::CardQueue;
 use crate::prelude::*;
 use crate::scheduler::states::load_balancer::LoadBalancer;
 use crate::timestamp::TimestampSecs;
@@ -177,6 +178,15 @@ impl CardQueues {
         }
     }
 
+    /// Remove the provided card from anywhere in the queues, adjusting the
+    /// counts.
+    fn remove_entry(&mut self, id: CardId) -> Option<QueueEntry> {
+        match self.remove_intraday_learning_card(id) {
+            Some(entry) => Some(entry.into()),
+            None => self.remove_main_entry(id).map(Into::into),
+        }
+    }
+
     fn push_undo_entry(&mut self, entry: QueueEntry) {
         match entry {
             QueueEntry::IntradayLearning(entry) => self.push_intraday_learning(entry),
@@ -209,7 +219,13 @@ impl Collection {
     }
 
     pub(crate) fn maybe_clear_study_queues_after_op(&mut self, op: &OpChanges) {
-        if op.op != Op::AnswerCard && op.requires_study_queue_rebuild() {
+        let queues_updated = match op.op {
+            Op::AnswerCard => true,
+            // undoing a grade does not restore the queues
+            Op::GradeNow => !self.undoing_or_redoing(),
+            _ => false,
+        };
+        if !queues_updated && op.requires_study_queue_rebuild() {
             self.state.card_queues = None;
         }
     }
@@ -220,28 +236,82 @@ impl Collection {
         timing: SchedTimingToday,
         is_finished_preview: bool,
     ) -> Result<()> {
-        if let Some(queues) = &mut self.state.card_queues {
-            let entry = queues.pop_entry(card.id)?;
-            let requeued_learning = if is_finished_preview {
-                None
-            } else {
-                queues.maybe_requeue_learning_card(card, timing)
-            };
-            let cutoff_snapshot = queues.update_learning_cutoff_and_count();
-            let queue_build_time = queues.build_time;
-            self.save_queue_update_undo(Box::new(QueueUpdate {
-                entry,
-                learning_requeue: requeued_learning,
-                queue_build_time,
-                cutoff_snapshot,
-            }));
-        } else {
+        if self.state.card_queues.is_none() {
             // we currently allow the queues to be empty for unit tests
+            return Ok(());
+        }
+        let unqueueable_siblings = self.unqueueable_siblings(card)?;
+        let queues = self.state.card_queues.as_mut().unwrap();
+        let entry = queues.pop_entry(card.id)?;
+        // siblings the answer buried may have been queued if their deck
+        // does not bury siblings, and must not be shown
+        let removed_siblings = unqueueable_siblings
+            .into_iter()
+            .filter(|&id| queues.remove_entry(id).is_some())
+            .collect();
+        let requeued_learning = if is_finished_preview {
+            None
+        } else {
+            queues.maybe_requeue_learning_card(card, timing)
+        };
+        let cutoff_snapshot = queues.update_learning_cutoff_and_count();
+        let queue_build_time = queues.build_time;
+        self.save_queue_update_undo(Box::new(QueueUpdate {
+            entry,
+            learning_requeue: requeued_learning,
+            removed_siblings,
+            queue_build_time,
+            cutoff_snapshot,
+        }));
+
+        Ok(())
+    }
+
+    /// Update the queues after `card` was answered from outside of them, eg
+    /// by [Collection::grade_now]. The card and any siblings it buried are
+    /// removed, and the card is requeued if it is still being learnt today.
+    /// If the card was not queued but should be now, the queues are cleared
+    /// so they get rebuilt.
+    pub(crate) fn update_queues_after_grading_card(
+        &mut self,
+        card: &Card,
+        timing: SchedTimingToday,
+    ) -> Result<()> {
+        if self.state.card_queues.is_none() {
+            return Ok(());
+        }
+        let unqueueable_siblings = self.unqueueable_siblings(card)?;
+        let queues = self.state.card_queues.as_mut().unwrap();
+        for sibling in unqueueable_siblings {
+            queues.remove_entry(sibling);
+        }
+        if queues.remove_entry(card.id).is_some() {
+            queues.maybe_requeue_learning_card(card, timing);
+        } else if card.is_intraday_learning() && card.due < timing.next_day_at.0 as i32 {
+            self.clear_study_queues();
         }
 
         Ok(())
     }
 
+    /// Siblings of `card` that are buried or suspended, such as those its
+    /// answer just buried.
+    fn unqueueable_siblings(&self, card: &Card) -> Result<Vec<CardId>> {
+        Ok(self
+            .storage
+            .all_cards_of_note(card.note_id)?
+            .into_iter()
+            .filter(|sibling| {
+                sibling.id != card.id
+                    && matches!(
+                        sibling.queue,
+                        CardQueue::SchedBuried | CardQueue::UserBuried | CardQueue::Suspended
+                    )
+            })
+            .map(|sibling| sibling.id)
+            .collect())
+    }
+
     /// Get the card queues, building if necessary.
     pub(crate) fn get_queues(&mut self) -> Result<&mut CardQueues> {
         let deck = self.get_current_deck()?;
@@ -15,6 +15,8 @@ pub(crate) enum UndoableQueueChange {
 pub(crate) struct QueueUpdate {
     pub entry: QueueEntry,
     pub learning_requeue: Option<LearningQueueEntry>,
+    /// Queued siblings that the answer buried.
+    pub removed_siblings: Vec<CardId>,
     pub queue_build_time: TimestampMillis,
     pub cutoff_snapshot: CutoffSnapshot,
 }
@@ -31,7 +33,12 @@ impl Collection {
     pub(crate) fn undo_queue_change(&mut self, change: UndoableQueueChange) -> Result<()> {
         match change {
             UndoableQueueChange::CardAnswered(update) => {
-                if let Some(queues) = self.get_or_invalidate_queues(update.queue_build_time)? {
+                if !update.removed_siblings.is_empty() {
+                    // the siblings' original positions are not known
+                    self.clear_study_queues();
+                } else if let Some(queues) =
+                    self.get_or_invalidate_queues(update.queue_build_time)?
+                {
                     queues.restore_cutoff(&update.cutoff_snapshot);
                     if let Some(learning) = &update.learning_requeue {
                         queues.remove_intraday_learning_card(learning.id);
@@ -57,7 +64,11 @@ impl Collection {
                 Ok(())
             }
             UndoableQueueChange::CardAnswerUndone(update) => {
-                if let Some(queues) = self.get_or_invalidate_queues(update.queue_build_time)? {
+                if !update.removed_siblings.is_empty() {
+                    self.clear_study_queues();
+                } else if let Some(queues) =
+                    self.get_or_invalidate_queues(update.queue_build_time)?
+                {
                     queues.pop_entry(update.entry.card_id())?;
                     if let Some(learning) = update.learning_requeue {
                         queues.insert_intraday_learning_card(learning);
@@ -82,6 +93,7 @@ mod test {
     use crate::card::CardType;
     use crate::deckconfig::LeechAction;
     use crate::prelude::*;
+    use crate::tests::DeckAdder;
 
     fn add_note(col: &mut Collection, with_reverse: bool) -> Result<NoteId> {
         let nt = col
@@ -257,6 +269,36 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn answer_removes_buried_siblings_from_queues() -> Result<()> {
+        let mut col = Collection::new();
+        let parent = DeckAdder::new("parent").add(&mut col);
+        let child = DeckAdder::new("parent::child")
+            .with_config(|config| config.inner.bury_new = true)
+            .add(&mut col);
+        let nid = add_note(&mut col, true)?;
+        let cids = col.storage.all_card_ids_of_note_in_template_order(nid)?;
+        col.set_deck(&[cids[0]], child.id)?;
+        col.set_deck(&[cids[1]], parent.id)?;
+        col.set_current_deck(parent.id)?;
+
+        // the reverse card is gathered first from a deck that does not bury
+        // siblings, so both cards are queued
+        assert_eq!(col.counts(), [2, 0, 0]);
+        // the child deck buries siblings, so answering the front card takes
+        // the reverse card out of the queues
+        col.answer_easy();
+        assert_eq!(col.counts(), [0, 0, 0]);
+        assert!(col.get_next_card()?.is_none());
+
+        col.undo()?;
+        assert_eq!(col.counts(), [2, 0, 0]);
+        col.redo()?;
+        assert_eq!(col.counts(), [0, 0, 0]);
+
+        Ok(())
+    }
+
     #[test]
     fn redo_after_queue_invalidation_bug() -> Result<()> {
         // add a note to the default deck
@@ -4,6 +4,7 @@
 use std::collections::HashMap;
 use std::sync::LazyLock;
 
+use itertools::Itertools;
 use rand::distr::Distribution;
 use rand::distr::Uniform;
 use regex::Regex;
@@ -17,6 +18,7 @@ use crate::collection::Collection;
 use crate::config::StringKey;
 use crate::error::Result;
 use crate::prelude::*;
+use crate::scheduler::states::fuzz::with_review_fuzz;
 use crate::scheduler::timing::is_unix_epoch_timestamp;
 
 impl Card {
@@ -76,23 +78,44 @@ impl Card {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct DueDateSpecifier {
     min: u32,
     max: u32,
+    /// Spread a single day as the scheduler fuzzes review intervals.
+    fuzz: bool,
     force_reset: bool,
 }
 
+/// Formats the specifier in the syntax accepted by [parse_due_date_str].
+impl std::fmt::Display for DueDateSpecifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.min)?;
+        if self.max != self.min {
+            write!(f, "-{}", self.max)?;
+        }
+        if self.fuzz {
+            write!(f, "~")?;
+        }
+        if self.force_reset {
+            write!(f, "!")?;
+        }
+        Ok(())
+    }
+}
+
 pub fn parse_due_date_str(s: &str) -> Result<DueDateSpecifier> {
     static RE: LazyLock<Regex> = LazyLock::new(|| {
         Regex::new(
             r"(?x)^
             # a number
             (?P<min>\d+)
-            # an optional hyphen and another number
+            # an optional hyphen and another number, or a tilde to fuzz
             (?:
                 -
                 (?P<max>\d+)
+                |
+                (?P<fuzz>~)
             )?
             # optional exclamation mark
             (?P<bang>!)?
@@ -112,10 +135,33 @@ pub fn parse_due_date_str(s: &str) -> Result<DueDateSpecifier> {
     Ok(DueDateSpecifier {
         min: min.min(max),
         max: max.max(min),
+        fuzz: caps.name("fuzz").is_some(),
         force_reset,
     })
 }
 
+/// Move `days` to a day in the range the scheduler would fuzz an interval of
+/// that length over, picked by the card's `fuzz_factor`.
+fn with_due_date_fuzz(days: u32, fuzz_factor: Option<f32>) -> u32 {
+    with_review_fuzz(fuzz_factor, days as f32, 0, u32::MAX)
+}
+
+/// How [Collection::grade_now] would change a card.
+#[derive(Debug, Clone)]
+pub struct GradeNowPreview {
+    pub card: Card,
+    /// True if the card would be forgotten and start relearning.
+    pub lapse: bool,
+}
+
+/// Per-operation state shared by cards having their due date set.
+struct DueDateContext {
+    today: u32,
+    next_day_start: i64,
+    usn: Usn,
+    decks_initial_ease: HashMap<DeckId, f32>,
+}
+
 impl Collection {
     /// `days` should be in a format parseable by `parse_due_date_str`.
     /// If `context` is provided, provided key will be updated with the new
@@ -125,6 +171,19 @@ impl Collection {
         cids: &[CardId],
         days: &str,
         context: Option<StringKey>,
+    ) -> Result<OpOutput<()>> {
+        self.set_due_date_inner(cids, days, context, false)
+    }
+
+    /// If `prefetch_deck_configs` is set, the initial ease of all decks is
+    /// read up front, instead of each deck's preset being looked up the first
+    /// time one of its cards is encountered.
+    pub(crate) fn set_due_date_inner(
+        &mut self,
+        cids: &[CardId],
+        days: &str,
+        context: Option<StringKey>,
+        prefetch_deck_configs: bool,
     ) -> Result<OpOutput<()>> {
         let spec = parse_due_date_str(days)?;
         if cids.is_empty() {
@@ -133,12 +192,10 @@ impl Collection {
                 changes: Default::default(),
             });
         }
-        let usn = self.usn()?;
-        let today = self.timing_today()?.days_elapsed;
-        let next_day_start = self.timing_today()?.next_day_at.0;
-        let mut rng = rand::rng();
-        let distribution = Uniform::new_inclusive(spec.min, spec.max).unwrap();
-        let mut decks_initial_ease: HashMap<DeckId, f32> = HashMap::new();
+        let mut ctx = self.due_date_context()?;
+        if prefetch_deck_configs {
+            ctx.decks_initial_ease = self.initial_ease_of_all_decks()?;
+        }
         self.transact(Op::SetDueDate, |col| {
             let cards = col.all_cards_for_ids(cids, false)?;
             if cards.len() != cids.len() {
@@ -154,34 +211,8 @@ impl Collection {
                     },
                 });
             }
-            for mut card in cards {
-                let deck_id = card.original_deck_id.or(card.deck_id);
-                let ease_factor = match decks_initial_ease.get(&deck_id) {
-                    Some(ease) => *ease,
-                    None => {
-                        let deck = col.get_deck(deck_id)?.or_not_found(deck_id)?;
-                        let config_id = deck.config_id().or_invalid("home deck is filtered")?;
-                        let ease = col
-                            .get_deck_config(config_id, true)?
-                            // just for compiler; get_deck_config() is guaranteed to return a value
-                            .unwrap_or_default()
-                            .inner
-                            .initial_ease;
-                        decks_initial_ease.insert(deck_id, ease);
-                        ease
-                    }
-                };
-                let original = card.clone();
-                let days_from_today = distribution.sample(&mut rng);
-                card.set_due_date(
-                    today,
-                    next_day_start,
-                    days_from_today,
-                    ease_factor,
-                    spec.force_reset,
-                );
-                col.log_manually_scheduled_review(&card, original.interval, usn)?;
-                col.update_card_inner(&mut card, original, usn)?;
+            for card in cards {
+                col.set_due_date_for_card(card, &spec, &mut ctx)?;
             }
             if let Some(key) = context {
                 col.set_config_string_inner(key, days)?;
@@ -190,39 +221,243 @@ impl Collection {
         })
     }
 
+    /// Like [Collection::set_due_date], but each card has its own `days`
+    /// string. Entries sharing a spec are parsed and updated together, in a
+    /// single undoable operation. A result is returned for each entry; entries
+    /// with an invalid spec, a missing card or a card that can't be rescheduled
+    /// are skipped, unless `atomic` is true, in which case the first failure
+    /// aborts the whole operation.
+    pub fn set_due_dates(
+        &mut self,
+        entries: &[(CardId, String)],
+        atomic: bool,
+    ) -> Result<OpOutput<Vec<Result<()>>>> {
+        let mut ctx = self.due_date_context()?;
+        self.transact(Op::SetDueDate, |col| {
+            let results = col.apply_due_dates(entries, atomic, &mut ctx, true)?;
+            Ok(results.into_iter().map(|res| res.map(|_| ())).collect())
+        })
+    }
+
+    /// The cards as [Collection::set_due_dates] would leave them, without
+    /// changing anything. Days picked from a range or fuzzed are random, so a
+    /// later call may place cards differently.
+    pub fn preview_due_dates(
+        &mut self,
+        entries: &[(CardId, String)],
+        atomic: bool,
+    ) -> Result<Vec<Result<Card>>> {
+        let mut ctx = self.due_date_context()?;
+        self.apply_due_dates(entries, atomic, &mut ctx, false)
+    }
+
+    /// Reschedule the cards of `entries`, saving them if `save` is true.
+    /// Returns each entry's card as it was left.
+    fn apply_due_dates(
+        &mut self,
+        entries: &[(CardId, String)],
+        atomic: bool,
+        ctx: &mut DueDateContext,
+        save: bool,
+    ) -> Result<Vec<Result<Card>>> {
+        let mut results: Vec<Option<Result<Card>>> = entries.iter().map(|_| None).collect();
+        let mut groups: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (idx, (_, days)) in entries.iter().enumerate() {
+            groups.entry(days.as_str()).or_default().push(idx);
+        }
+        let cids: Vec<CardId> = entries.iter().map(|(cid, _)| *cid).unique().collect();
+        let mut cards: HashMap<CardId, Card> = self
+            .all_cards_for_ids(&cids, false)?
+            .into_iter()
+            .map(|card| (card.id, card))
+            .collect();
+        // apply in entry order within each spec, so repeated ids behave
+        // predictably
+        for (days, indices) in groups.into_iter().sorted_by_key(|(_, idxs)| idxs[0]) {
+            let spec = match parse_due_date_str(days) {
+                Ok(spec) => spec,
+                Err(err) => {
+                    if atomic {
+                        return Err(err);
+                    }
+                    for idx in indices {
+                        results[idx] = Some(Err(parse_due_date_str(days).unwrap_err()));
+                    }
+                    continue;
+                }
+            };
+            for idx in indices {
+                let cid = entries[idx].0;
+                let card = match cards.get(&cid).cloned().or_not_found(cid) {
+                    Ok(card) => card,
+                    Err(err) if atomic => return Err(err),
+                    Err(err) => {
+                        results[idx] = Some(Err(err));
+                        continue;
+                    }
+                };
+                let card = if save {
+                    self.set_due_date_for_card(card, &spec, ctx)
+                } else {
+                    self.card_with_due_date(card, &spec, ctx)
+                };
+                match card {
+                    Ok(card) => {
+                        cards.insert(cid, card.clone());
+                        results[idx] = Some(Ok(card));
+                    }
+                    Err(err) if atomic => return Err(err),
+                    Err(err) => results[idx] = Some(Err(err)),
+                }
+            }
+        }
+        Ok(results.into_iter().map(Option::unwrap).collect())
+    }
+
+    fn due_date_context(&mut self) -> Result<DueDateContext> {
+        let timing = self.timing_today()?;
+        Ok(DueDateContext {
+            today: timing.days_elapsed,
+            next_day_start: timing.next_day_at.0,
+            usn: self.usn()?,
+            decks_initial_ease: HashMap::new(),
+        })
+    }
+
+    /// The initial ease of every normal deck's preset, read with one query for
+    /// the decks and one for the presets.
+    fn initial_ease_of_all_decks(&self) -> Result<HashMap<DeckId, f32>> {
+        let eases: HashMap<DeckConfigId, f32> = self
+            .storage
+            .all_deck_config()?
+            .into_iter()
+            .map(|config| (config.id, config.inner.initial_ease))
+            .collect();
+        Ok(self
+            .storage
+            .get_all_decks()?
+            .into_iter()
+            .filter_map(|deck| Some((deck.id, *eases.get(&deck.config_id()?)?)))
+            .collect())
+    }
+
+    /// Returns the updated card.
+    fn set_due_date_for_card(
+        &mut self,
+        card: Card,
+        spec: &DueDateSpecifier,
+        ctx: &mut DueDateContext,
+    ) -> Result<Card> {
+        let original = card.clone();
+        let mut card = self.card_with_due_date(card, spec, ctx)?;
+        self.log_manually_scheduled_review(&card, original.interval, ctx.usn)?;
+        self.update_card_inner(&mut card, original, ctx.usn)?;
+        Ok(card)
+    }
+
+    /// The card rescheduled as `spec` asks, without saving it.
+    fn card_with_due_date(
+        &mut self,
+        mut card: Card,
+        spec: &DueDateSpecifier,
+        ctx: &mut DueDateContext,
+    ) -> Result<Card> {
+        let deck_id = card.original_deck_id.or(card.deck_id);
+        let ease_factor = match ctx.decks_initial_ease.get(&deck_id) {
+            Some(ease) => *ease,
+            None => {
+                let deck = self.get_deck(deck_id)?.or_not_found(deck_id)?;
+                let config_id = deck.config_id().or_invalid("home deck is filtered")?;
+                let ease = self
+                    .get_deck_config(config_id, true)?
+                    // just for compiler; get_deck_config() is guaranteed to return a value
+                    .unwrap_or_default()
+                    .inner
+                    .initial_ease;
+                ctx.decks_initial_ease.insert(deck_id, ease);
+                ease
+            }
+        };
+        let distribution = Uniform::new_inclusive(spec.min, spec.max).unwrap();
+        let mut days_from_today = distribution.sample(&mut rand::rng());
+        if spec.fuzz {
+            days_from_today = with_due_date_fuzz(days_from_today, card.get_fuzz_factor(false));
+        }
+        card.set_due_date(
+            ctx.today,
+            ctx.next_day_start,
+            days_from_today,
+            ease_factor,
+            spec.force_reset,
+        );
+        Ok(card)
+    }
+
     pub fn grade_now(&mut self, cids: &[CardId], rating: i32) -> Result<OpOutput<()>> {
         self.transact(Op::GradeNow, |col| {
             for &card_id in cids {
-                let states = col.get_scheduling_states(card_id)?;
-                let new_state = match rating {
-                    0 => states.again,
-                    1 => states.hard,
-                    2 => states.good,
-                    3 => states.easy,
-                    _ => invalid_input!("invalid rating"),
-                };
-                let mut answer: CardAnswer = anki_proto::scheduler::CardAnswer {
-                    card_id: card_id.into(),
-                    current_state: Some(states.current.into()),
-                    new_state: Some(new_state.into()),
-                    rating,
-                    milliseconds_taken: 0,
-                    answered_at_millis: TimestampMillis::now().into(),
-                }
-                .into();
-                // Process the card without updating queues yet
-                answer.from_queue = false;
+                let mut answer = col.grade_now_answer(card_id, rating)?;
                 col.answer_card_inner(&mut answer)?;
             }
             Ok(())
         })
     }
+
+    /// The cards as [Collection::grade_now] would leave them, without changing
+    /// anything. Siblings that would be buried and leech tags are not
+    /// reported.
+    pub fn preview_grade_now(
+        &mut self,
+        cids: &[CardId],
+        rating: i32,
+    ) -> Result<Vec<GradeNowPreview>> {
+        cids.iter()
+            .map(|&card_id| {
+                let original = self.storage.get_card(card_id)?.or_not_found(card_id)?;
+                let answer = self.grade_now_answer(card_id, rating)?;
+                let card = self.card_after_answer(original.clone(), &answer)?;
+                Ok(GradeNowPreview {
+                    lapse: card.lapses > original.lapses,
+                    card,
+                })
+            })
+            .collect()
+    }
+
+    /// The answer [Collection::grade_now] records for a card.
+    fn grade_now_answer(&mut self, card_id: CardId, rating: i32) -> Result<CardAnswer> {
+        let states = self.get_scheduling_states(card_id)?;
+        let new_state = match rating {
+            0 => states.again,
+            1 => states.hard,
+            2 => states.good,
+            3 => states.easy,
+            _ => invalid_input!("invalid rating"),
+        };
+        let mut answer: CardAnswer = anki_proto::scheduler::CardAnswer {
+            card_id: card_id.into(),
+            current_state: Some(states.current.into()),
+            new_state: Some(new_state.into()),
+            rating,
+            milliseconds_taken: 0,
+            answered_at_millis: TimestampMillis::now().into(),
+        }
+        .into();
+        // the card may be anywhere in the queues, or not in them
+        answer.from_queue = false;
+        Ok(answer)
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use proptest::prelude::*;
+
     use super::*;
     use crate::prelude::*;
+    use crate::scheduler::states::fuzz::fuzz_bounds;
+    use crate::tests::DeckAdder;
+    use crate::tests::NoteAdder;
 
     #[test]
     fn parse() -> Result<()> {
@@ -235,6 +470,7 @@ mod test {
             S {
                 min: 5,
                 max: 5,
+                fuzz: false,
                 force_reset: false
             }
         );
@@ -243,6 +479,7 @@ mod test {
             S {
                 min: 5,
                 max: 5,
+                fuzz: false,
                 force_reset: true
             }
         );
@@ -251,6 +488,7 @@ mod test {
             S {
                 min: 50,
                 max: 70,
+                fuzz: false,
                 force_reset: false
             }
         );
@@ -259,12 +497,58 @@ mod test {
             S {
                 min: 50,
                 max: 70,
+                fuzz: false,
                 force_reset: true
             }
         );
+        assert_eq!(
+            parse_due_date_str("7~!")?,
+            S {
+                min: 7,
+                max: 7,
+                fuzz: true,
+                force_reset: true
+            }
+        );
+        // ranges are already spread out
+        assert!(parse_due_date_str("5-7~").is_err());
+        assert!(parse_due_date_str("~7").is_err());
         Ok(())
     }
 
+    proptest! {
+        #[test]
+        fn parsed_range_is_ordered(s in r"[0-9]{1,9}(-[0-9]{1,9}|~)?!?") {
+            let spec = parse_due_date_str(&s).unwrap();
+            prop_assert!(spec.min <= spec.max);
+        }
+
+        #[test]
+        fn parse_arbitrary_strings(s in r"\PC*") {
+            if let Ok(spec) = parse_due_date_str(&s) {
+                prop_assert!(spec.min <= spec.max);
+            }
+        }
+
+        #[test]
+        fn display_round_trips(min: u32, max: u32, fuzz: bool, force_reset: bool) {
+            let spec = DueDateSpecifier {
+                min: min.min(max),
+                max: max.max(min),
+                fuzz: fuzz && min == max,
+                force_reset,
+            };
+            prop_assert_eq!(parse_due_date_str(&spec.to_string()).unwrap(), spec);
+        }
+
+        #[test]
+        fn parse_rejects_overflow(n in u32::MAX as u64 + 1.., small: u32) {
+            for input in [n.to_string(), format!("{small}-{n}"), format!("{n}-{small}!")] {
+                prop_assert!(parse_due_date_str(&input).is_err());
+            }
+        }
+    }
+
     #[test]
     fn due_date() {
         let mut c = Card::new(NoteId(0), 0, DeckId(0), 0);
@@ -318,4 +602,153 @@ mod test {
         assert_eq!(c.interval, 2);
         assert_eq!(c.ease_factor, 2200); // interval doesn't change
     }
+
+    #[test]
+    fn due_date_fuzz_matches_scheduler() {
+        // (days, lowest fuzzed day, highest fuzzed day)
+        for (days, lower, upper) in [
+            (0, 0, 0),
+            (2, 2, 2),
+            (7, 5, 9),
+            (30, 27, 33),
+            (100, 93, 107),
+        ] {
+            assert_eq!(fuzz_bounds(days as f32), (lower, upper));
+            assert_eq!(with_due_date_fuzz(days, Some(0.0)), lower);
+            assert_eq!(with_due_date_fuzz(days, Some(0.9999)), upper);
+            for factor in [0.0, 0.3, 0.7, 0.9999] {
+                assert_eq!(
+                    with_due_date_fuzz(days, Some(factor)),
+                    with_review_fuzz(Some(factor), days as f32, 0, 36500)
+                );
+            }
+            assert_eq!(with_due_date_fuzz(days, None), days);
+        }
+    }
+
+    #[test]
+    fn due_dates_with_per_card_specs() -> Result<()> {
+        let mut col = Collection::new();
+        let nt = col.basic_notetype();
+        for _ in 0..3 {
+            NoteAdder::new(&nt).add(&mut col);
+        }
+        let cids: Vec<CardId> = col.storage.get_all_cards().iter().map(|c| c.id).collect();
+        let today = col.timing_today()?.days_elapsed as i32;
+        let entries = vec![
+            (cids[0], "3".to_string()),
+            (cids[1], "5!".to_string()),
+            (cids[2], "3".to_string()),
+            (cids[0], "x".to_string()),
+            (CardId(123), "1".to_string()),
+        ];
+
+        // an atomic call fails as a whole
+        assert!(col.set_due_dates(&entries, true).is_err());
+        assert_eq!(
+            col.storage.get_card(cids[0])?.unwrap().queue,
+            CardQueue::New
+        );
+
+        let results = col.set_due_dates(&entries, false)?.output;
+        assert!(results[..3].iter().all(|r| r.is_ok()));
+        assert!(matches!(results[3], Err(AnkiError::InvalidInput { .. })));
+        assert!(matches!(results[4], Err(AnkiError::NotFound { .. })));
+        for (cid, days) in [(cids[0], 3), (cids[1], 5), (cids[2], 3)] {
+            let card = col.storage.get_card(cid)?.unwrap();
+            assert_eq!(card.queue, CardQueue::Review);
+            assert_eq!(card.due, today + days);
+        }
+
+        // a card that can't be rescheduled only fails its own entry
+        let filtered = DeckAdder::new("filtered").filtered(true).add(&mut col);
+        let mut orphan = col.storage.get_card(cids[1])?.unwrap();
+        orphan.deck_id = filtered.id;
+        orphan.original_deck_id = DeckId(0);
+        col.storage.update_card(&orphan)?;
+        let entries = vec![(cids[1], "7".to_string()), (cids[2], "7".to_string())];
+        assert!(col.set_due_dates(&entries, true).is_err());
+        let results = col.set_due_dates(&entries, false)?.output;
+        assert!(matches!(results[0], Err(AnkiError::InvalidInput { .. })));
+        assert!(results[1].is_ok());
+        assert_eq!(col.storage.get_card(cids[2])?.unwrap().due, today + 7);
+        Ok(())
+    }
+    #[test]
+    fn grading_updates_queues() -> Result<()> {
+        let mut col = Collection::new();
+        let today = col.timing_today()?.days_elapsed as i32;
+        let mut cids = vec![];
+        for _ in 0..3 {
+            let note = NoteAdder::basic(&mut col).add(&mut col);
+            let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+            card.ctype = CardType::Review;
+            card.queue = CardQueue::Review;
+            card.due = today;
+            card.interval = 1;
+            col.storage.update_card(&card)?;
+            cids.push(card.id);
+        }
+        assert_eq!(col.counts(), [0, 0, 3]);
+        let first = col.get_next_card()?.unwrap().card.id;
+        let others: Vec<_> = cids.into_iter().filter(|&cid| cid != first).collect();
+
+        // a card further down the queue is removed without a rebuild
+        col.grade_now(&[others[0]], 2)?;
+        assert!(col.state.card_queues.is_some());
+        assert_eq!(col.counts(), [0, 0, 2]);
+        assert_eq!(col.get_next_card()?.unwrap().card.id, first);
+
+        // a lapsed card moves to the learning queue
+        col.grade_now(&[others[1]], 0)?;
+        assert!(col.state.card_queues.is_some());
+        assert_eq!(col.counts(), [0, 1, 1]);
+        assert_eq!(col.get_next_card()?.unwrap().card.id, first);
+
+        // undoing invalidates the queues
+        col.undo()?;
+        assert!(col.state.card_queues.is_none());
+        assert_eq!(col.counts(), [0, 0, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn previews_change_nothing() -> Result<()> {
+        let mut col = Collection::new();
+        let today = col.timing_today()?.days_elapsed as i32;
+        let note = NoteAdder::basic(&mut col).add(&mut col);
+        let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+        card.ctype = CardType::Review;
+        card.queue = CardQueue::Review;
+        card.due = today;
+        card.interval = 10;
+        card.ease_factor = 2500;
+        col.storage.update_card(&card)?;
+        let entries = vec![(card.id, "5".to_string()), (CardId(123), "1".to_string())];
+
+        let results = col.preview_due_dates(&entries, false)?;
+        let previewed = results[0].as_ref().unwrap();
+        assert_eq!((previewed.due, previewed.interval), (today + 5, 10));
+        assert!(matches!(results[1], Err(AnkiError::NotFound { .. })));
+        assert!(col.preview_due_dates(&entries, true).is_err());
+
+        let good = &col.preview_grade_now(&[card.id], 2)?[0];
+        assert!(!good.lapse);
+        assert!(good.card.interval > 10);
+        let again = &col.preview_grade_now(&[card.id], 0)?[0];
+        assert!(again.lapse);
+        assert_eq!(again.card.queue, CardQueue::Learn);
+
+        let stored = col.storage.get_card(card.id)?.unwrap();
+        assert_eq!(
+            (stored.due, stored.queue, stored.interval),
+            (today, CardQueue::Review, 10)
+        );
+        assert!(col
+            .storage
+            .get_all_revlog_entries(TimestampSecs(0))?
+            .is_empty());
+        assert_eq!(col.can_undo(), None);
+        Ok(())
+    }
 }
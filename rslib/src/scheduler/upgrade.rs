@@ -128,6 +128,27 @@ impl Collection {
         self.set_schema_modified()
     }
 
+    /// Switch to the v2 or v3 scheduler, upgrading from v1 if required.
+    /// Buried cards are restored, as v3 buries siblings differently. Switching
+    /// back to v2 from v3 is not supported.
+    pub fn set_scheduler_version(&mut self, version: u8) -> Result<()> {
+        require!(matches!(version, 2 | 3), "scheduler version must be 2 or 3");
+        let current = self.get_scheduler_version()?;
+        if current == version {
+            return Ok(());
+        }
+        if current == 3 {
+            return Err(AnkiError::SchedulerUpgradeRequired);
+        }
+        self.transact_no_undo(|col| {
+            col.upgrade_to_v2_scheduler()?;
+            let today = col.timing_today()?.days_elapsed;
+            col.unbury_on_day_rollover(today)?;
+            col.set_config_bool_inner(BoolKey::Sched2021, version == 3)?;
+            Ok(())
+        })
+    }
+
     fn upgrade_cards_to_v2(&mut self) -> Result<()> {
         let guard = self.search_cards_into_table(
             // can't add 'is:learn' here, as it matches on card type, not card queue
@@ -196,4 +217,33 @@ mod test {
         assert_eq!(c.ctype, CardType::Review);
         assert_eq!(c.queue, CardQueue::Review);
     }
+
+    #[test]
+    fn switching_scheduler_version() -> Result<()> {
+        let mut col = Collection::new();
+        assert!(col.v3_enabled());
+        assert!(col.set_scheduler_version(4).is_err());
+        assert_eq!(
+            col.set_scheduler_version(2),
+            Err(AnkiError::SchedulerUpgradeRequired)
+        );
+
+        // upgrading from v1 migrates the collection and unburies cards
+        let note = NoteAdder::basic(&mut col).add(&mut col);
+        let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+        card.queue = CardQueue::SchedBuried;
+        col.storage.update_card(&card)?;
+        col.set_scheduler_version_config_key(SchedulerVersion::V1)?;
+        col.set_scheduler_version(2)?;
+        assert!(col.v2_enabled() && !col.v3_enabled());
+        assert_eq!(
+            col.storage.get_card(card.id)?.unwrap().queue,
+            CardQueue::New
+        );
+
+        assert_eq!(col.get_scheduler_version()?, 2);
+        col.set_scheduler_version(3)?;
+        assert_eq!(col.get_scheduler_version()?, 3);
+        Ok(())
+    }
 }
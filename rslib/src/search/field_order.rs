@@ -0,0 +1,119 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::fmt::Write;
+
+use rusqlite::types::FromSql;
+
+use super::AsReturnItemType;
+use super::ReturnItemType;
+use super::SortMode;
+use super::TryIntoSearch;
+use crate::prelude::*;
+
+/// The note field that search results are ordered by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldOrder {
+    /// The field each notetype designates as its sort field.
+    SortField,
+    /// The field with this name, matched case-insensitively. Notes of
+    /// notetypes without such a field sort as if it were empty.
+    Named(String),
+}
+
+impl Collection {
+    /// Search cards or notes, ordering them by a field of their note. The
+    /// field's text is compared as the browser compares sort fields, and
+    /// ties are broken by note and then template, so the order is stable
+    /// and can be paged through.
+    pub fn search_in_field_order<T, N>(
+        &mut self,
+        search: N,
+        order: &FieldOrder,
+        reverse: bool,
+    ) -> Result<Vec<T>>
+    where
+        N: TryIntoSearch,
+        T: FromSql + AsReturnItemType,
+    {
+        let key = match order {
+            FieldOrder::SortField => "n.sfld".to_string(),
+            FieldOrder::Named(name) => self.named_field_sort_key(name)?,
+        };
+        let direction = if reverse { "desc" } else { "asc" };
+        let mut clause = format!("{key} collate nocase {direction}, n.id {direction}");
+        if T::as_return_item_type() == ReturnItemType::Cards {
+            write!(clause, ", c.ord {direction}").unwrap();
+        }
+        self.search(search, SortMode::Custom(clause))
+    }
+
+    /// An SQL expression for the text of the field called `name`, which
+    /// may be at a different position in each notetype.
+    fn named_field_sort_key(&mut self, name: &str) -> Result<String> {
+        let mut key = "case n.mid".to_string();
+        let mut found = false;
+        for notetype in self.get_all_notetypes()? {
+            if let Some(ord) = notetype.get_field_ord(name) {
+                write!(
+                    key,
+                    " when {} then sort_field_at_index(n.flds, {ord})",
+                    notetype.id
+                )
+                .unwrap();
+                found = true;
+            }
+        }
+        require!(found, "no notetype has a field named '{name}'");
+        key.push_str(" else '' end");
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn field_order_is_stable() -> Result<()> {
+        let mut col = Collection::new();
+        let basic = col.get_notetype_by_name("Basic")?.unwrap();
+        let mut nids = vec![];
+        for (front, back) in [("b", "<b>Z</b>"), ("A", "y"), ("a", "y"), ("c", "x")] {
+            let mut note = basic.new_note();
+            note.set_field(0, front)?;
+            note.set_field(1, back)?;
+            col.add_note(&mut note, DeckId(1))?;
+            nids.push(note.id);
+        }
+        let cloze = col.get_notetype_by_name("Cloze")?.unwrap();
+        let mut note = cloze.new_note();
+        note.set_field(0, "{{c1::B}} {{c2::b}}")?;
+        col.add_note(&mut note, DeckId(1))?;
+        nids.push(note.id);
+
+        // case is ignored, and equal keys are in creation order
+        let notes: Vec<NoteId> = col.search_in_field_order("", &FieldOrder::SortField, false)?;
+        assert_eq!(notes, [nids[1], nids[2], nids[0], nids[3], nids[4]]);
+        let reversed: Vec<NoteId> = col.search_in_field_order("", &FieldOrder::SortField, true)?;
+        assert_eq!(reversed, notes.iter().rev().copied().collect::<Vec<_>>());
+
+        // html is stripped from other fields, and the cloze note has no Back
+        // field
+        let notes: Vec<NoteId> =
+            col.search_in_field_order("", &FieldOrder::Named("back".into()), false)?;
+        assert_eq!(notes, [nids[4], nids[3], nids[1], nids[2], nids[0]]);
+
+        // cards of the same note are in template order
+        let cards: Vec<CardId> =
+            col.search_in_field_order("", &FieldOrder::Named("Text".into()), false)?;
+        let cloze_cards = col.storage.all_cards_of_note(nids[4])?;
+        assert_eq!(cards.len(), 6);
+        assert_eq!(&cards[4..], [cloze_cards[0].id, cloze_cards[1].id]);
+
+        assert!(col
+            .search_in_field_order::<NoteId, _>("", &FieldOrder::Named("nope".into()), false)
+            .is_err());
+        Ok(())
+    }
+}
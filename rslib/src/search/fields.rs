@@ -0,0 +1,86 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::collections::HashSet;
+use std::slice;
+
+use super::parse_search;
+use super::parser::Node;
+use super::parser::SearchNode;
+use crate::prelude::*;
+use crate::text::glob_matcher;
+use crate::text::normalize_to_nfc;
+
+impl Collection {
+    /// The field names of field-qualified terms in `search` that don't match
+    /// a field of any notetype, in the order they first appear. Like in the
+    /// desktop browser, such terms match nothing rather than making the search
+    /// fail.
+    pub fn unknown_search_fields(&mut self, search: &str) -> Result<Vec<String>> {
+        let nodes = parse_search(search)?;
+        let mut qualifiers = vec![];
+        collect_field_qualifiers(&nodes, &mut qualifiers);
+        if qualifiers.is_empty() {
+            return Ok(vec![]);
+        }
+        let field_names: HashSet<String> = self
+            .get_all_notetypes()?
+            .iter()
+            .flat_map(|nt| nt.fields.iter().map(|field| field.name.clone()))
+            .collect();
+        let mut unknown: Vec<String> = vec![];
+        for qualifier in qualifiers {
+            let qualifier = normalize_to_nfc(qualifier);
+            let matches = glob_matcher(&qualifier);
+            if !field_names.iter().any(|name| matches(name))
+                && !unknown.iter().any(|name| *name == qualifier)
+            {
+                unknown.push(qualifier.into_owned());
+            }
+        }
+        Ok(unknown)
+    }
+}
+
+fn collect_field_qualifiers<'a>(nodes: &'a [Node], qualifiers: &mut Vec<&'a str>) {
+    for node in nodes {
+        match node {
+            Node::Search(SearchNode::SingleField { field, .. })
+                if !matches!(field.as_str(), "*" | "_*" | "*_") =>
+            {
+                qualifiers.push(field)
+            }
+            Node::Group(nodes) => collect_field_qualifiers(nodes, qualifiers),
+            Node::Not(node) => collect_field_qualifiers(slice::from_ref(node.as_ref()), qualifiers),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unknown_fields() -> Result<()> {
+        let mut col = Collection::new();
+        let mut nt = col.get_notetype_by_name("Basic")?.unwrap().as_ref().clone();
+        nt.add_field("Extra Info");
+        col.update_notetype(&mut nt, false)?;
+
+        assert!(col
+            .unknown_search_fields("front:dog* back:re:c.t *:x")?
+            .is_empty());
+        // quoted names with spaces, and wildcards matching any field
+        assert!(col
+            .unknown_search_fields(r#""extra info:nc:cafe" -"Ex*o:x""#)?
+            .is_empty());
+        // field names can't contain colons, so an escaped one never matches
+        assert_eq!(
+            col.unknown_search_fields(r#"(Front:a or "Missing Field:b") -"a\:b:c""#)?,
+            ["Missing Field", "a:b"]
+        );
+        assert!(col.unknown_search_fields("dog (cat").is_err());
+        Ok(())
+    }
+}
@@ -2,8 +2,11 @@
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
 mod builder;
+mod field_order;
+mod fields;
 mod parser;
 mod service;
+mod snippet;
 mod sqlwriter;
 pub(crate) mod writer;
 
@@ -12,7 +15,9 @@ use std::borrow::Cow;
 pub use builder::JoinSearches;
 pub use builder::Negated;
 pub use builder::SearchBuilder;
+pub use field_order::FieldOrder;
 pub use parser::parse as parse_search;
+pub use parser::FieldSearchMode;
 pub use parser::Node;
 pub use parser::PropertyKind;
 pub use parser::RatingKind;
@@ -21,6 +26,7 @@ pub use parser::StateKind;
 pub use parser::TemplateKind;
 use rusqlite::params_from_iter;
 use rusqlite::types::FromSql;
+pub use snippet::NoteSnippet;
 use sqlwriter::RequiredTable;
 use sqlwriter::SqlWriter;
 pub use writer::replace_search_node;
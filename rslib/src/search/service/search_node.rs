@@ -6,6 +6,7 @@ use itertools::Itertools;
 
 use crate::prelude::*;
 use crate::search::parse_search;
+use crate::search::FieldSearchMode;
 use crate::search::Negated;
 use crate::search::Node;
 use crate::search::PropertyKind;
@@ -40,7 +41,7 @@ impl TryFrom<anki_proto::search::SearchNode> for Node {
                 Filter::FieldName(s) => Node::Search(SearchNode::SingleField {
                     field: escape_anki_wildcards_for_search_node(&s),
                     text: "_*".to_string(),
-                    is_re: false,
+                    mode: FieldSearchMode::Normal,
                 }),
                 Filter::Rated(rated) => Node::Search(SearchNode::Rated {
                     days: rated.days,
@@ -107,7 +108,11 @@ impl TryFrom<anki_proto::search::SearchNode> for Node {
                 Filter::Field(field) => Node::Search(SearchNode::SingleField {
                     field: escape_anki_wildcards(&field.field_name),
                     text: escape_anki_wildcards(&field.text),
-                    is_re: field.is_re,
+                    mode: if field.is_re {
+                        FieldSearchMode::Regex
+                    } else {
+                        FieldSearchMode::Normal
+                    },
                 }),
                 Filter::LiteralText(text) => {
                     let text = escape_anki_wildcards(&text);
@@ -0,0 +1,336 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::ops::Range;
+
+use regex::Regex;
+
+use super::parse_search;
+use super::parser::FieldSearchMode;
+use super::parser::Node;
+use super::parser::SearchNode;
+use super::SortMode;
+use crate::browser_table::Column;
+use crate::config::BoolKey;
+use crate::prelude::*;
+use crate::text::glob_matcher;
+use crate::text::html_to_text_line;
+use crate::text::is_glob;
+use crate::text::to_custom_lazy_re;
+use crate::text::to_re;
+use crate::text::without_combining;
+
+/// The number of characters shown either side of a match.
+const SNIPPET_CONTEXT_CHARS: usize = 40;
+
+/// A note matching a search, with an extract of the field that matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteSnippet {
+    pub note_id: NoteId,
+    /// The name of the field the extract was taken from.
+    pub field: String,
+    /// The field's text with HTML removed, cut down to the first match and
+    /// the text around it. The match is wrapped in `<mark>`, and the rest of
+    /// the text is escaped.
+    pub snippet: String,
+}
+
+/// A term of the search that matches note text.
+struct TextTerm {
+    re: Regex,
+    /// Used when `re` doesn't match, for wildcards that span several words.
+    fallback: Option<Regex>,
+    /// Only fields whose name matches this are searched, instead of all
+    /// fields that are not excluded from searches.
+    field: Option<String>,
+    /// Accents are ignored, as with nc:.
+    no_combining: bool,
+}
+
+impl TextTerm {
+    fn new(pattern: &str, field: Option<&str>, no_combining: bool) -> Result<Self> {
+        let pattern = if no_combining {
+            without_combining(pattern)
+        } else {
+            pattern.into()
+        };
+        Ok(TextTerm {
+            re: Self::regex(pattern.as_ref())?,
+            fallback: None,
+            field: field.map(Into::into),
+            no_combining,
+        })
+    }
+
+    /// A term for unqualified text. Its wildcards are matched within a word
+    /// where possible, so that only the matching word is highlighted, and
+    /// otherwise across words as the search does.
+    fn unqualified(text: &str, no_combining: bool) -> Result<Self> {
+        let mut term = TextTerm::new(&word_re(text), None, no_combining)?;
+        if is_glob(text) {
+            let pattern = to_custom_lazy_re(text, ".");
+            let pattern = if no_combining {
+                without_combining(&pattern).into_owned()
+            } else {
+                pattern.into_owned()
+            };
+            term.fallback = Some(Self::regex(&pattern)?);
+        }
+        Ok(term)
+    }
+
+    fn regex(pattern: &str) -> Result<Regex> {
+        Regex::new(&format!("(?is){pattern}")).or_invalid("invalid search")
+    }
+
+    /// The byte range of the first match in `text`.
+    fn find(&self, text: &str) -> Option<Range<usize>> {
+        let res = std::iter::once(&self.re).chain(&self.fallback);
+        if self.no_combining {
+            let (folded, offsets) = without_combining_with_offsets(text);
+            res.filter_map(|re| re.find(&folded))
+                .map(|m| offsets[m.start()]..offsets[m.end()])
+                .next()
+        } else {
+            res.filter_map(|re| re.find(text)).map(|m| m.range()).next()
+        }
+    }
+}
+
+/// `text` as a regex whose wildcards only match within a word, and as few
+/// characters as possible. A trailing wildcard runs to the end of the word.
+fn word_re(text: &str) -> String {
+    let re = to_custom_lazy_re(text, r"\w");
+    if re.ends_with(r"\w*?") {
+        format!(r"{re}\b")
+    } else {
+        re.into_owned()
+    }
+}
+
+impl Collection {
+    /// Search notes as the browser does, and return up to `limit` of them in
+    /// sort field order, each with an extract of the first field matching a
+    /// text term of the search. If `field` is provided, extracts only come
+    /// from fields of that name, and notes without a match there are
+    /// skipped.
+    pub fn search_note_snippets(
+        &mut self,
+        search: &str,
+        field: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<NoteSnippet>> {
+        let ignore_accents = self.get_config_bool(BoolKey::IgnoreAccentsInSearch);
+        let mut terms = vec![];
+        collect_text_terms(&parse_search(search)?, ignore_accents, &mut terms)?;
+        let nids = self.search_notes(
+            search,
+            SortMode::Builtin {
+                column: Column::SortField,
+                reverse: false,
+            },
+        )?;
+
+        let mut snippets = vec![];
+        for nid in nids {
+            if snippets.len() >= limit {
+                break;
+            }
+            let note = self.storage.get_note(nid)?.or_not_found(nid)?;
+            let notetype = self
+                .get_notetype(note.notetype_id)?
+                .or_not_found(note.notetype_id)?;
+            let searched_fields = notetype
+                .fields
+                .iter()
+                .zip(note.fields())
+                .filter(|(nt_field, _)| field.map_or(true, |name| nt_field.name == name));
+            'fields: for (nt_field, text) in searched_fields {
+                for term in &terms {
+                    let included = match &term.field {
+                        Some(pattern) => glob_matcher(pattern)(&nt_field.name),
+                        None => !nt_field.config.exclude_from_search,
+                    };
+                    // the search matches the stored HTML, so a term may only
+                    // be found there
+                    if !included || term.find(text).is_none() {
+                        continue;
+                    }
+                    let plain = html_to_text_line(text, false);
+                    snippets.push(NoteSnippet {
+                        note_id: nid,
+                        field: nt_field.name.clone(),
+                        snippet: snippet(&plain, term.find(&plain)),
+                    });
+                    break 'fields;
+                }
+            }
+        }
+        Ok(snippets)
+    }
+}
+
+/// Add the terms of `nodes` that match note text to `terms`. Negated terms
+/// are skipped, as notes are returned because they lack them.
+fn collect_text_terms(
+    nodes: &[Node],
+    ignore_accents: bool,
+    terms: &mut Vec<TextTerm>,
+) -> Result<()> {
+    for node in nodes {
+        match node {
+            Node::Group(nodes) => collect_text_terms(nodes, ignore_accents, terms)?,
+            Node::Search(search) => match search {
+                SearchNode::UnqualifiedText(text) | SearchNode::StripClozes(text) => {
+                    terms.push(TextTerm::unqualified(text, ignore_accents)?)
+                }
+                SearchNode::NoCombining(text) => terms.push(TextTerm::unqualified(text, true)?),
+                SearchNode::WordBoundary(text) => terms.push(TextTerm::new(
+                    &format!(r"\b{}\b", to_custom_lazy_re(text, r"\S")),
+                    None,
+                    ignore_accents,
+                )?),
+                SearchNode::Regex(re) => terms.push(TextTerm::new(re, None, false)?),
+                SearchNode::SingleField { field, text, mode } => {
+                    let pattern = if *mode == FieldSearchMode::Regex {
+                        text.clone()
+                    } else {
+                        format!("^{}$", to_re(text))
+                    };
+                    terms.push(TextTerm::new(
+                        &pattern,
+                        Some(field),
+                        *mode == FieldSearchMode::NoCombining,
+                    )?)
+                }
+                _ => (),
+            },
+            Node::And | Node::Or | Node::Not(_) => (),
+        }
+    }
+    Ok(())
+}
+
+/// `text` without combining characters, and for each byte of it and the end,
+/// the offset in `text` of the character it came from.
+fn without_combining_with_offsets(text: &str) -> (String, Vec<usize>) {
+    let mut folded = String::with_capacity(text.len());
+    let mut offsets = Vec::with_capacity(text.len() + 1);
+    let mut buf = [0; 4];
+    for (idx, chr) in text.char_indices() {
+        let replacement = without_combining(chr.encode_utf8(&mut buf));
+        folded.push_str(&replacement);
+        offsets.resize(folded.len(), idx);
+    }
+    offsets.push(text.len());
+    (folded, offsets)
+}
+
+/// Cut `text` down to `range` and its surroundings, and highlight the range.
+/// Without a range, the start of the text is used.
+fn snippet(text: &str, range: Option<Range<usize>>) -> String {
+    let Range { start, end } = range.unwrap_or(0..0);
+    let before = &text[..start];
+    let context_start = before
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT_CHARS - 1)
+        .map_or(0, |(idx, _)| idx);
+    let after = &text[end..];
+    let context_end = after
+        .char_indices()
+        .nth(SNIPPET_CONTEXT_CHARS)
+        .map_or(after.len(), |(idx, _)| idx);
+
+    let mut out = String::new();
+    if context_start > 0 {
+        out.push('…');
+    }
+    out.push_str(&htmlescape::encode_minimal(&before[context_start..]));
+    if start < end {
+        out.push_str("<mark>");
+        out.push_str(&htmlescape::encode_minimal(&text[start..end]));
+        out.push_str("</mark>");
+    }
+    out.push_str(&htmlescape::encode_minimal(&after[..context_end]));
+    if context_end < after.len() {
+        out.push('…');
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn snippets() -> Result<()> {
+        let mut col = Collection::new();
+        let long = format!(
+            "{} <b>Mitochondria</b> are the powerhouse of the cell & more {}",
+            "a".repeat(50),
+            "z".repeat(50)
+        );
+        let first = NoteAdder::basic(&mut col)
+            .fields(&["intro", &long])
+            .add(&mut col);
+        let accented = NoteAdder::basic(&mut col)
+            .fields(&["mitochondrião", "back"])
+            .add(&mut col);
+        NoteAdder::basic(&mut col)
+            .fields(&["unrelated", "text"])
+            .add(&mut col);
+
+        let results = col.search_note_snippets("mitochondri*", None, 10)?;
+        assert_eq!(
+            results,
+            [
+                NoteSnippet {
+                    note_id: first.id,
+                    field: "Back".into(),
+                    snippet: format!(
+                        "…{} <mark>Mitochondria</mark> are the powerhouse of the cell &amp; more z…",
+                        "a".repeat(39),
+                    ),
+                },
+                NoteSnippet {
+                    note_id: accented.id,
+                    field: "Front".into(),
+                    snippet: "<mark>mitochondrião</mark>".into(),
+                },
+            ]
+        );
+
+        // without a wildcard, only the word is highlighted
+        let results = col.search_note_snippets("powerhouse", None, 10)?;
+        assert_eq!(
+            results[0].snippet,
+            format!(
+                "…{} Mitochondria are the <mark>powerhouse</mark> of the cell &amp; more {}…",
+                "a".repeat(18),
+                "z".repeat(20)
+            )
+        );
+
+        // wildcards match as little as possible, and only span words when
+        // they must
+        let results = col.search_note_snippets("pow*e", None, 10)?;
+        assert!(results[0].snippet.contains("<mark>powe</mark>rhouse"));
+        let results = col.search_note_snippets("powerhouse*cell", None, 10)?;
+        assert!(results[0]
+            .snippet
+            .contains("<mark>powerhouse of the cell</mark> &amp;"));
+
+        // accents are ignored with nc:
+        let results = col.search_note_snippets("nc:mitochondriao", None, 10)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].snippet, "<mark>mitochondrião</mark>");
+
+        // limited to a field, and to a number of notes
+        assert!(col
+            .search_note_snippets("powerhouse", Some("Front"), 10)?
+            .is_empty());
+        assert_eq!(col.search_note_snippets("mitochondri*", None, 1)?.len(), 1);
+        Ok(())
+    }
+}
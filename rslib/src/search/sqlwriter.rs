@@ -7,6 +7,7 @@ use std::ops::Range;
 
 use itertools::Itertools;
 
+use super::parser::FieldSearchMode;
 use super::parser::Node;
 use super::parser::PropertyKind;
 use super::parser::RatingKind;
@@ -138,8 +139,8 @@ impl SqlWriter<'_> {
                     false,
                 )?
             }
-            SearchNode::SingleField { field, text, is_re } => {
-                self.write_field(&norm(field), &self.norm_note(text), *is_re)?
+            SearchNode::SingleField { field, text, mode } => {
+                self.write_field(&norm(field), &self.norm_note(text), *mode)?
             }
             SearchNode::Duplicates { notetype_id, text } => {
                 self.write_dupe(*notetype_id, &self.norm_note(text))?
@@ -172,7 +173,7 @@ impl SqlWriter<'_> {
             SearchNode::DeckIdsWithoutChildren(dids) => {
                 write!(
                     self.sql,
-                    "c.did in ({dids}) or (c.odid != 0 and c.odid in ({dids}))"
+                    "(c.did in ({dids}) or (c.odid != 0 and c.odid in ({dids})))"
                 )
                 .unwrap();
             }
@@ -567,18 +568,30 @@ impl SqlWriter<'_> {
         }
     }
 
-    fn write_field(&mut self, field_name: &str, val: &str, is_re: bool) -> Result<()> {
+    fn write_field(&mut self, field_name: &str, val: &str, mode: FieldSearchMode) -> Result<()> {
+        let no_combining = mode == FieldSearchMode::NoCombining;
         if matches!(field_name, "*" | "_*" | "*_") {
-            if is_re {
+            if mode == FieldSearchMode::Regex {
                 self.write_all_fields_regexp(val);
             } else {
-                self.write_all_fields(val);
+                self.write_all_fields(val, no_combining);
             }
             Ok(())
-        } else if is_re {
+        } else if mode == FieldSearchMode::Regex {
             self.write_single_field_regexp(field_name, val)
         } else {
-            self.write_single_field(field_name, val)
+            self.write_single_field(field_name, val, no_combining)
+        }
+    }
+
+    /// The note's fields, with combining characters removed if `no_combining`
+    /// is set.
+    fn fields_expr(no_combining: bool) -> Cow<'static, str> {
+        if no_combining {
+            let bits = ProcessTextFlags::NoCombining.bits();
+            Cow::from(format!("coalesce(process_text(n.flds, {bits}), n.flds)"))
+        } else {
+            Cow::from("n.flds")
         }
     }
 
@@ -587,9 +600,21 @@ impl SqlWriter<'_> {
         write!(self.sql, "regexp_fields(?{}, n.flds)", self.args.len()).unwrap();
     }
 
-    fn write_all_fields(&mut self, val: &str) {
-        self.args.push(format!("(?is)^{}$", to_re(val)));
-        write!(self.sql, "regexp_fields(?{}, n.flds)", self.args.len()).unwrap();
+    fn write_all_fields(&mut self, val: &str, no_combining: bool) {
+        let re = to_re(val);
+        let re = if no_combining {
+            without_combining(&re)
+        } else {
+            re
+        };
+        self.args.push(format!("(?is)^{re}$"));
+        write!(
+            self.sql,
+            "regexp_fields(?{}, {})",
+            self.args.len(),
+            Self::fields_expr(no_combining)
+        )
+        .unwrap();
     }
 
     fn write_single_field_regexp(&mut self, field_name: &str, val: &str) -> Result<()> {
@@ -615,7 +640,12 @@ impl SqlWriter<'_> {
         Ok(())
     }
 
-    fn write_single_field(&mut self, field_name: &str, val: &str) -> Result<()> {
+    fn write_single_field(
+        &mut self,
+        field_name: &str,
+        val: &str,
+        no_combining: bool,
+    ) -> Result<()> {
         let field_indicies_by_notetype = self.num_fields_and_fields_indices_by_notetype(
             field_name,
             matches!(val, "*" | "_*" | "*_"),
@@ -625,10 +655,17 @@ impl SqlWriter<'_> {
             return Ok(());
         }
 
-        self.args.push(to_sql(val).into());
+        let val = to_sql(val);
+        let val = if no_combining {
+            without_combining(&val)
+        } else {
+            val
+        };
+        self.args.push(val.into());
         let arg_idx = self.args.len();
         let field_idx_str = format!("' || ?{arg_idx} || '");
         let other_idx_str = "%".to_string();
+        let flds_expr = Self::fields_expr(no_combining);
 
         let notetype_clause = |ctx: &FieldQualifiedSearchContext| -> String {
             let field_index_clause = |range: &Range<u32>| {
@@ -643,7 +680,7 @@ impl SqlWriter<'_> {
                         }
                     })
                     .join("\x1f");
-                format!("n.flds like '{f}' escape '\\'")
+                format!("{flds_expr} like '{f}' escape '\\'")
             };
 
             let all_field_clauses = ctx
@@ -1116,6 +1153,19 @@ mod test {
                 vec!["(?i)te.*st".into()]
             )
         );
+        // field search ignoring combining characters
+        let (sql, args) = s(ctx, "front:nc:café");
+        assert!(sql.starts_with(
+            "(((n.mid = 1581236385344 and (coalesce(process_text(n.flds, 1), n.flds) like "
+        ));
+        assert_eq!(args, vec!["cafe".to_string()]);
+        assert_eq!(
+            s(ctx, "*:nc:café"),
+            (
+                "(regexp_fields(?1, coalesce(process_text(n.flds, 1), n.flds)))".into(),
+                vec!["(?is)^cafe$".into()]
+            )
+        );
         // all field search
         assert_eq!(
             s(ctx, "*:te*st"),
@@ -1170,6 +1220,10 @@ mod test {
         );
         assert_eq!(s(ctx, "deck:d*").1, vec!["(?i)^d.*($|\u{1f})".to_string()]);
         assert_eq!(s(ctx, "deck:filtered"), ("(c.odid != 0)".into(), vec![],));
+        assert_eq!(
+            s(ctx, "did:1,2 is:review").0,
+            "((c.did in (1,2) or (c.odid != 0 and c.odid in (1,2))) and c.type in (2, 3))"
+        );
 
         // card
         assert_eq!(
@@ -5,6 +5,7 @@ use anki_proto::stats::graphs_response::buttons::ButtonCounts;
 use anki_proto::stats::graphs_response::Buttons;
 
 use super::GraphsContext;
+use crate::prelude::*;
 use crate::revlog::RevlogEntry;
 use crate::revlog::RevlogReviewKind;
 
@@ -54,6 +55,27 @@ impl GraphsContext {
     }
 }
 
+/// Counts the answers of the reviews made at or after `since`, with the same
+/// stages as the graph.
+pub(super) fn button_counts_since(revlog: &[RevlogEntry], since: TimestampSecs) -> ButtonCounts {
+    let mut counts = ButtonCounts {
+        learning: vec![0; 4],
+        young: vec![0; 4],
+        mature: vec![0; 4],
+    };
+    for review in revlog {
+        if review.id.as_secs() < since {
+            continue;
+        }
+        if let (Some(interval_bucket), Some(button_idx)) =
+            (interval_bucket(review), button_index(review.button_chosen))
+        {
+            increment_button_counts(&mut counts, interval_bucket, button_idx);
+        }
+    }
+    counts
+}
+
 #[derive(Clone, Copy)]
 enum IntervalBucket {
     Learning,
@@ -90,3 +112,46 @@ fn button_index(button_chosen: u8) -> Option<usize> {
         None
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts_match_graph() -> Result<()> {
+        let mut col = Collection::new();
+        let now = TimestampMillis::now();
+        let add = |days_ago: i64, kind, last_interval, button_chosen| {
+            col.storage
+                .add_revlog_entry(
+                    &RevlogEntry {
+                        id: now.adding_secs(-86_400 * days_ago).into(),
+                        cid: CardId(1),
+                        button_chosen,
+                        last_interval,
+                        review_kind: kind,
+                        ..Default::default()
+                    },
+                    true,
+                )
+                .unwrap();
+        };
+        add(0, RevlogReviewKind::Learning, 0, 1);
+        add(10, RevlogReviewKind::Review, 21, 3);
+        add(10, RevlogReviewKind::Review, 20, 2);
+        add(40, RevlogReviewKind::Review, 30, 4);
+        add(5, RevlogReviewKind::Manual, 0, 0);
+
+        let month = col.answer_button_counts_for_search("", 30)?;
+        assert_eq!(month.learning, [1, 0, 0, 0]);
+        assert_eq!(month.young, [0, 1, 0, 0]);
+        assert_eq!(month.mature, [0, 0, 1, 0]);
+        let graph = col.graph_data_for_search("", 365)?.buttons.unwrap();
+        assert_eq!(Some(month), graph.one_month);
+        assert_eq!(
+            Some(col.answer_button_counts_for_search("", 0)?),
+            graph.all_time
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::collections::HashMap;
+
+use chrono::FixedOffset;
+use chrono::NaiveDate;
+
+use super::GraphsContext;
+use crate::card::CardQueue;
+use crate::prelude::*;
+use crate::revlog::RevlogReviewKind;
+
+/// How many review cards were due on a past day, and how many answers were
+/// given.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ReviewHistoryDay {
+    pub date: NaiveDate,
+    /// An estimate of the review cards that were due, including overdue
+    /// ones.
+    pub estimated_due: u32,
+    /// Answers given on the day, including learning steps and reviews in
+    /// filtered decks.
+    pub reviewed: u32,
+}
+
+impl GraphsContext {
+    /// Per-day history from `first_day` to `last_day` inclusive, which are
+    /// relative to today, so yesterday is -1. The revlog must be in card
+    /// order. See [Collection::get_review_history()] for how due counts are
+    /// estimated.
+    pub(super) fn review_history(
+        &self,
+        first_day: i64,
+        last_day: i64,
+    ) -> Result<Vec<ReviewHistoryDay>> {
+        let days = (last_day - first_day + 1).max(0) as usize;
+        let mut estimated_due = vec![0; days];
+        let mut reviewed = vec![0; days];
+        let mut count_due = |from: i64, to: i64| {
+            for day in from.max(first_day)..=to.min(last_day) {
+                estimated_due[(day - first_day) as usize] += 1;
+            }
+        };
+        let queues: HashMap<CardId, CardQueue> = self
+            .cards
+            .iter()
+            .map(|card| (card.id, card.queue))
+            .collect();
+        let still_waiting = |cid: CardId| {
+            matches!(
+                queues.get(&cid),
+                Some(CardQueue::Review | CardQueue::DayLearn)
+            )
+        };
+
+        // the day the current card is expected to become due, if any
+        let mut pending: Option<(CardId, i64)> = None;
+        for entry in &self.revlog {
+            if let Some((cid, due)) = pending {
+                if cid != entry.cid {
+                    if still_waiting(cid) {
+                        count_due(due, 0);
+                    }
+                    pending = None;
+                }
+            }
+            let day = self.day_of(entry.id.as_secs());
+            if matches!(
+                entry.review_kind,
+                RevlogReviewKind::Manual | RevlogReviewKind::Rescheduled
+            ) || entry.button_chosen == 0
+            {
+                if let Some((_, due)) = pending.take() {
+                    count_due(due, day - 1);
+                }
+                continue;
+            }
+            if (first_day..=last_day).contains(&day) {
+                reviewed[(day - first_day) as usize] += 1;
+            }
+            if let Some((_, due)) = pending.take() {
+                count_due(due, day);
+            }
+            if entry.interval > 0 {
+                pending = Some((entry.cid, day + entry.interval as i64));
+            }
+        }
+        if let Some((_, due)) = pending.filter(|&(cid, _)| still_waiting(cid)) {
+            count_due(due, 0);
+        }
+
+        let offset =
+            FixedOffset::east_opt(self.local_offset_secs as i32).or_invalid("bad offset")?;
+        (first_day..=last_day)
+            .zip(estimated_due.into_iter().zip(reviewed))
+            .map(|(day, (estimated_due, reviewed))| {
+                let start = self.next_day_start.adding_secs(86_400 * (day - 1));
+                Ok(ReviewHistoryDay {
+                    date: start.datetime(offset)?.date_naive(),
+                    estimated_due,
+                    reviewed,
+                })
+            })
+            .collect()
+    }
+
+    /// The day a timestamp falls on, relative to today.
+    fn day_of(&self, stamp: TimestampSecs) -> i64 {
+        stamp
+            .elapsed_secs_since(self.next_day_start)
+            .div_euclid(86_400)
+            + 1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::revlog::RevlogEntry;
+
+    fn answer(cid: i64, day: i64, interval: i32) -> RevlogEntry {
+        RevlogEntry {
+            id: RevlogId(day * 86_400_000 + 1000),
+            cid: CardId(cid),
+            button_chosen: 3,
+            interval,
+            review_kind: RevlogReviewKind::Review,
+            ..Default::default()
+        }
+    }
+
+    fn review_card(cid: i64) -> Card {
+        Card {
+            id: CardId(cid),
+            queue: CardQueue::Review,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn history_is_estimated_from_revlog() -> Result<()> {
+        let ctx = GraphsContext {
+            revlog: vec![
+                // due on day -8, answered late on day -6, then due on day -3
+                // and not answered since
+                answer(1, -10, 2),
+                answer(1, -6, 3),
+                // answered early, so never due
+                answer(2, -10, 5),
+                answer(2, -7, 30),
+                // rescheduled on day -4 while overdue
+                answer(3, -9, 1),
+                RevlogEntry {
+                    id: RevlogId(-4 * 86_400_000),
+                    cid: CardId(3),
+                    review_kind: RevlogReviewKind::Manual,
+                    ..Default::default()
+                },
+                // deleted cards are not counted once waiting for an answer
+                answer(4, -8, 1),
+            ],
+            cards: vec![review_card(1), review_card(2), review_card(3)],
+            next_day_start: TimestampSecs(86_400),
+            days_elapsed: 0,
+            local_offset_secs: 0,
+        };
+
+        let history = ctx.review_history(-10, 0)?;
+        assert_eq!(
+            history
+                .iter()
+                .map(|day| day.estimated_due)
+                .collect::<Vec<_>>(),
+            [0, 0, 2, 2, 2, 1, 0, 1, 1, 1, 1]
+        );
+        assert_eq!(
+            history.iter().map(|day| day.reviewed).collect::<Vec<_>>(),
+            [2, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            history[0].date,
+            NaiveDate::from_ymd_opt(1969, 12, 22).unwrap()
+        );
+        assert_eq!(
+            history[10].date,
+            NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()
+        );
+
+        let history = ctx.review_history(-3, -2)?;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].estimated_due, 1);
+        Ok(())
+    }
+}
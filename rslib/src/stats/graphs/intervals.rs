@@ -1,11 +1,25 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use std::collections::HashMap;
+
 use anki_proto::stats::graphs_response::Intervals;
 
 use crate::card::CardType;
 use crate::stats::graphs::GraphsContext;
 
+/// The number of bars the desktop interval graph aims for.
+const MAX_BUCKETS: u32 = 70;
+
+/// A histogram of day counts, such as intervals or stabilities.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct IntervalDistribution {
+    /// (start, end, card count) of each bucket, with `end` exclusive.
+    pub buckets: Vec<(u32, u32, u32)>,
+    pub median: f32,
+    pub p90: f32,
+}
+
 impl GraphsContext {
     pub(super) fn intervals(&self) -> Intervals {
         let mut data = Intervals::default();
@@ -33,3 +47,102 @@ impl GraphsContext {
         data
     }
 }
+
+impl IntervalDistribution {
+    /// Buckets the counts the way the desktop graph does when showing all
+    /// cards: evenly sized buckets starting at `first`, with a width of 1, 2
+    /// or 5 times a power of 10.
+    pub(super) fn new(counts: &HashMap<u32, u32>, first: u32) -> Self {
+        let mut sorted: Vec<_> = counts
+            .iter()
+            .filter(|(_, &count)| count > 0)
+            .map(|(&days, &count)| (days, count))
+            .collect();
+        sorted.sort_unstable();
+        let Some(&(max, _)) = sorted.last() else {
+            return Self::default();
+        };
+        let first = first.min(sorted[0].0);
+        let span = max + 1 - first;
+        let width = bucket_width(span, span.min(MAX_BUCKETS));
+        let mut buckets: Vec<_> = (first..=max)
+            .step_by(width as usize)
+            .map(|start| (start, start + width, 0))
+            .collect();
+        for &(days, count) in &sorted {
+            buckets[((days - first) / width) as usize].2 += count;
+        }
+        IntervalDistribution {
+            buckets,
+            median: quantile(&sorted, 0.5),
+            p90: quantile(&sorted, 0.9),
+        }
+    }
+}
+
+/// The smallest width of 1, 2 or 5 times a power of 10 that splits `span`
+/// into roughly `count` buckets, as d3's ticks would.
+fn bucket_width(span: u32, count: u32) -> u32 {
+    let step = span as f64 / count.max(1) as f64;
+    let power = 10f64.powf(step.log10().floor());
+    let error = step / power;
+    let factor = if error >= 50f64.sqrt() {
+        10.0
+    } else if error >= 10f64.sqrt() {
+        5.0
+    } else if error >= 2f64.sqrt() {
+        2.0
+    } else {
+        1.0
+    };
+    ((factor * power) as u32).max(1)
+}
+
+/// The interpolated quantile of sorted (value, count) pairs, matching d3's
+/// quantile().
+fn quantile(sorted: &[(u32, u32)], p: f32) -> f32 {
+    let total: u32 = sorted.iter().map(|(_, count)| count).sum();
+    let value_at = |mut idx: u32| {
+        for &(days, count) in sorted {
+            if idx < count {
+                return days as f32;
+            }
+            idx -= count;
+        }
+        sorted
+            .last()
+            .map(|&(days, _)| days as f32)
+            .unwrap_or_default()
+    };
+    let pos = (total - 1) as f32 * p;
+    let lower = pos.floor() as u32;
+    let low = value_at(lower);
+    low + (value_at(lower + 1) - low) * (pos - lower as f32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn distribution() {
+        let dist = IntervalDistribution::new(&HashMap::from([(1, 2), (3, 1), (10, 1)]), 1);
+        assert_eq!(dist.buckets.len(), 10);
+        assert_eq!(dist.buckets[0], (1, 2, 2));
+        assert_eq!(dist.buckets[2], (3, 4, 1));
+        assert_eq!(dist.buckets[9], (10, 11, 1));
+        assert_eq!(dist.median, 2.0);
+        assert!((dist.p90 - 7.9).abs() < 1e-5);
+
+        // wide ranges are split into roughly 70 evenly sized buckets
+        let dist = IntervalDistribution::new(&HashMap::from([(1, 1), (400, 1)]), 1);
+        assert_eq!(dist.buckets.len(), 80);
+        assert_eq!(dist.buckets[1], (6, 11, 0));
+        assert_eq!(dist.buckets[79], (396, 401, 1));
+
+        assert_eq!(
+            IntervalDistribution::new(&HashMap::new(), 0),
+            IntervalDistribution::default()
+        );
+    }
+}
@@ -6,17 +6,29 @@ mod buttons;
 mod card_counts;
 mod eases;
 mod future_due;
+mod history;
 mod hours;
 mod intervals;
 mod retention;
 mod retrievability;
 mod reviews;
 mod today;
+mod workload;
+
+use std::collections::HashMap;
+
+use anki_proto::stats::graphs_response::buttons::ButtonCounts;
+use chrono::NaiveDate;
+pub(crate) use history::ReviewHistoryDay;
+pub(crate) use intervals::IntervalDistribution;
+pub(crate) use reviews::ReviewTimeSeries;
+pub(crate) use workload::DeckWorkload;
 
 use crate::config::BoolKey;
 use crate::config::Weekday;
 use crate::prelude::*;
 use crate::revlog::RevlogEntry;
+use crate::search::SearchNode;
 use crate::search::SortMode;
 
 struct GraphsContext {
@@ -38,6 +50,161 @@ impl Collection {
         guard.col.graph_data(all, days)
     }
 
+    /// The answer button counts of the graph for the searched cards, for
+    /// reviews in the last `days` days, or all time if 0.
+    pub(crate) fn answer_button_counts_for_search(
+        &mut self,
+        search: &str,
+        days: u32,
+    ) -> Result<ButtonCounts> {
+        let guard = self.search_cards_into_table(search, SortMode::NoOrder)?;
+        let next_day_at = guard.col.timing_today()?.next_day_at;
+        let since = if days > 0 {
+            next_day_at.adding_secs(-86_400 * days as i64)
+        } else {
+            TimestampSecs(0)
+        };
+        let revlog = if search.trim().is_empty() {
+            guard.col.storage.get_all_revlog_entries(since)?
+        } else {
+            guard
+                .col
+                .storage
+                .get_revlog_entries_for_searched_cards_after_stamp(since)?
+        };
+        Ok(buttons::button_counts_since(&revlog, since))
+    }
+
+    /// The distribution of the searched cards' review intervals, and of their
+    /// stabilities if FSRS is enabled.
+    pub(crate) fn interval_distribution_for_search(
+        &mut self,
+        search: &str,
+    ) -> Result<(IntervalDistribution, Option<IntervalDistribution>)> {
+        let guard = self.search_cards_into_table(search, SortMode::NoOrder)?;
+        let timing = guard.col.timing_today()?;
+        let ctx = GraphsContext {
+            revlog: vec![],
+            days_elapsed: timing.days_elapsed,
+            cards: guard.col.storage.all_searched_cards()?,
+            next_day_start: timing.next_day_at,
+            local_offset_secs: 0,
+        };
+        let intervals = IntervalDistribution::new(&ctx.intervals().intervals, 1);
+        let stability = guard
+            .col
+            .get_config_bool(BoolKey::Fsrs)
+            .then(|| IntervalDistribution::new(&ctx.stability().intervals, 0));
+        Ok((intervals, stability))
+    }
+
+    /// Estimate the study time of each top-level deck over the next `days`
+    /// days, from its due cards and the average answer time of reviews in the
+    /// last `window_days` days. Decks without recent reviews assume
+    /// `fallback_secs` per review.
+    pub(crate) fn estimate_workload(
+        &mut self,
+        days: u32,
+        window_days: u32,
+        fallback_secs: f32,
+    ) -> Result<Vec<DeckWorkload>> {
+        let mut decks = HashMap::new();
+        let mut top_level_ids = HashMap::new();
+        let mut top_level = vec![];
+        // parents sort before their children
+        for (did, name) in self.storage.get_all_deck_names()? {
+            if let Some((parent, _)) = name.split_once("::") {
+                if let Some(&top_did) = top_level_ids.get(parent) {
+                    decks.insert(did, top_did);
+                }
+            } else {
+                decks.insert(did, did);
+                top_level_ids.insert(name.clone(), did);
+                top_level.push((did, name));
+            }
+        }
+        let timing = self.timing_today()?;
+        let since = timing.next_day_at.adding_secs(-86_400 * window_days as i64);
+        let guard = self.search_cards_into_table(SearchNode::WholeCollection, SortMode::NoOrder)?;
+        let ctx = GraphsContext {
+            revlog: guard.col.storage.get_all_revlog_entries(since)?,
+            days_elapsed: timing.days_elapsed,
+            cards: guard.col.storage.all_searched_cards()?,
+            next_day_start: timing.next_day_at,
+            local_offset_secs: 0,
+        };
+        Ok(ctx.workload(days, fallback_secs, &decks, top_level))
+    }
+
+    /// The time spent studying on each of the last `days` days, optionally
+    /// limited to the cards of a deck and its children.
+    pub(crate) fn get_review_time_series(
+        &mut self,
+        deck_id: Option<DeckId>,
+        days: u32,
+    ) -> Result<Vec<ReviewTimeSeries>> {
+        let timing = self.timing_today()?;
+        let since = timing.next_day_at.adding_secs(-86_400 * days as i64);
+        let revlog = match deck_id {
+            Some(did) => {
+                let deck = self.get_deck(did)?.or_not_found(did)?;
+                let search = SearchNode::from_deck_name(&deck.human_name());
+                let guard = self.search_cards_into_table(search, SortMode::NoOrder)?;
+                guard
+                    .col
+                    .storage
+                    .get_revlog_entries_for_searched_cards_after_stamp(since)?
+            }
+            None => self.storage.get_all_revlog_entries(since)?,
+        };
+        let ctx = GraphsContext {
+            revlog,
+            days_elapsed: timing.days_elapsed,
+            cards: vec![],
+            next_day_start: timing.next_day_at,
+            local_offset_secs: self.local_utc_offset_for_user()?.local_minus_utc() as i64,
+        };
+        ctx.review_time_series(days)
+    }
+
+    /// For each day from `from` to `to` (or today) inclusive, an estimate of how many
+    /// review cards were due, and the number of answers given.
+    ///
+    /// The collection does not record what was due on past days, so it is
+    /// reconstructed from the revlog: after each answer that set an interval
+    /// of a day or more, the card is assumed to have become due that many
+    /// days later, and to have stayed due until it was next answered, with
+    /// the answer's day included. Answering it early means it never became
+    /// due. Learning steps are not counted, and as manual rescheduling does
+    /// not record the new due date, it ends the estimate until the card is
+    /// next answered. Cards that are still waiting for an answer count up to
+    /// today, unless they have since been deleted, suspended or buried.
+    pub(crate) fn get_review_history(
+        &mut self,
+        from: NaiveDate,
+        to: Option<NaiveDate>,
+    ) -> Result<Vec<ReviewHistoryDay>> {
+        let timing = self.timing_today()?;
+        let offset = self.local_utc_offset_for_user()?;
+        let today = timing
+            .next_day_at
+            .adding_secs(-86_400)
+            .datetime(offset)?
+            .date_naive();
+        let to = to.unwrap_or(today);
+        require!(from <= to, "from must not be after to");
+        require!(to <= today, "to must not be in the future");
+        let guard = self.search_cards_into_table(SearchNode::WholeCollection, SortMode::NoOrder)?;
+        let ctx = GraphsContext {
+            revlog: guard.col.storage.get_all_revlog_entries_in_card_order()?,
+            days_elapsed: timing.days_elapsed,
+            cards: guard.col.storage.all_searched_cards()?,
+            next_day_start: timing.next_day_at,
+            local_offset_secs: offset.local_minus_utc() as i64,
+        };
+        ctx.review_history((from - today).num_days(), (to - today).num_days())
+    }
+
     fn graph_data(&mut self, all: bool, days: u32) -> Result<anki_proto::stats::GraphsResponse> {
         let timing = self.timing_today()?;
         let revlog_start = if days > 0 {
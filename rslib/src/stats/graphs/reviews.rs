@@ -2,10 +2,32 @@
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
 use anki_proto::stats::graphs_response::ReviewCountsAndTimes;
+use chrono::FixedOffset;
+use chrono::NaiveDate;
 
 use super::GraphsContext;
+use crate::prelude::*;
 use crate::revlog::RevlogReviewKind;
 
+/// Time spent studying on a single day.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ReviewTimeSeries {
+    pub date: NaiveDate,
+    /// Includes reviews in filtered decks that are not counted below.
+    pub total_seconds: u64,
+    pub review_seconds: u64,
+    pub learn_seconds: u64,
+    pub relearn_seconds: u64,
+}
+
+#[derive(Default, Clone, Copy)]
+struct DayMillis {
+    total: u64,
+    review: u64,
+    learn: u64,
+    relearn: u64,
+}
+
 impl GraphsContext {
     pub(super) fn review_counts_and_times(&self) -> ReviewCountsAndTimes {
         let mut data = ReviewCountsAndTimes::default();
@@ -45,4 +67,106 @@ impl GraphsContext {
         }
         data
     }
+
+    /// The time spent on each of the last `days` days, oldest first. Days
+    /// start at the rollover hour.
+    pub(super) fn review_time_series(&self, days: u32) -> Result<Vec<ReviewTimeSeries>> {
+        let mut millis = vec![DayMillis::default(); days as usize];
+        for review in &self.revlog {
+            if matches!(
+                review.review_kind,
+                RevlogReviewKind::Manual | RevlogReviewKind::Rescheduled
+            ) {
+                continue;
+            }
+            let days_ago = -review
+                .id
+                .as_secs()
+                .elapsed_secs_since(self.next_day_start)
+                .div_euclid(86_400)
+                - 1;
+            let Some(idx) = (days as i64 - 1)
+                .checked_sub(days_ago)
+                .filter(|idx| (0..days as i64).contains(idx))
+            else {
+                continue;
+            };
+            let day = &mut millis[idx as usize];
+            let taken = review.taken_millis as u64;
+            day.total += taken;
+            match review.review_kind {
+                RevlogReviewKind::Review => day.review += taken,
+                RevlogReviewKind::Learning => day.learn += taken,
+                RevlogReviewKind::Relearning => day.relearn += taken,
+                _ => (),
+            }
+        }
+        let offset =
+            FixedOffset::east_opt(self.local_offset_secs as i32).or_invalid("bad offset")?;
+        millis
+            .into_iter()
+            .enumerate()
+            .map(|(idx, day)| {
+                let start = self
+                    .next_day_start
+                    .adding_secs(-86_400 * (days as i64 - idx as i64));
+                Ok(ReviewTimeSeries {
+                    date: start.datetime(offset)?.date_naive(),
+                    total_seconds: day.total / 1000,
+                    review_seconds: day.review / 1000,
+                    learn_seconds: day.learn / 1000,
+                    relearn_seconds: day.relearn / 1000,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::revlog::RevlogEntry;
+
+    #[test]
+    fn time_series() -> Result<()> {
+        let mut col = Collection::new();
+        let now = TimestampMillis::now();
+        let add = |days_ago: i64, review_kind, taken_millis| {
+            col.storage
+                .add_revlog_entry(
+                    &RevlogEntry {
+                        id: now.adding_secs(-86_400 * days_ago).into(),
+                        cid: CardId(1),
+                        button_chosen: 3,
+                        review_kind,
+                        taken_millis,
+                        ..Default::default()
+                    },
+                    true,
+                )
+                .unwrap();
+        };
+        add(0, RevlogReviewKind::Learning, 5_000);
+        add(0, RevlogReviewKind::Review, 10_500);
+        add(0, RevlogReviewKind::Filtered, 1_000);
+        add(1, RevlogReviewKind::Relearning, 3_000);
+        add(1, RevlogReviewKind::Manual, 3_000);
+        add(5, RevlogReviewKind::Review, 3_000);
+
+        let series = col.get_review_time_series(None, 3)?;
+        assert_eq!(series.len(), 3);
+        assert_eq!(series[2].date - series[1].date, chrono::Duration::days(1));
+        assert_eq!(
+            (
+                series[2].total_seconds,
+                series[2].review_seconds,
+                series[2].learn_seconds
+            ),
+            (16, 10, 5)
+        );
+        assert_eq!(series[1].total_seconds, 3);
+        assert_eq!(series[1].relearn_seconds, 3);
+        assert_eq!(series[0].total_seconds, 0);
+        Ok(())
+    }
 }
@@ -0,0 +1,150 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::collections::HashMap;
+
+use super::GraphsContext;
+use crate::card::CardQueue;
+use crate::card::CardType;
+use crate::prelude::*;
+use crate::revlog::RevlogReviewKind;
+use crate::scheduler::timing::is_unix_epoch_timestamp;
+
+/// Estimated study time of a top-level deck and its children.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DeckWorkload {
+    pub deck_id: DeckId,
+    pub name: String,
+    /// Average answer time of recent reviews, or the fallback if there were
+    /// none.
+    pub secs_per_review: f32,
+    /// Cards due on each upcoming day, starting with today. Overdue cards are
+    /// counted as due today.
+    pub due: Vec<u32>,
+}
+
+impl DeckWorkload {
+    pub fn minutes(&self) -> impl Iterator<Item = f32> + '_ {
+        self.due
+            .iter()
+            .map(|&count| count as f32 * self.secs_per_review / 60.0)
+    }
+}
+
+impl GraphsContext {
+    /// `decks` maps every deck to its top-level deck, which are listed in
+    /// `top_level`. The revlog should only cover the averaging window.
+    pub(super) fn workload(
+        &self,
+        days: u32,
+        fallback_secs: f32,
+        decks: &HashMap<DeckId, DeckId>,
+        top_level: Vec<(DeckId, String)>,
+    ) -> Vec<DeckWorkload> {
+        let home_decks: HashMap<CardId, DeckId> = self
+            .cards
+            .iter()
+            .filter_map(|c| Some((c.id, *decks.get(&c.original_or_current_deck_id())?)))
+            .collect();
+        // top-level deck -> (millis, reviews)
+        let mut taken: HashMap<DeckId, (u64, u32)> = HashMap::new();
+        for entry in &self.revlog {
+            if matches!(
+                entry.review_kind,
+                RevlogReviewKind::Manual | RevlogReviewKind::Rescheduled
+            ) || entry.button_chosen == 0
+            {
+                continue;
+            }
+            if let Some(did) = home_decks.get(&entry.cid) {
+                let (millis, reviews) = taken.entry(*did).or_default();
+                *millis += entry.taken_millis as u64;
+                *reviews += 1;
+            }
+        }
+
+        let mut due: HashMap<DeckId, Vec<u32>> = HashMap::new();
+        for c in &self.cards {
+            if c.ctype == CardType::New || c.queue == CardQueue::Suspended {
+                continue;
+            }
+            let Some(did) = home_decks.get(&c.id) else {
+                continue;
+            };
+            let card_due = c.original_or_current_due();
+            let due_day = if is_unix_epoch_timestamp(card_due) {
+                (card_due as i64 - self.next_day_start.0) / 86_400
+            } else {
+                card_due as i64 - self.days_elapsed as i64
+            };
+            // buried cards will not be shown today
+            if due_day <= 0 && matches!(c.queue, CardQueue::UserBuried | CardQueue::SchedBuried) {
+                continue;
+            }
+            if let Ok(day) = usize::try_from(due_day.max(0)) {
+                if day < days as usize {
+                    due.entry(*did).or_insert_with(|| vec![0; days as usize])[day] += 1;
+                }
+            }
+        }
+
+        top_level
+            .into_iter()
+            .map(|(deck_id, name)| DeckWorkload {
+                secs_per_review: match taken.get(&deck_id) {
+                    Some(&(millis, reviews)) if reviews > 0 => {
+                        millis as f32 / reviews as f32 / 1000.0
+                    }
+                    _ => fallback_secs,
+                },
+                due: due
+                    .remove(&deck_id)
+                    .unwrap_or_else(|| vec![0; days as usize]),
+                deck_id,
+                name,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::revlog::RevlogEntry;
+
+    #[test]
+    fn workload_is_grouped_by_top_level_deck() -> Result<()> {
+        let mut col = Collection::new();
+        let parent = DeckAdder::new("parent").add(&mut col);
+        let child = DeckAdder::new("parent::child").add(&mut col);
+        let note = NoteAdder::basic(&mut col).deck(child.id).add(&mut col);
+        let mut card = col.storage.all_cards_of_note(note.id)?[0].clone();
+        card.ctype = CardType::Review;
+        card.queue = CardQueue::Review;
+        card.interval = 5;
+        card.due = col.timing_today()?.days_elapsed as i32 + 1;
+        col.storage.update_card(&card)?;
+        col.storage.add_revlog_entry(
+            &RevlogEntry {
+                id: TimestampMillis::now().into(),
+                cid: card.id,
+                button_chosen: 3,
+                review_kind: RevlogReviewKind::Review,
+                taken_millis: 30_000,
+                ..Default::default()
+            },
+            true,
+        )?;
+
+        let workload = col.estimate_workload(3, 30, 10.0)?;
+        let names: Vec<_> = workload.iter().map(|deck| deck.name.as_str()).collect();
+        assert_eq!(names, ["Default", "parent"]);
+        assert_eq!(workload[0].secs_per_review, 10.0);
+        assert_eq!(workload[0].due, [0, 0, 0]);
+        assert_eq!(workload[1].deck_id, parent.id);
+        assert_eq!(workload[1].secs_per_review, 30.0);
+        assert_eq!(workload[1].due, [0, 1, 0]);
+        assert_eq!(workload[1].minutes().collect::<Vec<_>>(), [0.0, 0.5, 0.0]);
+        Ok(())
+    }
+}
@@ -6,4 +6,6 @@ mod graphs;
 mod service;
 mod today;
 
+pub(crate) use graphs::DeckWorkload;
+pub(crate) use graphs::IntervalDistribution;
 pub use today::studied_today;
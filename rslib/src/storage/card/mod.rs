@@ -21,6 +21,9 @@ use rusqlite::Row;
 use self::data::CardData;
 use super::ids_to_string;
 use super::sqlite::SqlSortOrder;
+use crate::card::anomalies::MINIMUM_EASE_FACTOR_THOUSANDS;
+use crate::card::anomalies::NEW_POSITION_LIMIT;
+use crate::card::anomalies::REVIEW_DUE_LIMIT;
 use crate::card::Card;
 use crate::card::CardId;
 use crate::card::CardQueue;
@@ -53,6 +56,26 @@ pub struct RetentionCosts {
     pub learn_count: u32,
 }
 
+/// A summary of another card of the same note.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiblingInfo {
+    pub card_id: CardId,
+    pub template_idx: u16,
+    pub queue: CardQueue,
+    pub due: i32,
+}
+
+impl SiblingInfo {
+    /// True if the sibling will be shown before the next day rollover.
+    pub fn is_due_today(&self, timing: &SchedTimingToday) -> bool {
+        match self.queue {
+            CardQueue::Learn | CardQueue::PreviewRepeat => (self.due as i64) < timing.next_day_at.0,
+            CardQueue::Review | CardQueue::DayLearn => self.due <= timing.days_elapsed as i32,
+            _ => false,
+        }
+    }
+}
+
 impl FromSql for CardType {
     fn column_result(value: ValueRef<'_>) -> result::Result<Self, FromSqlError> {
         if let ValueRef::Integer(i) = value {
@@ -418,6 +441,13 @@ impl super::SqliteStorage {
             .collect()
     }
 
+    pub(crate) fn card_original_dues_in_deck(&self, did: DeckId) -> Result<Vec<(CardId, i32)>> {
+        self.db
+            .prepare("select id, odue from cards where did = ? order by id")?
+            .query_and_then([did], |r| -> Result<_> { Ok((r.get(0)?, r.get(1)?)) })?
+            .collect()
+    }
+
     pub(crate) fn max_new_card_position(&self) -> Result<u32> {
         self.db
             .prepare("select max(due)+1 from cards where type=0")?
@@ -425,6 +455,38 @@ impl super::SqliteStorage {
             .map_err(Into::into)
     }
 
+    /// The position after the last new card whose position is below `limit`.
+    pub(crate) fn next_new_card_position_below(&self, limit: i32) -> Result<u32> {
+        self.db
+            .prepare("select coalesce(max(due)+1, 0) from cards where type=0 and due < ?")?
+            .query_row([limit], |r| r.get(0))
+            .map_err(Into::into)
+    }
+
+    /// Cards that may have one of the problems
+    /// [Collection::card_anomalies](crate::collection::Collection::card_anomalies)
+    /// looks for, in card id order. The caller should check them.
+    pub(crate) fn cards_with_possible_anomalies(&self) -> Result<Vec<Card>> {
+        self.db
+            .prepare(concat!(
+                include_str!("get_card.sql"),
+                " where (type = 0 and (due >= ?1 or odue >= ?1))",
+                " or (queue = 2 and due > ?2)",
+                " or (type = 2 and ivl = 0)",
+                " or (type in (2, 3) and factor between 1 and ?3)",
+                " order by id"
+            ))?
+            .query_and_then(
+                params![
+                    NEW_POSITION_LIMIT,
+                    REVIEW_DUE_LIMIT,
+                    MINIMUM_EASE_FACTOR_THOUSANDS - 1
+                ],
+                |r| row_to_card(r).map_err(Into::into),
+            )?
+            .collect()
+    }
+
     pub(crate) fn get_card_by_ordinal(&self, nid: NoteId, ord: u16) -> Result<Option<Card>> {
         self.db
             .prepare_cached(concat!(
@@ -482,6 +544,14 @@ impl super::SqliteStorage {
             .collect()
     }
 
+    /// Up to `limit` card ids greater than `after`, in ascending order.
+    pub(crate) fn card_ids_after(&self, after: CardId, limit: u32) -> Result<Vec<CardId>> {
+        self.db
+            .prepare_cached("SELECT id FROM cards WHERE id > ? ORDER BY id LIMIT ?")?
+            .query_and_then(params![after, limit], |row| Ok(row.get(0)?))?
+            .collect()
+    }
+
     pub(crate) fn get_all_card_ids(&self) -> Result<HashSet<CardId>> {
         self.db
             .prepare("SELECT id FROM cards")?
@@ -489,6 +559,30 @@ impl super::SqliteStorage {
             .collect()
     }
 
+    pub(crate) fn total_cards(&self) -> Result<u32> {
+        self.db
+            .prepare("SELECT count() FROM cards")?
+            .query_row([], |r| r.get(0))
+            .map_err(Into::into)
+    }
+
+    pub(crate) fn card_ids_modified_since(&self, since: TimestampSecs) -> Result<Vec<CardId>> {
+        self.db
+            .prepare("SELECT id FROM cards WHERE mod >= ?")?
+            .query_and_then([since], |row| Ok(row.get(0)?))?
+            .collect()
+    }
+
+    pub(crate) fn cards_modified_since(
+        &self,
+        since: TimestampSecs,
+    ) -> Result<Vec<(CardId, TimestampSecs)>> {
+        self.db
+            .prepare("SELECT id, mod FROM cards WHERE mod >= ? ORDER BY mod, id")?
+            .query_and_then([since], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect()
+    }
+
     pub(crate) fn all_cards_as_nid_and_ord(&self) -> Result<HashSet<(NoteId, u16)>> {
         self.db
             .prepare("SELECT nid, ord FROM cards")?
@@ -509,6 +603,23 @@ impl super::SqliteStorage {
         Ok(cids)
     }
 
+    /// Other cards of note `nid`, in template order.
+    pub(crate) fn sibling_info(&self, cid: CardId, nid: NoteId) -> Result<Vec<SiblingInfo>> {
+        self.db
+            .prepare_cached(
+                "select id, ord, queue, due from cards where nid = ? and id != ? order by ord",
+            )?
+            .query_and_then(params![nid, cid], |row| {
+                Ok(SiblingInfo {
+                    card_id: row.get(0)?,
+                    template_idx: row.get(1)?,
+                    queue: row.get(2)?,
+                    due: row.get(3)?,
+                })
+            })?
+            .collect()
+    }
+
     pub(crate) fn all_siblings_for_bury(
         &self,
         cid: CardId,
@@ -935,7 +1046,11 @@ mod test {
     use anki_i18n::I18n;
 
     use crate::card::Card;
+    use crate::card::CardQueue;
+    use crate::collection::Collection;
+    use crate::error::Result;
     use crate::storage::SqliteStorage;
+    use crate::tests::CardAdder;
 
     #[test]
     fn add_card() {
@@ -948,4 +1063,22 @@ mod test {
         storage.add_card(&mut card).unwrap();
         assert_ne!(id1, card.id);
     }
+
+    #[test]
+    fn sibling_info() -> Result<()> {
+        let mut col = Collection::new();
+        let mut cards = CardAdder::new().siblings(3).add(&mut col);
+        cards.sort_by_key(|card| card.template_idx);
+        col.set_due_date(&[cards[1].id], "0", None)?;
+        col.set_due_date(&[cards[2].id], "5", None)?;
+        let timing = col.timing_today()?;
+        let siblings = col.storage.sibling_info(cards[0].id, cards[0].note_id)?;
+        assert_eq!(siblings.len(), 2);
+        assert_eq!(siblings[0].card_id, cards[1].id);
+        assert_eq!(siblings[0].template_idx, 1);
+        assert!(siblings[0].is_due_today(&timing));
+        assert_eq!(siblings[1].queue, CardQueue::Review);
+        assert!(!siblings[1].is_due_today(&timing));
+        Ok(())
+    }
 }
@@ -0,0 +1,11 @@
+SELECT CASE
+    WHEN odid = 0 THEN did
+    ELSE odid
+  END AS home_did,
+  COUNT(*)
+FROM cards
+WHERE id IN (
+    SELECT cid
+    FROM search_cids
+  )
+GROUP BY home_did
@@ -152,6 +152,15 @@ impl SqliteStorage {
             .collect()
     }
 
+    /// Returns the number of searched cards in each deck. Cards in filtered
+    /// decks are counted towards their home deck.
+    pub(crate) fn card_counts_of_search_cards(&self) -> Result<Vec<(DeckId, usize)>> {
+        self.db
+            .prepare_cached(include_str!("card_counts_of_search_cards.sql"))?
+            .query_and_then([], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect()
+    }
+
     // caller should ensure name unique
     pub(crate) fn add_deck(&self, deck: &mut Deck) -> Result<()> {
         assert_eq!(deck.id.0, 0);
@@ -222,6 +231,13 @@ impl SqliteStorage {
         Ok(())
     }
 
+    pub(crate) fn deck_ids_modified_since(&self, since: TimestampSecs) -> Result<Vec<DeckId>> {
+        self.db
+            .prepare("SELECT id FROM decks WHERE mtime_secs >= ?")?
+            .query_and_then([since], |row| Ok(row.get(0)?))?
+            .collect()
+    }
+
     pub(crate) fn all_cards_in_single_deck(&self, did: DeckId) -> Result<Vec<CardId>> {
         self.db
             .prepare_cached(include_str!("cards_for_deck.sql"))?
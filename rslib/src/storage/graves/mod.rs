@@ -53,7 +53,18 @@ impl SqliteStorage {
             "select oid, type from graves where {}",
             pending_usn.pending_object_clause()
         ))?;
-        let mut rows = stmt.query([pending_usn])?;
+        let rows = stmt.query([pending_usn])?;
+        Self::graves_from_rows(rows)
+    }
+
+    /// All graves recorded since the last full sync, whether synced or not.
+    pub(crate) fn all_graves(&self) -> Result<Graves> {
+        let mut stmt = self.db.prepare("select oid, type from graves")?;
+        let rows = stmt.query([])?;
+        Self::graves_from_rows(rows)
+    }
+
+    fn graves_from_rows(mut rows: rusqlite::Rows) -> Result<Graves> {
         let mut graves = Graves::default();
         while let Some(row) = rows.next()? {
             let oid: i64 = row.get(0)?;
@@ -5,6 +5,7 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 
 use rusqlite::params;
+use rusqlite::OptionalExtension;
 use rusqlite::Row;
 use unicase::UniCase;
 
@@ -50,6 +51,41 @@ impl super::SqliteStorage {
             .collect()
     }
 
+    /// Up to `limit` note ids greater than `after`, in ascending order.
+    pub(crate) fn note_ids_after(&self, after: NoteId, limit: u32) -> Result<Vec<NoteId>> {
+        self.db
+            .prepare_cached("SELECT id FROM notes WHERE id > ? ORDER BY id LIMIT ?")?
+            .query_and_then(params![after, limit], |row| Ok(row.get(0)?))?
+            .collect()
+    }
+
+    /// The id a new note would be given: newer than every existing note, and
+    /// no earlier than the current time. Ids after it are also free.
+    pub(crate) fn next_note_id(&self) -> Result<NoteId> {
+        let max: i64 = self
+            .db
+            .prepare_cached("SELECT coalesce(max(id), 0) FROM notes")?
+            .query_row([], |row| row.get(0))?;
+        Ok(NoteId((max + 1).max(TimestampMillis::now().0)))
+    }
+
+    pub(crate) fn note_ids_modified_since(&self, since: TimestampSecs) -> Result<Vec<NoteId>> {
+        self.db
+            .prepare("SELECT id FROM notes WHERE mod >= ?")?
+            .query_and_then([since], |row| Ok(row.get(0)?))?
+            .collect()
+    }
+
+    pub(crate) fn notes_modified_since(
+        &self,
+        since: TimestampSecs,
+    ) -> Result<Vec<(NoteId, TimestampSecs)>> {
+        self.db
+            .prepare("SELECT id, mod FROM notes WHERE mod >= ? ORDER BY mod, id")?
+            .query_and_then([since], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect()
+    }
+
     /// If fields have been modified, caller must call note.prepare_for_update()
     /// prior to calling this.
     pub(crate) fn update_note(&self, note: &Note) -> Result<()> {
@@ -178,6 +214,22 @@ impl super::SqliteStorage {
             .collect()
     }
 
+    /// Returns [(nid, flds)] of notes whose fields contain `text`, ignoring
+    /// ASCII case. The caller should check the fields to see if they
+    /// actually match.
+    pub(crate) fn notes_with_fields_containing(&self, text: &str) -> Result<Vec<(NoteId, String)>> {
+        let pattern = format!(
+            "%{}%",
+            text.replace('\\', r"\\")
+                .replace('%', r"\%")
+                .replace('_', r"\_")
+        );
+        self.db
+            .prepare_cached(r"select id, flds from notes where flds like ? escape '\'")?
+            .query_and_then([pattern], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect()
+    }
+
     /// Returns [(nid, field 0)] of notes with the same checksum.
     /// The caller should strip the fields and compare to see if they actually
     /// match.
@@ -274,6 +326,49 @@ impl super::SqliteStorage {
         Ok(())
     }
 
+    pub(crate) fn for_each_note_tag<F>(&self, mut func: F) -> Result<()>
+    where
+        F: FnMut(&str),
+    {
+        let mut stmt = self
+            .db
+            .prepare_cached("select tags from notes where tags != ''")?;
+        let mut rows = stmt.query(params![])?;
+        while let Some(row) = rows.next()? {
+            func(row.get_ref(0)?.as_str()?);
+        }
+
+        Ok(())
+    }
+
+    /// Call `func` with the tags of the note of each card in 'search_cids'.
+    pub(crate) fn for_each_note_tag_of_searched_cards<F>(&self, mut func: F) -> Result<()>
+    where
+        F: FnMut(&str),
+    {
+        let mut stmt = self.db.prepare_cached(concat!(
+            "select n.tags from cards c, notes n where c.nid = n.id",
+            " and c.id in (select cid from search_cids) and n.tags != ''"
+        ))?;
+        let mut rows = stmt.query(params![])?;
+        while let Some(row) = rows.next()? {
+            func(row.get_ref(0)?.as_str()?);
+        }
+
+        Ok(())
+    }
+
+    /// The tags of the notes of the cards in 'search_cids'.
+    pub(crate) fn note_tags_of_searched_cards(&self) -> Result<HashMap<NoteId, String>> {
+        self.db
+            .prepare_cached(concat!(
+                "select id, tags from notes where id in",
+                " (select nid from cards where id in (select cid from search_cids))"
+            ))?
+            .query_and_then([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect()
+    }
+
     pub(crate) fn all_searched_notes(&self) -> Result<Vec<Note>> {
         self.db
             .prepare_cached(concat!(
@@ -362,6 +457,14 @@ impl super::SqliteStorage {
             .collect()
     }
 
+    pub(crate) fn get_note_id_by_guid(&self, guid: &str) -> Result<Option<NoteId>> {
+        self.db
+            .prepare_cached("SELECT id FROM notes WHERE guid = ?")?
+            .query_row([guid], |r| r.get(0))
+            .optional()
+            .map_err(Into::into)
+    }
+
     pub(crate) fn all_notes_by_guid(&mut self) -> Result<HashMap<String, NoteId>> {
         self.db
             .prepare("SELECT guid, id FROM notes")?
@@ -0,0 +1,8 @@
+SELECT nt.id,
+  nt.name,
+  COUNT(n.id)
+FROM notetypes nt
+  LEFT JOIN notes n ON n.mid = nt.id
+GROUP BY nt.id
+ORDER BY COUNT(n.id) DESC,
+  nt.name
@@ -158,6 +158,14 @@ impl SqliteStorage {
             .collect()
     }
 
+    /// Returns list of (id, name, note count), most used first.
+    pub(crate) fn get_notetype_note_counts(&self) -> Result<Vec<(NotetypeId, String, usize)>> {
+        self.db
+            .prepare_cached(include_str!("get_note_counts.sql"))?
+            .query_and_then([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect()
+    }
+
     fn update_notetype_fields(&self, ntid: NotetypeId, fields: &[NoteField]) -> Result<()> {
         self.db
             .prepare_cached("delete from fields where ntid=?")?
@@ -10,6 +10,7 @@ use rusqlite::types::ValueRef;
 use rusqlite::OptionalExtension;
 use rusqlite::Row;
 
+use super::ids_to_string;
 use super::SqliteStorage;
 use crate::error::Result;
 use crate::prelude::*;
@@ -179,6 +180,41 @@ impl SqliteStorage {
             .collect()
     }
 
+    /// Up to `limit` entries with ids above `after` and at or after `since`,
+    /// in id order. Each comes with the note and home deck of its card, if
+    /// the card still exists. If `deck_ids` is provided, only entries of
+    /// existing cards whose home deck is in it are returned.
+    pub(crate) fn get_revlog_entries_with_card_info(
+        &self,
+        after: RevlogId,
+        since: TimestampSecs,
+        deck_ids: Option<&[DeckId]>,
+        limit: u32,
+    ) -> Result<Vec<(RevlogEntry, Option<(NoteId, DeckId)>)>> {
+        let mut sql = concat!(
+            "SELECT r.id, r.cid, r.usn, r.ease, cast(r.ivl AS integer), ",
+            "cast(r.lastIvl AS integer), r.factor, r.time, r.type, c.nid, ",
+            "iif(c.odid, c.odid, c.did) FROM revlog r ",
+            "LEFT JOIN cards c ON c.id = r.cid WHERE r.id > ? AND r.id >= ?"
+        )
+        .to_string();
+        if let Some(deck_ids) = deck_ids {
+            sql.push_str(" AND iif(c.odid, c.odid, c.did) IN ");
+            ids_to_string(&mut sql, deck_ids);
+        }
+        sql.push_str(" ORDER BY r.id LIMIT ?");
+        self.db
+            .prepare(&sql)?
+            .query_and_then(params![after, since.0 * 1000, limit], |row| {
+                let card_info = match row.get::<_, Option<NoteId>>(9)? {
+                    Some(nid) => Some((nid, row.get(10)?)),
+                    None => None,
+                };
+                Ok((row_to_revlog_entry(row)?, card_info))
+            })?
+            .collect()
+    }
+
     pub(crate) fn studied_today(&self, day_cutoff: TimestampSecs) -> Result<StudiedToday> {
         let start = day_cutoff.adding_secs(-86_400).as_millis();
         self.db
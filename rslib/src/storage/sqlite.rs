@@ -32,6 +32,7 @@ use crate::prelude::*;
 use crate::scheduler::timing::local_minutes_west_for_stamp;
 use crate::scheduler::timing::v1_creation_date;
 use crate::storage::card::data::CardData;
+use crate::text::strip_html_preserving_media_filenames;
 use crate::text::without_combining;
 use crate::text::CowMapping;
 
@@ -74,6 +75,7 @@ fn open_or_create_collection_db(path: &Path) -> Result<Connection> {
     db.set_prepared_statement_cache_capacity(50);
 
     add_field_index_function(&db)?;
+    add_sort_field_at_index_function(&db)?;
     add_regexp_function(&db)?;
     add_regexp_fields_function(&db)?;
     add_regexp_tags_function(&db)?;
@@ -114,6 +116,23 @@ fn add_field_index_function(db: &Connection) -> rusqlite::Result<()> {
     )
 }
 
+/// Adds sql function sort_field_at_index(flds, index)
+/// to return the field at zero-based index, prepared in the same way as
+/// the sort field stored in notes.sfld.
+fn add_sort_field_at_index_function(db: &Connection) -> rusqlite::Result<()> {
+    db.create_scalar_function(
+        "sort_field_at_index",
+        2,
+        FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let mut fields = ctx.get_raw(0).as_str()?.split('\x1f');
+            let idx: u16 = ctx.get(1)?;
+            let field = fields.nth(idx as usize).unwrap_or("");
+            Ok(strip_html_preserving_media_filenames(field).into_owned())
+        },
+    )
+}
+
 bitflags! {
     pub(crate) struct ProcessTextFlags: u8 {
         const NoCombining = 1;
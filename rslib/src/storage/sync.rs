@@ -45,6 +45,17 @@ impl SqliteStorage {
             .collect()
     }
 
+    pub(crate) fn count_objects_pending_sync(&self, table: &str, usn: Usn) -> Result<usize> {
+        Ok(self
+            .db
+            .prepare_cached(&format!(
+                "select count() from {} where {}",
+                table,
+                usn.pending_object_clause()
+            ))?
+            .query_row([usn], |r| r.get(0))?)
+    }
+
     pub(crate) fn maybe_update_object_usns<I: ToSql>(
         &self,
         table: &str,
@@ -51,6 +51,17 @@ pub struct DecksAndConfig {
     config: Vec<DeckConfSchema11>,
 }
 
+impl UnchunkedChanges {
+    pub(in crate::sync) fn is_empty(&self) -> bool {
+        self.notetypes.is_empty()
+            && self.decks_and_config.decks.is_empty()
+            && self.decks_and_config.config.is_empty()
+            && self.tags.is_empty()
+            && self.config.is_none()
+            && self.creation_stamp.is_none()
+    }
+}
+
 impl NormalSyncer<'_> {
     // This was assumed to a cheap operation when originally written - it didn't
     // anticipate the large deck trees and note types some users would create.
@@ -43,6 +43,13 @@ pub struct Chunk {
     pub notes: Vec<NoteEntry>,
 }
 
+impl Chunk {
+    /// True if the chunk carries no objects, whether or not it is the last.
+    pub(in crate::sync) fn is_empty(&self) -> bool {
+        self.revlog.is_empty() && self.cards.is_empty() && self.notes.is_empty()
+    }
+}
+
 #[derive(Serialize_tuple, Deserialize, Debug)]
 pub struct NoteEntry {
     pub id: NoteId,
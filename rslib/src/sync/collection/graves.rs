@@ -15,12 +15,16 @@ pub struct ApplyGravesRequest {
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Graves {
-    pub(crate) cards: Vec<CardId>,
-    pub(crate) decks: Vec<DeckId>,
-    pub(crate) notes: Vec<NoteId>,
+    pub cards: Vec<CardId>,
+    pub decks: Vec<DeckId>,
+    pub notes: Vec<NoteId>,
 }
 
 impl Graves {
+    pub(in crate::sync) fn is_empty(&self) -> bool {
+        self.cards.is_empty() && self.decks.is_empty() && self.notes.is_empty()
+    }
+
     pub(in crate::sync) fn take_chunk(&mut self) -> Option<Graves> {
         let mut limit = CHUNK_SIZE;
         let mut out = Graves::default();
@@ -57,6 +57,12 @@ pub struct SyncMeta {
 }
 
 impl SyncMeta {
+    /// The server forces a one-way sync of collections above the payload
+    /// limit.
+    pub(crate) fn too_large_for_normal_sync(&self) -> bool {
+        self.collection_bytes > *MAXIMUM_SYNC_PAYLOAD_BYTES_UNCOMPRESSED
+    }
+
     pub(in crate::sync) fn compared_to_remote(
         &self,
         remote: SyncMeta,
@@ -159,7 +165,7 @@ pub fn server_meta(req: MetaRequest, col: &mut Collection) -> HttpResult<SyncMet
         });
     }
     let mut meta = col.sync_meta().or_internal_err("sync meta")?;
-    if meta.collection_bytes > *MAXIMUM_SYNC_PAYLOAD_BYTES_UNCOMPRESSED {
+    if meta.too_large_for_normal_sync() {
         info!("collection is too large, forcing one-way sync");
         meta.schema = TimestampMillis::now();
     }
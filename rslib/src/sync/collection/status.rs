@@ -10,7 +10,52 @@ use crate::sync::collection::meta::SyncMeta;
 use crate::sync::collection::normal::ClientSyncState;
 use crate::sync::http_client::HttpSyncClient;
 
+/// The number of objects changed since the last sync, by type.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PendingSyncChanges {
+    pub cards: usize,
+    pub notes: usize,
+    pub decks: usize,
+    pub notetypes: usize,
+    pub revlog: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PendingSyncStatus {
+    pub pending: PendingSyncChanges,
+    pub last_sync: TimestampMillis,
+    pub modified: TimestampMillis,
+    pub schema_modified: TimestampMillis,
+    /// True if the next sync will have to be a one-way one.
+    pub full_sync_required: bool,
+}
+
 impl Collection {
+    /// Count the changes that the next sync will send, using the same usn
+    /// comparison as the sync itself.
+    pub fn pending_sync_status(&self) -> Result<PendingSyncStatus> {
+        let usn = self.usn()?;
+        let count = |table| self.storage.count_objects_pending_sync(table, usn);
+        let pending = PendingSyncChanges {
+            cards: count("cards")?,
+            notes: count("notes")?,
+            decks: count("decks")?,
+            notetypes: count("notetypes")?,
+            revlog: count("revlog")?,
+        };
+        let stamps = self.storage.get_collection_timestamps()?;
+        // a server also forces a full sync when the collection is too large
+        let full_sync_required = stamps.schema_changed_since_sync()
+            || (self.server && self.sync_meta()?.too_large_for_normal_sync());
+        Ok(PendingSyncStatus {
+            pending,
+            last_sync: stamps.last_sync,
+            modified: stamps.collection_change,
+            schema_modified: stamps.schema_change,
+            full_sync_required,
+        })
+    }
+
     /// Checks local collection only. If local collection is clean but changes
     /// are pending on AnkiWeb, NoChanges will be returned.
     pub fn sync_status_offline(&mut self) -> Result<sync_status_response::Required> {
@@ -44,7 +44,13 @@ use crate::sync::collection::start::StartRequest;
 use crate::sync::collection::upload::UploadResponse;
 use crate::sync::collection::upload::CORRUPT_MESSAGE;
 use crate::sync::http_client::HttpSyncClient;
+use crate::sync::http_server::config::UserCredentials;
+use crate::sync::http_server::default_delete_confirm_threshold;
 use crate::sync::http_server::default_ip_header;
+use crate::sync::http_server::default_login_lockout_max_secs;
+use crate::sync::http_server::default_login_lockout_threshold;
+use crate::sync::http_server::default_rate_limit_capacity;
+use crate::sync::http_server::default_rate_limit_refill_rate_per_sec;
 use crate::sync::http_server::SimpleServer;
 use crate::sync::http_server::SyncServerConfig;
 use crate::sync::login::HostKeyRequest;
@@ -74,6 +80,9 @@ static AUTH: LazyLock<TestAuth> = LazyLock::new(|| {
     }
 });
 
+/// Lets tests use the server's admin endpoints.
+const ADMIN_TOKEN: &str = "admin";
+
 pub(in crate::sync) async fn with_active_server<F, O>(op: F) -> Result<()>
 where
     F: FnOnce(HttpSyncClient) -> O,
@@ -82,12 +91,26 @@ where
     let _ = set_global_logger(None);
     // start server
     let base_folder = tempdir()?;
-    std::env::set_var("SYNC_USER1", "user:pass");
     let (addr, server_fut) = SimpleServer::make_server(SyncServerConfig {
         host: "127.0.0.1".parse().unwrap(),
         port: 0,
         base_folder: base_folder.path().into(),
         ip_header: default_ip_header(),
+        delete_confirm_threshold: default_delete_confirm_threshold(),
+        rate_limit_capacity: default_rate_limit_capacity(),
+        rate_limit_refill_rate_per_sec: default_rate_limit_refill_rate_per_sec(),
+        users: vec![UserCredentials {
+            name: "user".into(),
+            password: "pass".into(),
+        }],
+        passwords_hashed: false,
+        login_lockout_threshold: default_login_lockout_threshold(),
+        login_lockout_max_secs: default_login_lockout_max_secs(),
+        login_lockout_file: None,
+        read_only: false,
+        import_allow_private_hosts: false,
+        admin_token: Some(ADMIN_TOKEN.into()),
+        copy_base: None,
     })
     .await
     .unwrap();
@@ -391,6 +414,41 @@ async fn sync_errors_should_prompt_db_check() -> Result<()> {
     .await
 }
 
+#[tokio::test]
+async fn read_only_server_allows_pulling_changes() -> Result<()> {
+    with_active_server(|client| async move {
+        let ctx = SyncTestContext::new(client);
+        upload_download(&ctx).await?;
+
+        // a note added before the server became read-only
+        let mut col1 = ctx.col1();
+        col1_setup(&mut col1);
+        ctx.normal_sync(&mut col1).await;
+        ctx.set_read_only(true).await;
+
+        // can still be pulled by another client
+        let mut col2 = ctx.col2();
+        let out = ctx.normal_sync(&mut col2).await;
+        assert_eq!(out.required, SyncActionRequired::NoChanges);
+        assert_eq!(col2.storage.get_all_note_ids()?.len(), 2);
+
+        // but changes can't be sent until it is writable again
+        col1_setup(&mut col2);
+        let err = NormalSyncer::new(&mut col2, ctx.cloned_client())
+            .sync()
+            .await
+            .unwrap_err();
+        assert_eq!(unwrap_sync_err_kind(err), SyncErrorKind::ServerError);
+        ctx.set_read_only(false).await;
+        ctx.normal_sync(&mut col2).await;
+        ctx.normal_sync(&mut col1).await;
+        assert_eq!(col1.storage.get_all_note_ids()?.len(), 3);
+
+        Ok(())
+    })
+    .await
+}
+
 /// Old AnkiMobile versions sent grave ids as strings
 #[tokio::test]
 async fn string_grave_ids_are_handled() -> Result<()> {
@@ -530,6 +588,18 @@ impl SyncTestContext {
     fn cloned_client(&self) -> HttpSyncClient {
         self.client.clone()
     }
+
+    async fn set_read_only(&self, read_only: bool) {
+        Client::new()
+            .put(self.client.endpoint.join("api/v1/read-only").unwrap())
+            .bearer_auth(ADMIN_TOKEN)
+            .json(&json!({ "readOnly": read_only }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
 }
 
 // Setup + full syncs
@@ -4,7 +4,10 @@
 use std::error::Error;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::time::Duration;
 
+use axum::http::header;
+use axum::http::HeaderValue;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::response::Redirect;
@@ -34,6 +37,19 @@ impl Error for HttpError {
     }
 }
 
+/// The source of a [StatusCode::TOO_MANY_REQUESTS] error, telling the client
+/// when to try again.
+#[derive(Debug)]
+pub struct RetryAfter(pub Duration);
+
+impl Display for RetryAfter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "retry after {}s", self.0.as_secs())
+    }
+}
+
+impl Error for RetryAfter {}
+
 impl HttpError {
     pub fn new_without_source(code: StatusCode, context: impl Into<String>) -> Self {
         Self {
@@ -64,7 +80,17 @@ impl IntoResponse for HttpError {
         if code == StatusCode::PERMANENT_REDIRECT {
             Redirect::permanent(&context).into_response()
         } else {
-            (code, code.as_str().to_string()).into_response()
+            let mut response = (code, code.as_str().to_string()).into_response();
+            if let Some(RetryAfter(after)) = source
+                .as_deref()
+                .and_then(|err| err.downcast_ref::<RetryAfter>())
+            {
+                response.headers_mut().insert(
+                    header::RETRY_AFTER,
+                    HeaderValue::from(after.as_secs().max(1)),
+                );
+            }
+            response
         }
     }
 }
@@ -0,0 +1,111 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use anki_io::create_dir_all;
+use tracing::error;
+
+use crate::prelude::*;
+use crate::sync::http_server::SimpleServer;
+
+/// How often open collections are checked for a due backup.
+const BACKUP_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The result of the last automatic backup of a user's collection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BackupOutcome {
+    pub time: TimestampSecs,
+    /// Why the backup failed, if it did.
+    pub error: Option<String>,
+}
+
+impl BackupOutcome {
+    fn new(result: Result<()>) -> Self {
+        Self {
+            time: TimestampSecs::now(),
+            error: result.err().map(|err| err.to_string()),
+        }
+    }
+}
+
+impl SimpleServer {
+    /// Back up each open collection that has changed since its last backup,
+    /// if its backup interval has elapsed, and prune old backups according to
+    /// its limits. The desktop does this on close, which a server never does.
+    ///
+    /// The server state is locked once per user, only while the collection is
+    /// read into memory. Compressing and writing the backup happen afterwards.
+    pub(crate) fn run_scheduled_backups(&self) {
+        let Ok(hkeys) = self
+            .state
+            .lock()
+            .map(|state| state.users.keys().cloned().collect::<Vec<_>>())
+        else {
+            return;
+        };
+        let mut pending = vec![];
+        for hkey in hkeys {
+            let Ok(mut state) = self.state.lock() else {
+                return;
+            };
+            let Some(user) = state.users.get_mut(&hkey) else {
+                continue;
+            };
+            // a sync may have replaced the collection file
+            if user.sync_state.is_some() {
+                continue;
+            }
+            let Some(col) = user.col.as_mut() else {
+                continue;
+            };
+            match start_backup(col) {
+                Ok(Some(handle)) => pending.push((hkey, handle)),
+                Ok(None) => (),
+                Err(err) => {
+                    error!(%err, "automatic backup failed");
+                    user.last_backup = Some(BackupOutcome::new(Err(err)));
+                }
+            }
+        }
+
+        for (hkey, handle) in pending {
+            let result = finish_backup(handle);
+            if let Err(err) = &result {
+                error!(%err, "automatic backup failed");
+            }
+            if let Ok(mut state) = self.state.lock() {
+                if let Some(user) = state.users.get_mut(&hkey) {
+                    user.last_backup = Some(BackupOutcome::new(result));
+                }
+            }
+        }
+    }
+}
+
+fn start_backup(col: &mut Collection) -> Result<Option<JoinHandle<Result<()>>>> {
+    let folder = col.backup_folder();
+    create_dir_all(&folder)?;
+    col.maybe_backup(folder, false)
+}
+
+/// Wait for a backup thread. A panic is reported as the backup's error, so
+/// the outcome of the remaining users' backups is still recorded.
+fn finish_backup(handle: JoinHandle<Result<()>>) -> Result<()> {
+    match handle.join() {
+        Ok(result) => result,
+        Err(panic) => invalid_input!("backup panicked: {:?}", panic),
+    }
+}
+
+/// Run scheduled backups until the server shuts down.
+pub(super) async fn run_backup_task(server: Arc<SimpleServer>) {
+    let mut interval = tokio::time::interval(BACKUP_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let server = server.clone();
+        let _ = tokio::task::spawn_blocking(move || server.run_scheduled_backups()).await;
+    }
+}
@@ -0,0 +1,478 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::collections::HashSet;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use axum_client_ip::ClientIpSource;
+use pbkdf2::password_hash::PasswordHash;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::default_base;
+use super::default_delete_confirm_threshold;
+use super::default_host;
+use super::default_ip_header;
+use super::default_login_lockout_max_secs;
+use super::default_login_lockout_threshold;
+use super::default_port;
+use super::default_rate_limit_capacity;
+use super::default_rate_limit_refill_rate_per_sec;
+use super::SyncServerConfig;
+
+/// The env var naming an optional JSON file of server settings. SYNC_* env
+/// vars take precedence over the file's values.
+pub const CONFIG_FILE_ENV: &str = "SYNC_CONFIG_FILE";
+
+/// A problem with the server's settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// The offending setting, such as `users[0].name`, if known. Settings
+    /// provided by env vars use the same key as the config file, apart from
+    /// malformed SYNC_USER vars, which are named as is.
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl ConfigError {
+    fn new(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            key: Some(key.into()),
+            message: message.into(),
+        }
+    }
+
+    fn without_key(message: impl Into<String>) -> Self {
+        Self {
+            key: None,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "{key}: {}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct UserCredentials {
+    pub name: String,
+    /// A PHC string if [SyncServerConfig::passwords_hashed] is set.
+    pub password: String,
+}
+
+/// The contents of [CONFIG_FILE_ENV]. Keys match the SYNC_* env vars, without
+/// the prefix and in lowercase.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    host: Option<IpAddr>,
+    port: Option<u16>,
+    base: Option<PathBuf>,
+    ip_header: Option<ClientIpSource>,
+    delete_confirm_threshold: Option<usize>,
+    rate_limit_capacity: Option<u32>,
+    rate_limit_refill_rate_per_sec: Option<f64>,
+    login_lockout_threshold: Option<u32>,
+    login_lockout_max_secs: Option<u64>,
+    login_lockout_file: Option<PathBuf>,
+    read_only: Option<bool>,
+    import_allow_private_hosts: Option<bool>,
+    admin_token: Option<String>,
+    copy_base: Option<PathBuf>,
+    #[serde(default)]
+    users: Vec<UserCredentials>,
+    #[serde(default)]
+    passwords_hashed: bool,
+}
+
+/// The SYNC_* env vars. Users are read separately, as they are numbered.
+#[derive(Deserialize, Default)]
+struct EnvConfig {
+    config_file: Option<PathBuf>,
+    host: Option<IpAddr>,
+    port: Option<u16>,
+    base: Option<PathBuf>,
+    ip_header: Option<ClientIpSource>,
+    delete_confirm_threshold: Option<usize>,
+    rate_limit_capacity: Option<u32>,
+    rate_limit_refill_rate_per_sec: Option<f64>,
+    login_lockout_threshold: Option<u32>,
+    login_lockout_max_secs: Option<u64>,
+    login_lockout_file: Option<PathBuf>,
+    read_only: Option<bool>,
+    import_allow_private_hosts: Option<bool>,
+    admin_token: Option<String>,
+    copy_base: Option<PathBuf>,
+}
+
+/// The settings reported by the health endpoint, leaving out anything that
+/// is secret or reveals the server's layout on disk.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicConfig {
+    pub host: String,
+    pub port: u16,
+    pub ip_header: String,
+    pub delete_confirm_threshold: usize,
+    pub rate_limit_capacity: u32,
+    pub rate_limit_refill_rate_per_sec: f64,
+    pub login_lockout_threshold: u32,
+    pub login_lockout_max_secs: u64,
+    pub user_count: usize,
+    /// Whether the whole server refuses changes. The health endpoint reports
+    /// the current value, which may have been changed since startup.
+    pub read_only: bool,
+    /// The number of users refusing changes in their own right.
+    pub read_only_user_count: usize,
+}
+
+impl SyncServerConfig {
+    /// Read the file named by [CONFIG_FILE_ENV] if it is set, then apply any
+    /// SYNC_* env vars on top, and [SyncServerConfig::validate] the result.
+    /// SYNC_USER1, SYNC_USER2... replace the file's users if any are set.
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::load_from_vars(std::env::vars())
+    }
+
+    pub(crate) fn load_from_vars(
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let vars: Vec<_> = vars.into_iter().collect();
+        let env: EnvConfig = envy::prefixed("SYNC_")
+            .from_iter(vars.iter().cloned())
+            .map_err(|err| ConfigError::without_key(format!("reading SYNC_* env vars: {err}")))?;
+        let file = match &env.config_file {
+            Some(path) => {
+                let text = std::fs::read_to_string(path).map_err(|err| {
+                    ConfigError::without_key(format!("reading {}: {err}", path.display()))
+                })?;
+                parse_config_file(&text)?
+            }
+            None => ConfigFile::default(),
+        };
+        let env_var = |name: &str| {
+            vars.iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, val)| val.as_str())
+        };
+        let mut users = vec![];
+        for idx in 1.. {
+            let key = format!("SYNC_USER{idx}");
+            let Some(val) = env_var(&key) else {
+                break;
+            };
+            let (name, password) = val
+                .split_once(':')
+                .ok_or_else(|| ConfigError::new(&key, "should be in 'username:password' format"))?;
+            users.push(UserCredentials {
+                name: name.into(),
+                password: password.into(),
+            });
+        }
+        if users.is_empty() {
+            users = file.users;
+        }
+
+        let config = SyncServerConfig {
+            host: env.host.or(file.host).unwrap_or_else(default_host),
+            port: env.port.or(file.port).unwrap_or_else(default_port),
+            base_folder: env.base.or(file.base).unwrap_or_else(default_base),
+            ip_header: env
+                .ip_header
+                .or(file.ip_header)
+                .unwrap_or_else(default_ip_header),
+            delete_confirm_threshold: env
+                .delete_confirm_threshold
+                .or(file.delete_confirm_threshold)
+                .unwrap_or_else(default_delete_confirm_threshold),
+            rate_limit_capacity: env
+                .rate_limit_capacity
+                .or(file.rate_limit_capacity)
+                .unwrap_or_else(default_rate_limit_capacity),
+            rate_limit_refill_rate_per_sec: env
+                .rate_limit_refill_rate_per_sec
+                .or(file.rate_limit_refill_rate_per_sec)
+                .unwrap_or_else(default_rate_limit_refill_rate_per_sec),
+            users,
+            passwords_hashed: env_var("PASSWORDS_HASHED").is_some() || file.passwords_hashed,
+            login_lockout_threshold: env
+                .login_lockout_threshold
+                .or(file.login_lockout_threshold)
+                .unwrap_or_else(default_login_lockout_threshold),
+            login_lockout_max_secs: env
+                .login_lockout_max_secs
+                .or(file.login_lockout_max_secs)
+                .unwrap_or_else(default_login_lockout_max_secs),
+            login_lockout_file: env.login_lockout_file.or(file.login_lockout_file),
+            read_only: env.read_only.or(file.read_only).unwrap_or_default(),
+            import_allow_private_hosts: env
+                .import_allow_private_hosts
+                .or(file.import_allow_private_hosts)
+                .unwrap_or_default(),
+            admin_token: env.admin_token.or(file.admin_token),
+            copy_base: env.copy_base.or(file.copy_base),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check the settings are usable, returning the first problem found.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.base_folder.as_os_str().is_empty() {
+            return Err(ConfigError::new("base", "must not be empty"));
+        }
+        if self.rate_limit_capacity < 1 {
+            return Err(ConfigError::new(
+                "rate_limit_capacity",
+                "must be at least 1",
+            ));
+        }
+        if !(self.rate_limit_refill_rate_per_sec.is_finite()
+            && self.rate_limit_refill_rate_per_sec > 0.0)
+        {
+            return Err(ConfigError::new(
+                "rate_limit_refill_rate_per_sec",
+                "must be a positive number",
+            ));
+        }
+        if self.login_lockout_threshold < 1 {
+            return Err(ConfigError::new(
+                "login_lockout_threshold",
+                "must be at least 1",
+            ));
+        }
+        if self.login_lockout_max_secs < 1 {
+            return Err(ConfigError::new(
+                "login_lockout_max_secs",
+                "must be at least 1",
+            ));
+        }
+        if let Some(copy_base) = &self.copy_base {
+            if copy_base.as_os_str().is_empty()
+                || copy_base.starts_with(&self.base_folder)
+                || self.base_folder.starts_with(copy_base)
+            {
+                return Err(ConfigError::new(
+                    "copy_base",
+                    "must be a folder outside the base folder",
+                ));
+            }
+        }
+        if self.users.is_empty() {
+            return Err(ConfigError::new(
+                "users",
+                "no users defined; SYNC_USER1 env var should be set",
+            ));
+        }
+        let mut names = HashSet::new();
+        for (idx, user) in self.users.iter().enumerate() {
+            if user.name.is_empty() || user.name.contains(['/', '\\']) || user.name == ".." {
+                return Err(ConfigError::new(
+                    format!("users[{idx}].name"),
+                    "must be a non-empty name usable as a folder name",
+                ));
+            }
+            if !names.insert(&user.name) {
+                return Err(ConfigError::new(
+                    format!("users[{idx}].name"),
+                    format!("duplicate user '{}'", user.name),
+                ));
+            }
+            if self.passwords_hashed && PasswordHash::new(&user.password).is_err() {
+                return Err(ConfigError::new(
+                    format!("users[{idx}].password"),
+                    "not a valid password hash, but passwords_hashed is set",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn public_config(&self) -> PublicConfig {
+        PublicConfig {
+            host: self.host.to_string(),
+            port: self.port,
+            ip_header: format!("{:?}", self.ip_header),
+            delete_confirm_threshold: self.delete_confirm_threshold,
+            rate_limit_capacity: self.rate_limit_capacity,
+            rate_limit_refill_rate_per_sec: self.rate_limit_refill_rate_per_sec,
+            login_lockout_threshold: self.login_lockout_threshold,
+            login_lockout_max_secs: self.login_lockout_max_secs,
+            user_count: self.users.len(),
+            read_only: self.read_only,
+            // users can only be made read-only while the server is running
+            read_only_user_count: 0,
+        }
+    }
+}
+
+fn parse_config_file(text: &str) -> Result<ConfigFile, ConfigError> {
+    let mut deserializer = serde_json::Deserializer::from_str(text);
+    serde_path_to_error::deserialize(&mut deserializer).map_err(|err| {
+        let key = err.path().to_string();
+        ConfigError {
+            // the root of the file has no key
+            key: (key != ".").then_some(key),
+            message: err.into_inner().to_string(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(key, val)| (key.to_string(), val.to_string()))
+            .collect()
+    }
+
+    fn load_with_file(
+        json: &str,
+        extra_vars: &[(&str, &str)],
+    ) -> Result<SyncServerConfig, ConfigError> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, json).unwrap();
+        let mut vars = vars(extra_vars);
+        vars.push((CONFIG_FILE_ENV.into(), path.to_string_lossy().into()));
+        SyncServerConfig::load_from_vars(vars)
+    }
+
+    #[test]
+    fn env_vars_override_file() {
+        let config = load_with_file(
+            r#"{"port": 9000, "base": "/srv/sync", "rate_limit_capacity": 5,
+                "users": [{"name": "alice", "password": "secret"}]}"#,
+            &[("SYNC_PORT", "9001")],
+        )
+        .unwrap();
+        assert_eq!(config.port, 9001);
+        assert_eq!(config.base_folder, PathBuf::from("/srv/sync"));
+        assert_eq!(config.rate_limit_capacity, 5);
+        assert_eq!(config.users[0].name, "alice");
+        assert_eq!(config.public_config().user_count, 1);
+
+        // users in the env replace the file's
+        let config = load_with_file(
+            r#"{"base": "/srv/sync", "users": [{"name": "alice", "password": "secret"}]}"#,
+            &[("SYNC_USER1", "bob:pass"), ("SYNC_USER2", "carol:pass")],
+        )
+        .unwrap();
+        let names: Vec<_> = config.users.iter().map(|user| &user.name).collect();
+        assert_eq!(names, ["bob", "carol"]);
+
+        // no file is needed
+        let config = SyncServerConfig::load_from_vars(vars(&[
+            ("SYNC_BASE", "/srv/sync"),
+            ("SYNC_USER1", "bob:pass"),
+        ]))
+        .unwrap();
+        assert_eq!(config.port, default_port());
+        assert!(!config.read_only);
+
+        let config = load_with_file(
+            r#"{"base": "/srv/sync", "read_only": true,
+                "users": [{"name": "alice", "password": "secret"}]}"#,
+            &[],
+        )
+        .unwrap();
+        assert!(config.read_only);
+        assert!(config.public_config().read_only);
+        let config = load_with_file(
+            r#"{"base": "/srv/sync", "read_only": true,
+                "users": [{"name": "alice", "password": "secret"}]}"#,
+            &[("SYNC_READ_ONLY", "false")],
+        )
+        .unwrap();
+        assert!(!config.read_only);
+    }
+
+    #[test]
+    fn errors_name_the_key() {
+        let user = r#""users": [{"name": "alice", "password": "secret"}]"#;
+        let key_of = |json: String| load_with_file(&json, &[]).unwrap_err().key;
+        assert_eq!(
+            key_of(format!(r#"{{"base": "/srv", "port": "high", {user}}}"#)),
+            Some("port".into())
+        );
+        assert_eq!(
+            key_of(r#"{"base": "/srv", "users": [{"name": 1, "password": ""}]}"#.into()),
+            Some("users[0].name".into())
+        );
+        let err = load_with_file(&format!(r#"{{"bass": "/srv", {user}}}"#), &[]).unwrap_err();
+        assert!(err.message.contains("unknown field `bass`"));
+        assert_eq!(
+            key_of(format!(
+                r#"{{"base": "/srv", "rate_limit_capacity": 0, {user}}}"#
+            )),
+            Some("rate_limit_capacity".into())
+        );
+        assert_eq!(
+            key_of(format!(
+                r#"{{"base": "/srv", "rate_limit_refill_rate_per_sec": -1, {user}}}"#
+            )),
+            Some("rate_limit_refill_rate_per_sec".into())
+        );
+        assert_eq!(
+            key_of(format!(
+                r#"{{"base": "/srv", "login_lockout_threshold": 0, {user}}}"#
+            )),
+            Some("login_lockout_threshold".into())
+        );
+        assert_eq!(
+            key_of(format!(
+                r#"{{"base": "/srv", "passwords_hashed": true, {user}}}"#
+            )),
+            Some("users[0].password".into())
+        );
+        assert_eq!(
+            key_of(r#"{"base": "/srv", "users": []}"#.into()),
+            Some("users".into())
+        );
+        for copy_base in ["/srv/copies", "/", ""] {
+            assert_eq!(
+                key_of(format!(
+                    r#"{{"base": "/srv", "copy_base": "{copy_base}", {user}}}"#
+                )),
+                Some("copy_base".into())
+            );
+        }
+        assert!(load_with_file(
+            &format!(r#"{{"base": "/srv", "copy_base": "/copies", {user}}}"#),
+            &[]
+        )
+        .is_ok());
+        assert_eq!(
+            SyncServerConfig::load_from_vars(vars(&[
+                ("SYNC_BASE", "/srv"),
+                ("SYNC_USER1", "alice:pass"),
+                ("SYNC_USER2", "alice:word"),
+            ]))
+            .unwrap_err()
+            .key,
+            Some("users[1].name".into())
+        );
+        assert_eq!(
+            SyncServerConfig::load_from_vars(vars(&[
+                ("SYNC_BASE", "/srv"),
+                ("SYNC_USER1", "alice")
+            ]))
+            .unwrap_err()
+            .key,
+            Some("SYNC_USER1".into())
+        );
+    }
+}
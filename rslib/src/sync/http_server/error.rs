@@ -2,16 +2,19 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use std::time::Duration;
+
 use axum::{
     extract::rejection::JsonRejection,
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde_json::json;
 
 use crate::{
-    error::AnkiError,
+    error::{AnkiError, NetworkErrorKind},
+    links::help_page_to_link,
     prelude::I18n,
     sync::error::HttpError,
 };
@@ -21,18 +24,49 @@ pub enum ApiError {
     Anki(AnkiError),
     Json(JsonRejection),
     Http(HttpError),
+    /// Another request held the collection for too long.
+    Busy {
+        retry_after: Duration,
+    },
+    /// Too many expensive requests have been made recently.
+    RateLimited {
+        retry_after: Duration,
+    },
+    /// The server or user has been made read-only, so changes are refused.
+    ReadOnly,
+    /// A delete would remove more cards than the server allows without
+    /// explicit confirmation.
+    ConfirmationRequired {
+        count: usize,
+        threshold: usize,
+    },
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
+        let mut help_url = None;
+        let mut retry_after = None;
+        let mut would_delete = None;
+        let mut reason = None;
         let (status, code, message) = match self {
             ApiError::Anki(err) => {
                 let status = match &err {
                     AnkiError::NotFound { .. } => StatusCode::NOT_FOUND,
                     AnkiError::InvalidInput { .. } => StatusCode::BAD_REQUEST,
+                    AnkiError::FilteredDeckError { .. } => StatusCode::BAD_REQUEST,
+                    AnkiError::SearchError { .. } => StatusCode::BAD_REQUEST,
                     AnkiError::Existing { .. } => StatusCode::CONFLICT,
+                    AnkiError::SchedulerUpgradeRequired => StatusCode::CONFLICT,
+                    AnkiError::SchemaChangeNotAllowed => StatusCode::CONFLICT,
+                    AnkiError::FsrsInsufficientData => StatusCode::UNPROCESSABLE_ENTITY,
+                    AnkiError::FsrsParamsInvalid => StatusCode::BAD_REQUEST,
+                    AnkiError::NetworkError { source } => match source.kind {
+                        NetworkErrorKind::Timeout => StatusCode::GATEWAY_TIMEOUT,
+                        _ => StatusCode::BAD_GATEWAY,
+                    },
                     _ => StatusCode::INTERNAL_SERVER_ERROR,
                 };
+                help_url = err.help_page().map(help_page_to_link);
                 (status, status.as_u16(), err.message(&I18n::template_only()))
             }
             ApiError::Json(err) => (
@@ -41,8 +75,61 @@ impl IntoResponse for ApiError {
                 err.body_text(),
             ),
             ApiError::Http(err) => (err.code, err.code.as_u16(), err.context),
+            ApiError::Busy { retry_after: after } => {
+                retry_after = Some(HeaderValue::from(after.as_secs().max(1)));
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+                    "the collection is busy".to_string(),
+                )
+            }
+            ApiError::RateLimited { retry_after: after } => {
+                retry_after = Some(HeaderValue::from(after.as_secs().max(1)));
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    StatusCode::TOO_MANY_REQUESTS.as_u16(),
+                    "too many expensive requests; try again later".to_string(),
+                )
+            }
+            ApiError::ReadOnly => {
+                reason = Some("read-only");
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+                    "the server is read-only; only requests that make no changes are allowed"
+                        .to_string(),
+                )
+            }
+            ApiError::ConfirmationRequired { count, threshold } => {
+                would_delete = Some(count);
+                (
+                    StatusCode::PRECONDITION_REQUIRED,
+                    StatusCode::PRECONDITION_REQUIRED.as_u16(),
+                    format!(
+                        "this request would delete {count} cards, more than the {threshold} \
+                         allowed without confirmation; to proceed, repeat it with \
+                         \"confirm\": true in the body or an X-Confirm-Destructive: yes header"
+                    ),
+                )
+            }
         };
-        (status, Json(json!({ "error": { "code": code, "message": message } }))).into_response()
+        let mut error = json!({ "code": code, "message": message });
+        if let Some(help_url) = help_url {
+            error["helpUrl"] = help_url.into();
+        }
+        if let Some(would_delete) = would_delete {
+            error["wouldDelete"] = would_delete.into();
+        }
+        if let Some(reason) = reason {
+            error["reason"] = reason.into();
+        }
+        let mut response = (status, Json(json!({ "error": error }))).into_response();
+        if let Some(retry_after) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, retry_after);
+        }
+        response
     }
 }
 
@@ -55,7 +55,7 @@ impl SyncProtocol for Arc<SimpleServer> {
         &self,
         req: SyncRequest<HostKeyRequest>,
     ) -> HttpResult<SyncResponse<HostKeyResponse>> {
-        self.get_host_key(req.json()?)
+        self.get_host_key(req.ip, req.json()?)
     }
 
     async fn meta(&self, req: SyncRequest<MetaRequest>) -> HttpResult<SyncResponse<SyncMeta>> {
@@ -73,6 +73,13 @@ impl SyncProtocol for Arc<SimpleServer> {
         self.with_authenticated_user(req, |user, req| {
             let skey = req.skey()?;
             let req = req.json()?;
+            if req
+                .deprecated_client_graves
+                .as_ref()
+                .is_some_and(|graves| !graves.is_empty())
+            {
+                self.refuse_changes_if_read_only(user)?;
+            }
             user.start_new_sync(skey)?;
             user.with_sync_state(skey, |col, state| server_start(req, col, state))
                 .and_then(SyncResponse::try_from_obj)
@@ -87,6 +94,9 @@ impl SyncProtocol for Arc<SimpleServer> {
         self.with_authenticated_user(req, |user, req| {
             let skey = req.skey()?;
             let req = req.json()?;
+            if !req.chunk.is_empty() {
+                self.refuse_changes_if_read_only(user)?;
+            }
             user.with_sync_state(skey, |col, state| server_apply_graves(req, col, state))
                 .and_then(SyncResponse::try_from_obj)
         })
@@ -100,6 +110,9 @@ impl SyncProtocol for Arc<SimpleServer> {
         self.with_authenticated_user(req, |user, req| {
             let skey = req.skey()?;
             let req = req.json()?;
+            if !req.changes.is_empty() {
+                self.refuse_changes_if_read_only(user)?;
+            }
             user.with_sync_state(skey, |col, state| server_apply_changes(req, col, state))
                 .and_then(SyncResponse::try_from_obj)
         })
@@ -123,6 +136,9 @@ impl SyncProtocol for Arc<SimpleServer> {
         self.with_authenticated_user(req, |user, req| {
             let skey = req.skey()?;
             let req = req.json()?;
+            if !req.chunk.is_empty() {
+                self.refuse_changes_if_read_only(user)?;
+            }
             user.with_sync_state(skey, |col, state| server_apply_chunk(req, col, state))
                 .and_then(SyncResponse::try_from_obj)
         })
@@ -170,6 +186,7 @@ impl SyncProtocol for Arc<SimpleServer> {
 
     async fn upload(&self, req: SyncRequest<Vec<u8>>) -> HttpResult<SyncResponse<UploadResponse>> {
         self.with_authenticated_user(req, |user, req| {
+            self.refuse_changes_if_read_only(user)?;
             user.abort_stateful_sync_if_active();
             user.ensure_col_open()?;
             handle_received_upload(&mut user.col, req.data).map(SyncResponse::from_upload_response)
@@ -226,6 +243,7 @@ impl MediaSyncProtocol for Arc<SimpleServer> {
         req: SyncRequest<Vec<u8>>,
     ) -> HttpResult<SyncResponse<JsonResult<MediaUploadResponse>>> {
         self.with_authenticated_user(req, |user, req| {
+            self.refuse_changes_if_read_only(user)?;
             SyncResponse::try_from_obj(JsonResult::ok(
                 user.media.process_uploaded_changes(req.data)?,
             ))
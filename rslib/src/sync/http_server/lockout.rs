@@ -0,0 +1,435 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use anki_io::atomic_rename;
+use anki_io::new_tempfile_in_parent_of;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::default_login_lockout_max_secs;
+use super::default_login_lockout_threshold;
+use crate::prelude::*;
+
+/// The length of the first lockout. Each further failed login doubles it, up
+/// to the server's maximum.
+const FIRST_LOCKOUT_SECS: u64 = 15;
+/// Failures are forgotten once there have been none for this long.
+const FAILURE_MEMORY_SECS: i64 = 24 * 60 * 60;
+/// The most users and IPs tracked at once. When full, the one that failed
+/// longest ago is forgotten to make room.
+const MAX_TRACKED_KEYS: usize = 10_000;
+/// Failures are written to the file at most this often, so a flood of failed
+/// logins doesn't become a flood of writes. Successful logins and clears are
+/// written straight away.
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Something failed logins are counted against. A user is only counted
+/// against from a particular IP, so failed logins from elsewhere can't lock
+/// them out.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum LoginKey {
+    #[serde(rename_all = "camelCase")]
+    UserAtIp {
+        user: String,
+        ip: IpAddr,
+    },
+    Ip {
+        ip: IpAddr,
+    },
+}
+
+impl LoginKey {
+    fn ip(&self) -> IpAddr {
+        match self {
+            LoginKey::UserAtIp { ip, .. } | LoginKey::Ip { ip } => *ip,
+        }
+    }
+
+    fn user(&self) -> Option<&str> {
+        match self {
+            LoginKey::UserAtIp { user, .. } => Some(user),
+            LoginKey::Ip { .. } => None,
+        }
+    }
+
+    /// True if the key is for `user` (if given) and `ip` (if given). Keys
+    /// that aren't for a user only match when no user is given.
+    pub fn matches(&self, user: Option<&str>, ip: Option<IpAddr>) -> bool {
+        let user_matches = match user {
+            Some(user) => self.user() == Some(user),
+            None => true,
+        };
+        user_matches && ip.map_or(true, |ip| self.ip() == ip)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginFailures {
+    pub key: LoginKey,
+    /// Failed logins since the last successful one.
+    pub failures: u32,
+    pub last_failure: TimestampSecs,
+    /// Logins are refused until this time. Zero if the key was never locked
+    /// out.
+    pub locked_until: TimestampSecs,
+}
+
+/// Tracks failed logins by username and IP together, and by IP alone, and
+/// locks either out for a while once it has failed too often. Lockouts grow
+/// exponentially but are capped, so a client that retries with the correct
+/// password always gets in eventually, and a successful login clears the
+/// count for both its user and IP.
+#[derive(Debug)]
+pub struct LoginThrottle {
+    threshold: u32,
+    max_lockout_secs: u64,
+    max_tracked_keys: usize,
+    /// Where failures are saved, so restarting the server doesn't reset
+    /// them.
+    file: Option<PathBuf>,
+    failures: Mutex<TrackedFailures>,
+}
+
+#[derive(Debug, Default)]
+struct TrackedFailures {
+    by_key: HashMap<LoginKey, LoginFailures>,
+    saved_at: Option<Instant>,
+    /// Changes have been made since the file was last written.
+    unsaved: bool,
+}
+
+impl Default for LoginThrottle {
+    fn default() -> Self {
+        Self::new(
+            default_login_lockout_threshold(),
+            default_login_lockout_max_secs(),
+            None,
+        )
+    }
+}
+
+impl LoginThrottle {
+    /// Failures are loaded from `file` if it exists. An unreadable file is
+    /// logged and ignored.
+    pub fn new(threshold: u32, max_lockout_secs: u64, file: Option<PathBuf>) -> Self {
+        let failures = file
+            .as_ref()
+            .filter(|path| path.exists())
+            .and_then(|path| match load_failures(path) {
+                Ok(failures) => Some(failures),
+                Err(err) => {
+                    tracing::warn!(?err, path = %path.display(), "ignoring login failures file");
+                    None
+                }
+            })
+            .unwrap_or_default()
+            .into_iter()
+            .map(|failures| (failures.key.clone(), failures))
+            .collect();
+        Self {
+            threshold,
+            max_lockout_secs,
+            max_tracked_keys: MAX_TRACKED_KEYS,
+            file,
+            failures: Mutex::new(TrackedFailures {
+                by_key: failures,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Return how long until `user` may try to log in from `ip`, if either
+    /// is locked out.
+    pub fn check(&self, user: &str, ip: IpAddr) -> Result<(), Duration> {
+        self.check_at(user, ip, TimestampSecs::now())
+    }
+
+    fn check_at(&self, user: &str, ip: IpAddr, now: TimestampSecs) -> Result<(), Duration> {
+        let failures = self.failures.lock().unwrap();
+        let wait_secs = login_keys(user, ip)
+            .iter()
+            .filter_map(|key| failures.by_key.get(key))
+            .map(|failures| failures.locked_until.elapsed_secs_since(now))
+            .max()
+            .unwrap_or_default();
+        if wait_secs > 0 {
+            Err(Duration::from_secs(wait_secs as u64))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn record_failure(&self, user: &str, ip: IpAddr) {
+        self.record_failure_at(user, ip, TimestampSecs::now())
+    }
+
+    fn record_failure_at(&self, user: &str, ip: IpAddr, now: TimestampSecs) {
+        let mut failures = self.failures.lock().unwrap();
+        failures.by_key.retain(|_, failures| {
+            now.elapsed_secs_since(failures.last_failure) < FAILURE_MEMORY_SECS
+        });
+        for key in login_keys(user, ip) {
+            if !failures.by_key.contains_key(&key) && failures.by_key.len() >= self.max_tracked_keys
+            {
+                forget_oldest(&mut failures.by_key);
+            }
+            let entry = failures.by_key.entry(key.clone()).or_insert(LoginFailures {
+                key,
+                failures: 0,
+                last_failure: now,
+                locked_until: TimestampSecs::zero(),
+            });
+            entry.failures += 1;
+            entry.last_failure = now;
+            if let Some(lockout_secs) = self.lockout_secs(entry.failures) {
+                entry.locked_until = now.adding_secs(lockout_secs as i64);
+                tracing::warn!(
+                    key = ?entry.key,
+                    failures = entry.failures,
+                    lockout_secs,
+                    "locked out after failed logins"
+                );
+            }
+        }
+        failures.unsaved = true;
+        self.save_if_due(&mut failures);
+    }
+
+    pub fn record_success(&self, user: &str, ip: IpAddr) {
+        let mut failures = self.failures.lock().unwrap();
+        let mut changed = false;
+        for key in login_keys(user, ip) {
+            changed |= failures.by_key.remove(&key).is_some();
+        }
+        if changed {
+            self.save(&mut failures);
+        }
+    }
+
+    /// Everything with failed logins, most recent failure first.
+    pub fn failures(&self) -> Vec<LoginFailures> {
+        let mut failures: Vec<_> = self
+            .failures
+            .lock()
+            .unwrap()
+            .by_key
+            .values()
+            .cloned()
+            .collect();
+        failures.sort_by(|a, b| {
+            b.last_failure
+                .cmp(&a.last_failure)
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        failures
+    }
+
+    /// Forget the failures of the keys that [LoginKey::matches] `user` and
+    /// `ip`, or of everything if neither is given. Returns the number of keys
+    /// cleared.
+    pub fn clear(&self, user: Option<&str>, ip: Option<IpAddr>) -> usize {
+        let mut failures = self.failures.lock().unwrap();
+        let before = failures.by_key.len();
+        failures.by_key.retain(|key, _| !key.matches(user, ip));
+        let cleared = before - failures.by_key.len();
+        if cleared > 0 {
+            tracing::info!(?user, ?ip, cleared, "cleared login lockouts");
+            self.save(&mut failures);
+        }
+        cleared
+    }
+
+    /// The length of the lockout that starts after the `failures`th failed
+    /// login, if any.
+    fn lockout_secs(&self, failures: u32) -> Option<u64> {
+        let doublings = failures.checked_sub(self.threshold)?;
+        Some(
+            FIRST_LOCKOUT_SECS
+                .saturating_mul(1u64.checked_shl(doublings).unwrap_or(u64::MAX))
+                .min(self.max_lockout_secs),
+        )
+    }
+
+    fn save_if_due(&self, failures: &mut TrackedFailures) {
+        if failures
+            .saved_at
+            .map_or(true, |saved_at| saved_at.elapsed() >= SAVE_INTERVAL)
+        {
+            self.save(failures);
+        }
+    }
+
+    fn save(&self, failures: &mut TrackedFailures) {
+        save_or_warn(self.file.as_deref(), &failures.by_key);
+        failures.saved_at = Some(Instant::now());
+        failures.unsaved = false;
+    }
+}
+
+impl Drop for LoginThrottle {
+    /// Write any failures that are still waiting for [SAVE_INTERVAL].
+    fn drop(&mut self) {
+        let failures = self
+            .failures
+            .get_mut()
+            .unwrap_or_else(|err| err.into_inner());
+        if failures.unsaved {
+            save_or_warn(self.file.as_deref(), &failures.by_key);
+        }
+    }
+}
+
+fn login_keys(user: &str, ip: IpAddr) -> [LoginKey; 2] {
+    [
+        LoginKey::UserAtIp {
+            user: user.into(),
+            ip,
+        },
+        LoginKey::Ip { ip },
+    ]
+}
+
+fn forget_oldest(failures: &mut HashMap<LoginKey, LoginFailures>) {
+    if let Some(oldest) = failures
+        .values()
+        .min_by_key(|failures| failures.last_failure)
+        .map(|failures| failures.key.clone())
+    {
+        failures.remove(&oldest);
+    }
+}
+
+fn save_or_warn(path: Option<&Path>, failures: &HashMap<LoginKey, LoginFailures>) {
+    let Some(path) = path else {
+        return;
+    };
+    if let Err(err) = save_failures(path, failures.values().collect()) {
+        tracing::warn!(?err, path = %path.display(), "unable to save login failures");
+    }
+}
+
+fn load_failures(path: &Path) -> Result<Vec<LoginFailures>> {
+    Ok(serde_json::from_slice(&anki_io::read_file(path)?)?)
+}
+
+fn save_failures(path: &Path, failures: Vec<&LoginFailures>) -> Result<()> {
+    let mut file = new_tempfile_in_parent_of(path)?;
+    file.write_all(&serde_json::to_vec(&failures)?)?;
+    atomic_rename(file, path, false)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
+    const OTHER_IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED);
+
+    #[test]
+    fn lockouts_back_off_and_reset() {
+        let throttle = LoginThrottle::new(3, 60, None);
+        let now = TimestampSecs(1_000_000);
+        for _ in 0..2 {
+            throttle.record_failure_at("alice", IP, now);
+        }
+        assert_eq!(throttle.check_at("alice", IP, now), Ok(()));
+        throttle.record_failure_at("alice", IP, now);
+        assert_eq!(
+            throttle.check_at("alice", IP, now),
+            Err(Duration::from_secs(FIRST_LOCKOUT_SECS))
+        );
+        // the IP is locked out for other users, but the user can still log
+        // in from elsewhere
+        assert!(throttle.check_at("bob", IP, now).is_err());
+        assert_eq!(throttle.check_at("alice", OTHER_IP, now), Ok(()));
+        assert_eq!(throttle.check_at("bob", OTHER_IP, now), Ok(()));
+
+        // further failures double the lockout, up to the maximum
+        throttle.record_failure_at("alice", IP, now);
+        assert_eq!(
+            throttle.check_at("alice", IP, now),
+            Err(Duration::from_secs(FIRST_LOCKOUT_SECS * 2))
+        );
+        for _ in 0..100 {
+            throttle.record_failure_at("alice", IP, now);
+        }
+        assert_eq!(
+            throttle.check_at("alice", IP, now),
+            Err(Duration::from_secs(60))
+        );
+        assert_eq!(throttle.check_at("alice", IP, now.adding_secs(60)), Ok(()));
+
+        // a successful login starts the count again
+        throttle.record_success("alice", IP);
+        assert!(throttle.failures().is_empty());
+        throttle.record_failure_at("alice", IP, now);
+        assert_eq!(throttle.check_at("alice", IP, now), Ok(()));
+
+        // old failures are forgotten
+        throttle.record_failure_at("bob", OTHER_IP, now.adding_secs(FAILURE_MEMORY_SECS));
+        let keys: Vec<_> = throttle.failures().into_iter().map(|f| f.key).collect();
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(&LoginKey::UserAtIp {
+            user: "bob".into(),
+            ip: OTHER_IP
+        }));
+        assert_eq!(throttle.clear(Some("bob"), None), 1);
+        assert_eq!(throttle.clear(None, Some(OTHER_IP)), 1);
+        assert!(throttle.failures().is_empty());
+    }
+
+    #[test]
+    fn tracked_keys_are_capped() {
+        let mut throttle = LoginThrottle::new(3, 60, None);
+        throttle.max_tracked_keys = 10;
+        let now = TimestampSecs(1_000_000);
+        for idx in 0..10u32 {
+            let ip = IpAddr::V4(idx.into());
+            throttle.record_failure_at("alice", ip, now.adding_secs(idx as i64));
+        }
+        // each failure tracks the user at the IP, and the IP, so the oldest
+        // five IPs made way for the newest
+        let failures = throttle.failures();
+        assert_eq!(failures.len(), 10);
+        assert_eq!(failures.last().unwrap().last_failure, now.adding_secs(5));
+    }
+
+    #[test]
+    fn failures_are_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lockouts.json");
+        let throttle = LoginThrottle::new(1, 60, Some(path.clone()));
+        throttle.record_failure("alice", IP);
+        assert!(throttle.check("alice", IP).is_err());
+
+        let restarted = LoginThrottle::new(1, 60, Some(path.clone()));
+        assert_eq!(restarted.failures(), throttle.failures());
+        assert!(restarted.check("alice", IP).is_err());
+        restarted.record_success("alice", IP);
+        assert!(LoginThrottle::new(1, 60, Some(path.clone()))
+            .failures()
+            .is_empty());
+
+        // further failures are written at most once per interval, and when
+        // the throttle is dropped
+        restarted.record_failure("bob", IP);
+        restarted.record_failure("carol", IP);
+        assert!(LoginThrottle::new(1, 60, Some(path.clone()))
+            .failures()
+            .is_empty());
+        drop(restarted);
+        assert_eq!(LoginThrottle::new(1, 60, Some(path)).failures().len(), 3);
+    }
+}
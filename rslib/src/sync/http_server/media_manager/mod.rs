@@ -8,13 +8,28 @@ use std::path::Path;
 use std::path::PathBuf;
 
 use anki_io::create_dir_all;
+use tracing::warn;
 
+use crate::media::files::remove_files;
 use crate::prelude::*;
 use crate::sync::error::HttpResult;
 use crate::sync::error::OrHttpErr;
 use crate::sync::media::changes::MediaChange;
+use crate::sync::media::database::server::entry::upload::UploadedChangeResult;
 use crate::sync::media::database::server::ServerMediaDatabase;
 use crate::sync::media::sanity::MediaSanityCheckResponse;
+use crate::sync::media::zip::UploadedChange;
+use crate::sync::media::zip::UploadedChangeKind;
+use crate::text::normalize_to_nfc;
+
+/// The outcome of [ServerMediaManager::trash_files].
+#[derive(Default)]
+pub struct TrashedMedia {
+    /// The files moved to the trash.
+    pub trashed: Vec<String>,
+    /// The files that couldn't be moved, which are left in place.
+    pub failed: Vec<String>,
+}
 
 pub struct ServerMediaManager {
     pub media_folder: PathBuf,
@@ -42,6 +57,47 @@ impl ServerMediaManager {
             .or_internal_err("changes chunk")
     }
 
+    /// Move files to the media trash, recording their removal so that
+    /// clients delete them on their next sync. Files that aren't present are
+    /// skipped. This is called after the notes using the files are gone, so
+    /// a file that can't be moved is logged and reported instead of failing
+    /// the rest.
+    pub fn trash_files(&mut self, filenames: &[String]) -> TrashedMedia {
+        let mut outcome = TrashedMedia::default();
+        for filename in filenames {
+            match self.trash_file(filename) {
+                Ok(Some(filename)) => outcome.trashed.push(filename),
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(filename, %err, "couldn't trash media");
+                    outcome.failed.push(filename.clone());
+                }
+            }
+        }
+        outcome
+    }
+
+    /// Each file gets its own transaction, so that one that can't be moved
+    /// isn't recorded as removed.
+    fn trash_file(&mut self, filename: &str) -> Result<Option<String>> {
+        let folder = &self.media_folder;
+        let mut trashed = None;
+        self.db.with_transaction(|db, meta| {
+            let change = UploadedChange {
+                nfc_filename: normalize_to_nfc(filename).into(),
+                kind: UploadedChangeKind::Delete,
+            };
+            if let UploadedChangeResult::Removed { filename, .. } =
+                db.register_uploaded_change(meta, change)?
+            {
+                remove_files(folder, &[&filename])?;
+                trashed = Some(filename);
+            }
+            Ok(())
+        })?;
+        Ok(trashed)
+    }
+
     pub fn sanity_check(&self, client_file_count: u32) -> HttpResult<MediaSanityCheckResponse> {
         let server = self
             .db
@@ -19,6 +19,7 @@ use crate::sync::http_server::media_manager::ServerMediaManager;
 use crate::sync::media::database::server::entry::upload::UploadedChangeResult;
 use crate::sync::media::upload::MediaUploadResponse;
 use crate::sync::media::zip::unzip_and_validate_files;
+use crate::sync::media::zip::UploadedChange;
 
 impl ServerMediaManager {
     pub fn process_uploaded_changes(
@@ -26,12 +27,21 @@ impl ServerMediaManager {
         zip_data: Vec<u8>,
     ) -> HttpResult<MediaUploadResponse> {
         let extracted = unzip_and_validate_files(&zip_data).or_bad_request("unzip files")?;
+        self.apply_changes(extracted)
+    }
+
+    /// Add, replace or remove files as if a client had uploaded the changes,
+    /// so that they are sent to other clients on their next sync.
+    pub fn apply_changes(
+        &mut self,
+        changes: Vec<UploadedChange>,
+    ) -> HttpResult<MediaUploadResponse> {
         let folder = &self.media_folder;
         let mut processed = 0;
         let new_usn = self
             .db
             .with_transaction(|db, meta| {
-                for change in extracted {
+                for change in changes {
                     match db.register_uploaded_change(meta, change)? {
                         UploadedChangeResult::FileAlreadyDeleted { filename } => {
                             info!(filename, "already deleted");
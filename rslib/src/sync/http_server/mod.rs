@@ -1,21 +1,26 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+mod backups;
+pub mod config;
 pub mod error;
 mod handlers;
+pub mod lockout;
 mod logging;
 mod media_manager;
+pub mod read_only;
 pub mod rest;
-mod routes;
 pub mod rest_routes;
+mod routes;
 pub mod user;
+#[cfg(feature = "web-ui")]
+mod web_ui;
 
 use std::collections::HashMap;
 use std::future::Future;
 use std::future::IntoFuture;
 use std::net::IpAddr;
 use std::net::SocketAddr;
-use std::path::Path;
 use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -23,6 +28,7 @@ use std::sync::Mutex;
 
 use anki_io::create_dir_all;
 use axum::extract::DefaultBodyLimit;
+use axum::http::StatusCode;
 use axum::routing::get;
 use axum::Router;
 use axum_client_ip::ClientIpSource;
@@ -31,21 +37,27 @@ use pbkdf2::password_hash::PasswordHasher;
 use pbkdf2::password_hash::PasswordVerifier;
 use pbkdf2::password_hash::SaltString;
 use pbkdf2::Pbkdf2;
-use snafu::whatever;
-use snafu::OptionExt;
 use snafu::ResultExt;
 use snafu::Whatever;
 use tokio::net::TcpListener;
+use tokio::sync::RwLock;
 use tracing::Span;
 
-
-use crate::prelude::*;
 use crate::media::files::sha1_of_data;
+use crate::prelude::*;
+use crate::sync::error::HttpError;
 use crate::sync::error::HttpResult;
 use crate::sync::error::OrHttpErr;
+use crate::sync::error::RetryAfter;
+use crate::sync::http_server::config::PublicConfig;
+use crate::sync::http_server::config::UserCredentials;
+use crate::sync::http_server::lockout::LoginThrottle;
 use crate::sync::http_server::logging::with_logging_layer;
 use crate::sync::http_server::media_manager::ServerMediaManager;
+use crate::sync::http_server::read_only::ReadOnly;
 use crate::sync::http_server::rest::rest_router;
+use crate::sync::http_server::rest::RateLimiter;
+use crate::sync::http_server::rest_routes::undo_group::with_undo_groups;
 use crate::sync::http_server::routes::collection_sync_router;
 use crate::sync::http_server::routes::health_check_handler;
 use crate::sync::http_server::routes::media_sync_router;
@@ -62,6 +74,29 @@ pub type ApiResult<T> = Result<T, ApiError>;
 
 pub struct SimpleServer {
     pub state: Mutex<SimpleServerInner>,
+    /// REST deletes of more cards than this must be explicitly confirmed.
+    pub delete_confirm_threshold: usize,
+    /// Shared by the REST endpoints that are expensive to run.
+    pub rate_limiter: RateLimiter,
+    /// Reported by the health endpoint.
+    pub public_config: PublicConfig,
+    /// Locks out users and IPs with too many failed logins.
+    pub login_throttle: LoginThrottle,
+    pub read_only: ReadOnly,
+    /// Whether packages may be imported from URLs on the server's own
+    /// network.
+    pub import_allow_private_hosts: bool,
+    /// Required by REST endpoints that administer the server, as a bearer
+    /// token. If not set, those endpoints are refused.
+    pub admin_token: Option<String>,
+    /// Where REST clients can copy decks into new collections, in a folder
+    /// per user. If not set, copying is refused.
+    pub copy_base: Option<PathBuf>,
+    /// Held exclusively by REST requests in an undo group, and shared by
+    /// other REST requests, so nothing else changes the collection while a
+    /// grouped request runs. The REST API only serves one user, so this
+    /// amounts to a per-user guard.
+    pub undo_group_gate: RwLock<()>,
 }
 
 pub struct SimpleServerInner {
@@ -69,16 +104,43 @@ pub struct SimpleServerInner {
     pub users: HashMap<String, User>,
 }
 
-#[derive(serde::Deserialize, Debug)]
+/// The server's settings. See [SyncServerConfig::load] for where they are
+/// read from.
+#[derive(Debug)]
 pub struct SyncServerConfig {
-    #[serde(default = "default_host")]
     pub host: IpAddr,
-    #[serde(default = "default_port")]
     pub port: u16,
-    #[serde(default = "default_base", rename = "base")]
     pub base_folder: PathBuf,
-    #[serde(default = "default_ip_header")]
     pub ip_header: ClientIpSource,
+    pub delete_confirm_threshold: usize,
+    /// How many expensive REST requests can be made in a burst.
+    pub rate_limit_capacity: u32,
+    /// How quickly further expensive REST requests are allowed.
+    pub rate_limit_refill_rate_per_sec: f64,
+    pub users: Vec<UserCredentials>,
+    /// Whether user passwords are PHC strings instead of plain text.
+    pub passwords_hashed: bool,
+    /// How many failed logins a user or IP may make before being locked
+    /// out.
+    pub login_lockout_threshold: u32,
+    /// The longest a lockout can last.
+    pub login_lockout_max_secs: u64,
+    /// Where to keep failed logins across restarts. If not set, they are
+    /// only kept in memory.
+    pub login_lockout_file: Option<PathBuf>,
+    /// Start with the whole server refusing changes.
+    pub read_only: bool,
+    /// Allow packages to be imported from URLs that point to loopback,
+    /// private or link-local addresses. Off by default, so that REST clients
+    /// can't use the server to reach other services on its network.
+    pub import_allow_private_hosts: bool,
+    /// The bearer token for REST endpoints that administer the server, such
+    /// as clearing lockouts. If not set, those endpoints are refused.
+    pub admin_token: Option<String>,
+    /// Where REST clients can copy decks into new collections, in a folder
+    /// per user. Must be outside [SyncServerConfig::base_folder], so that
+    /// copies can't overwrite anyone's collection or media.
+    pub copy_base: Option<PathBuf>,
 }
 
 fn default_host() -> IpAddr {
@@ -99,59 +161,65 @@ pub fn default_ip_header() -> ClientIpSource {
     ClientIpSource::ConnectInfo
 }
 
+pub fn default_delete_confirm_threshold() -> usize {
+    500
+}
+
+pub fn default_rate_limit_capacity() -> u32 {
+    2
+}
+
+/// One request a minute.
+pub fn default_rate_limit_refill_rate_per_sec() -> f64 {
+    1.0 / 60.0
+}
 
+pub fn default_login_lockout_threshold() -> u32 {
+    5
+}
+
+/// Fifteen minutes.
+pub fn default_login_lockout_max_secs() -> u64 {
+    15 * 60
+}
 
 impl SimpleServerInner {
-    fn new_from_env(base_folder: &Path) -> Result<Self, Whatever> {
-        let mut idx = 1;
+    fn new(config: &SyncServerConfig) -> Result<Self, Whatever> {
         let mut users: HashMap<String, User> = Default::default();
-        loop {
-            let envvar = format!("SYNC_USER{idx}");
-            match std::env::var(&envvar) {
-                Ok(val) => {
-                    let hkey = derive_hkey(&val);
-                    let (name, pwhash) = {
-                        let (name, password) = val.split_once(':').with_whatever_context(|| {
-                            format!("{envvar} should be in 'username:password' format.")
-                        })?;
-                        if std::env::var("PASSWORDS_HASHED").is_ok() {
-                            (name, password.to_string())
-                        } else {
-                            (
-                                name,
-                                // Plain text passwords provided; hash them with a fixed salt.
-                                Pbkdf2
-                                    .hash_password(
-                                        password.as_bytes(),
-                                        &SaltString::from_b64("tonuvYGpksNFQBlEmm3lxg").unwrap(),
-                                    )
-                                    .expect("couldn't hash password")
-                                    .to_string(),
-                            )
-                        }
-                    };
-                    let folder = base_folder.join(name);
-                    create_dir_all(&folder).whatever_context("creating SYNC_BASE")?;
-                    let media =
-                        ServerMediaManager::new(&folder).whatever_context("opening media")?;
-                    users.insert(
-                        hkey,
-                        User {
-                            name: name.into(),
-                            password_hash: pwhash,
-                            col: None,
-                            sync_state: None,
-                            media,
-                            folder,
-                        },
-                    );
-                    idx += 1;
-                }
-                Err(_) => break,
-            }
-        }
-        if users.is_empty() {
-            whatever!("No users defined; SYNC_USER1 env var should be set.");
+        for UserCredentials { name, password } in &config.users {
+            let hkey = derive_hkey(&format!("{name}:{password}"));
+            let pwhash = if config.passwords_hashed {
+                password.clone()
+            } else {
+                // Plain text passwords provided; hash them with a fixed salt.
+                Pbkdf2
+                    .hash_password(
+                        password.as_bytes(),
+                        &SaltString::from_b64("tonuvYGpksNFQBlEmm3lxg").unwrap(),
+                    )
+                    .expect("couldn't hash password")
+                    .to_string()
+            };
+            let folder = config.base_folder.join(name);
+            create_dir_all(&folder).whatever_context("creating SYNC_BASE")?;
+            let media = ServerMediaManager::new(&folder).whatever_context("opening media")?;
+            users.insert(
+                hkey,
+                User {
+                    name: name.clone(),
+                    password_hash: pwhash,
+                    col: None,
+                    sync_state: None,
+                    media,
+                    folder,
+                    study_sessions: Default::default(),
+                    export_progress: Default::default(),
+                    export_running: Default::default(),
+                    import_logs: Default::default(),
+                    last_backup: None,
+                    undo_group: None,
+                },
+            );
         }
         Ok(Self { users })
     }
@@ -182,10 +250,32 @@ impl SimpleServer {
         op(user, req)
     }
 
+    /// Fail sync requests that would change `user`'s collection or media
+    /// while they or the server are read-only. Downloads are still allowed,
+    /// as are normal syncs in which the client sends no changes; the server
+    /// then only records the sync itself.
+    pub(in crate::sync) fn refuse_changes_if_read_only(&self, user: &User) -> HttpResult<()> {
+        if self.read_only.applies_to(&user.name) {
+            return Err(HttpError::new_without_source(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "the server is read-only",
+            ));
+        }
+        Ok(())
+    }
+
     pub(in crate::sync) fn get_host_key(
         &self,
+        ip: IpAddr,
         request: HostKeyRequest,
     ) -> HttpResult<SyncResponse<HostKeyResponse>> {
+        if let Err(retry_after) = self.login_throttle.check(&request.username, ip) {
+            return Err(HttpError {
+                code: StatusCode::TOO_MANY_REQUESTS,
+                context: "too many failed logins".into(),
+                source: Some(Box::new(RetryAfter(retry_after))),
+            });
+        }
         let state = self.state.lock().unwrap();
 
         // This control structure might seem a bit crude,
@@ -215,8 +305,10 @@ impl SimpleServer {
                     .verify_password(request.password.as_bytes(), pwhash)
                     .is_ok()
                 {
+                    self.login_throttle.record_success(&request.username, ip);
                     SyncResponse::try_from_obj(HostKeyResponse { key })
                 } else {
+                    self.login_throttle.record_failure(&request.username, ip);
                     None.or_forbidden("invalid user/pass in get_host_key")
                 }
             }
@@ -226,40 +318,64 @@ impl SimpleServer {
                 let pwhash =
                     &PasswordHash::new(&user.password_hash).expect("couldn't parse password hash");
                 let _ = Pbkdf2.verify_password(request.password.as_bytes(), pwhash);
+                self.login_throttle.record_failure(&request.username, ip);
                 None.or_forbidden("invalid user/pass in get_host_key")
             }
         }
     }
     pub fn is_running() -> bool {
-        let config = envy::prefixed("SYNC_")
-            .from_env::<SyncServerConfig>()
-            .unwrap();
+        let Ok(config) = SyncServerConfig::load() else {
+            return false;
+        };
         std::net::TcpStream::connect(format!("{}:{}", config.host, config.port)).is_ok()
     }
-    pub fn new(base_folder: &Path) -> Result<Self, Whatever> {
-        let inner = SimpleServerInner::new_from_env(base_folder)?;
+    pub fn new(config: &SyncServerConfig) -> Result<Self, Whatever> {
+        config.validate().whatever_context("invalid config")?;
+        let inner = SimpleServerInner::new(config)?;
         Ok(SimpleServer {
             state: Mutex::new(inner),
+            delete_confirm_threshold: config.delete_confirm_threshold,
+            rate_limiter: RateLimiter::new(
+                config.rate_limit_capacity,
+                config.rate_limit_refill_rate_per_sec,
+            ),
+            public_config: config.public_config(),
+            login_throttle: LoginThrottle::new(
+                config.login_lockout_threshold,
+                config.login_lockout_max_secs,
+                config.login_lockout_file.clone(),
+            ),
+            read_only: ReadOnly::new(config.read_only),
+            import_allow_private_hosts: config.import_allow_private_hosts,
+            admin_token: config.admin_token.clone(),
+            copy_base: config.copy_base.clone(),
+            undo_group_gate: Default::default(),
         })
     }
 
     pub async fn make_server(
         config: SyncServerConfig,
     ) -> Result<(SocketAddr, ServerFuture), Whatever> {
-        let server = Arc::new(
-            SimpleServer::new(&config.base_folder).whatever_context("unable to create server")?,
-        );
+        let server = SimpleServer::new(&config).whatever_context("unable to create server")?;
+        let server = Arc::new(server);
         let address = &format!("{}:{}", config.host, config.port);
         let listener = TcpListener::bind(address)
             .await
             .with_whatever_context(|_| format!("couldn't bind to {address}"))?;
         let addr = listener.local_addr().unwrap();
+        tokio::spawn(backups::run_backup_task(server.clone()));
+        let router = Router::new()
+            .nest("/sync", collection_sync_router())
+            .nest("/msync", media_sync_router())
+            .nest(
+                "/api/v1",
+                with_undo_groups(rest_router(server.clone()), server.clone()),
+            )
+            .route("/health", get(health_check_handler));
+        #[cfg(feature = "web-ui")]
+        let router = router.nest("/ui", web_ui::routes());
         let server = with_logging_layer(
-            Router::new()
-                .nest("/sync", collection_sync_router())
-                .nest("/msync", media_sync_router())
-                .nest("/api/v1", rest_router())
-                .route("/health", get(health_check_handler))
+            router
                 .with_state(server)
                 .layer(DefaultBodyLimit::max(*MAXIMUM_SYNC_PAYLOAD_BYTES))
                 .layer(config.ip_header.into_extension()),
@@ -279,13 +395,11 @@ impl SimpleServer {
     #[snafu::report]
     #[tokio::main]
     pub async fn run() -> Result<(), Whatever> {
-        let config = envy::prefixed("SYNC_")
-            .from_env::<SyncServerConfig>()
-            .whatever_context("reading SYNC_* env vars")?;
+        let config = SyncServerConfig::load().whatever_context("reading config")?;
         let (_addr, server_fut) = SimpleServer::make_server(config).await?;
         server_fut.await.whatever_context("await server")?;
         Ok(())
     }
 }
 
-pub type ServerFuture = Pin<Box<dyn Future<Output = Result<(), std::io::Error>> + Send>>;
\ No newline at end of file
+pub type ServerFuture = Pin<Box<dyn Future<Output = Result<(), std::io::Error>> + Send>>;
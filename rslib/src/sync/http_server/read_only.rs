@@ -0,0 +1,90 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::collections::BTreeSet;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+/// Whether the whole server, or some of its users, refuse changes while
+/// still answering reads, eg during a migration. It can be toggled while the
+/// server is running, and is kept outside the server's state so it can be
+/// checked without waiting for a collection operation to finish.
+#[derive(Debug, Default)]
+pub struct ReadOnly {
+    server: AtomicBool,
+    /// Users who are read-only even when the server isn't.
+    users: Mutex<BTreeSet<String>>,
+}
+
+impl ReadOnly {
+    pub fn new(server: bool) -> Self {
+        Self {
+            server: AtomicBool::new(server),
+            users: Default::default(),
+        }
+    }
+
+    pub fn server(&self) -> bool {
+        self.server.load(Ordering::Relaxed)
+    }
+
+    pub fn set_server(&self, read_only: bool) {
+        self.server.store(read_only, Ordering::Relaxed);
+    }
+
+    /// The users that are read-only in their own right, in name order.
+    pub fn users(&self) -> Vec<String> {
+        self.lock_users().iter().cloned().collect()
+    }
+
+    pub fn set_user(&self, user: &str, read_only: bool) {
+        let mut users = self.lock_users();
+        if read_only {
+            users.insert(user.to_string());
+        } else {
+            users.remove(user);
+        }
+    }
+
+    /// True if `user` may not make changes, because either they or the
+    /// whole server are read-only.
+    pub fn applies_to(&self, user: &str) -> bool {
+        self.server() || self.lock_users().contains(user)
+    }
+
+    /// True if anyone at all may not make changes.
+    pub fn any(&self) -> bool {
+        self.server() || !self.lock_users().is_empty()
+    }
+
+    fn lock_users(&self) -> std::sync::MutexGuard<'_, BTreeSet<String>> {
+        // the set is always left consistent, so a panic elsewhere can't
+        // have corrupted it
+        self.users.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn server_and_users() {
+        let read_only = ReadOnly::new(false);
+        assert!(!read_only.any());
+        read_only.set_user("alice", true);
+        assert!(read_only.applies_to("alice"));
+        assert!(!read_only.applies_to("bob"));
+        assert!(read_only.any());
+
+        read_only.set_server(true);
+        assert!(read_only.applies_to("bob"));
+        read_only.set_user("alice", false);
+        assert!(read_only.applies_to("alice"));
+        assert_eq!(read_only.users(), Vec::<String>::new());
+
+        read_only.set_server(false);
+        assert!(!read_only.any());
+    }
+}
@@ -2,16 +2,200 @@
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
 
+use axum::extract::Request;
+use axum::extract::State;
+use axum::http::Method;
+use axum::middleware::from_fn_with_state;
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::response::Response;
 use axum::Router;
 
 use super::rest_routes;
+use super::rest_routes::with_user;
+use crate::sync::http_server::ApiError;
 use crate::sync::http_server::SimpleServer;
 
+/// Endpoints that work through the whole collection or its review history,
+/// and so share the server's [RateLimiter]. A `*` segment matches any single
+/// segment, and a segment ending in `*` matches any segment starting with the
+/// rest.
+const RATE_LIMITED_PATHS: &[&str] = &[
+    "/collection/retention-distribution",
+    "/deck-configs/*/preview-change",
+    "/decks/*/copy-to-new",
+    "/export/colpkg",
+    "/fsrs/calibration",
+    "/import/apkg-url",
+    "/stats/history",
+    "/stats/workload",
+];
+
+/// Endpoints that are not GETs but make no changes to a collection, and so
+/// stay available while the server is read-only, matched as for
+/// [RATE_LIMITED_PATHS]. The read-only switch itself is included so it can be
+/// turned off again.
+const READ_ONLY_ALLOWED_PATHS: &[&str] =
+    &["/lockouts", "/read-only", "/search/*", "/utils/normalize"];
+
 /// The main router for the v1 REST API.
 ///
 /// This function simply delegates to the master router in the `rest_routes` module.
 /// This file should not be modified when adding new endpoints.
-pub fn rest_router() -> Router<Arc<SimpleServer>> {
+pub fn rest_router(server: Arc<SimpleServer>) -> Router<Arc<SimpleServer>> {
     rest_routes::routes()
+        .layer(from_fn_with_state(server.clone(), limit_expensive_requests))
+        .layer(from_fn_with_state(server, refuse_changes_when_read_only))
+}
+
+/// A token bucket. Each request takes a token, and tokens are added back at a
+/// steady rate, up to the bucket's capacity.
+#[derive(Debug)]
+pub struct RateLimiter {
+    capacity: f64,
+    refill_rate_per_sec: f64,
+    bucket: Mutex<TokenBucket>,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// A full bucket.
+    pub fn new(capacity: u32, refill_rate_per_sec: f64) -> Self {
+        Self {
+            capacity: capacity as f64,
+            refill_rate_per_sec,
+            bucket: Mutex::new(TokenBucket {
+                tokens: capacity as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take a token, or return how long it will be until one is available.
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|err| err.into_inner());
+        let now = Instant::now();
+        let refilled = (now - bucket.refilled_at).as_secs_f64() * self.refill_rate_per_sec;
+        bucket.tokens = (bucket.tokens + refilled).min(self.capacity);
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if self.capacity < 1.0 {
+            // the bucket can never hold a whole token
+            Err(Duration::MAX)
+        } else {
+            let wait_secs = (1.0 - bucket.tokens) / self.refill_rate_per_sec;
+            Err(Duration::try_from_secs_f64(wait_secs).unwrap_or(Duration::MAX))
+        }
+    }
+}
+
+fn is_rate_limited(path: &str) -> bool {
+    matches_any(RATE_LIMITED_PATHS, path)
+}
+
+fn allowed_when_read_only(method: &Method, path: &str) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+        || matches_any(READ_ONLY_ALLOWED_PATHS, path)
+}
+
+fn matches_any(patterns: &[&str], path: &str) -> bool {
+    patterns.iter().any(|pattern| {
+        let mut segments = path.split('/');
+        pattern.split('/').all(|expected| {
+            segments.next().is_some_and(|segment| match expected {
+                "*" => !segment.is_empty(),
+                _ => match expected.strip_suffix('*') {
+                    Some(prefix) => segment.starts_with(prefix),
+                    None => segment == expected,
+                },
+            })
+        }) && segments.next().is_none()
+    })
+}
+
+/// Reject requests to [RATE_LIMITED_PATHS] while the server's bucket is
+/// empty, telling the client when to retry.
+pub(crate) async fn limit_expensive_requests(
+    State(server): State<Arc<SimpleServer>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if is_rate_limited(request.uri().path()) {
+        if let Err(retry_after) = server.rate_limiter.try_acquire() {
+            return ApiError::RateLimited { retry_after }.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Reject requests that could make changes while the server, or the user the
+/// REST API acts as, is read-only.
+pub(crate) async fn refuse_changes_when_read_only(
+    State(server): State<Arc<SimpleServer>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if server.read_only.any() && !allowed_when_read_only(request.method(), request.uri().path()) {
+        let read_only = server.read_only.server()
+            || match with_user(&server, |user| Ok(server.read_only.applies_to(&user.name))).await {
+                Ok(read_only) => read_only,
+                Err(err) => return err.into_response(),
+            };
+        if read_only {
+            return ApiError::ReadOnly.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rate_limited_paths() {
+        assert!(is_rate_limited("/fsrs/calibration"));
+        assert!(is_rate_limited("/export/colpkg"));
+        assert!(is_rate_limited("/deck-configs/1/preview-change"));
+        assert!(is_rate_limited("/decks/1/copy-to-new"));
+        assert!(!is_rate_limited("/collection"));
+        assert!(!is_rate_limited("/collection/storage"));
+        assert!(!is_rate_limited("/export/colpkg/progress"));
+        assert!(!is_rate_limited("/deck-configs//preview-change"));
+    }
+
+    #[test]
+    fn read_only_paths() {
+        assert!(allowed_when_read_only(&Method::GET, "/cards/1"));
+        assert!(allowed_when_read_only(&Method::POST, "/search/rows"));
+        assert!(allowed_when_read_only(&Method::PUT, "/read-only"));
+        assert!(!allowed_when_read_only(&Method::POST, "/cards"));
+        assert!(!allowed_when_read_only(&Method::DELETE, "/cards"));
+        assert!(!allowed_when_read_only(&Method::POST, "/search"));
+    }
+
+    #[test]
+    fn token_bucket() {
+        let limiter = RateLimiter::new(2, 0.5);
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_ok());
+        let retry_after = limiter.try_acquire().unwrap_err();
+        assert!(retry_after > Duration::from_millis(1900) && retry_after <= Duration::from_secs(2));
+
+        let limiter = RateLimiter::new(1, 0.0);
+        assert!(limiter.try_acquire().is_ok());
+        assert_eq!(limiter.try_acquire(), Err(Duration::MAX));
+        assert_eq!(RateLimiter::new(0, 1.0).try_acquire(), Err(Duration::MAX));
+    }
 }
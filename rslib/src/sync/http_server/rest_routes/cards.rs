@@ -3,96 +3,569 @@
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use axum::{
-    extract::{rejection::JsonRejection, Path, State},
+    extract::{rejection::JsonRejection, Path, Query, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue},
+    response::{IntoResponse, Response},
     routing::{get, post, put},
     Json, Router,
 };
+use data_encoding::BASE64URL_NOPAD;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    card::CardId,
-    collection::Collection,
+    card::{CardId, CardQueue},
+    config::StringKey,
     error::{AnkiError, InvalidInputError},
     notes::Note,
+    ops::ChangedIds,
     prelude::*,
+    revlog::IntervalSnapshot,
+    scheduler::{
+        answering::{FuzzRange, Rating},
+        states::{CardState, FilteredState, NormalState},
+        timing::SchedTimingToday,
+    },
+    search::{SearchNode, SortMode, StateKind},
     sync::http_server::{ApiResult, SimpleServer},
 };
 
+use super::decks::deck_response;
+use super::expand::Expand;
+use super::expand::Expanded;
+use super::notes::NoteResponse;
+use super::notetypes::NotetypeResponse;
+use super::render_warnings;
+use super::rendered_html;
+use super::study::AnswerRating;
+use super::tags::normalize_tags;
+use super::with_col;
+use super::with_col_and_media_folder;
+use super::with_col_confirming_delete;
+use super::ChangedIdsResponse;
+use super::DeleteCountsResponse;
+use super::RenderWarningResponse;
+use super::TrashedMediaResponse;
+
+/// The maximum number of cards returned by one GET /cards request.
+const MAX_LIST_LIMIT: u32 = 1000;
+
 // Payloads for the API
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListCardsQuery {
+    #[serde(default = "default_list_limit")]
+    limit: u32,
+    /// The `nextCursor` of the previous page; omitted for the first page.
+    cursor: Option<String>,
+    /// Only list cards in the review queue with an ease of at least this,
+    /// eg 1.3. Ease-filtered results are sorted by ease and not paginated.
+    min_ease: Option<f32>,
+    /// Only list cards in the review queue with an ease of at most this.
+    max_ease: Option<f32>,
+    /// Limits an ease filter to the deck and its children.
+    deck_id: Option<i64>,
+}
+
+fn default_list_limit() -> u32 {
+    100
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardSummaryResponse {
+    card_id: i64,
+    note_id: i64,
+    deck_id: i64,
+}
+
+impl From<&Card> for CardSummaryResponse {
+    fn from(card: &Card) -> Self {
+        CardSummaryResponse {
+            card_id: card.id.0,
+            note_id: card.note_id.0,
+            deck_id: card.deck_id.0,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListCardsResponse {
+    cards: Vec<CardSummaryResponse>,
+    /// Pass as `cursor` to get the next page. Null once all cards have been
+    /// returned.
+    next_cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DueCardsResponse {
+    cards: Vec<CardSummaryResponse>,
+}
+
+/// Linked from the deprecation header of responses whose meaning depends on
+/// the scheduler version, when the collection is not using v3 yet.
+const V3_UPGRADE_GUIDE: &str = "https://faqs.ankiweb.net/the-2021-scheduler.html";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCardQuery {
+    /// Rewrite media references in the rendered sides into URLs with this
+    /// prefix.
+    media_url_prefix: Option<String>,
+    /// Any of "note", "deck" and "notetype", separated by commas, to include
+    /// those objects in the response.
+    expand: Option<String>,
+    /// Report media the card refers to that is missing from the media folder.
+    #[serde(default)]
+    check_media: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct AddCardRequest {
-    #[serde(rename = "deckName")]
     deck_name: String,
-    #[serde(rename = "notetypeName")]
     notetype_name: String,
     fields: HashMap<String, String>,
     tags: Vec<String>,
+    /// The position given to the new cards, instead of the next one from the
+    /// collection's counter. The counter is advanced past it if necessary.
+    new_position: Option<u32>,
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct AddCardResponse {
     card_ids: Vec<i64>,
+    /// The tags as saved, if they differ from the ones sent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    normalized_tags: Option<Vec<String>>,
+    changes: ChangedIdsResponse,
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CardInfoResponse {
     card_id: i64,
     deck_id: i64,
     due: i32,
     interval: u32,
     ease_factor: f32,
+    /// The card's position in the new queue, which sets the order new cards
+    /// are introduced in. Only meaningful for cards in the new queue, and null
+    /// for cards in any other queue, including suspended and buried new cards.
+    queue_position: Option<u32>,
+    /// True if `deckId` is a filtered deck the card was moved into.
+    in_filtered_deck: bool,
+    filtered_deck_name: Option<String>,
+    /// The deck the card returns to when it leaves the filtered deck. Null
+    /// when the card is in its home deck.
+    original_deck_id: Option<i64>,
+    /// The due the card had before it was moved into the filtered deck, if it
+    /// is in one and the filtered deck changed it.
+    original_due: Option<i32>,
     rendered_front: String,
     rendered_back: String,
+    /// Problems with the rendered card, such as an empty front.
+    warnings: Vec<RenderWarningResponse>,
+    siblings: Vec<SiblingResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SiblingResponse {
+    card_id: i64,
+    ord: u16,
+    queue: i8,
+    due: i32,
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchedulingStatesQuery {
+    /// Include the deck options' custom scheduling code in the response.
+    #[serde(default)]
+    include_custom_scheduling: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchedulingStateResponse {
+    kind: &'static str,
+    interval_secs: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scheduled_days: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ease_factor: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stability: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    difficulty: Option<f32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FuzzRangeResponse {
+    unfuzzed_days: u32,
+    lower_days: u32,
+    upper_days: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FuzzResponse {
+    seed: Option<u64>,
+    factor: Option<f32>,
+    load_balanced: bool,
+    /// For again/hard/good/easy; null if the answer does not lead to a review.
+    ranges: Vec<Option<FuzzRangeResponse>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomSchedulingResponse {
+    /// True if custom scheduling code is set; the desktop runs it to modify
+    /// the states below before they are applied.
+    set: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchedulingStatesResponse {
+    current: SchedulingStateResponse,
+    again: SchedulingStateResponse,
+    hard: SchedulingStateResponse,
+    good: SchedulingStateResponse,
+    easy: SchedulingStateResponse,
+    fuzz: FuzzResponse,
+    custom_scheduling: CustomSchedulingResponse,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct UpdateCardContentRequest {
     fields: HashMap<String, String>,
     tags: Option<Vec<String>>,
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct UpdateScheduleRequest {
     due: String,
+    /// Spread a single day as the scheduler fuzzes review intervals, like
+    /// a trailing "~".
+    #[serde(default)]
+    fuzz: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkScheduleEntry {
+    card_id: i64,
+    due: String,
+    #[serde(default)]
+    fuzz: bool,
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkScheduleRequest {
+    cards: Vec<BulkScheduleEntry>,
+    /// If true, any failing entry aborts the whole request.
+    #[serde(default)]
+    atomic: bool,
+    /// If true, report where the cards would land without changing them.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkScheduleResult {
+    card_id: i64,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    removed_from_filtered_deck: Option<RemovedFromFilteredDeckResponse>,
+    /// Only reported for dry runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    due_in_days: Option<i32>,
+    /// Only reported for dry runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<u32>,
+}
+
+/// Included when rescheduling a card returned it from a filtered deck to its
+/// home deck.
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RemovedFromFilteredDeckResponse {
+    filtered_deck_id: i64,
+    filtered_deck_name: String,
+    home_deck_id: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateScheduleResponse {
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    removed_from_filtered_deck: Option<RemovedFromFilteredDeckResponse>,
+    changes: ChangedIdsResponse,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkScheduleResponse {
+    /// For dry runs, the number of cards that would be updated.
+    updated: usize,
+    results: Vec<BulkScheduleResult>,
+    changes: ChangedIdsResponse,
+    /// For dry runs, how many cards would land on each day.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    histogram: Option<Vec<DueDayCount>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GradeCardsRequest {
+    card_ids: Vec<i64>,
+    rating: AnswerRating,
+    /// If true, report how the cards would be scheduled without changing
+    /// them.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GradeCardsResponse {
+    /// For dry runs, the number of cards that would be graded.
+    graded: usize,
+    changes: ChangedIdsResponse,
+    /// For dry runs, the outcome for each card.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    results: Option<Vec<GradeResult>>,
+    /// For dry runs, how many cards would be due on each day.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    histogram: Option<Vec<DueDayCount>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GradeResult {
+    card_id: i64,
+    due_in_days: i32,
+    interval: u32,
+    /// True if the card would be forgotten and start relearning.
+    lapse: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DueDayCount {
+    due_in_days: i32,
+    count: usize,
+}
+
+/// The cards an operation applies to, given by exactly one of the keys.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CardSelector {
+    CardIds(Vec<i64>),
+    /// The deck and its children.
+    DeckId(i64),
+    Search(String),
+}
+
+impl CardSelector {
+    fn card_ids(self, col: &mut Collection) -> Result<Vec<CardId>> {
+        match self {
+            CardSelector::CardIds(ids) => Ok(ids.into_iter().map(CardId).collect()),
+            CardSelector::DeckId(did) => {
+                let deck = col.get_deck(DeckId(did))?.or_not_found(did)?;
+                col.search_cards(
+                    SearchNode::from_deck_name(&deck.human_name()),
+                    SortMode::NoOrder,
+                )
+            }
+            CardSelector::Search(search) => col.search_cards(search.as_str(), SortMode::NoOrder),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetEaseRequest {
+    selector: CardSelector,
+    /// Between 1.3 and 5.0.
+    ease_factor: f32,
+    /// Only update cards whose ease is below this.
+    only_below: Option<f32>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetEaseResponse {
+    updated: usize,
+    /// Review cards left alone because FSRS is enabled.
+    skipped_fsrs: usize,
+    changes: ChangedIdsResponse,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DeleteCardsRequest {
+    // the snake_case key is accepted until the next API version
+    #[serde(alias = "card_ids")]
     card_ids: Vec<i64>,
+    /// Required when deleting more cards than the server's threshold.
+    #[serde(default)]
+    confirm: bool,
+    /// Move media files that were only referenced by the notes removed
+    /// with the cards to the media trash. Files that can't be moved don't
+    /// fail the delete, and are reported in `mediaNotTrashed`.
+    #[serde(default)]
+    cleanup_media: bool,
 }
 
 #[derive(Serialize)]
-pub struct SuccessResponse {
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCardContentResponse {
     success: bool,
+    /// The tags as saved, if they differ from the ones sent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    normalized_tags: Option<Vec<String>>,
+    changes: ChangedIdsResponse,
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DeleteCardsResponse {
     success: bool,
+    /// The same as `deleted`, kept for existing clients.
     deleted_count: usize,
+    #[serde(flatten)]
+    counts: DeleteCountsResponse,
+    /// The media files moved to the trash, if cleanupMedia was set.
+    #[serde(flatten)]
+    media: Option<TrashedMediaResponse>,
+    changes: ChangedIdsResponse,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DifficultyResponse {
+    difficulty: f32,
+    /// "fsrs" if taken from the card's memory state, or "sm2_estimated" if
+    /// derived from its ease factor and lapses.
+    method: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntervalSnapshotResponse {
+    /// In YYYY-MM-DD format. Days start at the collection's rollover hour.
+    review_date: String,
+    /// In days, or in seconds for learning steps.
+    interval_after: i32,
+    ease_after: u16,
+    rating: u8,
+    learning_step: bool,
+}
+
+impl From<IntervalSnapshot> for IntervalSnapshotResponse {
+    fn from(snapshot: IntervalSnapshot) -> Self {
+        Self {
+            review_date: snapshot.review_date.format("%Y-%m-%d").to_string(),
+            interval_after: snapshot.interval_after,
+            ease_after: snapshot.ease_after,
+            rating: snapshot.rating,
+            learning_step: snapshot.is_learning_step(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LongOverdueQuery {
+    overdue_days: u32,
+    /// Limits the search to the deck and its children.
+    deck_id: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LongOverdueCardResponse {
+    card_id: i64,
+    days_overdue: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SinceQuery {
+    /// Unix timestamp in seconds.
+    since: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModifiedCardResponse {
+    card_id: i64,
+    /// Modification time, in seconds.
+    modified_at: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModifiedCardsResponse {
+    cards: Vec<ModifiedCardResponse>,
+    /// The server's clock when the request was handled, to use as `since` in
+    /// the next request.
+    server_time: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletedCardsResponse {
+    card_ids: Vec<i64>,
+    /// The server's clock when the request was handled.
+    server_time: i64,
 }
 
 // Router definition
 pub fn routes() -> Router<Arc<SimpleServer>> {
     Router::new()
-        .route("/cards", post(add_card).delete(delete_cards))
+        .route(
+            "/cards",
+            get(list_cards).post(add_card).delete(delete_cards),
+        )
+        .route("/cards/due", get(due_cards))
+        .route("/cards/long-overdue", get(long_overdue_cards))
+        .route("/cards/modified-since", get(cards_modified_since))
+        .route("/cards/deleted-since", get(cards_deleted_since))
+        .route("/cards/schedule", post(bulk_schedule))
+        .route("/cards/set-ease", post(set_ease))
+        .route("/cards/grade", post(grade_cards))
         .route("/cards/{card_id}", get(get_card).put(update_card_content))
         .route("/cards/{card_id}/schedule", put(update_schedule))
-}
-
-fn with_col<F, T>(server: &SimpleServer, op: F) -> ApiResult<T>
-where
-    F: FnOnce(&mut Collection) -> Result<T, AnkiError>,
-{
-    let mut state = server.state.lock().unwrap();
-    // For now, we'll just grab the first user.
-    let user = state.users.values_mut().next().unwrap();
-    user.ensure_col_open()?;
-    let col = user.col.as_mut().unwrap();
-    op(col).map_err(Into::into)
+        .route("/cards/{card_id}/difficulty", get(get_difficulty))
+        .route(
+            "/cards/{card_id}/interval-history",
+            get(get_interval_history),
+        )
+        .route(
+            "/cards/{card_id}/scheduling-states",
+            get(get_scheduling_states),
+        )
 }
 
 // Handler for adding a card
@@ -102,6 +575,7 @@ async fn add_card(
 ) -> ApiResult<Json<AddCardResponse>> {
     let payload = payload?;
     with_col(&server, |col| {
+        let deck_existed = col.get_deck_id(&payload.deck_name)?.is_some();
         let deck_id = col.get_or_create_normal_deck(&payload.deck_name)?.id;
         let notetype = col
             .get_notetype_by_name(&payload.notetype_name)?
@@ -114,7 +588,8 @@ async fn add_card(
             })?;
 
         let mut note = Note::new(&notetype);
-        note.tags = payload.tags.clone();
+        note.tags = normalize_tags(col, &payload.tags)?;
+        let normalized_tags = (note.tags != payload.tags).then(|| note.tags.clone());
 
         for (name, value) in &payload.fields {
             if let Some(idx) = notetype.get_field_ord(name) {
@@ -122,22 +597,185 @@ async fn add_card(
             }
         }
 
-        col.add_note(&mut note, deck_id)?;
+        let mut changes = match payload.new_position {
+            Some(position) => col.add_note_at_position(&mut note, deck_id, position)?,
+            None => col.add_note(&mut note, deck_id)?,
+        }
+        .changes
+        .ids;
+        // a new deck is added in a step of its own
+        if !deck_existed {
+            changes.decks.push(deck_id);
+        }
 
         let card_ids = col.storage.card_ids_of_notes(&[note.id])?;
 
         Ok(Json(AddCardResponse {
             card_ids: card_ids.into_iter().map(|id| id.0).collect(),
+            normalized_tags,
+            changes: changes.into(),
+        }))
+    })
+    .await
+}
+
+/// Cursors are opaque to clients, so the way pages are keyed can change
+/// without breaking clients that store them.
+fn encode_cursor(last: CardId) -> String {
+    BASE64URL_NOPAD.encode(last.0.to_string().as_bytes())
+}
+
+fn decode_cursor(cursor: &str) -> Result<CardId> {
+    BASE64URL_NOPAD
+        .decode(cursor.as_bytes())
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|text| text.parse().ok())
+        .map(CardId)
+        .or_invalid("invalid cursor")
+}
+
+// Handler for listing cards a page at a time
+async fn list_cards(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<ListCardsQuery>,
+) -> ApiResult<Json<ListCardsResponse>> {
+    with_col(&server, |col| {
+        let limit = query.limit.clamp(1, MAX_LIST_LIMIT);
+        let (cids, next_cursor) = if query.min_ease.is_some() || query.max_ease.is_some() {
+            require!(
+                query.cursor.is_none(),
+                "ease-filtered results are not paginated"
+            );
+            let cids = col.get_cards_by_ease_range(
+                query.deck_id.map(DeckId),
+                query.min_ease.unwrap_or(0.0),
+                query.max_ease.unwrap_or(f32::MAX),
+                limit,
+            )?;
+            (cids, None)
+        } else {
+            require!(
+                query.deck_id.is_none(),
+                "deckId requires minEase or maxEase"
+            );
+            let after = query.cursor.as_deref().map(decode_cursor).transpose()?;
+            let cids = col.get_all_card_ids_paginated(after, limit)?;
+            let next_cursor = (cids.len() == limit as usize)
+                .then(|| cids.last().copied().map(encode_cursor))
+                .flatten();
+            (cids, next_cursor)
+        };
+        let cards = cids
+            .into_iter()
+            .map(|cid| {
+                let card = col.storage.get_card(cid)?.or_not_found(cid)?;
+                Ok(CardSummaryResponse::from(&card))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Json(ListCardsResponse { cards, next_cursor }))
+    })
+    .await
+}
+
+// Handler for listing the cards due today
+async fn due_cards(State(server): State<Arc<SimpleServer>>) -> ApiResult<Response> {
+    with_col(&server, |col| {
+        let cards = col
+            .all_cards_for_search(SearchNode::State(StateKind::Due))?
+            .iter()
+            .map(CardSummaryResponse::from)
+            .collect();
+        let mut response = Json(DueCardsResponse { cards }).into_response();
+        if col.get_scheduler_version()? < 3 {
+            let headers = response.headers_mut();
+            headers.insert(
+                HeaderName::from_static("deprecation"),
+                HeaderValue::from_static("true"),
+            );
+            headers.insert(
+                header::LINK,
+                HeaderValue::from_str(&format!("<{V3_UPGRADE_GUIDE}>; rel=\"deprecation\""))
+                    .unwrap(),
+            );
+        }
+        Ok(response)
+    })
+    .await
+}
+
+// Handler for listing review cards overdue by more than the given number of
+// days, most overdue first
+async fn long_overdue_cards(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<LongOverdueQuery>,
+) -> ApiResult<Json<Vec<LongOverdueCardResponse>>> {
+    with_col(&server, |col| {
+        Ok(Json(
+            col.get_long_overdue_cards(query.deck_id.map(DeckId), query.overdue_days)?
+                .into_iter()
+                .map(|(cid, days_overdue)| LongOverdueCardResponse {
+                    card_id: cid.0,
+                    days_overdue,
+                })
+                .collect(),
+        ))
+    })
+    .await
+}
+
+// Handler for listing cards modified since a given time, oldest first
+async fn cards_modified_since(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<SinceQuery>,
+) -> ApiResult<Json<ModifiedCardsResponse>> {
+    with_col(&server, |col| {
+        // taken first, so changes made while the query runs are not missed
+        let server_time = TimestampSecs::now();
+        Ok(Json(ModifiedCardsResponse {
+            cards: col
+                .get_cards_modified_since(TimestampSecs(query.since))?
+                .into_iter()
+                .map(|(cid, mtime)| ModifiedCardResponse {
+                    card_id: cid.0,
+                    modified_at: mtime.0,
+                })
+                .collect(),
+            server_time: server_time.0,
+        }))
+    })
+    .await
+}
+
+// Handler for listing deleted cards. Graves carry no deletion time, so this
+// includes every card deleted since the last full sync, whatever the value of
+// `since`.
+async fn cards_deleted_since(
+    State(server): State<Arc<SimpleServer>>,
+    Query(_query): Query<SinceQuery>,
+) -> ApiResult<Json<DeletedCardsResponse>> {
+    with_col(&server, |col| {
+        let server_time = TimestampSecs::now();
+        Ok(Json(DeletedCardsResponse {
+            card_ids: col
+                .get_deleted_cards()?
+                .into_iter()
+                .map(|cid| cid.0)
+                .collect(),
+            server_time: server_time.0,
         }))
     })
+    .await
 }
 
 // Handler for getting a card
 async fn get_card(
     State(server): State<Arc<SimpleServer>>,
     Path(card_id): Path<i64>,
-) -> ApiResult<Json<CardInfoResponse>> {
-    with_col(&server, |col| {
+    Query(query): Query<GetCardQuery>,
+) -> ApiResult<Json<Expanded<CardInfoResponse>>> {
+    with_col_and_media_folder(&server, |col, media_folder| {
+        let expand = Expand::parse(query.expand.as_deref(), &["note", "deck", "notetype"])?;
         let cid = CardId(card_id);
         let card = col.storage.get_card(cid)?.ok_or(AnkiError::NotFound {
             source: crate::error::NotFoundError {
@@ -147,17 +785,164 @@ async fn get_card(
             },
         })?;
         let rendered = col.render_existing_card(cid, false, false)?;
+        let prefix = query.media_url_prefix.as_deref();
+        let siblings = col
+            .storage
+            .sibling_info(card.id, card.note_id)?
+            .into_iter()
+            .map(|sibling| SiblingResponse {
+                card_id: sibling.card_id.0,
+                ord: sibling.template_idx,
+                queue: sibling.queue as i8,
+                due: sibling.due,
+            })
+            .collect();
 
-        Ok(Json(CardInfoResponse {
+        let filtered = filtered_deck_membership(col, &card)?;
+        let response = CardInfoResponse {
             card_id: card.id.0,
             deck_id: card.deck_id.0,
             due: card.due,
             interval: card.interval,
             ease_factor: card.ease_factor(),
-            rendered_front: rendered.question().to_string(),
-            rendered_back: rendered.answer().to_string(),
+            queue_position: col.get_card_queue_position(card.id)?,
+            in_filtered_deck: filtered.is_some(),
+            filtered_deck_name: filtered
+                .as_ref()
+                .map(|filtered| filtered.filtered_deck_name.clone()),
+            original_deck_id: filtered.as_ref().map(|filtered| filtered.home_deck_id),
+            original_due: (card.original_due != 0).then_some(card.original_due),
+            rendered_front: rendered_html(&rendered.question(), prefix),
+            rendered_back: rendered_html(&rendered.answer(), prefix),
+            warnings: render_warnings(&rendered, query.check_media.then_some(media_folder)),
+            siblings,
+        };
+        let note = col
+            .storage
+            .get_note(card.note_id)?
+            .or_not_found(card.note_id)?;
+        expand
+            .apply(response, |name| {
+                Ok(match name {
+                    "note" => serde_json::to_value(NoteResponse::from(note.clone()))?,
+                    "deck" => serde_json::to_value(deck_response(col, card.deck_id)?)?,
+                    _ => {
+                        let nt = col
+                            .get_notetype(note.notetype_id)?
+                            .or_not_found(note.notetype_id)?;
+                        serde_json::to_value(NotetypeResponse::from(nt.as_ref()))?
+                    }
+                })
+            })
+            .map(Json)
+    })
+    .await
+}
+
+// Handler for getting a card's difficulty
+async fn get_difficulty(
+    State(server): State<Arc<SimpleServer>>,
+    Path(card_id): Path<i64>,
+) -> ApiResult<Json<DifficultyResponse>> {
+    with_col(&server, |col| {
+        let cid = CardId(card_id);
+        let card = col.storage.get_card(cid)?.or_not_found(cid)?;
+        Ok(Json(DifficultyResponse {
+            difficulty: col.compute_card_difficulty(cid)?,
+            method: if card.memory_state.is_some() {
+                "fsrs"
+            } else {
+                "sm2_estimated"
+            },
+        }))
+    })
+    .await
+}
+
+// Handler for getting a card's interval after each answer
+async fn get_interval_history(
+    State(server): State<Arc<SimpleServer>>,
+    Path(card_id): Path<i64>,
+) -> ApiResult<Json<Vec<IntervalSnapshotResponse>>> {
+    with_col(&server, |col| {
+        Ok(Json(
+            col.get_interval_history_for_card(CardId(card_id))?
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        ))
+    })
+    .await
+}
+
+// Handler for getting a card's next states, and the inputs that produced them
+async fn get_scheduling_states(
+    State(server): State<Arc<SimpleServer>>,
+    Path(card_id): Path<i64>,
+    Query(query): Query<SchedulingStatesQuery>,
+) -> ApiResult<Json<SchedulingStatesResponse>> {
+    with_col(&server, |col| {
+        let cid = CardId(card_id);
+        let states = col.get_scheduling_states(cid)?;
+        let fuzz = col.get_scheduling_fuzz(cid)?;
+        let code = col.get_config_string(StringKey::CardStateCustomizer);
+        Ok(Json(SchedulingStatesResponse {
+            current: scheduling_state_response(states.current),
+            again: scheduling_state_response(states.again),
+            hard: scheduling_state_response(states.hard),
+            good: scheduling_state_response(states.good),
+            easy: scheduling_state_response(states.easy),
+            fuzz: FuzzResponse {
+                seed: fuzz.seed,
+                factor: fuzz.factor,
+                load_balanced: fuzz.load_balanced,
+                ranges: fuzz
+                    .ranges
+                    .iter()
+                    .map(|range| range.map(fuzz_range_response))
+                    .collect(),
+            },
+            custom_scheduling: CustomSchedulingResponse {
+                set: !code.trim().is_empty(),
+                code: query.include_custom_scheduling.then_some(code),
+            },
         }))
     })
+    .await
+}
+
+fn scheduling_state_response(state: CardState) -> SchedulingStateResponse {
+    let review = state.review_state();
+    SchedulingStateResponse {
+        kind: card_state_kind(state),
+        interval_secs: state.interval_kind().as_seconds(),
+        scheduled_days: review.map(|r| r.scheduled_days),
+        ease_factor: review.map(|r| r.ease_factor),
+        stability: review.and_then(|r| r.memory_state).map(|m| m.stability),
+        difficulty: review.and_then(|r| r.memory_state).map(|m| m.difficulty),
+    }
+}
+
+fn card_state_kind(state: CardState) -> &'static str {
+    let normal = match state {
+        CardState::Normal(normal) => normal,
+        CardState::Filtered(FilteredState::Preview(_)) => return "preview",
+        CardState::Filtered(FilteredState::Rescheduling(resched)) => resched.original_state,
+    };
+    match normal {
+        NormalState::New(_) => "new",
+        NormalState::Learning(_) => "learning",
+        NormalState::Review(_) => "review",
+        NormalState::Relearning(_) => "relearning",
+    }
+}
+
+fn fuzz_range_response(range: FuzzRange) -> FuzzRangeResponse {
+    FuzzRangeResponse {
+        unfuzzed_days: range.unfuzzed_days,
+        lower_days: range.lower_days,
+        upper_days: range.upper_days,
+    }
 }
 
 // Handler for updating a card's content
@@ -165,7 +950,7 @@ async fn update_card_content(
     State(server): State<Arc<SimpleServer>>,
     Path(card_id): Path<i64>,
     payload: Result<Json<UpdateCardContentRequest>, JsonRejection>,
-) -> ApiResult<Json<SuccessResponse>> {
+) -> ApiResult<Json<UpdateCardContentResponse>> {
     let payload = payload?;
     with_col(&server, |col| {
         let cid = CardId(card_id);
@@ -194,14 +979,23 @@ async fn update_card_content(
             }
         }
 
+        let mut normalized_tags = None;
         if let Some(tags) = &payload.tags {
-            note.tags = tags.clone();
+            note.tags = normalize_tags(col, tags)?;
+            if &note.tags != tags {
+                normalized_tags = Some(note.tags.clone());
+            }
         }
 
-        col.update_note(&mut note)?;
+        let changes = col.update_note(&mut note)?.changes;
 
-        Ok(Json(SuccessResponse { success: true }))
+        Ok(Json(UpdateCardContentResponse {
+            success: true,
+            normalized_tags,
+            changes: changes.ids.into(),
+        }))
     })
+    .await
 }
 
 // Handler for updating a card's schedule
@@ -209,36 +1003,259 @@ async fn update_schedule(
     State(server): State<Arc<SimpleServer>>,
     Path(card_id): Path<i64>,
     payload: Result<Json<UpdateScheduleRequest>, JsonRejection>,
-) -> ApiResult<Json<SuccessResponse>> {
+) -> ApiResult<Json<UpdateScheduleResponse>> {
     let payload = payload?;
     with_col(&server, |col| {
         let cid = CardId(card_id);
-        let due_str = if let Some(days) = payload
-            .due
-            .strip_prefix('+')
-            .and_then(|s| s.strip_suffix('d'))
-        {
-            days.to_string()
-        } else {
-            payload.due.clone()
-        };
-        col.set_due_date(&[cid], &due_str, None)?;
-        Ok(Json(SuccessResponse { success: true }))
+        let card = col.storage.get_card(cid)?.or_not_found(cid)?;
+        let removed_from_filtered_deck = filtered_deck_membership(col, &card)?;
+        let due_str = normalize_due_str(&payload.due, payload.fuzz);
+        let changes = col.set_due_date(&[cid], &due_str, None)?.changes;
+        Ok(Json(UpdateScheduleResponse {
+            success: true,
+            removed_from_filtered_deck,
+            changes: changes.ids.into(),
+        }))
+    })
+    .await
+}
+
+// Handler for scheduling many cards, each with its own due date
+async fn bulk_schedule(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<BulkScheduleRequest>, JsonRejection>,
+) -> ApiResult<Json<BulkScheduleResponse>> {
+    let payload = payload?;
+    with_col(&server, |col| {
+        let entries: Vec<(CardId, String)> = payload
+            .cards
+            .iter()
+            .map(|entry| {
+                (
+                    CardId(entry.card_id),
+                    normalize_due_str(&entry.due, entry.fuzz),
+                )
+            })
+            .collect();
+        let mut filtered = HashMap::new();
+        for (cid, _) in &entries {
+            if let Some(card) = col.storage.get_card(*cid)? {
+                if let Some(membership) = filtered_deck_membership(col, &card)? {
+                    filtered.insert(*cid, membership);
+                }
+            }
+        }
+        if payload.dry_run {
+            let timing = col.timing_today()?;
+            let results: Vec<_> = entries
+                .iter()
+                .zip(col.preview_due_dates(&entries, payload.atomic)?)
+                .map(|((cid, _), result)| match result {
+                    Ok(card) => BulkScheduleResult {
+                        card_id: cid.0,
+                        success: true,
+                        error: None,
+                        removed_from_filtered_deck: filtered.get(cid).cloned(),
+                        due_in_days: Some(due_in_days(&card, &timing)),
+                        interval: Some(card.interval),
+                    },
+                    Err(err) => BulkScheduleResult {
+                        card_id: cid.0,
+                        success: false,
+                        error: Some(err.message(&col.tr)),
+                        removed_from_filtered_deck: None,
+                        due_in_days: None,
+                        interval: None,
+                    },
+                })
+                .collect();
+            return Ok(Json(BulkScheduleResponse {
+                updated: results.iter().filter(|r| r.success).count(),
+                histogram: Some(due_histogram(results.iter().filter_map(|r| r.due_in_days))),
+                results,
+                changes: ChangedIds::default().into(),
+            }));
+        }
+        let out = col.set_due_dates(&entries, payload.atomic)?;
+        let results: Vec<_> = entries
+            .iter()
+            .zip(out.output)
+            .map(|((cid, _), result)| BulkScheduleResult {
+                card_id: cid.0,
+                success: result.is_ok(),
+                removed_from_filtered_deck: result
+                    .is_ok()
+                    .then(|| filtered.get(cid).cloned())
+                    .flatten(),
+                error: result.err().map(|err| err.message(&col.tr)),
+                due_in_days: None,
+                interval: None,
+            })
+            .collect();
+        Ok(Json(BulkScheduleResponse {
+            updated: results.iter().filter(|r| r.success).count(),
+            results,
+            changes: out.changes.ids.into(),
+            histogram: None,
+        }))
+    })
+    .await
+}
+
+// Handler for resetting the ease factor of many review cards
+async fn set_ease(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<SetEaseRequest>, JsonRejection>,
+) -> ApiResult<Json<SetEaseResponse>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        let cids = payload.selector.card_ids(col)?;
+        let out = col.set_ease_factor(&cids, payload.ease_factor, payload.only_below)?;
+        Ok(Json(SetEaseResponse {
+            updated: out.output.updated,
+            skipped_fsrs: out.output.skipped_fsrs,
+            changes: out.changes.ids.into(),
+        }))
     })
+    .await
+}
+
+// Handler for grading cards outside of the study queues, as if they had been
+// answered now
+async fn grade_cards(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<GradeCardsRequest>, JsonRejection>,
+) -> ApiResult<Json<GradeCardsResponse>> {
+    let Json(payload) = payload?;
+    let cids: Vec<CardId> = payload.card_ids.iter().copied().map(CardId).collect();
+    let rating = Rating::from(payload.rating) as i32;
+    with_col(&server, |col| {
+        if payload.dry_run {
+            let timing = col.timing_today()?;
+            let results: Vec<_> = col
+                .preview_grade_now(&cids, rating)?
+                .into_iter()
+                .map(|preview| GradeResult {
+                    card_id: preview.card.id.0,
+                    due_in_days: due_in_days(&preview.card, &timing),
+                    interval: preview.card.interval,
+                    lapse: preview.lapse,
+                })
+                .collect();
+            return Ok(Json(GradeCardsResponse {
+                graded: results.len(),
+                changes: ChangedIds::default().into(),
+                histogram: Some(due_histogram(results.iter().map(|r| r.due_in_days))),
+                results: Some(results),
+            }));
+        }
+        let changes = col.grade_now(&cids, rating)?.changes;
+        Ok(Json(GradeCardsResponse {
+            graded: cids.len(),
+            changes: changes.ids.into(),
+            results: None,
+            histogram: None,
+        }))
+    })
+    .await
+}
+
+/// Days from today until `card` is due. Learning cards due before the next
+/// day starts count as due today.
+fn due_in_days(card: &Card, timing: &SchedTimingToday) -> i32 {
+    if card.queue == CardQueue::Learn {
+        let secs = card.due as i64 - timing.next_day_at.0;
+        if secs < 0 {
+            0
+        } else {
+            (secs / 86_400 + 1) as i32
+        }
+    } else {
+        card.due - timing.days_elapsed as i32
+    }
+}
+
+/// The number of cards due on each day, in day order.
+fn due_histogram(days: impl Iterator<Item = i32>) -> Vec<DueDayCount> {
+    days.counts()
+        .into_iter()
+        .sorted()
+        .map(|(due_in_days, count)| DueDayCount { due_in_days, count })
+        .collect()
+}
+
+/// The filtered deck `card` is in, if any.
+fn filtered_deck_membership(
+    col: &mut Collection,
+    card: &Card,
+) -> Result<Option<RemovedFromFilteredDeckResponse>> {
+    if card.original_deck_id.0 == 0 {
+        return Ok(None);
+    }
+    let deck = col.get_deck(card.deck_id)?.or_not_found(card.deck_id)?;
+    Ok(Some(RemovedFromFilteredDeckResponse {
+        filtered_deck_id: deck.id.0,
+        filtered_deck_name: deck.human_name(),
+        home_deck_id: card.original_deck_id.0,
+    }))
+}
+
+/// Accept "+Nd" as an alias for "N", and add the "~" that `fuzz` stands for.
+fn normalize_due_str(due: &str, fuzz: bool) -> String {
+    let due = due
+        .strip_prefix('+')
+        .and_then(|s| s.strip_suffix('d'))
+        .unwrap_or(due);
+    if !fuzz || due.contains('~') {
+        return due.to_string();
+    }
+    match due.strip_suffix('!') {
+        Some(days) => format!("{days}~!"),
+        None => format!("{due}~"),
+    }
 }
 
 // Handler for deleting cards
 async fn delete_cards(
     State(server): State<Arc<SimpleServer>>,
+    headers: HeaderMap,
     payload: Result<Json<DeleteCardsRequest>, JsonRejection>,
 ) -> ApiResult<Json<DeleteCardsResponse>> {
     let payload = payload?;
-    with_col(&server, |col| {
-        let cids: Vec<CardId> = payload.card_ids.clone().into_iter().map(CardId).collect();
-        let count = col.remove_cards_and_orphaned_notes(&cids)?;
-        Ok(Json(DeleteCardsResponse {
-            success: true,
-            deleted_count: count,
-        }))
-    })
+    let cids: Vec<CardId> = payload.card_ids.iter().copied().map(CardId).collect();
+    with_col_confirming_delete(
+        &server,
+        &headers,
+        payload.confirm,
+        |col| {
+            let mut existing = HashSet::new();
+            for cid in &cids {
+                if col.storage.get_card(*cid)?.is_some() {
+                    existing.insert(*cid);
+                }
+            }
+            Ok(existing.len())
+        },
+        |col, media| {
+            let unused_media = if payload.cleanup_media {
+                let nids = col.notes_orphaned_by_removing_cards(&cids)?;
+                Some(col.media_only_referenced_by(&nids)?)
+            } else {
+                None
+            };
+            let out = col.transact(Op::EmptyCards, |col| {
+                col.remove_cards_and_orphaned_notes(&cids)
+            })?;
+            let removed: HashSet<i64> = out.output.into_iter().map(|cid| cid.0).collect();
+            let trashed = unused_media.map(|files| media.trash_files(&files).into());
+            Ok(Json(DeleteCardsResponse {
+                success: true,
+                deleted_count: removed.len(),
+                counts: DeleteCountsResponse::new(&payload.card_ids, |id| !removed.contains(&id)),
+                media: trashed,
+                changes: out.changes.ids.into(),
+            }))
+        },
+    )
+    .await
 }
@@ -0,0 +1,806 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::convert::Infallible;
+use std::io;
+use std::sync::Arc;
+
+use anki_proto::config::preferences::BackupLimits;
+use axum::body::Body;
+use axum::body::Bytes;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::get;
+use axum::routing::post;
+use axum::routing::put;
+use axum::Json;
+use axum::Router;
+use futures::stream;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::with_col;
+use super::with_col_and_media_folder;
+use super::with_user;
+use crate::card::anomalies::CardAnomaly;
+use crate::card::anomalies::CardAnomalyKind;
+use crate::collection::size::SizeBreakdown;
+use crate::prelude::*;
+use crate::revlog::export::review_log_csv_header;
+use crate::revlog::export::REVIEW_LOG_CSV_CHUNK_ROWS;
+use crate::scheduler::fsrs::retention::FsrsCardState;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+// Payloads for the API
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionInfoResponse {
+    /// 1, 2 or 3; due counts and intervals are interpreted differently by
+    /// each version.
+    scheduler_version: u8,
+    card_count: u32,
+    note_count: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupResponse {
+    filename: String,
+    size_bytes: u64,
+    created: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListBackupsResponse {
+    backups: Vec<BackupResponse>,
+    /// Age in days of the oldest available backup.
+    retention_days: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupConfigPayload {
+    /// The minimum time between automatic backups.
+    interval_hours: f32,
+    /// How many daily, weekly and monthly backups are kept when old ones are
+    /// pruned.
+    daily: u32,
+    weekly: u32,
+    monthly: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupOutcomeResponse {
+    /// Unix timestamp in seconds.
+    time: i64,
+    success: bool,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupStatusResponse {
+    interval_hours: f32,
+    /// Unix timestamp in seconds. The backup is skipped if the collection
+    /// has not changed since the last one.
+    next_backup: i64,
+    /// The last automatic backup since the server started, if any.
+    last_backup: Option<BackupOutcomeResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageResponse {
+    /// The collection file, and its write-ahead log if there is one.
+    database_bytes: u64,
+    media_bytes: u64,
+    media_count: u32,
+    backup_bytes: u64,
+    backup_count: u32,
+    total_bytes: u64,
+}
+
+impl From<SizeBreakdown> for StorageResponse {
+    fn from(breakdown: SizeBreakdown) -> Self {
+        Self {
+            database_bytes: breakdown.database_bytes,
+            media_bytes: breakdown.media_bytes,
+            media_count: breakdown.media_count,
+            backup_bytes: breakdown.backup_bytes,
+            backup_count: breakdown.backup_count,
+            total_bytes: breakdown.total_bytes,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangesSinceQuery {
+    /// Unix timestamp in seconds.
+    since: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangesSinceResponse {
+    updated_cards: Vec<i64>,
+    deleted_cards: Vec<i64>,
+    updated_notes: Vec<i64>,
+    deleted_notes: Vec<i64>,
+    updated_decks: Vec<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GravesSinceResponse {
+    cards: Vec<i64>,
+    notes: Vec<i64>,
+    decks: Vec<i64>,
+    /// The server's clock when the request was handled. Deletions after
+    /// this will be returned when it is passed as `since`.
+    until: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionUsnResponse {
+    usn: i32,
+    /// True if the schema has changed since the last sync, so the next sync
+    /// must be a full one.
+    schema_modified: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionBucket {
+    lower: f32,
+    count: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionDistributionResponse {
+    buckets: Vec<RetentionBucket>,
+    mean: f32,
+    p10: f32,
+    p50: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewLogCsvQuery {
+    /// Limits the reviews to cards whose home deck is this deck or one of its
+    /// children.
+    deck_id: Option<i64>,
+    /// Only include reviews at or after this time, in seconds.
+    since: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FsrsStatesQuery {
+    /// Limits the cards to a deck and its children.
+    deck_id: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FsrsStateResponse {
+    card_id: i64,
+    stability: f32,
+    difficulty: f32,
+    /// Unix timestamp in seconds.
+    last_review: Option<i64>,
+    retrievability: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedFsrsState {
+    card_id: i64,
+    stability: f32,
+    difficulty: f32,
+    /// Unix timestamp in seconds.
+    #[serde(default)]
+    last_review: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportFsrsStatesResponse {
+    updated: usize,
+    /// States of cards that are not in the collection.
+    skipped: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeSeriesQuery {
+    #[serde(default = "default_time_series_days")]
+    days: u32,
+    /// Limits the reviews to cards in a deck and its children.
+    deck_id: Option<i64>,
+}
+
+fn default_time_series_days() -> u32 {
+    30
+}
+
+const MAX_TIME_SERIES_DAYS: u32 = 3650;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeSeriesDay {
+    /// In YYYY-MM-DD format. Days start at the collection's rollover hour.
+    date: String,
+    total_seconds: u64,
+    review_seconds: u64,
+    learn_seconds: u64,
+    relearn_seconds: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EaseOutliersQuery {
+    #[serde(default = "default_std_devs")]
+    std_devs: f32,
+    /// Limits the cards to a deck and its children.
+    deck_id: Option<i64>,
+}
+
+fn default_std_devs() -> f32 {
+    2.0
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EaseOutlierResponse {
+    card_id: i64,
+    /// As a multiplier, eg 2.5.
+    ease: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchedulerVersionPayload {
+    /// 2 or 3.
+    version: u8,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuspendLeechesResponse {
+    suspended: usize,
+    /// The first suspended cards, up to [MAX_SUSPENDED_IDS].
+    suspended_ids: Vec<i64>,
+}
+
+const MAX_SUSPENDED_IDS: usize = 1000;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardAnomalyResponse {
+    card_id: i64,
+    /// newPositionTooHigh, reviewDueNotDays, reviewIntervalZero or
+    /// easeTooLow.
+    kind: &'static str,
+    /// A position, due number, interval or ease in thousandths.
+    value: i64,
+    /// Only in fix responses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fixed_value: Option<i64>,
+}
+
+impl From<CardAnomaly> for CardAnomalyResponse {
+    fn from(anomaly: CardAnomaly) -> Self {
+        CardAnomalyResponse {
+            card_id: anomaly.card_id.0,
+            kind: card_anomaly_kind_name(anomaly.kind),
+            value: anomaly.value,
+            fixed_value: anomaly.fixed_value,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardAnomaliesResponse {
+    anomalies: Vec<CardAnomalyResponse>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixCardAnomaliesRequest {
+    /// The cards to fix; all cards if omitted.
+    card_ids: Option<Vec<i64>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixCardAnomaliesResponse {
+    fixed: Vec<CardAnomalyResponse>,
+}
+
+fn card_anomaly_kind_name(kind: CardAnomalyKind) -> &'static str {
+    match kind {
+        CardAnomalyKind::NewPositionTooHigh => "newPositionTooHigh",
+        CardAnomalyKind::ReviewDueNotDays => "reviewDueNotDays",
+        CardAnomalyKind::ReviewIntervalZero => "reviewIntervalZero",
+        CardAnomalyKind::EaseTooLow => "easeTooLow",
+    }
+}
+
+const MAX_BACKUP_INTERVAL_MINS: f32 = 365.0 * 24.0 * 60.0;
+
+/// The number of states serialized into each chunk of the response body.
+const FSRS_STATES_CHUNK_SIZE: usize = 500;
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new()
+        .route("/collection", get(collection_info))
+        .route("/collection/backups", get(list_backups))
+        .route(
+            "/collection/backups/config",
+            get(backup_config).put(set_backup_config),
+        )
+        .route("/collection/backups/status", get(backup_status))
+        .route("/collection/changes-since", get(changes_since))
+        .route("/collection/graves-since", get(graves_since))
+        .route("/collection/usn", get(collection_usn))
+        .route("/collection/storage", get(collection_storage))
+        .route("/collection/review-log.csv", get(review_log_csv))
+        .route("/collection/ease-outliers", get(ease_outliers))
+        .route("/collection/anomalies", get(card_anomalies))
+        .route("/collection/anomalies/fix", post(fix_card_anomalies))
+        .route("/collection/time-series", get(time_series))
+        .route("/collection/scheduler", put(set_scheduler))
+        .route("/collection/suspend-leeches", post(suspend_leeches))
+        .route(
+            "/collection/fsrs-states",
+            get(fsrs_states).post(import_fsrs_states),
+        )
+        .route(
+            "/collection/retention-distribution",
+            get(retention_distribution),
+        )
+}
+
+// Handler for general information about the collection
+async fn collection_info(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<CollectionInfoResponse>> {
+    with_col(&server, |col| {
+        Ok(Json(CollectionInfoResponse {
+            scheduler_version: col.get_scheduler_version()?,
+            card_count: col.storage.total_cards()?,
+            note_count: col.storage.total_notes()?,
+        }))
+    })
+    .await
+}
+
+// Handler for listing backups
+async fn list_backups(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<ListBackupsResponse>> {
+    with_col(&server, |col| {
+        let backups = col.list_backups()?;
+        let retention_days = backups.last().map(|oldest| {
+            (TimestampSecs::now()
+                .elapsed_secs_since(oldest.created)
+                .max(0)
+                / 86_400) as u32
+        });
+        Ok(Json(ListBackupsResponse {
+            backups: backups
+                .into_iter()
+                .map(|backup| BackupResponse {
+                    filename: backup.filename,
+                    size_bytes: backup.size_bytes,
+                    created: backup.created.0,
+                })
+                .collect(),
+            retention_days,
+        }))
+    })
+    .await
+}
+
+// Handler for the automatic backup settings
+async fn backup_config(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<BackupConfigPayload>> {
+    with_col(&server, |col| {
+        let limits = col.get_backup_limits();
+        Ok(Json(BackupConfigPayload {
+            interval_hours: limits.minimum_interval_mins as f32 / 60.0,
+            daily: limits.daily,
+            weekly: limits.weekly,
+            monthly: limits.monthly,
+        }))
+    })
+    .await
+}
+
+// Handler for changing the automatic backup settings
+async fn set_backup_config(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<BackupConfigPayload>, JsonRejection>,
+) -> ApiResult<Json<BackupConfigPayload>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        let interval_mins = (payload.interval_hours * 60.0).round();
+        require!(
+            (1.0..=MAX_BACKUP_INTERVAL_MINS).contains(&interval_mins),
+            "intervalHours must be between 1 minute and 1 year"
+        );
+        col.set_backup_limits(BackupLimits {
+            daily: payload.daily,
+            weekly: payload.weekly,
+            monthly: payload.monthly,
+            minimum_interval_mins: interval_mins as u32,
+        })?;
+        Ok(Json(payload))
+    })
+    .await
+}
+
+// Handler for when the next automatic backup is due, and how the last one went
+async fn backup_status(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<BackupStatusResponse>> {
+    with_user(&server, |user| {
+        user.ensure_col_open()?;
+        let col = user.col.as_mut().unwrap();
+        let interval_mins = col.get_backup_limits().minimum_interval_mins;
+        let now = TimestampSecs::now();
+        let next_backup = col.list_backups()?.first().map_or(now, |newest| {
+            newest
+                .created
+                .adding_secs(interval_mins as i64 * 60)
+                .max(now)
+        });
+        Ok(Json(BackupStatusResponse {
+            interval_hours: interval_mins as f32 / 60.0,
+            next_backup: next_backup.0,
+            last_backup: user
+                .last_backup
+                .clone()
+                .map(|outcome| BackupOutcomeResponse {
+                    time: outcome.time.0,
+                    success: outcome.error.is_none(),
+                    error: outcome.error,
+                }),
+        }))
+    })
+    .await
+}
+
+// Handler for listing objects changed since a given time
+async fn changes_since(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<ChangesSinceQuery>,
+) -> ApiResult<Json<ChangesSinceResponse>> {
+    with_col(&server, |col| {
+        let changes = col.get_changed_entities_since(TimestampSecs(query.since))?;
+        Ok(Json(ChangesSinceResponse {
+            updated_cards: changes.updated_cards.into_iter().map(|id| id.0).collect(),
+            deleted_cards: changes.deleted_cards.into_iter().map(|id| id.0).collect(),
+            updated_notes: changes.updated_notes.into_iter().map(|id| id.0).collect(),
+            deleted_notes: changes.deleted_notes.into_iter().map(|id| id.0).collect(),
+            updated_decks: changes.updated_decks.into_iter().map(|id| id.0).collect(),
+        }))
+    })
+    .await
+}
+
+// Handler for listing deleted objects. The graves may include deletions from
+// before `since`, as they do not record when they were made.
+async fn graves_since(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<ChangesSinceQuery>,
+) -> ApiResult<Json<GravesSinceResponse>> {
+    with_col(&server, |col| {
+        let until = TimestampSecs::now();
+        let graves = col.get_graves_since(TimestampSecs(query.since))?;
+        Ok(Json(GravesSinceResponse {
+            cards: graves.cards.into_iter().map(|id| id.0).collect(),
+            notes: graves.notes.into_iter().map(|id| id.0).collect(),
+            decks: graves.decks.into_iter().map(|id| id.0).collect(),
+            until: until.0,
+        }))
+    })
+    .await
+}
+
+// Handler for the collection's usn, for clients coordinating their own syncs
+async fn collection_usn(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<CollectionUsnResponse>> {
+    with_col(&server, |col| {
+        Ok(Json(CollectionUsnResponse {
+            usn: col.get_collection_usn()?,
+            schema_modified: col.schema_modified_since_sync()?,
+        }))
+    })
+    .await
+}
+
+// Handler for the disk space used by the collection, its media and backups.
+// The result may be up to a minute old.
+async fn collection_storage(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<StorageResponse>> {
+    with_col_and_media_folder(&server, |col, media_folder| {
+        Ok(Json(
+            col.get_collection_size_breakdown_with_media_folder(media_folder)?
+                .into(),
+        ))
+    })
+    .await
+}
+
+// Handler for switching between the v2 and v3 schedulers
+async fn set_scheduler(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<SchedulerVersionPayload>, JsonRejection>,
+) -> ApiResult<Json<SchedulerVersionPayload>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        col.set_scheduler_version(payload.version)?;
+        Ok(Json(payload))
+    })
+    .await
+}
+
+// Handler for suspending the leeches in the collection
+async fn suspend_leeches(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<SuspendLeechesResponse>> {
+    with_col(&server, |col| suspend_leeches_response(col, None).map(Json)).await
+}
+
+/// Suspend the leeches in the deck and its children, or in the whole
+/// collection.
+pub(super) fn suspend_leeches_response(
+    col: &mut Collection,
+    deck_id: Option<DeckId>,
+) -> Result<SuspendLeechesResponse> {
+    let cids = col.suspend_leech_cards_returning_ids(deck_id)?.output;
+    Ok(SuspendLeechesResponse {
+        suspended: cids.len(),
+        suspended_ids: cids
+            .into_iter()
+            .take(MAX_SUSPENDED_IDS)
+            .map(|cid| cid.0)
+            .collect(),
+    })
+}
+
+// Handler for the retrievability distribution across the collection
+async fn retention_distribution(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<RetentionDistributionResponse>> {
+    with_col(&server, |col| {
+        let dist = col.get_fsrs_retention_for_all_cards()?;
+        Ok(Json(RetentionDistributionResponse {
+            buckets: dist
+                .buckets
+                .into_iter()
+                .map(|(lower, count)| RetentionBucket { lower, count })
+                .collect(),
+            mean: dist.mean,
+            p10: dist.p10,
+            p50: dist.p50,
+        }))
+    })
+    .await
+}
+
+// Handler for streaming the review log as CSV. Like /notes/export, the
+// collection is only locked while a chunk of rows is read.
+async fn review_log_csv(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<ReviewLogCsvQuery>,
+) -> ApiResult<Response> {
+    let deck_id = query.deck_id.map(DeckId);
+    let since = query.since.map(TimestampSecs);
+    let chunk = move |server: &SimpleServer, after| {
+        with_col(server, |col| {
+            col.review_log_csv_chunk(deck_id, since, after, REVIEW_LOG_CSV_CHUNK_ROWS)
+        })
+        .await
+    };
+    // errors reading the first chunk, such as a missing deck, can still be
+    // reported with a status code
+    let (last, rows) = chunk(&server, None)?;
+    let first = (last, review_log_csv_header() + &rows);
+    let body = stream::unfold(Some(first), move |next| {
+        let server = server.clone();
+        async move {
+            let (last, rows) = next?;
+            let Some(after) = last else {
+                return (!rows.is_empty()).then_some((Ok(rows), None));
+            };
+            // the status has already been sent, so an error cuts the response
+            // short instead
+            Some(match chunk(&server, Some(after)) {
+                Ok(next) => (Ok(rows), Some(next)),
+                Err(_) => (Err(io::Error::other("reading review log failed")), None),
+            })
+        }
+    });
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"review-log.csv\"",
+            ),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+// Handler for streaming the memory state of every card scheduled with FSRS, as
+// newline-delimited JSON
+async fn fsrs_states(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<FsrsStatesQuery>,
+) -> ApiResult<Response> {
+    let chunks = with_col(&server, |col| {
+        let states = col.get_fsrs_state_for_all_cards(query.deck_id.map(DeckId))?;
+        states
+            .chunks(FSRS_STATES_CHUNK_SIZE)
+            .map(|chunk| {
+                let mut out = vec![];
+                for state in chunk {
+                    serde_json::to_writer(
+                        &mut out,
+                        &FsrsStateResponse {
+                            card_id: state.card_id.0,
+                            stability: state.stability,
+                            difficulty: state.difficulty,
+                            last_review: state.last_review.map(|t| t.0),
+                            retrievability: state.retrievability,
+                        },
+                    )?;
+                    out.push(b'\n');
+                }
+                Ok(Ok::<_, Infallible>(out))
+            })
+            .collect::<Result<Vec<_>>>()
+    })
+    .await?;
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(stream::iter(chunks)),
+    )
+        .into_response())
+}
+
+// Handler for restoring memory states from newline-delimited JSON, as produced
+// by the export above
+async fn import_fsrs_states(
+    State(server): State<Arc<SimpleServer>>,
+    body: Bytes,
+) -> ApiResult<Json<ImportFsrsStatesResponse>> {
+    let states = body
+        .split(|&b| b == b'\n')
+        .enumerate()
+        .filter(|(_, line)| !line.trim_ascii().is_empty())
+        .map(|(idx, line)| {
+            let state: ImportedFsrsState = serde_json::from_slice(line)
+                .or_invalid(format!("invalid state on line {}", idx + 1))?;
+            Ok(FsrsCardState {
+                card_id: CardId(state.card_id),
+                stability: state.stability,
+                difficulty: state.difficulty,
+                last_review: state.last_review.map(TimestampSecs),
+                retrievability: 0.0,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let total = states.len();
+    with_col(&server, |col| {
+        let updated = col.import_fsrs_states(states)?.output;
+        Ok(Json(ImportFsrsStatesResponse {
+            updated,
+            skipped: total - updated,
+        }))
+    })
+    .await
+}
+
+// Handler for the time spent studying on each recent day
+async fn time_series(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<TimeSeriesQuery>,
+) -> ApiResult<Json<Vec<TimeSeriesDay>>> {
+    with_col(&server, |col| {
+        require!(
+            (1..=MAX_TIME_SERIES_DAYS).contains(&query.days),
+            "days must be between 1 and {MAX_TIME_SERIES_DAYS}"
+        );
+        let series = col.get_review_time_series(query.deck_id.map(DeckId), query.days)?;
+        Ok(Json(
+            series
+                .into_iter()
+                .map(|day| TimeSeriesDay {
+                    date: day.date.format("%Y-%m-%d").to_string(),
+                    total_seconds: day.total_seconds,
+                    review_seconds: day.review_seconds,
+                    learn_seconds: day.learn_seconds,
+                    relearn_seconds: day.relearn_seconds,
+                })
+                .collect(),
+        ))
+    })
+    .await
+}
+
+// Handler for listing cards with corrupt scheduling values
+async fn card_anomalies(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<CardAnomaliesResponse>> {
+    with_col(&server, |col| {
+        Ok(Json(CardAnomaliesResponse {
+            anomalies: col.card_anomalies()?.into_iter().map(Into::into).collect(),
+        }))
+    })
+    .await
+}
+
+// Handler for fixing the corrupt scheduling values of some or all cards. New
+// positions go to the end of the new queue, review dues to today, zero
+// intervals to 1 day, and eases below 130% to 130%.
+async fn fix_card_anomalies(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<FixCardAnomaliesRequest>, JsonRejection>,
+) -> ApiResult<Json<FixCardAnomaliesResponse>> {
+    let payload = payload?;
+    let cids: Option<Vec<CardId>> = payload
+        .card_ids
+        .as_ref()
+        .map(|ids| ids.iter().copied().map(CardId).collect());
+    with_col(&server, |col| {
+        let fixed = col.fix_card_anomalies(cids.as_deref())?.output;
+        Ok(Json(FixCardAnomaliesResponse {
+            fixed: fixed.into_iter().map(Into::into).collect(),
+        }))
+    })
+    .await
+}
+
+// Handler for listing cards whose ease is unusually high or low
+async fn ease_outliers(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<EaseOutliersQuery>,
+) -> ApiResult<Json<Vec<EaseOutlierResponse>>> {
+    with_col(&server, |col| {
+        require!(
+            query.std_devs.is_finite() && query.std_devs >= 0.0,
+            "invalid stdDevs"
+        );
+        let outliers = col.get_ease_factor_outliers(query.deck_id.map(DeckId), query.std_devs)?;
+        Ok(Json(
+            outliers
+                .into_iter()
+                .map(|(cid, ease)| EaseOutlierResponse {
+                    card_id: cid.0,
+                    ease,
+                })
+                .collect(),
+        ))
+    })
+    .await
+}
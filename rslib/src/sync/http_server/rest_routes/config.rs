@@ -0,0 +1,311 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::sync::Arc;
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::Path;
+use axum::extract::State;
+use axum::routing::get;
+use axum::routing::put;
+use axum::Json;
+use axum::Router;
+use serde::Deserialize;
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use super::with_col;
+use crate::config::BoolKey;
+use crate::config::I32ConfigKey;
+use crate::config::StringKey;
+use crate::config::Weekday;
+use crate::prelude::*;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+/// A config key with a known type, as declared by the key enums.
+#[derive(Clone, Copy)]
+enum KnownKey {
+    Bool(BoolKey),
+    String(StringKey),
+    Int(I32ConfigKey),
+}
+
+impl KnownKey {
+    /// The keys that can be read and changed through the API, as chosen by
+    /// each enum's `is_rest_preference()`. Only preferences that affect how
+    /// things are shown or entered are included; keys that change
+    /// scheduling, such as the scheduler version, FSRS or the card state
+    /// customizer, have to be changed through their own endpoints, if any.
+    /// The locale and first weekday are left to /config/preferences, which
+    /// checks their values.
+    fn all() -> impl Iterator<Item = KnownKey> {
+        let bools = BoolKey::iter()
+            .filter(|key| key.is_rest_preference())
+            .map(KnownKey::Bool);
+        let strings = StringKey::iter()
+            .filter(|key| key.is_rest_preference())
+            .map(KnownKey::String);
+        let ints = I32ConfigKey::iter()
+            .filter(|key| key.is_rest_preference())
+            .map(KnownKey::Int);
+        bools.chain(strings).chain(ints)
+    }
+
+    fn find(name: &str) -> Option<KnownKey> {
+        Self::all().find(|key| key.name() == name)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            KnownKey::Bool(key) => key.into(),
+            KnownKey::String(key) => key.into(),
+            KnownKey::Int(key) => key.into(),
+        }
+    }
+
+    fn kind(self) -> &'static str {
+        match self {
+            KnownKey::Bool(_) => "bool",
+            KnownKey::String(_) => "string",
+            KnownKey::Int(_) => "int",
+        }
+    }
+
+    fn value(self, col: &Collection) -> serde_json::Value {
+        match self {
+            KnownKey::Bool(key) => col.get_config_bool(key).into(),
+            KnownKey::String(key) => col.get_config_string(key).into(),
+            KnownKey::Int(key) => col.get_config_i32(key).into(),
+        }
+    }
+
+    fn set_value(self, col: &mut Collection, value: &serde_json::Value) -> Result<()> {
+        match self {
+            KnownKey::Bool(key) => {
+                let value = value.as_bool().or_invalid("expected a boolean")?;
+                col.set_config_bool(key, value, true)?;
+            }
+            KnownKey::String(key) => {
+                let value = value.as_str().or_invalid("expected a string")?;
+                col.set_config_string(key, value, true)?;
+            }
+            KnownKey::Int(key) => {
+                let value = value
+                    .as_i64()
+                    .and_then(|v| i32::try_from(v).ok())
+                    .or_invalid("expected an integer")?;
+                col.transact(Op::UpdateConfig, |col| {
+                    col.set_config_i32_inner(key, value)?;
+                    Ok(())
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn response(self, col: &Collection) -> KnownConfigEntry {
+        KnownConfigEntry {
+            key: self.name(),
+            kind: self.kind(),
+            value: self.value(col),
+        }
+    }
+}
+
+// Payloads for the API
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownConfigEntry {
+    key: &'static str,
+    /// One of "bool", "string" or "int".
+    #[serde(rename = "type")]
+    kind: &'static str,
+    value: serde_json::Value,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownConfigResponse {
+    keys: Vec<KnownConfigEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetKnownConfigRequest {
+    value: serde_json::Value,
+}
+
+/// The days weeks can start on, as offered by the desktop's calendar graph.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekdayPayload {
+    Sunday,
+    Monday,
+    Friday,
+    Saturday,
+}
+
+impl From<Weekday> for WeekdayPayload {
+    fn from(weekday: Weekday) -> Self {
+        match weekday {
+            Weekday::Sunday => WeekdayPayload::Sunday,
+            Weekday::Monday => WeekdayPayload::Monday,
+            Weekday::Friday => WeekdayPayload::Friday,
+            Weekday::Saturday => WeekdayPayload::Saturday,
+        }
+    }
+}
+
+impl From<WeekdayPayload> for Weekday {
+    fn from(weekday: WeekdayPayload) -> Self {
+        match weekday {
+            WeekdayPayload::Sunday => Weekday::Sunday,
+            WeekdayPayload::Monday => Weekday::Monday,
+            WeekdayPayload::Friday => Weekday::Friday,
+            WeekdayPayload::Saturday => Weekday::Saturday,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreferencesResponse {
+    /// The day weeks start on when stats are grouped by week, shared with
+    /// the desktop's calendar graph.
+    first_weekday: WeekdayPayload,
+    /// A language tag such as "en-US" for clients to format numbers and
+    /// dates with, eg in exported CSV, or empty if unset. The server does
+    /// not use it.
+    locale: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdatePreferencesRequest {
+    first_weekday: Option<WeekdayPayload>,
+    /// An empty string clears it.
+    locale: Option<String>,
+}
+
+/// The longest locale accepted, as for a language tag with a few subtags.
+const MAX_LOCALE_LENGTH: usize = 35;
+
+fn preferences(col: &Collection) -> PreferencesResponse {
+    PreferencesResponse {
+        first_weekday: col.get_first_day_of_week().into(),
+        locale: col.get_config_string(StringKey::Locale),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewPositionPayload {
+    /// The position the next added new card will receive.
+    position: u32,
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new()
+        .route("/config/known", get(list_known_config))
+        .route("/config/known/{key}", put(set_known_config))
+        .route(
+            "/config/preferences",
+            get(get_preferences).put(update_preferences),
+        )
+        .route(
+            "/scheduler/new-position",
+            get(get_new_position).put(set_new_position),
+        )
+}
+
+// Handler for listing the known config keys and their current values
+async fn list_known_config(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<KnownConfigResponse>> {
+    with_col(&server, |col| {
+        Ok(Json(KnownConfigResponse {
+            keys: KnownKey::all().map(|key| key.response(col)).collect(),
+        }))
+    })
+    .await
+}
+
+// Handler for updating a known config key, checking the value's type
+async fn set_known_config(
+    State(server): State<Arc<SimpleServer>>,
+    Path(key): Path<String>,
+    payload: Result<Json<SetKnownConfigRequest>, JsonRejection>,
+) -> ApiResult<Json<KnownConfigEntry>> {
+    let payload = payload?;
+    with_col(&server, |col| {
+        let key = KnownKey::find(&key).or_not_found(key)?;
+        key.set_value(col, &payload.value)?;
+        Ok(Json(key.response(col)))
+    })
+    .await
+}
+
+// Handler for reading the collection's week and locale preferences
+async fn get_preferences(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<PreferencesResponse>> {
+    with_col(&server, |col| Ok(Json(preferences(col)))).await
+}
+
+// Handler for changing the collection's week and locale preferences. Omitted
+// fields are left unchanged.
+async fn update_preferences(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<UpdatePreferencesRequest>, JsonRejection>,
+) -> ApiResult<Json<PreferencesResponse>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        if let Some(locale) = &payload.locale {
+            require!(
+                locale.len() <= MAX_LOCALE_LENGTH
+                    && locale
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-'),
+                "locale must be a language tag such as en-US"
+            );
+        }
+        col.transact(Op::UpdateConfig, |col| {
+            if let Some(weekday) = payload.first_weekday {
+                col.set_first_day_of_week(weekday.into())?;
+            }
+            if let Some(locale) = &payload.locale {
+                col.set_config_string_inner(StringKey::Locale, locale)?;
+            }
+            Ok(())
+        })?;
+        Ok(Json(preferences(col)))
+    })
+    .await
+}
+
+// Handler for reading the new card position counter
+async fn get_new_position(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<NewPositionPayload>> {
+    with_col(&server, |col| {
+        Ok(Json(NewPositionPayload {
+            position: col.get_next_card_position(),
+        }))
+    })
+    .await
+}
+
+// Handler for moving the new card position counter
+async fn set_new_position(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<NewPositionPayload>, JsonRejection>,
+) -> ApiResult<Json<NewPositionPayload>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        col.set_next_card_position_undoable(payload.position)?;
+        Ok(Json(payload))
+    })
+    .await
+}
@@ -0,0 +1,192 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::sync::Arc;
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::Path;
+use axum::extract::State;
+use axum::routing::delete;
+use axum::routing::get;
+use axum::routing::post;
+use axum::Json;
+use axum::Router;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::with_col;
+use crate::deckconfig::IntervalSettingsChange;
+use crate::prelude::*;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+// Payloads for the API
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeckSummaryResponse {
+    deck_id: i64,
+    name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeckConfigDecksResponse {
+    config_id: i64,
+    config_name: String,
+    decks: Vec<DeckSummaryResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveDeckConfigResponse {
+    /// The decks that used the preset, which now use the default preset.
+    reassigned_decks: Vec<DeckSummaryResponse>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntervalSettingsRequest {
+    /// In days, from 1 to 36500.
+    maximum_review_interval: Option<u32>,
+    /// From 0.5 to 2.
+    interval_multiplier: Option<f32>,
+    /// From 0.7 to 0.99. Only used when FSRS is enabled.
+    desired_retention: Option<f32>,
+}
+
+impl From<IntervalSettingsRequest> for IntervalSettingsChange {
+    fn from(req: IntervalSettingsRequest) -> Self {
+        IntervalSettingsChange {
+            maximum_review_interval: req.maximum_review_interval,
+            interval_multiplier: req.interval_multiplier,
+            desired_retention: req.desired_retention,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeckConfigIntervalsResponse {
+    config_id: i64,
+    config_name: String,
+    maximum_review_interval: u32,
+    interval_multiplier: f32,
+    desired_retention: f32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntervalChangePreviewResponse {
+    /// Review cards of the decks using the preset. Each is compared by the
+    /// interval it would get if answered Good today.
+    review_cards: u32,
+    shorter: u32,
+    longer: u32,
+    unchanged: u32,
+    /// The total number of days lost by the shorter intervals.
+    days_shorter: u64,
+    /// The total number of days gained by the longer intervals.
+    days_longer: u64,
+}
+
+fn deck_summary(deck: &Deck) -> DeckSummaryResponse {
+    DeckSummaryResponse {
+        deck_id: deck.id.0,
+        name: deck.human_name(),
+    }
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new()
+        .route(
+            "/deck-configs/{config_id}",
+            delete(remove_deck_config).put(update_deck_config_intervals),
+        )
+        .route("/deck-configs/{config_id}/decks", get(decks_using_config))
+        .route(
+            "/deck-configs/{config_id}/preview-change",
+            post(preview_deck_config_change),
+        )
+}
+
+// Handler for listing the decks that use a preset
+async fn decks_using_config(
+    State(server): State<Arc<SimpleServer>>,
+    Path(config_id): Path<i64>,
+) -> ApiResult<Json<DeckConfigDecksResponse>> {
+    with_col(&server, |col| {
+        let dcid = DeckConfigId(config_id);
+        let config = col.storage.get_deck_config(dcid)?.or_not_found(dcid)?;
+        let decks = col.decks_using_deck_config(dcid)?;
+        Ok(Json(DeckConfigDecksResponse {
+            config_id: config.id.0,
+            config_name: config.name,
+            decks: decks.iter().map(deck_summary).collect(),
+        }))
+    })
+    .await
+}
+
+// Handler for removing a preset
+async fn remove_deck_config(
+    State(server): State<Arc<SimpleServer>>,
+    Path(config_id): Path<i64>,
+) -> ApiResult<Json<RemoveDeckConfigResponse>> {
+    with_col(&server, |col| {
+        let reassigned = col.remove_deck_config(DeckConfigId(config_id))?.output;
+        let reassigned_decks = reassigned
+            .into_iter()
+            .map(|did| {
+                let deck = col.get_deck(did)?.or_not_found(did)?;
+                Ok(deck_summary(&deck))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Json(RemoveDeckConfigResponse { reassigned_decks }))
+    })
+    .await
+}
+
+// Handler for changing the interval settings of a preset
+async fn update_deck_config_intervals(
+    State(server): State<Arc<SimpleServer>>,
+    Path(config_id): Path<i64>,
+    payload: Result<Json<IntervalSettingsRequest>, JsonRejection>,
+) -> ApiResult<Json<DeckConfigIntervalsResponse>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        let config = col
+            .update_deck_config_intervals(DeckConfigId(config_id), payload.into())?
+            .output;
+        Ok(Json(DeckConfigIntervalsResponse {
+            config_id: config.id.0,
+            config_name: config.name,
+            maximum_review_interval: config.inner.maximum_review_interval,
+            interval_multiplier: config.inner.interval_multiplier,
+            desired_retention: config.inner.desired_retention,
+        }))
+    })
+    .await
+}
+
+// Handler for previewing how changed interval settings would affect a preset's cards
+async fn preview_deck_config_change(
+    State(server): State<Arc<SimpleServer>>,
+    Path(config_id): Path<i64>,
+    payload: Result<Json<IntervalSettingsRequest>, JsonRejection>,
+) -> ApiResult<Json<IntervalChangePreviewResponse>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        let preview =
+            col.preview_deck_config_intervals_change(DeckConfigId(config_id), payload.into())?;
+        Ok(Json(IntervalChangePreviewResponse {
+            review_cards: preview.review_cards,
+            shorter: preview.shorter,
+            longer: preview.longer,
+            unchanged: preview.unchanged,
+            days_shorter: preview.days_shorter,
+            days_longer: preview.days_longer,
+        }))
+    })
+    .await
+}
@@ -0,0 +1,369 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::sync::Arc;
+
+use anki_io::create_dir_all;
+use anki_io::filename_is_safe;
+use anki_proto::decks::DeckTreeNode;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::routing::post;
+use axum::routing::put;
+use axum::Json;
+use axum::Router;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::collection::suspend_leeches_response;
+use super::collection::SuspendLeechesResponse;
+use super::with_col;
+use super::with_user;
+use super::ChangedIdsResponse;
+use crate::decks::FilteredSearchOrder;
+use crate::prelude::*;
+use crate::sync::error::HttpError;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+use crate::text::html_to_text_line;
+
+// Payloads for the API
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateFilteredDeckRequest {
+    name: String,
+    search: String,
+    #[serde(default = "default_filtered_limit")]
+    limit: u32,
+    /// A FilteredSearchOrder value; defaults to random.
+    #[serde(default = "default_filtered_order")]
+    order: u32,
+}
+
+fn default_filtered_limit() -> u32 {
+    100
+}
+
+fn default_filtered_order() -> u32 {
+    FilteredSearchOrder::Random as u32
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateFilteredDeckResponse {
+    deck_id: i64,
+    changes: ChangedIdsResponse,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyToNewCollectionRequest {
+    /// The file name of the new collection, which is created in the user's
+    /// folder in the server's copy folder.
+    name: String,
+    #[serde(default)]
+    include_scheduling: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyToNewCollectionResponse {
+    name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeckCountsResponse {
+    deck_id: i64,
+    /// The full name, including parents.
+    name: String,
+    /// 1 for top-level decks.
+    level: u32,
+    /// The cards that can be studied today, including subdecks and after
+    /// limits are applied.
+    new: u32,
+    learning: u32,
+    review: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListDecksResponse {
+    /// In deck list order, each deck followed by its subdecks.
+    decks: Vec<DeckCountsResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeckResponse {
+    deck_id: i64,
+    name: String,
+    filtered: bool,
+    /// The preset of a normal deck. Decks whose preset is missing use the
+    /// default one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    config_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    config_name: Option<String>,
+    /// True if no new cards are introduced from the deck.
+    new_paused: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetNewPausedRequest {
+    paused: bool,
+    /// Also pause or resume the deck's subdecks.
+    #[serde(default)]
+    include_subdecks: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetNewPausedResponse {
+    decks_changed: usize,
+    changes: ChangedIdsResponse,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HighLapseCardsQuery {
+    #[serde(default = "default_min_lapses")]
+    min_lapses: u32,
+}
+
+fn default_min_lapses() -> u32 {
+    5
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HighLapseCardResponse {
+    card_id: i64,
+    lapses: u32,
+    /// The note's first field as plain text.
+    note_field: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilteredCardDueResponse {
+    card_id: i64,
+    /// The due value the card will have when returned to its home deck.
+    original_due: i32,
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new()
+        .route("/decks", get(list_decks))
+        .route("/decks/filtered", post(create_filtered_deck))
+        .route("/decks/{deck_id}", get(get_deck))
+        .route("/decks/{deck_id}/copy-to-new", post(copy_to_new_collection))
+        .route(
+            "/decks/{deck_id}/filtered-card-dues",
+            get(filtered_card_dues),
+        )
+        .route("/decks/{deck_id}/high-lapse-cards", get(high_lapse_cards))
+        .route("/decks/{deck_id}/new-paused", put(set_new_paused))
+        .route("/decks/{deck_id}/suspend-leeches", post(suspend_leeches))
+}
+
+// Handler for listing decks with their study counts
+async fn list_decks(State(server): State<Arc<SimpleServer>>) -> ApiResult<Json<ListDecksResponse>> {
+    with_col(&server, |col| {
+        let tree = col.deck_tree(Some(TimestampSecs::now()))?;
+        let mut decks = vec![];
+        add_deck_counts(&mut decks, &tree.children, "");
+        Ok(Json(ListDecksResponse { decks }))
+    })
+    .await
+}
+
+fn add_deck_counts(decks: &mut Vec<DeckCountsResponse>, nodes: &[DeckTreeNode], parent: &str) {
+    for node in nodes {
+        let name = if parent.is_empty() {
+            node.name.clone()
+        } else {
+            format!("{parent}::{}", node.name)
+        };
+        decks.push(DeckCountsResponse {
+            deck_id: node.deck_id,
+            name: name.clone(),
+            level: node.level,
+            new: node.new_count,
+            learning: node.learn_count,
+            review: node.review_count,
+        });
+        add_deck_counts(decks, &node.children, &name);
+    }
+}
+
+// Handler for getting a deck and the name of its preset
+async fn get_deck(
+    State(server): State<Arc<SimpleServer>>,
+    Path(deck_id): Path<i64>,
+) -> ApiResult<Json<DeckResponse>> {
+    with_col(&server, |col| deck_response(col, DeckId(deck_id)).map(Json)).await
+}
+
+/// Also used when a deck is expanded into another response.
+pub(super) fn deck_response(col: &mut Collection, did: DeckId) -> Result<DeckResponse> {
+    let deck = col.get_deck(did)?.or_not_found(did)?;
+    let config = match deck.config_id() {
+        Some(dcid) => col.get_deck_config(dcid, true)?,
+        None => None,
+    };
+    Ok(DeckResponse {
+        deck_id: deck.id.0,
+        name: deck.human_name(),
+        filtered: deck.is_filtered(),
+        config_id: config.as_ref().map(|config| config.id.0),
+        config_name: config.map(|config| config.name),
+        new_paused: deck
+            .normal()
+            .map(|normal| normal.new_paused)
+            .unwrap_or_default(),
+    })
+}
+
+// Handler for pausing or resuming the introduction of new cards from a deck.
+// Unlike setting the preset's new card limit to 0, this does not affect other
+// decks sharing the preset.
+async fn set_new_paused(
+    State(server): State<Arc<SimpleServer>>,
+    Path(deck_id): Path<i64>,
+    payload: Result<Json<SetNewPausedRequest>, JsonRejection>,
+) -> ApiResult<Json<SetNewPausedResponse>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        let out =
+            col.set_deck_new_paused(DeckId(deck_id), payload.paused, payload.include_subdecks)?;
+        Ok(Json(SetNewPausedResponse {
+            decks_changed: out.output,
+            changes: out.changes.ids.into(),
+        }))
+    })
+    .await
+}
+
+// Handler for listing the cards of a deck that have lapsed often
+async fn high_lapse_cards(
+    State(server): State<Arc<SimpleServer>>,
+    Path(deck_id): Path<i64>,
+    Query(query): Query<HighLapseCardsQuery>,
+) -> ApiResult<Json<Vec<HighLapseCardResponse>>> {
+    with_col(&server, |col| {
+        col.get_lapse_counts_by_card(DeckId(deck_id), query.min_lapses)?
+            .into_iter()
+            .map(|(cid, lapses)| {
+                let card = col.storage.get_card(cid)?.or_not_found(cid)?;
+                let note = col
+                    .storage
+                    .get_note(card.note_id)?
+                    .or_not_found(card.note_id)?;
+                Ok(HighLapseCardResponse {
+                    card_id: cid.0,
+                    lapses,
+                    note_field: html_to_text_line(&note.fields()[0], true).into(),
+                })
+            })
+            .collect::<Result<_>>()
+            .map(Json)
+    })
+    .await
+}
+
+// Handler for the original due values of the cards in a filtered deck
+async fn filtered_card_dues(
+    State(server): State<Arc<SimpleServer>>,
+    Path(deck_id): Path<i64>,
+) -> ApiResult<Json<Vec<FilteredCardDueResponse>>> {
+    with_col(&server, |col| {
+        Ok(Json(
+            col.get_card_ids_by_original_due(DeckId(deck_id))?
+                .into_iter()
+                .map(|(cid, original_due)| FilteredCardDueResponse {
+                    card_id: cid.0,
+                    original_due,
+                })
+                .collect(),
+        ))
+    })
+    .await
+}
+
+// Handler for creating a filtered deck
+async fn create_filtered_deck(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<CreateFilteredDeckRequest>, JsonRejection>,
+) -> ApiResult<Json<CreateFilteredDeckResponse>> {
+    let payload = payload?;
+    with_col(&server, |col| {
+        let out = col.create_filtered_deck_from_search(
+            &payload.name,
+            &payload.search,
+            payload.limit,
+            payload.order,
+        )?;
+        Ok(Json(CreateFilteredDeckResponse {
+            deck_id: out.output.0,
+            changes: out.changes.ids.into(),
+        }))
+    })
+    .await
+}
+
+// Handler for copying a deck into a new collection
+async fn copy_to_new_collection(
+    State(server): State<Arc<SimpleServer>>,
+    Path(deck_id): Path<i64>,
+    payload: Result<Json<CopyToNewCollectionRequest>, JsonRejection>,
+) -> ApiResult<Json<CopyToNewCollectionResponse>> {
+    let payload = payload?;
+    let Some(copy_base) = &server.copy_base else {
+        return Err(HttpError::new_without_source(
+            StatusCode::FORBIDDEN,
+            "copying decks is disabled; set SYNC_COPY_BASE to enable it",
+        )
+        .into());
+    };
+    if !filename_is_safe(&payload.name) {
+        return Err(HttpError::new_without_source(
+            StatusCode::BAD_REQUEST,
+            "name must be a file name, without folders",
+        )
+        .into());
+    }
+    with_user(&server, |user| {
+        // each user has their own folder, so they can't overwrite or read
+        // each other's copies
+        let folder = copy_base.join(&user.name);
+        create_dir_all(&folder).map_err(AnkiError::from)?;
+        user.ensure_col_open()?;
+        user.col.as_mut().unwrap().copy_deck_to_new_collection(
+            DeckId(deck_id),
+            &folder.join(&payload.name),
+            payload.include_scheduling,
+        )?;
+        Ok(Json(CopyToNewCollectionResponse {
+            name: payload.0.name,
+        }))
+    })
+    .await
+}
+
+// Handler for suspending the leeches in a deck and its children
+async fn suspend_leeches(
+    State(server): State<Arc<SimpleServer>>,
+    Path(deck_id): Path<i64>,
+) -> ApiResult<Json<SuspendLeechesResponse>> {
+    with_col(&server, |col| {
+        suspend_leeches_response(col, Some(DeckId(deck_id))).map(Json)
+    })
+    .await
+}
@@ -0,0 +1,79 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Support for an `expand` query parameter, which inlines objects related to
+//! the requested one so clients need fewer round trips.
+
+use serde::Serialize;
+use serde::Serializer;
+use serde_json::Map;
+use serde_json::Value;
+
+use crate::prelude::*;
+
+/// The related objects requested by a comma-separated `expand` parameter, eg
+/// `expand=note,deck`.
+pub(super) struct Expand(Vec<&'static str>);
+
+impl Expand {
+    /// Fails if a name is not one of `supported`.
+    pub(super) fn parse(param: Option<&str>, supported: &[&'static str]) -> Result<Self> {
+        let mut names = vec![];
+        for name in param.unwrap_or_default().split(',').map(str::trim) {
+            if name.is_empty() {
+                continue;
+            }
+            let Some(&name) = supported.iter().find(|&&supported| supported == name) else {
+                invalid_input!("cannot expand '{name}'; supported: {}", supported.join(","));
+            };
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        Ok(Expand(names))
+    }
+
+    /// Add the requested objects to `inner`, using `lookup` to build each one.
+    /// The objects should be plain responses that are not expanded
+    /// themselves, so that expansion is only ever one level deep.
+    pub(super) fn apply<T>(
+        &self,
+        inner: T,
+        mut lookup: impl FnMut(&'static str) -> Result<Value>,
+    ) -> Result<Expanded<T>> {
+        let mut related = Map::new();
+        for &name in &self.0 {
+            related.insert(name.to_string(), lookup(name)?);
+        }
+        Ok(Expanded { inner, related })
+    }
+}
+
+/// A response with the requested related objects as extra top-level keys.
+pub(super) struct Expanded<T> {
+    inner: T,
+    related: Map<String, Value>,
+}
+
+impl<T: Serialize> Serialize for Expanded<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Flattened<'a, T> {
+            #[serde(flatten)]
+            inner: &'a T,
+            #[serde(flatten)]
+            related: &'a Map<String, Value>,
+        }
+
+        // unexpanded responses are serialized exactly as before
+        if self.related.is_empty() {
+            self.inner.serialize(serializer)
+        } else {
+            Flattened {
+                inner: &self.inner,
+                related: &self.related,
+            }
+            .serialize(serializer)
+        }
+    }
+}
@@ -0,0 +1,384 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use anki_io::copy_file;
+use anki_io::new_tempfile;
+use axum::body::Body;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::State;
+use axum::http::header;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::get;
+use axum::routing::post;
+use axum::Json;
+use axum::Router;
+use serde::Deserialize;
+use serde::Serialize;
+use tempfile::NamedTempFile;
+use tokio::task::spawn_blocking;
+use tokio_util::io::ReaderStream;
+
+use super::with_col;
+use super::with_user;
+use crate::card::CardQueue;
+use crate::card::CardType;
+use crate::card::FsrsMemoryState;
+use crate::import_export::package::export_collection_file;
+use crate::import_export::scheduling::SchedulingRecord;
+use crate::import_export::ExportProgress;
+use crate::prelude::*;
+use crate::progress::Progress;
+use crate::progress::ProgressState;
+use crate::progress::ThrottlingProgressHandler;
+use crate::revlog::RevlogEntry;
+use crate::revlog::RevlogReviewKind;
+use crate::storage::SchemaVersion;
+use crate::sync::error::HttpError;
+use crate::sync::error::OrHttpErr;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+// Payloads for the API
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportColpkgRequest {
+    #[serde(default = "default_include_media")]
+    include_media: bool,
+    /// Write the legacy format that Anki 2.1.49 and earlier can import.
+    #[serde(default)]
+    legacy: bool,
+}
+
+fn default_include_media() -> bool {
+    true
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgressResponse {
+    active: bool,
+    /// One of "file", "gathering", "notes", "cards" or "media".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stage: Option<&'static str>,
+    /// The number of items processed in the current stage.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuccessResponse {
+    success: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportSchedulingRequest {
+    /// Defaults to the whole collection.
+    #[serde(default)]
+    search: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryStatePayload {
+    stability: f32,
+    difficulty: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevlogEntryPayload {
+    /// Review time in milliseconds.
+    id: i64,
+    /// 1-4, or 0 for manual rescheduling.
+    button: u8,
+    interval: i32,
+    last_interval: i32,
+    ease_factor: u32,
+    taken_millis: u32,
+    /// 0=learning, 1=review, 2=relearning, 3=filtered, 4=manual,
+    /// 5=rescheduled.
+    kind: u8,
+}
+
+/// Also accepted by POST /import/scheduling.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchedulingRecordPayload {
+    guid: String,
+    card_ord: u16,
+    /// 0=new, 1=learning, 2=review, 3=relearning.
+    #[serde(rename = "type")]
+    ctype: u8,
+    /// As in the cards table.
+    queue: i8,
+    /// For review cards, days from today; otherwise the new position or
+    /// learning step timestamp.
+    due: i32,
+    interval: u32,
+    ease: u16,
+    reps: u32,
+    lapses: u32,
+    #[serde(default)]
+    remaining_steps: u32,
+    memory_state: Option<MemoryStatePayload>,
+    /// Unix timestamp in seconds.
+    last_review: Option<i64>,
+    #[serde(default)]
+    revlog: Vec<RevlogEntryPayload>,
+}
+
+impl From<SchedulingRecord> for SchedulingRecordPayload {
+    fn from(record: SchedulingRecord) -> Self {
+        SchedulingRecordPayload {
+            guid: record.guid,
+            card_ord: record.card_ord,
+            ctype: record.ctype as u8,
+            queue: record.queue as i8,
+            due: record.due,
+            interval: record.interval,
+            ease: record.ease_factor,
+            reps: record.reps,
+            lapses: record.lapses,
+            remaining_steps: record.remaining_steps,
+            memory_state: record.memory_state.map(|state| MemoryStatePayload {
+                stability: state.stability,
+                difficulty: state.difficulty,
+            }),
+            last_review: record.last_review_time.map(|t| t.0),
+            revlog: record
+                .revlog
+                .into_iter()
+                .map(|entry| RevlogEntryPayload {
+                    id: entry.id.0,
+                    button: entry.button_chosen,
+                    interval: entry.interval,
+                    last_interval: entry.last_interval,
+                    ease_factor: entry.ease_factor,
+                    taken_millis: entry.taken_millis,
+                    kind: entry.review_kind as u8,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<SchedulingRecordPayload> for SchedulingRecord {
+    type Error = AnkiError;
+
+    fn try_from(payload: SchedulingRecordPayload) -> Result<Self> {
+        Ok(SchedulingRecord {
+            ctype: CardType::try_from(payload.ctype).or_invalid("invalid card type")?,
+            queue: CardQueue::try_from(payload.queue).or_invalid("invalid card queue")?,
+            guid: payload.guid,
+            card_ord: payload.card_ord,
+            due: payload.due,
+            interval: payload.interval,
+            ease_factor: payload.ease,
+            reps: payload.reps,
+            lapses: payload.lapses,
+            remaining_steps: payload.remaining_steps,
+            memory_state: payload.memory_state.map(|state| FsrsMemoryState {
+                stability: state.stability,
+                difficulty: state.difficulty,
+            }),
+            last_review_time: payload.last_review.map(TimestampSecs),
+            revlog: payload
+                .revlog
+                .into_iter()
+                .map(|entry| {
+                    Ok(RevlogEntry {
+                        id: RevlogId(entry.id),
+                        button_chosen: entry.button,
+                        interval: entry.interval,
+                        last_interval: entry.last_interval,
+                        ease_factor: entry.ease_factor,
+                        taken_millis: entry.taken_millis,
+                        review_kind: RevlogReviewKind::try_from(entry.kind)
+                            .or_invalid("invalid review kind")?,
+                        ..Default::default()
+                    })
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportSchedulingResponse {
+    records: Vec<SchedulingRecordPayload>,
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new()
+        .route("/export/colpkg", post(export_colpkg))
+        .route("/export/colpkg/progress", get(export_progress))
+        .route("/export/colpkg/abort", post(abort_export))
+        .route("/export/scheduling", post(export_scheduling))
+}
+
+// Handler for exporting the whole collection as a .colpkg
+async fn export_colpkg(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<ExportColpkgRequest>, JsonRejection>,
+) -> ApiResult<Response> {
+    let payload = payload?;
+    // Close the collection and copy it while holding the lock, so the package
+    // reflects a consistent state. Packaging it and the media happens later.
+    let (snapshot, media_folder, tr, running) = with_user(&server, |user| {
+        if user.export_running.swap(true, Ordering::AcqRel) {
+            return Err(HttpError::new_without_source(
+                StatusCode::CONFLICT,
+                "an export is already in progress",
+            )
+            .into());
+        }
+        // cleared again if the rest of this fails
+        let running = RunningExport {
+            running: user.export_running.clone(),
+            progress: user.export_progress.clone(),
+        };
+        user.abort_stateful_sync_if_active();
+        user.ensure_col_open()?;
+        let col = user.col.take().unwrap();
+        let tr = col.tr.clone();
+        let snapshot = snapshot_collection(col, payload.legacy)?;
+        let media_folder = payload
+            .include_media
+            .then(|| user.media.media_folder.clone());
+        Ok((snapshot, media_folder, tr, running))
+    })
+    .await?;
+
+    let legacy = payload.legacy;
+    let package = spawn_blocking(move || {
+        let mut progress = ThrottlingProgressHandler::new(running.progress.clone());
+        let result = new_tempfile().map_err(Into::into).and_then(|package| {
+            export_collection_file(
+                package.path(),
+                snapshot.path(),
+                media_folder,
+                legacy,
+                &tr,
+                &mut progress,
+            )
+            .map(|()| package)
+        });
+        // only now, so that the export counts as running even if the request
+        // was dropped before it finished
+        drop(running);
+        result
+    })
+    .await
+    .or_internal_err("export join")??;
+
+    // the open handle keeps the data readable after the temp file is removed
+    let file = tokio::fs::File::from_std(package.reopen().or_internal_err("reopen export")?);
+    drop(package);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/octet-stream"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"collection.colpkg\"",
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    )
+        .into_response())
+}
+
+/// Marks a user's collection export as running until dropped, when its
+/// progress is also cleared.
+struct RunningExport {
+    running: Arc<AtomicBool>,
+    progress: Arc<Mutex<ProgressState>>,
+}
+
+impl Drop for RunningExport {
+    fn drop(&mut self) {
+        // the flag is cleared with the progress locked, so an abort can't be
+        // left pending for the next export
+        let mut progress = self.progress.lock().unwrap_or_else(PoisonError::into_inner);
+        progress.reset();
+        self.running.store(false, Ordering::Release);
+    }
+}
+
+/// Close the collection with the schema version the package requires, and copy
+/// the closed file.
+fn snapshot_collection(col: Collection, legacy: bool) -> Result<NamedTempFile> {
+    let col_path = col.col_path.clone();
+    col.close(Some(if legacy {
+        SchemaVersion::V11
+    } else {
+        SchemaVersion::V18
+    }))?;
+    let snapshot = new_tempfile()?;
+    copy_file(&col_path, snapshot.path())?;
+    Ok(snapshot)
+}
+
+// Handler for checking on a running collection export
+async fn export_progress(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<ExportProgressResponse>> {
+    with_user(&server, |user| {
+        let progress = match user.export_progress.lock().unwrap().last_progress {
+            Some(Progress::Export(progress)) => Some(progress),
+            _ => None,
+        };
+        let (stage, count) = match progress {
+            None => (None, None),
+            Some(ExportProgress::File) => (Some("file"), None),
+            Some(ExportProgress::Gathering) => (Some("gathering"), None),
+            Some(ExportProgress::Notes(n)) => (Some("notes"), Some(n)),
+            Some(ExportProgress::Cards(n)) => (Some("cards"), Some(n)),
+            Some(ExportProgress::Media(n)) => (Some("media"), Some(n)),
+        };
+        Ok(Json(ExportProgressResponse {
+            active: user.export_running.load(Ordering::Acquire),
+            stage,
+            count,
+        }))
+    })
+    .await
+}
+
+// Handler for cancelling a running collection export
+async fn abort_export(State(server): State<Arc<SimpleServer>>) -> ApiResult<Json<SuccessResponse>> {
+    with_user(&server, |user| {
+        let mut progress = user.export_progress.lock().unwrap();
+        progress.want_abort = user.export_running.load(Ordering::Acquire);
+        Ok(Json(SuccessResponse {
+            success: progress.want_abort,
+        }))
+    })
+    .await
+}
+
+// Handler for exporting the scheduling of the cards matching a search
+async fn export_scheduling(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<ExportSchedulingRequest>, JsonRejection>,
+) -> ApiResult<Json<ExportSchedulingResponse>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        let records = col.export_scheduling(&payload.search)?;
+        Ok(Json(ExportSchedulingResponse {
+            records: records.into_iter().map(Into::into).collect(),
+        }))
+    })
+    .await
+}
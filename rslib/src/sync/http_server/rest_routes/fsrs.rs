@@ -0,0 +1,119 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::sync::Arc;
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::State;
+use axum::routing::post;
+use axum::Json;
+use axum::Router;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::with_col;
+use crate::prelude::*;
+use crate::scheduler::fsrs::params::ignore_revlogs_before_ms_from_config;
+use crate::search::SearchNode;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+const DEFAULT_HOLDOUT_FRACTION: f32 = 0.2;
+const DEFAULT_CALIBRATION_BUCKETS: usize = 10;
+
+// Payloads for the API
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalibrationRequest {
+    /// The parameters to evaluate. Defaults to the current parameters of the
+    /// deck's preset, or of the default preset.
+    params: Option<Vec<f32>>,
+    /// Limits the evaluated history to the deck and its children.
+    deck_id: Option<i64>,
+    /// Overrides the deck when provided.
+    search: Option<String>,
+    holdout_fraction: Option<f32>,
+    buckets: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalibrationResponse {
+    params: Vec<f32>,
+    holdout_reviews: usize,
+    training_reviews: usize,
+    log_loss: f32,
+    /// Mean predicted retention minus observed retention; positive when the
+    /// parameters are overconfident.
+    bias: f32,
+    buckets: Vec<CalibrationBucketResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalibrationBucketResponse {
+    lower: f32,
+    upper: f32,
+    count: u32,
+    mean_predicted: Option<f32>,
+    observed: Option<f32>,
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new().route("/fsrs/calibration", post(calibration))
+}
+
+// Handler for comparing predicted and observed retention on the most recent
+// reviews. Runs to completion within the request.
+async fn calibration(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<CalibrationRequest>, JsonRejection>,
+) -> ApiResult<Json<CalibrationResponse>> {
+    let payload = payload?.0;
+    with_col(&server, |col| {
+        let deck = match payload.deck_id.map(DeckId) {
+            Some(did) => Some(col.get_deck(did)?.or_not_found(did)?),
+            None => None,
+        };
+        let config_id = deck
+            .as_ref()
+            .and_then(|deck| deck.config_id())
+            .unwrap_or(DeckConfigId(1));
+        let config = col.get_deck_config(config_id, true)?.unwrap();
+        let params = payload
+            .params
+            .unwrap_or_else(|| config.fsrs_params().clone());
+        let search = match (&payload.search, &deck) {
+            (Some(search), _) => search.as_str().try_into_search()?,
+            (None, Some(deck)) => SearchNode::from_deck_id(deck.id, true).into(),
+            (None, None) => SearchNode::WholeCollection.into(),
+        };
+        let calibration = col.compute_fsrs_calibration(
+            &params,
+            search,
+            ignore_revlogs_before_ms_from_config(&config)?,
+            payload.holdout_fraction.unwrap_or(DEFAULT_HOLDOUT_FRACTION),
+            payload.buckets.unwrap_or(DEFAULT_CALIBRATION_BUCKETS),
+        )?;
+        Ok(Json(CalibrationResponse {
+            params,
+            holdout_reviews: calibration.holdout_reviews,
+            training_reviews: calibration.training_reviews,
+            log_loss: calibration.log_loss,
+            bias: calibration.bias,
+            buckets: calibration
+                .buckets
+                .into_iter()
+                .map(|bucket| CalibrationBucketResponse {
+                    lower: bucket.lower,
+                    upper: bucket.upper,
+                    count: bucket.count,
+                    mean_predicted: bucket.mean_predicted,
+                    observed: bucket.observed,
+                })
+                .collect(),
+        }))
+    })
+    .await
+}
@@ -0,0 +1,656 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::io::Write;
+use std::mem;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anki_io::new_tempfile;
+use anki_io::paths_in_dir;
+use anki_io::read_file;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::routing::get;
+use axum::routing::post;
+use axum::Json;
+use axum::Router;
+use reqwest::dns::Addrs;
+use reqwest::dns::Name;
+use reqwest::dns::Resolve;
+use reqwest::dns::Resolving;
+use reqwest::redirect::Policy;
+use reqwest::Client;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+use tempfile::tempdir;
+use tempfile::NamedTempFile;
+use tokio::net::lookup_host;
+
+use super::export::SchedulingRecordPayload;
+use super::with_col;
+use super::with_user;
+use crate::error::NetworkError;
+use crate::error::NetworkErrorKind;
+use crate::import_export::package::ImportAnkiPackageOptions;
+use crate::import_export::scheduling::SchedulingConflictPolicy;
+use crate::import_export::scheduling::SchedulingRecord;
+use crate::import_export::ImportDecision;
+use crate::import_export::NoteLog;
+use crate::import_export::NoteOutcome;
+use crate::import_export::NoteOutcomeKind;
+use crate::media::files::sha1_of_data;
+use crate::prelude::*;
+use crate::services::ImportExportService;
+use crate::sync::http_server::media_manager::ServerMediaManager;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+use crate::sync::media::zip::UploadedChange;
+use crate::sync::media::zip::UploadedChangeKind;
+use crate::text::normalize_to_nfc;
+
+/// Downloads larger than this are aborted.
+const MAX_DOWNLOAD_BYTES: u64 = 512 * 1024 * 1024;
+const MAX_REDIRECTS: usize = 5;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+/// The maximum number of per-note outcomes included in a response.
+const MAX_REPORTED_NOTES: usize = 1000;
+/// The number of import logs kept for GET /import/logs/{jobId}.
+const MAX_RETAINED_IMPORT_LOGS: usize = 10;
+
+// Payloads for the API
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportApkgUrlRequest {
+    url: String,
+    /// Hex-encoded SHA-256 of the package. If provided, the download is
+    /// rejected when it does not match.
+    sha256: Option<String>,
+    /// Permit plain http:// URLs (and redirects to them).
+    #[serde(default)]
+    allow_http: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportLogQuery {
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_log_limit")]
+    limit: usize,
+}
+
+fn default_log_limit() -> usize {
+    MAX_REPORTED_NOTES
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteOutcomeResponse {
+    guid: String,
+    note_id: Option<i64>,
+    first_field: String,
+    /// The log list the note was recorded in, eg "updated" or "missingDeck".
+    kind: &'static str,
+    /// One of "addedNew", "tookRemote", "keptLocal" or "skipped".
+    decision: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportLogResponse {
+    /// Can be passed to GET /import/logs/{jobId} to page through the notes.
+    job_id: String,
+    found_notes: u32,
+    new: usize,
+    updated: usize,
+    duplicate: usize,
+    conflicting: usize,
+    first_field_match: usize,
+    missing_notetype: usize,
+    missing_deck: usize,
+    empty_first_field: usize,
+    notes: Vec<NoteOutcomeResponse>,
+    /// True if there are more notes after the ones included.
+    truncated: bool,
+}
+
+impl ImportLogResponse {
+    fn new(job_id: &str, log: &NoteLog, offset: usize, limit: usize) -> Self {
+        let outcomes = NoteOutcome::from_log(log);
+        let limit = limit.min(MAX_REPORTED_NOTES);
+        ImportLogResponse {
+            job_id: job_id.to_string(),
+            found_notes: log.found_notes,
+            new: log.new.len(),
+            updated: log.updated.len(),
+            duplicate: log.duplicate.len(),
+            conflicting: log.conflicting.len(),
+            first_field_match: log.first_field_match.len(),
+            missing_notetype: log.missing_notetype.len(),
+            missing_deck: log.missing_deck.len(),
+            empty_first_field: log.empty_first_field.len(),
+            truncated: outcomes.len() > offset.saturating_add(limit),
+            notes: outcomes
+                .into_iter()
+                .skip(offset)
+                .take(limit)
+                .map(note_outcome_response)
+                .collect(),
+        }
+    }
+}
+
+fn note_outcome_response(outcome: NoteOutcome) -> NoteOutcomeResponse {
+    NoteOutcomeResponse {
+        guid: outcome.guid,
+        note_id: outcome.note_id.map(|nid| nid.0),
+        first_field: outcome.preview,
+        kind: match outcome.kind {
+            NoteOutcomeKind::New => "new",
+            NoteOutcomeKind::Updated => "updated",
+            NoteOutcomeKind::Duplicate => "duplicate",
+            NoteOutcomeKind::Conflicting => "conflicting",
+            NoteOutcomeKind::FirstFieldMatch => "firstFieldMatch",
+            NoteOutcomeKind::MissingNotetype => "missingNotetype",
+            NoteOutcomeKind::MissingDeck => "missingDeck",
+            NoteOutcomeKind::EmptyFirstField => "emptyFirstField",
+        },
+        decision: match outcome.decision {
+            ImportDecision::AddedNew => "addedNew",
+            ImportDecision::TookRemote => "tookRemote",
+            ImportDecision::KeptLocal => "keptLocal",
+            ImportDecision::Skipped => "skipped",
+        },
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictPolicyPayload {
+    KeepNewer,
+    PreferImported,
+    MergeRevlog,
+}
+
+impl From<ConflictPolicyPayload> for SchedulingConflictPolicy {
+    fn from(policy: ConflictPolicyPayload) -> Self {
+        match policy {
+            ConflictPolicyPayload::KeepNewer => SchedulingConflictPolicy::KeepNewer,
+            ConflictPolicyPayload::PreferImported => SchedulingConflictPolicy::PreferImported,
+            ConflictPolicyPayload::MergeRevlog => SchedulingConflictPolicy::MergeRevlog,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSchedulingRequest {
+    /// As produced by POST /export/scheduling.
+    records: Vec<SchedulingRecordPayload>,
+    policy: ConflictPolicyPayload,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingCardResponse {
+    guid: String,
+    card_ord: u16,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSchedulingResponse {
+    updated: usize,
+    unchanged: usize,
+    revlog_added: usize,
+    /// Records without a matching card, which were not created.
+    missing: Vec<MissingCardResponse>,
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new()
+        .route("/import/apkg-url", post(import_apkg_url))
+        .route("/import/logs/{job_id}", get(get_import_log))
+        .route("/import/scheduling", post(import_scheduling))
+}
+
+// Handler for downloading an .apkg and importing it into the collection
+async fn import_apkg_url(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<ImportApkgUrlRequest>, JsonRejection>,
+) -> ApiResult<Json<ImportLogResponse>> {
+    let payload = payload?;
+    let file = download_package(&payload, server.import_allow_private_hosts).await?;
+    with_user(&server, |user| {
+        user.ensure_col_open()?;
+        let col = user.col.as_mut().unwrap();
+        let options = col.get_import_anki_package_presets()?;
+        let log = import_with_server_media(col, &mut user.media, &file, options)?;
+        let job_id = format!("{:016x}", rand::random::<u64>());
+        let response = ImportLogResponse::new(&job_id, &log, 0, MAX_REPORTED_NOTES);
+        retain_import_log(&mut user.import_logs, job_id, log);
+        Ok(Json(response))
+    })
+    .await
+}
+
+// Handler for paging through the notes of a previous import
+async fn get_import_log(
+    State(server): State<Arc<SimpleServer>>,
+    Path(job_id): Path<String>,
+    Query(query): Query<ImportLogQuery>,
+) -> ApiResult<Json<ImportLogResponse>> {
+    with_user(&server, |user| {
+        let (_, log) = user
+            .import_logs
+            .iter()
+            .find(|(id, _)| *id == job_id)
+            .or_not_found(&job_id)?;
+        Ok(Json(ImportLogResponse::new(
+            &job_id,
+            log,
+            query.offset,
+            query.limit,
+        )))
+    })
+    .await
+}
+
+// Handler for applying scheduling exported from another collection
+async fn import_scheduling(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<ImportSchedulingRequest>, JsonRejection>,
+) -> ApiResult<Json<ImportSchedulingResponse>> {
+    let Json(payload) = payload?;
+    let records = payload
+        .records
+        .into_iter()
+        .map(SchedulingRecord::try_from)
+        .collect::<Result<Vec<_>>>()?;
+    with_col(&server, |col| {
+        let output = col
+            .import_scheduling(records, payload.policy.into())?
+            .output;
+        Ok(Json(ImportSchedulingResponse {
+            updated: output.updated,
+            unchanged: output.unchanged,
+            revlog_added: output.revlog_added,
+            missing: output
+                .missing
+                .into_iter()
+                .map(|(guid, card_ord)| MissingCardResponse { guid, card_ord })
+                .collect(),
+        }))
+    })
+    .await
+}
+
+fn retain_import_log(logs: &mut Vec<(String, NoteLog)>, job_id: String, log: NoteLog) {
+    logs.push((job_id, log));
+    let excess = logs.len().saturating_sub(MAX_RETAINED_IMPORT_LOGS);
+    logs.drain(..excess);
+}
+
+/// Server collections have no media folder, as their media is kept in a
+/// separate store for syncing. Any media in the package is unpacked into a
+/// scratch folder, and then added to the store so that clients download it on
+/// their next sync. The import checks for clashes against the store, so files
+/// whose name is already used by different media are renamed, and the notes
+/// refer to the new name.
+fn import_with_server_media(
+    col: &mut Collection,
+    media: &mut ServerMediaManager,
+    file: &NamedTempFile,
+    options: ImportAnkiPackageOptions,
+) -> Result<NoteLog> {
+    let existing_media = media.db.nonempty_checksums()?;
+    let scratch = tempdir()?;
+    let scratch_media = scratch.path().join("media");
+    let media_folder = mem::replace(&mut col.media_folder, scratch_media.clone());
+    let media_db = mem::replace(&mut col.media_db, scratch.path().join("media.db"));
+    let result = col.import_apkg_with_existing_media(file.path(), options, existing_media);
+    col.media_folder = media_folder;
+    col.media_db = media_db;
+    let log = result?.output;
+    add_imported_media(media, scratch_media)?;
+    Ok(log)
+}
+
+/// Add the files in `folder` to the user's media store. Files whose name is
+/// already used by different media are left out rather than replacing the
+/// existing file, though the import should have renamed them.
+fn add_imported_media(media: &mut ServerMediaManager, folder: PathBuf) -> Result<()> {
+    if !folder.exists() {
+        return Ok(());
+    }
+    let mut changes = vec![];
+    for path in paths_in_dir(&folder)? {
+        let Some(filename) = path.file_name() else {
+            continue;
+        };
+        let data = read_file(&path)?;
+        if data.is_empty() {
+            continue;
+        }
+        let nfc_filename = normalize_to_nfc(filename).to_string();
+        let sha1 = sha1_of_data(&data).to_vec();
+        if let Some(existing) = media.db.get_nonempty_entry(&nfc_filename)? {
+            if existing.sha1 != sha1 {
+                continue;
+            }
+        }
+        changes.push(UploadedChange {
+            nfc_filename,
+            kind: UploadedChangeKind::AddOrReplace {
+                nonempty_data: data,
+                sha1,
+            },
+        });
+    }
+    media.apply_changes(changes)?;
+    Ok(())
+}
+
+/// Stream the package at the requested URL into a temporary file, enforcing
+/// the size limit and verifying the checksum if one was provided. Unless
+/// `allow_private_hosts` is set, the URL and any redirects must lead to public
+/// addresses, so the server can't be used to reach services on its own
+/// network.
+async fn download_package(
+    request: &ImportApkgUrlRequest,
+    allow_private_hosts: bool,
+) -> Result<NamedTempFile> {
+    let url: reqwest::Url = request.url.parse().or_invalid("invalid url")?;
+    match url.scheme() {
+        "https" => (),
+        "http" => require!(request.allow_http, "only https urls are allowed"),
+        _ => invalid_input!("unsupported url scheme"),
+    }
+    require!(
+        allow_private_hosts || host_may_be_public(&url),
+        "url must not point to a private address"
+    );
+    let expected_hash = request
+        .sha256
+        .as_deref()
+        .map(|hash| hex::decode(hash.trim()).or_invalid("invalid sha256"))
+        .transpose()?;
+
+    let mut client = Client::builder()
+        .https_only(!request.allow_http)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT);
+    client = if allow_private_hosts {
+        client.redirect(Policy::limited(MAX_REDIRECTS))
+    } else {
+        client
+            .redirect(Policy::custom(|attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else if !host_may_be_public(attempt.url()) {
+                    attempt.error("redirect to a private address")
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .dns_resolver(Arc::new(PublicAddressResolver))
+    };
+    let client = client.build()?;
+    let mut resp = client.get(url).send().await?;
+    if !resp.status().is_success() {
+        return Err(AnkiError::NetworkError {
+            source: NetworkError {
+                info: format!("download failed with status {}", resp.status()),
+                kind: NetworkErrorKind::Other,
+            },
+        });
+    }
+    if resp.content_length().unwrap_or_default() > MAX_DOWNLOAD_BYTES {
+        invalid_input!("package is too large");
+    }
+
+    let mut file = new_tempfile()?;
+    let mut hasher = Sha256::new();
+    let mut size = 0;
+    while let Some(chunk) = resp.chunk().await? {
+        size += chunk.len() as u64;
+        require!(size <= MAX_DOWNLOAD_BYTES, "package is too large");
+        hasher.update(&chunk);
+        file.write_all(&chunk)?;
+    }
+    file.flush()?;
+
+    if let Some(expected) = expected_hash {
+        require!(
+            hasher.finalize().as_slice() == expected.as_slice(),
+            "sha256 does not match the downloaded package"
+        );
+    }
+    Ok(file)
+}
+
+/// False if the URL's host is an IP address that is not public. Hostnames are
+/// checked when they are resolved, by [PublicAddressResolver].
+fn host_may_be_public(url: &reqwest::Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    // IPv6 hosts are bracketed
+    match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) => is_public_address(ip),
+        Err(_) => true,
+    }
+}
+
+fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            // 100.64.0.0/10 is used for carrier-grade NAT
+            let shared = first == 100 && (second & 0xc0) == 64;
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || shared)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_address(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                // fc00::/7 is unique local, and fe80::/10 link-local
+                let unique_local = (first & 0xfe00) == 0xfc00;
+                let link_local = (first & 0xffc0) == 0xfe80;
+                !(ip.is_loopback() || ip.is_unspecified() || unique_local || link_local)
+            }
+        },
+    }
+}
+
+/// Resolves hostnames as usual, discarding any addresses that are not
+/// public. As connections are only made to the addresses it returns, a
+/// hostname can't be made to point to a private address after it has been
+/// checked.
+struct PublicAddressResolver;
+
+impl Resolve for PublicAddressResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_address(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err("host has no public address".into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wiremock::matchers::method;
+    use wiremock::matchers::path;
+    use wiremock::Mock;
+    use wiremock::MockServer;
+    use wiremock::ResponseTemplate;
+
+    use super::*;
+    use crate::import_export::package::ExportAnkiPackageOptions;
+    use crate::media::files::add_hash_suffix_to_file_stem;
+    use crate::search::SearchNode;
+    use crate::tests::NoteAdder;
+
+    fn request(url: &str, sha256: Option<String>, allow_http: bool) -> ImportApkgUrlRequest {
+        ImportApkgUrlRequest {
+            url: url.into(),
+            sha256,
+            allow_http,
+        }
+    }
+
+    #[tokio::test]
+    async fn import_apkg_from_url() -> Result<()> {
+        let mut col = Collection::new();
+        NoteAdder::basic(&mut col).add(&mut col);
+        let apkg = NamedTempFile::new()?;
+        col.export_apkg(
+            apkg.path(),
+            ExportAnkiPackageOptions {
+                with_scheduling: false,
+                with_deck_configs: false,
+                with_media: false,
+                legacy: false,
+            },
+            SearchNode::WholeCollection,
+            None,
+        )?;
+        let data = read_file(apkg.path())?;
+        let hash = hex::encode(Sha256::digest(&data));
+
+        let mock = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/deck.apkg"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(data))
+            .mount(&mock)
+            .await;
+        let url = format!("{}/deck.apkg", mock.uri());
+
+        // plain http is refused unless explicitly allowed
+        assert!(matches!(
+            download_package(&request(&url, None, false), true).await,
+            Err(AnkiError::InvalidInput { .. })
+        ));
+        // as is the mock server's loopback address
+        assert!(matches!(
+            download_package(&request(&url, None, true), false).await,
+            Err(AnkiError::InvalidInput { .. })
+        ));
+        assert!(matches!(
+            download_package(&request(&url, Some("00".repeat(32)), true), true).await,
+            Err(AnkiError::InvalidInput { .. })
+        ));
+        let missing = format!("{}/missing.apkg", mock.uri());
+        assert!(matches!(
+            download_package(&request(&missing, None, true), true).await,
+            Err(AnkiError::NetworkError { .. })
+        ));
+
+        let file = download_package(&request(&url, Some(hash), true), true).await?;
+        let mut col = Collection::new();
+        let user_folder = tempdir()?;
+        let mut media = ServerMediaManager::new(user_folder.path()).unwrap();
+        let options = col.get_import_anki_package_presets()?;
+        let log = import_with_server_media(&mut col, &mut media, &file, options)?;
+        assert_eq!(log.new.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn imported_media_does_not_replace_existing_files() -> Result<()> {
+        let (mut source, _source_dir) = crate::tests::open_fs_test_collection("source");
+        source.add_media(&[("clash.jpg", b"imported"), ("new.jpg", b"new")]);
+        NoteAdder::basic(&mut source)
+            .fields(&["<img src=clash.jpg>", "<img src=new.jpg>"])
+            .add(&mut source);
+        let apkg = NamedTempFile::new()?;
+        source.export_apkg(
+            apkg.path(),
+            ExportAnkiPackageOptions {
+                with_scheduling: false,
+                with_deck_configs: false,
+                with_media: true,
+                legacy: false,
+            },
+            SearchNode::WholeCollection,
+            None,
+        )?;
+
+        let user_folder = tempdir()?;
+        let mut media = ServerMediaManager::new(user_folder.path()).unwrap();
+        let existing = UploadedChange {
+            nfc_filename: "clash.jpg".into(),
+            kind: UploadedChangeKind::AddOrReplace {
+                nonempty_data: b"existing".to_vec(),
+                sha1: sha1_of_data(b"existing").to_vec(),
+            },
+        };
+        media.apply_changes(vec![existing]).unwrap();
+
+        let mut col = Collection::new();
+        let options = col.get_import_anki_package_presets()?;
+        import_with_server_media(&mut col, &mut media, &apkg, options)?;
+        assert_eq!(
+            read_file(media.media_folder.join("clash.jpg"))?,
+            b"existing"
+        );
+        // the clashing file is renamed, as a desktop import would do
+        let renamed = add_hash_suffix_to_file_stem("clash.jpg", &sha1_of_data(b"imported"));
+        assert_eq!(read_file(media.media_folder.join(&renamed))?, b"imported");
+        assert_eq!(read_file(media.media_folder.join("new.jpg"))?, b"new");
+        let note = col.storage.get_all_notes().pop().unwrap();
+        assert_eq!(
+            note.fields(),
+            &[format!("<img src={renamed}>"), "<img src=new.jpg>".into()]
+        );
+        // the files are sent to clients on their next sync
+        assert!(media.db.get_nonempty_entry(&renamed)?.is_some());
+        assert!(media.db.get_nonempty_entry("new.jpg")?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn private_hosts() {
+        let public = |url: &str| host_may_be_public(&url.parse().unwrap());
+        assert!(public("https://example.com/deck.apkg"));
+        assert!(public("https://93.184.216.34/deck.apkg"));
+        assert!(public("https://[2606:2800:220:1::]/deck.apkg"));
+        for url in [
+            "http://127.0.0.1/",
+            "http://10.1.2.3/",
+            "http://192.168.0.1/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://100.64.0.1/",
+            "http://0.0.0.0/",
+            "http://[::1]/",
+            "http://[fd00::1]/",
+            "http://[fe80::1]/",
+            "http://[::ffff:127.0.0.1]/",
+        ] {
+            assert!(!public(url), "{url}");
+        }
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::routing::get;
+use axum::Json;
+use axum::Router;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::require_admin;
+use crate::prelude::*;
+use crate::sync::http_server::lockout::LoginFailures;
+use crate::sync::http_server::lockout::LoginKey;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+// Payloads for the API
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClearLockoutsQuery {
+    /// Clear only this user's failures.
+    user: Option<String>,
+    /// Clear only failures from this IP.
+    ip: Option<IpAddr>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginFailuresResponse {
+    /// The user whose logins failed from the IP, or null for failures
+    /// counted against the IP itself, whichever user they were for.
+    user: Option<String>,
+    ip: String,
+    failures: u32,
+    /// In seconds.
+    last_failure: i64,
+    /// In seconds; null if logins are currently allowed.
+    locked_until: Option<i64>,
+}
+
+impl From<LoginFailures> for LoginFailuresResponse {
+    fn from(failures: LoginFailures) -> Self {
+        let (user, ip) = match failures.key {
+            LoginKey::UserAtIp { user, ip } => (Some(user), ip),
+            LoginKey::Ip { ip } => (None, ip),
+        };
+        LoginFailuresResponse {
+            user,
+            ip: ip.to_string(),
+            failures: failures.failures,
+            last_failure: failures.last_failure.0,
+            locked_until: (failures.locked_until > TimestampSecs::now())
+                .then_some(failures.locked_until.0),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockoutsResponse {
+    /// Users and IPs with recent failed logins, most recent first.
+    lockouts: Vec<LoginFailuresResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClearLockoutsResponse {
+    cleared: usize,
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new().route("/lockouts", get(list_lockouts).delete(clear_lockouts))
+}
+
+// Handler for listing the users and IPs with failed logins. Requires the
+// admin token, as it reveals who is logging in from where.
+async fn list_lockouts(
+    State(server): State<Arc<SimpleServer>>,
+    headers: HeaderMap,
+) -> ApiResult<Json<LockoutsResponse>> {
+    require_admin(&server, &headers)?;
+    Ok(Json(LockoutsResponse {
+        lockouts: server
+            .login_throttle
+            .failures()
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+    }))
+}
+
+// Handler for clearing the failed logins of a user, an IP, a user at an IP,
+// or of everything. Requires the admin token.
+async fn clear_lockouts(
+    State(server): State<Arc<SimpleServer>>,
+    headers: HeaderMap,
+    Query(query): Query<ClearLockoutsQuery>,
+) -> ApiResult<Json<ClearLockoutsResponse>> {
+    require_admin(&server, &headers)?;
+    let cleared = server.login_throttle.clear(query.user.as_deref(), query.ip);
+    Ok(Json(ClearLockoutsResponse { cleared }))
+}
@@ -1,16 +1,387 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use std::collections::HashSet;
+use std::path::Path;
 use std::sync::Arc;
+use std::sync::MutexGuard;
+use std::sync::TryLockError;
+use std::time::Duration;
+use std::time::Instant;
 
+use axum::http::header;
+use axum::http::HeaderMap;
+use axum::http::StatusCode;
 use axum::Router;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+use tokio::time::sleep;
 
+use crate::collection::Collection;
+use crate::error::AnkiError;
+use crate::notetype::RenderCardOutput;
+use crate::ops::ChangedIds;
+use crate::sync::error::HttpError;
+use crate::sync::http_server::media_manager::ServerMediaManager;
+use crate::sync::http_server::media_manager::TrashedMedia;
+use crate::sync::http_server::user::User;
+use crate::sync::http_server::ApiError;
+use crate::sync::http_server::ApiResult;
 use crate::sync::http_server::SimpleServer;
+use crate::sync::http_server::SimpleServerInner;
+use crate::text::prefix_media_refs;
 
 // Declare feature modules
 mod cards;
+mod collection;
+mod config;
+mod deck_configs;
+mod decks;
+mod expand;
+mod export;
+mod fsrs;
+mod import;
+mod lockouts;
+mod notes;
+mod notetypes;
+mod read_only;
+mod search;
+mod stats;
+pub(crate) mod study;
+mod sync;
+mod tags;
+mod templates;
+mod tests;
+pub(crate) mod undo_group;
+mod utils;
 
 /// The master router for all REST API endpoints.
 pub fn routes() -> Router<Arc<SimpleServer>> {
-    Router::new().merge(cards::routes())
+    Router::new()
+        .merge(cards::routes())
+        .merge(collection::routes())
+        .merge(config::routes())
+        .merge(deck_configs::routes())
+        .merge(decks::routes())
+        .merge(export::routes())
+        .merge(fsrs::routes())
+        .merge(import::routes())
+        .merge(lockouts::routes())
+        .merge(notes::routes())
+        .merge(notetypes::routes())
+        .merge(read_only::routes())
+        .merge(search::routes())
+        .merge(stats::routes())
+        .merge(study::routes())
+        .merge(sync::routes())
+        .merge(tags::routes())
+        .merge(templates::routes())
+        .merge(utils::routes())
+}
+
+/// How long a request waits for another request's collection operation to
+/// finish before giving up with 503.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Lock the server state, waiting at most `timeout` for it to become free.
+/// The wait yields to other tasks instead of blocking the runtime's thread.
+async fn lock_state(
+    server: &SimpleServer,
+    timeout: Duration,
+) -> ApiResult<MutexGuard<SimpleServerInner>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match server.state.try_lock() {
+            Ok(state) => return Ok(state),
+            // a handler panicked while holding the lock, and may have left
+            // the state inconsistent
+            Err(TryLockError::Poisoned(_)) => {
+                return Err(HttpError::new_without_source(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "the server state is unusable after an earlier failure",
+                )
+                .into())
+            }
+            Err(TryLockError::WouldBlock) => (),
+        }
+        if Instant::now() >= deadline {
+            return Err(ApiError::Busy {
+                retry_after: timeout,
+            });
+        }
+        sleep(LOCK_POLL_INTERVAL).await;
+    }
+}
+
+/// Run `op` with the user whose collection the REST API operates on.
+pub(super) async fn with_user<F, T>(server: &SimpleServer, op: F) -> ApiResult<T>
+where
+    F: FnOnce(&mut User) -> ApiResult<T>,
+{
+    let mut state = lock_state(server, LOCK_TIMEOUT).await?;
+    // For now, we'll just grab the first user.
+    let user = state.users.values_mut().next().unwrap();
+    op(user)
+}
+
+async fn with_col<F, T>(server: &SimpleServer, op: F) -> ApiResult<T>
+where
+    F: FnOnce(&mut Collection) -> Result<T, AnkiError>,
+{
+    with_user(server, |user| {
+        user.ensure_col_open()?;
+        let col = user.col.as_mut().unwrap();
+        op(col).map_err(Into::into)
+    })
+    .await
+}
+
+/// Refuse the request unless it carries the server's admin token, as
+/// `Authorization: Bearer <token>`. Without a configured token, every request
+/// is refused.
+fn require_admin(server: &SimpleServer, headers: &HeaderMap) -> ApiResult<()> {
+    let Some(expected) = &server.admin_token else {
+        return Err(HttpError::new_without_source(
+            StatusCode::FORBIDDEN,
+            "admin endpoints are disabled; set SYNC_ADMIN_TOKEN to enable them",
+        )
+        .into());
+    };
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    // the digests are compared in full, so the time taken doesn't reveal how
+    // much of the token was right
+    let matches = given.is_some_and(|given| {
+        Sha256::digest(given)
+            .iter()
+            .zip(Sha256::digest(expected))
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    });
+    if matches {
+        Ok(())
+    } else {
+        Err(HttpError::new_without_source(
+            StatusCode::UNAUTHORIZED,
+            "a valid admin token is required",
+        )
+        .into())
+    }
+}
+
+/// Confirms a delete above the server's threshold, as an alternative to
+/// sending `"confirm": true` in the request body.
+pub const CONFIRM_DESTRUCTIVE_HEADER: &str = "x-confirm-destructive";
+
+/// Like [with_col], for deletions. `count` reports how many cards `op` would
+/// delete; if that is more than the server's threshold, the request fails with
+/// 428 and `op` is not run, unless the client confirmed it with `confirm` or
+/// [CONFIRM_DESTRUCTIVE_HEADER]. `op` also gets the user's media, so that
+/// it can remove files the deletion leaves unused.
+async fn with_col_confirming_delete<C, F, T>(
+    server: &SimpleServer,
+    headers: &HeaderMap,
+    confirm: bool,
+    count: C,
+    op: F,
+) -> ApiResult<T>
+where
+    C: FnOnce(&mut Collection) -> Result<usize, AnkiError>,
+    F: FnOnce(&mut Collection, &mut ServerMediaManager) -> Result<T, AnkiError>,
+{
+    let confirmed = confirm
+        || headers
+            .get(CONFIRM_DESTRUCTIVE_HEADER)
+            .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"yes"));
+    with_user(server, |user| {
+        user.ensure_col_open()?;
+        let col = user.col.as_mut().unwrap();
+        if !confirmed {
+            let count = count(col)?;
+            if count > server.delete_confirm_threshold {
+                return Err(ApiError::ConfirmationRequired {
+                    count,
+                    threshold: server.delete_confirm_threshold,
+                });
+            }
+        }
+        op(col, &mut user.media).map_err(Into::into)
+    })
+    .await
+}
+
+/// A mutation response that also reports whether the change modified the
+/// schema, which commits the collection to a one-way full sync next time.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct SchemaChangeResponse<T> {
+    #[serde(flatten)]
+    inner: T,
+    schema_modified: bool,
+}
+
+/// The cards, notes and decks a mutation touched, so that open views can
+/// refresh just those rows. Each list is capped, with `truncated` set if ids
+/// were left out.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ChangedIdsResponse {
+    card_ids: Vec<i64>,
+    note_ids: Vec<i64>,
+    deck_ids: Vec<i64>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
+}
+
+impl From<ChangedIds> for ChangedIdsResponse {
+    fn from(ids: ChangedIds) -> Self {
+        ChangedIdsResponse {
+            card_ids: ids.cards.into_iter().map(|id| id.0).collect(),
+            note_ids: ids.notes.into_iter().map(|id| id.0).collect(),
+            deck_ids: ids.decks.into_iter().map(|id| id.0).collect(),
+            truncated: ids.truncated,
+        }
+    }
+}
+
+/// The most missing ids a delete response lists.
+const MAX_REPORTED_MISSING_IDS: usize = 1000;
+
+/// How a delete went for the ids it was sent. A retried delete finds the ids
+/// removed by the earlier attempt already missing, so clients can check
+/// `deleted + alreadyMissing == requested` instead of expecting `deleted` to
+/// match.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct DeleteCountsResponse {
+    /// Distinct ids sent; repeated ids are counted once.
+    requested: usize,
+    deleted: usize,
+    /// Ids that did not exist, whether removed earlier or never present.
+    already_missing: usize,
+    /// The first [MAX_REPORTED_MISSING_IDS] of the missing ids, in the order
+    /// sent.
+    missing_ids: Vec<i64>,
+}
+
+/// The media files that a delete with `cleanupMedia` moved to the trash. The
+/// delete has already happened by the time files are moved, so files that
+/// couldn't be are listed instead of failing the request. They stay in the
+/// media folder, where a media check will report them as unused.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct TrashedMediaResponse {
+    trashed_media: Vec<String>,
+    media_not_trashed: Vec<String>,
+}
+
+impl From<TrashedMedia> for TrashedMediaResponse {
+    fn from(media: TrashedMedia) -> Self {
+        TrashedMediaResponse {
+            trashed_media: media.trashed,
+            media_not_trashed: media.failed,
+        }
+    }
+}
+
+impl DeleteCountsResponse {
+    fn new(requested: &[i64], is_missing: impl Fn(i64) -> bool) -> Self {
+        let mut seen = HashSet::new();
+        let unique: Vec<i64> = requested
+            .iter()
+            .copied()
+            .filter(|id| seen.insert(*id))
+            .collect();
+        let missing: Vec<i64> = unique
+            .iter()
+            .copied()
+            .filter(|id| is_missing(*id))
+            .collect();
+        DeleteCountsResponse {
+            requested: unique.len(),
+            deleted: unique.len() - missing.len(),
+            already_missing: missing.len(),
+            missing_ids: missing.into_iter().take(MAX_REPORTED_MISSING_IDS).collect(),
+        }
+    }
+}
+
+/// Like [with_col], for operations that may modify the schema. Clients can
+/// send `allowSchemaChange: false` to have such an operation fail with 409
+/// instead of forcing a full sync.
+async fn with_col_guarding_schema<F, T>(
+    server: &SimpleServer,
+    allow_schema_change: Option<bool>,
+    op: F,
+) -> ApiResult<SchemaChangeResponse<T>>
+where
+    F: FnOnce(&mut Collection) -> Result<T, AnkiError>,
+{
+    with_col(server, |col| {
+        let (inner, schema_modified) =
+            col.guard_schema_change(allow_schema_change.unwrap_or(true), op)?;
+        Ok(SchemaChangeResponse {
+            inner,
+            schema_modified,
+        })
+    })
+    .await
+}
+
+/// Rendered card HTML refers to media by filename. Clients that don't serve
+/// the media folder at the document root can pass `mediaUrlPrefix` to have the
+/// references rewritten into URLs.
+fn rendered_html(html: &str, media_url_prefix: Option<&str>) -> String {
+    match media_url_prefix {
+        Some(prefix) => prefix_media_refs(html, prefix).into_owned(),
+        None => html.to_string(),
+    }
+}
+
+/// Something that may be wrong with a rendered card.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub(super) enum RenderWarningResponse {
+    /// The front rendered empty, so an explanation is shown instead.
+    EmptyQuestion,
+    /// A file the card refers to is not in the media folder.
+    MissingMedia { filename: String },
+}
+
+/// Warnings about `rendered`. Missing media is only looked for when
+/// `media_folder` is provided, as each reference costs a filesystem lookup.
+fn render_warnings(
+    rendered: &RenderCardOutput,
+    media_folder: Option<&Path>,
+) -> Vec<RenderWarningResponse> {
+    let mut warnings = vec![];
+    if rendered.is_empty {
+        warnings.push(RenderWarningResponse::EmptyQuestion);
+    }
+    if let Some(media_folder) = media_folder {
+        warnings.extend(
+            rendered
+                .missing_media(media_folder)
+                .into_iter()
+                .map(|filename| RenderWarningResponse::MissingMedia { filename }),
+        );
+    }
+    warnings
+}
+
+/// Like [with_col], also passing the user's media folder.
+async fn with_col_and_media_folder<F, T>(server: &SimpleServer, op: F) -> ApiResult<T>
+where
+    F: FnOnce(&mut Collection, &Path) -> Result<T, AnkiError>,
+{
+    with_user(server, |user| {
+        user.ensure_col_open()?;
+        let col = user.col.as_mut().unwrap();
+        op(col, &user.media.media_folder).map_err(Into::into)
+    })
+    .await
 }
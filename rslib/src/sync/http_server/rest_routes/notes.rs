@@ -0,0 +1,574 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::header;
+use axum::http::HeaderMap;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::delete;
+use axum::routing::get;
+use axum::routing::post;
+use axum::Json;
+use axum::Router;
+use futures::stream;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::cards::CardSummaryResponse;
+use super::expand::Expand;
+use super::expand::Expanded;
+use super::notetypes::NotetypeResponse;
+use super::rendered_html;
+use super::with_col;
+use super::with_col_confirming_delete;
+use super::with_col_guarding_schema;
+use super::ChangedIdsResponse;
+use super::DeleteCountsResponse;
+use super::SchemaChangeResponse;
+use super::TrashedMediaResponse;
+use crate::notes::diff::DiffOp;
+use crate::notes::diff::FieldDiffKind;
+use crate::prelude::*;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+const MAX_EXPORT_BATCH_SIZE: u32 = 5000;
+
+// Payloads for the API
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportNotesQuery {
+    /// The number of notes read from the collection at a time.
+    #[serde(default = "default_export_batch_size")]
+    batch_size: u32,
+}
+
+fn default_export_batch_size() -> u32 {
+    500
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteResponse {
+    id: i64,
+    guid: String,
+    notetype_id: i64,
+    /// Modification time, in seconds.
+    modified: i64,
+    tags: Vec<String>,
+    /// True if the note has the "marked" tag.
+    marked: bool,
+    fields: Vec<String>,
+}
+
+impl From<Note> for NoteResponse {
+    fn from(note: Note) -> Self {
+        NoteResponse {
+            id: note.id.0,
+            guid: note.guid.clone(),
+            notetype_id: note.notetype_id.0,
+            modified: note.mtime.0,
+            tags: note.tags.clone(),
+            marked: note.is_marked(),
+            fields: note.into_fields(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetNoteQuery {
+    /// Any of "cards" and "notetype", separated by commas, to include those
+    /// objects in the response.
+    expand: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeNotetypeRequest {
+    notetype_id: i64,
+    /// Maps ordinals of the note's current fields to ordinals of the new
+    /// notetype's fields. Unmapped fields of the new notetype are left empty.
+    field_mapping: HashMap<u16, u16>,
+    allow_schema_change: Option<bool>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarkResponse {
+    marked: bool,
+    changes: ChangedIdsResponse,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SinceQuery {
+    /// Unix timestamp in seconds.
+    since: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModifiedNoteResponse {
+    note_id: i64,
+    /// Modification time, in seconds.
+    modified_at: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletedNoteResponse {
+    note_id: i64,
+}
+
+/// The tag that marks a note, as added by the desktop.
+const MARKED_TAG: &str = "marked";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteNotesRequest {
+    note_ids: Vec<i64>,
+    /// Required when the notes have more cards than the server's threshold.
+    #[serde(default)]
+    confirm: bool,
+    /// Move media files that no remaining note references to the media
+    /// trash. Files that can't be moved don't fail the delete, and are
+    /// reported in `mediaNotTrashed`.
+    #[serde(default)]
+    cleanup_media: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteNotesResponse {
+    notes_deleted: usize,
+    cards_deleted: usize,
+    /// Ids that did not match a note. Unlike `missingIds`, this is not
+    /// capped, and is kept for existing clients.
+    not_found: Vec<i64>,
+    #[serde(flatten)]
+    counts: DeleteCountsResponse,
+    /// The media files moved to the trash, if cleanupMedia was set.
+    #[serde(flatten)]
+    media: Option<TrashedMediaResponse>,
+    changes: ChangedIdsResponse,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewNoteRequest {
+    notetype_id: i64,
+    /// Field values by name. Fields not included are left empty.
+    fields: HashMap<String, String>,
+    media_url_prefix: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewNoteResponse {
+    /// The number of cards adding the note would generate. When this is 0,
+    /// adding it creates a single card with an empty front instead.
+    card_count: usize,
+    cards: Vec<CardPreviewResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardPreviewResponse {
+    ord: u16,
+    template_name: String,
+    /// False if the card would not be generated.
+    nonempty: bool,
+    rendered_front: String,
+    rendered_back: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProposedNoteRequest {
+    /// Either the id or the guid of the note is required.
+    note_id: Option<i64>,
+    guid: Option<String>,
+    /// Proposed field values by name. Fields not included are unchanged.
+    fields: HashMap<String, String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffNotesResponse {
+    notes: Vec<NoteDiffResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteDiffResponse {
+    /// Null if the note was given by a guid that did not match.
+    note_id: Option<i64>,
+    guid: Option<String>,
+    found: bool,
+    /// True if any field would change.
+    changed: bool,
+    fields: Vec<FieldDiffResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldDiffResponse {
+    name: String,
+    /// "changed", "unchanged" or "unknownField".
+    status: &'static str,
+    current: String,
+    proposed: Option<String>,
+    /// For changed fields, the words of the field's text with HTML stripped,
+    /// as runs that are "equal", "removed" or "added".
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    diff: Vec<DiffSegmentResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffSegmentResponse {
+    op: &'static str,
+    text: String,
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new()
+        .route("/notes", delete(delete_notes))
+        .route("/notes/export", get(export_notes))
+        .route("/notes/preview", post(preview_note))
+        .route("/notes/diff", post(diff_notes))
+        .route("/notes/modified-since", get(notes_modified_since))
+        .route("/notes/deleted-since", get(notes_deleted_since))
+        .route("/notes/{note_id}", get(get_note))
+        .route("/notes/{note_id}/mark", post(mark_note).delete(unmark_note))
+        .route("/notes/{note_id}/change-notetype", post(change_notetype))
+}
+
+// Handler for deleting notes along with their cards
+async fn delete_notes(
+    State(server): State<Arc<SimpleServer>>,
+    headers: HeaderMap,
+    payload: Result<Json<DeleteNotesRequest>, JsonRejection>,
+) -> ApiResult<Json<DeleteNotesResponse>> {
+    let Json(payload) = payload?;
+    let nids: Vec<NoteId> = payload.note_ids.iter().copied().map(NoteId).collect();
+    with_col_confirming_delete(
+        &server,
+        &headers,
+        payload.confirm,
+        |col| {
+            let unique: HashSet<NoteId> = nids.iter().copied().collect();
+            let unique: Vec<NoteId> = unique.into_iter().collect();
+            Ok(col.storage.card_ids_of_notes(&unique)?.len())
+        },
+        |col, media| {
+            let unused_media = if payload.cleanup_media {
+                Some(col.media_only_referenced_by(&nids)?)
+            } else {
+                None
+            };
+            let out = col.bulk_delete_notes(nids)?;
+            let summary = out.output;
+            let not_found: Vec<i64> = summary.not_found.into_iter().map(|nid| nid.0).collect();
+            let missing: HashSet<i64> = not_found.iter().copied().collect();
+            let counts = DeleteCountsResponse::new(&payload.note_ids, |id| missing.contains(&id));
+            let trashed = unused_media.map(|files| media.trash_files(&files).into());
+            Ok(Json(DeleteNotesResponse {
+                notes_deleted: summary.notes_deleted,
+                cards_deleted: summary.cards_deleted,
+                not_found,
+                counts,
+                media: trashed,
+                changes: out.changes.ids.into(),
+            }))
+        },
+    )
+    .await
+}
+
+// Handler for getting a note's fields and tags
+async fn get_note(
+    State(server): State<Arc<SimpleServer>>,
+    Path(note_id): Path<i64>,
+    Query(query): Query<GetNoteQuery>,
+) -> ApiResult<Json<Expanded<NoteResponse>>> {
+    with_col(&server, |col| {
+        let expand = Expand::parse(query.expand.as_deref(), &["cards", "notetype"])?;
+        let nid = NoteId(note_id);
+        let note = col.storage.get_note(nid)?.or_not_found(nid)?;
+        let ntid = note.notetype_id;
+        expand
+            .apply(NoteResponse::from(note), |name| {
+                Ok(match name {
+                    "cards" => serde_json::to_value(
+                        col.storage
+                            .all_cards_of_note(nid)?
+                            .iter()
+                            .map(CardSummaryResponse::from)
+                            .collect::<Vec<_>>(),
+                    )?,
+                    _ => {
+                        let nt = col.get_notetype(ntid)?.or_not_found(ntid)?;
+                        serde_json::to_value(NotetypeResponse::from(nt.as_ref()))?
+                    }
+                })
+            })
+            .map(Json)
+    })
+    .await
+}
+
+// Handler for listing notes modified since a given time, oldest first
+async fn notes_modified_since(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<SinceQuery>,
+) -> ApiResult<Json<Vec<ModifiedNoteResponse>>> {
+    with_col(&server, |col| {
+        Ok(Json(
+            col.get_notes_modified_since(TimestampSecs(query.since))?
+                .into_iter()
+                .map(|(nid, mtime)| ModifiedNoteResponse {
+                    note_id: nid.0,
+                    modified_at: mtime.0,
+                })
+                .collect(),
+        ))
+    })
+    .await
+}
+
+// Handler for listing deleted notes. Graves carry no deletion time, so like
+// /collection/changes-since, this includes every note deleted since the last
+// full sync, whatever the value of `since`.
+async fn notes_deleted_since(
+    State(server): State<Arc<SimpleServer>>,
+    Query(_query): Query<SinceQuery>,
+) -> ApiResult<Json<Vec<DeletedNoteResponse>>> {
+    with_col(&server, |col| {
+        Ok(Json(
+            col.get_deleted_notes()?
+                .into_iter()
+                .map(|nid| DeletedNoteResponse { note_id: nid.0 })
+                .collect(),
+        ))
+    })
+    .await
+}
+
+// Handler for marking a note, using the same undoable op as adding the tag
+async fn mark_note(
+    State(server): State<Arc<SimpleServer>>,
+    Path(note_id): Path<i64>,
+) -> ApiResult<Json<MarkResponse>> {
+    with_col(&server, |col| {
+        let nid = NoteId(note_id);
+        col.storage.get_note(nid)?.or_not_found(nid)?;
+        let changes = col.add_tags_to_notes(&[nid], MARKED_TAG)?.changes;
+        Ok(Json(MarkResponse {
+            marked: true,
+            changes: changes.ids.into(),
+        }))
+    })
+    .await
+}
+
+// Handler for unmarking a note, using the same undoable op as removing the tag
+async fn unmark_note(
+    State(server): State<Arc<SimpleServer>>,
+    Path(note_id): Path<i64>,
+) -> ApiResult<Json<MarkResponse>> {
+    with_col(&server, |col| {
+        let nid = NoteId(note_id);
+        col.storage.get_note(nid)?.or_not_found(nid)?;
+        let changes = col.remove_tags_from_notes(&[nid], MARKED_TAG)?.changes;
+        Ok(Json(MarkResponse {
+            marked: false,
+            changes: changes.ids.into(),
+        }))
+    })
+    .await
+}
+
+// Handler for moving a note to another notetype. This modifies the schema, so
+// the next sync will be a full one.
+async fn change_notetype(
+    State(server): State<Arc<SimpleServer>>,
+    Path(note_id): Path<i64>,
+    payload: Result<Json<ChangeNotetypeRequest>, JsonRejection>,
+) -> ApiResult<Json<SchemaChangeResponse<NoteResponse>>> {
+    let Json(payload) = payload?;
+    with_col_guarding_schema(&server, payload.allow_schema_change, |col| {
+        let nid = NoteId(note_id);
+        col.set_note_notetype(nid, NotetypeId(payload.notetype_id), payload.field_mapping)?;
+        let note = col.storage.get_note(nid)?.or_not_found(nid)?;
+        Ok(NoteResponse::from(note))
+    })
+    .await
+    .map(Json)
+}
+
+// Handler for rendering the cards a note would have, without adding it
+async fn preview_note(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<PreviewNoteRequest>, JsonRejection>,
+) -> ApiResult<Json<PreviewNoteResponse>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        let ntid = NotetypeId(payload.notetype_id);
+        let nt = col.get_notetype(ntid)?.or_not_found(ntid)?;
+        let mut note = Note::new(&nt);
+        for (name, value) in &payload.fields {
+            if let Some(idx) = nt.get_field_ord(name) {
+                note.set_field(idx, value)?;
+            }
+        }
+        let prefix = payload.media_url_prefix.as_deref();
+        let cards: Vec<_> = col
+            .preview_note_cards(&mut note)?
+            .into_iter()
+            .map(|preview| CardPreviewResponse {
+                ord: preview.ord,
+                template_name: preview.template_name,
+                nonempty: preview.nonempty,
+                rendered_front: rendered_html(&preview.output.question(), prefix),
+                rendered_back: rendered_html(&preview.output.answer(), prefix),
+            })
+            .collect();
+        Ok(Json(PreviewNoteResponse {
+            card_count: cards.iter().filter(|card| card.nonempty).count(),
+            cards,
+        }))
+    })
+    .await
+}
+
+fn field_diff_status(kind: FieldDiffKind) -> &'static str {
+    match kind {
+        FieldDiffKind::Unchanged => "unchanged",
+        FieldDiffKind::Changed => "changed",
+        FieldDiffKind::UnknownField => "unknownField",
+    }
+}
+
+fn diff_op_name(op: DiffOp) -> &'static str {
+    match op {
+        DiffOp::Equal => "equal",
+        DiffOp::Removed => "removed",
+        DiffOp::Added => "added",
+    }
+}
+
+// Handler for comparing notes with proposed content, without changing them
+async fn diff_notes(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<Vec<ProposedNoteRequest>>, JsonRejection>,
+) -> ApiResult<Json<DiffNotesResponse>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        let mut notes = Vec::with_capacity(payload.len());
+        for proposed in payload {
+            let nid = match (proposed.note_id, &proposed.guid) {
+                (Some(nid), _) => Some(NoteId(nid)),
+                (None, Some(guid)) => col.storage.get_note_id_by_guid(guid)?,
+                (None, None) => invalid_input!("each note needs a noteId or guid"),
+            };
+            let diffs = match nid {
+                Some(nid) => match col.diff_note_fields(nid, &proposed.fields) {
+                    Ok(diffs) => Some(diffs),
+                    Err(AnkiError::NotFound { .. }) => None,
+                    Err(err) => return Err(err),
+                },
+                None => None,
+            };
+            let fields: Vec<_> = diffs
+                .iter()
+                .flatten()
+                .map(|diff| FieldDiffResponse {
+                    name: diff.name.clone(),
+                    status: field_diff_status(diff.kind),
+                    current: diff.current.clone(),
+                    proposed: diff.proposed.clone(),
+                    diff: diff
+                        .segments
+                        .iter()
+                        .map(|segment| DiffSegmentResponse {
+                            op: diff_op_name(segment.op),
+                            text: segment.text.clone(),
+                        })
+                        .collect(),
+                })
+                .collect();
+            notes.push(NoteDiffResponse {
+                note_id: nid.map(|nid| nid.0),
+                guid: proposed.guid,
+                found: diffs.is_some(),
+                changed: fields.iter().any(|field| field.status != "unchanged"),
+                fields,
+            });
+        }
+        Ok(Json(DiffNotesResponse { notes }))
+    })
+    .await
+}
+
+/// The notes following `after` in id order, as newline-delimited JSON. Returns
+/// the id of the last note, or [None] if there were no more notes.
+async fn note_batch(
+    server: &SimpleServer,
+    after: Option<NoteId>,
+    limit: u32,
+) -> ApiResult<(Option<NoteId>, Vec<u8>)> {
+    with_col(server, |col| {
+        let nids = col.get_all_note_ids_paginated(after, limit)?;
+        let mut out = vec![];
+        for &nid in &nids {
+            let note = col.storage.get_note(nid)?.or_not_found(nid)?;
+            serde_json::to_writer(&mut out, &NoteResponse::from(note))?;
+            out.push(b'\n');
+        }
+        Ok((nids.last().copied(), out))
+    })
+    .await
+}
+
+// Handler for streaming every note in the collection. The collection is only
+// locked while a batch is read, so other requests are not blocked for the
+// duration of the download.
+async fn export_notes(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<ExportNotesQuery>,
+) -> ApiResult<Response> {
+    let batch_size = query.batch_size.clamp(1, MAX_EXPORT_BATCH_SIZE);
+    // errors reading the first batch can still be reported with a status code
+    let first = note_batch(&server, None, batch_size).await?;
+    let body = stream::unfold(Some(first), move |batch| {
+        let server = server.clone();
+        async move {
+            let (last, lines) = batch?;
+            let after = last?;
+            // the status has already been sent, so an error cuts the response
+            // short instead
+            Some(match note_batch(&server, Some(after), batch_size).await {
+                Ok(next) => (Ok(lines), Some(next)),
+                Err(_) => (Err(io::Error::other("reading notes failed")), None),
+            })
+        }
+    });
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
@@ -0,0 +1,501 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::sync::Arc;
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::routing::get;
+use axum::routing::post;
+use axum::routing::put;
+use axum::Json;
+use axum::Router;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::with_col;
+use super::with_col_guarding_schema;
+use super::SchemaChangeResponse;
+use crate::notetype::CardChanges;
+use crate::notetype::CardTemplate;
+use crate::notetype::NoteField;
+use crate::notetype::NotetypeSchema11;
+use crate::notetype::LONG_CSS_BYTES;
+use crate::prelude::*;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+/// The maximum number of created/removed card ids included in a response. The
+/// counts are always complete.
+const MAX_REPORTED_CARD_IDS: usize = 1000;
+
+// Payloads for the API
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotetypeSummaryResponse {
+    id: i64,
+    name: String,
+    note_count: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateInput {
+    /// The ordinal of the existing template this entry replaces, or null to
+    /// add a new template. Existing templates that are not referenced are
+    /// removed.
+    ord: Option<u32>,
+    name: String,
+    front: String,
+    back: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateTemplatesRequest {
+    templates: Vec<TemplateInput>,
+    allow_schema_change: Option<bool>,
+}
+
+/// Options that are left out are not changed.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateFieldRequest {
+    name: Option<String>,
+    sticky: Option<bool>,
+    rtl: Option<bool>,
+    font_name: Option<String>,
+    font_size: Option<u32>,
+    description: Option<String>,
+    plain_text: Option<bool>,
+    collapsed: Option<bool>,
+    exclude_from_search: Option<bool>,
+    allow_schema_change: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCssRequest {
+    css: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkUpdateCssRequest {
+    notetype_ids: Vec<i64>,
+    css: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaChangeQuery {
+    allow_schema_change: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeNotetypeRequest {
+    note_ids: Vec<i64>,
+    new_notetype_id: i64,
+    /// For each field of the new notetype, the index of the old field to take
+    /// its content from. Defaults to matching by name, then position.
+    new_fields: Option<Vec<Option<usize>>>,
+    /// For each template of the new notetype, the index of the old template
+    /// whose cards should be moved to it. Defaults to matching by name, then
+    /// position.
+    new_templates: Option<Vec<Option<usize>>>,
+    allow_schema_change: Option<bool>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardChangesResponse {
+    cards_created: usize,
+    cards_removed: usize,
+    created_card_ids: Vec<i64>,
+    removed_card_ids: Vec<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldResponse {
+    ord: usize,
+    name: String,
+    /// Keep the field's content when adding the next note.
+    sticky: bool,
+    /// Edit the field right-to-left.
+    rtl: bool,
+    font_name: String,
+    font_size: u32,
+    /// Placeholder text shown while the field is empty.
+    description: String,
+    /// Edit the field as plain text by default.
+    plain_text: bool,
+    /// Show the field collapsed by default.
+    collapsed: bool,
+    exclude_from_search: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateResponse {
+    ord: usize,
+    name: String,
+    front: String,
+    back: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotetypeResponse {
+    id: i64,
+    name: String,
+    css: String,
+    fields: Vec<FieldResponse>,
+    templates: Vec<TemplateResponse>,
+}
+
+impl From<&Notetype> for NotetypeResponse {
+    fn from(nt: &Notetype) -> Self {
+        NotetypeResponse {
+            id: nt.id.0,
+            name: nt.name.clone(),
+            css: nt.config.css.clone(),
+            fields: nt.fields.iter().enumerate().map(field_response).collect(),
+            templates: nt
+                .templates
+                .iter()
+                .enumerate()
+                .map(|(ord, template)| TemplateResponse {
+                    ord,
+                    name: template.name.clone(),
+                    front: template.config.q_format.clone(),
+                    back: template.config.a_format.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn field_response((ord, field): (usize, &NoteField)) -> FieldResponse {
+    let config = &field.config;
+    FieldResponse {
+        ord,
+        name: field.name.clone(),
+        sticky: config.sticky,
+        rtl: config.rtl,
+        font_name: config.font_name.clone(),
+        font_size: config.font_size,
+        description: config.description.clone(),
+        plain_text: config.plain_text,
+        collapsed: config.collapsed,
+        exclude_from_search: config.exclude_from_search,
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCssResponse {
+    /// The number of notetypes whose styling was replaced.
+    updated: usize,
+    /// Problems with the CSS that did not stop it from being saved.
+    warnings: Vec<CssWarningResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum CssWarningResponse {
+    /// The CSS is longer than [LONG_CSS_BYTES].
+    LongCss { bytes: usize },
+}
+
+impl UpdateCssResponse {
+    fn new(updated: usize, css: &str) -> Self {
+        let mut warnings = vec![];
+        if css.len() > LONG_CSS_BYTES {
+            warnings.push(CssWarningResponse::LongCss { bytes: css.len() });
+        }
+        Self { updated, warnings }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewCardResponse {
+    note_id: i64,
+    ord: u16,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardDiffResponse {
+    would_add: Vec<NewCardResponse>,
+    would_remove: Vec<i64>,
+}
+
+impl From<CardChanges> for CardChangesResponse {
+    fn from(changes: CardChanges) -> Self {
+        let capped_ids = |ids: &[CardId]| {
+            ids.iter()
+                .take(MAX_REPORTED_CARD_IDS)
+                .map(|id| id.0)
+                .collect()
+        };
+        CardChangesResponse {
+            cards_created: changes.added.len(),
+            cards_removed: changes.removed.len(),
+            created_card_ids: capped_ids(&changes.added),
+            removed_card_ids: capped_ids(&changes.removed),
+        }
+    }
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new()
+        .route("/notetypes", get(list_notetypes))
+        .route("/notetypes/bulk-update-css", post(bulk_update_css))
+        .route("/notetypes/{notetype_id}", get(get_notetype))
+        .route("/notetypes/{notetype_id}/css", put(update_css))
+        .route("/notetypes/{notetype_id}/templates", put(update_templates))
+        .route("/notetypes/{notetype_id}/card-diff", post(card_diff))
+        .route(
+            "/notetypes/{notetype_id}/fields/{ord}",
+            put(update_field).delete(delete_field),
+        )
+        .route("/notes/change-notetype", post(change_notetype))
+}
+
+// Handler for listing notetypes, most used first
+async fn list_notetypes(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<Vec<NotetypeSummaryResponse>>> {
+    with_col(&server, |col| {
+        Ok(Json(
+            col.get_note_count_by_notetype()?
+                .into_iter()
+                .map(|(ntid, name, note_count)| NotetypeSummaryResponse {
+                    id: ntid.0,
+                    name,
+                    note_count,
+                })
+                .collect(),
+        ))
+    })
+    .await
+}
+
+// Handler for getting a notetype with its fields and templates
+async fn get_notetype(
+    State(server): State<Arc<SimpleServer>>,
+    Path(notetype_id): Path<i64>,
+) -> ApiResult<Json<NotetypeResponse>> {
+    with_col(&server, |col| {
+        let ntid = NotetypeId(notetype_id);
+        let nt = col.get_notetype(ntid)?.or_not_found(ntid)?;
+        Ok(Json(NotetypeResponse::from(nt.as_ref())))
+    })
+    .await
+}
+
+// Handler for replacing a notetype's templates
+async fn update_templates(
+    State(server): State<Arc<SimpleServer>>,
+    Path(notetype_id): Path<i64>,
+    payload: Result<Json<UpdateTemplatesRequest>, JsonRejection>,
+) -> ApiResult<Json<SchemaChangeResponse<CardChangesResponse>>> {
+    let payload = payload?;
+    with_col_guarding_schema(&server, payload.allow_schema_change, |col| {
+        let ntid = NotetypeId(notetype_id);
+        let mut nt = col.storage.get_notetype(ntid)?.or_not_found(ntid)?;
+        let mut templates = Vec::with_capacity(payload.templates.len());
+        for input in &payload.templates {
+            let mut template = match input.ord {
+                Some(ord) => nt
+                    .templates
+                    .get(ord as usize)
+                    .cloned()
+                    .or_invalid("no template with that ordinal")?,
+                None => CardTemplate::new("", "", ""),
+            };
+            template.name.clone_from(&input.name);
+            template.config.q_format.clone_from(&input.front);
+            template.config.a_format.clone_from(&input.back);
+            templates.push(template);
+        }
+        nt.templates = templates;
+        let changes = col.update_notetype(&mut nt, false)?.output;
+        Ok(changes.into())
+    })
+    .await
+    .map(Json)
+}
+
+// Handler for replacing a notetype's styling
+async fn update_css(
+    State(server): State<Arc<SimpleServer>>,
+    Path(notetype_id): Path<i64>,
+    payload: Result<Json<UpdateCssRequest>, JsonRejection>,
+) -> ApiResult<Json<UpdateCssResponse>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        col.update_template_css(NotetypeId(notetype_id), &payload.css)?;
+        Ok(Json(UpdateCssResponse::new(1, &payload.css)))
+    })
+    .await
+}
+
+// Handler for giving several notetypes the same styling. Nothing is changed
+// if any of the notetypes does not exist.
+async fn bulk_update_css(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<BulkUpdateCssRequest>, JsonRejection>,
+) -> ApiResult<Json<UpdateCssResponse>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        let mut ntids: Vec<NotetypeId> = vec![];
+        for &id in &payload.notetype_ids {
+            let ntid = NotetypeId(id);
+            col.get_notetype(ntid)?.or_not_found(ntid)?;
+            if !ntids.contains(&ntid) {
+                ntids.push(ntid);
+            }
+        }
+        for &ntid in &ntids {
+            col.update_template_css(ntid, &payload.css)?;
+        }
+        Ok(Json(UpdateCssResponse::new(ntids.len(), &payload.css)))
+    })
+    .await
+}
+
+// Handler for previewing the cards a draft notetype would add or remove
+async fn card_diff(
+    State(server): State<Arc<SimpleServer>>,
+    Path(notetype_id): Path<i64>,
+    payload: Result<Json<NotetypeSchema11>, JsonRejection>,
+) -> ApiResult<Json<CardDiffResponse>> {
+    let Json(draft) = payload?;
+    with_col(&server, |col| {
+        let mut nt: Notetype = draft.into();
+        nt.id = NotetypeId(notetype_id);
+        let diff = col.get_notetype_card_generation_diff(&nt)?;
+        Ok(Json(CardDiffResponse {
+            would_add: diff
+                .would_add
+                .into_iter()
+                .map(|(nid, ord)| NewCardResponse {
+                    note_id: nid.0,
+                    ord,
+                })
+                .collect(),
+            would_remove: diff.would_remove.into_iter().map(|cid| cid.0).collect(),
+        }))
+    })
+    .await
+}
+
+// Handler for renaming a field or changing its options. Options the request
+// does not mention, including ones this version does not know about, are
+// preserved.
+async fn update_field(
+    State(server): State<Arc<SimpleServer>>,
+    Path((notetype_id, ord)): Path<(i64, usize)>,
+    payload: Result<Json<UpdateFieldRequest>, JsonRejection>,
+) -> ApiResult<Json<SchemaChangeResponse<FieldResponse>>> {
+    let Json(payload) = payload?;
+    with_col_guarding_schema(&server, payload.allow_schema_change, |col| {
+        let ntid = NotetypeId(notetype_id);
+        let mut nt = col.storage.get_notetype(ntid)?.or_not_found(ntid)?;
+        let field = nt
+            .fields
+            .get_mut(ord)
+            .or_invalid("no field with that ordinal")?;
+        if let Some(name) = payload.name {
+            field.name = name;
+        }
+        let config = &mut field.config;
+        if let Some(sticky) = payload.sticky {
+            config.sticky = sticky;
+        }
+        if let Some(rtl) = payload.rtl {
+            config.rtl = rtl;
+        }
+        if let Some(font_name) = payload.font_name {
+            config.font_name = font_name;
+        }
+        if let Some(font_size) = payload.font_size {
+            config.font_size = font_size;
+        }
+        if let Some(description) = payload.description {
+            config.description = description;
+        }
+        if let Some(plain_text) = payload.plain_text {
+            config.plain_text = plain_text;
+        }
+        if let Some(collapsed) = payload.collapsed {
+            config.collapsed = collapsed;
+        }
+        if let Some(exclude_from_search) = payload.exclude_from_search {
+            config.exclude_from_search = exclude_from_search;
+        }
+        col.update_notetype(&mut nt, false)?;
+        Ok(field_response((ord, &nt.fields[ord])))
+    })
+    .await
+    .map(Json)
+}
+
+// Handler for deleting a field from a notetype
+async fn delete_field(
+    State(server): State<Arc<SimpleServer>>,
+    Path((notetype_id, ord)): Path<(i64, usize)>,
+    Query(query): Query<SchemaChangeQuery>,
+) -> ApiResult<Json<SchemaChangeResponse<CardChangesResponse>>> {
+    with_col_guarding_schema(&server, query.allow_schema_change, |col| {
+        let ntid = NotetypeId(notetype_id);
+        let mut nt = col.storage.get_notetype(ntid)?.or_not_found(ntid)?;
+        require!(ord < nt.fields.len(), "no field with that ordinal");
+        nt.fields.remove(ord);
+        let changes = col.update_notetype(&mut nt, false)?.output;
+        Ok(changes.into())
+    })
+    .await
+    .map(Json)
+}
+
+// Handler for moving notes to a different notetype
+async fn change_notetype(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<ChangeNotetypeRequest>, JsonRejection>,
+) -> ApiResult<Json<SchemaChangeResponse<CardChangesResponse>>> {
+    let payload = payload?;
+    with_col_guarding_schema(&server, payload.allow_schema_change, |col| {
+        let note_ids: Vec<NoteId> = payload.note_ids.iter().map(|&id| NoteId(id)).collect();
+        let mut old_notetype_id = None;
+        for &nid in &note_ids {
+            let ntid = col.storage.get_note(nid)?.or_not_found(nid)?.notetype_id;
+            require!(
+                *old_notetype_id.get_or_insert(ntid) == ntid,
+                "all notes must share the same notetype"
+            );
+        }
+        let old_notetype_id = old_notetype_id.or_invalid("no notes provided")?;
+        let mut input = col
+            .notetype_change_info(old_notetype_id, NotetypeId(payload.new_notetype_id))?
+            .input;
+        input.note_ids = note_ids;
+        if let Some(new_fields) = &payload.new_fields {
+            input.new_fields.clone_from(new_fields);
+        }
+        if let Some(new_templates) = &payload.new_templates {
+            input.new_templates = Some(new_templates.clone());
+        }
+        let changes = col.change_notetype_of_notes(input)?.output;
+        Ok(changes.into())
+    })
+    .await
+    .map(Json)
+}
@@ -0,0 +1,82 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::sync::Arc;
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::routing::get;
+use axum::Json;
+use axum::Router;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::lock_state;
+use super::require_admin;
+use super::LOCK_TIMEOUT;
+use crate::prelude::*;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+// Payloads for the API
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetReadOnlyRequest {
+    read_only: bool,
+    /// Change only this user, instead of the whole server.
+    user: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadOnlyResponse {
+    /// True if the whole server refuses changes.
+    server: bool,
+    /// Users who refuse changes even when the server doesn't, in name order.
+    users: Vec<String>,
+}
+
+impl From<&SimpleServer> for ReadOnlyResponse {
+    fn from(server: &SimpleServer) -> Self {
+        ReadOnlyResponse {
+            server: server.read_only.server(),
+            users: server.read_only.users(),
+        }
+    }
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new().route("/read-only", get(get_read_only).put(set_read_only))
+}
+
+// Handler for showing whether the server and its users are read-only
+async fn get_read_only(State(server): State<Arc<SimpleServer>>) -> Json<ReadOnlyResponse> {
+    Json(server.as_ref().into())
+}
+
+// Handler for making the server or one user read-only, or writable again.
+// Takes effect immediately, for both the REST API and syncing. Requires the
+// admin token.
+async fn set_read_only(
+    State(server): State<Arc<SimpleServer>>,
+    headers: HeaderMap,
+    payload: Result<Json<SetReadOnlyRequest>, JsonRejection>,
+) -> ApiResult<Json<ReadOnlyResponse>> {
+    require_admin(&server, &headers)?;
+    let Json(payload) = payload?;
+    match payload.user {
+        Some(name) => {
+            let state = lock_state(&server, LOCK_TIMEOUT).await?;
+            state
+                .users
+                .values()
+                .find(|user| user.name == name)
+                .or_not_found(&name)?;
+            server.read_only.set_user(&name, payload.read_only);
+        }
+        None => server.read_only.set_server(payload.read_only),
+    }
+    Ok(Json(server.as_ref().into()))
+}
@@ -0,0 +1,377 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::sync::Arc;
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::State;
+use axum::routing::post;
+use axum::Json;
+use axum::Router;
+use itertools::Itertools;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::with_col;
+use crate::prelude::*;
+use crate::search::parse_search;
+use crate::search::FieldOrder;
+use crate::search::Negated;
+use crate::search::Node;
+use crate::search::NoteSnippet;
+use crate::search::RatingKind;
+use crate::search::SearchBuilder;
+use crate::search::SearchNode;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+use crate::text::strip_html_preserving_media_filenames;
+
+/// The most notes a full-text search can return.
+const MAX_FULLTEXT_LIMIT: usize = 500;
+/// The most rows a single page of browser rows can contain.
+const MAX_ROWS_LIMIT: usize = 1000;
+
+// Payloads for the API
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FullTextSearchRequest {
+    /// A search in the browser's syntax.
+    query: String,
+    /// Only take snippets from fields with this name, skipping notes where
+    /// it does not match.
+    field: Option<String>,
+    #[serde(default = "default_fulltext_limit")]
+    limit: usize,
+}
+
+fn default_fulltext_limit() -> usize {
+    50
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FullTextMatchResponse {
+    note_id: i64,
+    /// The name of the field the snippet was taken from.
+    field: String,
+    /// The field's text with HTML removed, around the first match, which is
+    /// wrapped in <mark>. Other text is HTML-escaped.
+    snippet: String,
+}
+
+impl From<NoteSnippet> for FullTextMatchResponse {
+    fn from(snippet: NoteSnippet) -> Self {
+        FullTextMatchResponse {
+            note_id: snippet.note_id.0,
+            field: snippet.field,
+            snippet: snippet.snippet,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FullTextSearchResponse {
+    /// Matching notes in sort field order.
+    results: Vec<FullTextMatchResponse>,
+    /// Problems with the query that did not stop it from running.
+    warnings: Vec<SearchWarningResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum SearchWarningResponse {
+    /// A field-qualified term names a field no notetype has, so it matches
+    /// nothing.
+    UnknownField { field: String },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowserRowsRequest {
+    /// A search in the browser's syntax.
+    query: String,
+    /// Return a row per note instead of per card.
+    #[serde(default)]
+    notes: bool,
+    /// Order by the field with this name instead of each notetype's sort
+    /// field. Notes without such a field sort as if it were empty.
+    sort_field: Option<String>,
+    #[serde(default)]
+    reverse: bool,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_rows_limit")]
+    limit: usize,
+}
+
+fn default_rows_limit() -> usize {
+    100
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowserRowResponse {
+    /// A card id, or a note id if notes were requested.
+    id: i64,
+    note_id: i64,
+    /// The field text the row was ordered by, with HTML removed.
+    sort_key: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowserRowsResponse {
+    /// The number of rows matching the search, across all pages.
+    total: usize,
+    rows: Vec<BrowserRowResponse>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildSearchRequest {
+    node: SearchNodeRequest,
+}
+
+/// A search term, or terms combined into one. Periods of days count today as
+/// the first day, with days starting at the collection's rollover hour.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum SearchNodeRequest {
+    /// A search in the browser's syntax.
+    Text {
+        text: String,
+    },
+    /// Cards added in the last `days` days.
+    Added {
+        days: u32,
+    },
+    /// Cards whose note was edited in the last `days` days.
+    Edited {
+        days: u32,
+    },
+    /// Cards first answered in the last `days` days.
+    Introduced {
+        days: u32,
+    },
+    /// Cards answered in the last `days` days, or rescheduled by hand if
+    /// `rating` is `rescheduled`.
+    Rated {
+        days: u32,
+        #[serde(default)]
+        rating: RatedRequest,
+    },
+    Not {
+        node: Box<SearchNodeRequest>,
+    },
+    Group {
+        #[serde(default)]
+        joiner: JoinerRequest,
+        nodes: Vec<SearchNodeRequest>,
+    },
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum RatedRequest {
+    #[default]
+    Any,
+    Again,
+    Hard,
+    Good,
+    Easy,
+    Rescheduled,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum JoinerRequest {
+    #[default]
+    And,
+    Or,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildSearchResponse {
+    /// The search in the browser's syntax.
+    search: String,
+}
+
+impl TryFrom<SearchNodeRequest> for Node {
+    type Error = AnkiError;
+
+    fn try_from(request: SearchNodeRequest) -> Result<Self> {
+        let in_days = |days: u32, node: fn(u32) -> SearchNode| {
+            require!(days > 0, "days must be at least 1");
+            Ok(Node::Search(node(days)))
+        };
+        match request {
+            SearchNodeRequest::Text { text } => {
+                let mut nodes = parse_search(&text)?;
+                Ok(if nodes.len() == 1 {
+                    nodes.pop().unwrap()
+                } else {
+                    Node::Group(nodes)
+                })
+            }
+            SearchNodeRequest::Added { days } => in_days(days, SearchNode::AddedInDays),
+            SearchNodeRequest::Edited { days } => in_days(days, SearchNode::EditedInDays),
+            SearchNodeRequest::Introduced { days } => in_days(days, SearchNode::IntroducedInDays),
+            SearchNodeRequest::Rated { days, rating } => {
+                require!(days > 0, "days must be at least 1");
+                let ease = match rating {
+                    RatedRequest::Any => RatingKind::AnyAnswerButton,
+                    RatedRequest::Again => RatingKind::AnswerButton(1),
+                    RatedRequest::Hard => RatingKind::AnswerButton(2),
+                    RatedRequest::Good => RatingKind::AnswerButton(3),
+                    RatedRequest::Easy => RatingKind::AnswerButton(4),
+                    RatedRequest::Rescheduled => RatingKind::ManualReschedule,
+                };
+                Ok(Node::Search(SearchNode::Rated { days, ease }))
+            }
+            SearchNodeRequest::Not { node } => Ok(Node::try_from(*node)?.negated()),
+            SearchNodeRequest::Group { joiner, mut nodes } => match nodes.len() {
+                0 => invalid_input!("a group must contain at least one node"),
+                1 => nodes.pop().unwrap().try_into(),
+                _ => {
+                    let joiner = match joiner {
+                        JoinerRequest::And => Node::And,
+                        JoinerRequest::Or => Node::Or,
+                    };
+                    let nodes: Vec<Node> = nodes
+                        .into_iter()
+                        .map(TryFrom::try_from)
+                        .collect::<Result<_>>()?;
+                    Ok(Node::Group(
+                        Itertools::intersperse(nodes.into_iter(), joiner).collect(),
+                    ))
+                }
+            },
+        }
+    }
+}
+
+/// The text of the field `order` refers to, as it is compared when sorting.
+fn row_sort_key(col: &mut Collection, nid: NoteId, order: &FieldOrder) -> Result<String> {
+    let note = col.storage.get_note(nid)?.or_not_found(nid)?;
+    let notetype = col
+        .get_notetype(note.notetype_id)?
+        .or_not_found(note.notetype_id)?;
+    let ord = match order {
+        FieldOrder::SortField => Some(notetype.config.sort_field_idx as usize),
+        FieldOrder::Named(name) => notetype.get_field_ord(name),
+    };
+    let field = ord
+        .and_then(|ord| note.fields().get(ord))
+        .map(String::as_str)
+        .unwrap_or_default();
+    Ok(strip_html_preserving_media_filenames(field).into_owned())
+}
+
+/// Warnings about field qualifiers in `query` that match no field.
+fn search_warnings(col: &mut Collection, query: &str) -> Result<Vec<SearchWarningResponse>> {
+    Ok(col
+        .unknown_search_fields(query)?
+        .into_iter()
+        .map(|field| SearchWarningResponse::UnknownField { field })
+        .collect())
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new()
+        .route("/search/fulltext", post(fulltext_search))
+        .route("/search/rows", post(browser_rows))
+        .route("/search/build", post(build_search))
+}
+
+// Handler for searching note text, returning a snippet of each match
+async fn fulltext_search(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<FullTextSearchRequest>, JsonRejection>,
+) -> ApiResult<Json<FullTextSearchResponse>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        require!(
+            (1..=MAX_FULLTEXT_LIMIT).contains(&payload.limit),
+            "limit must be between 1 and {MAX_FULLTEXT_LIMIT}"
+        );
+        require!(!payload.query.trim().is_empty(), "query must not be empty");
+        let warnings = search_warnings(col, &payload.query)?;
+        let results = col
+            .search_note_snippets(&payload.query, payload.field.as_deref(), payload.limit)?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        Ok(Json(FullTextSearchResponse { results, warnings }))
+    })
+    .await
+}
+
+// Handler for listing the browser's rows a page at a time, in field order
+async fn browser_rows(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<BrowserRowsRequest>, JsonRejection>,
+) -> ApiResult<Json<BrowserRowsResponse>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        require!(
+            (1..=MAX_ROWS_LIMIT).contains(&payload.limit),
+            "limit must be between 1 and {MAX_ROWS_LIMIT}"
+        );
+        let order = match payload.sort_field {
+            Some(name) => FieldOrder::Named(name),
+            None => FieldOrder::SortField,
+        };
+        let (offset, limit) = (payload.offset, payload.limit);
+        let (total, page): (usize, Vec<(i64, NoteId)>) = if payload.notes {
+            let nids: Vec<NoteId> =
+                col.search_in_field_order(&payload.query, &order, payload.reverse)?;
+            let page = nids
+                .iter()
+                .skip(offset)
+                .take(limit)
+                .map(|nid| (nid.0, *nid))
+                .collect();
+            (nids.len(), page)
+        } else {
+            let cids: Vec<CardId> =
+                col.search_in_field_order(&payload.query, &order, payload.reverse)?;
+            let page = cids
+                .iter()
+                .skip(offset)
+                .take(limit)
+                .map(|&cid| {
+                    let card = col.storage.get_card(cid)?.or_not_found(cid)?;
+                    Ok((cid.0, card.note_id))
+                })
+                .collect::<Result<_>>()?;
+            (cids.len(), page)
+        };
+        let rows = page
+            .into_iter()
+            .map(|(id, nid)| {
+                Ok(BrowserRowResponse {
+                    id,
+                    note_id: nid.0,
+                    sort_key: row_sort_key(col, nid, &order)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Json(BrowserRowsResponse { total, rows }))
+    })
+    .await
+}
+
+// Handler for writing a structured search in the browser's syntax, so clients
+// can combine terms without escaping text themselves
+async fn build_search(
+    payload: Result<Json<BuildSearchRequest>, JsonRejection>,
+) -> ApiResult<Json<BuildSearchResponse>> {
+    let Json(payload) = payload?;
+    let node = Node::try_from(payload.node)?;
+    Ok(Json(BuildSearchResponse {
+        search: SearchBuilder::from_root(node).write(),
+    }))
+}
@@ -0,0 +1,584 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::collections::HashMap;
+use std::io;
+use std::iter;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::get;
+use axum::Json;
+use axum::Router;
+use chrono::NaiveDate;
+use futures::stream;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::with_col;
+use crate::card::CardType;
+use crate::prelude::*;
+use crate::revlog::RevlogEntry;
+use crate::revlog::RevlogReviewKind;
+use crate::search::SortMode;
+use crate::stats::DeckWorkload;
+use crate::stats::IntervalDistribution;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+// Payloads for the API
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnswerButtonsQuery {
+    /// A search limiting the cards, eg `deck:French`. Defaults to the whole
+    /// collection.
+    #[serde(default)]
+    search: String,
+    /// Only reviews from the last this many days are counted; 0 counts all
+    /// reviews.
+    #[serde(default = "default_days")]
+    days: u32,
+}
+
+fn default_days() -> u32 {
+    30
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ButtonCountsResponse {
+    again: u32,
+    hard: u32,
+    good: u32,
+    easy: u32,
+}
+
+impl From<&[u32]> for ButtonCountsResponse {
+    fn from(counts: &[u32]) -> Self {
+        ButtonCountsResponse {
+            again: counts[0],
+            hard: counts[1],
+            good: counts[2],
+            easy: counts[3],
+        }
+    }
+}
+
+/// Reviews are split into stages the same way as the desktop's answer buttons
+/// graph: learning includes relearning and filtered deck reviews, and mature
+/// reviews are of cards with an interval of 21 days or more.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnswerButtonsResponse {
+    days: u32,
+    learning: ButtonCountsResponse,
+    young: ButtonCountsResponse,
+    mature: ButtonCountsResponse,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntervalsQuery {
+    /// A search limiting the cards. Defaults to the whole collection.
+    #[serde(default)]
+    search: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntervalBucketResponse {
+    /// The first day count in the bucket.
+    start: u32,
+    /// The day count following the bucket.
+    end: u32,
+    count: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DistributionResponse {
+    buckets: Vec<IntervalBucketResponse>,
+    median: f32,
+    p90: f32,
+}
+
+impl From<IntervalDistribution> for DistributionResponse {
+    fn from(dist: IntervalDistribution) -> Self {
+        DistributionResponse {
+            buckets: dist
+                .buckets
+                .into_iter()
+                .map(|(start, end, count)| IntervalBucketResponse { start, end, count })
+                .collect(),
+            median: dist.median,
+            p90: dist.p90,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntervalsResponse {
+    /// Current intervals in days of cards in review or relearning.
+    intervals: DistributionResponse,
+    /// Stability in days of cards with a memory state, if FSRS is enabled.
+    stability: Option<DistributionResponse>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkloadQuery {
+    /// The number of upcoming days to estimate, starting with today.
+    #[serde(default = "default_workload_days")]
+    days: u32,
+    /// The number of past days whose reviews are used to average answer
+    /// times.
+    #[serde(default = "default_window_days")]
+    window_days: u32,
+    /// Seconds per review assumed for decks without recent reviews.
+    #[serde(default = "default_fallback_secs")]
+    fallback_secs: f32,
+}
+
+fn default_workload_days() -> u32 {
+    14
+}
+
+fn default_window_days() -> u32 {
+    30
+}
+
+fn default_fallback_secs() -> f32 {
+    10.0
+}
+
+const MAX_WORKLOAD_DAYS: u32 = 365;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeckWorkloadResponse {
+    deck_id: i64,
+    name: String,
+    secs_per_review: f32,
+    /// Cards due on each day, starting with today. Overdue cards are counted
+    /// as due today.
+    due: Vec<u32>,
+    minutes: Vec<f32>,
+}
+
+impl From<DeckWorkload> for DeckWorkloadResponse {
+    fn from(deck: DeckWorkload) -> Self {
+        DeckWorkloadResponse {
+            minutes: deck.minutes().collect(),
+            deck_id: deck.deck_id.0,
+            name: deck.name,
+            secs_per_review: deck.secs_per_review,
+            due: deck.due,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkloadResponse {
+    /// Top-level decks, including their children.
+    decks: Vec<DeckWorkloadResponse>,
+    /// Minutes across all decks for each day.
+    total_minutes: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryQuery {
+    /// The first day, in YYYY-MM-DD format.
+    from: String,
+    /// The last day, in YYYY-MM-DD format. Defaults to today.
+    to: Option<String>,
+    #[serde(default)]
+    group_by: HistoryGrouping,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryGrouping {
+    #[default]
+    Day,
+    /// Weeks start on the collection's first weekday, as set in the
+    /// preferences.
+    Week,
+}
+
+const MAX_HISTORY_DAYS: i64 = 3650;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryDay {
+    /// In YYYY-MM-DD format. Days start at the collection's rollover hour.
+    /// When grouping by week, the week's first day, which may be before
+    /// `from`; counts only include days in the requested range.
+    date: String,
+    /// Review cards estimated to have been due, including overdue ones. The
+    /// collection does not record this, so it is reconstructed from the
+    /// review history.
+    estimated_due: u32,
+    /// Answers given, including learning steps.
+    reviewed: u32,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CsvExportKind {
+    /// One row per review, oldest first. See [REVIEWS_CSV_COLUMNS].
+    Reviews,
+    /// One row per card. See [CARDS_CSV_COLUMNS].
+    Cards,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportCsvQuery {
+    kind: CsvExportKind,
+    /// A search limiting the cards. Defaults to the whole collection.
+    #[serde(default)]
+    search: String,
+    /// Start the file with a byte order mark, so Excel reads it as UTF-8.
+    #[serde(default)]
+    bom: bool,
+}
+
+/// The columns of the reviews export. Existing columns keep their position
+/// and meaning; new ones are only added at the end.
+///
+/// - `reviewedAt`: ISO 8601, in the collection's timezone
+/// - `reviewId`: the answer time in milliseconds, unique per review
+/// - `deck`: the card's current deck
+/// - `kind`: learning, review, relearning, filtered, manual or rescheduled
+/// - `rating`: 1-4 for again-easy, or 0 for manual rescheduling
+/// - `interval`, `lastInterval`: in days, or negative seconds for learning
+///   steps
+/// - `ease`: 10x the percentage, or normalized difficulty if FSRS was active
+pub const REVIEWS_CSV_COLUMNS: [&str; 11] = [
+    "reviewedAt",
+    "reviewId",
+    "cardId",
+    "noteId",
+    "deck",
+    "kind",
+    "rating",
+    "interval",
+    "lastInterval",
+    "ease",
+    "timeTakenMillis",
+];
+
+/// The columns of the cards export, with the same stability guarantee as
+/// [REVIEWS_CSV_COLUMNS].
+///
+/// - `type`: new, learning, review or relearning
+/// - `dueDate`: YYYY-MM-DD, empty for new cards
+/// - `interval`: in days
+/// - `ease`: 10x the percentage, 0 for new cards
+/// - `stability` (days) and `difficulty` (0-1): empty without a memory state
+/// - `tags`: the note's tags, separated by spaces
+pub const CARDS_CSV_COLUMNS: [&str; 12] = [
+    "cardId",
+    "noteId",
+    "deck",
+    "type",
+    "dueDate",
+    "interval",
+    "ease",
+    "stability",
+    "difficulty",
+    "lapses",
+    "reviews",
+    "tags",
+];
+
+/// Rows are serialized this many at a time as the response is sent.
+const CSV_CHUNK_ROWS: usize = 1000;
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new()
+        .route("/stats/answer-buttons", get(answer_buttons))
+        .route("/stats/intervals", get(intervals))
+        .route("/stats/workload", get(workload))
+        .route("/stats/history", get(history))
+        .route("/stats/export.csv", get(export_csv))
+}
+
+// Handler for the answer button counts of the cards matching a search
+async fn answer_buttons(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<AnswerButtonsQuery>,
+) -> ApiResult<Json<AnswerButtonsResponse>> {
+    with_col(&server, |col| {
+        let counts = col.answer_button_counts_for_search(&query.search, query.days)?;
+        Ok(Json(AnswerButtonsResponse {
+            days: query.days,
+            learning: counts.learning.as_slice().into(),
+            young: counts.young.as_slice().into(),
+            mature: counts.mature.as_slice().into(),
+        }))
+    })
+    .await
+}
+
+// Handler for the interval and stability distributions of the cards matching
+// a search
+async fn intervals(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<IntervalsQuery>,
+) -> ApiResult<Json<IntervalsResponse>> {
+    with_col(&server, |col| {
+        let (intervals, stability) = col.interval_distribution_for_search(&query.search)?;
+        Ok(Json(IntervalsResponse {
+            intervals: intervals.into(),
+            stability: stability.map(Into::into),
+        }))
+    })
+    .await
+}
+
+// Handler for estimating the upcoming study time of each top-level deck
+async fn workload(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<WorkloadQuery>,
+) -> ApiResult<Json<WorkloadResponse>> {
+    with_col(&server, |col| {
+        require!(
+            (1..=MAX_WORKLOAD_DAYS).contains(&query.days),
+            "days must be between 1 and {MAX_WORKLOAD_DAYS}"
+        );
+        require!(
+            query.fallback_secs.is_finite() && query.fallback_secs >= 0.0,
+            "invalid fallbackSecs"
+        );
+        let decks: Vec<DeckWorkloadResponse> = col
+            .estimate_workload(query.days, query.window_days, query.fallback_secs)?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        let mut total_minutes = vec![0.0; query.days as usize];
+        for deck in &decks {
+            for (total, minutes) in total_minutes.iter_mut().zip(&deck.minutes) {
+                *total += minutes;
+            }
+        }
+        Ok(Json(WorkloadResponse {
+            decks,
+            total_minutes,
+        }))
+    })
+    .await
+}
+
+// Handler for estimating how many cards were due on past days, alongside the
+// reviews actually done
+async fn history(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<HistoryQuery>,
+) -> ApiResult<Json<Vec<HistoryDay>>> {
+    with_col(&server, |col| {
+        let parse_date =
+            |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d").or_invalid("invalid date");
+        let from = parse_date(&query.from)?;
+        let to = query.to.as_deref().map(parse_date).transpose()?;
+        let history = col.get_review_history(from, to)?;
+        require!(
+            history.len() as i64 <= MAX_HISTORY_DAYS,
+            "at most {MAX_HISTORY_DAYS} days can be requested"
+        );
+        let first_weekday = col.get_first_day_of_week();
+        let mut grouped: Vec<HistoryDay> = vec![];
+        for day in history {
+            let date = match query.group_by {
+                HistoryGrouping::Day => day.date,
+                HistoryGrouping::Week => first_weekday.start_of_week(day.date),
+            }
+            .format("%Y-%m-%d")
+            .to_string();
+            match grouped.last_mut() {
+                Some(last) if last.date == date => {
+                    last.estimated_due += day.estimated_due;
+                    last.reviewed += day.reviewed;
+                }
+                _ => grouped.push(HistoryDay {
+                    date,
+                    estimated_due: day.estimated_due,
+                    reviewed: day.reviewed,
+                }),
+            }
+        }
+        Ok(Json(grouped))
+    })
+    .await
+}
+
+fn csv_chunk(records: Vec<Vec<String>>) -> Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for record in records {
+        writer.write_record(&record).or_invalid("invalid csv")?;
+    }
+    writer.into_inner().map_err(|err| err.into_error().into())
+}
+
+/// A CSV response with `columns` as its header. Rows are only serialized as
+/// the body is sent, after the collection has been released.
+fn csv_response<T, F>(columns: &[&str], rows: Vec<T>, bom: bool, mut record: F) -> Result<Response>
+where
+    T: Send + 'static,
+    F: FnMut(T) -> Result<Vec<String>> + Send + 'static,
+{
+    let mut header_chunk = if bom {
+        "\u{feff}".as_bytes().to_vec()
+    } else {
+        vec![]
+    };
+    header_chunk.extend(csv_chunk(vec![columns
+        .iter()
+        .map(ToString::to_string)
+        .collect()])?);
+    let mut rows = rows.into_iter();
+    let row_chunks = iter::from_fn(move || {
+        if rows.len() == 0 {
+            return None;
+        }
+        Some(
+            rows.by_ref()
+                .take(CSV_CHUNK_ROWS)
+                .map(&mut record)
+                .collect::<Result<_>>()
+                .and_then(csv_chunk)
+                // the status has already been sent, so an error cuts the
+                // response short instead
+                .map_err(|_| io::Error::other("writing csv failed")),
+        )
+    });
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"export.csv\"",
+            ),
+        ],
+        Body::from_stream(stream::iter(iter::once(Ok(header_chunk)).chain(row_chunks))),
+    )
+        .into_response())
+}
+
+fn review_kind_name(kind: RevlogReviewKind) -> &'static str {
+    match kind {
+        RevlogReviewKind::Learning => "learning",
+        RevlogReviewKind::Review => "review",
+        RevlogReviewKind::Relearning => "relearning",
+        RevlogReviewKind::Filtered => "filtered",
+        RevlogReviewKind::Manual => "manual",
+        RevlogReviewKind::Rescheduled => "rescheduled",
+    }
+}
+
+fn card_type_name(ctype: CardType) -> &'static str {
+    match ctype {
+        CardType::New => "new",
+        CardType::Learn => "learning",
+        CardType::Review => "review",
+        CardType::Relearn => "relearning",
+    }
+}
+
+fn reviews_csv(col: &mut Collection, query: &ExportCsvQuery) -> Result<Response> {
+    let offset = col.local_utc_offset_for_user()?;
+    let deck_names: HashMap<DeckId, String> =
+        col.storage.get_all_deck_names()?.into_iter().collect();
+    let guard = col.search_cards_into_table(query.search.as_str(), SortMode::NoOrder)?;
+    let cards: HashMap<CardId, (NoteId, DeckId)> = guard
+        .col
+        .storage
+        .all_searched_cards()?
+        .into_iter()
+        .map(|card| (card.id, (card.note_id, card.deck_id)))
+        .collect();
+    let mut entries = guard.col.storage.get_revlog_entries_for_searched_cards()?;
+    entries.sort_unstable_by_key(|entry| entry.id);
+    csv_response(
+        &REVIEWS_CSV_COLUMNS,
+        entries,
+        query.bom,
+        move |entry: RevlogEntry| {
+            let (note_id, deck_id) = cards.get(&entry.cid).copied().unwrap_or_default();
+            Ok(vec![
+                entry.id.as_secs().datetime(offset)?.to_rfc3339(),
+                entry.id.to_string(),
+                entry.cid.to_string(),
+                note_id.to_string(),
+                deck_names.get(&deck_id).cloned().unwrap_or_default(),
+                review_kind_name(entry.review_kind).to_string(),
+                entry.button_chosen.to_string(),
+                entry.interval.to_string(),
+                entry.last_interval.to_string(),
+                entry.ease_factor.to_string(),
+                entry.taken_millis.to_string(),
+            ])
+        },
+    )
+}
+
+fn cards_csv(col: &mut Collection, query: &ExportCsvQuery) -> Result<Response> {
+    let offset = col.local_utc_offset_for_user()?;
+    let timing = col.timing_today()?;
+    let deck_names: HashMap<DeckId, String> =
+        col.storage.get_all_deck_names()?.into_iter().collect();
+    let guard = col.search_cards_into_table(query.search.as_str(), SortMode::NoOrder)?;
+    let note_tags = guard.col.storage.note_tags_of_searched_cards()?;
+    let mut cards = guard.col.storage.all_searched_cards()?;
+    cards.sort_unstable_by_key(|card| card.id);
+    csv_response(&CARDS_CSV_COLUMNS, cards, query.bom, move |card: Card| {
+        let due_date = match card.due_time(&timing) {
+            Some(due) if card.ctype != CardType::New => {
+                due.datetime(offset)?.format("%Y-%m-%d").to_string()
+            }
+            _ => String::new(),
+        };
+        let (stability, difficulty) = card
+            .memory_state
+            .map(|state| (state.stability.to_string(), state.difficulty().to_string()))
+            .unwrap_or_default();
+        Ok(vec![
+            card.id.to_string(),
+            card.note_id.to_string(),
+            deck_names.get(&card.deck_id).cloned().unwrap_or_default(),
+            card_type_name(card.ctype).to_string(),
+            due_date,
+            card.interval.to_string(),
+            card.ease_factor.to_string(),
+            stability,
+            difficulty,
+            card.lapses.to_string(),
+            card.reps.to_string(),
+            note_tags
+                .get(&card.note_id)
+                .map(|tags| tags.trim().to_string())
+                .unwrap_or_default(),
+        ])
+    })
+}
+
+// Handler for exporting reviews or card scheduling as CSV, for spreadsheets
+async fn export_csv(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<ExportCsvQuery>,
+) -> ApiResult<Response> {
+    with_col(&server, |col| match query.kind {
+        CsvExportKind::Reviews => reviews_csv(col, &query),
+        CsvExportKind::Cards => cards_csv(col, &query),
+    })
+    .await
+}
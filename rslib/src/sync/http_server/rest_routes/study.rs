@@ -0,0 +1,763 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Study endpoints. `GET /study/next` is stateless; the `/study/sessions`
+//! endpoints keep a cursor on the server for clients that can't hold state,
+//! and operate on the collection's cached card queues.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use anki_proto::scheduler::bury_or_suspend_cards_request::Mode as BuryOrSuspendMode;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::routing::post;
+use axum::Json;
+use axum::Router;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::render_warnings;
+use super::rendered_html;
+use super::with_col;
+use super::with_col_and_media_folder;
+use super::with_user;
+use super::RenderWarningResponse;
+use crate::prelude::*;
+use crate::scheduler::answering::CardAnswer;
+use crate::scheduler::answering::Rating;
+use crate::scheduler::queue::QueueEntryKind;
+use crate::scheduler::queue::QueuedCard;
+use crate::scheduler::queue::QueuedCards;
+use crate::scheduler::states::CardState;
+use crate::scheduler::states::SchedulingStates;
+use crate::sync::error::HttpError;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+/// Sessions that have not been used for this long are discarded.
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Server-side state of a study session opened by a REST client.
+pub(crate) struct StudySession {
+    deck_id: DeckId,
+    /// The scheduler day the session's current card was shown on.
+    day: u32,
+    /// Set when the day has rolled over since the last response, until the
+    /// client has been told.
+    day_rolled_over: bool,
+    /// Siblings the last answer buried, until the client has been told.
+    deferred_siblings: Vec<CardId>,
+    last_used: Instant,
+    answered: usize,
+    media_url_prefix: Option<String>,
+    check_media: bool,
+}
+
+impl StudySession {
+    fn is_idle(&self) -> bool {
+        self.last_used.elapsed() > SESSION_IDLE_TIMEOUT
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StudyNextQuery {
+    deck_id: Option<i64>,
+    /// Rewrite media references in the rendered card into URLs with this
+    /// prefix.
+    media_url_prefix: Option<String>,
+    /// Report media the card refers to that is missing from the media folder.
+    #[serde(default)]
+    check_media: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CongratsQuery {
+    deck_id: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenSessionRequest {
+    deck_id: i64,
+    /// Applies to every card the session returns.
+    media_url_prefix: Option<String>,
+    /// Report missing media for every card the session returns.
+    #[serde(default)]
+    check_media: bool,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AnswerRating {
+    Again,
+    Hard,
+    Good,
+    Easy,
+}
+
+impl From<AnswerRating> for Rating {
+    fn from(rating: AnswerRating) -> Self {
+        match rating {
+            AnswerRating::Again => Rating::Again,
+            AnswerRating::Hard => Rating::Hard,
+            AnswerRating::Good => Rating::Good,
+            AnswerRating::Easy => Rating::Easy,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionAnswerRequest {
+    /// If provided, must match the session's current card.
+    card_id: Option<i64>,
+    rating: AnswerRating,
+    #[serde(default)]
+    milliseconds_taken: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionCardRequest {
+    /// If provided, must match the session's current card.
+    card_id: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueSnapshotRequest {
+    deck_id: i64,
+    #[serde(default = "default_snapshot_limit")]
+    limit: usize,
+    media_url_prefix: Option<String>,
+    #[serde(default)]
+    check_media: bool,
+}
+
+fn default_snapshot_limit() -> usize {
+    50
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchAnswersRequest {
+    /// The `snapshotAt` value of the queue snapshot the answers were made
+    /// against. If provided, cards modified after it are reported as
+    /// conflicts instead of being answered.
+    snapshot_at: Option<i64>,
+    /// At most [MAX_BATCH_ANSWERS].
+    answers: Vec<OfflineAnswer>,
+}
+
+/// The most answers a single batch may contain.
+const MAX_BATCH_ANSWERS: usize = 1000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OfflineAnswer {
+    card_id: i64,
+    rating: AnswerRating,
+    /// When the card was answered; recorded as the review's time. Must not
+    /// be in the future, or before the snapshot the answer was made against.
+    answered_at_millis: i64,
+    #[serde(default)]
+    milliseconds_taken: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StudyCounts {
+    new: usize,
+    learning: usize,
+    review: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StudyCard {
+    card_id: i64,
+    note_id: i64,
+    deck_id: i64,
+    kind: &'static str,
+    question: String,
+    answer: String,
+    /// Problems with the rendered card, such as media missing from the media
+    /// folder.
+    warnings: Vec<RenderWarningResponse>,
+    /// Labels for the again/hard/good/easy buttons.
+    button_labels: Vec<String>,
+    /// The intervals the again/hard/good/easy buttons would give, in seconds.
+    interval_secs: Vec<u32>,
+    /// Modification time of the card, in seconds.
+    modified: i64,
+    /// True if another card of the same note is also due today.
+    sibling_due_today: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StudyNextResponse {
+    card: Option<StudyCard>,
+    counts: StudyCounts,
+}
+
+/// Why there is nothing left to study in a deck.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CongratsResponse {
+    learn_remaining: u32,
+    secs_until_next_learn: u32,
+    /// Reviews remain, but the deck's limit has been reached.
+    review_remaining: bool,
+    /// New cards remain, but the deck's limit has been reached or new cards
+    /// are paused.
+    new_remaining: bool,
+    /// New cards are paused in the deck or one of its subdecks.
+    new_paused: bool,
+    have_sched_buried: bool,
+    have_user_buried: bool,
+    filtered: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StudySessionResponse {
+    session_id: String,
+    deck_id: i64,
+    answered: usize,
+    card: Option<StudyCard>,
+    counts: StudyCounts,
+    /// Only included when the day has rolled over since the previous
+    /// response, so the queues were rebuilt and the counts changed.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    day_rolled_over: bool,
+    /// Siblings of the card just answered that the answer buried, so they
+    /// will not be shown until tomorrow.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    deferred_siblings: Vec<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueSnapshotResponse {
+    deck_id: i64,
+    snapshot_at: i64,
+    cards: Vec<StudyCard>,
+    counts: StudyCounts,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BatchAnswerStatus {
+    Answered,
+    Conflict,
+    NotFound,
+    Failed,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchAnswerResult {
+    card_id: i64,
+    answered_at_millis: i64,
+    status: BatchAnswerStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchAnswersResponse {
+    answered: usize,
+    results: Vec<BatchAnswerResult>,
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new()
+        .route("/study/next", get(study_next))
+        .route("/study/congrats", get(congrats))
+        .route("/study/queue-snapshot", post(queue_snapshot))
+        .route("/study/answers/batch", post(answer_batch))
+        .route("/study/sessions", post(open_session))
+        .route("/study/sessions/{session_id}/current", get(session_current))
+        .route("/study/sessions/{session_id}/answer", post(session_answer))
+        .route("/study/sessions/{session_id}/bury", post(session_bury))
+        .route(
+            "/study/sessions/{session_id}/suspend",
+            post(session_suspend),
+        )
+}
+
+fn queue_kind_name(kind: QueueEntryKind) -> &'static str {
+    match kind {
+        QueueEntryKind::New => "new",
+        QueueEntryKind::Learning => "learning",
+        QueueEntryKind::Review => "review",
+    }
+}
+
+fn study_counts(queued: &QueuedCards) -> StudyCounts {
+    StudyCounts {
+        new: queued.new_count,
+        learning: queued.learning_count,
+        review: queued.review_count,
+    }
+}
+
+fn study_card(
+    col: &mut Collection,
+    queued: &QueuedCard,
+    media_url_prefix: Option<&str>,
+    media_folder: Option<&std::path::Path>,
+) -> Result<StudyCard> {
+    let rendered = col.render_existing_card(queued.card.id, false, false)?;
+    let timing = col.timing_today()?;
+    let secs_until_rollover = timing.next_day_at.elapsed_secs_since(timing.now).max(0) as u32;
+    let states = &queued.states;
+    let sibling_due_today = col
+        .storage
+        .sibling_info(queued.card.id, queued.card.note_id)?
+        .iter()
+        .any(|sibling| sibling.is_due_today(&timing));
+    Ok(StudyCard {
+        card_id: queued.card.id.0,
+        note_id: queued.card.note_id.0,
+        deck_id: queued.card.deck_id.0,
+        kind: queue_kind_name(queued.kind),
+        question: rendered_html(&rendered.question(), media_url_prefix),
+        answer: rendered_html(&rendered.answer(), media_url_prefix),
+        warnings: render_warnings(&rendered, media_folder),
+        button_labels: col.describe_next_states(states)?,
+        interval_secs: [states.again, states.hard, states.good, states.easy]
+            .iter()
+            .map(|state| {
+                state
+                    .interval_kind()
+                    .maybe_as_days(secs_until_rollover)
+                    .as_seconds()
+            })
+            .collect(),
+        modified: queued.card.mtime.0,
+        sibling_due_today,
+    })
+}
+
+/// Select `deck_id` for study, unless it is already selected. Changing the
+/// current deck causes the card queues to be rebuilt.
+fn select_deck(col: &mut Collection, deck_id: DeckId) -> Result<()> {
+    if col.get_current_deck()?.id != deck_id {
+        col.get_deck(deck_id)?.or_not_found(deck_id)?;
+        col.set_current_deck(deck_id)?;
+    }
+    Ok(())
+}
+
+/// Look up a live session, discarding it if it has expired. If the day has
+/// rolled over since the session was last used, the card queues are rebuilt,
+/// and the next response tells the client.
+fn live_session<'a>(
+    col: &mut Collection,
+    sessions: &'a mut HashMap<String, StudySession>,
+    session_id: &str,
+) -> ApiResult<&'a mut StudySession> {
+    let timing = col.timing_today()?;
+    let expired = match sessions.get(session_id) {
+        Some(session) => session.is_idle(),
+        None => {
+            return Err(HttpError::new_without_source(
+                StatusCode::NOT_FOUND,
+                "no such study session",
+            )
+            .into())
+        }
+    };
+    if expired {
+        sessions.remove(session_id);
+        return Err(
+            HttpError::new_without_source(StatusCode::GONE, "study session expired").into(),
+        );
+    }
+    let session = sessions.get_mut(session_id).unwrap();
+    session.last_used = Instant::now();
+    if session.day != timing.days_elapsed {
+        // the queues may have been dropped since they were built, in which
+        // case building them again would not unbury yesterday's cards
+        col.unbury_if_day_rolled_over(timing)?;
+        col.clear_study_queues();
+        session.day = timing.days_elapsed;
+        session.day_rolled_over = true;
+    }
+    Ok(session)
+}
+
+/// Run `op` with the collection positioned on the session's deck, then
+/// return the session's current card.
+async fn with_session<F>(
+    server: &SimpleServer,
+    session_id: &str,
+    op: F,
+) -> ApiResult<Json<StudySessionResponse>>
+where
+    F: FnOnce(&mut Collection, &mut StudySession) -> ApiResult<()>,
+{
+    with_user(server, |user| {
+        user.ensure_col_open()?;
+        let col = user.col.as_mut().unwrap();
+        let session = live_session(col, &mut user.study_sessions, session_id)?;
+        select_deck(col, session.deck_id)?;
+        op(col, session)?;
+        let response = session_response(col, session_id, session, &user.media.media_folder)?;
+        session.day_rolled_over = false;
+        session.deferred_siblings.clear();
+        Ok(response)
+    })
+    .await
+}
+
+fn session_response(
+    col: &mut Collection,
+    session_id: &str,
+    session: &StudySession,
+    media_folder: &std::path::Path,
+) -> Result<Json<StudySessionResponse>> {
+    let queued = col.get_queued_cards(1, false)?;
+    let card = match queued.cards.first() {
+        Some(card) => Some(study_card(
+            col,
+            card,
+            session.media_url_prefix.as_deref(),
+            session.check_media.then_some(media_folder),
+        )?),
+        None => None,
+    };
+    Ok(Json(StudySessionResponse {
+        session_id: session_id.to_string(),
+        deck_id: session.deck_id.0,
+        answered: session.answered,
+        card,
+        counts: study_counts(&queued),
+        day_rolled_over: session.day_rolled_over,
+        deferred_siblings: session.deferred_siblings.iter().map(|id| id.0).collect(),
+    }))
+}
+
+fn rating_state(states: &SchedulingStates, rating: Rating) -> CardState {
+    match rating {
+        Rating::Again => states.again,
+        Rating::Hard => states.hard,
+        Rating::Good => states.good,
+        Rating::Easy => states.easy,
+    }
+}
+
+/// Answer a card outside of the study queues, as it was answered offline.
+/// `snapshot_at` is the time of the snapshot the answer was made against, if
+/// known. With `check_conflict`, a card modified since then is not answered.
+fn answer_offline(
+    col: &mut Collection,
+    answer: &OfflineAnswer,
+    snapshot_at: Option<TimestampSecs>,
+    check_conflict: bool,
+) -> Result<BatchAnswerStatus> {
+    let card_id = CardId(answer.card_id);
+    let answered_at = TimestampMillis(answer.answered_at_millis);
+    require!(
+        answered_at <= TimestampMillis::now(),
+        "answeredAtMillis is in the future"
+    );
+    let Some(card) = col.storage.get_card(card_id)? else {
+        return Ok(BatchAnswerStatus::NotFound);
+    };
+    if let Some(snapshot_at) = snapshot_at {
+        require!(
+            answered_at.as_secs() >= snapshot_at,
+            "answeredAtMillis is before snapshotAt"
+        );
+        if check_conflict && card.mtime > snapshot_at {
+            return Ok(BatchAnswerStatus::Conflict);
+        }
+    }
+    let states = col.get_scheduling_states(card_id)?;
+    let rating = Rating::from(answer.rating);
+    col.answer_card(&mut CardAnswer {
+        card_id,
+        current_state: states.current,
+        new_state: rating_state(&states, rating),
+        rating,
+        answered_at,
+        milliseconds_taken: answer.milliseconds_taken,
+        custom_data: None,
+        from_queue: false,
+    })?;
+    Ok(BatchAnswerStatus::Answered)
+}
+
+/// Return the card at the top of the queue, checking it against the card the
+/// client believes it is acting on.
+fn current_card(col: &mut Collection, expected: Option<i64>) -> ApiResult<QueuedCard> {
+    let card = col
+        .get_queued_cards(1, false)?
+        .cards
+        .into_iter()
+        .next()
+        .ok_or_else(|| HttpError::new_without_source(StatusCode::CONFLICT, "no card to study"))?;
+    if let Some(expected) = expected {
+        if card.card.id.0 != expected {
+            return Err(HttpError::new_without_source(
+                StatusCode::CONFLICT,
+                "card is not the session's current card",
+            )
+            .into());
+        }
+    }
+    Ok(card)
+}
+
+// Handler for stateless study
+async fn study_next(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<StudyNextQuery>,
+) -> ApiResult<Json<StudyNextResponse>> {
+    with_col_and_media_folder(&server, |col, media_folder| {
+        if let Some(deck_id) = query.deck_id {
+            select_deck(col, DeckId(deck_id))?;
+        }
+        let queued = col.get_queued_cards(1, false)?;
+        let card = match queued.cards.first() {
+            Some(card) => Some(study_card(
+                col,
+                card,
+                query.media_url_prefix.as_deref(),
+                query.check_media.then_some(media_folder),
+            )?),
+            None => None,
+        };
+        Ok(Json(StudyNextResponse {
+            card,
+            counts: study_counts(&queued),
+        }))
+    })
+    .await
+}
+
+// Handler for explaining an empty queue, like the desktop's congratulations
+// screen
+async fn congrats(
+    State(server): State<Arc<SimpleServer>>,
+    Query(query): Query<CongratsQuery>,
+) -> ApiResult<Json<CongratsResponse>> {
+    with_col(&server, |col| {
+        if let Some(deck_id) = query.deck_id {
+            select_deck(col, DeckId(deck_id))?;
+        }
+        let info = col.congrats_info()?;
+        Ok(Json(CongratsResponse {
+            learn_remaining: info.learn_remaining,
+            secs_until_next_learn: info.secs_until_next_learn,
+            review_remaining: info.review_remaining,
+            new_remaining: info.new_remaining,
+            new_paused: info.new_paused,
+            have_sched_buried: info.have_sched_buried,
+            have_user_buried: info.have_user_buried,
+            filtered: info.is_filtered_deck,
+        }))
+    })
+    .await
+}
+
+// Handler for downloading upcoming cards for offline review
+async fn queue_snapshot(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<QueueSnapshotRequest>, JsonRejection>,
+) -> ApiResult<Json<QueueSnapshotResponse>> {
+    let payload = payload?;
+    with_col_and_media_folder(&server, |col, media_folder| {
+        let deck_id = DeckId(payload.deck_id);
+        select_deck(col, deck_id)?;
+        let snapshot_at = TimestampSecs::now();
+        let queued = col.get_queued_cards(payload.limit, false)?;
+        let media_folder = payload.check_media.then_some(media_folder);
+        let cards = queued
+            .cards
+            .iter()
+            .map(|card| study_card(col, card, payload.media_url_prefix.as_deref(), media_folder))
+            .collect::<Result<_>>()?;
+        Ok(Json(QueueSnapshotResponse {
+            deck_id: deck_id.0,
+            snapshot_at: snapshot_at.0,
+            cards,
+            counts: study_counts(&queued),
+        }))
+    })
+    .await
+}
+
+// Handler for submitting answers made offline
+async fn answer_batch(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<BatchAnswersRequest>, JsonRejection>,
+) -> ApiResult<Json<BatchAnswersResponse>> {
+    let mut payload = payload?;
+    with_col(&server, |col| {
+        require!(
+            payload.answers.len() <= MAX_BATCH_ANSWERS,
+            "at most {MAX_BATCH_ANSWERS} answers can be submitted at once"
+        );
+        payload
+            .answers
+            .sort_by_key(|answer| answer.answered_at_millis);
+        let snapshot_at = payload.snapshot_at.map(TimestampSecs);
+        // a card answered more than once offline is modified by its first
+        // applied answer, so later answers are not checked for conflicts
+        let mut answered_cards = HashSet::new();
+        let mut answered = 0;
+        let results = payload
+            .answers
+            .iter()
+            .map(|answer| {
+                let check_conflict = !answered_cards.contains(&answer.card_id);
+                let (status, message) =
+                    match answer_offline(col, answer, snapshot_at, check_conflict) {
+                        Ok(status) => (status, None),
+                        Err(err) => (BatchAnswerStatus::Failed, Some(err.message(&col.tr))),
+                    };
+                if matches!(status, BatchAnswerStatus::Answered) {
+                    answered_cards.insert(answer.card_id);
+                    answered += 1;
+                }
+                BatchAnswerResult {
+                    card_id: answer.card_id,
+                    answered_at_millis: answer.answered_at_millis,
+                    status,
+                    message,
+                }
+            })
+            .collect();
+        // the answers bypassed the study queues
+        col.clear_study_queues();
+        Ok(Json(BatchAnswersResponse { answered, results }))
+    })
+    .await
+}
+
+// Handler for opening a study session
+async fn open_session(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<OpenSessionRequest>, JsonRejection>,
+) -> ApiResult<Json<StudySessionResponse>> {
+    let Json(payload) = payload?;
+    with_user(&server, |user| {
+        user.ensure_col_open()?;
+        user.study_sessions.retain(|_, session| !session.is_idle());
+        let col = user.col.as_mut().unwrap();
+        let deck_id = DeckId(payload.deck_id);
+        select_deck(col, deck_id)?;
+        let session = StudySession {
+            deck_id,
+            day: col.timing_today()?.days_elapsed,
+            day_rolled_over: false,
+            deferred_siblings: vec![],
+            last_used: Instant::now(),
+            answered: 0,
+            media_url_prefix: payload.media_url_prefix,
+            check_media: payload.check_media,
+        };
+        let session_id = format!("{:016x}", rand::random::<u64>());
+        let response = session_response(col, &session_id, &session, &user.media.media_folder)?;
+        user.study_sessions.insert(session_id, session);
+        Ok(response)
+    })
+    .await
+}
+
+// Handler for getting a session's current card
+async fn session_current(
+    State(server): State<Arc<SimpleServer>>,
+    Path(session_id): Path<String>,
+) -> ApiResult<Json<StudySessionResponse>> {
+    with_session(&server, &session_id, |_col, _session| Ok(())).await
+}
+
+// Handler for answering a session's current card
+async fn session_answer(
+    State(server): State<Arc<SimpleServer>>,
+    Path(session_id): Path<String>,
+    payload: Result<Json<SessionAnswerRequest>, JsonRejection>,
+) -> ApiResult<Json<StudySessionResponse>> {
+    let payload = payload?;
+    with_session(&server, &session_id, |col, session| {
+        // the card's states the client answered against were calculated for
+        // the previous day, and may not be valid today
+        if session.day_rolled_over {
+            return Err(HttpError::new_without_source(
+                StatusCode::PRECONDITION_FAILED,
+                "the day rolled over since the card was shown; fetch the current card again",
+            )
+            .into());
+        }
+        let queued = current_card(col, payload.card_id)?;
+        let rating = Rating::from(payload.rating);
+        let output = col.answer_card(&mut CardAnswer {
+            card_id: queued.card.id,
+            current_state: queued.states.current,
+            new_state: rating_state(&queued.states, rating),
+            rating,
+            answered_at: TimestampMillis::now(),
+            milliseconds_taken: payload.milliseconds_taken,
+            custom_data: None,
+            from_queue: true,
+        })?;
+        // the answered card is the only other card an answer changes
+        session.deferred_siblings = output
+            .changes
+            .ids
+            .cards
+            .into_iter()
+            .filter(|&id| id != queued.card.id)
+            .collect();
+        session.answered += 1;
+        Ok(())
+    })
+    .await
+}
+
+// Handler for burying a session's current card
+async fn session_bury(
+    State(server): State<Arc<SimpleServer>>,
+    Path(session_id): Path<String>,
+    payload: Result<Json<SessionCardRequest>, JsonRejection>,
+) -> ApiResult<Json<StudySessionResponse>> {
+    let payload = payload?;
+    with_session(&server, &session_id, |col, _session| {
+        let queued = current_card(col, payload.card_id)?;
+        col.bury_or_suspend_cards(&[queued.card.id], BuryOrSuspendMode::BuryUser)?;
+        Ok(())
+    })
+    .await
+}
+
+// Handler for suspending a session's current card
+async fn session_suspend(
+    State(server): State<Arc<SimpleServer>>,
+    Path(session_id): Path<String>,
+    payload: Result<Json<SessionCardRequest>, JsonRejection>,
+) -> ApiResult<Json<StudySessionResponse>> {
+    let payload = payload?;
+    with_session(&server, &session_id, |col, _session| {
+        let queued = current_card(col, payload.card_id)?;
+        col.bury_or_suspend_cards(&[queued.card.id], BuryOrSuspendMode::Suspend)?;
+        Ok(())
+    })
+    .await
+}
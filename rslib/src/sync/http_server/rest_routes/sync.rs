@@ -0,0 +1,68 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::routing::get;
+use axum::Json;
+use axum::Router;
+use serde::Serialize;
+
+use super::with_col;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+// Payloads for the API
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingChangesResponse {
+    cards: usize,
+    notes: usize,
+    decks: usize,
+    notetypes: usize,
+    revlog: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStatusResponse {
+    /// Objects changed since the last sync.
+    pending: PendingChangesResponse,
+    /// In milliseconds; [None] if the collection has never been synced.
+    last_sync: Option<i64>,
+    /// In milliseconds.
+    modified: i64,
+    /// In milliseconds.
+    schema_modified: i64,
+    /// True if the next sync will have to be a one-way one.
+    full_sync_required: bool,
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new().route("/sync/status", get(sync_status))
+}
+
+// Handler for summarizing the changes the next sync will send
+async fn sync_status(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<SyncStatusResponse>> {
+    with_col(&server, |col| {
+        let status = col.pending_sync_status()?;
+        Ok(Json(SyncStatusResponse {
+            pending: PendingChangesResponse {
+                cards: status.pending.cards,
+                notes: status.pending.notes,
+                decks: status.pending.decks,
+                notetypes: status.pending.notetypes,
+                revlog: status.pending.revlog,
+            },
+            last_sync: (status.last_sync.0 != 0).then_some(status.last_sync.0),
+            modified: status.modified.0,
+            schema_modified: status.schema_modified.0,
+            full_sync_required: status.full_sync_required,
+        }))
+    })
+    .await
+}
@@ -0,0 +1,133 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::sync::Arc;
+
+use axum::extract::Path;
+use axum::extract::State;
+use axum::routing::get;
+use axum::Json;
+use axum::Router;
+use serde::Serialize;
+
+use super::with_col;
+use crate::prelude::*;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+use crate::tags::split_tags;
+
+/// The longest tag accepted from clients, in characters.
+const MAX_TAG_LENGTH: usize = 255;
+
+// Payloads for the API
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagDeckResponse {
+    deck_id: i64,
+    name: String,
+    /// The number of cards in the deck whose notes have the tag.
+    card_count: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagDecksResponse {
+    tag: String,
+    decks: Vec<TagDeckResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCountsResponse {
+    /// The full name, eg `parent::child`.
+    name: String,
+    /// Notes with exactly this tag.
+    note_count: usize,
+    /// Notes with this tag or any of its child tags.
+    total_note_count: usize,
+    /// Cards due today whose notes have this tag or any of its child tags.
+    due_card_count: usize,
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new()
+        .route("/tags/counts", get(tag_counts))
+        .route("/tags/{tag}/decks", get(decks_containing_tag))
+}
+
+// Handler for note and due card counts of every tag in the tag tree
+async fn tag_counts(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<Vec<TagCountsResponse>>> {
+    with_col(&server, |col| {
+        Ok(Json(
+            col.get_tag_counts()?
+                .into_iter()
+                .map(|counts| TagCountsResponse {
+                    name: counts.name,
+                    note_count: counts.notes,
+                    total_note_count: counts.total_notes,
+                    due_card_count: counts.due_cards,
+                })
+                .collect(),
+        ))
+    })
+    .await
+}
+
+// Handler for listing the decks with notes that have a tag or its child tags
+async fn decks_containing_tag(
+    State(server): State<Arc<SimpleServer>>,
+    Path(tag): Path<String>,
+) -> ApiResult<Json<TagDecksResponse>> {
+    with_col(&server, |col| {
+        let decks = col
+            .get_decks_containing_tag(&tag)?
+            .into_iter()
+            .map(|(did, name, card_count)| TagDeckResponse {
+                deck_id: did.0,
+                name,
+                card_count,
+            })
+            .collect();
+        Ok(Json(TagDecksResponse { tag, decks }))
+    })
+    .await
+}
+
+/// Tags sent by a client, normalized as the desktop would: split on spaces,
+/// stripped of invalid characters, matched to the case of existing tags,
+/// deduplicated and sorted. Tags that are blank, before or after stripping,
+/// or too long are rejected.
+pub(super) fn normalize_tags(col: &mut Collection, tags: &[String]) -> Result<Vec<String>> {
+    for tag in tags {
+        require!(split_tags(tag).next().is_some(), "blank tag");
+        for name in split_tags(tag) {
+            // the desktop would save these as 'blank'
+            require!(
+                name.split("::")
+                    .all(|component| !is_blank_tag_component(component)),
+                "blank tag: {name:?}"
+            );
+        }
+    }
+    let usn = col.usn()?;
+    let tags = col.canonify_tags_without_registering(tags.to_vec(), usn)?;
+    for tag in &tags {
+        require!(
+            tag.chars().count() <= MAX_TAG_LENGTH,
+            "tag longer than {MAX_TAG_LENGTH} characters: {tag}"
+        );
+    }
+    Ok(tags)
+}
+
+fn is_blank_tag_component(component: &str) -> bool {
+    component
+        .chars()
+        .filter(|c| !c.is_ascii_control())
+        .collect::<String>()
+        .trim()
+        .is_empty()
+}
@@ -0,0 +1,56 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::routing::get;
+use axum::Json;
+use axum::Router;
+use serde::Serialize;
+
+use super::with_col;
+use crate::notetype::TemplateWithNotetype;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+// Payloads for the API
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateSummaryResponse {
+    notetype_id: i64,
+    notetype_name: String,
+    template_ord: u16,
+    template_name: String,
+}
+
+impl From<TemplateWithNotetype> for TemplateSummaryResponse {
+    fn from(template: TemplateWithNotetype) -> Self {
+        Self {
+            notetype_id: template.notetype_id.0,
+            notetype_name: template.notetype_name,
+            template_ord: template.template_ord,
+            template_name: template.template_name,
+        }
+    }
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new().route("/templates", get(list_templates))
+}
+
+// Handler for listing the templates of all notetypes
+async fn list_templates(
+    State(server): State<Arc<SimpleServer>>,
+) -> ApiResult<Json<Vec<TemplateSummaryResponse>>> {
+    with_col(&server, |col| {
+        Ok(Json(
+            col.get_all_templates_with_notetypes()?
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        ))
+    })
+    .await
+}
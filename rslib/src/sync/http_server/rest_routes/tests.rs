@@ -0,0 +1,4275 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+#![cfg(test)]
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::net::IpAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use anki_io::create_dir_all;
+use anki_io::read_file;
+use anki_io::write_file;
+use axum::body::to_bytes;
+use axum::body::Body;
+use axum::body::Bytes;
+use axum::http::header;
+use axum::http::Method;
+use axum::http::Request;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::Router;
+use chrono::Datelike;
+use chrono::Days;
+use serde_json::json;
+use serde_json::Value;
+use sha2::Digest;
+use sha2::Sha256;
+use tempfile::tempdir;
+use tempfile::TempDir;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tower::ServiceExt;
+use wiremock::matchers::method;
+use wiremock::matchers::path;
+use wiremock::Mock;
+use wiremock::MockServer;
+use wiremock::ResponseTemplate;
+use zip::ZipArchive;
+
+use crate::card::CardQueue;
+use crate::card::CardType;
+use crate::card::FsrsMemoryState;
+use crate::collection::CollectionBuilder;
+use crate::decks::FilteredSearchOrder;
+use crate::error::NetworkError;
+use crate::error::NetworkErrorKind;
+use crate::import_export::package::ExportAnkiPackageOptions;
+use crate::media::files::sha1_of_data;
+use crate::notetype::LONG_CSS_BYTES;
+use crate::prelude::*;
+use crate::revlog::RevlogEntry;
+use crate::revlog::RevlogReviewKind;
+use crate::search::SearchNode;
+use crate::sync::http_server::default_delete_confirm_threshold;
+use crate::sync::http_server::default_login_lockout_threshold;
+use crate::sync::http_server::media_manager::ServerMediaManager;
+use crate::sync::http_server::rest::rest_router;
+use crate::sync::http_server::rest::RateLimiter;
+use crate::sync::http_server::rest_routes::lock_state;
+use crate::sync::http_server::rest_routes::undo_group::with_undo_groups;
+use crate::sync::http_server::rest_routes::undo_group::UndoGroup;
+use crate::sync::http_server::rest_routes::undo_group::MAX_UNDO_GROUP_REQUESTS;
+use crate::sync::http_server::rest_routes::undo_group::UNDO_GROUP_EXPIRY;
+use crate::sync::http_server::rest_routes::undo_group::UNDO_GROUP_HEADER;
+use crate::sync::http_server::rest_routes::undo_group::UNDO_GROUP_NAME_HEADER;
+use crate::sync::http_server::rest_routes::with_user;
+use crate::sync::http_server::rest_routes::CONFIRM_DESTRUCTIVE_HEADER;
+use crate::sync::http_server::user::User;
+use crate::sync::http_server::ApiError;
+use crate::sync::http_server::SimpleServer;
+use crate::sync::http_server::SimpleServerInner;
+use crate::sync::login::HostKeyRequest;
+use crate::sync::media::zip::UploadedChange;
+use crate::sync::media::zip::UploadedChangeKind;
+
+mod serialization;
+
+/// A REST router backed by a single user with a fresh collection.
+struct TestServer {
+    router: Router,
+    server: Arc<SimpleServer>,
+    // kept alive for the duration of the test
+    _folder: TempDir,
+}
+
+impl TestServer {
+    fn new() -> Result<Self> {
+        Self::configured(|_| {})
+    }
+
+    fn with_delete_confirm_threshold(delete_confirm_threshold: usize) -> Result<Self> {
+        Self::configured(|server| server.delete_confirm_threshold = delete_confirm_threshold)
+    }
+
+    /// A server whose settings have been changed by `configure`.
+    fn configured(configure: impl FnOnce(&mut SimpleServer)) -> Result<Self> {
+        let base_folder = tempdir()?;
+        let folder = base_folder.path().join("user");
+        create_dir_all(&folder)?;
+        let media = ServerMediaManager::new(&folder).unwrap();
+        let user = User {
+            name: "user".into(),
+            password_hash: String::new(),
+            col: None,
+            sync_state: None,
+            media,
+            folder,
+            study_sessions: Default::default(),
+            export_progress: Default::default(),
+            export_running: Default::default(),
+            import_logs: Default::default(),
+            last_backup: None,
+            undo_group: None,
+        };
+        let mut server = SimpleServer {
+            state: Mutex::new(SimpleServerInner {
+                users: HashMap::from([("hkey".to_string(), user)]),
+            }),
+            delete_confirm_threshold: default_delete_confirm_threshold(),
+            rate_limiter: test_rate_limiter(),
+            public_config: Default::default(),
+            login_throttle: Default::default(),
+            read_only: Default::default(),
+            import_allow_private_hosts: false,
+            admin_token: Some(ADMIN_TOKEN.into()),
+            copy_base: None,
+            undo_group_gate: Default::default(),
+        };
+        configure(&mut server);
+        let server = Arc::new(server);
+        let router = Router::new().nest(
+            "/api/v1",
+            with_undo_groups(rest_router(server.clone()), server.clone()),
+        );
+        #[cfg(feature = "web-ui")]
+        let router = router.nest("/ui", crate::sync::http_server::web_ui::routes());
+        Ok(TestServer {
+            router: router.with_state(server.clone()),
+            server,
+            _folder: base_folder,
+        })
+    }
+
+    /// Access the collection directly, eg to set up state the API can't.
+    fn with_col<T>(&self, op: impl FnOnce(&mut Collection) -> Result<T>) -> T {
+        let mut state = self.server.state.lock().unwrap();
+        let user = state.users.values_mut().next().unwrap();
+        user.ensure_col_open().unwrap();
+        op(user.col.as_mut().unwrap()).unwrap()
+    }
+
+    async fn request(&self, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let (status, bytes) = self.request_raw(method, uri, body).await;
+        let json = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+        (status, json)
+    }
+
+    async fn request_raw(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Bytes) {
+        let body = match body {
+            Some(body) => Body::from(body.to_string()),
+            None => Body::empty(),
+        };
+        self.request_body(method, uri, body).await
+    }
+
+    async fn request_body(&self, method: Method, uri: &str, body: Body) -> (StatusCode, Bytes) {
+        let response = self.response(method, uri, body).await;
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, bytes)
+    }
+
+    /// As [TestServer::request], also sending `headers`.
+    async fn request_with_headers(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+        headers: &[(&str, &str)],
+    ) -> (StatusCode, Value) {
+        let body = match body {
+            Some(body) => Body::from(body.to_string()),
+            None => Body::empty(),
+        };
+        let response = self.response_with_headers(method, uri, body, headers).await;
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+        (status, json)
+    }
+
+    /// As [TestServer::request], authorized with the admin token.
+    async fn admin_request(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let authorization = format!("Bearer {ADMIN_TOKEN}");
+        self.request_with_headers(method, uri, body, &[("authorization", &authorization)])
+            .await
+    }
+
+    /// The unprocessed response, eg to check its headers.
+    async fn response(&self, method: Method, uri: &str, body: Body) -> Response {
+        self.response_with_headers(method, uri, body, &[]).await
+    }
+
+    async fn response_with_headers(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Body,
+        headers: &[(&str, &str)],
+    ) -> Response {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(format!("/api/v1{uri}"))
+            .header("content-type", "application/json");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let request = request.body(body).unwrap();
+        self.router.clone().oneshot(request).await.unwrap()
+    }
+
+    /// Make a request in a separate task, so that the test can do other things
+    /// while it runs.
+    fn spawn_request(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+        headers: &[(&str, &str)],
+    ) -> JoinHandle<Response> {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(format!("/api/v1{uri}"))
+            .header("content-type", "application/json");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+        let request = request.body(body).unwrap();
+        let router = self.router.clone();
+        tokio::spawn(async move { router.oneshot(request).await.unwrap() })
+    }
+
+    async fn add_basic_card(&self, front: &str) -> i64 {
+        let (status, body) = self
+            .request(
+                Method::POST,
+                "/cards",
+                Some(json!({
+                    "deckName": "Default",
+                    "notetypeName": "Basic",
+                    "fields": {"Front": front, "Back": "back"},
+                    "tags": [],
+                })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        body["cardIds"][0].as_i64().unwrap()
+    }
+}
+
+/// The admin token of test servers.
+const ADMIN_TOKEN: &str = "admin-token";
+
+/// Roomy enough that tests calling an expensive endpoint several times are
+/// not limited.
+fn test_rate_limiter() -> RateLimiter {
+    RateLimiter::new(1_000, 1_000.0)
+}
+
+fn status_of(err: AnkiError) -> StatusCode {
+    ApiError::from(err).into_response().status()
+}
+
+#[tokio::test]
+async fn card_lifecycle() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+
+    let (status, card) = server
+        .request(Method::GET, &format!("/cards/{cid}"), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(card["cardId"], cid);
+    assert!(card["renderedFront"].as_str().unwrap().contains("front"));
+    let new_due = card["due"].as_i64().unwrap();
+    assert_eq!(card["queuePosition"], new_due);
+
+    let (status, _) = server
+        .request(
+            Method::PUT,
+            &format!("/cards/{cid}"),
+            Some(json!({"fields": {"Front": "updated"}})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, card) = server
+        .request(Method::GET, &format!("/cards/{cid}"), None)
+        .await;
+    assert!(card["renderedFront"].as_str().unwrap().contains("updated"));
+
+    let (status, _) = server
+        .request(
+            Method::PUT,
+            &format!("/cards/{cid}/schedule"),
+            Some(json!({"due": "5"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, card) = server
+        .request(Method::GET, &format!("/cards/{cid}"), None)
+        .await;
+    assert_ne!(card["due"].as_i64().unwrap(), new_due);
+    assert_eq!(card["interval"], 5);
+    assert_eq!(card["queuePosition"], Value::Null);
+
+    let (status, body) = server
+        .request(Method::DELETE, "/cards", Some(json!({"cardIds": [cid]})))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["deletedCount"], 1);
+    let (status, _) = server
+        .request(Method::GET, &format!("/cards/{cid}"), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn fuzzed_schedule() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let today = server.with_col(|col| Ok(col.timing_today()?.days_elapsed as i64));
+
+    // fuzz is disabled in tests, so the card lands on the requested day
+    for body in [
+        json!({"due": "+7d", "fuzz": true}),
+        json!({"due": "7!", "fuzz": true}),
+        json!({"due": "7~"}),
+    ] {
+        let (status, _) = server
+            .request(Method::PUT, &format!("/cards/{cid}/schedule"), Some(body))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, card) = server
+            .request(Method::GET, &format!("/cards/{cid}"), None)
+            .await;
+        assert_eq!(card["due"], today + 7);
+    }
+
+    // ranges can't be fuzzed
+    let (status, _) = server
+        .request(
+            Method::PUT,
+            &format!("/cards/{cid}/schedule"),
+            Some(json!({"due": "5-9", "fuzz": true})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (_, body) = server
+        .request(
+            Method::POST,
+            "/cards/schedule",
+            Some(json!({"cards": [
+                {"cardId": cid, "due": "3", "fuzz": true},
+                {"cardId": cid, "due": "1-2", "fuzz": true},
+            ]})),
+        )
+        .await;
+    assert_eq!(body["updated"], 1);
+    assert_eq!(body["results"][1]["success"], false);
+    Ok(())
+}
+
+#[tokio::test]
+async fn dry_runs() -> Result<()> {
+    let server = TestServer::new()?;
+    let first = server.add_basic_card("one").await;
+    let second = server.add_basic_card("two").await;
+    let is_new = |cid: i64| {
+        server.with_col(move |col| {
+            Ok(col.storage.get_card(CardId(cid))?.unwrap().queue == CardQueue::New)
+        })
+    };
+
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/cards/schedule",
+            Some(json!({"dryRun": true, "cards": [
+                {"cardId": first, "due": "3"},
+                {"cardId": second, "due": "3!"},
+                {"cardId": 123, "due": "1"},
+            ]})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["updated"], 2);
+    assert_eq!(
+        (
+            &body["results"][0]["dueInDays"],
+            &body["results"][0]["interval"]
+        ),
+        (&json!(3), &json!(3))
+    );
+    assert_eq!(body["results"][2]["success"], false);
+    assert_eq!(body["histogram"], json!([{"dueInDays": 3, "count": 2}]));
+    assert_eq!(body["changes"]["cardIds"], json!([]));
+    assert!(is_new(first) && is_new(second));
+
+    // an easy new card graduates straight to review
+    let grade = |dry_run: bool| json!({"cardIds": [first], "rating": "easy", "dryRun": dry_run});
+    let (status, body) = server
+        .request(Method::POST, "/cards/grade", Some(grade(true)))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["results"],
+        json!([{"cardId": first, "dueInDays": 4, "interval": 4, "lapse": false}])
+    );
+    assert_eq!(body["histogram"], json!([{"dueInDays": 4, "count": 1}]));
+    assert!(is_new(first));
+    let (_, body) = server
+        .request(Method::POST, "/cards/grade", Some(grade(false)))
+        .await;
+    assert_eq!(body["graded"], 1);
+    assert_eq!(body["changes"]["cardIds"], json!([first]));
+    assert_eq!(body.get("results"), None);
+    assert!(!is_new(first));
+    Ok(())
+}
+
+#[tokio::test]
+async fn normalize_texts() -> Result<()> {
+    let server = TestServer::new()?;
+    let front = "<b>caf\u{65}\u{301}</b> <img src=\"dog.jpg\">";
+    let cid = server.add_basic_card(front).await;
+    let stored: u32 = server.with_col(|col| {
+        let nid = col.storage.get_card(CardId(cid))?.unwrap().note_id;
+        Ok(col
+            .storage
+            .db
+            .query_row("select csum from notes where id = ?", [nid], |row| {
+                row.get(0)
+            })?)
+    });
+
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/utils/normalize",
+            Some(json!({"texts": [front, "café  dog.jpg "]})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results[0]["normalized"], "caf\u{e9}  dog.jpg ");
+    assert_eq!(results[0]["checksum"], stored);
+    // the same text without markup is a duplicate
+    assert_eq!(results[1], results[0]);
+
+    let (status, _) = server
+        .request(
+            Method::POST,
+            "/utils/normalize",
+            Some(json!({"texts": vec![""; 1001]})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = server
+        .request(
+            Method::POST,
+            "/utils/normalize",
+            Some(json!({"texts": ["x".repeat(100_001)]})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn media_url_prefix() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server
+        .add_basic_card("<img src=\"dog.jpg\"><img src=\"https://example.com/cat.jpg\">")
+        .await;
+    let prefixed = "<img src=\"/media/dog.jpg\"><img src=\"https://example.com/cat.jpg\">";
+
+    let (_, card) = server
+        .request(
+            Method::GET,
+            &format!("/cards/{cid}?mediaUrlPrefix=/media/"),
+            None,
+        )
+        .await;
+    assert!(card["renderedFront"].as_str().unwrap().contains(prefixed));
+    let (_, card) = server
+        .request(Method::GET, &format!("/cards/{cid}"), None)
+        .await;
+    assert!(card["renderedFront"]
+        .as_str()
+        .unwrap()
+        .contains("<img src=\"dog.jpg\">"));
+
+    let (status, session) = server
+        .request(
+            Method::POST,
+            "/study/sessions",
+            Some(json!({"deckId": 1, "mediaUrlPrefix": "/media"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(session["card"]["question"]
+        .as_str()
+        .unwrap()
+        .contains(prefixed));
+    Ok(())
+}
+
+#[tokio::test]
+async fn render_warnings() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server
+        .add_basic_card("<img src=\"here.jpg\">[sound:gone.mp3]")
+        .await;
+    let media_folder = with_user(&server.server, |user| Ok(user.media.media_folder.clone()))
+        .await
+        .ok()
+        .unwrap();
+    write_file(media_folder.join("here.jpg"), "")?;
+
+    // the filesystem is only checked on request
+    let (_, card) = server
+        .request(Method::GET, &format!("/cards/{cid}"), None)
+        .await;
+    assert_eq!(card["warnings"], json!([]));
+    let (_, card) = server
+        .request(Method::GET, &format!("/cards/{cid}?checkMedia=true"), None)
+        .await;
+    assert_eq!(
+        card["warnings"],
+        json!([{"kind": "missingMedia", "filename": "gone.mp3"}])
+    );
+    let (_, next) = server
+        .request(Method::GET, "/study/next?checkMedia=true", None)
+        .await;
+    assert_eq!(next["card"]["warnings"], card["warnings"]);
+    let (_, session) = server
+        .request(
+            Method::POST,
+            "/study/sessions",
+            Some(json!({"deckId": 1, "checkMedia": true})),
+        )
+        .await;
+    assert_eq!(session["card"]["warnings"], card["warnings"]);
+
+    // a front that renders empty is always reported
+    server.with_col(|col| {
+        let nid = col.storage.get_card(CardId(cid))?.unwrap().note_id;
+        let mut note = col.storage.get_note(nid)?.unwrap();
+        note.set_field(0, "")?;
+        col.update_note(&mut note)?;
+        Ok(())
+    });
+    let (_, card) = server
+        .request(Method::GET, &format!("/cards/{cid}"), None)
+        .await;
+    assert_eq!(card["warnings"], json!([{"kind": "emptyQuestion"}]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn notetype_field_options() -> Result<()> {
+    let server = TestServer::new()?;
+    let ntid = server.with_col(|col| {
+        let mut nt = col.get_notetype_by_name("Basic")?.unwrap();
+        // as written by a newer client
+        Arc::make_mut(&mut nt).fields[1].config.other = b"{\"future\":1}".to_vec();
+        col.update_notetype(Arc::make_mut(&mut nt), false)?;
+        Ok(nt.id)
+    });
+
+    let (status, nt) = server
+        .request(Method::GET, &format!("/notetypes/{ntid}"), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(nt["fields"][1]["name"], "Back");
+    assert_eq!(nt["fields"][1]["rtl"], false);
+    assert_eq!(nt["templates"][0]["name"], "Card 1");
+
+    let (status, field) = server
+        .request(
+            Method::PUT,
+            &format!("/notetypes/{ntid}/fields/1"),
+            Some(json!({"name": "Answer", "rtl": true, "description": "hint"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(field["name"], "Answer");
+    assert_eq!(field["rtl"], true);
+    assert_eq!(field["description"], "hint");
+    assert_eq!(field["sticky"], false);
+    let config =
+        server.with_col(|col| Ok(col.get_notetype(ntid)?.unwrap().fields[1].config.clone()));
+    assert!(config.rtl);
+    assert_eq!(config.other, b"{\"future\":1}");
+
+    let (status, _) = server
+        .request(
+            Method::PUT,
+            &format!("/notetypes/{ntid}/fields/5"),
+            Some(json!({"rtl": true})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_templates() -> Result<()> {
+    let server = TestServer::new()?;
+    let ntid = server.with_col(|col| Ok(col.get_notetype_by_name("Basic")?.unwrap().id));
+
+    let (status, templates) = server.request(Method::GET, "/templates", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        templates[0],
+        json!({
+            "notetypeId": ntid.0,
+            "notetypeName": "Basic",
+            "templateOrd": 0,
+            "templateName": "Card 1",
+        })
+    );
+    assert_eq!(templates[2]["notetypeName"], "Basic (and reversed card)");
+    assert_eq!(templates[2]["templateOrd"], 1);
+    assert_eq!(templates[2]["templateName"], "Card 2");
+    Ok(())
+}
+
+#[tokio::test]
+async fn notetype_css() -> Result<()> {
+    let server = TestServer::new()?;
+    let (basic, cloze) = server.with_col(|col| {
+        Ok((
+            col.get_notetype_by_name("Basic")?.unwrap().id.0,
+            col.get_notetype_by_name("Cloze")?.unwrap().id.0,
+        ))
+    });
+    let css_of = |ntid: i64| {
+        server.with_col(|col| {
+            Ok(col
+                .get_notetype(NotetypeId(ntid))?
+                .unwrap()
+                .config
+                .css
+                .clone())
+        })
+    };
+
+    let (status, body) = server
+        .request(
+            Method::PUT,
+            &format!("/notetypes/{basic}/css"),
+            Some(json!({"css": ".card { color: red; }"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"updated": 1, "warnings": []}));
+    assert_eq!(css_of(basic), ".card { color: red; }");
+
+    // nothing is changed if any notetype is missing
+    let (status, _) = server
+        .request(
+            Method::POST,
+            "/notetypes/bulk-update-css",
+            Some(json!({"notetypeIds": [basic, 123], "css": "x"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(css_of(basic), ".card { color: red; }");
+
+    let long_css = "a".repeat(LONG_CSS_BYTES + 1);
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/notetypes/bulk-update-css",
+            Some(json!({"notetypeIds": [basic, cloze, basic], "css": long_css})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["updated"], 2);
+    assert_eq!(
+        body["warnings"],
+        json!([{"kind": "longCss", "bytes": LONG_CSS_BYTES + 1}])
+    );
+    assert_eq!(css_of(basic), long_css);
+    assert_eq!(css_of(cloze), long_css);
+    Ok(())
+}
+
+#[tokio::test]
+async fn preview_note() -> Result<()> {
+    let server = TestServer::new()?;
+    let (reversed, cloze) = server.with_col(|col| {
+        Ok((
+            col.get_notetype_by_name("Basic (optional reversed card)")?
+                .unwrap()
+                .id
+                .0,
+            col.get_notetype_by_name("Cloze")?.unwrap().id.0,
+        ))
+    });
+
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/notes/preview",
+            Some(json!({"notetypeId": reversed, "fields": {"Front": "<img src=a.jpg>", "Back": "b"}, "mediaUrlPrefix": "/media/"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["cardCount"], 1);
+    assert_eq!(body["cards"][0]["templateName"], "Card 1");
+    assert_eq!(body["cards"][0]["nonempty"], true);
+    assert!(body["cards"][0]["renderedFront"]
+        .as_str()
+        .unwrap()
+        .contains("/media/a.jpg"));
+    assert_eq!(body["cards"][1]["ord"], 1);
+    assert_eq!(body["cards"][1]["nonempty"], false);
+
+    // a cloze note without deletions would create no cards
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/notes/preview",
+            Some(json!({"notetypeId": cloze, "fields": {"Text": "no deletions"}})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"cardCount": 0, "cards": []}));
+
+    // nothing is added
+    assert_eq!(
+        server.with_col(|col| Ok(col.storage.get_all_note_ids()?.len())),
+        0
+    );
+
+    let (status, _) = server
+        .request(
+            Method::POST,
+            "/notes/preview",
+            Some(json!({"notetypeId": 123, "fields": {}})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn diff_notes() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("<b>old</b> front").await;
+    let (nid, guid) = server.with_col(|col| {
+        let note = col
+            .storage
+            .get_note(col.storage.get_card(CardId(cid))?.unwrap().note_id)?;
+        let note = note.unwrap();
+        Ok((note.id.0, note.guid))
+    });
+
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/notes/diff",
+            Some(json!([
+                {"noteId": nid, "fields": {"Front": "<i>new</i> front", "Extra": "x"}},
+                {"guid": guid, "fields": {"Back": "back"}},
+                {"guid": "missing", "fields": {}},
+            ])),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let notes = &body["notes"];
+    assert_eq!(notes[0]["changed"], true);
+    assert_eq!(notes[0]["fields"][0]["status"], "changed");
+    assert_eq!(
+        notes[0]["fields"][0]["diff"],
+        json!([
+            {"op": "removed", "text": "old"},
+            {"op": "added", "text": "new"},
+            {"op": "equal", "text": "front"},
+        ])
+    );
+    assert_eq!(notes[0]["fields"][1]["status"], "unchanged");
+    assert_eq!(notes[0]["fields"][2]["name"], "Extra");
+    assert_eq!(notes[0]["fields"][2]["status"], "unknownField");
+    assert_eq!(notes[1]["noteId"], nid);
+    assert_eq!(notes[1]["changed"], false);
+    assert_eq!(notes[2]["found"], false);
+    assert_eq!(notes[2]["noteId"], Value::Null);
+
+    // nothing is written
+    let front =
+        server.with_col(|col| Ok(col.storage.get_note(NoteId(nid))?.unwrap().fields()[0].clone()));
+    assert_eq!(front, "<b>old</b> front");
+
+    let (status, _) = server
+        .request(Method::POST, "/notes/diff", Some(json!([{"fields": {}}])))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn export_notes() -> Result<()> {
+    let server = TestServer::new()?;
+    for front in ["one", "two", "three"] {
+        server.add_basic_card(front).await;
+    }
+    let (status, data) = server
+        .request_raw(Method::GET, "/notes/export?batchSize=2", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let notes: Vec<Value> = data
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect();
+    assert_eq!(
+        notes
+            .iter()
+            .map(|note| note["fields"][0].as_str().unwrap())
+            .collect::<Vec<_>>(),
+        ["one", "two", "three"]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn export_stats_csv() -> Result<()> {
+    let server = TestServer::new()?;
+    server.add_basic_card("new").await;
+    let cid = server.add_basic_card("reviewed").await;
+    server.with_col(|col| {
+        let deck = col.get_or_create_normal_deck("Lang, \"French\"")?;
+        let mut card = col.storage.get_card(CardId(cid))?.unwrap();
+        card.deck_id = deck.id;
+        card.ctype = CardType::Review;
+        card.queue = CardQueue::Review;
+        card.interval = 3;
+        card.ease_factor = 2500;
+        col.storage.update_card(&card)?;
+        col.storage.add_revlog_entry(
+            &RevlogEntry {
+                id: RevlogId(1_700_000_000_000),
+                cid: card.id,
+                button_chosen: 3,
+                interval: 3,
+                ease_factor: 2500,
+                taken_millis: 4000,
+                review_kind: RevlogReviewKind::Review,
+                ..Default::default()
+            },
+            false,
+        )?;
+        Ok(())
+    });
+    let csv_rows = |data: &[u8]| -> Vec<Vec<String>> {
+        csv::Reader::from_reader(data)
+            .records()
+            .map(|record| record.unwrap().iter().map(ToString::to_string).collect())
+            .collect()
+    };
+
+    let (status, data) = server
+        .request_raw(Method::GET, "/stats/export.csv?kind=reviews&bom=true", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let data = data.strip_prefix("\u{feff}".as_bytes()).unwrap();
+    assert!(data.starts_with(b"reviewedAt,reviewId,cardId,"));
+    let rows = csv_rows(data);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0][1..3], ["1700000000000", cid.to_string().as_str()]);
+    assert_eq!(
+        rows[0][4..],
+        ["Lang, \"French\"", "review", "3", "3", "0", "2500", "4000"]
+    );
+
+    let (status, data) = server
+        .request_raw(
+            Method::GET,
+            "/stats/export.csv?kind=cards&search=deck:Default",
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(data.starts_with(b"cardId,noteId,deck,type,dueDate,"));
+    let rows = csv_rows(&data);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0][2..7], ["Default", "new", "", "0", "0"]);
+
+    let (status, _) = server
+        .request_raw(Method::GET, "/stats/export.csv?kind=notes", None)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn review_log_csv() -> Result<()> {
+    let server = TestServer::new()?;
+    let (status, data) = server
+        .request_raw(Method::GET, "/collection/review-log.csv", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        data,
+        "reviewedAt,cardId,noteId,deckId,ease,interval,lastInterval,timeTakenMs\n"
+    );
+
+    let cid = server.add_basic_card("reviewed").await;
+    let (nid, did) = server.with_col(|col| {
+        let card = col.storage.get_card(CardId(cid))?.unwrap();
+        for (id, interval) in [(1_000, -600), (5_000, 1)] {
+            col.storage.add_revlog_entry(
+                &RevlogEntry {
+                    id: RevlogId(id),
+                    cid: card.id,
+                    button_chosen: 3,
+                    interval,
+                    taken_millis: 4000,
+                    ..Default::default()
+                },
+                false,
+            )?;
+        }
+        Ok((card.note_id.0, card.deck_id.0))
+    });
+    let (status, data) = server
+        .request_raw(
+            Method::GET,
+            &format!("/collection/review-log.csv?deckId={did}&since=2"),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let data = String::from_utf8(data.to_vec()).unwrap();
+    let lines: Vec<_> = data.lines().collect();
+    assert_eq!(lines[1..], [format!("5000,{cid},{nid},{did},3,1,0,4000")]);
+
+    let (status, _) = server
+        .request_raw(Method::GET, "/collection/review-log.csv?deckId=123", None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn export_fsrs_states() -> Result<()> {
+    let server = TestServer::new()?;
+    server.add_basic_card("one").await;
+    let cid = server.add_basic_card("two").await;
+    server.with_col(|col| {
+        let mut card = col.storage.get_card(CardId(cid))?.unwrap();
+        card.memory_state = Some(FsrsMemoryState {
+            stability: 3.0,
+            difficulty: 4.0,
+        });
+        col.storage.update_card(&card)
+    });
+
+    let (status, data) = server
+        .request_raw(Method::GET, "/collection/fsrs-states?deckId=1", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let states: Vec<Value> = data
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect();
+    assert_eq!(states.len(), 1);
+    assert_eq!(states[0]["cardId"], cid);
+    assert_eq!(states[0]["stability"], 3.0);
+    assert_eq!(states[0]["lastReview"], Value::Null);
+
+    let (status, _) = server
+        .request(Method::GET, "/collection/fsrs-states?deckId=123", None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn import_fsrs_states() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("one").await;
+    let body = format!(
+        "{{\"cardId\":{cid},\"stability\":3.0,\"difficulty\":4.0,\"lastReview\":1700000000}}\n\
+         {{\"cardId\":123,\"stability\":3.0,\"difficulty\":4.0}}\n"
+    );
+    let (status, data) = server
+        .request_body(Method::POST, "/collection/fsrs-states", Body::from(body))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let response: Value = serde_json::from_slice(&data).unwrap();
+    assert_eq!(response, json!({"updated": 1, "skipped": 1}));
+    let card = server.with_col(|col| Ok(col.storage.get_card(CardId(cid))?.unwrap()));
+    assert_eq!(card.memory_state.unwrap().stability, 3.0);
+    assert_eq!(card.last_review_time, Some(TimestampSecs(1_700_000_000)));
+
+    let (status, _) = server
+        .request_body(
+            Method::POST,
+            "/collection/fsrs-states",
+            Body::from("{\"cardId\":1}\n"),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn fsrs_calibration() -> Result<()> {
+    let server = TestServer::new()?;
+    server.add_basic_card("front").await;
+    let (status, body) = server
+        .request(Method::POST, "/fsrs/calibration", Some(json!({})))
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["code"], 422);
+
+    // a learning step followed by five reviews for each card, with every
+    // fourth card forgotten at the end
+    server.with_col(|col| {
+        for _ in 0..59 {
+            NoteAdder::basic(col).add(col);
+        }
+        let now = TimestampSecs::now();
+        for (idx, card) in col.storage.get_all_cards().into_iter().enumerate() {
+            for (step, days_ago) in [60, 50, 40, 30, 20, 10].into_iter().enumerate() {
+                let forgotten = step == 5 && idx % 4 == 0;
+                col.storage.add_revlog_entry(
+                    &RevlogEntry {
+                        id: now.adding_secs(-86_400 * days_ago).as_millis().into(),
+                        cid: card.id,
+                        button_chosen: if forgotten { 1 } else { 3 },
+                        interval: if step == 0 { -600 } else { 10 },
+                        ease_factor: 2500,
+                        review_kind: if step == 0 {
+                            RevlogReviewKind::Learning
+                        } else {
+                            RevlogReviewKind::Review
+                        },
+                        ..Default::default()
+                    },
+                    true,
+                )?;
+            }
+        }
+        Ok(())
+    });
+
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/fsrs/calibration",
+            Some(json!({"holdoutFraction": 0.2, "buckets": 5})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["holdoutReviews"], 60);
+    assert_eq!(body["trainingReviews"], 240);
+    let buckets = body["buckets"].as_array().unwrap();
+    assert_eq!(buckets.len(), 5);
+    assert_eq!(buckets[0]["lower"], 0.0);
+    let counted: u64 = buckets.iter().map(|b| b["count"].as_u64().unwrap()).sum();
+    assert_eq!(counted, 60);
+    let observed: f64 = buckets
+        .iter()
+        .filter(|b| b["count"] != 0)
+        .map(|b| b["observed"].as_f64().unwrap() * b["count"].as_f64().unwrap())
+        .sum();
+    assert!((observed - 45.0).abs() < 1e-3);
+    assert!(body["logLoss"].as_f64().unwrap() > 0.0);
+    assert!(body["params"].is_array());
+
+    let (status, _) = server
+        .request(
+            Method::POST,
+            "/fsrs/calibration",
+            Some(json!({"holdoutFraction": 1.5})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = server
+        .request(
+            Method::POST,
+            "/fsrs/calibration",
+            Some(json!({"deckId": 123})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn review_time_series() -> Result<()> {
+    let server = TestServer::new()?;
+    server.add_basic_card("front").await;
+    let (_, session) = server
+        .request(Method::POST, "/study/sessions", Some(json!({"deckId": 1})))
+        .await;
+    let session_id = session["sessionId"].as_str().unwrap();
+    server
+        .request(
+            Method::POST,
+            &format!("/study/sessions/{session_id}/answer"),
+            Some(json!({"rating": "good"})),
+        )
+        .await;
+
+    let (status, series) = server
+        .request(Method::GET, "/collection/time-series?days=7&deckId=1", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let series = series.as_array().unwrap();
+    assert_eq!(series.len(), 7);
+    assert_eq!(series[6]["date"].as_str().unwrap().len(), 10);
+
+    let (status, _) = server
+        .request(Method::GET, "/collection/time-series?deckId=123", None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn scheduling_round_trip() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let (status, export) = server
+        .request(
+            Method::POST,
+            "/export/scheduling",
+            Some(json!({"search": "front"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let mut record = export["records"][0].clone();
+    assert_eq!(record["cardOrd"], 0);
+    assert_eq!(record["type"], 0);
+
+    record["type"] = json!(2);
+    record["queue"] = json!(2);
+    record["interval"] = json!(12);
+    record["due"] = json!(4);
+    let mut missing = record.clone();
+    missing["guid"] = json!("missing");
+    let (status, output) = server
+        .request(
+            Method::POST,
+            "/import/scheduling",
+            Some(json!({"records": [record, missing], "policy": "preferImported"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(output["updated"], 1);
+    assert_eq!(
+        output["missing"],
+        json!([{"guid": "missing", "cardOrd": 0}])
+    );
+    let (card, today) = server.with_col(|col| {
+        Ok((
+            col.storage.get_card(CardId(cid))?.unwrap(),
+            col.timing_today()?.days_elapsed,
+        ))
+    });
+    assert_eq!(card.interval, 12);
+    assert_eq!(card.due, today as i32 + 4);
+
+    let (status, _) = server
+        .request(
+            Method::POST,
+            "/import/scheduling",
+            Some(json!({"records": [], "policy": "newest"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn high_lapse_cards() -> Result<()> {
+    let server = TestServer::new()?;
+    server.add_basic_card("easy").await;
+    let cid = server.add_basic_card("<b>hard</b>").await;
+    server.with_col(|col| {
+        let mut card = col.storage.get_card(CardId(cid))?.unwrap();
+        card.lapses = 6;
+        col.storage.update_card(&card)
+    });
+
+    let (status, cards) = server
+        .request(Method::GET, "/decks/1/high-lapse-cards", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        cards,
+        json!([{"cardId": cid, "lapses": 6, "noteField": "hard"}])
+    );
+    let (status, cards) = server
+        .request(Method::GET, "/decks/1/high-lapse-cards?minLapses=0", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cards.as_array().unwrap().len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn reschedule_card_in_filtered_deck() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let other = server.add_basic_card("other").await;
+    let (due, did) = server.with_col(|col| {
+        let due = col.storage.get_card(CardId(cid))?.unwrap().due;
+        let did = col
+            .create_filtered_deck_from_search("Filtered", "", 10, 0)?
+            .output;
+        Ok((due, did))
+    });
+
+    let (_, card) = server
+        .request(Method::GET, &format!("/cards/{cid}"), None)
+        .await;
+    assert_eq!(card["inFilteredDeck"], true);
+    assert_eq!(card["deckId"], did.0);
+    assert_eq!(card["filteredDeckName"], "Filtered");
+    assert_eq!(card["originalDeckId"], 1);
+    assert_eq!(card["originalDue"], due);
+
+    let (status, body) = server
+        .request(
+            Method::PUT,
+            &format!("/cards/{cid}/schedule"),
+            Some(json!({"due": "3"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["removedFromFilteredDeck"],
+        json!({"filteredDeckId": did.0, "filteredDeckName": "Filtered", "homeDeckId": 1})
+    );
+    let (_, card) = server
+        .request(Method::GET, &format!("/cards/{cid}"), None)
+        .await;
+    assert_eq!(card["inFilteredDeck"], false);
+    assert_eq!(card["deckId"], 1);
+    assert_eq!(card["filteredDeckName"], Value::Null);
+    assert_eq!(card["originalDeckId"], Value::Null);
+
+    // only cards that were in a filtered deck are reported
+    let (_, body) = server
+        .request(
+            Method::POST,
+            "/cards/schedule",
+            Some(json!({"cards": [{"cardId": cid, "due": "1"}, {"cardId": other, "due": "1"}]})),
+        )
+        .await;
+    assert_eq!(body["results"][0].get("removedFromFilteredDeck"), None);
+    assert_eq!(
+        body["results"][1]["removedFromFilteredDeck"]["filteredDeckId"],
+        did.0
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn filtered_card_dues() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let (due, did) = server.with_col(|col| {
+        let due = col.storage.get_card(CardId(cid))?.unwrap().due;
+        let did = col
+            .create_filtered_deck_from_search("Filtered", "", 10, 0)?
+            .output;
+        Ok((due, did))
+    });
+
+    let (status, cards) = server
+        .request(
+            Method::GET,
+            &format!("/decks/{}/filtered-card-dues", did.0),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(cards, json!([{"cardId": cid, "originalDue": due}]));
+
+    let (status, body) = server
+        .request(Method::GET, "/decks/1/filtered-card-dues", None)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("filtered deck"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn create_filtered_deck_defaults_to_random_order() -> Result<()> {
+    let server = TestServer::new()?;
+    server.add_basic_card("front").await;
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/decks/filtered",
+            Some(json!({"name": "Filtered", "search": ""})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let did = DeckId(body["deckId"].as_i64().unwrap());
+    let term = server.with_col(|col| {
+        let deck = col.get_deck(did)?.unwrap();
+        Ok(deck.filtered()?.search_terms[0].clone())
+    });
+    assert_eq!(term.order, FilteredSearchOrder::Random as i32);
+    assert_eq!(term.limit, 100);
+    Ok(())
+}
+
+#[tokio::test]
+async fn new_card_position() -> Result<()> {
+    let server = TestServer::new()?;
+    let (status, pos) = server
+        .request(Method::GET, "/scheduler/new-position", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(pos, json!({"position": 1}));
+
+    let (status, _) = server
+        .request(
+            Method::PUT,
+            "/scheduler/new-position",
+            Some(json!({"position": 100})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let cid = server.add_basic_card("front").await;
+    let (_, body) = server
+        .request(
+            Method::POST,
+            "/cards",
+            Some(json!({
+                "deckName": "Default",
+                "notetypeName": "Basic",
+                "fields": {"Front": "placed"},
+                "tags": [],
+                "newPosition": 5,
+            })),
+        )
+        .await;
+    let placed = body["cardIds"][0].as_i64().unwrap();
+    let dues = server.with_col(|col| {
+        Ok([cid, placed].map(|cid| col.storage.get_card(CardId(cid)).unwrap().unwrap().due))
+    });
+    assert_eq!(dues, [100, 5]);
+
+    let (_, pos) = server
+        .request(Method::GET, "/scheduler/new-position", None)
+        .await;
+    assert_eq!(pos, json!({"position": 101}));
+    Ok(())
+}
+
+#[tokio::test]
+async fn card_anomalies() -> Result<()> {
+    let server = TestServer::new()?;
+    let high = server.add_basic_card("high").await;
+    let timestamp = server.add_basic_card("timestamp").await;
+    server.with_col(|col| {
+        let mut card = col.storage.get_card(CardId(high))?.unwrap();
+        card.due = 1_500_000;
+        col.storage.update_card(&card)?;
+        let mut card = col.storage.get_card(CardId(timestamp))?.unwrap();
+        card.ctype = CardType::Review;
+        card.queue = CardQueue::Review;
+        card.due = 1_700_000_000;
+        card.interval = 10;
+        card.ease_factor = 2500;
+        col.storage.update_card(&card)?;
+        Ok(())
+    });
+
+    let (status, body) = server
+        .request(Method::GET, "/collection/anomalies", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({"anomalies": [
+            {"cardId": high, "kind": "newPositionTooHigh", "value": 1_500_000},
+            {"cardId": timestamp, "kind": "reviewDueNotDays", "value": 1_700_000_000},
+        ]})
+    );
+
+    // fixes can be limited to some cards
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/collection/anomalies/fix",
+            Some(json!({"cardIds": [timestamp]})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let today = server.with_col(|col| Ok(col.timing_today()?.days_elapsed as i64));
+    assert_eq!(
+        body["fixed"],
+        json!([{"cardId": timestamp, "kind": "reviewDueNotDays", "value": 1_700_000_000, "fixedValue": today}])
+    );
+    let (_, body) = server
+        .request(Method::POST, "/collection/anomalies/fix", Some(json!({})))
+        .await;
+    assert_eq!(body["fixed"][0]["cardId"], high);
+    assert!(body["fixed"][0]["fixedValue"].as_i64().unwrap() < 1_000_000);
+    let (_, body) = server
+        .request(Method::GET, "/collection/anomalies", None)
+        .await;
+    assert_eq!(body["anomalies"], json!([]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn ease_outliers() -> Result<()> {
+    let server = TestServer::new()?;
+    let mut cids = vec![];
+    for (idx, ease) in [2500, 2500, 2500, 2500, 1300].into_iter().enumerate() {
+        let cid = server.add_basic_card(&idx.to_string()).await;
+        server.with_col(|col| {
+            let mut card = col.storage.get_card(CardId(cid))?.unwrap();
+            card.ctype = CardType::Review;
+            card.ease_factor = ease;
+            col.storage.update_card(&card)
+        });
+        cids.push(cid);
+    }
+
+    let (status, outliers) = server
+        .request(
+            Method::GET,
+            "/collection/ease-outliers?stdDevs=1.5&deckId=1",
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(outliers, json!([{"cardId": cids[4], "ease": 1.3}]));
+
+    let (status, _) = server
+        .request(Method::GET, "/collection/ease-outliers?stdDevs=-1", None)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn expand_related_objects() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+
+    let (status, plain) = server
+        .request(Method::GET, &format!("/cards/{cid}"), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    for key in ["note", "deck", "notetype"] {
+        assert!(plain.get(key).is_none());
+    }
+
+    let (status, card) = server
+        .request(
+            Method::GET,
+            &format!("/cards/{cid}?expand=note,deck,notetype"),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(card["cardId"], plain["cardId"]);
+    assert_eq!(card["note"]["fields"], json!(["front", "back"]));
+    assert_eq!(card["deck"]["name"], "Default");
+    assert_eq!(card["notetype"]["name"], "Basic");
+
+    let nid = card["note"]["id"].as_i64().unwrap();
+    let (status, note) = server
+        .request(
+            Method::GET,
+            &format!("/notes/{nid}?expand=cards,notetype"),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        note["cards"],
+        json!([{"cardId": cid, "noteId": nid, "deckId": 1}])
+    );
+    assert_eq!(note["notetype"]["name"], "Basic");
+
+    let (status, _) = server
+        .request(Method::GET, &format!("/cards/{cid}?expand=cards"), None)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn scheduler_version() -> Result<()> {
+    let server = TestServer::new()?;
+    let (status, body) = server
+        .request(
+            Method::PUT,
+            "/collection/scheduler",
+            Some(json!({"version": 3})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"version": 3}));
+
+    let (status, _) = server
+        .request(
+            Method::PUT,
+            "/collection/scheduler",
+            Some(json!({"version": 2})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = server
+        .request(
+            Method::PUT,
+            "/collection/scheduler",
+            Some(json!({"version": 1})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn scheduler_version_info() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let (status, info) = server.request(Method::GET, "/collection", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        info,
+        json!({"schedulerVersion": 3, "cardCount": 1, "noteCount": 1})
+    );
+
+    let response = server
+        .response(Method::GET, "/cards/due", Body::empty())
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("deprecation").is_none());
+
+    server.with_col(|col| {
+        let mut card = col.storage.get_card(CardId(cid))?.unwrap();
+        card.ctype = CardType::Review;
+        card.queue = CardQueue::Review;
+        card.due = col.timing_today()?.days_elapsed as i32;
+        col.storage.update_card(&card)?;
+        col.set_config_bool(BoolKey::Sched2021, false, false)
+    });
+    let (_, info) = server.request(Method::GET, "/collection", None).await;
+    assert_eq!(info["schedulerVersion"], 2);
+    let response = server
+        .response(Method::GET, "/cards/due", Body::empty())
+        .await;
+    assert_eq!(response.headers()["deprecation"], "true");
+    assert!(response.headers()[header::LINK]
+        .to_str()
+        .unwrap()
+        .contains("rel=\"deprecation\""));
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["cards"][0]["cardId"], cid);
+    Ok(())
+}
+
+#[tokio::test]
+async fn tags_are_normalized() -> Result<()> {
+    let server = TestServer::new()?;
+    let add = |tags: Value| {
+        json!({
+            "deckName": "Default",
+            "notetypeName": "Basic",
+            "fields": {"Front": "front"},
+            "tags": tags,
+        })
+    };
+
+    let (status, body) = server
+        .request(Method::POST, "/cards", Some(add(json!(["one", "two"]))))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.get("normalizedTags").is_none());
+
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/cards",
+            Some(add(json!(["two words", "ONE", "Two"]))),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["normalizedTags"], json!(["one", "two", "words"]));
+    let cid = body["cardIds"][0].as_i64().unwrap();
+    let tags = server.with_col(|col| {
+        let card = col.storage.get_card(CardId(cid))?.unwrap();
+        Ok(col.storage.get_note(card.note_id)?.unwrap().tags)
+    });
+    assert_eq!(tags, ["one", "two", "words"]);
+
+    let (status, body) = server
+        .request(
+            Method::PUT,
+            &format!("/cards/{cid}"),
+            Some(json!({"fields": {}, "tags": ["a\tb", "Words"]})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["normalizedTags"], json!(["ab", "words"]));
+
+    for tags in [
+        json!(["  "]),
+        json!(["\t"]),
+        json!(["one::\u{1}"]),
+        json!(["two::"]),
+        json!(["x".repeat(256)]),
+    ] {
+        let (status, _) = server
+            .request(Method::POST, "/cards", Some(add(tags)))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn suspend_leeches() -> Result<()> {
+    let server = TestServer::new()?;
+    let mut cids = vec![];
+    for lapses in [8, 2] {
+        let cid = server.add_basic_card(&lapses.to_string()).await;
+        server.with_col(|col| {
+            let mut card = col.storage.get_card(CardId(cid))?.unwrap();
+            card.lapses = lapses;
+            col.storage.update_card(&card)
+        });
+        cids.push(cid);
+    }
+
+    let (status, body) = server
+        .request(Method::POST, "/decks/1/suspend-leeches", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"suspended": 1, "suspendedIds": [cids[0]]}));
+    let (status, body) = server
+        .request(Method::POST, "/collection/suspend-leeches", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"suspended": 0, "suspendedIds": []}));
+
+    let (status, _) = server
+        .request(Method::POST, "/decks/123/suspend-leeches", None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn mark_notes() -> Result<()> {
+    let server = TestServer::new()?;
+    let marked_cid = server.add_basic_card("marked").await;
+    let flagged_cid = server.add_basic_card("flagged").await;
+    let nid = server.with_col(|col| Ok(col.storage.get_card(CardId(marked_cid))?.unwrap().note_id));
+
+    let (status, body) = server
+        .request(Method::POST, &format!("/notes/{nid}/mark"), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["marked"], true);
+    let (_, note) = server
+        .request(Method::GET, &format!("/notes/{nid}"), None)
+        .await;
+    assert_eq!(note["marked"], true);
+    assert_eq!(note["tags"], json!(["marked"]));
+    assert_eq!(
+        server.with_col(|col| Ok(col.undo_status().undo)),
+        Some(Op::UpdateTag)
+    );
+
+    // searches behave as on the desktop
+    server.with_col(|col| col.set_card_flag(&[CardId(flagged_cid)], 1));
+    for (search, cid) in [("tag:marked", marked_cid), ("flag:1", flagged_cid)] {
+        let (_, body) = server
+            .request(
+                Method::POST,
+                "/export/scheduling",
+                Some(json!({"search": search})),
+            )
+            .await;
+        let records = body["records"].as_array().unwrap();
+        assert_eq!(records.len(), 1, "{search}");
+        let guid = server.with_col(|col| {
+            let card = col.storage.get_card(CardId(cid))?.unwrap();
+            Ok(col.storage.get_note(card.note_id)?.unwrap().guid)
+        });
+        assert_eq!(records[0]["guid"], guid);
+    }
+
+    let (status, body) = server
+        .request(Method::DELETE, &format!("/notes/{nid}/mark"), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["marked"], false);
+    let (_, note) = server
+        .request(Method::GET, &format!("/notes/{nid}"), None)
+        .await;
+    assert_eq!(note["marked"], false);
+    assert_eq!(
+        server.with_col(|col| Ok(col.undo_status().undo)),
+        Some(Op::RemoveTag)
+    );
+
+    let (status, _) = server.request(Method::POST, "/notes/123/mark", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn card_difficulty() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let uri = format!("/cards/{cid}/difficulty");
+
+    let (status, body) = server.request(Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["method"], "sm2_estimated");
+
+    server.with_col(|col| {
+        let mut card = col.storage.get_card(CardId(cid))?.unwrap();
+        card.memory_state = Some(FsrsMemoryState {
+            stability: 10.0,
+            difficulty: 5.5,
+        });
+        col.storage.update_card(&card)
+    });
+    let (_, body) = server.request(Method::GET, &uri, None).await;
+    assert_eq!(body, json!({"difficulty": 0.5, "method": "fsrs"}));
+
+    let (status, _) = server
+        .request(Method::GET, "/cards/1/difficulty", None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn interval_history() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let today = server.with_col(|col| {
+        // a learning step two days ago, and a review a minute into today
+        let today_start = col.timing_today()?.next_day_at.adding_secs(-86_400);
+        for (answered, interval, ease_factor, button_chosen) in [
+            (today_start.adding_secs(-86_400 * 2), -600, 0, 1),
+            (today_start.adding_secs(60), 3, 2500, 3),
+        ] {
+            col.storage.add_revlog_entry(
+                &RevlogEntry {
+                    id: answered.as_millis().into(),
+                    cid: CardId(cid),
+                    button_chosen,
+                    interval,
+                    ease_factor,
+                    ..Default::default()
+                },
+                true,
+            )?;
+        }
+        Ok(today_start
+            .datetime(col.local_utc_offset_for_user()?)?
+            .format("%Y-%m-%d")
+            .to_string())
+    });
+
+    let (status, body) = server
+        .request(Method::GET, &format!("/cards/{cid}/interval-history"), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    assert_eq!(
+        body[0],
+        json!({
+            "reviewDate": body[0]["reviewDate"],
+            "intervalAfter": -600,
+            "easeAfter": 0,
+            "rating": 1,
+            "learningStep": true,
+        })
+    );
+    assert_eq!(
+        body[1],
+        json!({
+            "reviewDate": today,
+            "intervalAfter": 3,
+            "easeAfter": 2500,
+            "rating": 3,
+            "learningStep": false,
+        })
+    );
+
+    let (status, _) = server
+        .request(Method::GET, "/cards/1/interval-history", None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn schema_changes() -> Result<()> {
+    let server = TestServer::new()?;
+    let ntid = server.with_col(|col| {
+        col.storage.set_schema_modified_time(TimestampMillis(0))?;
+        Ok(col.get_notetype_by_name("Basic")?.unwrap().id)
+    });
+    let field_uri = format!("/notetypes/{ntid}/fields/1");
+
+    let (status, body) = server
+        .request(
+            Method::PUT,
+            &field_uri,
+            Some(json!({"name": "Answer", "allowSchemaChange": false})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["schemaModified"], false);
+
+    let (status, _) = server
+        .request(
+            Method::DELETE,
+            &format!("{field_uri}?allowSchemaChange=false"),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let field_count = || server.with_col(|col| Ok(col.get_notetype(ntid)?.unwrap().fields.len()));
+    assert_eq!(field_count(), 2);
+
+    let (status, body) = server.request(Method::DELETE, &field_uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["schemaModified"], true);
+    assert_eq!(field_count(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn delete_notes() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let nid = server.with_col(|col| Ok(col.storage.get_card(CardId(cid))?.unwrap().note_id));
+
+    let (status, body) = server
+        .request(
+            Method::DELETE,
+            "/notes",
+            Some(json!({"noteIds": [nid.0, 123]})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({
+            "notesDeleted": 1,
+            "cardsDeleted": 1,
+            "notFound": [123],
+            "requested": 2,
+            "deleted": 1,
+            "alreadyMissing": 1,
+            "missingIds": [123],
+            "changes": {"cardIds": [cid], "noteIds": [nid.0], "deckIds": []},
+        })
+    );
+    let (status, _) = server
+        .request(Method::GET, &format!("/cards/{cid}"), None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn retried_deletes_report_missing_ids() -> Result<()> {
+    let server = TestServer::new()?;
+    let first = server.add_basic_card("first").await;
+    let second = server.add_basic_card("second").await;
+    let request = json!({"cardIds": [first, second, first]});
+
+    let (status, body) = server
+        .request(Method::DELETE, "/cards", Some(request.clone()))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["requested"], 2);
+    assert_eq!(body["deleted"], 2);
+    assert_eq!(body["deletedCount"], 2);
+    assert_eq!(body["alreadyMissing"], 0);
+    assert_eq!(body["missingIds"], json!([]));
+
+    // a retry succeeds, finding everything already gone
+    let (status, body) = server
+        .request(Method::DELETE, "/cards", Some(request))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["requested"], 2);
+    assert_eq!(body["deleted"], 0);
+    assert_eq!(body["alreadyMissing"], 2);
+    assert_eq!(body["missingIds"], json!([first, second]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn deletes_can_trash_unused_media() -> Result<()> {
+    let server = TestServer::new()?;
+    let deleted = server
+        .add_basic_card(r#"<img src="shared.jpg"><img src="only.jpg">"#)
+        .await;
+    let surviving = server.add_basic_card(r#"<img src="shared.jpg">"#).await;
+    let (media_folder, usn_before) = with_user(&server.server, |user| {
+        user.media.db.with_transaction(|db, meta| {
+            for name in ["shared.jpg", "only.jpg"] {
+                let data = name.as_bytes().to_vec();
+                let change = UploadedChange {
+                    nfc_filename: name.into(),
+                    kind: UploadedChangeKind::AddOrReplace {
+                        sha1: sha1_of_data(&data).to_vec(),
+                        nonempty_data: data,
+                    },
+                };
+                db.register_uploaded_change(meta, change)?;
+                write_file(user.media.media_folder.join(name), name)?;
+            }
+            Ok(())
+        })?;
+        Ok((user.media.media_folder.clone(), user.media.last_usn()?))
+    })
+    .await
+    .ok()
+    .unwrap();
+    let nid = server.with_col(|col| Ok(col.storage.get_card(CardId(deleted))?.unwrap().note_id));
+
+    // the shared file is still used by the surviving note
+    let (status, body) = server
+        .request(
+            Method::DELETE,
+            "/notes",
+            Some(json!({"noteIds": [nid], "cleanupMedia": true})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["trashedMedia"], json!(["only.jpg"]));
+    assert_eq!(body["mediaNotTrashed"], json!([]));
+    assert!(!media_folder.join("only.jpg").exists());
+    let trash_folder = media_folder.with_file_name("media.trash");
+    assert!(trash_folder.join("only.jpg").exists());
+    assert!(media_folder.join("shared.jpg").exists());
+    // clients are told about the removal
+    let usn_after = with_user(&server.server, |user| Ok(user.media.last_usn()?))
+        .await
+        .ok()
+        .unwrap();
+    assert!(usn_after > usn_before);
+
+    // removing the last card of the other note frees the shared file, but if
+    // it can't be moved, the delete still succeeds and reports it
+    create_dir_all(trash_folder.join("shared.jpg").join("blocker"))?;
+    let (status, body) = server
+        .request(
+            Method::DELETE,
+            "/cards",
+            Some(json!({"cardIds": [surviving], "cleanupMedia": true})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["deleted"], 1);
+    assert_eq!(body["trashedMedia"], json!([]));
+    assert_eq!(body["mediaNotTrashed"], json!(["shared.jpg"]));
+    assert!(media_folder.join("shared.jpg").exists());
+    // and clients aren't told to remove it
+    let usn_after_failure = with_user(&server.server, |user| Ok(user.media.last_usn()?))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(usn_after_failure, usn_after);
+
+    // without cleanupMedia, nothing is reported
+    let other = server.add_basic_card("other").await;
+    let (_, body) = server
+        .request(Method::DELETE, "/cards", Some(json!({"cardIds": [other]})))
+        .await;
+    assert!(body.get("trashedMedia").is_none());
+    assert!(body.get("mediaNotTrashed").is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn large_deletes_require_confirmation() -> Result<()> {
+    let server = TestServer::with_delete_confirm_threshold(1)?;
+    let cids = [
+        server.add_basic_card("one").await,
+        server.add_basic_card("two").await,
+    ];
+    let nids: Vec<i64> = server.with_col(|col| {
+        cids.iter()
+            .map(|cid| Ok(col.storage.get_card(CardId(*cid))?.unwrap().note_id.0))
+            .collect()
+    });
+
+    // refused with the would-be-deleted count; nothing is removed
+    let (status, body) = server
+        .request(Method::DELETE, "/cards", Some(json!({"cardIds": cids})))
+        .await;
+    assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+    assert_eq!(body["error"]["code"], 428);
+    assert_eq!(body["error"]["wouldDelete"], 2);
+    let message = body["error"]["message"].as_str().unwrap();
+    assert!(message.contains("\"confirm\": true"));
+    assert!(message.contains("X-Confirm-Destructive: yes"));
+    let (status, body) = server
+        .request(Method::DELETE, "/notes", Some(json!({"noteIds": nids})))
+        .await;
+    assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+    assert_eq!(body["error"]["wouldDelete"], 2);
+    assert_eq!(server.with_col(|col| col.storage.total_cards()), 2);
+
+    // at or below the threshold, no confirmation is needed
+    let (status, body) = server
+        .request(
+            Method::DELETE,
+            "/cards",
+            Some(json!({"cardIds": [cids[0], cids[0], 123]})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["deletedCount"], 1);
+
+    // confirmed with the header or the body field
+    let (status, body) = server
+        .request_with_headers(
+            Method::DELETE,
+            "/notes",
+            Some(json!({"noteIds": nids})),
+            &[(CONFIRM_DESTRUCTIVE_HEADER, "yes")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["notesDeleted"], 1);
+    let cids = [
+        server.add_basic_card("three").await,
+        server.add_basic_card("four").await,
+    ];
+    let (status, _) = server
+        .request(
+            Method::DELETE,
+            "/cards",
+            Some(json!({"cardIds": cids, "confirm": true})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(server.with_col(|col| col.storage.total_cards()), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn change_note_notetype() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let (nid, reversed) = server.with_col(|col| {
+        col.storage.set_schema_modified_time(TimestampMillis(0))?;
+        let nid = col.storage.get_card(CardId(cid))?.unwrap().note_id;
+        let reversed = col.get_notetype_by_name("Basic (and reversed card)")?;
+        Ok((nid.0, reversed.unwrap().id.0))
+    });
+    let uri = format!("/notes/{nid}/change-notetype");
+    let request = |allow_schema_change: bool| {
+        json!({
+            "notetypeId": reversed,
+            "fieldMapping": {"0": 1, "1": 0},
+            "allowSchemaChange": allow_schema_change,
+        })
+    };
+
+    let (status, _) = server
+        .request(Method::POST, &uri, Some(request(false)))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, body) = server
+        .request(Method::POST, &uri, Some(request(true)))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["notetypeId"], reversed);
+    assert_eq!(body["fields"], json!(["back", "front"]));
+    assert_eq!(body["schemaModified"], true);
+    let card_count = server.with_col(|col| Ok(col.storage.all_cards_of_note(NoteId(nid))?.len()));
+    assert_eq!(card_count, 2);
+
+    let (status, _) = server
+        .request(
+            Method::POST,
+            &uri,
+            Some(json!({"notetypeId": reversed, "fieldMapping": {"0": 5}})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn notes_modified_and_deleted_since() -> Result<()> {
+    let server = TestServer::new()?;
+    let kept = server.add_basic_card("kept").await;
+    let removed = server.add_basic_card("removed").await;
+    let (kept_nid, removed_nid) = server.with_col(|col| {
+        let kept = col.storage.get_card(CardId(kept))?.unwrap().note_id;
+        let removed = col.storage.get_card(CardId(removed))?.unwrap().note_id;
+        col.storage
+            .db
+            .execute("update notes set mod = 100 where id = ?", [kept])?;
+        col.remove_notes(&[removed])?;
+        Ok((kept, removed))
+    });
+
+    let (status, body) = server
+        .request(Method::GET, "/notes/modified-since?since=100", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!([{"noteId": kept_nid, "modifiedAt": 100}]));
+    let (_, body) = server
+        .request(Method::GET, "/notes/modified-since?since=101", None)
+        .await;
+    assert_eq!(body, json!([]));
+
+    let (status, body) = server
+        .request(Method::GET, "/notes/deleted-since?since=0", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!([{"noteId": removed_nid}]));
+
+    let (status, _) = server
+        .request(Method::GET, "/notes/modified-since", None)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn review_history() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let from = server.with_col(|col| {
+        let mut card = col.storage.get_card(CardId(cid))?.unwrap();
+        card.ctype = CardType::Review;
+        card.queue = CardQueue::Review;
+        col.storage.update_card(&card)?;
+        // answered three days ago, and due two days ago
+        let answered = col
+            .timing_today()?
+            .next_day_at
+            .adding_secs(-86_400 * 4 + 60);
+        col.storage.add_revlog_entry(
+            &RevlogEntry {
+                id: answered.as_millis().into(),
+                cid: card.id,
+                button_chosen: 3,
+                interval: 1,
+                review_kind: RevlogReviewKind::Review,
+                ..Default::default()
+            },
+            true,
+        )?;
+        Ok(answered
+            .datetime(col.local_utc_offset_for_user()?)?
+            .format("%Y-%m-%d")
+            .to_string())
+    });
+
+    let (status, body) = server
+        .request(Method::GET, &format!("/stats/history?from={from}"), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body[0]["date"], from);
+    let series = |key: &str| -> Vec<u64> {
+        body.as_array()
+            .unwrap()
+            .iter()
+            .map(|day| day[key].as_u64().unwrap())
+            .collect()
+    };
+    assert_eq!(series("estimatedDue"), [0, 1, 1, 1]);
+    assert_eq!(series("reviewed"), [1, 0, 0, 0]);
+
+    for uri in [
+        "/stats/history?from=yesterday",
+        "/stats/history?from=2020-01-02&to=2020-01-01",
+        "/stats/history?from=1900-01-01",
+    ] {
+        let (status, _) = server.request(Method::GET, uri, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn weekly_history_follows_first_weekday() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    // answers on a Sunday at least a week ago, and the Monday after it
+    let sunday = server.with_col(|col| {
+        let today_start = col.timing_today()?.next_day_at.adding_secs(-86_400);
+        let offset = col.local_utc_offset_for_user()?;
+        let today = today_start.datetime(offset)?.date_naive();
+        let days_back = 7 + today.weekday().num_days_from_sunday() as i64;
+        for days_ago in [days_back, days_back - 1] {
+            // a couple of hours in, so daylight saving can't move it to
+            // another day
+            let answered = today_start.adding_secs(-86_400 * days_ago + 7200);
+            col.storage.add_revlog_entry(
+                &RevlogEntry {
+                    id: answered.as_millis().into(),
+                    cid: CardId(cid),
+                    button_chosen: 3,
+                    ..Default::default()
+                },
+                true,
+            )?;
+        }
+        Ok(today - Days::new(days_back as u64))
+    });
+    let date = |days: i64| {
+        (sunday + chrono::Duration::days(days))
+            .format("%Y-%m-%d")
+            .to_string()
+    };
+    let uri = format!(
+        "/stats/history?from={}&to={}&groupBy=week",
+        date(0),
+        date(7)
+    );
+    let weeks = |body: Value| -> Vec<(String, u64)> {
+        body.as_array()
+            .unwrap()
+            .iter()
+            .map(|week| {
+                (
+                    week["date"].as_str().unwrap().to_string(),
+                    week["reviewed"].as_u64().unwrap(),
+                )
+            })
+            .collect()
+    };
+
+    let (status, body) = server
+        .request(Method::GET, "/config/preferences", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"firstWeekday": "sunday", "locale": ""}));
+    let (_, body) = server.request(Method::GET, &uri, None).await;
+    assert_eq!(weeks(body), [(date(0), 2), (date(7), 0)]);
+
+    // with weeks starting on Monday, the Sunday ends the previous week
+    let (status, body) = server
+        .request(
+            Method::PUT,
+            "/config/preferences",
+            Some(json!({"firstWeekday": "monday", "locale": "en-AU"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"firstWeekday": "monday", "locale": "en-AU"}));
+    let (_, body) = server.request(Method::GET, &uri, None).await;
+    assert_eq!(weeks(body), [(date(-6), 1), (date(1), 1)]);
+
+    // omitted fields are kept
+    let (_, body) = server
+        .request(
+            Method::PUT,
+            "/config/preferences",
+            Some(json!({"locale": ""})),
+        )
+        .await;
+    assert_eq!(body, json!({"firstWeekday": "monday", "locale": ""}));
+    for body in [
+        json!({"locale": "en_US"}),
+        json!({"firstWeekday": "tuesday"}),
+    ] {
+        let (status, _) = server
+            .request(Method::PUT, "/config/preferences", Some(body))
+            .await;
+        assert!(status.is_client_error());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn known_config_keys() -> Result<()> {
+    let server = TestServer::new()?;
+    let (status, body) = server
+        .request(
+            Method::PUT,
+            "/config/known/collapseDecks",
+            Some(json!({"value": true})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({"key": "collapseDecks", "type": "bool", "value": true})
+    );
+    let (status, _) = server
+        .request(
+            Method::PUT,
+            "/config/known/collapseDecks",
+            Some(json!({"value": "yes"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // keys that affect scheduling are neither listed nor writable
+    let (_, body) = server.request(Method::GET, "/config/known", None).await;
+    let keys: Vec<_> = body["keys"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["key"].as_str().unwrap().to_string())
+        .collect();
+    assert!(keys.contains(&"collapseDecks".to_string()));
+    for key in ["sched2021", "fsrs", "cardStateCustomizer"] {
+        assert!(!keys.contains(&key.to_string()));
+        let (status, _) = server
+            .request(
+                Method::PUT,
+                &format!("/config/known/{key}"),
+                Some(json!({"value": true})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+    // nor is the locale, which is validated by /config/preferences
+    assert!(!keys.contains(&"locale".to_string()));
+    let (status, _) = server
+        .request(
+            Method::PUT,
+            "/config/known/locale",
+            Some(json!({"value": "not a locale"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn cards_modified_and_deleted_since() -> Result<()> {
+    let server = TestServer::new()?;
+    let kept = server.add_basic_card("kept").await;
+    let removed = server.add_basic_card("removed").await;
+    server.with_col(|col| {
+        col.storage
+            .db
+            .execute("update cards set mod = 100 where id = ?", [kept])?;
+        Ok(())
+    });
+    server
+        .request(
+            Method::DELETE,
+            "/cards",
+            Some(json!({"cardIds": [removed]})),
+        )
+        .await;
+
+    let before = TimestampSecs::now().0;
+    let (status, body) = server
+        .request(Method::GET, "/cards/modified-since?since=100", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["cards"], json!([{"cardId": kept, "modifiedAt": 100}]));
+    let server_time = body["serverTime"].as_i64().unwrap();
+    assert!(server_time >= before);
+    let (_, body) = server
+        .request(
+            Method::GET,
+            &format!("/cards/modified-since?since={server_time}"),
+            None,
+        )
+        .await;
+    assert_eq!(body["cards"], json!([]));
+
+    let (status, body) = server
+        .request(Method::GET, "/cards/deleted-since?since=0", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["cardIds"], json!([removed]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn fulltext_search() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server
+        .add_basic_card("The <i>mitochondria</i> is the powerhouse")
+        .await;
+    server.add_basic_card("unrelated").await;
+    let nid = server.with_col(|col| Ok(col.storage.get_card(CardId(cid))?.unwrap().note_id));
+
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/search/fulltext",
+            Some(json!({"query": "mitochondria"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["results"],
+        json!([{
+            "noteId": nid,
+            "field": "Front",
+            "snippet": "The <mark>mitochondria</mark> is the powerhouse",
+        }])
+    );
+
+    // results agree with the browser's search
+    let (_, body) = server
+        .request(
+            Method::POST,
+            "/search/fulltext",
+            Some(json!({"query": "mito -powerhouse"})),
+        )
+        .await;
+    assert_eq!(body["results"], json!([]));
+    let (_, body) = server
+        .request(
+            Method::POST,
+            "/search/fulltext",
+            Some(json!({"query": "power", "field": "Back"})),
+        )
+        .await;
+    assert_eq!(body["results"], json!([]));
+    assert_eq!(body["warnings"], json!([]));
+
+    // unknown fields are reported, but the rest of the search still runs
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/search/fulltext",
+            Some(json!({"query": r#"mito or "Back Side:x" or front:nc:mito*"#})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["results"][0]["noteId"], nid);
+    assert_eq!(
+        body["warnings"],
+        json!([{"kind": "unknownField", "field": "Back Side"}])
+    );
+
+    for body in [json!({"query": ""}), json!({"query": "a", "limit": 0})] {
+        let (status, _) = server
+            .request(Method::POST, "/search/fulltext", Some(body))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn browser_rows_are_stable_across_pages() -> Result<()> {
+    let server = TestServer::new()?;
+    let mut cids = vec![];
+    for front in ["b", "A", "<b>a</b>", "B", "c", "a"] {
+        cids.push(server.add_basic_card(front).await);
+    }
+
+    let fetch_pages = |body: Value| {
+        let server = &server;
+        async move {
+            let mut ids = vec![];
+            let mut keys = vec![];
+            for offset in (0..6).step_by(2) {
+                let mut body = body.clone();
+                body["offset"] = json!(offset);
+                body["limit"] = json!(2);
+                let (status, page) = server
+                    .request(Method::POST, "/search/rows", Some(body))
+                    .await;
+                assert_eq!(status, StatusCode::OK);
+                assert_eq!(page["total"], 6);
+                for row in page["rows"].as_array().unwrap() {
+                    ids.push(row["id"].as_i64().unwrap());
+                    keys.push(row["sortKey"].as_str().unwrap().to_string());
+                }
+            }
+            (ids, keys)
+        }
+    };
+
+    // case is ignored, and ties keep creation order on every page
+    let (ids, keys) = fetch_pages(json!({"query": ""})).await;
+    assert_eq!(ids, [cids[1], cids[2], cids[5], cids[0], cids[3], cids[4]]);
+    assert_eq!(keys, ["A", "a", "a", "b", "B", "c"]);
+    let (reversed, _) = fetch_pages(json!({"query": "", "reverse": true})).await;
+    assert_eq!(reversed, ids.iter().rev().copied().collect::<Vec<_>>());
+
+    // every note has the same Back, so only the tiebreak orders them
+    let (ids, keys) = fetch_pages(json!({"query": "", "sortField": "Back", "notes": true})).await;
+    let nids: Vec<i64> = server.with_col(|col| {
+        cids.iter()
+            .map(|&cid| Ok(col.storage.get_card(CardId(cid))?.unwrap().note_id.0))
+            .collect()
+    });
+    assert_eq!(ids, nids);
+    assert!(keys.iter().all(|key| key == "back"));
+
+    for body in [
+        json!({"query": "", "sortField": "Missing"}),
+        json!({"query": "", "limit": 0}),
+    ] {
+        let (status, _) = server
+            .request(Method::POST, "/search/rows", Some(body))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn search_timeframes() -> Result<()> {
+    let server = TestServer::new()?;
+    let before = server.add_basic_card("before").await;
+    let after = server.add_basic_card("after").await;
+    let resched = server.add_basic_card("resched").await;
+
+    // with a noon rollover, one card was added, edited and answered just
+    // before today started, and the others just after
+    let day_start = server.with_col(|col| {
+        col.set_rollover_for_current_scheduler(12)?;
+        let day_start = col.timing_today()?.next_day_at.adding_secs(-86_400);
+        let before_ms = day_start.as_millis().0 - 1;
+        col.storage.db.execute(
+            "update notes set mod = ? where id = (select nid from cards where id = ?)",
+            [day_start.0 - 1, before],
+        )?;
+        col.storage
+            .db
+            .execute("update cards set id = ? where id = ?", [before_ms, before])?;
+        for (cid, id, button_chosen, review_kind) in [
+            (before_ms, before_ms, 3, RevlogReviewKind::Learning),
+            (
+                after,
+                day_start.as_millis().0,
+                3,
+                RevlogReviewKind::Learning,
+            ),
+            (
+                resched,
+                day_start.as_millis().0,
+                0,
+                RevlogReviewKind::Manual,
+            ),
+        ] {
+            col.storage.add_revlog_entry(
+                &RevlogEntry {
+                    id: RevlogId(id),
+                    cid: CardId(cid),
+                    button_chosen,
+                    review_kind,
+                    ..Default::default()
+                },
+                false,
+            )?;
+        }
+        Ok(day_start)
+    });
+    let before = day_start.as_millis().0 - 1;
+
+    let search = |node: Value| {
+        let server = &server;
+        async move {
+            let (status, built) = server
+                .request(Method::POST, "/search/build", Some(json!({"node": node})))
+                .await;
+            assert_eq!(status, StatusCode::OK);
+            let query = built["search"].as_str().unwrap().to_string();
+            let (_, rows) = server
+                .request(Method::POST, "/search/rows", Some(json!({"query": query})))
+                .await;
+            let mut ids: Vec<i64> = rows["rows"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|row| row["id"].as_i64().unwrap())
+                .collect();
+            ids.sort_unstable();
+            (query, ids)
+        }
+    };
+
+    for (node, expected_query, expected_ids) in [
+        (
+            json!({"kind": "added", "days": 1}),
+            "added:1",
+            vec![after, resched],
+        ),
+        (
+            json!({"kind": "added", "days": 2}),
+            "added:2",
+            vec![before, after, resched],
+        ),
+        (
+            json!({"kind": "edited", "days": 1}),
+            "edited:1",
+            vec![after, resched],
+        ),
+        (
+            json!({"kind": "introduced", "days": 1}),
+            "introduced:1",
+            vec![after],
+        ),
+        (
+            json!({"kind": "introduced", "days": 2}),
+            "introduced:2",
+            vec![before, after],
+        ),
+        (json!({"kind": "rated", "days": 1}), "rated:1", vec![after]),
+        (
+            json!({"kind": "rated", "days": 2, "rating": "good"}),
+            "rated:2:3",
+            vec![before, after],
+        ),
+        (
+            json!({"kind": "rated", "days": 1, "rating": "again"}),
+            "rated:1:1",
+            vec![],
+        ),
+        (
+            json!({"kind": "rated", "days": 1, "rating": "rescheduled"}),
+            "resched:1",
+            vec![resched],
+        ),
+        (
+            json!({"kind": "group", "joiner": "or", "nodes": [
+                {"kind": "text", "text": "front:before"},
+                {"kind": "not", "node": {"kind": "added", "days": 1}},
+            ]}),
+            "front:before OR -added:1",
+            vec![before],
+        ),
+    ] {
+        assert_eq!(
+            search(node).await,
+            (expected_query.to_string(), expected_ids)
+        );
+    }
+
+    for node in [
+        json!({"kind": "added", "days": 0}),
+        json!({"kind": "group", "nodes": []}),
+        json!({"kind": "text", "text": "prop:"}),
+    ] {
+        let (status, _) = server
+            .request(Method::POST, "/search/build", Some(json!({"node": node})))
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn graves_since() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    server
+        .request(Method::DELETE, "/cards", Some(json!({"cardIds": [cid]})))
+        .await;
+
+    let (status, body) = server
+        .request(Method::GET, "/collection/graves-since?since=0", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["cards"], json!([cid]));
+    assert_eq!(body["notes"].as_array().unwrap().len(), 1);
+    assert_eq!(body["decks"], json!([]));
+    assert!(body["until"].as_i64().unwrap() >= TimestampSecs::now().0 - 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn set_ease() -> Result<()> {
+    let server = TestServer::new()?;
+    let mut cids = vec![];
+    for ease in [1300, 2000, 2800] {
+        let cid = server.add_basic_card("front").await;
+        server.with_col(|col| {
+            let mut card = col.storage.get_card(CardId(cid))?.unwrap();
+            card.ctype = CardType::Review;
+            card.queue = CardQueue::Review;
+            card.ease_factor = ease;
+            col.storage.update_card(&card)?;
+            Ok(())
+        });
+        cids.push(cid);
+    }
+
+    let (status, _) = server
+        .request(
+            Method::POST,
+            "/cards/set-ease",
+            Some(json!({"selector": {"deckId": 1}, "easeFactor": 5.5})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/cards/set-ease",
+            Some(json!({"selector": {"deckId": 1}, "easeFactor": 2.5, "onlyBelow": 2.5})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        (&body["updated"], &body["skippedFsrs"]),
+        (&json!(2), &json!(0))
+    );
+    let mut changed: Vec<i64> = serde_json::from_value(body["changes"]["cardIds"].clone()).unwrap();
+    changed.sort();
+    assert_eq!(changed, cids[..2]);
+    let eases = server.with_col(|col| {
+        cids.iter()
+            .map(|&cid| Ok(col.storage.get_card(CardId(cid))?.unwrap().ease_factor))
+            .collect::<Result<Vec<_>>>()
+    });
+    assert_eq!(eases, [2500, 2500, 2800]);
+
+    server.with_col(|col| col.set_config_bool(BoolKey::Fsrs, true, false).map(|_| ()));
+    let (_, body) = server
+        .request(
+            Method::POST,
+            "/cards/set-ease",
+            Some(json!({"selector": {"cardIds": cids}, "easeFactor": 2.0})),
+        )
+        .await;
+    assert_eq!(
+        body,
+        json!({
+            "updated": 0,
+            "skippedFsrs": 3,
+            "changes": {"cardIds": [], "noteIds": [], "deckIds": []},
+        })
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn mutations_report_changed_ids() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let nid = server.with_col(|col| Ok(col.storage.get_card(CardId(cid))?.unwrap().note_id.0));
+
+    let (_, body) = server
+        .request(
+            Method::PUT,
+            &format!("/cards/{cid}"),
+            Some(json!({"fields": {"Front": "edited"}})),
+        )
+        .await;
+    assert_eq!(
+        body["changes"],
+        json!({"cardIds": [], "noteIds": [nid], "deckIds": []})
+    );
+    let (_, body) = server
+        .request(
+            Method::PUT,
+            &format!("/cards/{cid}/schedule"),
+            Some(json!({"due": "3"})),
+        )
+        .await;
+    assert_eq!(
+        body["changes"],
+        json!({"cardIds": [cid], "noteIds": [], "deckIds": []})
+    );
+    let (_, body) = server
+        .request(Method::POST, &format!("/notes/{nid}/mark"), None)
+        .await;
+    assert_eq!(
+        body["changes"],
+        json!({"cardIds": [], "noteIds": [nid], "deckIds": []})
+    );
+
+    // adding to a new deck reports the deck too
+    let (_, body) = server
+        .request(
+            Method::POST,
+            "/cards",
+            Some(json!({
+                "deckName": "Added",
+                "notetypeName": "Basic",
+                "fields": {"Front": "added", "Back": "back"},
+                "tags": [],
+            })),
+        )
+        .await;
+    let added_cid = body["cardIds"][0].as_i64().unwrap();
+    let (added_nid, added_did) = server.with_col(|col| {
+        let card = col.storage.get_card(CardId(added_cid))?.unwrap();
+        Ok((card.note_id.0, card.deck_id.0))
+    });
+    assert_eq!(
+        body["changes"],
+        json!({"cardIds": [added_cid], "noteIds": [added_nid], "deckIds": [added_did]})
+    );
+
+    // deletes report what they removed
+    let (_, body) = server
+        .request(
+            Method::DELETE,
+            "/cards",
+            Some(json!({"cardIds": [added_cid]})),
+        )
+        .await;
+    assert_eq!(
+        body["changes"],
+        json!({"cardIds": [added_cid], "noteIds": [added_nid], "deckIds": []})
+    );
+    let (_, body) = server
+        .request(Method::DELETE, "/notes", Some(json!({"noteIds": [nid]})))
+        .await;
+    assert_eq!(
+        body["changes"],
+        json!({"cardIds": [cid], "noteIds": [nid], "deckIds": []})
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn undo_groups() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let nid = server.with_col(|col| Ok(col.storage.get_card(CardId(cid))?.unwrap().note_id));
+    let group = [(UNDO_GROUP_HEADER, "a"), (UNDO_GROUP_NAME_HEADER, "Edit")];
+
+    // an edit, a tag and a batch of answers become one undo step
+    let (status, _) = server
+        .request_with_headers(
+            Method::PUT,
+            &format!("/cards/{cid}"),
+            Some(json!({"fields": {"Front": "edited"}})),
+            &group,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    server
+        .request_with_headers(
+            Method::POST,
+            &format!("/notes/{nid}/mark"),
+            None,
+            &group[..1],
+        )
+        .await;
+    let (_, body) = server
+        .request_with_headers(
+            Method::POST,
+            "/study/answers/batch",
+            Some(json!({"answers": [{
+                "cardId": cid,
+                "rating": "good",
+                "answeredAtMillis": TimestampMillis::now().0,
+            }]})),
+            &group[..1],
+        )
+        .await;
+    assert_eq!(body["answered"], 1);
+
+    server.with_col(|col| {
+        assert_eq!(col.can_undo(), Some(&Op::Custom("Edit".into())));
+        col.undo()?;
+        let note = col.storage.get_note(nid)?.unwrap();
+        assert_eq!(note.fields()[0], "front");
+        assert!(note.tags.is_empty());
+        let card = col.storage.get_card(CardId(cid))?.unwrap();
+        assert_eq!(card.queue, CardQueue::New);
+        assert_ne!(col.can_undo(), Some(&Op::Custom("Edit".into())));
+        Ok(())
+    });
+    Ok(())
+}
+
+#[tokio::test]
+async fn undo_group_boundaries() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let nid = server.with_col(|col| Ok(col.storage.get_card(CardId(cid))?.unwrap().note_id));
+    let mark = format!("/notes/{nid}/mark");
+    let group = [(UNDO_GROUP_HEADER, "b"), (UNDO_GROUP_NAME_HEADER, "B")];
+    let set_group = |op: fn(&mut UndoGroup)| {
+        let mut state = server.server.state.lock().unwrap();
+        op(state
+            .users
+            .get_mut("hkey")
+            .unwrap()
+            .undo_group
+            .as_mut()
+            .unwrap());
+    };
+
+    // a change outside the group ends it
+    server
+        .request_with_headers(Method::POST, &mark, None, &group)
+        .await;
+    server.request(Method::DELETE, &mark, None).await;
+    server
+        .request_with_headers(Method::POST, &mark, None, &group)
+        .await;
+    // as does the group expiring
+    set_group(|group| group.last_used -= UNDO_GROUP_EXPIRY);
+    server
+        .request_with_headers(Method::DELETE, &mark, None, &group)
+        .await;
+    // or growing too large
+    set_group(|group| group.requests = MAX_UNDO_GROUP_REQUESTS);
+    server
+        .request_with_headers(Method::POST, &mark, None, &group)
+        .await;
+
+    let undone = server.with_col(|col| {
+        (0..5)
+            .map(|_| Ok(col.undo()?.output.undone_op))
+            .collect::<Result<Vec<_>>>()
+    });
+    let named = Op::Custom("B".into());
+    assert_eq!(undone[..3], [named.clone(), named.clone(), named.clone()]);
+    assert_ne!(undone[3], named);
+    assert_eq!(undone[4], named);
+    Ok(())
+}
+
+#[tokio::test]
+async fn undo_group_excludes_concurrent_requests() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let nid = server.with_col(|col| Ok(col.storage.get_card(CardId(cid))?.unwrap().note_id));
+    let mark = format!("/notes/{nid}/mark");
+    let group = [(UNDO_GROUP_HEADER, "c"), (UNDO_GROUP_NAME_HEADER, "C")];
+    server
+        .request_with_headers(Method::POST, &mark, None, &group)
+        .await;
+
+    // while the state is held, the next grouped request waits inside the
+    // group, and an ungrouped one made meanwhile has to wait for it
+    let held = server.server.state.lock().unwrap();
+    let grouped = server.spawn_request(Method::DELETE, &mark, None, &group);
+    while server.server.undo_group_gate.try_read().is_ok() {
+        sleep(Duration::from_millis(1)).await;
+    }
+    let ungrouped = server.spawn_request(
+        Method::POST,
+        "/cards",
+        Some(json!({
+            "deckName": "Default",
+            "notetypeName": "Basic",
+            "fields": {"Front": "ungrouped", "Back": "back"},
+            "tags": [],
+        })),
+        &[],
+    );
+    sleep(Duration::from_millis(20)).await;
+    assert!(!ungrouped.is_finished());
+    drop(held);
+    assert_eq!(grouped.await.unwrap().status(), StatusCode::OK);
+    assert_eq!(ungrouped.await.unwrap().status(), StatusCode::OK);
+
+    // the ungrouped card is its own step, after the group
+    let undone = server.with_col(|col| {
+        (0..2)
+            .map(|_| Ok(col.undo()?.output.undone_op))
+            .collect::<Result<Vec<_>>>()
+    });
+    let named = Op::Custom("C".into());
+    assert_ne!(undone[0], named);
+    assert_eq!(undone[1], named);
+    server.with_col(|col| {
+        assert_eq!(col.storage.get_all_cards().len(), 1);
+        assert!(col.storage.get_note(nid)?.unwrap().tags.is_empty());
+        Ok(())
+    });
+    Ok(())
+}
+
+#[tokio::test]
+async fn sync_status() -> Result<()> {
+    let server = TestServer::new()?;
+    server.with_col(|col| {
+        col.storage
+            .db
+            .execute("update col set usn = 5, ls = scm", [])?;
+        Ok(())
+    });
+    server.add_basic_card("front").await;
+
+    let (status, body) = server.request(Method::GET, "/sync/status", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["pending"]["cards"], 1);
+    assert_eq!(body["pending"]["notes"], 1);
+    assert_eq!(body["pending"]["revlog"], 0);
+    assert_eq!(body["lastSync"], body["schemaModified"]);
+    assert_eq!(body["fullSyncRequired"], false);
+
+    server.with_col(|col| {
+        col.storage.db.execute("update col set scm = scm + 1", [])?;
+        Ok(())
+    });
+    let (_, body) = server.request(Method::GET, "/sync/status", None).await;
+    assert_eq!(body["fullSyncRequired"], true);
+    Ok(())
+}
+
+#[tokio::test]
+async fn collection_usn() -> Result<()> {
+    let server = TestServer::new()?;
+    server.with_col(|col| {
+        col.storage
+            .db
+            .execute("update col set usn = 42, ls = scm", [])?;
+        Ok(())
+    });
+    let (status, body) = server.request(Method::GET, "/collection/usn", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"usn": 42, "schemaModified": false}));
+
+    server.with_col(|col| {
+        col.storage.db.execute("update col set scm = scm + 1", [])?;
+        Ok(())
+    });
+    let (_, body) = server.request(Method::GET, "/collection/usn", None).await;
+    assert_eq!(body["schemaModified"], json!(true));
+    Ok(())
+}
+
+#[tokio::test]
+async fn collection_storage() -> Result<()> {
+    let server = TestServer::new()?;
+    let media_folder = with_user(&server.server, |user| Ok(user.media.media_folder.clone()))
+        .await
+        .ok()
+        .unwrap();
+    write_file(media_folder.join("a.jpg"), "12345")?;
+
+    let (status, body) = server
+        .request(Method::GET, "/collection/storage", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["mediaBytes"], 5);
+    assert_eq!(body["mediaCount"], 1);
+    assert_eq!(body["backupCount"], 0);
+    let database_bytes = body["databaseBytes"].as_u64().unwrap();
+    assert!(database_bytes > 0);
+    assert_eq!(body["totalBytes"], database_bytes + 5);
+    Ok(())
+}
+
+#[tokio::test]
+async fn scheduled_backups() -> Result<()> {
+    let server = TestServer::new()?;
+    let (status, _) = server
+        .request(
+            Method::PUT,
+            "/collection/backups/config",
+            Some(json!({"intervalHours": 0, "daily": 1, "weekly": 1, "monthly": 1})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = server
+        .request(
+            Method::PUT,
+            "/collection/backups/config",
+            Some(json!({"intervalHours": 6, "daily": 2, "weekly": 1, "monthly": 1})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, config) = server
+        .request(Method::GET, "/collection/backups/config", None)
+        .await;
+    assert_eq!(
+        config,
+        json!({"intervalHours": 6.0, "daily": 2, "weekly": 1, "monthly": 1})
+    );
+
+    // due straight away without a backup
+    let (_, status) = server
+        .request(Method::GET, "/collection/backups/status", None)
+        .await;
+    assert!(status["nextBackup"].as_i64().unwrap() <= TimestampSecs::now().0);
+    assert_eq!(status["lastBackup"], json!(null));
+
+    server.server.run_scheduled_backups();
+    let (_, status) = server
+        .request(Method::GET, "/collection/backups/status", None)
+        .await;
+    assert_eq!(status["lastBackup"]["success"], json!(true));
+    assert!(status["nextBackup"].as_i64().unwrap() > TimestampSecs::now().0 + 5 * 3600);
+    let (_, backups) = server
+        .request(Method::GET, "/collection/backups", None)
+        .await;
+    assert_eq!(backups["backups"].as_array().unwrap().len(), 1);
+
+    // not repeated until the interval elapses
+    server.add_basic_card("front").await;
+    let time = status["lastBackup"]["time"].clone();
+    server.server.run_scheduled_backups();
+    let (_, status) = server
+        .request(Method::GET, "/collection/backups/status", None)
+        .await;
+    assert_eq!(status["lastBackup"]["time"], time);
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_cards_by_cursor() -> Result<()> {
+    let server = TestServer::new()?;
+    let mut cids = vec![];
+    for front in ["one", "two", "three"] {
+        cids.push(server.add_basic_card(front).await);
+    }
+
+    let mut listed = vec![];
+    let mut uri = "/cards?limit=2".to_string();
+    loop {
+        let (status, page) = server.request(Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        for card in page["cards"].as_array().unwrap() {
+            listed.push(card["cardId"].as_i64().unwrap());
+        }
+        match page["nextCursor"].as_str() {
+            Some(cursor) => uri = format!("/cards?limit=2&cursor={cursor}"),
+            None => break,
+        }
+    }
+    assert_eq!(listed, cids);
+
+    let (status, _) = server
+        .request(Method::GET, "/cards?cursor=not-a-cursor", None)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_cards_by_ease() -> Result<()> {
+    let server = TestServer::new()?;
+    let mut cids = vec![];
+    for (front, ease) in [("easy", 2500), ("hard", 1300), ("medium", 1700)] {
+        let cid = server.add_basic_card(front).await;
+        server.with_col(|col| {
+            let mut card = col.storage.get_card(CardId(cid))?.unwrap();
+            card.ctype = CardType::Review;
+            card.queue = CardQueue::Review;
+            card.ease_factor = ease;
+            col.storage.update_card(&card)
+        });
+        cids.push(cid);
+    }
+    let listed = |page: &Value| -> Vec<i64> {
+        page["cards"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|card| card["cardId"].as_i64().unwrap())
+            .collect()
+    };
+
+    let (status, page) = server
+        .request(Method::GET, "/cards?minEase=1.3&maxEase=1.8&deckId=1", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed(&page), [cids[1], cids[2]]);
+    assert_eq!(page["nextCursor"], Value::Null);
+    let (_, page) = server.request(Method::GET, "/cards?minEase=2", None).await;
+    assert_eq!(listed(&page), [cids[0]]);
+
+    for uri in [
+        "/cards?minEase=2&maxEase=1",
+        "/cards?deckId=1",
+        "/cards?maxEase=2&cursor=abc",
+    ] {
+        let (status, _) = server.request(Method::GET, uri, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn long_overdue_cards() -> Result<()> {
+    let server = TestServer::new()?;
+    let mut cids = vec![];
+    for (front, days_overdue) in [("recent", 3), ("oldest", 60), ("old", 45)] {
+        let cid = server.add_basic_card(front).await;
+        server.with_col(|col| {
+            let today = col.timing_today()?.days_elapsed as i32;
+            let mut card = col.storage.get_card(CardId(cid))?.unwrap();
+            card.ctype = CardType::Review;
+            card.queue = CardQueue::Review;
+            card.due = today - days_overdue;
+            col.storage.update_card(&card)
+        });
+        cids.push(cid);
+    }
+
+    let (status, cards) = server
+        .request(
+            Method::GET,
+            "/cards/long-overdue?overdueDays=30&deckId=1",
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        cards,
+        json!([
+            {"cardId": cids[1], "daysOverdue": 60},
+            {"cardId": cids[2], "daysOverdue": 45},
+        ])
+    );
+    let (_, cards) = server
+        .request(Method::GET, "/cards/long-overdue?overdueDays=60", None)
+        .await;
+    assert_eq!(cards, json!([]));
+
+    let (status, _) = server
+        .request(
+            Method::GET,
+            "/cards/long-overdue?overdueDays=1&deckId=123",
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn deck_list_counts() -> Result<()> {
+    let server = TestServer::new()?;
+    server.add_basic_card("front").await;
+    let child = server.with_col(|col| Ok(DeckAdder::new("Default::Child").add(col).id.0));
+
+    let (status, body) = server.request(Method::GET, "/decks", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({"decks": [
+            {"deckId": 1, "name": "Default", "level": 1, "new": 1, "learning": 0, "review": 0},
+            {"deckId": child, "name": "Default::Child", "level": 2, "new": 0, "learning": 0, "review": 0},
+        ]})
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn copy_deck_to_new_collection() -> Result<()> {
+    let body = json!({"name": "copy.anki2"});
+    let server = TestServer::new()?;
+    let (status, _) = server
+        .request(Method::POST, "/decks/1/copy-to-new", Some(body.clone()))
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let copy_base = tempdir()?;
+    let copy_folder = copy_base.path().to_owned();
+    let server = TestServer::configured(|server| server.copy_base = Some(copy_folder))?;
+    server.add_basic_card("front").await;
+    // copies can only be made in the user's own folder
+    for name in ["../other/copy.anki2", "/tmp/copy.anki2", "..", ""] {
+        let (status, _) = server
+            .request(
+                Method::POST,
+                "/decks/1/copy-to-new",
+                Some(json!({ "name": name })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{name}");
+    }
+    let (status, response) = server
+        .request(Method::POST, "/decks/1/copy-to-new", Some(body.clone()))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response, body);
+    let copy = CollectionBuilder::new(copy_base.path().join("user/copy.anki2")).build()?;
+    assert_eq!(copy.storage.get_all_cards().len(), 1);
+    // existing copies aren't overwritten
+    let (status, _) = server
+        .request(Method::POST, "/decks/1/copy-to-new", Some(body))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[cfg(feature = "web-ui")]
+#[tokio::test]
+async fn web_ui() -> Result<()> {
+    let server = TestServer::new()?;
+    let get = |uri: &'static str| {
+        let router = server.router.clone();
+        async move {
+            let request = Request::get(uri).body(Body::empty()).unwrap();
+            let response = router.oneshot(request).await.unwrap();
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, headers, body)
+        }
+    };
+
+    let (status, headers, body) = get("/ui").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_TYPE], "text/html; charset=utf-8");
+    assert!(String::from_utf8_lossy(&body).contains("/ui/app.js"));
+    let (status, headers, _) = get("/ui/app.js").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        headers[header::CONTENT_TYPE],
+        "text/javascript; charset=utf-8"
+    );
+    assert_eq!(get("/ui/missing.js").await.0, StatusCode::NOT_FOUND);
+
+    // media is served from the user's media folder, and only from there
+    let media_folder = with_user(&server.server, |user| Ok(user.media.media_folder.clone()))
+        .await
+        .ok()
+        .unwrap();
+    write_file(media_folder.join("dog.jpg"), "woof")?;
+    let (status, headers, body) = get("/ui/media/dog.jpg").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_TYPE], "image/jpeg");
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    assert_eq!(headers[header::CONTENT_SECURITY_POLICY], "sandbox");
+    assert!(!headers.contains_key(header::CONTENT_DISPOSITION));
+    assert_eq!(&body[..], b"woof");
+    // anything a browser could run is only offered as a download
+    for filename in ["page.html", "drawing.svg", "script.js"] {
+        write_file(media_folder.join(filename), "<script></script>")?;
+    }
+    for uri in [
+        "/ui/media/page.html",
+        "/ui/media/drawing.svg",
+        "/ui/media/script.js",
+    ] {
+        let (status, headers, _) = get(uri).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::CONTENT_TYPE], "application/octet-stream");
+        assert_eq!(headers[header::CONTENT_DISPOSITION], "attachment");
+        assert_eq!(headers[header::CONTENT_SECURITY_POLICY], "sandbox");
+    }
+    write_file(media_folder.with_file_name("secret.txt"), "")?;
+    assert_eq!(
+        get("/ui/media/..%2Fsecret.txt").await.0,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(get("/ui/media/..").await.0, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn deck_config_usage() -> Result<()> {
+    let server = TestServer::new()?;
+    let (dcid, did) = server.with_col(|col| {
+        let mut config = DeckConfig {
+            name: "preset".into(),
+            ..Default::default()
+        };
+        col.add_or_update_deck_config(&mut config)?;
+        let mut deck = DeckAdder::new("deck").add(col);
+        deck.normal_mut()?.config_id = config.id.0;
+        col.add_or_update_deck(&mut deck)?;
+        Ok((config.id, deck.id))
+    });
+
+    let (_, deck) = server
+        .request(Method::GET, &format!("/decks/{did}"), None)
+        .await;
+    assert_eq!(deck["configName"], "preset");
+    let (status, usage) = server
+        .request(Method::GET, &format!("/deck-configs/{dcid}/decks"), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(usage["decks"][0]["name"], "deck");
+
+    let (status, removed) = server
+        .request(Method::DELETE, &format!("/deck-configs/{dcid}"), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(removed["reassignedDecks"][0]["deckId"], did.0);
+    let (_, deck) = server
+        .request(Method::GET, &format!("/decks/{did}"), None)
+        .await;
+    assert_eq!(deck["configId"], 1);
+    let (status, _) = server
+        .request(Method::DELETE, "/deck-configs/1", None)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn deck_config_interval_settings() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    server.with_col(|col| {
+        let mut card = col.storage.get_card(CardId(cid))?.unwrap();
+        card.ctype = CardType::Review;
+        card.queue = CardQueue::Review;
+        card.interval = 100;
+        card.due = col.timing_today()?.days_elapsed as i32;
+        card.ease_factor = 2500;
+        col.storage.update_card(&card)?;
+        Ok(())
+    });
+
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/deck-configs/1/preview-change",
+            Some(json!({"maximumReviewInterval": 30})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["reviewCards"], 1);
+    assert_eq!(body["shorter"], 1);
+    assert!(body["daysShorter"].as_u64().unwrap() > 200);
+    let (_, body) = server
+        .request(
+            Method::PUT,
+            "/deck-configs/1",
+            Some(json!({"intervalMultiplier": 1.5})),
+        )
+        .await;
+    assert_eq!(body["maximumReviewInterval"], 36_500);
+    assert_eq!(body["intervalMultiplier"], 1.5);
+
+    let (status, body) = server
+        .request(
+            Method::PUT,
+            "/deck-configs/1",
+            Some(json!({"maximumReviewInterval": 0})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    let (status, _) = server
+        .request(
+            Method::POST,
+            "/deck-configs/123/preview-change",
+            Some(json!({})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn decks_containing_tag() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let (status, _) = server
+        .request(
+            Method::PUT,
+            &format!("/cards/{cid}"),
+            Some(json!({"fields": {}, "tags": ["lang::french"]})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = server.request(Method::GET, "/tags/lang/decks", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["decks"],
+        json!([{"deckId": 1, "name": "Default", "cardCount": 1}])
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn tag_counts() -> Result<()> {
+    let server = TestServer::new()?;
+    for tags in [json!(["lang::french"]), json!(["Lang::French", "lang"])] {
+        let cid = server.add_basic_card("front").await;
+        server
+            .request(
+                Method::PUT,
+                &format!("/cards/{cid}"),
+                Some(json!({"fields": {}, "tags": tags})),
+            )
+            .await;
+    }
+
+    let (status, body) = server.request(Method::GET, "/tags/counts", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!([
+            {"name": "lang", "noteCount": 1, "totalNoteCount": 2, "dueCardCount": 0},
+            {"name": "lang::french", "noteCount": 2, "totalNoteCount": 2, "dueCardCount": 0},
+        ])
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn study_session_day_rollover() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let (_, session) = server
+        .request(Method::POST, "/study/sessions", Some(json!({"deckId": 1})))
+        .await;
+    let session_id = session["sessionId"].as_str().unwrap();
+    assert!(session.get("dayRolledOver").is_none());
+    let answer_uri = format!("/study/sessions/{session_id}/answer");
+    let answer = json!({"cardId": cid, "rating": "good"});
+
+    server.with_col(|col| {
+        let crt = col.storage.creation_stamp()?;
+        col.set_creation_stamp(crt.adding_secs(-86_400))
+    });
+    // the card was shown yesterday, so the answer is rejected
+    let (status, _) = server
+        .request(Method::POST, &answer_uri, Some(answer.clone()))
+        .await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+    let (_, body) = server
+        .request(
+            Method::GET,
+            &format!("/study/sessions/{session_id}/current"),
+            None,
+        )
+        .await;
+    assert_eq!(body["dayRolledOver"], true);
+    assert_eq!(body["card"]["cardId"], cid);
+    assert_eq!(body["answered"], 0);
+
+    // the client has been told, so answers are accepted again
+    let (status, body) = server
+        .request(Method::POST, &answer_uri, Some(answer))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.get("dayRolledOver").is_none());
+    assert_eq!(body["answered"], 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn study_session_defers_siblings() -> Result<()> {
+    let server = TestServer::new()?;
+    // the first cloze is in a subdeck that buries siblings, and the others in
+    // a deck that doesn't, so all three are gathered into the queues
+    let (deck_id, cids) = server.with_col(|col| {
+        let parent = DeckAdder::new("parent").add(col);
+        let child = DeckAdder::new("parent::child")
+            .with_config(|config| config.inner.bury_new = true)
+            .add(col);
+        let note = NoteAdder::cloze(col)
+            .fields(&["{{c1::one}} {{c2::two}} {{c3::three}}", ""])
+            .deck(parent.id)
+            .add(col);
+        NoteAdder::basic(col).deck(parent.id).add(col);
+        let cids = col
+            .storage
+            .all_card_ids_of_note_in_template_order(note.id)?;
+        col.set_deck(&cids[..1], child.id)?;
+        Ok((parent.id.0, cids))
+    });
+
+    let (_, mut body) = server
+        .request(
+            Method::POST,
+            "/study/sessions",
+            Some(json!({"deckId": deck_id})),
+        )
+        .await;
+    assert_eq!(body["counts"]["new"], 4);
+    let session_id = body["sessionId"].as_str().unwrap().to_string();
+    let answer_uri = format!("/study/sessions/{session_id}/answer");
+    let mut shown_notes = vec![];
+    while !body["card"].is_null() {
+        shown_notes.push(body["card"]["noteId"].as_i64().unwrap());
+        let card_id = body["card"]["cardId"].clone();
+        let (status, next) = server
+            .request(
+                Method::POST,
+                &answer_uri,
+                Some(json!({"cardId": card_id, "rating": "easy"})),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        if card_id == cids[0].0 {
+            let mut deferred: Vec<i64> =
+                serde_json::from_value(next["deferredSiblings"].clone()).unwrap();
+            deferred.sort_unstable();
+            assert_eq!(deferred, [cids[1].0, cids[2].0]);
+        } else {
+            assert!(next.get("deferredSiblings").is_none());
+        }
+        body = next;
+    }
+    // the two deferred clozes were never shown
+    assert_eq!(shown_notes.len(), 2);
+    assert!(shown_notes.windows(2).all(|pair| pair[0] != pair[1]));
+    Ok(())
+}
+
+#[tokio::test]
+async fn answer_batch() -> Result<()> {
+    let server = TestServer::new()?;
+    let modified = server.add_basic_card("modified").await;
+    let unmodified = server.add_basic_card("unmodified").await;
+    let now = TimestampMillis::now();
+    let snapshot_at = now.as_secs().0 - 100;
+    server.with_col(|col| {
+        let mut card = col.storage.get_card(CardId(modified))?.unwrap();
+        card.mtime = TimestampSecs::now();
+        col.storage.update_card(&card)
+    });
+    let answer = |card_id: i64, answered_at: i64| json!({"cardId": card_id, "rating": "good", "answeredAtMillis": answered_at});
+
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/study/answers/batch",
+            Some(json!({"snapshotAt": snapshot_at, "answers": [
+                answer(modified, now.0 - 2000),
+                answer(modified, now.0 - 1000),
+                answer(unmodified, now.0 - 3000),
+                answer(unmodified, now.0 + 60_000),
+                answer(unmodified, (snapshot_at - 10) * 1000),
+            ]})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["answered"], 1);
+    let statuses: Vec<_> = body["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|result| (result["cardId"].clone(), result["status"].clone()))
+        .collect();
+    // a conflicting card stays conflicting, and answers outside the snapshot
+    // and the present are refused
+    assert_eq!(
+        statuses,
+        [
+            (json!(unmodified), json!("failed")),
+            (json!(unmodified), json!("answered")),
+            (json!(modified), json!("conflict")),
+            (json!(modified), json!("conflict")),
+            (json!(unmodified), json!("failed")),
+        ]
+    );
+    // the review is recorded at the time it was made
+    server.with_col(|col| {
+        let revlog = col
+            .storage
+            .get_revlog_entries_for_card(CardId(unmodified))?;
+        assert_eq!(revlog.len(), 1);
+        assert_eq!(revlog[0].id.0, now.0 - 3000);
+        Ok(())
+    });
+
+    let answers: Vec<_> = (0..1001).map(|_| answer(unmodified, now.0)).collect();
+    let (status, _) = server
+        .request(
+            Method::POST,
+            "/study/answers/batch",
+            Some(json!({"answers": answers})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn pause_new_cards() -> Result<()> {
+    let server = TestServer::new()?;
+    server.add_basic_card("front").await;
+    let (status, body) = server
+        .request(
+            Method::PUT,
+            "/decks/1/new-paused",
+            Some(json!({"paused": true})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["decksChanged"], 1);
+    let (_, deck) = server.request(Method::GET, "/decks/1", None).await;
+    assert_eq!(deck["newPaused"], true);
+
+    let (_, next) = server
+        .request(Method::GET, "/study/next?deckId=1", None)
+        .await;
+    assert_eq!(next["card"], Value::Null);
+    assert_eq!(next["counts"]["new"], 0);
+    let (status, congrats) = server
+        .request(Method::GET, "/study/congrats?deckId=1", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(congrats["newRemaining"], true);
+    assert_eq!(congrats["newPaused"], true);
+
+    server
+        .request(
+            Method::PUT,
+            "/decks/1/new-paused",
+            Some(json!({"paused": false})),
+        )
+        .await;
+    let (_, next) = server
+        .request(Method::GET, "/study/next?deckId=1", None)
+        .await;
+    assert_eq!(next["counts"]["new"], 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn answer_button_stats() -> Result<()> {
+    let server = TestServer::new()?;
+    server.add_basic_card("front").await;
+    let (_, session) = server
+        .request(Method::POST, "/study/sessions", Some(json!({"deckId": 1})))
+        .await;
+    let session_id = session["sessionId"].as_str().unwrap();
+    server
+        .request(
+            Method::POST,
+            &format!("/study/sessions/{session_id}/answer"),
+            Some(json!({"rating": "again"})),
+        )
+        .await;
+
+    let (status, stats) = server
+        .request(
+            Method::GET,
+            "/stats/answer-buttons?search=deck:Default&days=7",
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(stats["days"], 7);
+    assert_eq!(stats["learning"]["again"], 1);
+    assert_eq!(stats["mature"]["again"], 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn interval_stats() -> Result<()> {
+    let server = TestServer::new()?;
+    server.add_basic_card("new").await;
+    let cid = server.add_basic_card("review").await;
+    server.with_col(|col| {
+        let mut card = col.storage.get_card(CardId(cid))?.unwrap();
+        card.ctype = CardType::Review;
+        card.queue = CardQueue::Review;
+        card.interval = 5;
+        col.storage.update_card(&card)
+    });
+
+    let (status, stats) = server
+        .request(Method::GET, "/stats/intervals?search=deck:Default", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(stats["intervals"]["median"], 5.0);
+    let buckets = stats["intervals"]["buckets"].as_array().unwrap();
+    assert_eq!(
+        buckets.last().unwrap(),
+        &json!({"start": 5, "end": 6, "count": 1})
+    );
+    assert_eq!(stats["stability"], Value::Null);
+    Ok(())
+}
+
+#[tokio::test]
+async fn workload_stats() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("review").await;
+    server.with_col(|col| {
+        let mut card = col.storage.get_card(CardId(cid))?.unwrap();
+        card.ctype = CardType::Review;
+        card.queue = CardQueue::Review;
+        card.interval = 5;
+        card.due = col.timing_today()?.days_elapsed as i32 - 2;
+        col.storage.update_card(&card)
+    });
+
+    let (status, stats) = server
+        .request(Method::GET, "/stats/workload?days=2&fallbackSecs=30", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(stats["decks"][0]["name"], "Default");
+    assert_eq!(stats["decks"][0]["due"], json!([1, 0]));
+    assert_eq!(stats["totalMinutes"], json!([0.5, 0.0]));
+
+    let (status, _) = server
+        .request(Method::GET, "/stats/workload?days=0", None)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    Ok(())
+}
+
+#[tokio::test]
+async fn error_statuses() -> Result<()> {
+    let server = TestServer::new()?;
+
+    let (status, body) = server.request(Method::GET, "/cards/1234", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], 404);
+
+    let (status, _) = server
+        .request(
+            Method::POST,
+            "/cards",
+            Some(json!({
+                "deckName": "Default",
+                "notetypeName": "no such notetype",
+                "fields": {},
+                "tags": [],
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // malformed payloads are rejected before reaching the handler
+    let (status, body) = server
+        .request(Method::POST, "/cards", Some(json!({"deckName": 1})))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], 400);
+
+    let cid = server.add_basic_card("front").await;
+    let (status, _) = server
+        .request(
+            Method::PUT,
+            &format!("/cards/{cid}/schedule"),
+            Some(json!({"due": "not a date"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    assert_eq!(status_of(AnkiError::Existing), StatusCode::CONFLICT);
+    let network_error = |kind| AnkiError::NetworkError {
+        source: NetworkError {
+            info: String::new(),
+            kind,
+        },
+    };
+    assert_eq!(
+        status_of(network_error(NetworkErrorKind::Timeout)),
+        StatusCode::GATEWAY_TIMEOUT
+    );
+    assert_eq!(
+        status_of(network_error(NetworkErrorKind::Offline)),
+        StatusCode::BAD_GATEWAY
+    );
+    assert_eq!(
+        status_of(AnkiError::Interrupted),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn import_logs() -> Result<()> {
+    let mut col = Collection::new();
+    NoteAdder::basic(&mut col).add(&mut col);
+    let apkg = tempfile::NamedTempFile::new()?;
+    col.export_apkg(
+        apkg.path(),
+        ExportAnkiPackageOptions {
+            with_scheduling: false,
+            with_deck_configs: false,
+            with_media: false,
+            legacy: false,
+        },
+        SearchNode::WholeCollection,
+        None,
+    )?;
+    let data = read_file(apkg.path())?;
+    let hash = hex::encode(Sha256::digest(&data));
+
+    let mock = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/deck.apkg"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(data))
+        .mount(&mock)
+        .await;
+    let url = format!("{}/deck.apkg", mock.uri());
+    // the mock server is on a loopback address
+    let (status, _) = TestServer::new()?
+        .request(
+            Method::POST,
+            "/import/apkg-url",
+            Some(json!({"url": url, "allowHttp": true})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let server = TestServer::configured(|server| server.import_allow_private_hosts = true)?;
+
+    let (status, log) = server
+        .request(
+            Method::POST,
+            "/import/apkg-url",
+            Some(json!({"url": url, "allowHttp": true, "sha256": hash})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{log}");
+    assert_eq!(log["new"], 1);
+    assert_eq!(log["notes"][0]["decision"], "addedNew");
+    assert_eq!(log["truncated"], false);
+    let guid = log["notes"][0]["guid"].clone();
+    assert!(!guid.as_str().unwrap().is_empty());
+
+    // importing the same package again leaves the existing note alone
+    let (_, log) = server
+        .request(
+            Method::POST,
+            "/import/apkg-url",
+            Some(json!({"url": url, "allowHttp": true})),
+        )
+        .await;
+    assert_eq!(log["duplicate"], 1);
+    assert_eq!(log["notes"][0]["guid"], guid);
+    assert_eq!(log["notes"][0]["decision"], "keptLocal");
+
+    let job_id = log["jobId"].as_str().unwrap();
+    let (status, page) = server
+        .request(Method::GET, &format!("/import/logs/{job_id}?limit=0"), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["notes"], json!([]));
+    assert_eq!(page["truncated"], true);
+    let (status, _) = server
+        .request(Method::GET, "/import/logs/unknown", None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    Ok(())
+}
+
+#[tokio::test]
+async fn export_colpkg() -> Result<()> {
+    let server = TestServer::new()?;
+    server.add_basic_card("front").await;
+
+    let (status, data) = server
+        .request_raw(
+            Method::POST,
+            "/export/colpkg",
+            Some(json!({"includeMedia": true})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let mut archive = ZipArchive::new(Cursor::new(data)).unwrap();
+    assert!(archive.by_name("collection.anki21b").is_ok());
+    assert!(archive.by_name("media").is_ok());
+
+    let (_, progress) = server
+        .request(Method::GET, "/export/colpkg/progress", None)
+        .await;
+    assert_eq!(progress["active"], false);
+    // the collection is reopened on the next request
+    server.add_basic_card("after export").await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn overlapping_exports() -> Result<()> {
+    let server = TestServer::new()?;
+    server.add_basic_card("front").await;
+    let (running, progress) = with_user(&server.server, |user| {
+        Ok((user.export_running.clone(), user.export_progress.clone()))
+    })
+    .await
+    .ok()
+    .unwrap();
+    let export = || {
+        server.spawn_request(
+            Method::POST,
+            "/export/colpkg",
+            Some(json!({"includeMedia": false})),
+            &[],
+        )
+    };
+
+    // holding the progress keeps the first export from finishing
+    let held = progress.lock().unwrap();
+    let first = export();
+    while !running.load(Ordering::Acquire) {
+        sleep(Duration::from_millis(1)).await;
+    }
+    assert_eq!(export().await.unwrap().status(), StatusCode::CONFLICT);
+    drop(held);
+    assert_eq!(first.await.unwrap().status(), StatusCode::OK);
+
+    // once it has finished, another can start
+    assert!(!running.load(Ordering::Acquire));
+    assert_eq!(export().await.unwrap().status(), StatusCode::OK);
+    Ok(())
+}
+
+#[tokio::test]
+async fn rate_limited_requests() -> Result<()> {
+    let server = TestServer::configured(|server| server.rate_limiter = RateLimiter::new(2, 0.01))?;
+    let calibrate = || {
+        server.response(
+            Method::POST,
+            "/fsrs/calibration",
+            Body::from(json!({}).to_string()),
+        )
+    };
+    let mut accepted = 0;
+    let response = loop {
+        let response = calibrate().await;
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            break response;
+        }
+        accepted += 1;
+        assert!(accepted <= 2, "calibration was never rate limited");
+    };
+    assert_eq!(accepted, 2);
+    let retry_after: u64 = response.headers()[header::RETRY_AFTER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((90..=100).contains(&retry_after));
+
+    // other endpoints are not limited
+    for _ in 0..3 {
+        let (status, _) = server.request(Method::GET, "/collection/usn", None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn login_lockouts() -> Result<()> {
+    let server = TestServer::new()?;
+    let throttle = &server.server.login_throttle;
+    let ip: IpAddr = "192.0.2.1".parse().unwrap();
+    for _ in 0..default_login_lockout_threshold() {
+        throttle.record_failure("user", ip);
+    }
+
+    // logins are refused before the password is checked
+    let err = server
+        .server
+        .get_host_key(
+            ip,
+            HostKeyRequest {
+                username: "user".into(),
+                password: "pass".into(),
+            },
+        )
+        .err()
+        .unwrap();
+    assert_eq!(err.code, StatusCode::TOO_MANY_REQUESTS);
+    let response = err.into_response();
+    assert!(response.headers().contains_key(header::RETRY_AFTER));
+    // failures from one IP don't lock the user out everywhere
+    assert!(throttle.check("user", "192.0.2.2".parse().unwrap()).is_ok());
+
+    // managing lockouts needs the admin token
+    let (status, _) = server.request(Method::GET, "/lockouts", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = server
+        .request_with_headers(
+            Method::DELETE,
+            "/lockouts",
+            None,
+            &[("authorization", "Bearer wrong")],
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = server.admin_request(Method::GET, "/lockouts", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let lockouts = body["lockouts"].as_array().unwrap();
+    assert_eq!(lockouts.len(), 2);
+    assert!(lockouts.iter().all(|lockout| {
+        lockout["ip"] == "192.0.2.1"
+            && lockout["failures"] == default_login_lockout_threshold()
+            && lockout["lockedUntil"].is_i64()
+    }));
+    assert!(lockouts.iter().any(|lockout| lockout["user"] == "user"));
+    assert!(lockouts.iter().any(|lockout| lockout["user"].is_null()));
+
+    let (status, body) = server
+        .admin_request(Method::DELETE, "/lockouts?user=user", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["cleared"], 1);
+    assert!(throttle.check("other", ip).is_err());
+    let (status, body) = server
+        .admin_request(Method::DELETE, "/lockouts?user=user&ip=192.0.2.1", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["cleared"], 0);
+    let (_, body) = server
+        .admin_request(Method::DELETE, "/lockouts", None)
+        .await;
+    assert_eq!(body["cleared"], 1);
+    assert!(throttle.failures().is_empty());
+
+    // without a configured token, the endpoints are disabled
+    let server = TestServer::configured(|server| server.admin_token = None)?;
+    let (status, _) = server.admin_request(Method::GET, "/lockouts", None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    Ok(())
+}
+
+#[tokio::test]
+async fn read_only_mode() -> Result<()> {
+    let server = TestServer::new()?;
+    let card_id = server.add_basic_card("front").await;
+    let (status, _) = server
+        .request(Method::PUT, "/read-only", Some(json!({"readOnly": true})))
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(!server.server.read_only.any());
+    let (status, body) = server
+        .admin_request(Method::PUT, "/read-only", Some(json!({"readOnly": true})))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"server": true, "users": []}));
+
+    // changes are refused, but reads and searches still work
+    let (status, body) = server
+        .request(
+            Method::POST,
+            "/cards",
+            Some(json!({
+                "deckName": "Default",
+                "notetypeName": "Basic",
+                "fields": {"Front": "other", "Back": "back"},
+                "tags": [],
+            })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["reason"], "read-only");
+    let (status, _) = server
+        .request(Method::GET, &format!("/cards/{card_id}"), None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = server
+        .request(Method::POST, "/search/rows", Some(json!({"query": ""})))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["total"], 1);
+    // as does syncing down, but not up
+    let state = server.server.state.lock().unwrap();
+    assert!(server
+        .server
+        .refuse_changes_if_read_only(&state.users["hkey"])
+        .is_err());
+    drop(state);
+
+    // the REST API acts as the first user, so making them read-only has the
+    // same effect
+    server
+        .admin_request(Method::PUT, "/read-only", Some(json!({"readOnly": false})))
+        .await;
+    let (status, body) = server
+        .admin_request(
+            Method::PUT,
+            "/read-only",
+            Some(json!({"readOnly": true, "user": "user"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({"server": false, "users": ["user"]}));
+    let (status, _) = server
+        .request(
+            Method::DELETE,
+            "/cards",
+            Some(json!({"cardIds": [card_id]})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    let (status, _) = server
+        .admin_request(
+            Method::PUT,
+            "/read-only",
+            Some(json!({"readOnly": true, "user": "missing"})),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    server
+        .admin_request(
+            Method::PUT,
+            "/read-only",
+            Some(json!({"readOnly": false, "user": "user"})),
+        )
+        .await;
+    assert!(!server.server.read_only.any());
+    server.add_basic_card("back").await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn busy_and_poisoned_state() {
+    let server = SimpleServer {
+        state: Mutex::new(SimpleServerInner {
+            users: HashMap::new(),
+        }),
+        delete_confirm_threshold: default_delete_confirm_threshold(),
+        rate_limiter: test_rate_limiter(),
+        public_config: Default::default(),
+        login_throttle: Default::default(),
+        read_only: Default::default(),
+        import_allow_private_hosts: false,
+        admin_token: None,
+        copy_base: None,
+        undo_group_gate: Default::default(),
+    };
+    let timeout = Duration::from_millis(20);
+    let guard = lock_state(&server, timeout).await.ok().unwrap();
+    let response = lock_state(&server, timeout)
+        .await
+        .err()
+        .unwrap()
+        .into_response();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+    drop(guard);
+
+    thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                let _guard = server.state.lock().unwrap();
+                panic!("handler failed");
+            })
+            .join()
+            .unwrap_err();
+    });
+    let response = lock_state(&server, timeout)
+        .await
+        .err()
+        .unwrap()
+        .into_response();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
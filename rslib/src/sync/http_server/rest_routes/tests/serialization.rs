@@ -0,0 +1,590 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Snapshots of the keys in each response, so that accidental renames of
+//! payload fields are caught.
+
+use axum::http::Method;
+use axum::http::StatusCode;
+use serde_json::json;
+use serde_json::Value;
+
+use super::TestServer;
+use crate::prelude::*;
+
+/// The sorted paths of all object keys in `value`, eg `siblings[].cardId`.
+fn key_paths(value: &Value) -> Vec<String> {
+    fn walk(value: &Value, prefix: &str, out: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    let path = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{prefix}.{key}")
+                    };
+                    walk(value, &path, out);
+                    out.push(path);
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    walk(item, &format!("{prefix}[]"), out);
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut out = vec![];
+    walk(value, "", &mut out);
+    out.sort();
+    out.dedup();
+    out
+}
+
+#[tokio::test]
+async fn response_keys() -> Result<()> {
+    let server = TestServer::new()?;
+    let cid = server.add_basic_card("front").await;
+    let (_, session) = server
+        .request(Method::POST, "/study/sessions", Some(json!({"deckId": 1})))
+        .await;
+    let session = session["sessionId"].as_str().unwrap().to_string();
+    server
+        .request(
+            Method::POST,
+            &format!("/study/sessions/{session}/answer"),
+            Some(json!({"rating": "good"})),
+        )
+        .await;
+    let (nid, ntid) = server.with_col(|col| {
+        let card = col.storage.get_card(CardId(cid))?.unwrap();
+        let note = col.storage.get_note(card.note_id)?.unwrap();
+        Ok((note.id, note.notetype_id))
+    });
+
+    let requests: Vec<(Method, String, Option<Value>, &[&str])> = vec![
+        (
+            Method::GET,
+            "/cards".into(),
+            None,
+            &[
+                "cards",
+                "cards[].cardId",
+                "cards[].deckId",
+                "cards[].noteId",
+                "nextCursor",
+            ],
+        ),
+        (
+            Method::GET,
+            format!("/cards/{cid}?expand=note,deck,notetype"),
+            None,
+            &[
+                "cardId",
+                "deck",
+                "deck.configId",
+                "deck.configName",
+                "deck.deckId",
+                "deck.filtered",
+                "deck.name",
+                "deck.newPaused",
+                "deckId",
+                "due",
+                "easeFactor",
+                "filteredDeckName",
+                "inFilteredDeck",
+                "interval",
+                "note",
+                "note.fields",
+                "note.guid",
+                "note.id",
+                "note.marked",
+                "note.modified",
+                "note.notetypeId",
+                "note.tags",
+                "notetype",
+                "notetype.css",
+                "notetype.fields",
+                "notetype.fields[].collapsed",
+                "notetype.fields[].description",
+                "notetype.fields[].excludeFromSearch",
+                "notetype.fields[].fontName",
+                "notetype.fields[].fontSize",
+                "notetype.fields[].name",
+                "notetype.fields[].ord",
+                "notetype.fields[].plainText",
+                "notetype.fields[].rtl",
+                "notetype.fields[].sticky",
+                "notetype.id",
+                "notetype.name",
+                "notetype.templates",
+                "notetype.templates[].back",
+                "notetype.templates[].front",
+                "notetype.templates[].name",
+                "notetype.templates[].ord",
+                "originalDeckId",
+                "originalDue",
+                "queuePosition",
+                "renderedBack",
+                "renderedFront",
+                "siblings",
+                "warnings",
+            ],
+        ),
+        (
+            Method::GET,
+            format!("/cards/{cid}/difficulty"),
+            None,
+            &["difficulty", "method"],
+        ),
+        (
+            Method::GET,
+            format!("/cards/{cid}/scheduling-states?includeCustomScheduling=true"),
+            None,
+            &[
+                "again",
+                "again.intervalSecs",
+                "again.kind",
+                "current",
+                "current.intervalSecs",
+                "current.kind",
+                "customScheduling",
+                "customScheduling.code",
+                "customScheduling.set",
+                "easy",
+                "easy.easeFactor",
+                "easy.intervalSecs",
+                "easy.kind",
+                "easy.scheduledDays",
+                "fuzz",
+                "fuzz.factor",
+                "fuzz.loadBalanced",
+                "fuzz.ranges",
+                "fuzz.ranges[].lowerDays",
+                "fuzz.ranges[].unfuzzedDays",
+                "fuzz.ranges[].upperDays",
+                "fuzz.seed",
+                "good",
+                "good.easeFactor",
+                "good.intervalSecs",
+                "good.kind",
+                "good.scheduledDays",
+                "hard",
+                "hard.intervalSecs",
+                "hard.kind",
+            ],
+        ),
+        (
+            Method::POST,
+            "/cards/schedule".into(),
+            Some(json!({"cards": [{"cardId": cid, "due": "1"}]})),
+            &[
+                "changes",
+                "changes.cardIds",
+                "changes.deckIds",
+                "changes.noteIds",
+                "results",
+                "results[].cardId",
+                "results[].success",
+                "updated",
+            ],
+        ),
+        (
+            Method::GET,
+            format!("/notes/{nid}?expand=cards,notetype"),
+            None,
+            &[
+                "cards",
+                "cards[].cardId",
+                "cards[].deckId",
+                "cards[].noteId",
+                "fields",
+                "guid",
+                "id",
+                "marked",
+                "modified",
+                "notetype",
+                "notetype.css",
+                "notetype.fields",
+                "notetype.fields[].collapsed",
+                "notetype.fields[].description",
+                "notetype.fields[].excludeFromSearch",
+                "notetype.fields[].fontName",
+                "notetype.fields[].fontSize",
+                "notetype.fields[].name",
+                "notetype.fields[].ord",
+                "notetype.fields[].plainText",
+                "notetype.fields[].rtl",
+                "notetype.fields[].sticky",
+                "notetype.id",
+                "notetype.name",
+                "notetype.templates",
+                "notetype.templates[].back",
+                "notetype.templates[].front",
+                "notetype.templates[].name",
+                "notetype.templates[].ord",
+                "notetypeId",
+                "tags",
+            ],
+        ),
+        (
+            Method::GET,
+            format!("/notetypes/{ntid}"),
+            None,
+            &[
+                "css",
+                "fields",
+                "fields[].collapsed",
+                "fields[].description",
+                "fields[].excludeFromSearch",
+                "fields[].fontName",
+                "fields[].fontSize",
+                "fields[].name",
+                "fields[].ord",
+                "fields[].plainText",
+                "fields[].rtl",
+                "fields[].sticky",
+                "id",
+                "name",
+                "templates",
+                "templates[].back",
+                "templates[].front",
+                "templates[].name",
+                "templates[].ord",
+            ],
+        ),
+        (
+            Method::GET,
+            "/notes/modified-since?since=0".into(),
+            None,
+            &["[].modifiedAt", "[].noteId"],
+        ),
+        (
+            Method::GET,
+            "/notetypes".into(),
+            None,
+            &["[].id", "[].name", "[].noteCount"],
+        ),
+        (
+            Method::GET,
+            "/decks/1".into(),
+            None,
+            &[
+                "configId",
+                "configName",
+                "deckId",
+                "filtered",
+                "name",
+                "newPaused",
+            ],
+        ),
+        (
+            Method::GET,
+            "/decks/1/high-lapse-cards?minLapses=0".into(),
+            None,
+            &["[].cardId", "[].lapses", "[].noteField"],
+        ),
+        (
+            Method::GET,
+            "/deck-configs/1/decks".into(),
+            None,
+            &[
+                "configId",
+                "configName",
+                "decks",
+                "decks[].deckId",
+                "decks[].name",
+            ],
+        ),
+        (
+            Method::GET,
+            "/tags/missing/decks".into(),
+            None,
+            &["decks", "tag"],
+        ),
+        (
+            Method::GET,
+            "/config/known".into(),
+            None,
+            &["keys", "keys[].key", "keys[].type", "keys[].value"],
+        ),
+        (
+            Method::GET,
+            "/scheduler/new-position".into(),
+            None,
+            &["position"],
+        ),
+        (
+            Method::GET,
+            "/collection".into(),
+            None,
+            &["cardCount", "noteCount", "schedulerVersion"],
+        ),
+        (Method::GET, "/cards/due".into(), None, &["cards"]),
+        (
+            Method::GET,
+            "/cards/modified-since?since=0".into(),
+            None,
+            &[
+                "cards",
+                "cards[].cardId",
+                "cards[].modifiedAt",
+                "serverTime",
+            ],
+        ),
+        (
+            Method::GET,
+            "/cards/deleted-since?since=0".into(),
+            None,
+            &["cardIds", "serverTime"],
+        ),
+        (
+            Method::GET,
+            "/collection/backups".into(),
+            None,
+            &["backups", "retentionDays"],
+        ),
+        (
+            Method::GET,
+            "/collection/changes-since?since=0".into(),
+            None,
+            &[
+                "deletedCards",
+                "deletedNotes",
+                "updatedCards",
+                "updatedDecks",
+                "updatedNotes",
+            ],
+        ),
+        (Method::GET, "/collection/ease-outliers".into(), None, &[]),
+        (
+            Method::GET,
+            "/collection/time-series?days=1".into(),
+            None,
+            &[
+                "[].date",
+                "[].learnSeconds",
+                "[].relearnSeconds",
+                "[].reviewSeconds",
+                "[].totalSeconds",
+            ],
+        ),
+        (
+            Method::GET,
+            "/collection/retention-distribution".into(),
+            None,
+            &[
+                "buckets",
+                "buckets[].count",
+                "buckets[].lower",
+                "mean",
+                "p10",
+                "p50",
+            ],
+        ),
+        (
+            Method::GET,
+            "/stats/answer-buttons".into(),
+            None,
+            &[
+                "days",
+                "learning",
+                "learning.again",
+                "learning.easy",
+                "learning.good",
+                "learning.hard",
+                "mature",
+                "mature.again",
+                "mature.easy",
+                "mature.good",
+                "mature.hard",
+                "young",
+                "young.again",
+                "young.easy",
+                "young.good",
+                "young.hard",
+            ],
+        ),
+        (
+            Method::GET,
+            "/stats/intervals".into(),
+            None,
+            &[
+                "intervals",
+                "intervals.buckets",
+                "intervals.buckets[].count",
+                "intervals.buckets[].end",
+                "intervals.buckets[].start",
+                "intervals.median",
+                "intervals.p90",
+                "stability",
+            ],
+        ),
+        (
+            Method::POST,
+            "/search/fulltext".into(),
+            Some(json!({"query": "front"})),
+            &[
+                "results",
+                "results[].field",
+                "results[].noteId",
+                "results[].snippet",
+                "warnings",
+            ],
+        ),
+        (
+            Method::GET,
+            "/collection/backups/config".into(),
+            None,
+            &["daily", "intervalHours", "monthly", "weekly"],
+        ),
+        (
+            Method::GET,
+            "/collection/backups/status".into(),
+            None,
+            &["intervalHours", "lastBackup", "nextBackup"],
+        ),
+        (
+            Method::POST,
+            "/cards/set-ease".into(),
+            Some(json!({"selector": {"search": ""}, "easeFactor": 2.5})),
+            &["skippedFsrs", "updated"],
+        ),
+        (
+            Method::GET,
+            "/collection/usn".into(),
+            None,
+            &["schemaModified", "usn"],
+        ),
+        (
+            Method::GET,
+            "/collection/graves-since?since=0".into(),
+            None,
+            &["cards", "decks", "notes", "until"],
+        ),
+        (
+            Method::GET,
+            "/stats/history?from=2020-01-01&to=2020-01-01".into(),
+            None,
+            &["[].date", "[].estimatedDue", "[].reviewed"],
+        ),
+        (
+            Method::GET,
+            "/stats/workload?days=1".into(),
+            None,
+            &[
+                "decks",
+                "decks[].deckId",
+                "decks[].due",
+                "decks[].minutes",
+                "decks[].name",
+                "decks[].secsPerReview",
+                "totalMinutes",
+            ],
+        ),
+        (
+            Method::GET,
+            "/study/next".into(),
+            None,
+            &[
+                "card",
+                "counts",
+                "counts.learning",
+                "counts.new",
+                "counts.review",
+            ],
+        ),
+        (
+            Method::GET,
+            format!("/study/sessions/{session}/current"),
+            None,
+            &[
+                "answered",
+                "card",
+                "counts",
+                "counts.learning",
+                "counts.new",
+                "counts.review",
+                "deckId",
+                "sessionId",
+            ],
+        ),
+        (
+            Method::GET,
+            "/export/colpkg/progress".into(),
+            None,
+            &["active"],
+        ),
+        (
+            Method::POST,
+            "/export/scheduling".into(),
+            Some(json!({})),
+            &[
+                "records",
+                "records[].cardOrd",
+                "records[].due",
+                "records[].ease",
+                "records[].guid",
+                "records[].interval",
+                "records[].lapses",
+                "records[].lastReview",
+                "records[].memoryState",
+                "records[].queue",
+                "records[].remainingSteps",
+                "records[].reps",
+                "records[].revlog",
+                "records[].revlog[].button",
+                "records[].revlog[].easeFactor",
+                "records[].revlog[].id",
+                "records[].revlog[].interval",
+                "records[].revlog[].kind",
+                "records[].revlog[].lastInterval",
+                "records[].revlog[].takenMillis",
+                "records[].type",
+            ],
+        ),
+        (
+            Method::PUT,
+            format!("/notetypes/{ntid}/fields/1"),
+            Some(json!({})),
+            &[
+                "collapsed",
+                "description",
+                "excludeFromSearch",
+                "fontName",
+                "fontSize",
+                "name",
+                "ord",
+                "plainText",
+                "rtl",
+                "schemaModified",
+                "sticky",
+            ],
+        ),
+        // the snake_case key is still accepted
+        (
+            Method::DELETE,
+            "/cards".into(),
+            Some(json!({"card_ids": [cid]})),
+            &[
+                "alreadyMissing",
+                "changes",
+                "changes.cardIds",
+                "changes.deckIds",
+                "changes.noteIds",
+                "deleted",
+                "deletedCount",
+                "missingIds",
+                "requested",
+                "success",
+            ],
+        ),
+    ];
+    for (method, uri, body, expected) in requests {
+        let (status, response) = server.request(method.clone(), &uri, body).await;
+        assert_eq!(status, StatusCode::OK, "{method} {uri}");
+        assert_eq!(key_paths(&response), expected, "{method} {uri}");
+    }
+    Ok(())
+}
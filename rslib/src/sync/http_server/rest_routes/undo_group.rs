@@ -0,0 +1,160 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use axum::extract::Request;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::middleware::from_fn_with_state;
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::Router;
+use tokio::time::timeout;
+use tracing::warn;
+
+use super::with_user;
+use super::LOCK_TIMEOUT;
+use crate::sync::http_server::user::User;
+use crate::sync::http_server::ApiError;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+/// Requests carrying the same token in this header have their changes merged
+/// into a single undo step, as long as no other changes are made in between.
+pub const UNDO_GROUP_HEADER: &str = "x-undo-group";
+/// Optionally names a group's undo step. Without it, the step keeps the name
+/// of the first operation in the group.
+pub const UNDO_GROUP_NAME_HEADER: &str = "x-undo-group-name";
+
+/// How long a group stays open after its last request.
+pub(crate) const UNDO_GROUP_EXPIRY: Duration = Duration::from_secs(60);
+/// The most requests merged into one group. Later requests with the same
+/// token start a new group.
+pub(crate) const MAX_UNDO_GROUP_REQUESTS: usize = 50;
+
+/// A user's most recent undo group.
+#[derive(Debug)]
+pub(crate) struct UndoGroup {
+    pub token: String,
+    /// The counter of the undo step the group's changes are merged into.
+    pub step: usize,
+    pub requests: usize,
+    pub last_used: Instant,
+}
+
+impl UndoGroup {
+    fn accepts(&self, token: &str, last_step: Option<usize>) -> bool {
+        self.token == token
+            && self.last_used.elapsed() < UNDO_GROUP_EXPIRY
+            && self.requests < MAX_UNDO_GROUP_REQUESTS
+            && last_step == Some(self.step)
+    }
+}
+
+/// Merge the undo steps of requests that share an [UNDO_GROUP_HEADER] token.
+/// Batch endpoints are no exception: all of a grouped request's steps join
+/// the group. A grouped request runs on its own, so that the steps it merges
+/// can't include changes made by other requests.
+pub fn with_undo_groups(
+    router: Router<Arc<SimpleServer>>,
+    server: Arc<SimpleServer>,
+) -> Router<Arc<SimpleServer>> {
+    router.layer(from_fn_with_state(server, group_undo_steps))
+}
+
+async fn group_undo_steps(
+    State(server): State<Arc<SimpleServer>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(token) = header_str(request.headers(), UNDO_GROUP_HEADER) else {
+        let _gate = match wait_for_gate(server.undo_group_gate.read()).await {
+            Ok(gate) => gate,
+            Err(err) => return err.into_response(),
+        };
+        return next.run(request).await;
+    };
+    let _gate = match wait_for_gate(server.undo_group_gate.write()).await {
+        Ok(gate) => gate,
+        Err(err) => return err.into_response(),
+    };
+    let name = header_str(request.headers(), UNDO_GROUP_NAME_HEADER);
+    let after = match with_user(&server, |user| {
+        user.ensure_col_open()?;
+        Ok(undo_group_start(user, &token))
+    })
+    .await
+    {
+        Ok(after) => after,
+        Err(err) => return err.into_response(),
+    };
+    let response = next.run(request).await;
+    // the changes have been made, so the request should not fail here
+    if with_user(&server, |user| {
+        extend_undo_group(user, token, name, after);
+        Ok(())
+    })
+    .await
+    .is_err()
+    {
+        warn!("couldn't merge undo group");
+    }
+    response
+}
+
+/// Like [super::lock_state], gives up with 503 if the gate isn't free in time.
+async fn wait_for_gate<G>(gate: impl Future<Output = G>) -> ApiResult<G> {
+    timeout(LOCK_TIMEOUT, gate)
+        .await
+        .map_err(|_| ApiError::Busy {
+            retry_after: LOCK_TIMEOUT,
+        })
+}
+
+fn header_str(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(ToString::to_string)
+}
+
+/// The undo counter that steps made by the request should be merged after.
+/// Continues the user's group if the token matches and nothing else has been
+/// done since its last request; otherwise starts a new one.
+fn undo_group_start(user: &mut User, token: &str) -> usize {
+    let col = user.col.as_ref().unwrap();
+    let last_step = col.previous_undo_op().map(|op| op.counter);
+    match &user.undo_group {
+        Some(group) if group.accepts(token, last_step) => group.step - 1,
+        _ => {
+            user.undo_group = None;
+            col.undo_status().last_step
+        }
+    }
+}
+
+fn extend_undo_group(user: &mut User, token: String, name: Option<String>, after: usize) {
+    // a full sync may have closed the collection
+    let Some(col) = user.col.as_mut() else {
+        return;
+    };
+    let Some(step) = col.merge_undo_steps_after(after, name) else {
+        return;
+    };
+    let requests = match user.undo_group.take() {
+        Some(group) if group.step == step && group.token == token => group.requests + 1,
+        _ => 1,
+    };
+    user.undo_group = Some(UndoGroup {
+        token,
+        step,
+        requests,
+        last_used: Instant::now(),
+    });
+}
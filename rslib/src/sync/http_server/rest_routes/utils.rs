@@ -0,0 +1,89 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::sync::Arc;
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::State;
+use axum::routing::post;
+use axum::Json;
+use axum::Router;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::with_col;
+use crate::notes::normalized_first_field;
+use crate::prelude::*;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+/// The most strings one normalize request accepts.
+const MAX_NORMALIZE_TEXTS: usize = 1000;
+/// The longest string a normalize request accepts, in bytes.
+const MAX_NORMALIZE_TEXT_BYTES: usize = 100_000;
+
+// Payloads for the API
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizeRequest {
+    texts: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizedTextResponse {
+    /// The text as duplicate checks compare first fields.
+    normalized: String,
+    /// The checksum stored for a note with this first field, which the
+    /// duplicate check looks notes up by.
+    checksum: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizeResponse {
+    results: Vec<NormalizedTextResponse>,
+}
+
+// Router definition
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new().route("/utils/normalize", post(normalize))
+}
+
+// Handler for normalizing first fields as duplicate checks do, so clients can
+// find duplicates before adding notes. The collection's text normalization
+// setting is respected.
+async fn normalize(
+    State(server): State<Arc<SimpleServer>>,
+    payload: Result<Json<NormalizeRequest>, JsonRejection>,
+) -> ApiResult<Json<NormalizeResponse>> {
+    let Json(payload) = payload?;
+    with_col(&server, |col| {
+        require!(
+            payload.texts.len() <= MAX_NORMALIZE_TEXTS,
+            "at most {MAX_NORMALIZE_TEXTS} texts can be normalized at once"
+        );
+        if let Some(idx) = payload
+            .texts
+            .iter()
+            .position(|text| text.len() > MAX_NORMALIZE_TEXT_BYTES)
+        {
+            invalid_input!("text {idx} is longer than {MAX_NORMALIZE_TEXT_BYTES} bytes");
+        }
+        let normalize_text = col.get_config_bool(BoolKey::NormalizeNoteText);
+        Ok(Json(NormalizeResponse {
+            results: payload
+                .texts
+                .iter()
+                .map(|text| {
+                    let (normalized, checksum) = normalized_first_field(text, normalize_text);
+                    NormalizedTextResponse {
+                        normalized,
+                        checksum,
+                    }
+                })
+                .collect(),
+        }))
+    })
+    .await
+}
@@ -1,6 +1,8 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use std::sync::Arc;
+
 use axum::extract::Path;
 use axum::extract::Query;
 use axum::extract::State;
@@ -9,12 +11,15 @@ use axum::response::IntoResponse;
 use axum::response::Response;
 use axum::routing::get;
 use axum::routing::post;
+use axum::Json;
 use axum::Router;
 
 use crate::sync::collection::protocol::SyncMethod;
 use crate::sync::collection::protocol::SyncProtocol;
 use crate::sync::error::HttpResult;
 use crate::sync::error::OrHttpErr;
+use crate::sync::http_server::config::PublicConfig;
+use crate::sync::http_server::SimpleServer;
 use crate::sync::media::begin::SyncBeginQuery;
 use crate::sync::media::begin::SyncBeginRequest;
 use crate::sync::media::protocol::MediaSyncMethod;
@@ -88,8 +93,15 @@ async fn media_begin_post<P: MediaSyncProtocol>(
     media_sync_handler(Path(MediaSyncMethod::Begin), server, req.into_output_type()).await
 }
 
-pub async fn health_check_handler() -> impl IntoResponse {
-    StatusCode::OK
+/// Reports the server's non-secret settings, so deployments can confirm what
+/// they are running with.
+pub async fn health_check_handler(State(server): State<Arc<SimpleServer>>) -> impl IntoResponse {
+    let config = PublicConfig {
+        read_only: server.read_only.server(),
+        read_only_user_count: server.read_only.users().len(),
+        ..server.public_config.clone()
+    };
+    (StatusCode::OK, Json(config))
 }
 
 async fn media_sync_handler<P: MediaSyncProtocol>(
@@ -1,17 +1,26 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::sync::Mutex;
 
 use tracing::info;
 
 use crate::collection::Collection;
 use crate::collection::CollectionBuilder;
 use crate::error;
+use crate::import_export::NoteLog;
+use crate::progress::ProgressState;
 use crate::sync::collection::start::ServerSyncState;
 use crate::sync::error::HttpResult;
 use crate::sync::error::OrHttpErr;
+use crate::sync::http_server::backups::BackupOutcome;
 use crate::sync::http_server::media_manager::ServerMediaManager;
+use crate::sync::http_server::rest_routes::study::StudySession;
+use crate::sync::http_server::rest_routes::undo_group::UndoGroup;
 
 pub struct User {
     pub name: String,
@@ -20,6 +29,20 @@ pub struct User {
     pub sync_state: Option<ServerSyncState>,
     pub media: ServerMediaManager,
     pub folder: PathBuf,
+    /// REST study sessions, keyed by session id.
+    pub(crate) study_sessions: HashMap<String, StudySession>,
+    /// Progress of the REST collection export, if one is running.
+    pub(crate) export_progress: Arc<Mutex<ProgressState>>,
+    /// Set while a REST collection export is running. It is checked and set
+    /// while the server state is locked, and cleared when the export's task
+    /// finishes, whether or not it succeeded.
+    pub(crate) export_running: Arc<AtomicBool>,
+    /// Logs of recent REST imports, oldest first, keyed by job id.
+    pub(crate) import_logs: Vec<(String, NoteLog)>,
+    /// The result of the last scheduled backup, if one was attempted.
+    pub(crate) last_backup: Option<BackupOutcome>,
+    /// The REST requests whose undo steps are currently being merged.
+    pub(crate) undo_group: Option<UndoGroup>,
 }
 
 impl User {
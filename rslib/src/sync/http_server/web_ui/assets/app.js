@@ -0,0 +1,313 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+// A minimal client for the REST API. Each screen is a function that renders
+// into <main>, chosen by the location hash.
+
+"use strict";
+
+const API = "/api/v1";
+const MEDIA_URL_PREFIX = "/ui/media/";
+const RATINGS = ["again", "hard", "good", "easy"];
+
+const main = document.getElementById("main");
+
+async function api(method, path, body) {
+    const options = { method, headers: {} };
+    if (body !== undefined) {
+        options.headers["Content-Type"] = "application/json";
+        options.body = JSON.stringify(body);
+    }
+    const response = await fetch(API + path, options);
+    const json = await response.json().catch(() => null);
+    if (!response.ok) {
+        throw new Error(json?.error?.message ?? response.statusText);
+    }
+    return json;
+}
+
+function el(tag, attrs = {}, ...children) {
+    const node = document.createElement(tag);
+    for (const [key, value] of Object.entries(attrs)) {
+        if (key.startsWith("on")) {
+            node.addEventListener(key.slice(2), value);
+        } else {
+            node.setAttribute(key, value);
+        }
+    }
+    node.append(...children);
+    return node;
+}
+
+function show(...nodes) {
+    main.replaceChildren(...nodes);
+}
+
+function showError(err) {
+    main.append(el("p", { class: "error" }, err.message));
+}
+
+function cardDocument(html) {
+    const style = "body { text-align: center; } img { max-width: 100%; }";
+    return `<!doctype html><meta charset="utf-8"><style>${style}</style>${html}`;
+}
+
+function formatInterval(secs) {
+    const units = [
+        [365 * 86400, "y"],
+        [30 * 86400, "mo"],
+        [86400, "d"],
+        [3600, "h"],
+        [60, "m"],
+    ];
+    for (const [size, unit] of units) {
+        if (secs >= size) {
+            return `${Math.round((secs / size) * 10) / 10}${unit}`;
+        }
+    }
+    return `${secs}s`;
+}
+
+// Deck list
+
+async function deckList() {
+    const { decks } = await api("GET", "/decks");
+    const rows = decks.map((deck) => {
+        const name = deck.name.split("::").pop();
+        return el(
+            "tr",
+            {},
+            el(
+                "td",
+                { style: `padding-left: ${deck.level - 1}em` },
+                el("a", { href: `#/study/${deck.deckId}` }, name),
+            ),
+            el("td", { class: "count new" }, String(deck.new)),
+            el("td", { class: "count learning" }, String(deck.learning)),
+            el("td", { class: "count review" }, String(deck.review)),
+        );
+    });
+    show(el("h1", {}, "Decks"), el("table", {}, ...rows));
+}
+
+// Studying
+
+async function study(deckId) {
+    let session = await api("POST", "/study/sessions", {
+        deckId: Number(deckId),
+        mediaUrlPrefix: MEDIA_URL_PREFIX,
+    });
+    let shownAt = Date.now();
+
+    const answer = async (rating) => {
+        session = await api("POST", `/study/sessions/${session.sessionId}/answer`, {
+            cardId: session.card.cardId,
+            rating,
+            millisecondsTaken: Date.now() - shownAt,
+        });
+        render();
+    };
+
+    const render = () => {
+        const { card, counts } = session;
+        const header = el(
+            "p",
+            {},
+            el("span", { class: "new" }, `${counts.new} `),
+            el("span", { class: "learning" }, `${counts.learning} `),
+            el("span", { class: "review" }, `${counts.review}`),
+        );
+        if (!card) {
+            show(header, el("p", {}, "Congratulations! You have finished this deck for now."));
+            return;
+        }
+        shownAt = Date.now();
+        // card templates can contain anything, so they're shown in a sandbox
+        // that can't run scripts or reach the UI
+        const side = el("iframe", {
+            class: "card",
+            sandbox: "",
+            srcdoc: cardDocument(card.question),
+        });
+        const showAnswer = el("button", {
+            onclick: () => {
+                side.srcdoc = cardDocument(card.answer);
+                buttons.replaceChildren(
+                    ...RATINGS.map((rating, idx) =>
+                        el(
+                            "button",
+                            { onclick: () => answer(rating).catch(showError) },
+                            card.buttonLabels[idx],
+                            el("small", {}, formatInterval(card.intervalSecs[idx])),
+                        )
+                    ),
+                );
+            },
+        }, "Show Answer");
+        const buttons = el("div", { class: "buttons" }, showAnswer);
+        show(header, side, buttons);
+    };
+
+    render();
+}
+
+// Browsing and editing
+
+async function browse() {
+    const query = el("input", { placeholder: "Search", value: "deck:current" });
+    const results = el("table");
+    const search = async () => {
+        const { total, rows } = await api("POST", "/search/rows", {
+            query: query.value,
+            limit: 50,
+        });
+        results.replaceChildren(
+            ...rows.map((row) =>
+                el(
+                    "tr",
+                    {},
+                    el("td", {}, el("a", { href: `#/cards/${row.id}` }, row.sortKey || "(empty)")),
+                )
+            ),
+            el("tr", {}, el("td", {}, `${rows.length} of ${total} cards`)),
+        );
+    };
+    const form = el("form", {
+        onsubmit: (event) => {
+            event.preventDefault();
+            search().catch(showError);
+        },
+    }, query);
+    show(el("h1", {}, "Browse"), form, results);
+    await search();
+}
+
+function fieldEditors(names, values) {
+    return names.map((name, idx) => {
+        const input = el("textarea", { name });
+        input.value = values[idx] ?? "";
+        return [el("label", {}, name), input];
+    }).flat();
+}
+
+function fieldValues(form) {
+    const fields = {};
+    for (const input of form.querySelectorAll("textarea")) {
+        fields[input.name] = input.value;
+    }
+    return fields;
+}
+
+function tagList(input) {
+    return input.value.split(/\s+/).filter((tag) => tag);
+}
+
+async function editCard(cardId) {
+    const card = await api("GET", `/cards/${cardId}?expand=note,notetype`);
+    const names = card.notetype.fields.map((field) => field.name);
+    const tags = el("input", { name: "tags" });
+    tags.value = card.note.tags.join(" ");
+    const status = el("p");
+    const form = el(
+        "form",
+        {
+            onsubmit: async (event) => {
+                event.preventDefault();
+                try {
+                    await api("PUT", `/cards/${cardId}`, {
+                        fields: fieldValues(form),
+                        tags: tagList(tags),
+                    });
+                    status.textContent = "Saved.";
+                } catch (err) {
+                    showError(err);
+                }
+            },
+        },
+        ...fieldEditors(names, card.note.fields),
+        el("label", {}, "Tags"),
+        tags,
+        el("p", {}, el("button", {}, "Save")),
+        status,
+    );
+    show(el("h1", {}, card.notetype.name), form);
+}
+
+async function addNote() {
+    const [{ decks }, notetypes] = await Promise.all([
+        api("GET", "/decks"),
+        api("GET", "/notetypes"),
+    ]);
+    const deck = el("select", {}, ...decks.map((d) => el("option", {}, d.name)));
+    const notetype = el(
+        "select",
+        {},
+        ...notetypes.map((nt) => el("option", { value: nt.id }, nt.name)),
+    );
+    const fields = el("div");
+    const tags = el("input", { name: "tags" });
+    const status = el("p");
+    const loadFields = async () => {
+        const nt = await api("GET", `/notetypes/${notetype.value}`);
+        fields.replaceChildren(...fieldEditors(nt.fields.map((field) => field.name), []));
+    };
+    notetype.addEventListener("change", () => loadFields().catch(showError));
+    const form = el(
+        "form",
+        {
+            onsubmit: async (event) => {
+                event.preventDefault();
+                try {
+                    const { cardIds } = await api("POST", "/cards", {
+                        deckName: deck.value,
+                        notetypeName: notetype.selectedOptions[0].textContent,
+                        fields: fieldValues(form),
+                        tags: tagList(tags),
+                    });
+                    status.textContent = `Added ${cardIds.length} card(s).`;
+                    await loadFields();
+                } catch (err) {
+                    showError(err);
+                }
+            },
+        },
+        el("label", {}, "Type"),
+        notetype,
+        el("label", {}, "Deck"),
+        deck,
+        fields,
+        el("label", {}, "Tags"),
+        tags,
+        el("p", {}, el("button", {}, "Add")),
+        status,
+    );
+    show(el("h1", {}, "Add"), form);
+    await loadFields();
+}
+
+// Routing
+
+const ROUTES = [
+    [/^#\/study\/(\d+)$/, study],
+    [/^#\/notes$/, browse],
+    [/^#\/cards\/(\d+)$/, editCard],
+    [/^#\/add$/, addNote],
+    [/^/, deckList],
+];
+
+function route() {
+    for (const [pattern, screen] of ROUTES) {
+        const match = location.hash.match(pattern);
+        if (match) {
+            show(el("p", {}, "Loading..."));
+            screen(...match.slice(1)).catch((err) => {
+                show();
+                showError(err);
+            });
+            return;
+        }
+    }
+}
+
+window.addEventListener("hashchange", route);
+route();
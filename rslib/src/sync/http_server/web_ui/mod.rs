@@ -0,0 +1,138 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! A small single-page UI for the REST API, mounted at `/ui` when the
+//! `web-ui` feature is enabled. It only talks to `/api/v1`, so it doubles as
+//! a demonstration of the API. Card media is served from `/ui/media`, which
+//! the UI passes to the API as its `mediaUrlPrefix`. Card sides are shown in
+//! sandboxed frames, and media that a browser could run as a page, such as
+//! HTML or SVG, is only served as a download.
+
+use std::sync::Arc;
+
+use axum::extract::Path;
+use axum::extract::State;
+use axum::http::header;
+use axum::http::HeaderValue;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+
+use crate::media::files::filename_if_normalized;
+use crate::prelude::*;
+use crate::sync::http_server::rest_routes::with_user;
+use crate::sync::http_server::ApiResult;
+use crate::sync::http_server::SimpleServer;
+
+/// The files of the UI, embedded in the binary.
+const ASSETS: &[(&str, &[u8])] = &[
+    ("index.html", include_bytes!("assets/index.html")),
+    ("app.js", include_bytes!("assets/app.js")),
+    ("app.css", include_bytes!("assets/app.css")),
+];
+
+pub fn routes() -> Router<Arc<SimpleServer>> {
+    Router::new()
+        .route("/", get(index))
+        .route("/{file}", get(asset))
+        .route("/media/{filename}", get(media_file))
+}
+
+async fn index() -> Response {
+    asset_response("index.html")
+}
+
+async fn asset(Path(file): Path<String>) -> Response {
+    asset_response(&file)
+}
+
+fn asset_response(name: &str) -> Response {
+    match ASSETS.iter().find(|(file, _)| *file == name) {
+        Some((_, data)) => ([(header::CONTENT_TYPE, content_type(name))], *data).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+// Handler for a file in the user's media folder
+async fn media_file(
+    State(server): State<Arc<SimpleServer>>,
+    Path(filename): Path<String>,
+) -> ApiResult<Response> {
+    let data = with_user(&server, |user| {
+        Ok(read_media_file(&user.media.media_folder, &filename)?)
+    })
+    .await?;
+    let mut response = (
+        [
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+            (header::CONTENT_SECURITY_POLICY, "sandbox"),
+        ],
+        data,
+    )
+        .into_response();
+    let headers = response.headers_mut();
+    match inline_media_type(&filename) {
+        Some(content_type) => {
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        }
+        None => {
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/octet-stream"),
+            );
+            headers.insert(
+                header::CONTENT_DISPOSITION,
+                HeaderValue::from_static("attachment"),
+            );
+        }
+    }
+    Ok(response)
+}
+
+/// The content type of a media file that's safe to show in the browser, or
+/// [None] if it should be downloaded instead. Only images, audio and video
+/// qualify, apart from SVGs, which can carry scripts.
+fn inline_media_type(filename: &str) -> Option<&'static str> {
+    Some(content_type(filename)).filter(|content_type| {
+        *content_type != "image/svg+xml"
+            && ["image/", "audio/", "video/"]
+                .iter()
+                .any(|prefix| content_type.starts_with(prefix))
+    })
+}
+
+/// Only names that could have been written by a media sync are looked up, so
+/// the folder can't be escaped.
+fn read_media_file(media_folder: &std::path::Path, filename: &str) -> Result<Vec<u8>> {
+    let path = filename_if_normalized(filename)
+        .map(|name| media_folder.join(name.as_ref()))
+        .filter(|path| path.is_file())
+        .or_not_found(filename)?;
+    Ok(anki_io::read_file(path)?)
+}
+
+fn content_type(filename: &str) -> &'static str {
+    let extension = filename
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" => "text/html; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "mp3" => "audio/mpeg",
+        "ogg" | "oga" => "audio/ogg",
+        "wav" => "audio/wav",
+        "m4a" => "audio/mp4",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
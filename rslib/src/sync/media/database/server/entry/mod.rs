@@ -1,6 +1,7 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use std::collections::HashMap;
 use std::mem;
 
 use rusqlite::params;
@@ -8,6 +9,7 @@ use rusqlite::OptionalExtension;
 use rusqlite::Row;
 
 use crate::error;
+use crate::prelude::Sha1Hash;
 use crate::prelude::TimestampSecs;
 use crate::prelude::Usn;
 use crate::sync::media::database::server::meta::StoreMetadata;
@@ -24,6 +26,22 @@ impl ServerMediaDatabase {
             .map(|e| e.filter(|e| !e.is_deleted()))
     }
 
+    /// The checksum of each file that has not been deleted.
+    pub fn nonempty_checksums(&self) -> error::Result<HashMap<String, Sha1Hash>> {
+        let mut checksums = HashMap::new();
+        let mut stmt = self
+            .db
+            .prepare("select fname, csum from media where size > 0")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let sha1: Vec<u8> = row.get(1)?;
+            if let Ok(sha1) = sha1.try_into() {
+                checksums.insert(row.get(0)?, sha1);
+            }
+        }
+        Ok(checksums)
+    }
+
     pub fn get_entry(&self, nfc_filename: &str) -> error::Result<Option<MediaEntry>> {
         self.db
             .prepare_cached(include_str!("get_entry.sql"))?
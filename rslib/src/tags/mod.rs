@@ -14,6 +14,7 @@ mod service;
 mod tree;
 pub(crate) mod undo;
 
+pub use notes::TagCounts;
 use unicase::UniCase;
 
 use crate::prelude::*;
@@ -1,13 +1,30 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::iter;
 
 use unicase::UniCase;
 
 use super::split_tags;
 use crate::prelude::*;
 use crate::search::SearchNode;
+use crate::search::SortMode;
+use crate::search::StateKind;
+
+/// Note and due card counts for a tag in the tag tree.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TagCounts {
+    /// The full name, eg `parent::child`.
+    pub name: String,
+    /// Notes that have this exact tag.
+    pub notes: usize,
+    /// Notes that have this tag or any of its descendants, each counted once.
+    pub total_notes: usize,
+    /// Cards due today whose notes have this tag or any of its descendants.
+    pub due_cards: usize,
+}
 
 impl Collection {
     pub(crate) fn all_tags_in_deck(&mut self, deck_id: DeckId) -> Result<HashSet<UniCase<String>>> {
@@ -26,4 +43,146 @@ impl Collection {
             })?;
         Ok(all_tags)
     }
+
+    /// The decks with cards of notes that have `tag` or one of its child tags,
+    /// with the number of such cards in each, in deck name order. Cards in
+    /// filtered decks are counted towards their home deck.
+    pub fn get_decks_containing_tag(&mut self, tag: &str) -> Result<Vec<(DeckId, String, usize)>> {
+        let guard =
+            self.search_cards_into_table(SearchNode::from_tag_name(tag), SortMode::NoOrder)?;
+        let counts = guard.col.storage.card_counts_of_search_cards()?;
+        drop(guard);
+        let mut decks = vec![];
+        for (did, count) in counts {
+            // cards in missing decks are left for the database check to fix
+            if let Some(deck) = self.get_deck(did)? {
+                decks.push((did, deck.human_name(), count));
+            }
+        }
+        decks.sort_unstable_by(|a, b| a.1.cmp(&b.1));
+        Ok(decks)
+    }
+
+    /// Counts for every tag in the tag tree, including parents that are only
+    /// implied by their children, sorted by name. Tags differing only in case
+    /// are counted together, under the registered spelling if there is one.
+    /// As in the browser sidebar, a note with `a::b` counts towards the
+    /// total of `a`.
+    pub fn get_tag_counts(&mut self) -> Result<Vec<TagCounts>> {
+        let mut counts: HashMap<UniCase<String>, TagCounts> = HashMap::new();
+        for tag in self.storage.all_tags()? {
+            tag_counts_entry(&mut counts, &tag.name);
+        }
+        self.storage.for_each_note_tag(|tags| {
+            let mut seen = HashSet::new();
+            for tag in split_tags(tags) {
+                tag_counts_entry(&mut counts, tag).notes += 1;
+                for name in tag_and_ancestors(tag) {
+                    if seen.insert(UniCase::new(name)) {
+                        tag_counts_entry(&mut counts, name).total_notes += 1;
+                    }
+                }
+            }
+        })?;
+        let guard =
+            self.search_cards_into_table(SearchNode::State(StateKind::Due), SortMode::NoOrder)?;
+        guard
+            .col
+            .storage
+            .for_each_note_tag_of_searched_cards(|tags| {
+                let mut seen = HashSet::new();
+                for tag in split_tags(tags) {
+                    for name in tag_and_ancestors(tag) {
+                        if seen.insert(UniCase::new(name)) {
+                            tag_counts_entry(&mut counts, name).due_cards += 1;
+                        }
+                    }
+                }
+            })?;
+        let mut counts: Vec<_> = counts.into_values().collect();
+        counts.sort_unstable_by(|a, b| UniCase::new(&a.name).cmp(&UniCase::new(&b.name)));
+        Ok(counts)
+    }
+}
+
+/// The counts for `name`, added along with its case if it is new.
+fn tag_counts_entry<'a>(
+    counts: &'a mut HashMap<UniCase<String>, TagCounts>,
+    name: &str,
+) -> &'a mut TagCounts {
+    counts
+        .entry(UniCase::new(name.to_string()))
+        .or_insert_with(|| TagCounts {
+            name: name.to_string(),
+            ..Default::default()
+        })
+}
+
+/// `a`, `a::b` and `a::b::c` for `a::b::c`.
+fn tag_and_ancestors(tag: &str) -> impl Iterator<Item = &str> {
+    tag.match_indices("::")
+        .map(|(idx, _)| &tag[..idx])
+        .chain(iter::once(tag))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decks_containing_tag() -> Result<()> {
+        let mut col = Collection::new();
+        let french = DeckAdder::new("French").add(&mut col);
+        let german = DeckAdder::new("German").add(&mut col);
+        for (deck, tags) in [
+            (french.id, "french"),
+            (french.id, "french::verbs"),
+            (german.id, "german frenchy"),
+        ] {
+            let mut note = NoteAdder::basic(&mut col).deck(deck).note();
+            note.tags = tags.split(' ').map(Into::into).collect();
+            col.add_note(&mut note, deck)?;
+        }
+
+        assert_eq!(
+            col.get_decks_containing_tag("French")?,
+            [(french.id, "French".to_string(), 2)]
+        );
+        assert_eq!(col.get_decks_containing_tag("none_such")?, []);
+        Ok(())
+    }
+
+    #[test]
+    fn tag_counts() -> Result<()> {
+        let mut col = Collection::new();
+        let mut nids = vec![];
+        for tags in ["a::b a::c", "A::b", "a", "d"] {
+            let mut note = NoteAdder::basic(&mut col).note();
+            note.tags = tags.split(' ').map(Into::into).collect();
+            col.add_note(&mut note, DeckId(1))?;
+            nids.push(note.id);
+        }
+        // make the first note's card due
+        let card = col.storage.all_cards_of_note(nids[0])?.remove(0);
+        col.storage.db.execute(
+            "update cards set type = 2, queue = 2, due = 0 where id = ?",
+            [card.id],
+        )?;
+
+        let counts: Vec<_> = col
+            .get_tag_counts()?
+            .into_iter()
+            .map(|c| (c.name, c.notes, c.total_notes, c.due_cards))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                ("a".to_string(), 1, 3, 1),
+                ("a::b".to_string(), 2, 2, 1),
+                ("a::c".to_string(), 1, 1, 1),
+                ("d".to_string(), 1, 1, 0),
+            ]
+        );
+        Ok(())
+    }
 }
@@ -321,8 +321,12 @@ pub fn replace_media_refs(
     text: &str,
     mut replacer: impl FnMut(&str) -> Option<String>,
 ) -> Option<String> {
-    let mut rep = |caps: &Captures| {
+    let mut rep = |caps: &Captures, is_av_tag: bool| {
         let whole_match = caps.get(0).unwrap().as_str();
+        if is_av_tag && caps.get(1).is_none() {
+            // a tts tag, which has no filename
+            return whole_match.to_owned();
+        }
         let old_name = caps.iter().skip(1).find_map(|g| g).unwrap().as_str();
         let old_name_decoded = decode_entities(old_name);
 
@@ -337,8 +341,8 @@ pub fn replace_media_refs(
     };
 
     HTML_MEDIA_TAGS
-        .replace_all(text, &mut rep)
-        .map_cow(|s| AV_TAGS.replace_all(s, &mut rep))
+        .replace_all(text, |caps: &Captures| rep(caps, false))
+        .map_cow(|s| AV_TAGS.replace_all(s, |caps: &Captures| rep(caps, true)))
         .get_owned()
 }
 
@@ -478,13 +482,22 @@ pub(crate) fn to_re(txt: &str) -> Cow<str> {
 
 /// Convert Anki style to RegEx using the provided wildcard.
 pub(crate) fn to_custom_re<'a>(txt: &'a str, wildcard: &str) -> Cow<'a, str> {
+    to_re_with_repetition(txt, wildcard, "*")
+}
+
+/// Like [to_custom_re()], but `*` matches as few characters as possible.
+pub(crate) fn to_custom_lazy_re<'a>(txt: &'a str, wildcard: &str) -> Cow<'a, str> {
+    to_re_with_repetition(txt, wildcard, "*?")
+}
+
+fn to_re_with_repetition<'a>(txt: &'a str, wildcard: &str, repetition: &str) -> Cow<'a, str> {
     static RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\\?.").unwrap());
     RE.replace_all(txt, |caps: &Captures| {
         let s = &caps[0];
         match s {
             r"\\" | r"\*" => s.to_string(),
             r"\_" => "_".to_string(),
-            "*" => format!("{wildcard}*"),
+            "*" => format!("{wildcard}{repetition}"),
             "_" => wildcard.to_string(),
             s => regex::escape(s),
         }
@@ -567,6 +580,25 @@ pub(crate) fn encode_iri_paths(unescaped_html: &str) -> Cow<str> {
     })
 }
 
+/// Rewrite local media references, including sound tags, into URLs starting
+/// with `prefix`, so rendered HTML does not depend on the media folder being
+/// served at the document root. Remote and data: sources are left as is.
+pub(crate) fn prefix_media_refs<'a>(text: &'a str, prefix: &str) -> Cow<'a, str> {
+    let prefix = prefix.trim_end_matches('/');
+    replace_media_refs(text, |fname| {
+        if REMOTE_FILENAME.is_match(fname) || fname.starts_with("data:") {
+            None
+        } else {
+            Some(format!(
+                "{prefix}/{}",
+                utf8_percent_encode(fname, FRAGMENT_QUERY_UNION)
+            ))
+        }
+    })
+    .map(Cow::Owned)
+    .unwrap_or(Cow::Borrowed(text))
+}
+
 /// URI-decode escaped local paths in HTML fragment.
 pub(crate) fn decode_iri_paths(escaped_html: &str) -> Cow<str> {
     transform_html_paths(escaped_html, |fname| {
@@ -630,6 +662,7 @@ mod test {
     fn conversion() {
         assert_eq!(&to_re(r"[te\*st]"), r"\[te\*st\]");
         assert_eq!(&to_custom_re("f_o*", r"\d"), r"f\do\d*");
+        assert_eq!(&to_custom_lazy_re(r"f*\*", r"\w"), r"f\w*?\*");
         assert_eq!(&to_sql("%f_o*"), r"\%f_o%");
         assert_eq!(&to_text(r"\*\_*_"), "*_*_");
         assert!(is_glob(r"\\\\_"));
@@ -712,4 +745,24 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn media_url_prefix() {
+        assert_eq!(
+            prefix_media_refs(
+                "<img src=\"dog 1.jpg\"><object data='clip.mp4'></object>[sound:a&amp;b.mp3]",
+                "https://example.com/media/"
+            ),
+            "<img src=\"https://example.com/media/dog%201.jpg\">\
+             <object data='https://example.com/media/clip.mp4'></object>\
+             [sound:https://example.com/media/a&amp;b.mp3]"
+        );
+        for unchanged in [
+            "<img src=\"data:image/png;base64,AAAA\">",
+            "<img src=\"HTTPS://example.com/dog.jpg\">",
+            "[anki:tts][en_US]text[/anki:tts]",
+        ] {
+            assert_eq!(prefix_media_refs(unchanged, "/media"), unchanged);
+        }
+    }
 }
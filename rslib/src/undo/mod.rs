@@ -4,12 +4,17 @@
 mod changes;
 
 use std::collections::VecDeque;
+use std::hash::Hash;
+
+use itertools::Itertools;
 
 pub(crate) use changes::UndoableChange;
 
+use crate::ops::ChangedIds;
 pub use crate::ops::Op;
 use crate::ops::OpChanges;
 use crate::ops::StateChanges;
+use crate::ops::CHANGED_IDS_LIMIT;
 use crate::prelude::*;
 
 const UNDO_LIMIT: usize = 30;
@@ -128,10 +133,10 @@ impl UndoManager {
             .as_ref()
             .expect("current_changes() called when no op set");
 
-        let changes = StateChanges::from(&current_op.changes[..]);
         OpChanges {
             op: current_op.kind.clone(),
-            changes,
+            changes: StateChanges::from(&current_op.changes[..]),
+            ids: ChangedIds::from(&current_op.changes[..]),
         }
     }
 
@@ -161,9 +166,27 @@ impl UndoManager {
         Ok(OpChanges {
             op: target.kind.clone(),
             changes: StateChanges::from(&target.changes[..]),
+            ids: ChangedIds::from(&target.changes[..]),
         })
     }
 
+    /// Merge the steps added after the one with counter `after` into the
+    /// oldest of them, renaming it if a name is provided. Returns the merged
+    /// step's counter, or None if no steps were added.
+    fn merge_steps_after(&mut self, after: usize, name: Option<String>) -> Option<usize> {
+        let target = self
+            .undo_steps
+            .iter()
+            .rev()
+            .find(|step| step.counter > after)?
+            .counter;
+        self.merge_undoable_ops(target).ok()?;
+        if let Some(name) = name {
+            self.undo_steps.front_mut().unwrap().kind = Op::Custom(name);
+        }
+        Some(target)
+    }
+
     /// Start a new step with a custom name, and return its associated
     /// counter value, which can be used with `merge_undoable_ops`.
     fn add_custom_step(&mut self, name: String) -> usize {
@@ -249,6 +272,17 @@ impl Collection {
         self.state.undo.current_op()
     }
 
+    /// Merge the undo steps added since `undo_status().last_step` was `after`
+    /// into one, optionally giving it a custom name. Returns the merged
+    /// step's counter, or None if there were no new steps.
+    pub(crate) fn merge_undo_steps_after(
+        &mut self,
+        after: usize,
+        name: Option<String>,
+    ) -> Option<usize> {
+        self.state.undo.merge_steps_after(after, name)
+    }
+
     pub(crate) fn previous_undo_op(&self) -> Option<&UndoableOp> {
         self.state.undo.previous_op()
     }
@@ -328,10 +362,42 @@ impl From<&[UndoableChange]> for StateChanges {
     }
 }
 
+impl From<&[UndoableChange]> for ChangedIds {
+    fn from(changes: &[UndoableChange]) -> Self {
+        let (cards, cards_truncated) = capped_unique(changes.iter().filter_map(|c| match c {
+            UndoableChange::Card(c) => Some(c.card_id()),
+            _ => None,
+        }));
+        let (notes, notes_truncated) = capped_unique(changes.iter().filter_map(|c| match c {
+            UndoableChange::Note(c) => Some(c.note_id()),
+            _ => None,
+        }));
+        let (decks, decks_truncated) = capped_unique(changes.iter().filter_map(|c| match c {
+            UndoableChange::Deck(c) => Some(c.deck_id()),
+            _ => None,
+        }));
+        ChangedIds {
+            cards,
+            notes,
+            decks,
+            truncated: cards_truncated || notes_truncated || decks_truncated,
+        }
+    }
+}
+
+/// The first [CHANGED_IDS_LIMIT] distinct ids, and whether there were more.
+fn capped_unique<T: Copy + Eq + Hash>(ids: impl Iterator<Item = T>) -> (Vec<T>, bool) {
+    let mut ids: Vec<T> = ids.unique().take(CHANGED_IDS_LIMIT + 1).collect();
+    let truncated = ids.len() > CHANGED_IDS_LIMIT;
+    ids.truncate(CHANGED_IDS_LIMIT);
+    (ids, truncated)
+}
+
 #[cfg(test)]
 mod test {
     use super::UndoableChange;
     use crate::card::Card;
+    use crate::ops::CHANGED_IDS_LIMIT;
     use crate::prelude::*;
 
     #[test]
@@ -506,6 +572,42 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn merge_steps_after() -> Result<()> {
+        let mut col = Collection::new();
+        let note = NoteAdder::basic(&mut col).add(&mut col);
+        let card = col.storage.all_cards_of_note(note.id)?.remove(0);
+        let after = col.undo_status().last_step;
+        assert_eq!(col.merge_undo_steps_after(after, None), None);
+
+        for due in [10, 20] {
+            col.transact(Op::UpdateCard, |col| {
+                col.get_and_update_card(card.id, |card| {
+                    card.due = due;
+                    Ok(())
+                })
+            })?;
+        }
+        let merged = col.merge_undo_steps_after(after, Some("group".into()));
+        assert_eq!(merged, Some(after + 1));
+        assert_eq!(col.can_undo(), Some(&Op::Custom("group".into())));
+
+        // later steps can be merged into the same one
+        col.transact(Op::UpdateCard, |col| {
+            col.get_and_update_card(card.id, |card| {
+                card.due = 30;
+                Ok(())
+            })
+        })?;
+        assert_eq!(col.merge_undo_steps_after(after, None), merged);
+        assert_eq!(col.state.undo.undo_steps.len(), 2);
+        col.undo()?;
+        assert_eq!(col.storage.get_card(card.id)?.unwrap().due, card.due);
+        assert_eq!(col.can_undo(), Some(&Op::AddNote));
+
+        Ok(())
+    }
+
     #[test]
     fn undo_mtime_bump() -> Result<()> {
         let mut col = Collection::new();
@@ -561,4 +663,29 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn changed_ids() -> Result<()> {
+        let mut col = Collection::new();
+        let nt = col.get_notetype_by_name("Basic")?.unwrap();
+        let mut note = nt.new_note();
+        let ids = col.add_note(&mut note, DeckId(1))?.changes.ids;
+        let cid = col.storage.all_cards_of_note(note.id)?[0].id;
+        assert_eq!(ids.cards, [cid]);
+        assert_eq!(ids.notes, [note.id]);
+        assert!(ids.decks.is_empty());
+
+        // removal and undo are reported too, each id once
+        let ids = col.remove_notes(&[note.id])?.changes.ids;
+        assert_eq!((ids.cards, ids.notes), (vec![cid], vec![note.id]));
+        let ids = col.undo()?.changes.ids;
+        assert_eq!((ids.cards, ids.notes), (vec![cid], vec![note.id]));
+
+        let (ids, truncated) = super::capped_unique([3, 1, 3, 2].into_iter());
+        assert_eq!((ids, truncated), (vec![3, 1, 2], false));
+        let (ids, truncated) = super::capped_unique(0..CHANGED_IDS_LIMIT + 1);
+        assert_eq!((ids.len(), truncated), (CHANGED_IDS_LIMIT, true));
+
+        Ok(())
+    }
 }
@@ -5,6 +5,7 @@ use std::process;
 
 use anki::log::set_global_logger;
 use anki::sync::http_server::SimpleServer;
+use anki::sync::http_server::SyncServerConfig;
 
 fn main() {
     if let Some(arg) = env::args().nth(1) {
@@ -12,6 +13,10 @@ fn main() {
             run_health_check();
             return;
         }
+        if arg == "--check-config" {
+            run_config_check();
+            return;
+        }
     }
     if env::var("RUST_LOG").is_err() {
         env::set_var("RUST_LOG", "anki=info")
@@ -27,3 +32,13 @@ fn run_health_check() {
         process::exit(1);
     }
 }
+
+fn run_config_check() {
+    match SyncServerConfig::load() {
+        Ok(config) => println!("{:#?}", config.public_config()),
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    }
+}